@@ -1,7 +1,8 @@
 use std::{env, fs, path::Path};
 
-const DEPENDENCY_VERSIONS: [(&str, &str); 4] = [
+const DEPENDENCY_VERSIONS: [(&str, &str); 5] = [
     ("mollusk-svm", "MOLLUSK_SVM_VERSION"),
+    ("serde_json", "SERDE_JSON_VERSION"),
     ("solana-account", "SOLANA_ACCOUNT_VERSION"),
     ("solana-address", "SOLANA_ADDRESS_VERSION"),
     ("solana-instruction", "SOLANA_INSTRUCTION_VERSION"),
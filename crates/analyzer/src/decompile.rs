@@ -0,0 +1,430 @@
+use {
+    either::Either,
+    sbpf_common::{
+        inst_param::{Number, Register},
+        instruction::Instruction,
+        opcode::Opcode,
+        syscalls::SYSCALLS,
+    },
+    sbpf_ir::{Block, Cfg},
+    std::fmt::Write,
+};
+
+/// Lift `cfg` into C-like pseudo-code: one `fn` per function, one statement per
+/// instruction, with jumps as `if (...) goto label;` and calls as
+/// `r0 = name(r1, r2, r3, r4, r5);`.
+///
+/// This is a reading aid, not a compiler: registers stay registers and control
+/// flow stays `goto`. Every statement maps back to exactly one instruction.
+pub fn decompile(cfg: &Cfg) -> String {
+    let mut output = String::new();
+
+    for (index, function) in cfg.functions().iter().enumerate() {
+        if index > 0 {
+            output.push('\n');
+        }
+        writeln!(output, "fn {}() {{", function.name()).expect("writing to a String cannot fail");
+        for (position, block) in function.blocks().iter().enumerate() {
+            write_block(&mut output, function.name(), position == 0, block);
+        }
+        output.push_str("}\n");
+    }
+
+    output
+}
+
+fn write_block(output: &mut String, function: &str, first: bool, block: &Block) {
+    for (label, _) in block.labels() {
+        if !(first && label == function) {
+            writeln!(output, "{label}:").expect("writing to a String cannot fail");
+        }
+    }
+    for node in block.instructions() {
+        let statement = match node.instruction() {
+            Some(instruction) => statement(instruction),
+            None => format!("/* {} */", node.opcode),
+        };
+        writeln!(output, "    {statement}").expect("writing to a String cannot fail");
+    }
+}
+
+/// The pseudo-code for one instruction.
+fn statement(instruction: &Instruction) -> String {
+    let dst = register(&instruction.dst);
+    let operand = operand(instruction);
+    let mnemonic = instruction.opcode.to_str();
+
+    match instruction.opcode {
+        Opcode::Lddw => match &instruction.imm {
+            Some(Either::Right(number)) => format!("{dst} = {:#x};", value(number)),
+            _ => format!("{dst} = {operand};"),
+        },
+        Opcode::Ldxb | Opcode::Ldxh | Opcode::Ldxw | Opcode::Ldxdw => format!(
+            "{dst} = {};",
+            memory(instruction.opcode, &instruction.src, &instruction.off)
+        ),
+        Opcode::Stb | Opcode::Sth | Opcode::Stw | Opcode::Stdw => format!(
+            "{} = {operand};",
+            memory(instruction.opcode, &instruction.dst, &instruction.off)
+        ),
+        Opcode::Stxb | Opcode::Stxh | Opcode::Stxw | Opcode::Stxdw => format!(
+            "{} = {};",
+            memory(instruction.opcode, &instruction.dst, &instruction.off),
+            register(&instruction.src)
+        ),
+        Opcode::Le | Opcode::Be => format!("{dst} = {mnemonic}{operand}({dst});"),
+        Opcode::Neg64 => format!("{dst} = -{dst};"),
+        Opcode::Neg32 => format!("{dst} = (u32)-{dst};"),
+        Opcode::Hor64Imm => format!("{dst} |= (u64){operand} << 32;"),
+        Opcode::Ja => format!("goto {};", target(instruction)),
+        Opcode::Call => format!("r0 = {}(r1, r2, r3, r4, r5);", callee(instruction)),
+        Opcode::Callx => format!("r0 = (*{dst})(r1, r2, r3, r4, r5);"),
+        Opcode::Exit => "return r0;".to_string(),
+        _ if instruction.is_jump() => {
+            format!(
+                "if ({}) goto {};",
+                condition(mnemonic, &dst, &operand, instruction.src.is_some()),
+                target(instruction)
+            )
+        }
+        _ => arithmetic(mnemonic, &dst, &operand, instruction.src.is_some()),
+    }
+}
+
+fn arithmetic(mnemonic: &str, dst: &str, operand: &str, register_operand: bool) -> String {
+    let (operation, wide) = match mnemonic.strip_suffix("64") {
+        Some(operation) => (operation, true),
+        None => (mnemonic.trim_end_matches("32"), false),
+    };
+    let (signed, unsigned) = if wide { ("i64", "u64") } else { ("i32", "u32") };
+    let signed_operand = if register_operand {
+        format!("({signed}){operand}")
+    } else {
+        operand.to_string()
+    };
+
+    let expression = match operation {
+        "mov" => operand.to_string(),
+        "arsh" => format!("({signed}){dst} >> {operand}"),
+        "sdiv" => format!("({signed}){dst} / {signed_operand}"),
+        "srem" => format!("({signed}){dst} % {signed_operand}"),
+        "uhmul" => format!("((u128){dst} * {operand}) >> 64"),
+        "shmul" => format!("((i128)({signed}){dst} * {signed_operand}) >> 64"),
+        _ => {
+            let operator = binary_operator(operation);
+            if wide {
+                return format!("{dst} {operator}= {operand};");
+            }
+            format!("{dst} {operator} {operand}")
+        }
+    };
+    if wide {
+        format!("{dst} = {expression};")
+    } else {
+        format!("{dst} = ({unsigned})({expression});")
+    }
+}
+
+fn binary_operator(operation: &str) -> &'static str {
+    match operation {
+        "add" => "+",
+        "sub" => "-",
+        "mul" | "lmul" => "*",
+        "div" | "udiv" => "/",
+        "mod" | "urem" => "%",
+        "or" => "|",
+        "and" => "&",
+        "xor" => "^",
+        "lsh" => "<<",
+        "rsh" => ">>",
+        _ => "?",
+    }
+}
+
+fn condition(mnemonic: &str, dst: &str, operand: &str, register_operand: bool) -> String {
+    let (operation, wide) = match mnemonic.strip_suffix("32") {
+        Some(operation) => (operation, false),
+        None => (mnemonic, true),
+    };
+    let (operator, signed) = match &operation[1..] {
+        "eq" => ("==", false),
+        "ne" => ("!=", false),
+        "gt" => (">", false),
+        "ge" => (">=", false),
+        "lt" => ("<", false),
+        "le" => ("<=", false),
+        "sgt" => (">", true),
+        "sge" => (">=", true),
+        "slt" => ("<", true),
+        "sle" => ("<=", true),
+        "set" => ("&", false),
+        _ => ("?", false),
+    };
+    let cast = match (signed, wide) {
+        (false, true) => "",
+        (true, true) => "(i64)",
+        (false, false) => "(u32)",
+        (true, false) => "(i32)",
+    };
+    let operand_cast = if register_operand { cast } else { "" };
+    format!("{cast}{dst} {operator} {operand_cast}{operand}")
+}
+
+/// A memory access like `*(u64 *)(r1 + 8)`.
+fn memory(opcode: Opcode, base: &Option<Register>, off: &Option<Either<String, i16>>) -> String {
+    let width = match opcode {
+        Opcode::Ldxb | Opcode::Stb | Opcode::Stxb => "u8",
+        Opcode::Ldxh | Opcode::Sth | Opcode::Stxh => "u16",
+        Opcode::Ldxw | Opcode::Stw | Opcode::Stxw => "u32",
+        _ => "u64",
+    };
+    let base = register(base);
+    match off {
+        Some(Either::Right(0)) | None => format!("*({width} *){base}"),
+        Some(Either::Right(off)) if *off < 0 => {
+            format!("*({width} *)({base} - {})", off.unsigned_abs())
+        }
+        Some(Either::Right(off)) => format!("*({width} *)({base} + {off})"),
+        Some(Either::Left(label)) => format!("*({width} *)({base} + {label})"),
+    }
+}
+
+fn callee(instruction: &Instruction) -> String {
+    match &instruction.imm {
+        Some(Either::Left(name)) => name.clone(),
+        Some(Either::Right(number)) => {
+            let hash = value(number) as u32;
+            match SYSCALLS.get(hash) {
+                Some(name) => name.to_string(),
+                None => format!("syscall_{hash:#010x}"),
+            }
+        }
+        None => "unknown".to_string(),
+    }
+}
+
+fn target(instruction: &Instruction) -> String {
+    match &instruction.off {
+        Some(Either::Left(label)) => label.clone(),
+        Some(Either::Right(off)) => format!("{off:+}"),
+        None => "?".to_string(),
+    }
+}
+
+fn operand(instruction: &Instruction) -> String {
+    if instruction.src.is_some() {
+        return register(&instruction.src);
+    }
+    match &instruction.imm {
+        Some(Either::Left(label)) => label.clone(),
+        Some(Either::Right(number)) => value(number).to_string(),
+        None => String::new(),
+    }
+}
+
+fn value(number: &Number) -> i64 {
+    match number {
+        Number::Int(value) | Number::Addr(value) => *value,
+    }
+}
+
+fn register(register: &Option<Register>) -> String {
+    register
+        .as_ref()
+        .map_or_else(|| "r?".to_string(), Register::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_ir::{InputNode, control_flow_graph},
+        std::collections::HashSet,
+    };
+
+    fn instruction(
+        opcode: Opcode,
+        dst: Option<u8>,
+        src: Option<u8>,
+        off: Option<Either<String, i16>>,
+        imm: Option<Either<String, Number>>,
+    ) -> Instruction {
+        Instruction {
+            opcode,
+            dst: dst.map(|n| Register { n }),
+            src: src.map(|n| Register { n }),
+            off,
+            imm,
+            span: 0..0,
+        }
+    }
+
+    fn imm(value: i64) -> Option<Either<String, Number>> {
+        Some(Either::Right(Number::Int(value)))
+    }
+
+    #[test]
+    fn test_statements() {
+        let cases = [
+            (
+                instruction(
+                    Opcode::Ldxdw,
+                    Some(2),
+                    Some(1),
+                    Some(Either::Right(8)),
+                    None,
+                ),
+                "r2 = *(u64 *)(r1 + 8);",
+            ),
+            (
+                instruction(
+                    Opcode::Stxb,
+                    Some(10),
+                    Some(3),
+                    Some(Either::Right(-4)),
+                    None,
+                ),
+                "*(u8 *)(r10 - 4) = r3;",
+            ),
+            (
+                instruction(Opcode::Stw, Some(1), None, Some(Either::Right(0)), imm(7)),
+                "*(u32 *)r1 = 7;",
+            ),
+            (
+                instruction(Opcode::Add64Imm, Some(1), None, None, imm(5)),
+                "r1 += 5;",
+            ),
+            (
+                instruction(Opcode::Mov64Reg, Some(0), Some(2), None, None),
+                "r0 = r2;",
+            ),
+            (
+                instruction(Opcode::Sub32Reg, Some(1), Some(2), None, None),
+                "r1 = (u32)(r1 - r2);",
+            ),
+            (
+                instruction(Opcode::Arsh64Imm, Some(3), None, None, imm(2)),
+                "r3 = (i64)r3 >> 2;",
+            ),
+            (
+                instruction(Opcode::Be, Some(4), None, None, imm(32)),
+                "r4 = be32(r4);",
+            ),
+            (
+                instruction(Opcode::Lddw, Some(1), None, None, imm(0x1_0000_0000)),
+                "r1 = 0x100000000;",
+            ),
+            (
+                instruction(
+                    Opcode::Lddw,
+                    Some(1),
+                    None,
+                    None,
+                    Some(Either::Left("message".to_string())),
+                ),
+                "r1 = message;",
+            ),
+            (
+                instruction(
+                    Opcode::JsgtReg,
+                    Some(1),
+                    Some(2),
+                    Some(Either::Left("done".to_string())),
+                    None,
+                ),
+                "if ((i64)r1 > (i64)r2) goto done;",
+            ),
+            (
+                instruction(
+                    Opcode::Jne32Imm,
+                    Some(1),
+                    None,
+                    Some(Either::Right(3)),
+                    imm(0),
+                ),
+                "if ((u32)r1 != 0) goto +3;",
+            ),
+            (
+                instruction(
+                    Opcode::Call,
+                    None,
+                    None,
+                    None,
+                    Some(Either::Left("sol_log_".to_string())),
+                ),
+                "r0 = sol_log_(r1, r2, r3, r4, r5);",
+            ),
+            (
+                instruction(Opcode::Exit, None, None, None, None),
+                "return r0;",
+            ),
+        ];
+        for (instruction, expected) in cases {
+            assert_eq!(statement(&instruction), expected);
+        }
+    }
+
+    #[test]
+    fn test_static_syscalls_are_named() {
+        let hash = syscall_map::murmur3_32("sol_log_");
+        let call = instruction(Opcode::Call, None, None, None, imm(hash as i64));
+        assert_eq!(statement(&call), "r0 = sol_log_(r1, r2, r3, r4, r5);");
+    }
+
+    #[test]
+    fn test_decompile_functions_and_labels() {
+        let load = instruction(
+            Opcode::Ldxdw,
+            Some(2),
+            Some(1),
+            Some(Either::Right(0)),
+            None,
+        );
+        let branch = instruction(
+            Opcode::JeqImm,
+            Some(2),
+            None,
+            Some(Either::Left("done".to_string())),
+            imm(0),
+        );
+        let call = instruction(
+            Opcode::Call,
+            None,
+            None,
+            None,
+            Some(Either::Left("helper".to_string())),
+        );
+        let exit = instruction(Opcode::Exit, None, None, None, None);
+        let mov = instruction(Opcode::Mov64Imm, Some(0), None, None, imm(1));
+        let nodes = [
+            InputNode::Label("entrypoint"),
+            InputNode::Instruction(&load),
+            InputNode::Instruction(&branch),
+            InputNode::Instruction(&call),
+            InputNode::Label("done"),
+            InputNode::Instruction(&exit),
+            InputNode::Label("helper"),
+            InputNode::Instruction(&mov),
+            InputNode::Instruction(&exit),
+        ];
+        let entries = HashSet::from(["entrypoint".to_string(), "helper".to_string()]);
+        let cfg = control_flow_graph(nodes, &entries, Some("entrypoint"));
+
+        assert_eq!(
+            decompile(&cfg),
+            "fn entrypoint() {
+    r2 = *(u64 *)r1;
+    if (r2 == 0) goto done;
+    r0 = helper(r1, r2, r3, r4, r5);
+done:
+    return r0;
+}
+
+fn helper() {
+    r0 = 1;
+    return r0;
+}
+"
+        );
+    }
+}
@@ -1,7 +1,13 @@
+pub mod decompile;
 pub mod dump_cfg;
+pub mod pattern;
 pub mod remove_dead_functions;
+pub mod security;
 
 pub use {
+    decompile::decompile,
     dump_cfg::{CfgDumpOverlay, dump_cfg, dump_cfg_with},
+    pattern::{Match, Operand, Pattern},
     remove_dead_functions::{RemovedFunction, remove_dead_functions},
+    security::{Finding, SecurityRule, security_lints},
 };
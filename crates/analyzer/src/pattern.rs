@@ -0,0 +1,746 @@
+//! A small pattern language for finding instructions, used by `sbpf grep`.
+//!
+//! A pattern is an instruction template, optionally followed by conditions on
+//! what it captured and by context clauses on the instructions before it:
+//!
+//! ```text
+//! stxdw [r10+$off], _ where $off > 4096
+//! div64|udiv64|mod64 _, $d:reg not after jeq|jne $d, 0 within 4
+//! ```
+//!
+//! - The mnemonic is a `|`-separated list of alternatives, each of which may
+//!   use `*` as a wildcard (`st*`, `*32`). Operands left off the end match
+//!   anything, so `jeq r1, 0` matches whatever the jump target is.
+//! - Operands are `_` (anything), `reg`, `imm`, a register (`r10`), a number,
+//!   a label or syscall name (wildcards allowed), `[base+offset]`, or a
+//!   capture `$name`, optionally restricted with `:reg` or `:imm`. A capture
+//!   used twice must match the same value both times.
+//! - `where $a > 4096 and $b != $a` compares captured numbers and registers.
+//! - `after <template>` requires a matching instruction among the preceding
+//!   ones, `not after <template>` requires there to be none. Both look back 8
+//!   instructions unless followed by `within <n>`.
+
+use {
+    either::Either,
+    sbpf_common::{
+        inst_handler::OPCODE_TO_TYPE,
+        inst_param::{Number, Register},
+        instruction::Instruction,
+        opcode::{Opcode, OperationType},
+    },
+    std::{collections::HashMap, fmt, str::FromStr},
+};
+
+/// How far `after` and `not after` look back by default.
+const DEFAULT_WINDOW: usize = 8;
+
+/// An operand of a decoded instruction, in the order assembly prints them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Immediate(i64),
+    Label(String),
+    Memory { base: u8, offset: i64 },
+}
+
+impl Operand {
+    fn number(&self) -> Option<i64> {
+        match self {
+            Operand::Register(n) => Some(*n as i64),
+            Operand::Immediate(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(n) => write!(f, "r{n}"),
+            Operand::Immediate(value) => write!(f, "{value}"),
+            Operand::Label(label) => write!(f, "{label}"),
+            Operand::Memory { base, offset } => write!(f, "[r{base}{offset:+}]"),
+        }
+    }
+}
+
+/// An instruction the pattern matched, with what it captured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Index into the searched instructions.
+    pub index: usize,
+    pub captures: HashMap<String, Operand>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    template: Template,
+    conditions: Vec<Condition>,
+    context: Vec<Context>,
+}
+
+impl Pattern {
+    /// Every instruction in `instructions` the pattern matches.
+    pub fn find(&self, instructions: &[Instruction]) -> Vec<Match> {
+        (0..instructions.len())
+            .filter_map(|index| self.match_at(instructions, index))
+            .collect()
+    }
+
+    /// Match the pattern against `instructions[index]`.
+    pub fn match_at(&self, instructions: &[Instruction], index: usize) -> Option<Match> {
+        let mut captures = HashMap::new();
+        if !self.template.matches(&instructions[index], &mut captures)
+            || !self
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(&captures))
+        {
+            return None;
+        }
+        for context in &self.context {
+            let found = instructions[index.saturating_sub(context.window)..index]
+                .iter()
+                .any(|instruction| {
+                    let mut scratch = captures.clone();
+                    context.template.matches(instruction, &mut scratch)
+                });
+            if found == context.negated {
+                return None;
+            }
+        }
+        Some(Match { index, captures })
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(pattern)?,
+            position: 0,
+        };
+        let template = parser.template()?;
+        let mut conditions = Vec::new();
+        let mut context = Vec::new();
+        if parser.eat_word("where") {
+            conditions.push(parser.condition()?);
+            while parser.eat_word("and") {
+                conditions.push(parser.condition()?);
+            }
+        }
+        while parser.peek().is_some() {
+            let negated = parser.eat_word("not");
+            if !parser.eat_word("after") {
+                return Err(parser.unexpected("`after`"));
+            }
+            let template = parser.template()?;
+            let window = if parser.eat_word("within") {
+                parser.count()?
+            } else {
+                DEFAULT_WINDOW
+            };
+            context.push(Context {
+                negated,
+                template,
+                window,
+            });
+        }
+        Ok(Self {
+            template,
+            conditions,
+            context,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Template {
+    mnemonics: Vec<String>,
+    operands: Vec<OperandPattern>,
+}
+
+impl Template {
+    fn matches(&self, instruction: &Instruction, captures: &mut HashMap<String, Operand>) -> bool {
+        let mnemonic = mnemonic(instruction);
+        if !self
+            .mnemonics
+            .iter()
+            .any(|pattern| glob_match(pattern, &mnemonic))
+        {
+            return false;
+        }
+        if self.operands.is_empty() {
+            return true;
+        }
+        let Some(operands) = operands(instruction) else {
+            return false;
+        };
+        operands.len() >= self.operands.len()
+            && self
+                .operands
+                .iter()
+                .zip(&operands)
+                .all(|(pattern, operand)| pattern.matches(operand, captures))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum OperandPattern {
+    Any,
+    AnyRegister,
+    AnyImmediate,
+    Register(u8),
+    Immediate(i64),
+    Label(String),
+    Memory(Box<OperandPattern>, Box<OperandPattern>),
+    Capture(String, Option<Kind>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Register,
+    Immediate,
+}
+
+impl OperandPattern {
+    fn matches(&self, operand: &Operand, captures: &mut HashMap<String, Operand>) -> bool {
+        match (self, operand) {
+            (OperandPattern::Any, _) => true,
+            (OperandPattern::AnyRegister, Operand::Register(_)) => true,
+            (OperandPattern::AnyImmediate, Operand::Immediate(_)) => true,
+            (OperandPattern::Register(expected), Operand::Register(n)) => expected == n,
+            (OperandPattern::Immediate(expected), Operand::Immediate(value)) => expected == value,
+            (OperandPattern::Label(pattern), Operand::Label(label)) => glob_match(pattern, label),
+            (
+                OperandPattern::Memory(base, offset),
+                Operand::Memory {
+                    base: n,
+                    offset: off,
+                },
+            ) => {
+                base.matches(&Operand::Register(*n), captures)
+                    && offset.matches(&Operand::Immediate(*off), captures)
+            }
+            (OperandPattern::Capture(name, kind), operand) => {
+                let kind_matches = match kind {
+                    Some(Kind::Register) => matches!(operand, Operand::Register(_)),
+                    Some(Kind::Immediate) => matches!(operand, Operand::Immediate(_)),
+                    None => true,
+                };
+                kind_matches
+                    && match captures.get(name) {
+                        Some(captured) => captured == operand,
+                        None => {
+                            captures.insert(name.clone(), operand.clone());
+                            true
+                        }
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    left: Value,
+    comparison: Comparison,
+    right: Value,
+}
+
+impl Condition {
+    fn holds(&self, captures: &HashMap<String, Operand>) -> bool {
+        let (Some(left), Some(right)) = (self.left.resolve(captures), self.right.resolve(captures))
+        else {
+            return false;
+        };
+        match self.comparison {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Capture(String),
+}
+
+impl Value {
+    fn resolve(&self, captures: &HashMap<String, Operand>) -> Option<i64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            Value::Capture(name) => captures.get(name).and_then(Operand::number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone)]
+struct Context {
+    negated: bool,
+    template: Template,
+    window: usize,
+}
+
+/// The mnemonic as assembly prints it, with the width of `le`/`be`.
+fn mnemonic(instruction: &Instruction) -> String {
+    match instruction.opcode {
+        Opcode::Le | Opcode::Be => instruction
+            .op_imm_bits()
+            .unwrap_or_else(|_| instruction.opcode.to_string()),
+        opcode => opcode.to_string(),
+    }
+}
+
+/// The operands of `instruction` in assembly order, or `None` if any of them
+/// is an unresolved symbolic offset.
+fn operands(instruction: &Instruction) -> Option<Vec<Operand>> {
+    let register = |r: &Option<Register>| r.as_ref().map(|r| Operand::Register(r.n));
+    let immediate = || match instruction.imm.as_ref()? {
+        Either::Left(label) => Some(Operand::Label(label.clone())),
+        Either::Right(Number::Int(value) | Number::Addr(value)) => Some(Operand::Immediate(*value)),
+    };
+    let target = || match instruction.off.as_ref()? {
+        Either::Left(label) => Some(Operand::Label(label.clone())),
+        Either::Right(off) => Some(Operand::Immediate(*off as i64)),
+    };
+    let memory = |base: &Option<Register>| {
+        let base = base.as_ref()?.n;
+        match instruction.off.as_ref()? {
+            Either::Right(off) => Some(Operand::Memory {
+                base,
+                offset: *off as i64,
+            }),
+            Either::Left(_) => None,
+        }
+    };
+
+    let operands = match OPCODE_TO_TYPE.get(&instruction.opcode)? {
+        OperationType::LoadImmediate | OperationType::BinaryImmediate => {
+            vec![register(&instruction.dst)?, immediate()?]
+        }
+        OperationType::LoadMemory => vec![register(&instruction.dst)?, memory(&instruction.src)?],
+        OperationType::StoreImmediate => vec![memory(&instruction.dst)?, immediate()?],
+        OperationType::StoreRegister => {
+            vec![memory(&instruction.dst)?, register(&instruction.src)?]
+        }
+        OperationType::BinaryRegister => {
+            vec![register(&instruction.dst)?, register(&instruction.src)?]
+        }
+        OperationType::Unary | OperationType::Endian | OperationType::CallRegister => {
+            vec![register(&instruction.dst)?]
+        }
+        OperationType::Jump => vec![target()?],
+        OperationType::JumpImmediate | OperationType::Jump32Immediate => {
+            vec![register(&instruction.dst)?, immediate()?, target()?]
+        }
+        OperationType::JumpRegister | OperationType::Jump32Register => vec![
+            register(&instruction.dst)?,
+            register(&instruction.src)?,
+            target()?,
+        ],
+        OperationType::CallImmediate => vec![immediate()?],
+        OperationType::Exit => vec![],
+    };
+    Some(operands)
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut text) = text.strip_prefix(prefix) else {
+        return false;
+    };
+    let mut parts = rest.split('*').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return text.len() >= part.len() && text.ends_with(part);
+        }
+        match text.find(part) {
+            Some(at) => text = &text[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, String> {
+    const SYMBOLS: [&str; 11] = ["<=", ">=", "==", "!=", "<", ">", ",", "[", "]", "+", "-"];
+
+    let mut tokens = Vec::new();
+    let mut rest = pattern.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_$*|:.".contains(c)))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!(
+                    "unexpected character '{}'",
+                    rest.chars().next().unwrap_or_default()
+                ));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+const KEYWORDS: [&str; 5] = ["where", "and", "not", "after", "within"];
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn at_keyword(&self) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if KEYWORDS.contains(&w.to_ascii_lowercase().as_str()))
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(Token::Word(word)) => format!("expected {expected}, found '{word}'"),
+            Some(Token::Symbol(symbol)) => format!("expected {expected}, found '{symbol}'"),
+            None => format!("expected {expected} at the end of the pattern"),
+        }
+    }
+
+    fn template(&mut self) -> Result<Template, String> {
+        if self.at_keyword() {
+            return Err(self.unexpected("a mnemonic"));
+        }
+        let Some(Token::Word(mnemonic)) = self.next() else {
+            self.position -= 1;
+            return Err(self.unexpected("a mnemonic"));
+        };
+        let mnemonics = mnemonic
+            .to_ascii_lowercase()
+            .split('|')
+            .map(str::to_string)
+            .collect();
+
+        let mut operands = Vec::new();
+        if self.peek().is_some() && !self.at_keyword() {
+            operands.push(self.operand()?);
+            while self.eat_symbol(",") {
+                operands.push(self.operand()?);
+            }
+        }
+        Ok(Template {
+            mnemonics,
+            operands,
+        })
+    }
+
+    fn operand(&mut self) -> Result<OperandPattern, String> {
+        if self.eat_symbol("[") {
+            let base = self.operand()?;
+            let offset = if self.eat_symbol("+") {
+                self.operand()?
+            } else if self.eat_symbol("-") {
+                OperandPattern::Immediate(-self.number()?)
+            } else {
+                OperandPattern::Immediate(0)
+            };
+            if !self.eat_symbol("]") {
+                return Err(self.unexpected("']'"));
+            }
+            return Ok(OperandPattern::Memory(Box::new(base), Box::new(offset)));
+        }
+        if self.eat_symbol("-") {
+            return Ok(OperandPattern::Immediate(-self.number()?));
+        }
+        if self.at_keyword() {
+            return Err(self.unexpected("an operand"));
+        }
+        let Some(Token::Word(word)) = self.next() else {
+            self.position -= 1;
+            return Err(self.unexpected("an operand"));
+        };
+        let lower = word.to_ascii_lowercase();
+        Ok(match lower.as_str() {
+            "_" => OperandPattern::Any,
+            "reg" => OperandPattern::AnyRegister,
+            "imm" => OperandPattern::AnyImmediate,
+            _ if word.starts_with('$') => {
+                let (name, kind) = match word.split_once(':') {
+                    Some((name, "reg")) => (name, Some(Kind::Register)),
+                    Some((name, "imm")) => (name, Some(Kind::Immediate)),
+                    Some((_, kind)) => return Err(format!("unknown operand kind '{kind}'")),
+                    None => (word.as_str(), None),
+                };
+                OperandPattern::Capture(name[1..].to_string(), kind)
+            }
+            _ => match (
+                lower.strip_prefix('r').and_then(|n| n.parse().ok()),
+                parse_number(&lower),
+            ) {
+                (Some(n), _) => OperandPattern::Register(n),
+                (None, Some(value)) => OperandPattern::Immediate(value),
+                (None, None) => OperandPattern::Label(word),
+            },
+        })
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let left = self.value()?;
+        let comparison = match self.next() {
+            Some(Token::Symbol("<")) => Comparison::Less,
+            Some(Token::Symbol("<=")) => Comparison::LessOrEqual,
+            Some(Token::Symbol(">")) => Comparison::Greater,
+            Some(Token::Symbol(">=")) => Comparison::GreaterOrEqual,
+            Some(Token::Symbol("==")) => Comparison::Equal,
+            Some(Token::Symbol("!=")) => Comparison::NotEqual,
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected("a comparison"));
+            }
+        };
+        let right = self.value()?;
+        Ok(Condition {
+            left,
+            comparison,
+            right,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.eat_symbol("-") {
+            return Ok(Value::Number(-self.number()?));
+        }
+        match self.peek() {
+            Some(Token::Word(word)) if word.starts_with('$') => {
+                let name = word[1..].to_string();
+                self.position += 1;
+                Ok(Value::Capture(name))
+            }
+            _ => Ok(Value::Number(self.number()?)),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, String> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let value = parse_number(&word.to_ascii_lowercase())
+                    .ok_or_else(|| self.unexpected("a number"))?;
+                self.position += 1;
+                Ok(value)
+            }
+            _ => Err(self.unexpected("a number")),
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        let value = self.number()?;
+        usize::try_from(value).map_err(|_| format!("'within' needs a positive count, got {value}"))
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(
+        opcode: Opcode,
+        dst: Option<u8>,
+        src: Option<u8>,
+        off: Option<i16>,
+        imm: Option<i64>,
+    ) -> Instruction {
+        Instruction {
+            opcode,
+            dst: dst.map(|n| Register { n }),
+            src: src.map(|n| Register { n }),
+            off: off.map(Either::Right),
+            imm: imm.map(|value| Either::Right(Number::Int(value))),
+            span: 0..0,
+        }
+    }
+
+    fn indices(pattern: &str, instructions: &[Instruction]) -> Vec<usize> {
+        pattern
+            .parse::<Pattern>()
+            .unwrap()
+            .find(instructions)
+            .iter()
+            .map(|found| found.index)
+            .collect()
+    }
+
+    #[test]
+    fn test_mnemonics_and_operands() {
+        let program = [
+            instruction(Opcode::Mov64Imm, Some(1), None, None, Some(5)),
+            instruction(Opcode::Mov64Reg, Some(2), Some(1), None, None),
+            instruction(Opcode::Add32Imm, Some(1), None, None, Some(-1)),
+            instruction(Opcode::Exit, None, None, None, None),
+        ];
+        assert_eq!(indices("mov64", &program), vec![0, 1]);
+        assert_eq!(indices("mov64 _, imm", &program), vec![0]);
+        assert_eq!(indices("mov64 r2, reg", &program), vec![1]);
+        assert_eq!(indices("*32 r1, -1", &program), vec![2]);
+        assert_eq!(indices("add*|exit", &program), vec![2, 3]);
+        assert_eq!(indices("MOV64 R1, 0x5", &program), vec![0]);
+    }
+
+    #[test]
+    fn test_memory_operands_and_conditions() {
+        let program = [
+            instruction(Opcode::Stxdw, Some(10), Some(1), Some(-8), None),
+            instruction(Opcode::Stxdw, Some(10), Some(1), Some(4104), None),
+            instruction(Opcode::Stxdw, Some(2), Some(1), Some(5000), None),
+            instruction(Opcode::Ldxdw, Some(3), Some(10), Some(-8), None),
+        ];
+        assert_eq!(
+            indices("stxdw [r10+$off], _ where $off > 4096", &program),
+            vec![1]
+        );
+        assert_eq!(indices("stxdw [r10-8], _", &program), vec![0]);
+        assert_eq!(
+            indices("st* [$base+_], _ where $base != 10", &program),
+            vec![2]
+        );
+        assert_eq!(indices("ldx* _, [r10]", &program), Vec::<usize>::new());
+        assert_eq!(indices("ldx* $d, [$d-8]", &program), Vec::<usize>::new());
+        assert_eq!(indices("ldx* $d, [$b-8] where $d < $b", &program), vec![3]);
+    }
+
+    #[test]
+    fn test_context_clauses() {
+        // div64 r1, r2 is guarded, div64 r1, r3 is not.
+        let program = [
+            instruction(Opcode::JeqImm, Some(2), None, Some(4), Some(0)),
+            instruction(Opcode::Div64Reg, Some(1), Some(2), None, None),
+            instruction(Opcode::Mov64Imm, Some(0), None, None, Some(0)),
+            instruction(Opcode::Div64Reg, Some(1), Some(3), None, None),
+            instruction(Opcode::Exit, None, None, None, None),
+        ];
+        let unguarded = "div64|udiv64|mod64|urem64 _, $d:reg not after jeq|jne $d, 0";
+        assert_eq!(indices(unguarded, &program), vec![3]);
+        assert_eq!(indices("div64 not after jeq within 1", &program), vec![3]);
+        assert_eq!(
+            indices("div64 not after jeq within 3", &program),
+            Vec::<usize>::new()
+        );
+        assert_eq!(indices("exit after div64 _, r3", &program), vec![4]);
+    }
+
+    #[test]
+    fn test_captures_and_labels() {
+        let bytes = [
+            0x85, 0x00, 0x00, 0x00, 0x78, 0x31, 0x2a, 0x5c, // call 0x5c2a3178
+            0xdc, 0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, // be32 r1
+        ];
+        let mut program: Vec<Instruction> = bytes
+            .chunks(8)
+            .map(|bytes| Instruction::from_bytes(bytes).unwrap())
+            .collect();
+        program[0].imm = Some(Either::Left("sol_log_".to_string()));
+
+        let pattern: Pattern = "call $target".parse().unwrap();
+        assert_eq!(
+            pattern.find(&program)[0].captures["target"],
+            Operand::Label("sol_log_".to_string())
+        );
+        assert_eq!(indices("call sol_log*", &program), vec![0]);
+        assert_eq!(indices("be32 r1", &program), vec![1]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |pattern: &str| pattern.parse::<Pattern>().unwrap_err();
+        assert_eq!(error(""), "expected a mnemonic at the end of the pattern");
+        assert_eq!(
+            error("mov64 r1 where"),
+            "expected a number at the end of the pattern"
+        );
+        assert_eq!(
+            error("mov64 r1,"),
+            "expected an operand at the end of the pattern"
+        );
+        assert_eq!(
+            error("ldxdw r1, [r2+8"),
+            "expected ']' at the end of the pattern"
+        );
+        assert_eq!(error("mov64 $x:mem"), "unknown operand kind 'mem'");
+        assert_eq!(
+            error("mov64 r1, 1 before exit"),
+            "expected `after`, found 'before'"
+        );
+        assert_eq!(error("mov64 r1 # x"), "unexpected character '#'");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("st*", "stxdw"));
+        assert!(glob_match("*32", "add32"));
+        assert!(glob_match("j*32", "jsgt32"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("j*32", "jsgt"));
+        assert!(!glob_match("a*a", "a"));
+    }
+}
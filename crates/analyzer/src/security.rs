@@ -0,0 +1,652 @@
+use {
+    either::Either,
+    sbpf_common::{
+        inst_param::Number, instruction::Instruction, opcode::Opcode, syscalls::SYSCALLS,
+    },
+    sbpf_ir::{BlockId, Cfg, CfgFunction},
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+    },
+};
+
+/// Offset of the first account's data in the program input: the account
+/// count, then an 88-byte header (flags, key, owner, lamports, data length).
+const FIRST_ACCOUNT_DATA: i64 = 8 + 88;
+
+/// Offset of `is_signer` within a serialized account. Accounts start 8-byte
+/// aligned, so a byte load at `1 mod 8` is taken to be a signer flag.
+const IS_SIGNER: i64 = 1;
+
+/// A rule in the security pack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecurityRule {
+    /// Lamports are debited on a path that never branches on `is_signer`.
+    MissingSignerCheck,
+    /// Lamports are increased without an overflow check, or decreased
+    /// without comparing the balance first.
+    UncheckedLamportsArithmetic,
+    /// Account data is read before any 32-byte key or owner is compared.
+    UnvalidatedAccountOwner,
+}
+
+impl SecurityRule {
+    pub const ALL: [SecurityRule; 3] = [
+        SecurityRule::MissingSignerCheck,
+        SecurityRule::UncheckedLamportsArithmetic,
+        SecurityRule::UnvalidatedAccountOwner,
+    ];
+
+    /// The name used in output and in `sbpf-allow(...)` comments.
+    pub fn name(self) -> &'static str {
+        match self {
+            SecurityRule::MissingSignerCheck => "missing-signer-check",
+            SecurityRule::UncheckedLamportsArithmetic => "unchecked-lamports-arithmetic",
+            SecurityRule::UnvalidatedAccountOwner => "unvalidated-account-owner",
+        }
+    }
+}
+
+impl fmt::Display for SecurityRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One rule violation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: SecurityRule,
+    /// Input node of the offending instruction, as passed to
+    /// [`sbpf_ir::control_flow_graph`].
+    pub node_id: usize,
+    pub message: String,
+}
+
+/// Run the security rules over the entrypoint function of `cfg`.
+///
+/// The rules follow pointers into the program input from `r1` at entry, so
+/// they only see accesses at offsets known statically. Lamport moves are
+/// recognised as a `ldxdw`, `add64`/`sub64`, `stxdw` round trip through the
+/// same input address. Checks count when they happen earlier in the same
+/// block or in a block that dominates the access. These are heuristics: silence
+/// false positives with an `sbpf-allow(rule-name)` comment.
+pub fn security_lints(cfg: &Cfg) -> Vec<Finding> {
+    let Some(function) = cfg
+        .functions()
+        .iter()
+        .find(|function| function.name() == "entrypoint")
+        .or_else(|| cfg.functions().first())
+    else {
+        return Vec::new();
+    };
+    let Some(entry) = function.entry_block_id() else {
+        return Vec::new();
+    };
+
+    let in_states = input_states(cfg, function, entry);
+    let dominators = dominators(cfg, function, entry, &in_states);
+
+    let mut events = Events::default();
+    for (&block_id, state) in &in_states {
+        step_block(cfg, block_id, *state, &mut events);
+    }
+
+    // Whether `check` happens before `access` on every path to it.
+    let precedes = |check: &At, access: &At| {
+        if check.block == access.block {
+            check.index < access.index
+        } else {
+            dominators
+                .get(&access.block)
+                .is_some_and(|dominators| dominators.contains(&check.block))
+        }
+    };
+
+    let mut findings = Vec::new();
+    for update in &events.balance_updates {
+        if !update.checked {
+            let what = match update.op {
+                Adjust::Add => "increased without an overflow check",
+                Adjust::Sub => "decreased without first comparing the balance",
+            };
+            findings.push(Finding {
+                rule: SecurityRule::UncheckedLamportsArithmetic,
+                node_id: update.arithmetic.node_id,
+                message: format!("lamports at input+{:#x} are {}", update.offset, what),
+            });
+        }
+        if update.op == Adjust::Sub
+            && !events
+                .signer_checks
+                .iter()
+                .any(|check| precedes(check, &update.store))
+        {
+            findings.push(Finding {
+                rule: SecurityRule::MissingSignerCheck,
+                node_id: update.store.node_id,
+                message: format!(
+                    "lamports at input+{:#x} are debited without branching on is_signer first",
+                    update.offset
+                ),
+            });
+        }
+    }
+
+    let balance_loads: HashSet<At> = events
+        .balance_updates
+        .iter()
+        .map(|update| update.load)
+        .collect();
+    for load in &events.loads {
+        if load.offset < FIRST_ACCOUNT_DATA
+            || balance_loads.contains(&load.at)
+            || events.key_loads.contains(&load.at)
+        {
+            continue;
+        }
+        let key_validated = events
+            .key_checks
+            .iter()
+            .any(|check| precedes(check, &load.at))
+            || {
+                let compared: HashSet<i64> = events
+                    .key_compares
+                    .iter()
+                    .filter(|(check, _)| precedes(check, &load.at))
+                    .map(|(_, offset)| *offset)
+                    .collect();
+                compared
+                    .iter()
+                    .any(|offset| (1..4).all(|word| compared.contains(&(offset + word * 8))))
+            };
+        if !key_validated {
+            findings.push(Finding {
+                rule: SecurityRule::UnvalidatedAccountOwner,
+                node_id: load.at.node_id,
+                message: format!(
+                    "account data at input+{:#x} is read before any account key or owner is \
+                     compared",
+                    load.offset
+                ),
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| (finding.node_id, finding.rule.name()));
+    findings.dedup();
+    findings
+}
+
+/// Where an instruction is: its block and position, and its input node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct At {
+    block: BlockId,
+    index: usize,
+    node_id: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Adjust {
+    Add,
+    Sub,
+}
+
+/// What is known about a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Unknown,
+    /// A pointer into the program input.
+    Input(i64),
+    /// A value loaded from the input.
+    Loaded {
+        offset: i64,
+        size: u8,
+        load: At,
+        /// Whether a conditional jump has looked at it.
+        compared: bool,
+    },
+    /// A loaded u64 after `add64` or `sub64`.
+    Adjusted {
+        offset: i64,
+        load: At,
+        arithmetic: At,
+        op: Adjust,
+        checked: bool,
+    },
+}
+
+type State = [Value; 11];
+
+#[derive(Debug)]
+struct BalanceUpdate {
+    offset: i64,
+    op: Adjust,
+    checked: bool,
+    load: At,
+    arithmetic: At,
+    store: At,
+}
+
+#[derive(Debug)]
+struct Load {
+    at: At,
+    offset: i64,
+}
+
+/// Facts collected while stepping through the entrypoint.
+#[derive(Debug, Default)]
+struct Events {
+    signer_checks: Vec<At>,
+    /// 8-byte input words compared by a conditional jump, by input offset.
+    key_compares: Vec<(At, i64)>,
+    /// Loads feeding those comparisons.
+    key_loads: HashSet<At>,
+    /// Whole-key comparisons made with `sol_memcmp_`.
+    key_checks: Vec<At>,
+    balance_updates: Vec<BalanceUpdate>,
+    loads: Vec<Load>,
+}
+
+/// The register state at the start of each reachable block of `function`.
+fn input_states(cfg: &Cfg, function: &CfgFunction, entry: BlockId) -> HashMap<BlockId, State> {
+    let mut entry_state = [Value::Unknown; 11];
+    entry_state[1] = Value::Input(0);
+
+    let mut states = HashMap::from([(entry, entry_state)]);
+    let mut worklist = vec![entry];
+    while let Some(block_id) = worklist.pop() {
+        let state = step_block(cfg, block_id, states[&block_id], &mut Events::default());
+        for &successor in cfg.successors(block_id) {
+            if !function.block_ids().contains(&successor) {
+                continue;
+            }
+            let merged = match states.get(&successor) {
+                Some(existing) => {
+                    let mut merged = *existing;
+                    for (value, incoming) in merged.iter_mut().zip(state) {
+                        if *value != incoming {
+                            *value = Value::Unknown;
+                        }
+                    }
+                    merged
+                }
+                None => state,
+            };
+            if states.get(&successor) != Some(&merged) {
+                states.insert(successor, merged);
+                worklist.push(successor);
+            }
+        }
+    }
+    states
+}
+
+/// The blocks dominating each reachable block, itself excluded.
+fn dominators(
+    cfg: &Cfg,
+    function: &CfgFunction,
+    entry: BlockId,
+    reachable: &HashMap<BlockId, State>,
+) -> HashMap<BlockId, HashSet<BlockId>> {
+    let all: HashSet<BlockId> = reachable.keys().copied().collect();
+    let mut dominators: HashMap<BlockId, HashSet<BlockId>> = all
+        .iter()
+        .map(|&block| {
+            let initial = if block == entry {
+                HashSet::from([entry])
+            } else {
+                all.clone()
+            };
+            (block, initial)
+        })
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in function.block_ids() {
+            if block == entry || !all.contains(&block) {
+                continue;
+            }
+            let mut incoming = cfg
+                .predecessors(block)
+                .iter()
+                .filter(|predecessor| all.contains(predecessor))
+                .map(|predecessor| &dominators[predecessor]);
+            let Some(first) = incoming.next() else {
+                continue;
+            };
+            let mut updated = first.clone();
+            for other in incoming {
+                updated.retain(|block| other.contains(block));
+            }
+            updated.insert(block);
+            if updated != dominators[&block] {
+                dominators.insert(block, updated);
+                changed = true;
+            }
+        }
+    }
+
+    for (block, dominators) in &mut dominators {
+        dominators.remove(block);
+    }
+    dominators
+}
+
+/// Step through the instructions of `block_id` from `state`, returning the
+/// state at its end.
+fn step_block(cfg: &Cfg, block_id: BlockId, mut state: State, events: &mut Events) -> State {
+    let nodes = cfg
+        .block(block_id)
+        .map(|block| block.instructions())
+        .unwrap_or_default();
+    for (index, node) in nodes.iter().enumerate() {
+        if let Some(instruction) = node.instruction() {
+            let at = At {
+                block: block_id,
+                index,
+                node_id: node.source_node_id().unwrap_or_default(),
+            };
+            step(&mut state, at, instruction, events);
+        }
+    }
+    state
+}
+
+/// Apply `instruction` to `state`, recording what it does in `events`.
+fn step(state: &mut State, at: At, instruction: &Instruction, events: &mut Events) {
+    let dst = instruction.dst.as_ref().map(|register| register.n as usize);
+    let src = instruction.src.as_ref().map(|register| register.n as usize);
+    let off = match &instruction.off {
+        Some(Either::Right(off)) => *off as i64,
+        _ => 0,
+    };
+    let imm = match &instruction.imm {
+        Some(Either::Right(number)) => Some(number.to_i64()),
+        _ => None,
+    };
+    let value = |register: Option<usize>| {
+        register
+            .and_then(|register| state.get(register))
+            .copied()
+            .unwrap_or(Value::Unknown)
+    };
+
+    match instruction.opcode {
+        Opcode::Ldxb | Opcode::Ldxh | Opcode::Ldxw | Opcode::Ldxdw => {
+            let loaded = match value(src) {
+                Value::Input(base) => {
+                    let offset = base + off;
+                    events.loads.push(Load { at, offset });
+                    Value::Loaded {
+                        offset,
+                        size: load_size(instruction.opcode),
+                        load: at,
+                        compared: false,
+                    }
+                }
+                _ => Value::Unknown,
+            };
+            set(state, dst, loaded);
+        }
+        Opcode::Stxdw => {
+            if let (
+                Value::Input(base),
+                Value::Adjusted {
+                    offset,
+                    load,
+                    arithmetic,
+                    op,
+                    checked,
+                },
+            ) = (value(dst), value(src))
+                && base + off == offset
+            {
+                events.balance_updates.push(BalanceUpdate {
+                    offset,
+                    op,
+                    checked,
+                    load,
+                    arithmetic,
+                    store: at,
+                });
+            }
+        }
+        Opcode::Stb | Opcode::Sth | Opcode::Stw | Opcode::Stdw => {}
+        Opcode::Stxb | Opcode::Stxh | Opcode::Stxw => {}
+        Opcode::Mov64Reg => set(state, dst, value(src)),
+        Opcode::Add64Imm | Opcode::Sub64Imm if matches!(value(dst), Value::Input(_)) => {
+            let pointer = match (value(dst), imm) {
+                (Value::Input(base), Some(imm)) if instruction.opcode == Opcode::Add64Imm => {
+                    Value::Input(base + imm)
+                }
+                (Value::Input(base), Some(imm)) => Value::Input(base - imm),
+                _ => Value::Unknown,
+            };
+            set(state, dst, pointer);
+        }
+        Opcode::Add64Imm | Opcode::Add64Reg | Opcode::Sub64Imm | Opcode::Sub64Reg => {
+            let adjusted = match value(dst) {
+                Value::Loaded {
+                    offset,
+                    size: 8,
+                    load,
+                    compared,
+                } => {
+                    let op = match instruction.opcode {
+                        Opcode::Add64Imm | Opcode::Add64Reg => Adjust::Add,
+                        _ => Adjust::Sub,
+                    };
+                    Value::Adjusted {
+                        offset,
+                        load,
+                        arithmetic: at,
+                        op,
+                        // A debit is safe once the balance has been compared;
+                        // a credit needs a check afterwards.
+                        checked: op == Adjust::Sub && compared,
+                    }
+                }
+                _ => Value::Unknown,
+            };
+            set(state, dst, adjusted);
+        }
+        Opcode::Call => {
+            if is_memcmp(instruction) {
+                events.key_checks.push(at);
+            }
+            for register in &mut state[0..=5] {
+                *register = Value::Unknown;
+            }
+        }
+        Opcode::Callx => {
+            for register in &mut state[0..=5] {
+                *register = Value::Unknown;
+            }
+        }
+        Opcode::Exit | Opcode::Ja => {}
+        _ if instruction.is_jump() => {
+            for register in [dst, src].into_iter().flatten() {
+                compare(state, register, at, events);
+            }
+        }
+        _ => set(state, dst, Value::Unknown),
+    }
+}
+
+/// Record that a conditional jump looks at `register`.
+fn compare(state: &mut State, register: usize, at: At, events: &mut Events) {
+    let Some(value) = state.get_mut(register) else {
+        return;
+    };
+    match value {
+        Value::Loaded {
+            offset,
+            size,
+            load,
+            compared,
+        } => {
+            if *size == 1 && offset.rem_euclid(8) == IS_SIGNER {
+                events.signer_checks.push(at);
+            }
+            if *size == 8 && offset.rem_euclid(8) == 0 {
+                events.key_compares.push((at, *offset));
+                events.key_loads.insert(*load);
+            }
+            *compared = true;
+        }
+        Value::Adjusted {
+            op: Adjust::Add,
+            checked,
+            ..
+        } => *checked = true,
+        _ => {}
+    }
+}
+
+fn set(state: &mut State, register: Option<usize>, value: Value) {
+    if let Some(slot) = register.and_then(|register| state.get_mut(register)) {
+        *slot = value;
+    }
+}
+
+fn load_size(opcode: Opcode) -> u8 {
+    match opcode {
+        Opcode::Ldxb => 1,
+        Opcode::Ldxh => 2,
+        Opcode::Ldxw => 4,
+        _ => 8,
+    }
+}
+
+fn is_memcmp(instruction: &Instruction) -> bool {
+    match &instruction.imm {
+        Some(Either::Left(name)) => name == "sol_memcmp_",
+        Some(Either::Right(Number::Int(hash))) => SYSCALLS.get(*hash as u32) == Some("sol_memcmp_"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_common::inst_param::Register,
+        sbpf_ir::{InputNode, control_flow_graph},
+    };
+
+    enum Line {
+        Label(&'static str),
+        Ix(Instruction),
+    }
+
+    fn ix(opcode: Opcode, dst: Option<u8>, src: Option<u8>, off: i16, imm: Option<i64>) -> Line {
+        Line::Ix(Instruction {
+            opcode,
+            dst: dst.map(|n| Register { n }),
+            src: src.map(|n| Register { n }),
+            off: Some(Either::Right(off)),
+            imm: imm.map(|value| Either::Right(Number::Int(value))),
+            span: 0..0,
+        })
+    }
+
+    fn jump(opcode: Opcode, dst: u8, imm: i64, target: &str) -> Line {
+        Line::Ix(Instruction {
+            opcode,
+            dst: Some(Register { n: dst }),
+            src: None,
+            off: Some(Either::Left(target.to_string())),
+            imm: Some(Either::Right(Number::Int(imm))),
+            span: 0..0,
+        })
+    }
+
+    fn exit() -> Line {
+        Line::Ix(Instruction {
+            opcode: Opcode::Exit,
+            dst: None,
+            src: None,
+            off: None,
+            imm: None,
+            span: 0..0,
+        })
+    }
+
+    fn lint(program: &[Line]) -> Vec<(usize, &'static str)> {
+        let nodes = program.iter().map(|line| match line {
+            Line::Label(label) => InputNode::Label(label),
+            Line::Ix(instruction) => InputNode::Instruction(instruction),
+        });
+        let entries = HashSet::from(["entrypoint".to_string()]);
+        let cfg = control_flow_graph(nodes, &entries, Some("entrypoint"));
+        security_lints(&cfg)
+            .iter()
+            .map(|finding| (finding.node_id, finding.rule.name()))
+            .collect()
+    }
+
+    #[test]
+    fn test_unchecked_lamport_moves() {
+        let program = [
+            Line::Label("entrypoint"),
+            ix(Opcode::Ldxdw, Some(2), Some(1), 80, None),
+            ix(Opcode::Sub64Imm, Some(2), None, 0, Some(100)),
+            ix(Opcode::Stxdw, Some(1), Some(2), 80, None),
+            ix(Opcode::Ldxdw, Some(3), Some(1), 10424, None),
+            ix(Opcode::Add64Imm, Some(3), None, 0, Some(100)),
+            ix(Opcode::Stxdw, Some(1), Some(3), 10424, None),
+            exit(),
+        ];
+        assert_eq!(
+            lint(&program),
+            vec![
+                (2, "unchecked-lamports-arithmetic"),
+                (3, "missing-signer-check"),
+                (5, "unchecked-lamports-arithmetic"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checked_lamport_moves() {
+        let program = [
+            Line::Label("entrypoint"),
+            ix(Opcode::Ldxb, Some(4), Some(1), 9, None),
+            jump(Opcode::JeqImm, 4, 0, "fail"),
+            ix(Opcode::Ldxdw, Some(2), Some(1), 80, None),
+            jump(Opcode::JltImm, 2, 100, "fail"),
+            ix(Opcode::Sub64Imm, Some(2), None, 0, Some(100)),
+            ix(Opcode::Stxdw, Some(1), Some(2), 80, None),
+            ix(Opcode::Ldxdw, Some(3), Some(1), 10424, None),
+            ix(Opcode::Add64Imm, Some(3), None, 0, Some(100)),
+            jump(Opcode::JltImm, 3, 100, "fail"),
+            ix(Opcode::Stxdw, Some(1), Some(3), 10424, None),
+            exit(),
+            Line::Label("fail"),
+            exit(),
+        ];
+        assert_eq!(lint(&program), Vec::new());
+    }
+
+    #[test]
+    fn test_account_data_read_before_owner_check() {
+        let mut program = vec![
+            Line::Label("entrypoint"),
+            ix(Opcode::Ldxdw, Some(2), Some(1), 96, None),
+        ];
+        for word in 0..4 {
+            program.push(ix(Opcode::Ldxdw, Some(3), Some(1), 48 + word * 8, None));
+            program.push(jump(Opcode::JneImm, 3, 0, "fail"));
+        }
+        program.extend([
+            ix(Opcode::Ldxdw, Some(4), Some(1), 96, None),
+            exit(),
+            Line::Label("fail"),
+            exit(),
+        ]);
+        assert_eq!(lint(&program), vec![(1, "unvalidated-account-owner")]);
+    }
+}
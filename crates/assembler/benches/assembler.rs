@@ -0,0 +1,79 @@
+//! Throughput of each assembler stage on synthetic programs.
+//!
+//! Save a baseline before a change and compare against it afterwards:
+//!
+//! ```sh
+//! cargo bench -p sbpf-assembler -- --save-baseline before
+//! cargo bench -p sbpf-assembler -- --baseline before
+//! ```
+
+use {
+    criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main},
+    pest::Parser,
+    sbpf_assembler::{
+        Assembler, AssemblerOption, Program, SbpfArch, parse,
+        parser::{Rule, SbpfParser},
+    },
+    std::{fmt::Write, hint::black_box},
+};
+
+/// Program sizes to measure, in source lines.
+const SIZES: &[usize] = &[10_000, 100_000];
+
+/// Lines emitted per synthetic function.
+const FUNCTION_LINES: usize = 10;
+
+/// A program of roughly `lines` lines made of small functions that exercise
+/// labels, jumps, calls, memory operands, `lddw` and rodata references.
+fn synthetic_program(lines: usize) -> String {
+    let functions = lines.div_ceil(FUNCTION_LINES);
+    let mut source = String::from(".globl entrypoint\nentrypoint:\n  call fn_0\n  exit\n");
+    for i in 0..functions {
+        let next = (i + 1) % functions;
+        writeln!(
+            source,
+            "fn_{i}:\n  mov64 r0, {i}\n  ldxdw r2, [r1+8]\n  add64 r0, r2\n  jeq r0, 0, fn_{i}_done\n  lddw r3, message\n  stxdw [r10-8], r3\n  call fn_{next}\nfn_{i}_done:\n  exit"
+        )
+        .unwrap();
+    }
+    source.push_str(".rodata\n  message: .ascii \"benchmark\"\n");
+    source
+}
+
+fn bench_stages(c: &mut Criterion) {
+    for &lines in SIZES {
+        let source = synthetic_program(lines);
+        let mut group = c.benchmark_group(format!("{}k_lines", lines / 1000));
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        if lines >= 100_000 {
+            group.sample_size(10);
+        }
+
+        group.bench_function("lexer", |b| {
+            b.iter(|| {
+                SbpfParser::parse(Rule::program, black_box(&source))
+                    .unwrap()
+                    .flatten()
+                    .count()
+            })
+        });
+        group.bench_function("parser", |b| {
+            b.iter(|| parse(black_box(&source), SbpfArch::V3).unwrap())
+        });
+        group.bench_function("encoder", |b| {
+            b.iter_batched(
+                || parse(&source, SbpfArch::V3).unwrap(),
+                |layout| Program::from_parse_result(layout, None, None).emit_bytecode(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function("pipeline", |b| {
+            let assembler = Assembler::new(AssemblerOption::default());
+            b.iter(|| assembler.assemble(black_box(&source)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_stages);
+criterion_main!(benches);
@@ -0,0 +1,212 @@
+use {
+    crate::{
+        AssembleErrors, astnode::ASTNode, dynsym::RelDynMap, errors::CompileError,
+        parser::ProgramLayout,
+    },
+    sbpf_common::program_error::ErrorRegistry,
+    std::{ops::Range, time::Duration},
+};
+
+/// Source file name used when assembling without preprocessing.
+pub const INPUT_NAME: &str = "<input>";
+
+/// Everything produced by a single assembly run.
+#[derive(Debug, Clone)]
+pub struct Artifacts {
+    /// The emitted ELF.
+    pub elf: Vec<u8>,
+    /// Source line of every instruction, ordered by `.text` offset.
+    pub sourcemap: Vec<LineMapping>,
+    /// Labels in `.text` and `.rodata`.
+    pub symbols: Vec<SymbolInfo>,
+    /// Dynamic relocations, empty for static programs.
+    pub relocations: RelDynMap,
+    /// Non-fatal diagnostics.
+    pub warnings: Vec<CompileError>,
+    /// `warnings` located in the original files, when the program was
+    /// assembled with preprocessing.
+    pub located_warnings: Option<AssembleErrors>,
+    /// Custom errors declared with `.error`, also embedded in the ELF.
+    pub errors: ErrorRegistry,
+    pub stats: AssemblyStats,
+    /// Time spent in each phase. Artifacts served from a cache keep the
+    /// timings of the run that produced them.
+    pub timings: PhaseTimings,
+}
+
+/// Maps a `.text` offset back to the line it was assembled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMapping {
+    pub offset: u64,
+    pub file: String,
+    /// 1-based line number
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSection {
+    Text,
+    Rodata,
+    Data,
+    Bss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub section: SymbolSection,
+    /// Offset from the start of the section.
+    pub offset: u64,
+    /// Size in bytes, known for data labels only.
+    pub size: Option<u64>,
+    pub file: String,
+    /// 1-based line number
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssemblyStats {
+    pub instruction_count: usize,
+    pub text_size: u64,
+    pub rodata_size: u64,
+    pub data_size: u64,
+    pub bss_size: u64,
+    pub relocation_count: usize,
+    pub elf_size: usize,
+}
+
+/// Wall-clock time spent in each phase of an assembly run. Phases a run
+/// skips stay zero, as do all of them on wasm32, which has no clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// Front-end translation, includes, macros and conditional assembly.
+    pub preprocess: Duration,
+    /// Tokenizing and parsing into an AST, with constants folded.
+    pub parse: Duration,
+    /// Merging the objects of a multi-file program.
+    pub link: Duration,
+    /// Optimization, branch relaxation and label resolution.
+    pub layout: Duration,
+    /// The loader's checks over `.text`.
+    pub verify: Duration,
+    /// Encoding instructions, data and debug info into sections.
+    pub encode: Duration,
+    /// Writing out the ELF image.
+    pub elf: Duration,
+}
+
+impl PhaseTimings {
+    /// Each phase with its name, in the order they run.
+    pub fn phases(&self) -> [(&'static str, Duration); 7] {
+        [
+            ("preprocess", self.preprocess),
+            ("parse", self.parse),
+            ("link", self.link),
+            ("layout", self.layout),
+            ("verify", self.verify),
+            ("encode", self.encode),
+            ("elf", self.elf),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+/// Run `f`, returning its result and how long it took.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = std::time::Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    (f(), Duration::ZERO)
+}
+
+/// Collect the source map, symbols and statistics of a parsed program.
+/// `locate` resolves a span to its file and 1-based line. The ELF is left
+/// empty for the caller to fill in once the layout has been emitted.
+pub(crate) fn collect_artifacts(
+    layout: &ProgramLayout,
+    locate: impl Fn(&Range<usize>) -> (String, u32),
+) -> Artifacts {
+    let mut sourcemap = Vec::new();
+    let mut symbols = Vec::new();
+
+    for node in layout.code_section.get_nodes() {
+        match node {
+            ASTNode::Instruction {
+                instruction,
+                offset,
+            } => {
+                let (file, line) = locate(&instruction.span);
+                sourcemap.push(LineMapping {
+                    offset: *offset,
+                    file,
+                    line,
+                });
+            }
+            ASTNode::Label { label, offset } => {
+                let (file, line) = locate(&label.span);
+                symbols.push(SymbolInfo {
+                    name: label.name.clone(),
+                    section: SymbolSection::Text,
+                    offset: *offset,
+                    size: None,
+                    file,
+                    line,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    for (section, data_section) in [
+        (SymbolSection::Rodata, &layout.data_section),
+        (SymbolSection::Data, &layout.writable_data_section),
+        (SymbolSection::Bss, &layout.bss_section),
+    ] {
+        for node in data_section.get_nodes() {
+            if let ASTNode::ROData { rodata, offset } = node
+                && !rodata.is_unlabeled()
+            {
+                let (file, line) = locate(&rodata.span);
+                symbols.push(SymbolInfo {
+                    name: rodata.name.clone(),
+                    section,
+                    offset: *offset,
+                    size: Some(rodata.get_size()),
+                    file,
+                    line,
+                });
+            }
+        }
+    }
+
+    let relocations = layout.relocation_data.copy();
+    let stats = AssemblyStats {
+        instruction_count: sourcemap.len(),
+        text_size: layout.code_section.get_size(),
+        rodata_size: layout.data_section.get_size(),
+        data_size: layout.writable_data_section.get_size(),
+        bss_size: layout.bss_section.get_size(),
+        relocation_count: relocations.get_rel_dyns().len(),
+        elf_size: 0,
+    };
+
+    Artifacts {
+        elf: Vec::new(),
+        sourcemap,
+        symbols,
+        relocations,
+        warnings: layout.warnings.clone(),
+        located_warnings: None,
+        errors: layout.custom_errors.clone(),
+        stats,
+        timings: PhaseTimings::default(),
+    }
+}
@@ -1,21 +1,26 @@
 use {
     crate::{
         CompileError, SbpfArch,
-        astnode::{ASTNode, ROData},
+        astnode::{ASTNode, ROData, SourceLocation},
         dynsym::{DynamicSymbolMap, RelDynMap, RelocationType},
         header::ProgramHeader,
-        optimizer,
-        parser::ProgramLayout,
+        lint, optimizer,
+        parser::{ProgramLayout, common::split_addend},
+        relax,
         section::{CodeSection, DataSection},
+        syscalls::SyscallRegistry,
     },
     either::Either,
     sbpf_common::{
         inst_param::{Number, Register},
         instruction::Instruction,
         opcode::Opcode,
+        profile::ExecutionProfile,
+        program_error::{ErrorDefinition, ErrorRegistry},
     },
     std::{
         collections::{HashMap, HashSet},
+        ops::Range,
         path::PathBuf,
     },
     syscall_map::murmur3_32,
@@ -24,10 +29,25 @@ use {
 type LabelOffsetMap = HashMap<String, u64>;
 type NumericLabel = (String, u64, usize);
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Which optimization passes run when optimization is enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptLevel {
+    /// Peephole rewrites and dead function elimination.
+    #[default]
+    Basic,
+    /// Also inline small internal functions when that reduces both size and CU.
+    Aggressive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum OptimizationConfig {
     Disabled,
-    Enabled { cfg_dump_dir: Option<PathBuf> },
+    Enabled {
+        level: OptLevel,
+        cfg_dump_dir: Option<PathBuf>,
+        /// Execution profile used to order functions by how hot they are.
+        profile: Option<ExecutionProfile>,
+    },
 }
 
 impl Default for OptimizationConfig {
@@ -42,27 +62,78 @@ impl OptimizationConfig {
     }
 
     pub fn enabled() -> Self {
-        Self::Enabled { cfg_dump_dir: None }
+        Self::Enabled {
+            level: OptLevel::default(),
+            cfg_dump_dir: None,
+            profile: None,
+        }
+    }
+
+    pub fn with_level(self, level: OptLevel) -> Self {
+        match self {
+            Self::Enabled {
+                cfg_dump_dir,
+                profile,
+                ..
+            } => Self::Enabled {
+                level,
+                cfg_dump_dir,
+                profile,
+            },
+            Self::Disabled => Self::Disabled,
+        }
     }
 
     pub fn with_cfg_dump_dir(self, path: impl Into<PathBuf>) -> Self {
         match self {
-            Self::Enabled { .. } => Self::Enabled {
+            Self::Enabled { level, profile, .. } => Self::Enabled {
+                level,
                 cfg_dump_dir: Some(path.into()),
+                profile,
+            },
+            Self::Disabled => Self::Disabled,
+        }
+    }
+
+    pub fn with_profile(self, profile: ExecutionProfile) -> Self {
+        match self {
+            Self::Enabled {
+                level,
+                cfg_dump_dir,
+                ..
+            } => Self::Enabled {
+                level,
+                cfg_dump_dir,
+                profile: Some(profile),
             },
             Self::Disabled => Self::Disabled,
         }
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct AST {
     pub nodes: Vec<ASTNode>,
     pub rodata_nodes: Vec<ASTNode>,
+    /// Labelled data in the writable `.data` section.
+    pub data_nodes: Vec<ASTNode>,
+    /// `.zero`/`.space` reservations in the zero-initialized `.bss` section.
+    pub bss_nodes: Vec<ASTNode>,
+    /// Locations set by `.loc`, keyed by the span start of each instruction
+    /// they cover.
+    pub source_locations: HashMap<usize, SourceLocation>,
+    /// Warnings found while parsing, such as unused labels and constants.
+    pub warnings: Vec<CompileError>,
+    /// Custom errors declared with `.error`, with the span of each
+    /// declaration.
+    pub custom_errors: Vec<(ErrorDefinition, Range<usize>)>,
 
     function_entries: HashSet<String>,
     text_size: u64,
     rodata_size: u64,
+    data_size: u64,
+    bss_size: u64,
+    syscalls: SyscallRegistry,
 }
 
 impl AST {
@@ -70,6 +141,11 @@ impl AST {
         Self::default()
     }
 
+    /// Resolve calls to the syscalls in `syscalls` instead of the default set.
+    pub fn set_syscalls(&mut self, syscalls: SyscallRegistry) {
+        self.syscalls = syscalls;
+    }
+
     pub fn add_function_entry(&mut self, name: String) {
         self.function_entries.insert(name);
     }
@@ -78,6 +154,26 @@ impl AST {
         &self.function_entries
     }
 
+    pub(crate) fn syscalls(&self) -> &SyscallRegistry {
+        &self.syscalls
+    }
+
+    pub(crate) fn text_size(&self) -> u64 {
+        self.text_size
+    }
+
+    pub(crate) fn rodata_size(&self) -> u64 {
+        self.rodata_size
+    }
+
+    pub(crate) fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    pub(crate) fn bss_size(&self) -> u64 {
+        self.bss_size
+    }
+
     //
     pub fn set_text_size(&mut self, text_size: u64) {
         self.text_size = text_size;
@@ -88,40 +184,36 @@ impl AST {
         self.rodata_size = rodata_size;
     }
 
+    //
+    pub fn set_data_size(&mut self, data_size: u64) {
+        self.data_size = data_size;
+    }
+
+    //
+    pub fn set_bss_size(&mut self, bss_size: u64) {
+        self.bss_size = bss_size;
+    }
+
     //
     pub fn get_instruction_at_offset(&mut self, offset: u64) -> Option<&mut Instruction> {
-        self.nodes
-            .iter_mut()
-            .find(|node| match node {
-                ASTNode::Instruction {
-                    instruction: _,
-                    offset: inst_offset,
-                    ..
-                } => offset == *inst_offset,
-                _ => false,
-            })
-            .map(|node| match node {
-                ASTNode::Instruction { instruction, .. } => instruction,
-                _ => panic!("Expected Instruction node"),
-            })
+        self.nodes.iter_mut().find_map(|node| match node {
+            ASTNode::Instruction {
+                instruction,
+                offset: inst_offset,
+            } if *inst_offset == offset => Some(instruction),
+            _ => None,
+        })
     }
 
     //
     pub fn get_rodata_at_offset(&self, offset: u64) -> Option<&ROData> {
-        self.rodata_nodes
-            .iter()
-            .find(|node| match node {
-                ASTNode::ROData {
-                    rodata: _,
-                    offset: rodata_offset,
-                    ..
-                } => offset == *rodata_offset,
-                _ => false,
-            })
-            .map(|node| match node {
-                ASTNode::ROData { rodata, .. } => rodata,
-                _ => panic!("Expected ROData node"),
-            })
+        self.rodata_nodes.iter().find_map(|node| match node {
+            ASTNode::ROData {
+                rodata,
+                offset: rodata_offset,
+            } if *rodata_offset == offset => Some(rodata),
+            _ => None,
+        })
     }
 
     /// Resolve numeric label references (like "2f" or "1b")
@@ -160,19 +252,30 @@ pub fn build_program(
     arch: SbpfArch,
     optimization: OptimizationConfig,
 ) -> Result<ProgramLayout, Vec<CompileError>> {
+    let addends = optimizer::resolve_jump_addends(&mut ast.nodes);
+    let mut warnings = std::mem::take(&mut ast.warnings);
+    warnings.extend(lint::check_byte_order(&ast));
+    warnings.extend(lint::check_unreachable_code(&ast));
+    warnings.extend(lint::check_writable_data(&ast));
+    warnings.sort_by_key(|warning| warning.span().start);
     let optimization = run_optimizations(&mut ast, &optimization);
-    let mut errors = optimization.errors;
+    let mut errors = addends.errors;
+    errors.extend(optimization.errors);
+    let relaxation = relax::relax_branches(&mut ast);
+    errors.extend(relaxation.errors);
 
     let (label_offset_map, numeric_labels) = label_offset_map(&ast);
+    let syscalls = ast.syscalls.clone();
     let program_is_static = arch.is_v3()
         || !ast.nodes.iter().any(|node| {
             matches!(node, ASTNode::Instruction { instruction: inst, .. }
-                if inst.is_syscall()
+                if syscalls.is_syscall(inst)
                 || (inst.opcode == Opcode::Lddw && matches!(&inst.imm, Some(Either::Left(_)))))
         });
 
     let label_resolution = resolve_label_references(
         &mut ast,
+        &syscalls,
         arch,
         program_is_static,
         &label_offset_map,
@@ -182,20 +285,37 @@ pub fn build_program(
 
     optimizer::remove_temp_control_flow_target_labels(
         &mut ast.nodes,
-        &optimization.labels_to_remove,
+        &addends
+            .labels_to_remove
+            .into_iter()
+            .chain(optimization.labels_to_remove)
+            .chain(relaxation.labels_to_remove)
+            .collect(),
     );
 
     if !errors.is_empty() {
         Err(errors)
     } else {
         Ok(ProgramLayout {
-            code_section: CodeSection::new(std::mem::take(&mut ast.nodes), ast.text_size),
+            code_section: CodeSection::new(std::mem::take(&mut ast.nodes), ast.text_size)
+                .with_arch(arch),
             data_section: DataSection::new(std::mem::take(&mut ast.rodata_nodes), ast.rodata_size),
+            writable_data_section: DataSection::writable(
+                std::mem::take(&mut ast.data_nodes),
+                ast.data_size,
+            ),
+            bss_section: DataSection::bss(std::mem::take(&mut ast.bss_nodes), ast.bss_size),
             dynamic_symbols: label_resolution.dynamic_symbols,
             relocation_data: label_resolution.relocations,
             prog_is_static: program_is_static,
             arch,
             debug_sections: Vec::default(),
+            source_locations: std::mem::take(&mut ast.source_locations),
+            warnings,
+            custom_errors: std::mem::take(&mut ast.custom_errors)
+                .into_iter()
+                .map(|(error, _)| error)
+                .collect::<ErrorRegistry>(),
         })
     }
 }
@@ -207,7 +327,12 @@ struct OptimizationOutcome {
 }
 
 fn run_optimizations(ast: &mut AST, config: &OptimizationConfig) -> OptimizationOutcome {
-    let OptimizationConfig::Enabled { cfg_dump_dir } = config else {
+    let OptimizationConfig::Enabled {
+        level,
+        cfg_dump_dir,
+        profile,
+    } = config
+    else {
         return OptimizationOutcome::default();
     };
 
@@ -219,6 +344,15 @@ fn run_optimizations(ast: &mut AST, config: &OptimizationConfig) -> Optimization
     let mut errors = Vec::new();
 
     if canonicalized_targets.errors.is_empty() {
+        if let Some(profile) = profile
+            && let Err(error) = optimizer::order_functions_by_profile(ast, profile)
+        {
+            errors.push(error);
+        }
+        if *level >= OptLevel::Aggressive {
+            optimizer::inline_small_functions(ast);
+        }
+        optimizer::peephole_optimize(ast);
         if let Some(dump_dir) = cfg_dump_dir.as_deref() {
             let mut dump_errors = Vec::new();
             if let Err(error) = std::fs::create_dir_all(dump_dir) {
@@ -259,6 +393,7 @@ struct LabelResolution {
 
 fn resolve_label_references(
     ast: &mut AST,
+    syscalls: &SyscallRegistry,
     arch: SbpfArch,
     program_is_static: bool,
     label_offset_map: &LabelOffsetMap,
@@ -274,7 +409,7 @@ fn resolve_label_references(
             instruction: inst,
             offset,
         } = node
-            && inst.is_syscall()
+            && syscalls.is_syscall(inst)
             && let Some(Either::Left(syscall_name)) = &inst.imm
         {
             let syscall_name = syscall_name.clone();
@@ -314,10 +449,17 @@ fn resolve_label_references(
 
                 if let Some(target_offset) = target_offset {
                     let rel_offset = (target_offset as i64 - *offset as i64) / 8 - 1;
-                    inst.off = Some(Either::Right(rel_offset as i16));
+                    match i16::try_from(rel_offset) {
+                        Ok(rel_offset) => inst.off = Some(Either::Right(rel_offset)),
+                        Err(_) => errors.push(CompileError::JumpOutOfRange {
+                            label: label.clone(),
+                            span: inst.span.clone(),
+                            custom_label: None,
+                        }),
+                    }
                 } else {
                     errors.push(CompileError::UndefinedLabel {
-                        label: label.clone(),
+                        label: split_addend(label).0.to_string(),
                         span: inst.span.clone(),
                         custom_label: None,
                     });
@@ -329,18 +471,27 @@ fn resolve_label_references(
                 let rel_offset = (*target_offset as i64 - *offset as i64) / 8 - 1;
                 inst.src = Some(Register { n: 1 });
                 inst.imm = Some(Either::Right(Number::Int(rel_offset)));
+            } else if let Some(Either::Left(name)) = &inst.off {
+                // Memory offsets only take constants, which the parser has
+                // already folded in.
+                errors.push(CompileError::UndefinedSymbol {
+                    symbol: name.clone(),
+                    span: inst.span.clone(),
+                    custom_label: None,
+                });
             }
 
             if inst.opcode == Opcode::Lddw
                 && let Some(Either::Left(name)) = &inst.imm
             {
-                let label = name.clone();
-                // Add relocation for lddw (only for v0)
+                // The relocation is REL-style: the addend stays in the
+                // immediate and the loader adds the program's base to it.
                 if !arch.is_v3() {
-                    relocations.add_rel_dyn(*offset, RelocationType::RSbf64Relative, label.clone());
+                    relocations.add_rel_dyn(*offset, RelocationType::RSbf64Relative, name.clone());
                 }
 
-                if let Some(target_offset) = label_offset_map.get(&label) {
+                let (label, addend) = split_addend(name);
+                if let Some(target_offset) = label_offset_map.get(label) {
                     let abs_offset = if arch.is_v3() {
                         if *target_offset >= ast.text_size {
                             (ProgramHeader::V3_RODATA_VADDR + *target_offset - ast.text_size) as i64
@@ -353,7 +504,7 @@ fn resolve_label_references(
                         *target_offset as i64 + ph_offset
                     };
                     // Replace label with immediate value
-                    inst.imm = Some(Either::Right(Number::Addr(abs_offset)));
+                    inst.imm = Some(Either::Right(Number::Addr(abs_offset + addend)));
                 } else {
                     errors.push(CompileError::UndefinedLabel {
                         label: name.clone(),
@@ -361,22 +512,49 @@ fn resolve_label_references(
                         custom_label: None,
                     });
                 }
+            } else if let Some(Either::Left(name)) = &inst.imm {
+                // Any other symbolic immediate is a data address. Only v3
+                // .rodata and .data addresses fit in 32 bits; everything else needs
+                // lddw (and, for v0, a relocation).
+                let (label, addend) = split_addend(name);
+                match label_offset_map.get(label) {
+                    Some(target_offset) if arch.is_v3() && *target_offset >= ast.text_size => {
+                        let address =
+                            ProgramHeader::V3_RODATA_VADDR + *target_offset - ast.text_size;
+                        inst.imm = Some(Either::Right(Number::Addr(address as i64 + addend)));
+                    }
+                    Some(_) => errors.push(CompileError::SymbolAddressOutOfRange {
+                        symbol: name.clone(),
+                        span: inst.span.clone(),
+                        custom_label: None,
+                    }),
+                    None => errors.push(CompileError::UndefinedSymbol {
+                        symbol: name.clone(),
+                        span: inst.span.clone(),
+                        custom_label: None,
+                    }),
+                }
             }
         }
     }
 
     // Set entry point offset if a GlobalDecl was specified
-    let entry_label = ast.nodes.iter().find_map(|node| {
+    let entry = ast.nodes.iter().find_map(|node| {
         if let ASTNode::GlobalDecl { global_decl } = node {
-            Some(global_decl.entry_label.clone())
+            Some(global_decl.clone())
         } else {
             None
         }
     });
-    if let Some(entry_label) = entry_label
-        && let Some(offset) = label_offset_map.get(&entry_label)
-    {
-        dynamic_symbols.add_entry_point(entry_label, *offset);
+    if let Some(entry) = entry {
+        match label_offset_map.get(&entry.entry_label) {
+            Some(offset) => dynamic_symbols.add_entry_point(entry.entry_label, *offset),
+            None => errors.push(CompileError::UndefinedEntrypoint {
+                custom_label: Some(label_suggestions(ast, &entry.entry_label)),
+                label: entry.entry_label,
+                span: entry.span,
+            }),
+        }
     }
 
     LabelResolution {
@@ -386,6 +564,44 @@ fn resolve_label_references(
     }
 }
 
+/// The `.text` labels closest in spelling to `name`, for an undefined
+/// entrypoint's diagnostic.
+fn label_suggestions(ast: &AST, name: &str) -> String {
+    let mut labels: Vec<&str> = ast
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Label { label, .. }
+                if !label.name.starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                Some(label.name.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    if labels.is_empty() {
+        return "No labels are defined".to_string();
+    }
+    labels.sort_by_key(|label| edit_distance(label, name));
+    labels.truncate(5);
+    format!("Defined labels: {}", labels.join(", "))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 fn label_offset_map(ast: &AST) -> (LabelOffsetMap, Vec<NumericLabel>) {
     let mut label_offset_map = HashMap::new();
     let mut numeric_labels = Vec::new();
@@ -398,11 +614,33 @@ fn label_offset_map(ast: &AST) -> (LabelOffsetMap, Vec<NumericLabel>) {
     }
 
     for node in &ast.rodata_nodes {
-        if let ASTNode::ROData { rodata, offset } = node {
+        if let ASTNode::ROData { rodata, offset } = node
+            && !rodata.is_unlabeled()
+        {
             label_offset_map.insert(rodata.name.clone(), *offset + ast.text_size);
         }
     }
 
+    // .data follows .rodata, which is padded to 8 bytes.
+    let data_base = ast.text_size + ast.rodata_size.next_multiple_of(8);
+    for node in &ast.data_nodes {
+        if let ASTNode::ROData { rodata, offset } = node
+            && !rodata.is_unlabeled()
+        {
+            label_offset_map.insert(rodata.name.clone(), *offset + data_base);
+        }
+    }
+
+    // .bss follows .data, which is padded to 8 bytes too.
+    let bss_base = data_base + ast.data_size.next_multiple_of(8);
+    for node in &ast.bss_nodes {
+        if let ASTNode::ROData { rodata, offset } = node
+            && !rodata.is_unlabeled()
+        {
+            label_offset_map.insert(rodata.name.clone(), *offset + bss_base);
+        }
+    }
+
     (label_offset_map, numeric_labels)
 }
 
@@ -37,6 +37,16 @@ pub enum ASTNode {
     },
 }
 
+/// The line of a higher-level source an instruction was generated from, as
+/// declared with `.file` and `.loc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub directory: String,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct Directive {
     pub name: String,
@@ -101,7 +111,7 @@ pub struct ROData {
 
 impl ROData {
     /// Validates that an immediate value is within the specified range
-    fn validate_immediate_range(
+    pub(crate) fn validate_immediate_range(
         value: &Number,
         min: i64,
         max: u64,
@@ -118,9 +128,37 @@ impl ROData {
         Ok(())
     }
 
+    /// Width in bytes of each value of an explicit byte order directive
+    /// (`le16` to `be64`), and whether it is stored big-endian.
+    pub fn endian_width(directive: &str) -> Option<(u64, bool)> {
+        match directive {
+            "le16" => Some((2, false)),
+            "le32" => Some((4, false)),
+            "le64" => Some((8, false)),
+            "be16" => Some((2, true)),
+            "be32" => Some((4, true)),
+            "be64" => Some((8, true)),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a `.zero`/`.space` reservation, or a `.fill` of
+    /// zeroes, the only kind of data allowed in `.bss`.
+    pub fn is_zero_fill(&self) -> bool {
+        matches!(&self.args[0], Token::Directive(directive, _) if directive == "zero")
+    }
+
+    /// Whether this is data continuing its section with no label of its own.
+    pub fn is_unlabeled(&self) -> bool {
+        self.name.is_empty()
+    }
+
     pub fn get_size(&self) -> u64 {
         let size: u64;
         match (&self.args[0], &self.args[1]) {
+            (Token::Directive(_, _), Token::ImmediateValue(count, _)) => {
+                size = count.to_i64().max(0) as u64;
+            }
             (Token::Directive(_, _), Token::StringLiteral(s, _)) => {
                 size = s.len() as u64;
             }
@@ -138,7 +176,14 @@ impl ROData {
                     "quad" => {
                         size = values.len() as u64 * 8;
                     }
-                    _ => panic!("Invalid ROData declaration"),
+                    "fill" => {
+                        size = (values[0].to_i64().max(0) * values[1].to_i64().max(0)) as u64;
+                    }
+                    directive => {
+                        let (width, _) =
+                            Self::endian_width(directive).expect("Invalid ROData declaration");
+                        size = values.len() as u64 * width;
+                    }
                 }
             }
             _ => panic!("Invalid ROData declaration"),
@@ -147,6 +192,15 @@ impl ROData {
     }
     pub fn verify(&self) -> Result<(), CompileError> {
         match (&self.args[0], &self.args[1]) {
+            (Token::Directive(directive, directive_span), Token::ImmediateValue(count, span)) => {
+                if directive.as_str() != "zero" {
+                    return Err(CompileError::InvalidRODataDirective {
+                        span: directive_span.clone(),
+                        custom_label: None,
+                    });
+                }
+                Self::validate_immediate_range(count, 0, u32::MAX as u64, span.clone())?;
+            }
             (Token::Directive(directive, directive_span), Token::StringLiteral(_, _)) => {
                 if directive.as_str() != "ascii" {
                     return Err(CompileError::InvalidRODataDirective {
@@ -199,11 +253,43 @@ impl ROData {
                         )?;
                     }
                 }
-                _ => {
-                    return Err(CompileError::InvalidRODataDirective {
-                        span: directive_span.clone(),
-                        custom_label: None,
-                    });
+                "fill" => {
+                    let [count, size, value] = values.as_slice() else {
+                        return Err(CompileError::InvalidRodataDecl {
+                            span: self.span.clone(),
+                            custom_label: None,
+                        });
+                    };
+                    Self::validate_immediate_range(size, 1, 8, vector_literal_span.clone())?;
+                    let size = size.to_i64() as u32;
+                    Self::validate_immediate_range(
+                        count,
+                        0,
+                        u32::MAX as u64 / size as u64,
+                        vector_literal_span.clone(),
+                    )?;
+                    Self::validate_immediate_range(
+                        value,
+                        i64::MIN >> (64 - 8 * size),
+                        u64::MAX >> (64 - 8 * size),
+                        vector_literal_span.clone(),
+                    )?;
+                }
+                directive => {
+                    let Some((width, _)) = Self::endian_width(directive) else {
+                        return Err(CompileError::InvalidRODataDirective {
+                            span: directive_span.clone(),
+                            custom_label: None,
+                        });
+                    };
+                    for value in values {
+                        Self::validate_immediate_range(
+                            value,
+                            i64::MIN >> (64 - 8 * width),
+                            u64::MAX >> (64 - 8 * width),
+                            vector_literal_span.clone(),
+                        )?;
+                    }
                 }
             },
             _ => {
@@ -217,16 +303,72 @@ impl ROData {
     }
 }
 
+/// Move `span` `delta` bytes later in the source.
+fn shift_span(span: &mut Range<usize>, delta: usize) {
+    *span = span.start + delta..span.end + delta;
+}
+
 impl ASTNode {
+    /// Move this node `span_delta` bytes later in the source and
+    /// `text_delta`/`rodata_delta` bytes later in its section, for placing
+    /// it after other code when linking.
+    pub(crate) fn relocate(&mut self, span_delta: usize, text_delta: u64, rodata_delta: u64) {
+        match self {
+            ASTNode::Directive { directive } => {
+                shift_span(&mut directive.span, span_delta);
+                for arg in &mut directive.args {
+                    shift_span(arg.span_mut(), span_delta);
+                }
+            }
+            ASTNode::GlobalDecl { global_decl } => shift_span(&mut global_decl.span, span_delta),
+            ASTNode::EquDecl { equ_decl } => {
+                shift_span(&mut equ_decl.span, span_delta);
+                shift_span(equ_decl.value.span_mut(), span_delta);
+            }
+            ASTNode::ExternDecl { extern_decl } => {
+                shift_span(&mut extern_decl.span, span_delta);
+                for arg in &mut extern_decl.args {
+                    shift_span(arg.span_mut(), span_delta);
+                }
+            }
+            ASTNode::RodataDecl { rodata_decl } => shift_span(&mut rodata_decl.span, span_delta),
+            ASTNode::Label { label, offset } => {
+                shift_span(&mut label.span, span_delta);
+                *offset += text_delta;
+            }
+            ASTNode::ROData { rodata, offset } => {
+                shift_span(&mut rodata.span, span_delta);
+                for arg in &mut rodata.args {
+                    shift_span(arg.span_mut(), span_delta);
+                }
+                *offset += rodata_delta;
+            }
+            ASTNode::Instruction {
+                instruction,
+                offset,
+            } => {
+                shift_span(&mut instruction.span, span_delta);
+                *offset += text_delta;
+            }
+        }
+    }
+
     pub fn bytecode(&self) -> Option<Vec<u8>> {
         match self {
-            ASTNode::Instruction { instruction, .. } => Some(instruction.to_bytes().unwrap()),
+            ASTNode::Instruction { instruction, .. } => Some(
+                instruction
+                    .to_bytes()
+                    .expect("instructions are checked to encode before emitting"),
+            ),
             ASTNode::ROData {
                 rodata: ROData { args, .. },
                 ..
             } => {
                 let mut bytes = Vec::new();
                 match (&args[0], &args[1]) {
+                    (Token::Directive(_, _), Token::ImmediateValue(count, _)) => {
+                        bytes.resize(count.to_i64().max(0) as usize, 0);
+                    }
                     (Token::Directive(_, _), Token::StringLiteral(str_literal, _)) => {
                         let str_bytes = str_literal.as_bytes().to_vec();
                         bytes.extend(str_bytes);
@@ -264,6 +406,22 @@ impl ASTNode {
                                 };
                                 bytes.extend(imm64.to_le_bytes());
                             }
+                        } else if let Some((width, big_endian)) = ROData::endian_width(directive) {
+                            for value in values {
+                                let value = value.to_i64();
+                                if big_endian {
+                                    bytes.extend(&value.to_be_bytes()[8 - width as usize..]);
+                                } else {
+                                    bytes.extend(&value.to_le_bytes()[..width as usize]);
+                                }
+                            }
+                        } else if directive == "fill" {
+                            let count = values[0].to_i64().max(0) as usize;
+                            let size = values[1].to_i64().clamp(0, 8) as usize;
+                            let pattern = values[2].to_i64().to_le_bytes();
+                            for _ in 0..count {
+                                bytes.extend(&pattern[..size]);
+                            }
                         } else {
                             panic!("Invalid ROData declaration");
                         }
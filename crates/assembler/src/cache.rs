@@ -0,0 +1,397 @@
+use {
+    crate::{
+        AssemblerOption,
+        artifacts::Artifacts,
+        ast::{AST, OptimizationConfig},
+        errors::CompileError,
+        preprocessor::source_map::SourceMap,
+    },
+    sha2::{Digest, Sha256},
+    std::{
+        collections::{HashMap, VecDeque},
+        hash::{Hash, Hasher},
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Entries kept by [`ParseCache::new`] before the least recently used one is
+/// evicted.
+const DEFAULT_CAPACITY: usize = 64;
+
+/// Outcome of one assembly, as stored in the cache.
+pub type CachedResult = Result<Artifacts, Vec<CompileError>>;
+
+/// Outcome of parsing one object of a multi-file program, before linking.
+pub(crate) type CachedObject = Result<AST, Vec<CompileError>>;
+
+#[derive(Debug, Clone)]
+enum CacheEntry {
+    Program(CachedResult),
+    Object(CachedObject),
+}
+
+/// Content hash identifying one assembly: the source text, the options it was
+/// assembled with and, for preprocessed sources, the file and line each
+/// expanded line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Key for a source assembled without preprocessing.
+    pub fn new(options: &AssemblerOption, source: &str) -> Self {
+        let mut hasher = Self::hasher(options);
+        hasher.update(source.as_bytes());
+        Self(hasher.finalize().into())
+    }
+
+    /// Key for preprocessed source. Two expansions with the same text can
+    /// still map back to different files, so the source map is part of the
+    /// key.
+    pub fn preprocessed(options: &AssemblerOption, expanded: &str, source_map: &SourceMap) -> Self {
+        Self::expansion(Self::hasher(options), expanded, source_map)
+    }
+
+    /// Key for one preprocessed object of a multi-file program. Kept apart
+    /// from [`CacheKey::preprocessed`] since the same source parses to an
+    /// object and assembles to a program.
+    pub(crate) fn object(
+        options: &AssemblerOption,
+        expanded: &str,
+        source_map: &SourceMap,
+    ) -> Self {
+        let mut hasher = Self::hasher(options);
+        hasher.update(b"object\0");
+        Self::expansion(hasher, expanded, source_map)
+    }
+
+    fn expansion(mut hasher: Sha256, expanded: &str, source_map: &SourceMap) -> Self {
+        hasher.update(expanded.as_bytes());
+        for (path, line) in source_map.line_locations() {
+            hasher.update(path.as_bytes());
+            hasher.update([0]);
+            hasher.update(line.to_le_bytes());
+        }
+        Self(hasher.finalize().into())
+    }
+
+    fn hasher(options: &AssemblerOption) -> Sha256 {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        options.hash(&mut DigestHasher(&mut hasher));
+        hasher
+    }
+}
+
+/// Feeds what a [`Hash`] impl writes into a digest. The cache lives in
+/// memory, so the platform-dependent encoding of `usize` and the like is
+/// fine.
+struct DigestHasher<'a>(&'a mut Sha256);
+
+impl Hasher for DigestHasher<'_> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    /// Unused: the key is the digest, not this hash.
+    fn finish(&self) -> u64 {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Assembly results keyed by content hash, so unchanged sources are not
+/// lexed, parsed and emitted again. The objects of a multi-file program are
+/// cached one by one, so editing one file only parses that file again
+/// before linking.
+///
+/// Clones share the same entries, which lets a watcher, a language server
+/// and any other `Assembler` in the process reuse each other's work. Attach
+/// it with [`crate::Assembler::with_cache`].
+#[derive(Debug, Clone)]
+pub struct ParseCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Debug)]
+struct CacheState {
+    capacity: usize,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys from least to most recently used.
+    recency: VecDeque<CacheKey>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CacheState {
+                capacity,
+                entries: HashMap::new(),
+                recency: VecDeque::new(),
+                hits: 0,
+                misses: 0,
+            })),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResult> {
+        match self.lookup(key)? {
+            CacheEntry::Program(result) => Some(result),
+            CacheEntry::Object(_) => None,
+        }
+    }
+
+    pub fn insert(&self, key: CacheKey, result: CachedResult) {
+        self.store(key, CacheEntry::Program(result));
+    }
+
+    /// Return the cached result for `key`, or compute and store it.
+    pub fn get_or_insert_with(
+        &self,
+        key: CacheKey,
+        assemble: impl FnOnce() -> CachedResult,
+    ) -> CachedResult {
+        if let Some(result) = self.get(&key) {
+            return result;
+        }
+        // Not holding the lock while assembling; two threads racing on the
+        // same key both do the work and store identical results.
+        let result = assemble();
+        self.insert(key, result.clone());
+        result
+    }
+
+    /// Like [`ParseCache::get_or_insert_with`], for one object of a
+    /// multi-file program.
+    pub(crate) fn object_or_insert_with(
+        &self,
+        key: CacheKey,
+        parse: impl FnOnce() -> CachedObject,
+    ) -> CachedObject {
+        if let Some(CacheEntry::Object(result)) = self.lookup(&key) {
+            return result;
+        }
+        let result = parse();
+        self.store(key, CacheEntry::Object(result.clone()));
+        result
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let mut state = self.lock();
+        match state.entries.get(key).cloned() {
+            Some(entry) => {
+                state.hits += 1;
+                state.touch(key);
+                Some(entry)
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn store(&self, key: CacheKey, entry: CacheEntry) {
+        let mut state = self.lock();
+        if state.capacity == 0 {
+            return;
+        }
+        if state.entries.insert(key, entry).is_some() {
+            state.touch(&key);
+            return;
+        }
+        state.recency.push_back(key);
+        while state.entries.len() > state.capacity {
+            if let Some(oldest) = state.recency.pop_front() {
+                state.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.recency.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock();
+        CacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // The state stays consistent even if a holder panicked.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &CacheKey) {
+        if let Some(index) = self.recency.iter().position(|k| k == key) {
+            self.recency.remove(index);
+        }
+        self.recency.push_back(*key);
+    }
+}
+
+/// Whether assembling with `options` has side effects a cache hit would skip.
+pub(crate) fn has_side_effects(options: &AssemblerOption) -> bool {
+    matches!(
+        options.optimization,
+        OptimizationConfig::Enabled {
+            cfg_dump_dir: Some(_),
+            ..
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Assembler, MockFileResolver, SbpfArch, SyscallRegistry},
+    };
+
+    const SOURCE: &str = ".globl entrypoint\nentrypoint:\n  mov64 r0, 1\n  exit\n";
+
+    #[test]
+    fn test_repeated_assembly_hits_cache() {
+        let cache = ParseCache::new();
+        let assembler = Assembler::new(AssemblerOption::default()).with_cache(cache.clone());
+        let first = assembler.assemble(SOURCE).unwrap();
+        let second = assembler.assemble(SOURCE).unwrap();
+        assert_eq!(first, second);
+        assert!(assembler.assemble("mov64 r99, 1").is_err());
+        assert!(assembler.assemble("mov64 r99, 1").is_err());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                entries: 2,
+            }
+        );
+
+        // Another assembler with different options shares the cache but not
+        // the entries.
+        let v0 = Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V0))
+            .with_cache(cache.clone());
+        assert_ne!(v0.assemble(SOURCE).unwrap(), first);
+        assert_eq!(cache.stats().misses, 3);
+    }
+
+    #[test]
+    fn test_key_follows_options() {
+        let options = AssemblerOption::default();
+        let key = CacheKey::new(&options, SOURCE);
+        assert_eq!(CacheKey::new(&options.clone(), SOURCE), key);
+        assert_ne!(CacheKey::new(&options, "exit\n"), key);
+        let other_options = [
+            AssemblerOption::default().with_arch(SbpfArch::V0),
+            AssemblerOption::default().with_define("DEBUG", 1),
+            AssemblerOption::default().with_syscalls(
+                SyscallRegistry::default()
+                    .with_syscall("sol_custom")
+                    .unwrap(),
+            ),
+        ];
+        for other in &other_options {
+            assert_ne!(CacheKey::new(other, SOURCE), key, "{:?}", other);
+        }
+    }
+
+    #[test]
+    fn test_preprocessed_key_follows_includes() {
+        let cache = ParseCache::new();
+        let assembler = Assembler::new(AssemblerOption::default()).with_cache(cache.clone());
+        let source = ".globl entrypoint\nentrypoint:\n.include \"body.s\"\n";
+        let assemble = |body: &str| {
+            let mut resolver = MockFileResolver::new();
+            resolver.add_file("body.s", body);
+            assembler
+                .assemble_with_preprocess(source, "main.s", Some(&resolver))
+                .map_err(|e| e.errors[0].origin.as_ref().map(|o| o.line))
+        };
+
+        let one = assemble("  mov64 r0, 1\n  exit\n").unwrap();
+        assert_eq!(assemble("  mov64 r0, 1\n  exit\n").unwrap(), one);
+        assert_ne!(assemble("  mov64 r0, 2\n  exit\n").unwrap(), one);
+        assert_eq!(cache.stats().hits, 1);
+
+        // Cached errors are still mapped back to the included file.
+        assert_eq!(assemble("  exit\n  mov64 r99, 1\n"), Err(Some(2)));
+        assert_eq!(assemble("  exit\n  mov64 r99, 1\n"), Err(Some(2)));
+        assert_eq!(cache.stats().hits, 2);
+    }
+
+    #[test]
+    fn test_objects_are_cached_per_file() {
+        let cache = ParseCache::new();
+        let assembler = Assembler::new(AssemblerOption::default()).with_cache(cache.clone());
+        let main = ".globl entrypoint\n.extern helper\nentrypoint:\n  call helper\n  exit\n";
+        let link = |helper: &str| {
+            let objects = vec![
+                assembler.assemble_object(main, "main.s", None).unwrap(),
+                assembler.assemble_object(helper, "helper.s", None).unwrap(),
+            ];
+            assembler.link(objects).unwrap().elf
+        };
+
+        let first = link(".globl helper\nhelper:\n  mov64 r0, 1\n  exit\n");
+        assert_eq!(cache.stats().hits, 0);
+        let second = link(".globl helper\nhelper:\n  mov64 r0, 2\n  exit\n");
+        assert_eq!(cache.stats().hits, 1);
+        assert_ne!(first, second);
+        assert_eq!(
+            link(".globl helper\nhelper:\n  mov64 r0, 1\n  exit\n"),
+            first
+        );
+        assert_eq!(cache.stats().hits, 3);
+
+        // An object and a program from the same source don't share an entry.
+        assembler
+            .assemble_full_with_preprocess(main, "main.s", None)
+            .unwrap_err();
+        assert_eq!(cache.stats().hits, 3);
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = ParseCache::with_capacity(2);
+        let assembler = Assembler::new(AssemblerOption::default()).with_cache(cache.clone());
+        let programs: Vec<String> = (0..3).map(|i| format!("mov64 r0, {}\nexit\n", i)).collect();
+
+        assembler.assemble(&programs[0]).unwrap();
+        assembler.assemble(&programs[1]).unwrap();
+        assembler.assemble(&programs[0]).unwrap();
+        assembler.assemble(&programs[2]).unwrap();
+        assert_eq!(cache.stats().entries, 2);
+
+        let hits = cache.stats().hits;
+        assembler.assemble(&programs[0]).unwrap();
+        assert_eq!(cache.stats().hits, hits + 1);
+        assembler.assemble(&programs[1]).unwrap();
+        assert_eq!(cache.stats().hits, hits + 1);
+    }
+}
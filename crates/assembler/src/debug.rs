@@ -1,26 +1,43 @@
+#[cfg(feature = "dwarf")]
+use gimli::{
+    DW_AT_comp_dir, DW_AT_decl_file, DW_AT_decl_line, DW_AT_high_pc, DW_AT_language, DW_AT_low_pc,
+    DW_AT_name, DW_AT_producer, DW_AT_stmt_list, DW_LANG_Mips_Assembler, DW_TAG_label, Encoding,
+    Format, LineEncoding, LittleEndian,
+    write::{Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections},
+};
 use {
     crate::section::{DebugSection, SectionType},
-    gimli::{
-        DW_AT_comp_dir, DW_AT_decl_file, DW_AT_decl_line, DW_AT_high_pc, DW_AT_language,
-        DW_AT_low_pc, DW_AT_name, DW_AT_producer, DW_AT_stmt_list, DW_LANG_Mips_Assembler,
-        DW_TAG_label, Encoding, Format, LineEncoding, LittleEndian, SectionId,
-        write::{Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections},
-    },
+    gimli::SectionId,
 };
 
+#[cfg(feature = "dwarf")]
 const SBPF_INSTRUCTION_LENGTH: u8 = 8;
 
+/// Line and label information for DWARF generation. Without the `dwarf`
+/// feature it is accepted but no debug sections are generated from it.
 #[derive(Debug, Clone)]
 pub struct DebugData {
     pub filename: String,
     pub directory: String,
-    pub lines: Vec<(u64, u32)>,
+    /// Other source files rows refer to, as (directory, name).
+    pub files: Vec<(String, String)>,
+    pub lines: Vec<LineRow>,
     pub labels: Vec<(String, u64, u32)>,
     pub code_start: u64,
     pub code_end: u64,
 }
 
-fn calc_name_offset(names: &[String]) -> u32 {
+/// A row of the line table: the instruction at `offset` came from `line` of
+/// `files[file]`, or of `filename` when `file` is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRow {
+    pub offset: u64,
+    pub file: Option<usize>,
+    pub line: u32,
+    pub column: u32,
+}
+
+pub(crate) fn calc_name_offset(names: &[String]) -> u32 {
     (names
         .iter()
         .filter(|n| !n.is_empty())
@@ -30,6 +47,7 @@ fn calc_name_offset(names: &[String]) -> u32 {
 }
 
 /// Generate DebugSections from debug data
+#[cfg(feature = "dwarf")]
 pub fn generate_debug_sections(
     data: &DebugData,
     text_offset: u64,
@@ -90,6 +108,7 @@ pub fn generate_debug_sections(
 }
 
 // Generate DWARF sections using gimli
+#[cfg(feature = "dwarf")]
 fn generate_dwarf_sections(
     data: &DebugData,
     text_offset: u64,
@@ -125,14 +144,32 @@ fn generate_dwarf_sections(
 
     let dir_id = line_program.default_directory();
     let file_id = line_program.add_file(LineString::LineStringRef(file_string_id), dir_id, None);
+    let file_ids: Vec<_> = data
+        .files
+        .iter()
+        .map(|(directory, name)| {
+            let dir_id = if directory.is_empty() {
+                dir_id
+            } else {
+                line_program.add_directory(LineString::LineStringRef(
+                    dwarf.line_strings.add(directory.clone().into_bytes()),
+                ))
+            };
+            {
+                let name_id = dwarf.line_strings.add(name.clone().into_bytes());
+                line_program.add_file(LineString::LineStringRef(name_id), dir_id, None)
+            }
+        })
+        .collect();
 
     // Add line entries.
     line_program.begin_sequence(Some(Address::Constant(code_start)));
-    for (address, line) in &data.lines {
-        let adjusted_addr = address + text_offset;
-        line_program.row().file = file_id;
+    for row in &data.lines {
+        let adjusted_addr = row.offset + text_offset;
+        line_program.row().file = row.file.map_or(file_id, |file| file_ids[file]);
         line_program.row().address_offset = adjusted_addr - code_start;
-        line_program.row().line = *line as u64;
+        line_program.row().line = row.line as u64;
+        line_program.row().column = row.column as u64;
         line_program.generate_row();
     }
     line_program.end_sequence(code_end);
@@ -233,7 +270,7 @@ pub fn reuse_debug_sections(
     sections
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "dwarf"))]
 mod tests {
     use super::*;
 
@@ -245,7 +282,21 @@ mod tests {
         let data = DebugData {
             filename: "test.s".to_string(),
             directory: "/tmp".to_string(),
-            lines: vec![(0, 5), (8, 6)],
+            files: vec![("/src".to_string(), "lib.rs".to_string())],
+            lines: vec![
+                LineRow {
+                    offset: 0,
+                    file: None,
+                    line: 5,
+                    column: 0,
+                },
+                LineRow {
+                    offset: 8,
+                    file: Some(0),
+                    line: 6,
+                    column: 3,
+                },
+            ],
             labels: vec![("entrypoint".to_string(), 0, 4)],
             code_start: 0,
             code_end: 16,
@@ -113,6 +113,16 @@ pub enum RelocationType {
     RSbfSyscall = 0x0a,
 }
 
+impl RelocationType {
+    /// ELF name of the relocation type, as printed by `readelf -r`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelocationType::RSbf64Relative => "R_SBF_64_RELATIVE",
+            RelocationType::RSbfSyscall => "R_SBF_SYSCALL",
+        }
+    }
+}
+
 pub fn get_relocation_info(inst: &Instruction) -> (RelocationType, String) {
     match inst.opcode {
         Opcode::Lddw => match &inst.imm {
@@ -163,7 +173,7 @@ impl RelDyn {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct RelDynMap {
     rel_dyns: BTreeMap<u64, Vec<(RelocationType, String)>>,
 }
@@ -313,6 +323,12 @@ mod tests {
         assert_eq!(rel_dyns.len(), 2);
     }
 
+    #[test]
+    fn test_relocation_type_as_str() {
+        assert_eq!(RelocationType::RSbf64Relative.as_str(), "R_SBF_64_RELATIVE");
+        assert_eq!(RelocationType::RSbfSyscall.as_str(), "R_SBF_SYSCALL");
+    }
+
     #[test]
     fn test_rel_dyn_map_copy() {
         let mut map = RelDynMap::new();
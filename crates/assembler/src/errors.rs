@@ -9,6 +9,11 @@ define_compile_errors! {
         label = "Invalid number",
         fields = { number: String, span: Range<usize> }
     },
+    InvalidTypedLiteral {
+        error = "Invalid literal '{literal}': {reason}",
+        label = "Invalid unit literal",
+        fields = { literal: String, reason: String, span: Range<usize> }
+    },
     InvalidRegister {
         error = "Invalid register '{register}'",
         label = "Invalid register",
@@ -24,6 +29,21 @@ define_compile_errors! {
         label = "Unterminated string literal",
         fields = { span: Range<usize> }
     },
+    UnterminatedBlockComment {
+        error = "Unterminated block comment",
+        label = "Block comment is never closed",
+        fields = { span: Range<usize> }
+    },
+    InvalidUtf8 {
+        error = "Invalid UTF-8 sequence {bytes} at byte {offset} (line {line}, column {column})",
+        label = "Invalid UTF-8",
+        fields = { bytes: String, offset: usize, line: usize, column: usize, span: Range<usize> }
+    },
+    UnsupportedEncoding {
+        error = "Source is encoded as {encoding}, only UTF-8 is supported",
+        label = "Unsupported encoding",
+        fields = { encoding: String, span: Range<usize> }
+    },
     // Syntactic errors
     InvalidGlobalDecl {
         error = "Invalid global declaration",
@@ -50,6 +70,11 @@ define_compile_errors! {
         label = "Invalid directive",
         fields = { directive: String, span: Range<usize> }
     },
+    InvalidDirectiveOperand {
+        error = "Invalid operand for '{directive}': expected {expected} (usage: {usage})",
+        label = "Invalid operand",
+        fields = { directive: String, expected: String, usage: String, span: Range<usize> }
+    },
     InvalidInstruction {
         error = "Invalid '{instruction}' instruction",
         label = "Invalid instruction",
@@ -85,27 +110,127 @@ define_compile_errors! {
         label = "Invalid rodata directive",
         fields = { span: Range<usize> }
     },
+    InitializedBss {
+        error = "Only zero fills (.zero, .space, .fill) can reserve space in .bss",
+        label = "Move initialized data to .data",
+        fields = { span: Range<usize> }
+    },
     CrossSectionArithmetic {
         error = "Cross-section label arithmetic: '{label1}' and '{label2}' are in different sections",
         label = "Cross-section arithmetic",
         fields = { label1: String, label2: String, span: Range<usize> }
     },
+    UnrelocatableExpression {
+        error = "Expression is not a link-time constant",
+        label = "Only a label plus a constant, or a difference of labels, can be resolved",
+        fields = { span: Range<usize> }
+    },
     // Semantic errors
     UndefinedLabel {
         error = "Undefined label '{label}'",
         label = "Undefined label",
         fields = { label: String, span: Range<usize> }
     },
+    UndefinedEntrypoint {
+        error = "Entrypoint '{label}' is declared .globl but never defined",
+        label = "Undefined entrypoint",
+        fields = { label: String, span: Range<usize> }
+    },
+    UndefinedSymbol {
+        error = "Undefined symbol '{symbol}'",
+        label = "Undefined symbol",
+        fields = { symbol: String, span: Range<usize> }
+    },
+    UndeclaredSourceFile {
+        error = "File number {file} has not been declared with .file",
+        label = "Undeclared file number",
+        fields = { file: u32, span: Range<usize> }
+    },
+    JumpOutOfRange {
+        error = "Jump to '{label}' is out of range",
+        label = "Target is more than 32767 instructions away",
+        fields = { label: String, span: Range<usize> }
+    },
+    ByteSwappedData {
+        error = "'{label}' is stored little-endian with .{directive} but read with a byte swap",
+        label = "Byte-swapped load of little-endian data",
+        fields = { label: String, directive: String, span: Range<usize> }
+    },
+    UnreachableCode {
+        error = "Unreachable code",
+        label = "No jump, call or fall-through reaches this code",
+        fields = { span: Range<usize> }
+    },
+    UnusedLabel {
+        error = "Unused label '{label}'",
+        label = "Nothing jumps to, calls or loads this label",
+        fields = { label: String, span: Range<usize> }
+    },
+    UnusedConstant {
+        error = "Unused constant '{name}'",
+        label = "Constant is never used",
+        fields = { name: String, span: Range<usize> }
+    },
+    ShadowedConstant {
+        error = "Constant '{name}' is redefined",
+        label = "Uses after this line see the new value",
+        fields = { name: String, span: Range<usize> }
+    },
+    WritableData {
+        error = "The Solana program loader rejects writable section '{section}'",
+        label = "Only sbpf-vm can load this data",
+        fields = { section: String, span: Range<usize> }
+    },
+    StackLimitExceeded {
+        error = "Function '{function}' uses {size} bytes of stack, more than the {limit} allowed",
+        label = "Deepest stack access of the function",
+        fields = { function: String, size: u64, limit: u64, span: Range<usize> }
+    },
+    ComputeLimitExceeded {
+        error = "Function '{function}' may use {units} compute units, more than the {limit} allowed",
+        label = "Function starts here",
+        fields = { function: String, units: u64, limit: u64, span: Range<usize> }
+    },
+    SymbolAddressOutOfRange {
+        error = "Address of '{symbol}' does not fit in a 32-bit immediate",
+        label = "Load this address with lddw",
+        fields = { symbol: String, span: Range<usize> }
+    },
     DuplicateLabel {
         error = "Duplicate label '{label}'",
         label = "Label redefined",
         fields = { label: String, span: Range<usize>, original_span: Range<usize> }
     },
+    DuplicateSymbol {
+        error = "Symbol '{symbol}' is also declared .globl in {file}",
+        label = "Symbol redefined",
+        fields = { symbol: String, file: String, span: Range<usize> }
+    },
+    SymbolNotGlobal {
+        error = "'{symbol}' is defined in {file} but not declared .globl there",
+        label = "Symbol is local to another file",
+        fields = { symbol: String, file: String, span: Range<usize> }
+    },
+    UnresolvedExtern {
+        error = "'{symbol}' is declared external but no file defines it as .globl",
+        label = "Unresolved external symbol",
+        fields = { symbol: String, span: Range<usize> }
+    },
     BytecodeError {
         error = "Bytecode error: {error}",
         label = "Bytecode error",
         fields = { error: String, span: Range<usize> }
     },
+    VerifierError {
+        error = "Verifier error: {error}",
+        label = "The loader would reject this instruction",
+        fields = { error: String, span: Range<usize> }
+    },
+    ProfileMismatch {
+        error = "Profile does not match the program: expected {expected} instruction counts, found {found}",
+        label = "Profile mismatch",
+        fields = { expected: usize, found: usize, span: Range<usize> }
+    },
     MissingTextDirective {
         error = "Missing text directive",
         label = "Missing text directive",
@@ -127,6 +252,11 @@ define_compile_errors! {
         label = "Read error",
         fields = { path: String, reason: String, span: Range<usize> }
     },
+    FrontendError {
+        error = "{frontend}: {message}",
+        label = "Front-end error",
+        fields = { frontend: String, message: String, span: Range<usize> }
+    },
     UnclosedMacro {
         error = "Macro '{name}' missing .endm",
         label = "Unclosed macro definition",
@@ -172,4 +302,197 @@ define_compile_errors! {
         label = "Multiple vararg parameters",
         fields = { name: String, span: Range<usize> }
     },
+    UnclosedLayout {
+        error = "Layout '{name}' missing .endlayout",
+        label = "Unclosed layout block",
+        fields = { name: String, span: Range<usize> }
+    },
+    DuplicateLayout {
+        error = "Layout '{name}' already defined",
+        label = "Duplicate layout definition",
+        fields = { name: String, span: Range<usize> }
+    },
+    InvalidLayoutField {
+        error = "Invalid layout field '{field}'",
+        label = "Expected `name: type` or `name: type @ offset`",
+        fields = { field: String, span: Range<usize> }
+    },
+    UnclosedInstruction {
+        error = "Instruction '{name}' missing .endinstruction",
+        label = "Unclosed instruction block",
+        fields = { name: String, span: Range<usize> }
+    },
+    InvalidInstructionBlock {
+        error = "Invalid instruction declaration '{text}'",
+        label = "Expected `.instruction name, discriminator`",
+        fields = { text: String, span: Range<usize> }
+    },
+    DuplicateDiscriminator {
+        error = "Instruction '{name}' reuses discriminator {discriminator}",
+        label = "Discriminator already taken",
+        fields = { name: String, discriminator: u8, span: Range<usize> }
+    },
+    InvalidErrorCode {
+        error = "Error code {code} is out of range",
+        label = "Expected a code from 1 to 4294967295",
+        fields = { code: i64, span: Range<usize> }
+    },
+    DuplicateErrorCode {
+        error = "Error code {code} is declared twice",
+        label = "Code already declared",
+        fields = { code: u32, span: Range<usize> }
+    },
+    UnclosedConditional {
+        error = "Missing .endif for {directive}",
+        label = "Unclosed conditional block",
+        fields = { directive: String, span: Range<usize> }
+    },
+    UnmatchedConditional {
+        error = "{directive} without a matching .if",
+        label = "Unmatched conditional directive",
+        fields = { directive: String, span: Range<usize> }
+    },
+    InvalidCondition {
+        error = "Cannot evaluate condition '{condition}'",
+        label = "Conditions may use numbers, .equ constants and --define values",
+        fields = { condition: String, span: Range<usize> }
+    },
+    // Bugs in the assembler itself, raised with `bug!`
+    InternalError {
+        error = "Internal error: {message}",
+        label = "Thanks for abusing the compiler <3 you've hunted a bug! Please report it at https://github.com/blueshift-gg/sbpf/issues/new",
+        fields = { message: String, span: Range<usize> }
+    },
+}
+
+/// How serious a diagnostic is. Errors stop the build; warnings and notes
+/// are reported and the build goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl CompileError {
+    /// Severity of the diagnostic. Warnings are collected alongside a
+    /// successful build rather than failing it.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::ByteSwappedData { .. }
+            | Self::UnreachableCode { .. }
+            | Self::UnusedLabel { .. }
+            | Self::UnusedConstant { .. }
+            | Self::ShadowedConstant { .. }
+            | Self::WritableData { .. }
+            | Self::StackLimitExceeded { .. }
+            | Self::ComputeLimitExceeded { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.severity() == Severity::Warning
+    }
+
+    /// Stable identifier of the diagnostic for tools, the variant name in
+    /// kebab case: `undefined-label`, `invalid-ro-data-directive`.
+    pub fn code(&self) -> String {
+        let name: Vec<char> = self.name().chars().collect();
+        let mut code = String::new();
+        for (i, &ch) in name.iter().enumerate() {
+            let starts_word = i > 0
+                && ch.is_ascii_uppercase()
+                && (!name[i - 1].is_ascii_uppercase()
+                    || name.get(i + 1).is_some_and(char::is_ascii_lowercase));
+            if starts_word {
+                code.push('-');
+            }
+            code.push(ch.to_ascii_lowercase());
+        }
+        code
+    }
+
+    /// A fix for the diagnostic, where there is an obvious one.
+    pub fn suggestion(&self) -> Option<String> {
+        let suggestion = match self {
+            Self::ByteSwappedData { directive, .. } => {
+                let width = match directive.as_str() {
+                    "short" | "word" => 16,
+                    "int" | "long" => 32,
+                    _ => 64,
+                };
+                format!("replace .{} with .be{}", directive, width)
+            }
+            Self::UnterminatedBlockComment { .. } => "close the comment with */".to_string(),
+            Self::InitializedBss { .. } => "move initialized data to .data".to_string(),
+            Self::WritableData { .. } => {
+                "move constants to .rodata and keep state in accounts".to_string()
+            }
+            Self::UndefinedEntrypoint { label, .. } => format!("define '{}:' in .text", label),
+            Self::SymbolAddressOutOfRange { symbol, .. } => {
+                format!("load the address of '{}' with lddw", symbol)
+            }
+            Self::MissingTextDirective { .. } => "add .text before the code".to_string(),
+            Self::UnclosedMacro { .. } => "add .endm".to_string(),
+            Self::UnclosedRept { .. } => "add .endr".to_string(),
+            Self::UnclosedLayout { .. } => "add .endlayout".to_string(),
+            Self::UnclosedInstruction { .. } => "add .endinstruction".to_string(),
+            Self::UnclosedConditional { .. } => "add .endif".to_string(),
+            Self::UnmatchedConditional { directive, .. } => {
+                format!("remove {} or add the .if it belongs to", directive)
+            }
+            _ => return None,
+        };
+        Some(suggestion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_and_suggestion() {
+        let error = CompileError::UnreachableCode {
+            span: 0..1,
+            custom_label: None,
+        };
+        assert_eq!(error.code(), "unreachable-code");
+        assert_eq!(error.suggestion(), None);
+
+        let error = CompileError::InvalidRODataDirective {
+            span: 0..1,
+            custom_label: None,
+        };
+        assert_eq!(error.code(), "invalid-ro-data-directive");
+
+        let error = CompileError::ByteSwappedData {
+            label: "port".to_string(),
+            directive: "int".to_string(),
+            span: 0..1,
+            custom_label: None,
+        };
+        assert_eq!(error.code(), "byte-swapped-data");
+        assert_eq!(
+            error.suggestion().as_deref(),
+            Some("replace .int with .be32")
+        );
+    }
 }
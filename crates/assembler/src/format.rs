@@ -0,0 +1,444 @@
+//! The canonical layout of assembly sources, as applied by `sbpf fmt`.
+//!
+//! Formatting works on the [`lexer`](crate::lexer) token stream, so it
+//! needs no valid program and never drops a comment:
+//!
+//! - labels, sections, symbol and constant declarations, and preprocessor
+//!   blocks start at column 0; everything else is indented by two spaces
+//! - a statement written after a label moves to its own line
+//! - operands of consecutive statements start in the same column
+//! - operands are separated by `, `, and `+` or `-` in `[...]` and `(...)`
+//!   take a space on each side
+//! - trailing comments of consecutive lines are aligned, and comment-only
+//!   lines take the indentation of the code they precede
+//! - runs of blank lines become one, trailing whitespace is removed and the
+//!   file ends with a single newline
+//!
+//! A line holding a comment or string that spans lines is kept as written,
+//! and so are the fields of `.layout` and `.instruction` blocks.
+
+use crate::{
+    errors::CompileError,
+    lexer::{Token, TokenKind, tokenize},
+};
+
+const INDENT: &str = "  ";
+
+/// Directives written at column 0.
+const TOP_LEVEL_DIRECTIVES: &[&str] = &[
+    ".text",
+    ".data",
+    ".rodata",
+    ".bss",
+    ".globl",
+    ".global",
+    ".extern",
+    ".equ",
+    ".error",
+    ".file",
+    ".include",
+    ".macro",
+    ".endm",
+    ".if",
+    ".ifdef",
+    ".ifndef",
+    ".elseif",
+    ".else",
+    ".endif",
+    ".layout",
+    ".endlayout",
+    ".instruction",
+    ".endinstruction",
+];
+
+#[derive(Debug)]
+enum Line {
+    Blank,
+    /// Kept as written, apart from trailing whitespace.
+    Verbatim(String),
+    Comment(String),
+    Code(Code),
+}
+
+#[derive(Debug)]
+struct Code {
+    indented: bool,
+    /// The mnemonic or directive, padded so the operands line up, or the
+    /// whole statement if it isn't written that way.
+    head: String,
+    operands: Option<String>,
+    comment: Option<String>,
+}
+
+impl Code {
+    /// Whether `head` is padded to line up the operands.
+    fn aligns(&self) -> bool {
+        self.indented && self.operands.is_some()
+    }
+}
+
+/// Format `source` in the canonical layout. Formatting formatted source
+/// leaves it unchanged.
+pub fn format_source(source: &str) -> Result<String, CompileError> {
+    // A byte order mark is kept in front of whatever the first line becomes.
+    if let Some(source) = source.strip_prefix('\u{feff}') {
+        return format_source(source).map(|formatted| format!("\u{feff}{}", formatted));
+    }
+    let tokens = tokenize(source)?;
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let mut lines = Vec::new();
+    let mut in_layout = false;
+    for line in tokens.split(|token| token.kind == TokenKind::Newline) {
+        classify(line, source, &mut in_layout, &mut lines);
+    }
+
+    let lines = collapse_blank_lines(lines);
+    let mut output = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        output.push(match line {
+            Line::Blank => String::new(),
+            Line::Verbatim(text) => text.clone(),
+            Line::Comment(text) => {
+                let indent = if comment_indented(&lines, index) {
+                    INDENT
+                } else {
+                    ""
+                };
+                format!("{}{}", indent, text)
+            }
+            Line::Code(code) => render_code(code, operand_column(&lines, index)),
+        });
+    }
+    align_comments(&lines, &mut output);
+
+    let mut formatted = output.join(newline);
+    if !formatted.is_empty() {
+        formatted.push_str(newline);
+    }
+    Ok(formatted)
+}
+
+/// Turn the tokens of one source line into the lines it formats to.
+fn classify(tokens: &[Token<'_>], source: &str, in_layout: &mut bool, lines: &mut Vec<Line>) {
+    let tokens = trim_whitespace(tokens);
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+        lines.push(Line::Blank);
+        return;
+    };
+    let text = &source[first.span.start..last.span.end];
+    if tokens.iter().any(|token| token.text.contains('\n')) {
+        lines.push(Line::Verbatim(trailing_trimmed(
+            &source[line_start(source, first.span.start)..last.span.end],
+        )));
+        return;
+    }
+
+    let significant: Vec<_> = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .collect();
+    let Some(head) = significant.first() else {
+        lines.push(Line::Comment(trailing_trimmed(text)));
+        return;
+    };
+
+    if *in_layout && !matches!(head.text, ".endlayout" | ".endinstruction") {
+        lines.push(Line::Code(Code {
+            indented: true,
+            head: trailing_trimmed(text),
+            operands: None,
+            comment: None,
+        }));
+        return;
+    }
+    *in_layout = matches!(head.text, ".layout" | ".instruction");
+
+    // A trailing comment stays on the line it ends.
+    let (tokens, comment) = match last.kind {
+        TokenKind::LineComment | TokenKind::BlockComment => (
+            trim_whitespace(&tokens[..tokens.len() - 1]),
+            Some(trailing_trimmed(last.text)),
+        ),
+        _ => (tokens, None),
+    };
+
+    // A label is `name:`, possibly followed by a statement.
+    let mut rest = tokens;
+    let colon = match tokens {
+        [_, colon, ..] if colon.text == ":" => Some(1),
+        [_, space, colon, ..] if space.kind == TokenKind::Whitespace && colon.text == ":" => {
+            Some(2)
+        }
+        _ => None,
+    };
+    if let Some(colon) = colon
+        && first.kind == TokenKind::Word
+    {
+        let name = first;
+        rest = trim_whitespace(&tokens[colon + 1..]);
+        lines.push(Line::Code(Code {
+            indented: false,
+            head: format!("{}:", name.text),
+            operands: None,
+            comment: if rest.is_empty() {
+                comment.clone()
+            } else {
+                None
+            },
+        }));
+        if rest.is_empty() {
+            return;
+        }
+    }
+
+    lines.push(Line::Code(statement(rest, comment)));
+}
+
+/// Lay out a statement that isn't a label.
+fn statement(tokens: &[Token<'_>], comment: Option<String>) -> Code {
+    let words = spaced_tokens(tokens);
+    let (first, _) = words[0];
+    let indented = !TOP_LEVEL_DIRECTIVES.contains(&first.text);
+    // LLVM-dialect statements such as `r1 += 2` or `if r1 > 0 goto end`
+    // don't start with a mnemonic.
+    let has_mnemonic = first.kind == TokenKind::Word
+        && !is_register(first.text)
+        && !matches!(first.text, "if" | "goto")
+        && words
+            .get(1)
+            .is_some_and(|(second, spaced)| *spaced && second.text != ",");
+    if has_mnemonic {
+        Code {
+            indented,
+            head: first.text.to_string(),
+            operands: Some(join(&words[1..])),
+            comment,
+        }
+    } else {
+        Code {
+            indented,
+            head: join(&words),
+            operands: None,
+            comment,
+        }
+    }
+}
+
+/// The significant tokens of a statement, each with whether whitespace
+/// came before it.
+fn spaced_tokens<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<(&'b Token<'a>, bool)> {
+    let mut words = Vec::new();
+    let mut spaced = false;
+    for token in tokens {
+        if token.kind == TokenKind::Whitespace {
+            spaced = true;
+        } else {
+            words.push((token, spaced));
+            spaced = false;
+        }
+    }
+    words
+}
+
+/// Write tokens back with the canonical spacing, keeping whether other
+/// tokens were separated.
+fn join(words: &[(&Token<'_>, bool)]) -> String {
+    let mut text = String::new();
+    // Bracket depth, and whether the last `+` or `-` was a binary operator.
+    let mut depth = 0usize;
+    let mut binary = false;
+    let mut previous: Option<&Token<'_>> = None;
+    for &(token, spaced) in words {
+        let sign = depth > 0 && matches!(token.text, "+" | "-");
+        let space = match previous {
+            None => false,
+            _ if matches!(token.text, "," | ")" | "]") => false,
+            Some(previous) if previous.text == "," => true,
+            Some(previous) if matches!(previous.text, "(" | "[") => false,
+            Some(previous) if sign => is_operand(previous),
+            Some(previous) if depth > 0 && matches!(previous.text, "+" | "-") => binary,
+            _ => spaced,
+        };
+        if sign {
+            binary = previous.is_some_and(is_operand);
+        }
+        match token.text {
+            "(" | "[" => depth += 1,
+            ")" | "]" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if space {
+            text.push(' ');
+        }
+        text.push_str(token.text);
+        previous = Some(token);
+    }
+    text
+}
+
+/// Whether a `+` or `-` after `token` is a binary operator.
+fn is_operand(token: &Token<'_>) -> bool {
+    matches!(token.kind, TokenKind::Word | TokenKind::String) || matches!(token.text, ")" | "]")
+}
+
+fn is_register(word: &str) -> bool {
+    word.strip_prefix(['r', 'w'])
+        .and_then(|number| number.parse::<u8>().ok())
+        .is_some_and(|number| number <= 10)
+}
+
+fn trim_whitespace<'a, 'b>(tokens: &'b [Token<'a>]) -> &'b [Token<'a>] {
+    let start = tokens
+        .iter()
+        .position(|token| token.kind != TokenKind::Whitespace)
+        .unwrap_or(tokens.len());
+    let end = tokens
+        .iter()
+        .rposition(|token| token.kind != TokenKind::Whitespace)
+        .map_or(start, |index| index + 1);
+    &tokens[start..end]
+}
+
+fn trailing_trimmed(text: &str) -> String {
+    text.trim_end().to_string()
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |index| index + 1)
+}
+
+/// Drop blank lines at the start and end and merge runs of them.
+fn collapse_blank_lines(lines: Vec<Line>) -> Vec<Line> {
+    let mut collapsed: Vec<Line> = Vec::with_capacity(lines.len());
+    for line in lines {
+        if matches!(line, Line::Blank) && matches!(collapsed.last(), None | Some(Line::Blank)) {
+            continue;
+        }
+        collapsed.push(line);
+    }
+    if matches!(collapsed.last(), Some(Line::Blank)) {
+        collapsed.pop();
+    }
+    collapsed
+}
+
+/// A comment-only line is indented like the code after it, or like the
+/// code before it if none follows.
+fn comment_indented(lines: &[Line], index: usize) -> bool {
+    let code_indented = |line: &Line| match line {
+        Line::Code(code) => Some(code.indented),
+        _ => None,
+    };
+    lines[index + 1..]
+        .iter()
+        .find_map(code_indented)
+        .or_else(|| lines[..index].iter().rev().find_map(code_indented))
+        .unwrap_or(false)
+}
+
+/// The width the mnemonics of the run of indented statements around
+/// `index` are padded to. Comment-only lines don't end a run.
+fn operand_column(lines: &[Line], index: usize) -> usize {
+    let in_run = |line: &&Line| match line {
+        Line::Code(code) => code.indented,
+        Line::Comment(_) => true,
+        _ => false,
+    };
+    let start = lines[..index]
+        .iter()
+        .rposition(|line| !in_run(&line))
+        .map_or(0, |index| index + 1);
+    let end = lines[index..]
+        .iter()
+        .position(|line| !in_run(&line))
+        .map_or(lines.len(), |offset| index + offset);
+    lines[start..end]
+        .iter()
+        .filter_map(|line| match line {
+            Line::Code(code) if code.aligns() => Some(code.head.chars().count()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn render_code(code: &Code, column: usize) -> String {
+    let indent = if code.indented { INDENT } else { "" };
+    match &code.operands {
+        Some(operands) if code.aligns() => {
+            format!("{}{:<column$} {}", indent, code.head, operands)
+        }
+        Some(operands) => format!("{}{} {}", indent, code.head, operands),
+        None => format!("{}{}", indent, code.head),
+    }
+}
+
+/// Append trailing comments, lined up across consecutive lines that have
+/// one.
+fn align_comments(lines: &[Line], output: &mut [String]) {
+    let comment = |index: usize| match &lines[index] {
+        Line::Code(code) => code.comment.as_deref(),
+        _ => None,
+    };
+    let mut start = 0;
+    while start < lines.len() {
+        if comment(start).is_none() {
+            start += 1;
+            continue;
+        }
+        let end = (start..lines.len())
+            .find(|&index| comment(index).is_none())
+            .unwrap_or(lines.len());
+        let column = output[start..end]
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (index, line) in output.iter_mut().enumerate().take(end).skip(start) {
+            let padding = column - line.chars().count() + 1;
+            line.push_str(&" ".repeat(padding));
+            line.push_str(comment(index).unwrap_or_default());
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_layout() {
+        let source = "\n\n  .globl entrypoint\n   ; entry\nentrypoint: ldxdw r1,[r1+8]   ; len\n\tjne r1 , 0,error\n    lddw r2, [ r1 -8 ]; off\n\n\n  exit   \n# errors\nerror:\n  mov64 r0,1\n  exit\n";
+        let expected = ".globl entrypoint\n; entry\nentrypoint:\n  ldxdw r1, [r1 + 8] ; len\n  jne   r1, 0, error\n  lddw  r2, [r1 - 8] ; off\n\n  exit\n# errors\nerror:\n  mov64 r0, 1\n  exit\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_llvm_dialect() {
+        let source =
+            "entry:\n  r1 = *(u64 *)( r2+0x8 )\n  if r1 s> -1 goto entry\n  r0 = -r1\n  exit\n";
+        let expected =
+            "entry:\n  r1 = *(u64 *)(r2 + 0x8)\n  if r1 s> -1 goto entry\n  r0 = -r1\n  exit\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_preprocessor_syntax() {
+        let source = ".layout counter\n    bump: u8\n  data: [u8; 32]\n.endlayout\n    .macro PUSH reg offset\n  stxdw [r10-\\offset], \\reg\n  .endm\n/* spans\n   lines */ exit  \n";
+        let expected = ".layout counter\n  bump: u8\n  data: [u8; 32]\n.endlayout\n.macro PUSH reg offset\n  stxdw [r10 - \\offset], \\reg\n.endm\n/* spans\n   lines */ exit\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+
+        let crlf = "entry:\r\n  exit\r\n";
+        assert_eq!(format_source(crlf).unwrap(), crlf);
+        let bom = "\u{feff}.globl entrypoint\n";
+        assert_eq!(format_source(bom).unwrap(), bom);
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+}
@@ -0,0 +1,257 @@
+#[cfg(feature = "dwarf")]
+use crate::preprocessor::source_map::{FileId, SourceMap};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::Arc,
+};
+
+/// Translates another source language into sBPF assembly ahead of
+/// preprocessing, so that programs written in it build like any other: the
+/// assembly is preprocessed, parsed and emitted as usual, diagnostics point
+/// into the original source and, with debug info, so does the DWARF line
+/// table.
+///
+/// Front-ends are registered with [`crate::AssemblerOption::with_frontend`]
+/// and chosen by the extension of the source path.
+pub trait Frontend: Send + Sync {
+    /// Name shown in diagnostics
+    fn name(&self) -> &str;
+
+    /// Extension, without the dot, of the files this front-end reads
+    fn extension(&self) -> &str;
+
+    /// Translate `source`, read from `path`, into assembly. `.include`
+    /// directives in the output resolve relative to `path`.
+    fn translate(&self, source: &str, path: &str) -> Result<Translation, Vec<FrontendError>>;
+}
+
+/// Assembly produced by a [`Frontend`], with the source line each line of it
+/// was generated from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Translation {
+    assembly: String,
+    /// 1-based source line for each line of `assembly`, or 0 if it has none
+    lines: Vec<u32>,
+}
+
+impl Translation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assembly with no line mapping. Diagnostics point into the assembly
+    /// itself, shown as `<path>.s`.
+    pub fn from_assembly(assembly: impl Into<String>) -> Self {
+        let assembly = assembly.into();
+        let lines = vec![0; assembly.lines().count()];
+        Self { assembly, lines }
+    }
+
+    /// Append a line of assembly generated from 1-based `source_line`, or
+    /// from no particular line if it is 0.
+    pub fn push_line(&mut self, line: &str, source_line: u32) {
+        self.assembly.push_str(line);
+        self.assembly.push('\n');
+        self.lines.push(source_line);
+    }
+
+    pub fn assembly(&self) -> &str {
+        &self.assembly
+    }
+
+    pub fn lines(&self) -> &[u32] {
+        &self.lines
+    }
+
+    /// Whether any line of the assembly maps back to the source.
+    pub fn has_line_mapping(&self) -> bool {
+        self.lines.iter().any(|&line| line != 0)
+    }
+}
+
+/// An error in a front-end's source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontendError {
+    pub message: String,
+    /// 1-based line of the source, if known
+    pub line: Option<u32>,
+    /// 0-based column within the line, if known
+    pub column: Option<usize>,
+}
+
+impl FrontendError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            line: None,
+            column: None,
+        }
+    }
+
+    pub fn with_line(mut self, line: u32) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = Some(column);
+        self
+    }
+}
+
+/// The front-ends an assembler accepts sources for, by extension. Cloning is
+/// cheap, so one registry can back any number of assemblers and threads.
+#[derive(Clone, Default)]
+pub struct FrontendRegistry {
+    frontends: Vec<Arc<dyn Frontend>>,
+}
+
+impl FrontendRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `frontend`, replacing any earlier one for the same extension.
+    pub fn register(&mut self, frontend: impl Frontend + 'static) {
+        self.frontends
+            .retain(|existing| existing.extension() != frontend.extension());
+        self.frontends.push(Arc::new(frontend));
+    }
+
+    /// The front-end for `path`, if its extension has one.
+    pub fn for_path(&self, path: &str) -> Option<&dyn Frontend> {
+        let extension = Path::new(path).extension()?;
+        self.frontends
+            .iter()
+            .find(|frontend| extension == frontend.extension())
+            .map(|frontend| frontend.as_ref())
+    }
+
+    /// Extensions with a registered front-end
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.frontends.iter().map(|frontend| frontend.extension())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frontends.is_empty()
+    }
+}
+
+/// Front-ends are told apart by extension and name, as in their `Debug` form.
+impl Hash for FrontendRegistry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for frontend in &self.frontends {
+            frontend.extension().hash(state);
+            frontend.name().hash(state);
+        }
+    }
+}
+
+impl fmt::Debug for FrontendRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.frontends
+                    .iter()
+                    .map(|frontend| (frontend.extension(), frontend.name())),
+            )
+            .finish()
+    }
+}
+
+/// Path the assembly generated from `path` is registered under.
+pub(crate) fn generated_path(path: &str) -> String {
+    format!("{}.s", path)
+}
+
+/// Precede each run of expanded lines from `file` with a `.loc` for the
+/// source line they came from, so the DWARF line table points into the
+/// front-end's source rather than the generated assembly. Assembly that
+/// carries its own `.file` or `.loc` directives is left as it is.
+#[cfg(feature = "dwarf")]
+pub(crate) fn annotate_lines(
+    expanded: String,
+    source_map: SourceMap,
+    file: FileId,
+) -> (String, SourceMap) {
+    let has_directives = expanded.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with(".file") || line.starts_with(".loc")
+    });
+    let Some(first) = source_map.origins().first() else {
+        return (expanded, source_map);
+    };
+    if has_directives {
+        return (expanded, source_map);
+    }
+
+    let name = Path::new(source_map.file_registry.path(file))
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let mut annotated = format!(".file 1 \"{}\"\n", name);
+    let mut origins = vec![first.clone()];
+    let mut last = None;
+    for (line, origin) in expanded.lines().zip(source_map.origins()) {
+        if origin.file_id == file && last != Some(origin.line) {
+            annotated.push_str(&format!(".loc 1 {}\n", origin.line));
+            origins.push(origin.clone());
+            last = Some(origin.line);
+        }
+        annotated.push_str(line);
+        annotated.push('\n');
+        origins.push(origin.clone());
+    }
+    (
+        annotated,
+        SourceMap::new(source_map.file_registry.clone(), origins),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upper;
+
+    impl Frontend for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        fn extension(&self) -> &str {
+            "up"
+        }
+
+        fn translate(&self, source: &str, _path: &str) -> Result<Translation, Vec<FrontendError>> {
+            Ok(Translation::from_assembly(source.to_lowercase()))
+        }
+    }
+
+    #[test]
+    fn test_translation_lines() {
+        let mut translation = Translation::new();
+        translation.push_line(".globl e", 0);
+        translation.push_line("e:", 1);
+        translation.push_line("  exit", 1);
+        assert_eq!(translation.assembly(), ".globl e\ne:\n  exit\n");
+        assert_eq!(translation.lines(), &[0, 1, 1]);
+        assert!(translation.has_line_mapping());
+        assert!(!Translation::from_assembly("exit\nexit").has_line_mapping());
+        assert_eq!(Translation::from_assembly("exit\nexit").lines().len(), 2);
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let mut registry = FrontendRegistry::new();
+        assert!(registry.for_path("src/a/a.up").is_none());
+        registry.register(Upper);
+        registry.register(Upper);
+        assert_eq!(registry.extensions().collect::<Vec<_>>(), ["up"]);
+        assert_eq!(registry.for_path("src/a/a.up").unwrap().name(), "upper");
+        assert!(registry.for_path("src/a/a.s").is_none());
+        assert!(registry.for_path("src/a/up").is_none());
+        assert_eq!(format!("{:?}", registry), r#"{"up": "upper"}"#);
+    }
+}
@@ -63,6 +63,7 @@ impl ElfHeader {
     ];
     const SOLANA_TYPE: u16 = 3; // ET_DYN
     const SOLANA_MACHINE: u16 = 247; // BPF
+    const SBPF_MACHINE: u16 = 263; // SBPF, which v1 and v2 programs record
     const SOLANA_VERSION: u32 = 1; // EV_CURRENT
     const ELF64_HEADER_SIZE: u16 = 64;
     const PROGRAM_HEADER_SIZE: u16 = 56;
@@ -72,6 +73,15 @@ impl ElfHeader {
         Self::default()
     }
 
+    /// Set the machine and flags the loader checks to pick `arch`.
+    pub fn set_arch(&mut self, arch: SbpfArch) {
+        self.e_machine = match arch {
+            SbpfArch::V1 | SbpfArch::V2 => Self::SBPF_MACHINE,
+            SbpfArch::V0 | SbpfArch::V3 => Self::SOLANA_MACHINE,
+        };
+        self.e_flags = arch.e_flags();
+    }
+
     pub fn bytecode(&self) -> Vec<u8> {
         let mut bytecode = Vec::with_capacity(Self::ELF64_HEADER_SIZE as usize);
 
@@ -113,11 +123,11 @@ impl ProgramHeader {
     pub const V3_BYTECODE_VADDR: u64 = 1 << 32;
 
     pub fn new_load(offset: u64, size: u64, executable: bool, arch: SbpfArch) -> Self {
-        let (flags, vaddr, align) = match (arch, executable) {
-            (SbpfArch::V0, true) => (Self::PF_R | Self::PF_X, offset, Self::PAGE_SIZE),
-            (SbpfArch::V0, false) => (Self::PF_R, offset, Self::PAGE_SIZE),
-            (SbpfArch::V3, true) => (Self::PF_X, Self::V3_BYTECODE_VADDR, 0),
-            (SbpfArch::V3, false) => (Self::PF_R, Self::V3_RODATA_VADDR, 0),
+        let (flags, vaddr, align) = match (arch.is_v3(), executable) {
+            (false, true) => (Self::PF_R | Self::PF_X, offset, Self::PAGE_SIZE),
+            (false, false) => (Self::PF_R, offset, Self::PAGE_SIZE),
+            (true, true) => (Self::PF_X, Self::V3_BYTECODE_VADDR, 0),
+            (true, false) => (Self::PF_R, Self::V3_RODATA_VADDR, 0),
         };
 
         ProgramHeader {
@@ -132,6 +142,29 @@ impl ProgramHeader {
         }
     }
 
+    /// A writable v3 segment loaded at `vaddr`, for `.data` and `.bss`. The
+    /// Solana loader only takes the first two segments, so only `sbpf-vm`
+    /// maps it.
+    pub fn new_writable_load(offset: u64, size: u64, vaddr: u64) -> Self {
+        ProgramHeader {
+            p_type: Self::PT_LOAD,
+            p_flags: Self::PF_R | Self::PF_W,
+            p_offset: offset,
+            p_vaddr: vaddr,
+            p_paddr: vaddr,
+            p_filesz: size,
+            p_memsz: size,
+            p_align: 0 // p_align is ignored in v3
+        }
+    }
+
+    /// Reserve `mem_size` bytes for the segment, zero-filling past its file
+    /// contents, for `.bss`.
+    pub fn with_mem_size(mut self, mem_size: u64) -> Self {
+        self.p_memsz = mem_size;
+        self
+    }
+
     pub fn new_dynamic(offset: u64, size: u64) -> Self {
         ProgramHeader {
             p_type: Self::PT_DYNAMIC,
@@ -179,9 +212,11 @@ impl SectionHeader {
     // Section types
     pub const SHT_NULL: u32 = 0;          // Section header table entry unused
     pub const SHT_PROGBITS: u32 = 1;      // Program data
+    pub const SHT_SYMTAB: u32 = 2;        // Symbol table
     pub const SHT_STRTAB: u32 = 3;        // String table
     pub const SHT_NOBITS: u32 = 8;        // Program space with no data (bss)
     pub const SHT_DYNAMIC: u32 = 6;       // Dynamic section
+    pub const SHT_NOTE: u32 = 7;          // Notes
     pub const SHT_DYNSYM: u32 = 11;       // Dynamic symbol table
     pub const SHT_REL: u32 = 9;           // Relocation table
     
@@ -0,0 +1,255 @@
+use {
+    crate::{
+        AssembleErrors, SymbolSection, map_parse_errors, original_column,
+        parser::{Rule, Section, parse_pairs, section_from_directive},
+        preprocessor::source_map::SourceMap,
+    },
+    pest::iterators::Pair,
+    std::ops::Range,
+};
+
+/// What a defined name stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    /// A label, in the section it was defined in.
+    Label(SymbolSection),
+    /// An `.equ` constant.
+    Constant,
+}
+
+/// Where a name is written in the original files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolLocation {
+    pub file: String,
+    /// 1-based line number
+    pub line: u32,
+    /// 0-based byte offset of the name within the line
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolDefinition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub location: SymbolLocation,
+    /// The expression of an `.equ`, as written.
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolReference {
+    pub name: String,
+    pub location: SymbolLocation,
+}
+
+/// The labels and constants a program defines and the places it names them,
+/// read from the grammar alone so that it is available while the program
+/// still has errors past parsing, such as undefined names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolIndex {
+    pub definitions: Vec<SymbolDefinition>,
+    pub references: Vec<SymbolReference>,
+}
+
+impl SymbolIndex {
+    /// Index preprocessed source, locating names through `source_map`.
+    /// Names that come from `--define` or whose column is lost in a macro
+    /// expansion are left out.
+    pub(crate) fn build(expanded: &str, source_map: &SourceMap) -> Result<Self, AssembleErrors> {
+        let pairs = parse_pairs(expanded)
+            .map_err(|errors| map_parse_errors(errors, expanded, source_map))?;
+        let locate = |span: Range<usize>| {
+            let origin = source_map.resolve_span(&span, expanded);
+            if origin.line == 0 {
+                return None;
+            }
+            let registry = &source_map.file_registry;
+            Some(SymbolLocation {
+                file: registry.path(origin.file_id).to_string(),
+                line: origin.line,
+                column: original_column(&span, expanded, origin, registry)?,
+            })
+        };
+
+        let mut index = Self::default();
+        let mut section = Section::Text;
+        for pair in pairs.flatten() {
+            match pair.as_rule() {
+                Rule::directive_section => {
+                    section = section_from_directive(pair.as_str()).unwrap_or(section);
+                }
+                Rule::label_default | Rule::label_llvm => {
+                    let Some(name) = first_identifier(&pair) else {
+                        continue;
+                    };
+                    if let Some(location) = locate(span(&name)) {
+                        index.definitions.push(SymbolDefinition {
+                            name: name.as_str().to_string(),
+                            kind: DefinitionKind::Label(symbol_section(section)),
+                            location,
+                            value: None,
+                        });
+                    }
+                }
+                Rule::directive_equ => {
+                    let Some(name) = first_identifier(&pair) else {
+                        continue;
+                    };
+                    let value = pair
+                        .clone()
+                        .into_inner()
+                        .find(|inner| inner.as_rule() == Rule::expression)
+                        .map(|expression| expression.as_str().trim().to_string());
+                    if let Some(location) = locate(span(&name)) {
+                        index.definitions.push(SymbolDefinition {
+                            name: name.as_str().to_string(),
+                            kind: DefinitionKind::Constant,
+                            location,
+                            value,
+                        });
+                    }
+                }
+                Rule::symbol | Rule::globl_symbol => {
+                    if let Some(location) = locate(span(&pair)) {
+                        index.references.push(SymbolReference {
+                            name: pair.as_str().to_string(),
+                            location,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(index)
+    }
+
+    /// Where `name` is defined. A redefined constant resolves to its first
+    /// definition.
+    pub fn definition(&self, name: &str) -> Option<&SymbolDefinition> {
+        self.definitions.iter().find(|d| d.name == name)
+    }
+
+    /// Every place `name` is referenced.
+    pub fn references_to<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a SymbolReference> + 'a {
+        self.references.iter().filter(move |r| r.name == name)
+    }
+
+    /// Definitions written in `file`, in source order.
+    pub fn definitions_in<'a>(
+        &'a self,
+        file: &'a str,
+    ) -> impl Iterator<Item = &'a SymbolDefinition> + 'a {
+        self.definitions
+            .iter()
+            .filter(move |d| d.location.file == file)
+    }
+}
+
+fn first_identifier<'a>(pair: &Pair<'a, Rule>) -> Option<Pair<'a, Rule>> {
+    pair.clone()
+        .into_inner()
+        .next()
+        .filter(|inner| inner.as_rule() == Rule::identifier)
+}
+
+fn span(pair: &Pair<Rule>) -> Range<usize> {
+    let span = pair.as_span();
+    span.start()..span.end()
+}
+
+fn symbol_section(section: Section) -> SymbolSection {
+    match section {
+        Section::Text => SymbolSection::Text,
+        Section::Rodata => SymbolSection::Rodata,
+        Section::Data => SymbolSection::Data,
+        Section::Bss => SymbolSection::Bss,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Assembler, AssemblerOption, DefinitionKind, MockFileResolver, SymbolSection};
+
+    #[test]
+    fn test_index_definitions_and_references() {
+        let mut resolver = MockFileResolver::new();
+        resolver.add_file("consts.inc", ".equ LEN, 12\n");
+        let source = ".include \"consts.inc\"\n.globl entrypoint\nentrypoint:\n  lddw r1, message\n  mov64 r2, LEN\n  call sol_log_\n  exit\n.rodata\nmessage: .ascii \"Hello world!\"\n";
+        let index = Assembler::new(AssemblerOption::default())
+            .index_with_preprocess(source, "main.s", Some(&resolver))
+            .unwrap();
+
+        let entrypoint = index.definition("entrypoint").unwrap();
+        assert_eq!(entrypoint.kind, DefinitionKind::Label(SymbolSection::Text));
+        assert_eq!(
+            (entrypoint.location.line, entrypoint.location.column),
+            (3, 0)
+        );
+
+        let message = index.definition("message").unwrap();
+        assert_eq!(message.kind, DefinitionKind::Label(SymbolSection::Rodata));
+        assert_eq!(message.location.line, 9);
+
+        let len = index.definition("LEN").unwrap();
+        assert_eq!(len.kind, DefinitionKind::Constant);
+        assert_eq!(len.value.as_deref(), Some("12"));
+        assert!(len.location.file.ends_with("consts.inc"));
+        assert_eq!((len.location.line, len.location.column), (1, 5));
+
+        let uses: Vec<_> = index
+            .references_to("message")
+            .map(|r| (r.location.line, r.location.column))
+            .collect();
+        assert_eq!(uses, [(4, 11)]);
+        assert_eq!(index.references_to("LEN").count(), 1);
+        assert_eq!(index.references_to("entrypoint").count(), 1);
+        assert_eq!(
+            index
+                .definitions_in("main.s")
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            ["entrypoint", "message"]
+        );
+    }
+
+    #[test]
+    fn test_index_tolerates_undefined_names() {
+        let index = Assembler::new(AssemblerOption::default())
+            .index_with_preprocess("entrypoint:\n  ja missing\n", "main.s", None)
+            .unwrap();
+        assert!(index.definition("missing").is_none());
+        assert_eq!(index.references_to("missing").count(), 1);
+    }
+
+    #[test]
+    fn test_assemble_and_index_share_preprocessing() {
+        let assembler = Assembler::new(AssemblerOption::default());
+        // Assembly fails on the undefined label, but indexing doesn't.
+        let (result, index) = assembler.assemble_and_index_with_preprocess(
+            ".globl entrypoint\nentrypoint:\n  ja missing\n",
+            "main.s",
+            None,
+        );
+        assert!(result.is_err());
+        assert_eq!(index.unwrap().references_to("missing").count(), 1);
+
+        let (result, index) = assembler.assemble_and_index_with_preprocess(
+            "entrypoint:\n  mov64 r1,\n",
+            "main.s",
+            None,
+        );
+        assert!(result.is_err());
+        assert!(index.is_none());
+    }
+
+    #[test]
+    fn test_index_reports_parse_errors() {
+        let errors = Assembler::new(AssemblerOption::default())
+            .index_with_preprocess("entrypoint:\n  mov64 r1,\n", "main.s", None)
+            .unwrap_err();
+        assert_eq!(errors.errors[0].origin.as_ref().unwrap().line, 2);
+    }
+}
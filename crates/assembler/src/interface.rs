@@ -0,0 +1,208 @@
+use {
+    crate::{
+        AssembleErrors, map_parse_errors,
+        parser::{Rule, directive::eval_expression, parse_pairs},
+        preprocessor::{
+            PreprocessResult,
+            layout::{Instruction, Layout},
+        },
+    },
+    sbpf_common::program_error::ErrorDefinition,
+    std::collections::HashMap,
+};
+
+/// Prefix of the `.equ` constants read as custom error codes.
+pub const ERROR_CONSTANT_PREFIX: &str = "ERROR_";
+
+/// What a program declares about how it is called, for generating clients
+/// and IDLs: its `.globl` symbols, the `.layout` and `.instruction` blocks,
+/// and its custom errors, read from `.equ ERROR_<NAME>, <code>` constants
+/// and `.error` directives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramInterface {
+    /// `.globl` symbols in source order. The first is the entrypoint.
+    pub globals: Vec<String>,
+    pub layouts: Vec<Layout>,
+    pub instructions: Vec<Instruction>,
+    /// Custom errors by ascending code. `ERROR_INVALID_PDA` is named
+    /// `InvalidPda`.
+    pub errors: Vec<ErrorDefinition>,
+}
+
+impl ProgramInterface {
+    /// Read the interface out of preprocessed source. Constants defined
+    /// inside macros are only seen once expanded, like any other.
+    pub(crate) fn build(preprocessed: PreprocessResult) -> Result<Self, AssembleErrors> {
+        let expanded = &preprocessed.expanded_source;
+        let pairs = parse_pairs(expanded)
+            .map_err(|errors| map_parse_errors(errors, expanded, &preprocessed.source_map))?;
+
+        let mut interface = Self {
+            layouts: preprocessed.layouts,
+            instructions: preprocessed.instructions,
+            ..Default::default()
+        };
+        let mut consts = HashMap::new();
+        for pair in pairs.flatten() {
+            match pair.as_rule() {
+                Rule::globl_symbol => interface.globals.push(pair.as_str().to_string()),
+                Rule::directive_equ => {
+                    let mut inner = pair.into_inner();
+                    let (Some(name), Some(value)) = (inner.next(), inner.next()) else {
+                        continue;
+                    };
+                    if let Some(error) = error_definition(name.as_str(), value.as_str()) {
+                        interface.errors.retain(|other| other.code != error.code);
+                        interface.errors.push(error);
+                    }
+                    if let Ok(value) = eval_expression(value, &consts) {
+                        consts.insert(name.as_str().to_string(), value);
+                    }
+                }
+                Rule::directive_error => {
+                    let mut inner = pair.into_inner();
+                    let (Some(code), Some(message)) = (inner.next(), inner.next()) else {
+                        continue;
+                    };
+                    let code_text = code.as_str().trim().to_string();
+                    let Some(value) = eval_expression(code, &consts)
+                        .ok()
+                        .and_then(|value| u32::try_from(value.to_i64()).ok())
+                    else {
+                        continue;
+                    };
+                    let message = message.as_str();
+                    let message = &message[1..message.len() - 1];
+                    match interface
+                        .errors
+                        .iter_mut()
+                        .find(|error| error.code == value)
+                    {
+                        Some(error) => error.message = Some(message.to_string()),
+                        None => interface
+                            .errors
+                            .push(declared_error(&code_text, value, message)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        interface.errors.sort_by_key(|error| error.code);
+        Ok(interface)
+    }
+}
+
+/// The error an `.equ` declares, if it is named `ERROR_*` and set to a
+/// number that fits a `u32`. Codes computed from other constants are left
+/// out.
+fn error_definition(name: &str, value: &str) -> Option<ErrorDefinition> {
+    let suffix = name.strip_prefix(ERROR_CONSTANT_PREFIX)?;
+    let value = value.trim().replace('_', "");
+    let code = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+    Some(ErrorDefinition {
+        code,
+        name: error_name(suffix),
+        message: None,
+    })
+}
+
+/// The error `.error <code>, "<message>"` declares. A code written as an
+/// `ERROR_*` constant is named after it, `InvalidPda` for
+/// `ERROR_INVALID_PDA`, and any other `Error<code>`.
+pub(crate) fn declared_error(code_text: &str, code: u32, message: &str) -> ErrorDefinition {
+    let name = match code_text.strip_prefix(ERROR_CONSTANT_PREFIX) {
+        Some(suffix)
+            if code_text
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_') =>
+        {
+            error_name(suffix)
+        }
+        _ => format!("Error{}", code),
+    };
+    ErrorDefinition {
+        code,
+        name,
+        message: Some(message.to_string()),
+    }
+}
+
+/// An `ERROR_*` constant's name without the prefix, in PascalCase.
+fn error_name(suffix: &str) -> String {
+    suffix
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+            std::iter::once(first)
+                .chain(chars.map(|c| c.to_ascii_lowercase()))
+                .collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Assembler, AssemblerOption, MockFileResolver};
+
+    #[test]
+    fn test_interface() {
+        let mut resolver = MockFileResolver::new();
+        resolver.add_file(
+            "errors.s",
+            ".equ ERROR_INVALID_PDA, 0xc\n.equ ERROR_OVERFLOW, 13\n.equ ERROR_SUM, 1 + 2\n",
+        );
+        let source = r#".include "errors.s"
+.globl entrypoint
+.equ NOT_AN_ERROR, 1
+.error ERROR_INVALID_PDA, "Vault address does not match its seeds"
+.error 20, "Amount is zero"
+.layout counter
+  count: u64
+.endlayout
+.instruction increment, 1
+  .account authority, signer
+  .account counter, writable
+  amount: u32
+.endinstruction
+entrypoint:
+  exit
+"#;
+        let interface = Assembler::new(AssemblerOption::default())
+            .interface_with_preprocess(source, "main.s", Some(&resolver))
+            .unwrap();
+
+        assert_eq!(interface.globals, ["entrypoint"]);
+        assert_eq!(interface.layouts[0].name, "counter");
+        let increment = &interface.instructions[0];
+        assert_eq!(increment.discriminator, 1);
+        assert_eq!(increment.data.fields[0].offset, 1);
+        assert_eq!(increment.data.size, 5);
+        let accounts: Vec<_> = increment
+            .accounts
+            .iter()
+            .map(|account| (account.name.as_str(), account.signer, account.writable))
+            .collect();
+        assert_eq!(
+            accounts,
+            [("authority", true, false), ("counter", false, true)]
+        );
+        let errors: Vec<_> = interface
+            .errors
+            .iter()
+            .map(|error| (error.code, error.name.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            [(12, "InvalidPda"), (13, "Overflow"), (20, "Error20")]
+        );
+        assert_eq!(
+            interface.errors[0].message.as_deref(),
+            Some("Vault address does not match its seeds")
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! A lossless tokenizer for assembly sources.
+//!
+//! Unlike the grammar, which skips whitespace and comments, [`tokenize`]
+//! keeps them as trivia tokens, so concatenating the text of every token
+//! gives back the source exactly. Tools that rewrite sources, such as the
+//! formatter, work on this stream.
+
+use {crate::errors::CompileError, std::ops::Range};
+
+/// What a [`Token`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces, tabs and carriage returns.
+    Whitespace,
+    Newline,
+    /// A `;`, `#` or `//` comment, up to the end of its line.
+    LineComment,
+    /// A `/* ... */` comment, which may nest and span lines.
+    BlockComment,
+    /// A string literal with its quotes.
+    String,
+    /// A mnemonic, directive, register, name or number, including macro
+    /// parameters such as `\reg`.
+    Word,
+    /// Any other single character.
+    Punct,
+}
+
+impl TokenKind {
+    /// Whether the grammar skips tokens of this kind.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            Self::Whitespace | Self::Newline | Self::LineComment | Self::BlockComment
+        )
+    }
+
+    pub fn is_comment(self) -> bool {
+        matches!(self, Self::LineComment | Self::BlockComment)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Range<usize>,
+}
+
+/// Split `source` into tokens covering every byte of it.
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, CompileError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let (kind, end) = match bytes[start] {
+            b'\n' => (TokenKind::Newline, start + 1),
+            b' ' | b'\t' | b'\r' => (
+                TokenKind::Whitespace,
+                scan(bytes, start, |b| matches!(b, b' ' | b'\t' | b'\r')),
+            ),
+            b';' | b'#' => (TokenKind::LineComment, line_end(bytes, start)),
+            b'/' if bytes.get(start + 1) == Some(&b'/') => {
+                (TokenKind::LineComment, line_end(bytes, start))
+            }
+            b'/' if bytes.get(start + 1) == Some(&b'*') => {
+                (TokenKind::BlockComment, block_comment_end(bytes, start)?)
+            }
+            // Strings have no escapes, as in the grammar. One left open runs
+            // to the end of the source.
+            b'"' => (
+                TokenKind::String,
+                bytes[start + 1..]
+                    .iter()
+                    .position(|&b| b == b'"')
+                    .map_or(bytes.len(), |offset| start + offset + 2),
+            ),
+            _ => {
+                let word_end = source[start..]
+                    .find(|c: char| !is_word_char(c))
+                    .map_or(source.len(), |offset| start + offset);
+                if word_end > start {
+                    (TokenKind::Word, word_end)
+                } else {
+                    let width = source[start..].chars().next().map_or(1, char::len_utf8);
+                    (TokenKind::Punct, start + width)
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &source[start..end],
+            span: start..end,
+        });
+        start = end;
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '\\' | '@' | '$')
+}
+
+fn scan(bytes: &[u8], start: usize, accept: impl Fn(u8) -> bool) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&b| !accept(b))
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+/// End of the line comment at `start`, leaving out the `\r` of a `\r\n`.
+fn line_end(bytes: &[u8], start: usize) -> usize {
+    let end = scan(bytes, start, |b| b != b'\n');
+    if bytes[..end].ends_with(b"\r") && bytes.get(end) == Some(&b'\n') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+fn block_comment_end(bytes: &[u8], start: usize) -> Result<usize, CompileError> {
+    let mut depth = 0usize;
+    let mut i = start;
+    loop {
+        match (bytes.get(i), bytes.get(i + 1)) {
+            (None, _) => {
+                return Err(CompileError::UnterminatedBlockComment {
+                    span: start..start + 2,
+                    custom_label: None,
+                });
+            }
+            (Some(b'/'), Some(b'*')) => {
+                depth += 1;
+                i += 2;
+            }
+            (Some(b'*'), Some(b'/')) => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_cover_the_source() {
+        let source =
+            "entry: /* a /* nested */ one */ ldxdw r1, [r2+8] ; load\r\n.ascii \"; no\"\n# done";
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(
+            tokens.iter().map(|token| token.text).collect::<String>(),
+            source
+        );
+
+        let significant: Vec<_> = tokens
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| (token.kind, token.text))
+            .collect();
+        assert_eq!(
+            significant,
+            [
+                (TokenKind::Word, "entry"),
+                (TokenKind::Punct, ":"),
+                (TokenKind::Word, "ldxdw"),
+                (TokenKind::Word, "r1"),
+                (TokenKind::Punct, ","),
+                (TokenKind::Punct, "["),
+                (TokenKind::Word, "r2"),
+                (TokenKind::Punct, "+"),
+                (TokenKind::Word, "8"),
+                (TokenKind::Punct, "]"),
+                (TokenKind::Word, ".ascii"),
+                (TokenKind::String, "\"; no\""),
+            ]
+        );
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.kind.is_comment())
+            .map(|token| token.text)
+            .collect();
+        assert_eq!(comments, ["/* a /* nested */ one */", "; load", "# done"]);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let error = tokenize("exit\n/* /* */\n").unwrap_err();
+        assert!(matches!(
+            error,
+            CompileError::UnterminatedBlockComment { ref span, .. } if *span == (5..7)
+        ));
+    }
+}
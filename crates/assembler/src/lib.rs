@@ -1,20 +1,33 @@
-use {anyhow::Result, codespan::Files};
-
 // Parser
 pub mod parser;
 
 // Preprocessor (include + macro expansion)
 pub mod preprocessor;
 
+// Front-ends translating other languages to assembly
+pub mod frontend;
+
 // Error handling and diagnostics
 pub mod errors;
 pub mod macros;
 
+// Assembly output
+pub mod artifacts;
+pub mod cache;
+
 // Intermediate Representation
 pub mod ast;
 pub mod astnode;
 pub mod dynsym;
+pub mod format;
+pub mod index;
+pub mod interface;
+pub mod lexer;
+pub mod linker;
+mod lint;
 pub mod optimizer;
+mod relax;
+pub mod syscalls;
 
 // ELF header, program, section
 pub mod header;
@@ -25,45 +38,54 @@ pub mod section;
 pub mod debug;
 
 // WASM bindings
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
 pub mod wasm;
 
 pub use self::{
-    ast::OptimizationConfig,
+    artifacts::{Artifacts, AssemblyStats, LineMapping, PhaseTimings, SymbolInfo, SymbolSection},
+    ast::{OptLevel, OptimizationConfig},
     astnode::ASTNode,
+    cache::{CacheKey, CacheStats, ParseCache},
     debug::DebugData,
-    errors::CompileError,
-    parser::{ProgramLayout, Token, parse, parse_with_optimization},
+    dynsym::{RelDynMap, RelocationType},
+    errors::{CompileError, Severity},
+    frontend::{Frontend, FrontendError, FrontendRegistry, Translation},
+    index::{DefinitionKind, SymbolDefinition, SymbolIndex, SymbolLocation, SymbolReference},
+    interface::ProgramInterface,
+    linker::Object,
+    lint::{LINTS, is_allowed},
+    parser::{ProgramLayout, Token, parse, parse_with_optimization, parse_with_syscalls},
     preprocessor::{
-        FileResolver, FsFileResolver, MockFileResolver, PreprocessResult, preprocess,
+        FileResolver, FsFileResolver, MockFileResolver, PreprocessResult,
+        encoding::decode_source,
+        preprocess, preprocess_with_defines, read_source,
         source_map::{FileRegistry, SourceMap, SourceOrigin},
+        vfs::{FileProvider, FsProvider, IncludeResolver, MemoryProvider},
     },
     program::Program,
+    syscalls::SyscallRegistry,
+};
+pub use sbpf_common::{
+    lint::{LintConfig, LintLevel},
+    version::SbpfVersion,
+};
+use {
+    artifacts::timed,
+    preprocessor::PreprocessFailure,
+    sbpf_common::verifier,
+    section::Section,
+    std::{collections::HashMap, ops::Range, time::Duration},
 };
 
-/// sBPF target architecture
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
-pub enum SbpfArch {
-    V0,
-    #[default]
-    V3,
-}
-
-impl SbpfArch {
-    pub fn is_v3(&self) -> bool {
-        matches!(self, SbpfArch::V3)
-    }
+#[cfg(feature = "zip")]
+pub use self::preprocessor::vfs::ZipProvider;
 
-    pub fn e_flags(&self) -> u32 {
-        match self {
-            SbpfArch::V0 => 0,
-            SbpfArch::V3 => 3,
-        }
-    }
-}
+/// sBPF target architecture
+pub type SbpfArch = SbpfVersion;
 
 /// Debug mode configuration for the assembler
-#[derive(Debug, Clone)]
+#[cfg(feature = "dwarf")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DebugMode {
     /// Source filename for debug info
     pub filename: String,
@@ -71,15 +93,53 @@ pub struct DebugMode {
     pub directory: String,
 }
 
+/// Build provenance embedded in a `.note.sbpf.build` section. The build-id and
+/// assembler version are filled in by the assembler.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BuildInfo {
+    /// Git commit the program was built from
+    pub git_commit: Option<String>,
+}
+
+/// What the assembler does when it hits a bug in itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum InternalErrors {
+    /// Report it as a [`CompileError::InternalError`] diagnostic
+    #[default]
+    Error,
+    /// Panic at the first one, for debugging the assembler with a backtrace
+    Panic,
+}
+
 /// Options for the assembler
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Hash)]
 pub struct AssemblerOption {
     /// sBPF target architecture
     pub arch: SbpfArch,
     /// Optional debug mode configuration
+    #[cfg(feature = "dwarf")]
     pub debug_mode: Option<DebugMode>,
+    /// Optional build provenance note
+    pub build_info: Option<BuildInfo>,
     /// Optional optimization and CFG diagnostic configuration
     pub optimization: OptimizationConfig,
+    /// Syscalls that `call` instructions may name
+    pub syscalls: SyscallRegistry,
+    /// Directories searched for `.include` files by `Assembler::assemble_file`
+    /// and `Assembler::assemble_file_from`
+    pub include_paths: Vec<std::path::PathBuf>,
+    /// Constants declared ahead of preprocessed sources, for conditional
+    /// assembly
+    pub defines: Vec<(String, i64)>,
+    /// Front-ends that translate sources with other extensions into assembly
+    pub frontends: FrontendRegistry,
+    /// Skip the loader's checks on the emitted bytecode, for fragments that
+    /// are run but never deployed
+    pub skip_verification: bool,
+    /// Whether bugs in the assembler are reported or panicked on
+    pub internal_errors: InternalErrors,
+    /// Levels of the warnings and the limits functions are checked against
+    pub lints: LintConfig,
 }
 
 impl AssemblerOption {
@@ -90,15 +150,65 @@ impl AssemblerOption {
     }
 
     /// Enable debug mode with the given config
+    #[cfg(feature = "dwarf")]
     pub fn with_debug_mode(mut self, debug_mode: DebugMode) -> Self {
         self.debug_mode = Some(debug_mode);
         self
     }
+
+    /// Embed a build provenance note
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.build_info = Some(build_info);
+        self
+    }
+
+    /// Resolve calls against `syscalls` instead of the runtime's syscalls
+    pub fn with_syscalls(mut self, syscalls: SyscallRegistry) -> Self {
+        self.syscalls = syscalls;
+        self
+    }
+
+    /// Search `path` for `.include` files, after the including file's directory
+    pub fn with_include_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    /// Define `name` as `value` in preprocessed sources, as `--define` does
+    pub fn with_define(mut self, name: impl Into<String>, value: i64) -> Self {
+        self.defines.push((name.into(), value));
+        self
+    }
+
+    /// Translate sources with `frontend`'s extension through it before
+    /// preprocessing
+    pub fn with_frontend(mut self, frontend: impl Frontend + 'static) -> Self {
+        self.frontends.register(frontend);
+        self
+    }
+
+    /// Emit bytecode the loader would reject instead of failing
+    pub fn without_verification(mut self) -> Self {
+        self.skip_verification = true;
+        self
+    }
+
+    /// Report or panic on bugs in the assembler itself
+    pub fn with_internal_errors(mut self, internal_errors: InternalErrors) -> Self {
+        self.internal_errors = internal_errors;
+        self
+    }
+
+    /// Drop warnings `lints` allows and check functions against its limits
+    pub fn with_lints(mut self, lints: LintConfig) -> Self {
+        self.lints = lints;
+        self
+    }
 }
 
 /// An error enriched with source location information from preprocessing.
 /// Wraps a `CompileError` with the resolved original source location.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AssemblerError {
     pub error: CompileError,
     pub origin: Option<SourceOrigin>,
@@ -106,6 +216,12 @@ pub struct AssemblerError {
     pub column: Option<usize>,
 }
 
+impl AssemblerError {
+    pub fn severity(&self) -> Severity {
+        self.error.severity()
+    }
+}
+
 impl std::fmt::Display for AssemblerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.error)
@@ -121,58 +237,80 @@ impl std::error::Error for AssemblerError {
 /// Returned when assembly with preprocessing fails.
 /// Contains the errors and the file registry so callers can render
 /// diagnostics against the original source files.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AssembleErrors {
     pub errors: Vec<AssemblerError>,
     pub file_registry: FileRegistry,
 }
 
-/// Assembler for SBPF assembly code
+/// Assembler for SBPF assembly code.
+///
+/// An assembler holds no state between runs apart from its cache, so one
+/// instance can be shared across threads and reused for any number of
+/// sources.
 #[derive(Debug, Clone)]
 pub struct Assembler {
     options: AssemblerOption,
+    cache: Option<ParseCache>,
 }
 
 impl Assembler {
     /// Create a new Assembler with the given options
     pub fn new(options: AssemblerOption) -> Self {
-        Self { options }
+        Self {
+            options,
+            cache: None,
+        }
+    }
+
+    /// Reuse results from `cache` for sources that were already assembled with
+    /// the same options. Builds that dump CFGs always run in full.
+    pub fn with_cache(mut self, cache: ParseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The cache to use for this assembly, if any.
+    fn cache(&self) -> Option<&ParseCache> {
+        self.cache
+            .as_ref()
+            .filter(|_| !cache::has_side_effects(&self.options))
     }
 
     /// Assemble source code directly (no preprocessing).
-    /// This is the original API -- macros and includes are not supported.
+    /// This is the original API -- macros, includes and conditional assembly
+    /// are not supported.
     pub fn assemble(&self, source: &str) -> Result<Vec<u8>, Vec<CompileError>> {
-        let parse_result = match parse_with_optimization(
-            source,
-            self.options.arch,
-            self.options.optimization.clone(),
-        ) {
-            Ok(result) => result,
-            Err(errors) => {
-                return Err(errors);
-            }
-        };
-
-        // Build debug data if debug mode is enabled
-        let debug_data = if let Some(ref debug_mode) = self.options.debug_mode {
-            let (lines, labels) = collect_line_and_label_entries(source, &parse_result);
-            let code_end = parse_result.code_section.get_size();
+        self.assemble_full(source).map(|artifacts| artifacts.elf)
+    }
 
-            Some(DebugData {
-                filename: debug_mode.filename.clone(),
-                directory: debug_mode.directory.clone(),
-                lines,
-                labels,
-                code_start: 0,
-                code_end,
-            })
-        } else {
-            None
-        };
+    /// Assemble source code directly and return the ELF along with its source
+    /// map, symbols, relocations and size statistics.
+    pub fn assemble_full(&self, source: &str) -> Result<Artifacts, Vec<CompileError>> {
+        match self.cache() {
+            Some(cache) => cache.get_or_insert_with(CacheKey::new(&self.options, source), || {
+                self.assemble_full_uncached(source)
+            }),
+            None => self.assemble_full_uncached(source),
+        }
+    }
 
-        let program = Program::from_parse_result(parse_result, debug_data);
-        let bytecode = program.emit_bytecode();
-        Ok(bytecode)
+    fn assemble_full_uncached(&self, source: &str) -> Result<Artifacts, Vec<CompileError>> {
+        let mut timings = PhaseTimings::default();
+        let mut parse_result = self.parse(source, &mut timings)?;
+        parse_result
+            .warnings
+            .retain(|warning| !lint::is_silenced(warning, source));
+
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let mut artifacts = artifacts::collect_artifacts(&parse_result, |span| {
+            let line = line_starts.partition_point(|&start| start <= span.start);
+            (artifacts::INPUT_NAME.to_string(), line as u32)
+        });
+        artifacts.timings = timings;
+        Ok(self.emit(source, None, parse_result, artifacts))
     }
 
     /// Assemble with preprocessing: resolves `.include` and expands `.macro` directives
@@ -185,113 +323,693 @@ impl Assembler {
         source_path: &str,
         resolver: Option<&dyn FileResolver>,
     ) -> Result<Vec<u8>, AssembleErrors> {
-        // Run preprocessor
-        let preprocess_result =
-            preprocess(source, source_path, resolver).map_err(|failure| AssembleErrors {
-                errors: failure
-                    .errors
-                    .into_iter()
-                    .map(|e| AssemblerError {
-                        error: e.error,
-                        origin: e.origin,
-                        column: None,
-                    })
-                    .collect(),
-                file_registry: failure.file_registry,
-            })?;
+        self.assemble_full_with_preprocess(source, source_path, resolver)
+            .map(|artifacts| artifacts.elf)
+    }
 
-        let expanded = &preprocess_result.expanded_source;
-        let source_map = &preprocess_result.source_map;
+    /// Like `assemble_full`, with preprocessing. Source map and symbol
+    /// locations point into the original files, not the expanded source.
+    pub fn assemble_full_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<Artifacts, AssembleErrors> {
+        // Includes can change without `source` changing, so preprocessing
+        // always runs and the cache is keyed on its output.
+        let (preprocessed, preprocess_time) =
+            timed(|| self.preprocess(source, source_path, resolver));
+        let (expanded, source_map) = preprocessed?;
+        self.assemble_preprocessed(&expanded, &source_map, preprocess_time)
+    }
 
-        // Parse the expanded source
-        let parse_result = match parse_with_optimization(
+    /// Assemble `source` like [`Assembler::assemble_full_with_preprocess`]
+    /// and index it like [`Assembler::index_with_preprocess`], preprocessing
+    /// it once for both, for editors that show diagnostics for the same text
+    /// they navigate. The index is `None` when the source doesn't parse.
+    pub fn assemble_and_index_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> (Result<Artifacts, AssembleErrors>, Option<SymbolIndex>) {
+        let (preprocessed, preprocess_time) =
+            timed(|| self.preprocess(source, source_path, resolver));
+        match preprocessed {
+            Ok((expanded, source_map)) => (
+                self.assemble_preprocessed(&expanded, &source_map, preprocess_time),
+                SymbolIndex::build(&expanded, &source_map).ok(),
+            ),
+            Err(errors) => (Err(errors), None),
+        }
+    }
+
+    fn assemble_preprocessed(
+        &self,
+        expanded: &str,
+        source_map: &SourceMap,
+        preprocess_time: Duration,
+    ) -> Result<Artifacts, AssembleErrors> {
+        let assemble = || {
+            let mut timings = PhaseTimings::default();
+            let parse_result = self.parse(expanded, &mut timings)?;
+            let mut artifacts = artifacts::collect_artifacts(&parse_result, |span| {
+                let origin = source_map.resolve_span(span, expanded);
+                (
+                    source_map.file_registry.path(origin.file_id).to_string(),
+                    origin.line,
+                )
+            });
+            artifacts.timings = timings;
+            Ok(self.emit(expanded, Some(source_map), parse_result, artifacts))
+        };
+        let result = match self.cache() {
+            Some(cache) => cache.get_or_insert_with(
+                CacheKey::preprocessed(&self.options, expanded, source_map),
+                assemble,
+            ),
+            None => assemble(),
+        };
+        let mut artifacts =
+            result.map_err(|errors| map_parse_errors(errors, expanded, source_map))?;
+        artifacts.timings.preprocess = preprocess_time;
+        artifacts
+            .warnings
+            .retain(|warning| reported(warning, expanded, source_map));
+        artifacts.located_warnings = Some(map_parse_errors(
+            artifacts.warnings.clone(),
             expanded,
-            self.options.arch,
-            self.options.optimization.clone(),
-        ) {
-            Ok(result) => result,
-            Err(errors) => {
-                // Extract file registry from source map before moving errors
-                let file_registry = source_map.file_registry.clone();
-                return Err(AssembleErrors {
-                    errors: errors
-                        .into_iter()
-                        .map(|e| {
-                            let span = e.span();
-                            let origin = source_map.resolve_span(span, expanded).clone();
-                            // Compute column offset within the line from the
-                            // expanded source so we can highlight the right token.
-                            let col = expanded[..span.start]
-                                .rfind('\n')
-                                .map(|nl| span.start - nl - 1)
-                                .unwrap_or(span.start);
-                            AssemblerError {
-                                error: e,
-                                column: Some(col),
-                                origin: Some(origin),
-                            }
-                        })
-                        .collect(),
-                    file_registry,
-                });
+            source_map,
+        ));
+        Ok(artifacts)
+    }
+
+    /// Check source code without emitting it, returning its warnings: data
+    /// read back in the wrong byte order and code no jump, call or
+    /// fall-through reaches.
+    pub fn lint(&self, source: &str) -> Result<Vec<CompileError>, Vec<CompileError>> {
+        let mut warnings = self.parse(source, &mut PhaseTimings::default())?.warnings;
+        warnings.retain(|warning| !lint::is_silenced(warning, source));
+        Ok(warnings)
+    }
+
+    /// Like `lint`, with preprocessing. The warnings are located in the
+    /// original files the same way errors are.
+    pub fn lint_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<AssembleErrors, AssembleErrors> {
+        let (expanded, source_map, mut layout) =
+            self.parse_with_preprocess(source, source_path, resolver)?;
+        layout
+            .warnings
+            .retain(|warning| reported(warning, &expanded, &source_map));
+        Ok(map_parse_errors(layout.warnings, &expanded, &source_map))
+    }
+
+    /// Preprocess `source` and index the labels and constants it defines
+    /// and references, for editors. Only the grammar has to be satisfied, so
+    /// a program with undefined names or invalid operands is still indexed.
+    pub fn index_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<SymbolIndex, AssembleErrors> {
+        let (expanded, source_map) = self.preprocess(source, source_path, resolver)?;
+        SymbolIndex::build(&expanded, &source_map)
+    }
+
+    /// Preprocess `source` and read the interface it declares: its
+    /// `.globl` symbols, `.layout` and `.instruction` blocks and `ERROR_*`
+    /// constants. Like indexing, only the grammar has to be satisfied.
+    pub fn interface_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<ProgramInterface, AssembleErrors> {
+        let preprocessed =
+            preprocess_with_defines(source, source_path, resolver, &self.options.defines)
+                .map_err(preprocess_errors)?;
+        ProgramInterface::build(preprocessed)
+    }
+
+    /// Preprocess and parse one file of a multi-file program, leaving its
+    /// symbols unresolved so it can be passed to [`Assembler::link`].
+    pub fn assemble_object(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<Object, AssembleErrors> {
+        let (preprocessed, preprocess) = timed(|| self.preprocess(source, source_path, resolver));
+        let (expanded, source_map) = preprocessed?;
+        let parse_object =
+            || parser::parse_ast(&expanded, self.options.arch, self.options.syscalls.clone());
+        let (parsed, parse) = timed(|| match self.cache() {
+            Some(cache) => cache.object_or_insert_with(
+                CacheKey::object(&self.options, &expanded, &source_map),
+                parse_object,
+            ),
+            None => parse_object(),
+        });
+        match parsed {
+            Ok(mut ast) => {
+                ast.warnings
+                    .retain(|warning| reported(warning, &expanded, &source_map));
+                let timings = PhaseTimings {
+                    preprocess,
+                    parse,
+                    ..Default::default()
+                };
+                Ok(Object::new(source_path, expanded, source_map, ast, timings))
             }
-        };
+            Err(errors) => Err(map_parse_errors(errors, &expanded, &source_map)),
+        }
+    }
 
+    /// Link objects from [`Assembler::assemble_object`] into one program.
+    /// Objects are laid out in order, and the entrypoint is the first `.globl`
+    /// of the first object.
+    pub fn link(&self, objects: Vec<Object>) -> Result<Artifacts, AssembleErrors> {
+        let mut timings = PhaseTimings::default();
+        for object in &objects {
+            timings.preprocess += object.timings.preprocess;
+            timings.parse += object.timings.parse;
+        }
+        let ((linked, errors), link_time) = timed(|| linker::link(objects));
+        timings.link = link_time;
+        if !errors.is_empty() {
+            return Err(map_parse_errors(errors, &linked.source, &linked.source_map));
+        }
+        let (built, layout_time) = timed(|| {
+            let mut ast = linked.ast;
+            ast.warnings
+                .extend(lint::check_limits(&ast, &self.options.lints));
+            ast::build_program(ast, self.options.arch, self.options.optimization.clone())
+        });
+        timings.layout = layout_time;
+        let parse_result = built
+            .and_then(|parse_result| {
+                check_encoding(&parse_result, self.options.arch)?;
+                Ok(parse_result)
+            })
+            .map_err(|errors| self.internal_errors(errors))
+            .map_err(|errors| map_parse_errors(errors, &linked.source, &linked.source_map))?;
+        if !self.options.skip_verification {
+            let (verified, verify_time) = timed(|| verify(&parse_result, self.options.arch));
+            timings.verify = verify_time;
+            verified
+                .map_err(|errors| map_parse_errors(errors, &linked.source, &linked.source_map))?;
+        }
+        let mut artifacts = artifacts::collect_artifacts(&parse_result, |span| {
+            let origin = linked.source_map.resolve_span(span, &linked.source);
+            (
+                linked
+                    .source_map
+                    .file_registry
+                    .path(origin.file_id)
+                    .to_string(),
+                origin.line,
+            )
+        });
+        artifacts.timings = timings;
+        let mut artifacts = self.emit(
+            &linked.source,
+            Some(&linked.source_map),
+            parse_result,
+            artifacts,
+        );
+        artifacts.warnings.retain(|warning| {
+            !self.options.lints.is_allowed(&warning.code())
+                && !silenced(warning, &linked.source, &linked.source_map)
+        });
+        artifacts.located_warnings = Some(map_parse_errors(
+            artifacts.warnings.clone(),
+            &linked.source,
+            &linked.source_map,
+        ));
+        Ok(artifacts)
+    }
+
+    /// Emit the ELF for a parsed program into `artifacts`. `source_map`
+    /// points the lines of a preprocessed `source` back at where they were
+    /// written, for the debug line table.
+    fn emit(
+        &self,
+        source: &str,
+        source_map: Option<&SourceMap>,
+        parse_result: ProgramLayout,
+        mut artifacts: Artifacts,
+    ) -> Artifacts {
         // Build debug data if debug mode is enabled
-        let debug_data = if let Some(ref debug_mode) = self.options.debug_mode {
-            let (lines, labels) = collect_line_and_label_entries(expanded, &parse_result);
+        #[cfg(feature = "dwarf")]
+        let debug_data = self.options.debug_mode.as_ref().map(|debug_mode| {
+            let (files, lines, labels) =
+                collect_line_and_label_entries(source, source_map, &parse_result);
             let code_end = parse_result.code_section.get_size();
 
-            Some(DebugData {
+            DebugData {
                 filename: debug_mode.filename.clone(),
                 directory: debug_mode.directory.clone(),
+                files,
                 lines,
                 labels,
                 code_start: 0,
                 code_end,
-            })
-        } else {
+            }
+        });
+        #[cfg(not(feature = "dwarf"))]
+        let debug_data = {
+            let _ = (source, source_map);
             None
         };
 
-        let program = Program::from_parse_result(parse_result, debug_data);
-        let bytecode = program.emit_bytecode();
-        Ok(bytecode)
+        let (program, encode_time) = timed(|| {
+            Program::from_parse_result(parse_result, debug_data, self.options.build_info.as_ref())
+        });
+        let (elf, elf_time) = timed(|| program.emit_bytecode());
+        artifacts.elf = elf;
+        artifacts.timings.encode = encode_time;
+        artifacts.timings.elf = elf_time;
+        artifacts.stats.elf_size = artifacts.elf.len();
+        artifacts
+    }
+
+    /// Run preprocessing and parsing like `assemble_with_preprocess`, but return the
+    /// dynamic relocation table instead of emitting bytecode. Offsets are relative
+    /// to the start of `.text`. Static (v3) programs produce an empty table.
+    pub fn relocations_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<RelDynMap, AssembleErrors> {
+        let (_, _, parse_result) = self.parse_with_preprocess(source, source_path, resolver)?;
+        Ok(parse_result.relocation_data)
+    }
+
+    /// Preprocess and parse, returning the expanded source and its source map
+    /// alongside the layout.
+    fn parse_with_preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<(String, SourceMap, ProgramLayout), AssembleErrors> {
+        let (expanded, source_map) = self.preprocess(source, source_path, resolver)?;
+        match self.parse(&expanded, &mut PhaseTimings::default()) {
+            Ok(result) => Ok((expanded, source_map, result)),
+            Err(errors) => Err(map_parse_errors(errors, &expanded, &source_map)),
+        }
+    }
+
+    /// Translate sources a front-end is registered for, then resolve includes
+    /// and expand macros. Lines of translated sources map back to the
+    /// front-end's source.
+    fn preprocess(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<(String, SourceMap), AssembleErrors> {
+        let Some(frontend) = self.options.frontends.for_path(source_path) else {
+            return self.preprocess_assembly(source, source_path, resolver);
+        };
+        let translation = frontend.translate(source, source_path).map_err(|errors| {
+            let mut file_registry = FileRegistry::new();
+            let file_id = file_registry.add(source_path, source.to_string());
+            AssembleErrors {
+                errors: errors
+                    .into_iter()
+                    .map(|e| AssemblerError {
+                        error: CompileError::FrontendError {
+                            frontend: frontend.name().to_string(),
+                            message: e.message,
+                            span: 0..0,
+                            custom_label: None,
+                        },
+                        origin: e.line.map(|line| SourceOrigin::new(file_id, line)),
+                        column: e.column,
+                    })
+                    .collect(),
+                file_registry,
+            }
+        })?;
+
+        let generated_path = frontend::generated_path(source_path);
+        let redirect = |registry: &FileRegistry| {
+            registry
+                .file_ids()
+                .find(|&id| registry.path(id) == generated_path)
+                .filter(|_| translation.has_line_mapping())
+        };
+        let (expanded, mut source_map) =
+            match self.preprocess_assembly(translation.assembly(), &generated_path, resolver) {
+                Ok(result) => result,
+                Err(mut failure) => {
+                    if let Some(generated) = redirect(&failure.file_registry) {
+                        let to = failure.file_registry.add(source_path, source.to_string());
+                        for error in &mut failure.errors {
+                            if let Some(origin) = &mut error.origin {
+                                origin.redirect(generated, to, translation.lines());
+                            }
+                        }
+                    }
+                    return Err(failure);
+                }
+            };
+        let Some(generated) = redirect(&source_map.file_registry) else {
+            return Ok((expanded, source_map));
+        };
+        let file = source_map.redirect(
+            generated,
+            source_path,
+            source.to_string(),
+            translation.lines(),
+        );
+        #[cfg(feature = "dwarf")]
+        if self.options.debug_mode.is_some() {
+            return Ok(frontend::annotate_lines(expanded, source_map, file));
+        }
+        #[cfg(not(feature = "dwarf"))]
+        let _ = file;
+        Ok((expanded, source_map))
+    }
+
+    /// Resolve includes and expand macros.
+    fn preprocess_assembly(
+        &self,
+        source: &str,
+        source_path: &str,
+        resolver: Option<&dyn FileResolver>,
+    ) -> Result<(String, SourceMap), AssembleErrors> {
+        let preprocess_result =
+            preprocess_with_defines(source, source_path, resolver, &self.options.defines)
+                .map_err(preprocess_errors)?;
+        Ok((
+            preprocess_result.expanded_source,
+            preprocess_result.source_map,
+        ))
+    }
+
+    /// Parse, lay out and verify `source`, recording how long each took
+    /// in `timings`.
+    fn parse(
+        &self,
+        source: &str,
+        timings: &mut PhaseTimings,
+    ) -> Result<ProgramLayout, Vec<CompileError>> {
+        let (ast, parse_time) =
+            timed(|| parser::parse_ast(source, self.options.arch, self.options.syscalls.clone()));
+        timings.parse = parse_time;
+        let (parse_result, layout_time) = timed(|| {
+            let mut ast = ast?;
+            ast.warnings
+                .extend(lint::check_limits(&ast, &self.options.lints));
+            ast::build_program(ast, self.options.arch, self.options.optimization.clone())
+        });
+        timings.layout = layout_time;
+        let mut parse_result = parse_result
+            .and_then(|parse_result| {
+                check_encoding(&parse_result, self.options.arch)?;
+                Ok(parse_result)
+            })
+            .map_err(|errors| self.internal_errors(errors))?;
+        if !self.options.skip_verification {
+            let (verified, verify_time) = timed(|| verify(&parse_result, self.options.arch));
+            timings.verify = verify_time;
+            verified?;
+        }
+        parse_result
+            .warnings
+            .retain(|warning| !self.options.lints.is_allowed(&warning.code()));
+        Ok(parse_result)
+    }
+
+    /// Hand back `errors`, or panic on the first internal error among them
+    /// when asked to.
+    fn internal_errors(&self, errors: Vec<CompileError>) -> Vec<CompileError> {
+        if self.options.internal_errors == InternalErrors::Panic
+            && let Some(error) = errors
+                .iter()
+                .find(|error| matches!(error, CompileError::InternalError { .. }))
+        {
+            panic!(
+                "{} at {:?}\nPlease file a bug report at: \
+                 https://github.com/blueshift-gg/sbpf/issues/new",
+                error,
+                error.span()
+            );
+        }
+        errors
     }
 
     /// Convenience method: read a file from disk and assemble with full preprocessing.
     pub fn assemble_file(&self, path: &std::path::Path) -> Result<Vec<u8>, AssembleErrors> {
-        let source = std::fs::read_to_string(path).map_err(|e| AssembleErrors {
+        self.assemble_file_from(FsProvider, path)
+    }
+
+    /// Read `path` from `provider` and assemble it with full preprocessing.
+    /// Includes are looked up in the same provider, next to the including file
+    /// and then in the configured include paths.
+    pub fn assemble_file_from<P: FileProvider>(
+        &self,
+        provider: P,
+        path: &std::path::Path,
+    ) -> Result<Vec<u8>, AssembleErrors> {
+        let read_error = |error| AssembleErrors {
             errors: vec![AssemblerError {
-                error: CompileError::IncludeReadError {
-                    path: path.display().to_string(),
-                    reason: e.to_string(),
-                    span: 0..0,
-                    custom_label: Some("Failed to read source file".to_string()),
-                },
+                error,
                 origin: None,
                 column: None,
             }],
             file_registry: FileRegistry::new(),
+        };
+        let bytes = provider.read(path).map_err(|e| {
+            read_error(CompileError::IncludeReadError {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+                span: 0..0,
+                custom_label: Some("Failed to read source file".to_string()),
+            })
         })?;
+        let source = decode_source(&bytes).map_err(read_error)?;
 
         let source_path = path.to_string_lossy();
-        let resolver = FsFileResolver::new();
+        let resolver = IncludeResolver::with_provider(provider, self.options.include_paths.clone());
         self.assemble_with_preprocess(&source, &source_path, Some(&resolver))
     }
 }
 
-type LineEntry = (u64, u32); // (offset, line)
+/// Make sure every instruction in `.text` encodes before anything emits it.
+/// Whatever doesn't is left over from a pass that should have resolved or
+/// rejected it.
+fn check_encoding(parse_result: &ProgramLayout, arch: SbpfArch) -> Result<(), Vec<CompileError>> {
+    let errors: Vec<_> = parse_result
+        .code_section
+        .get_nodes()
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Instruction { instruction, .. } => instruction
+                .to_bytes_for(arch)
+                .err()
+                .map(|e| bug!(instruction.span.clone(), "cannot encode instruction: {}", e)),
+            _ => None,
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Run the loader's checks over the program's `.text`, pointing each failure
+/// at the instruction it is about.
+fn verify(parse_result: &ProgramLayout, arch: SbpfArch) -> Result<(), Vec<CompileError>> {
+    let code_section = &parse_result.code_section;
+    let errors = match verifier::verify(&code_section.bytecode(), arch) {
+        Ok(()) => return Ok(()),
+        Err(errors) => errors,
+    };
+    let spans: HashMap<u64, &Range<usize>> = code_section
+        .get_nodes()
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Instruction {
+                instruction,
+                offset,
+            } => Some((*offset, &instruction.span)),
+            _ => None,
+        })
+        .collect();
+    Err(errors
+        .into_iter()
+        .map(|error| CompileError::VerifierError {
+            span: error
+                .pc()
+                .and_then(|pc| spans.get(&(pc as u64 * 8)))
+                .map_or(0..0, |span| (*span).clone()),
+            error: error.to_string(),
+            custom_label: None,
+        })
+        .collect())
+}
+
+/// Whether a warning about expanded source is reported. Those silenced with
+/// `sbpf-allow` are not. Labels and constants from included headers, macro
+/// expansions or `--define` may serve other programs or other invocations,
+/// so they are only reported unused where the program itself declares them.
+fn reported(warning: &CompileError, expanded: &str, source_map: &SourceMap) -> bool {
+    if silenced(warning, expanded, source_map) {
+        return false;
+    }
+    if !matches!(
+        warning,
+        CompileError::UnusedLabel { .. } | CompileError::UnusedConstant { .. }
+    ) {
+        return true;
+    }
+    let origin = source_map.resolve_span(warning.span(), expanded);
+    origin.file_id.index() == 0
+        && origin.line > 0
+        && origin.macro_expansion.is_none()
+        && !origin.generated
+}
+
+/// Whether an `sbpf-allow` comment in the file a warning about expanded
+/// source was written in silences it.
+fn silenced(warning: &CompileError, expanded: &str, source_map: &SourceMap) -> bool {
+    let origin = source_map.resolve_span(warning.span(), expanded);
+    lint::is_allowed(
+        source_map.file_registry.content(origin.file_id),
+        origin.line,
+        &warning.code(),
+    )
+}
+
+/// Attach the original file and line to errors from parsing expanded source.
+/// Errors from the preprocessor, located in the files they came from.
+fn preprocess_errors(failure: PreprocessFailure) -> AssembleErrors {
+    AssembleErrors {
+        errors: failure
+            .errors
+            .into_iter()
+            .map(|e| AssemblerError {
+                error: e.error,
+                origin: e.origin,
+                column: None,
+            })
+            .collect(),
+        file_registry: failure.file_registry,
+    }
+}
+
+fn map_parse_errors(
+    errors: Vec<CompileError>,
+    expanded: &str,
+    source_map: &SourceMap,
+) -> AssembleErrors {
+    AssembleErrors {
+        errors: errors
+            .into_iter()
+            .map(|e| {
+                let span = e.span();
+                let origin = source_map.resolve_span(span, expanded).clone();
+                let column = original_column(span, expanded, &origin, &source_map.file_registry);
+                AssemblerError {
+                    error: e,
+                    column,
+                    origin: Some(origin),
+                }
+            })
+            .collect(),
+        file_registry: source_map.file_registry.clone(),
+    }
+}
+
+/// Column of `span` within the original line `origin` points at. Lines
+/// produced by macro expansion differ from the body line they came from, so
+/// the offending text is looked up in the original line instead; if it was
+/// substituted in from an argument there is no column and the whole line is
+/// highlighted.
+fn original_column(
+    span: &std::ops::Range<usize>,
+    expanded: &str,
+    origin: &SourceOrigin,
+    registry: &FileRegistry,
+) -> Option<usize> {
+    let line_start = expanded[..span.start].rfind('\n').map_or(0, |nl| nl + 1);
+    let line_end = expanded[span.start..]
+        .find('\n')
+        .map_or(expanded.len(), |len| span.start + len);
+    let col = span.start - line_start;
+
+    let original_start = registry.line_byte_offset(origin.file_id, origin.line);
+    let original = &registry.content(origin.file_id)
+        [original_start..original_start + registry.line_length(origin.file_id, origin.line)];
+    if original.get(..col) == expanded.get(line_start..span.start) {
+        return Some(col);
+    }
+    let text = &expanded[span.start..span.end.clamp(span.start, line_end)];
+    (!text.trim().is_empty())
+        .then(|| original.find(text))
+        .flatten()
+}
+
+#[cfg(feature = "dwarf")]
+type SourceFile = (String, String); // (directory, name)
+#[cfg(feature = "dwarf")]
 type LabelEntry = (String, u64, u32); // (label, offset, line)
 
-/// Helper function to collect line and label entries
+/// Helper function to collect line and label entries. Instructions follow
+/// the `.loc` directives when the source has any, and otherwise the line
+/// they were written on: their own line in `source`, or where `source_map`
+/// traces it back to. Lines from files other than the one assembled, such
+/// as includes, are recorded against those files.
+#[cfg(feature = "dwarf")]
 fn collect_line_and_label_entries(
     source: &str,
+    source_map: Option<&SourceMap>,
     parse_result: &ProgramLayout,
-) -> (Vec<LineEntry>, Vec<LabelEntry>) {
-    let mut files: Files<&str> = Files::new();
-    let file_id = files.add("source", source);
-
+) -> (Vec<SourceFile>, Vec<debug::LineRow>, Vec<LabelEntry>) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    // 1-based line containing byte `offset`
+    let line_number = |offset: usize| line_starts.partition_point(|&start| start <= offset) as u32;
+    let main_file = source_map.and_then(|map| map.file_registry.file_ids().next());
+    // The file other than the assembled one and the line byte `offset` was
+    // written on.
+    let origin = |offset: usize| -> (Option<&str>, u32) {
+        let line = line_number(offset);
+        match source_map {
+            Some(map) if !map.is_empty() => {
+                let origin = &map.origins()[(line as usize - 1).min(map.len() - 1)];
+                let file = (Some(origin.file_id) != main_file)
+                    .then(|| map.file_registry.path(origin.file_id));
+                (file, origin.line)
+            }
+            _ => (None, line),
+        }
+    };
+
+    let mut files: Vec<SourceFile> = Vec::new();
+    let mut file_index = |directory: &str, name: &str| {
+        files
+            .iter()
+            .position(|(known_directory, known_name)| {
+                known_directory == directory && known_name == name
+            })
+            .unwrap_or_else(|| {
+                files.push((directory.to_string(), name.to_string()));
+                files.len() - 1
+            })
+    };
     let mut line_entries = Vec::new();
     let mut label_entries = Vec::new();
 
@@ -301,28 +1019,43 @@ fn collect_line_and_label_entries(
                 instruction,
                 offset,
             } => {
-                let line_index = files.line_index(file_id, instruction.span.start as u32);
-                let line_number = (line_index.to_usize() + 1) as u32;
-                line_entries.push((*offset, line_number));
+                if parse_result.source_locations.is_empty() {
+                    // Included files are named as the include directive
+                    // names them, relative to the assembled file.
+                    let (file, line) = origin(instruction.span.start);
+                    line_entries.push(debug::LineRow {
+                        offset: *offset,
+                        file: file.map(|name| file_index("", name)),
+                        line,
+                        column: 0,
+                    });
+                } else if let Some(location) =
+                    parse_result.source_locations.get(&instruction.span.start)
+                {
+                    line_entries.push(debug::LineRow {
+                        offset: *offset,
+                        file: Some(file_index(&location.directory, &location.file)),
+                        line: location.line,
+                        column: location.column,
+                    });
+                }
             }
             ASTNode::Label { label, offset } => {
-                let line_index = files.line_index(file_id, label.span.start as u32);
-                let line_number = (line_index.to_usize() + 1) as u32;
-                label_entries.push((label.name.clone(), *offset, line_number));
+                label_entries.push((label.name.clone(), *offset, origin(label.span.start).1));
             }
             _ => {}
         }
     }
 
     for node in parse_result.data_section.get_nodes() {
-        if let ASTNode::ROData { rodata, offset } = node {
-            let line_index = files.line_index(file_id, rodata.span.start as u32);
-            let line_number = (line_index.to_usize() + 1) as u32;
-            label_entries.push((rodata.name.clone(), *offset, line_number));
+        if let ASTNode::ROData { rodata, offset } = node
+            && !rodata.is_unlabeled()
+        {
+            label_entries.push((rodata.name.clone(), *offset, origin(rodata.span.start).1));
         }
     }
 
-    (line_entries, label_entries)
+    (files, line_entries, label_entries)
 }
 
 #[cfg(test)]
@@ -332,7 +1065,7 @@ pub fn assemble(source: &str) -> Result<Vec<u8>, Vec<CompileError>> {
     assembler.assemble(source)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "dwarf"))]
 pub fn assemble_with_debug_data(
     source: &str,
     filename: &str,
@@ -574,11 +1307,56 @@ mod tests {
         target:
             jne r1, r2, target
             exit
+            exit
         "#;
         let result = assemble(source);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_assemble_jump_label_addend() {
+        let source = |jumps: &str| {
+            format!(
+                ".globl entrypoint\n.equ SKIP, 1\nentrypoint:\n{jumps}\ntable:\n    mov64 r0, 0\n    lddw r1, 0x1122334455667788\n    mov64 r0, 1\ndone:\n    exit\n"
+            )
+        };
+        // Addends count instructions, so `table+3` steps over the lddw.
+        let relative =
+            assemble(&source("    ja +5\n    jeq r1, r2, +4\n    jne r1, 0, +3")).unwrap();
+        let labelled = assemble(&source(
+            "    ja table+3\n    jeq r1, r2, table+SKIP+2\n    jne r1, 0, done-1",
+        ))
+        .unwrap();
+        assert_eq!(labelled, relative);
+
+        let optimized = Assembler::new(AssemblerOption {
+            optimization: OptimizationConfig::enabled(),
+            ..AssemblerOption::default()
+        });
+        assert!(
+            optimized
+                .assemble(&source("    ja table+3\n    ja 1f-1\n1:"))
+                .is_ok()
+        );
+
+        for (jump, error) in [
+            (
+                "ja table+2",
+                "Bytecode error: Jump target 'table+2' resolves to byte offset 40, which is not \
+                 an instruction",
+            ),
+            (
+                "ja table-4",
+                "Bytecode error: Jump target 'table-4' resolves outside the program",
+            ),
+            ("ja nowhere+1", "Undefined label 'nowhere'"),
+            ("ja table+done", "Expression is not a link-time constant"),
+        ] {
+            let errors = assemble(&source(&format!("    {jump}\n    exit\n    exit"))).unwrap_err();
+            assert_eq!(errors[0].to_string(), error, "{jump}");
+        }
+    }
+
     #[test]
     fn test_assemble_jump32_v3() {
         let source = r#"
@@ -587,6 +1365,7 @@ mod tests {
             jeq32 r1, 0, +1
             jset32 r1, r2, +1
             exit
+            exit
         "#;
         let assembler = Assembler::new(AssemblerOption::default());
         let result = assembler.assemble(source);
@@ -615,6 +1394,7 @@ mod tests {
             if w1 == 0 goto +1
             if w1 & w2 goto +1
             exit
+            exit
         "#;
         let assembler = Assembler::new(AssemblerOption::default());
         let result = assembler.assemble(source);
@@ -683,6 +1463,295 @@ mod tests {
         assert!(result.is_ok(), "Failed: {:?}", result.err());
     }
 
+    #[test]
+    fn test_assemble_forward_rodata_reference() {
+        // Data symbols may be used before the .rodata section defines them
+        let source = r#"
+        .globl entrypoint
+        entrypoint:
+            mov64 r1, message
+            lddw r2, message
+            exit
+        .rodata
+        prefix: .ascii "> "
+        message: .ascii "Hello, Solana!"
+        "#;
+        let elf = assemble(source).unwrap();
+        // mov64 r1, 2: the address of `message` in v3's .rodata
+        assert!(
+            elf.windows(8)
+                .any(|ix| ix == [0xb7, 0x01, 0, 0, 2, 0, 0, 0])
+        );
+
+        let errors = Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V0))
+            .assemble(source)
+            .unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Address of 'message' does not fit in a 32-bit immediate"
+        );
+    }
+
+    #[test]
+    fn test_assemble_data_section() {
+        let source = r#"
+        .globl entrypoint
+        entrypoint:
+            lddw r1, counter
+            ldxdw r2, [r1+0]
+            add64 r2, 1
+            stxdw [r1+0], r2
+            exit
+        .rodata
+        msg: .ascii "hi!"
+        .data
+        counter: .quad 41
+        flags:
+            .byte 1, 2
+        "#;
+        let artifacts = Assembler::new(AssemblerOption::default())
+            .assemble_full(source)
+            .unwrap();
+        let data_symbols: Vec<(&str, u64)> = artifacts
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.section == SymbolSection::Data)
+            .map(|symbol| (symbol.name.as_str(), symbol.offset))
+            .collect();
+        assert_eq!(data_symbols, vec![("counter", 0), ("flags", 8)]);
+        assert_eq!(artifacts.stats.data_size, 10);
+
+        let elf = &artifacts.elf;
+        // e_phnum: .rodata, .data and .text segments
+        assert_eq!(u16::from_le_bytes([elf[56], elf[57]]), 3);
+        // The .data segment is writable and follows the padded .rodata
+        let data_header = &elf[64 + 56..64 + 2 * 56];
+        assert_eq!(data_header[4], 0x6); // PF_R | PF_W
+        assert_eq!(
+            u64::from_le_bytes(data_header[16..24].try_into().unwrap()),
+            8
+        );
+        // lddw r1, counter
+        assert!(
+            elf.windows(8)
+                .any(|ix| ix == [0x18, 0x01, 0, 0, 8, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn test_assemble_bss_section() {
+        let source = |size: u64| {
+            format!(
+                r#"
+        .globl entrypoint
+        entrypoint:
+            lddw r1, buffer
+            stxdw [r1+0], r2
+            exit
+        .data
+        counter: .quad 41
+        .bss
+        buffer: .zero {size}
+        tail:
+            .space 3
+        "#
+            )
+        };
+        let assembler = Assembler::new(AssemblerOption::default());
+        let artifacts = assembler.assemble_full(&source(64)).unwrap();
+        let bss_symbols: Vec<(&str, u64)> = artifacts
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.section == SymbolSection::Bss)
+            .map(|symbol| (symbol.name.as_str(), symbol.offset))
+            .collect();
+        assert_eq!(bss_symbols, vec![("buffer", 0), ("tail", 64)]);
+        assert_eq!(artifacts.stats.bss_size, 67);
+
+        let elf = &artifacts.elf;
+        // The writable segment holds .data in the file and reserves the
+        // padded .bss after it in memory.
+        let data_header = &elf[64..64 + 56];
+        assert_eq!(data_header[4], 0x6); // PF_R | PF_W
+        assert_eq!(
+            u64::from_le_bytes(data_header[32..40].try_into().unwrap()),
+            8
+        );
+        assert_eq!(
+            u64::from_le_bytes(data_header[40..48].try_into().unwrap()),
+            80
+        );
+        // lddw r1, buffer
+        assert!(
+            elf.windows(8)
+                .any(|ix| ix == [0x18, 0x01, 0, 0, 8, 0, 0, 0])
+        );
+
+        // Reserving more memory does not grow the ELF.
+        let larger = assembler.assemble_full(&source(1 << 20)).unwrap();
+        assert_eq!(larger.elf.len(), elf.len());
+    }
+
+    #[test]
+    fn test_assemble_initialized_bss_error() {
+        let source = r#"
+        .globl entrypoint
+        entrypoint:
+            exit
+        .bss
+        counter: .quad 41
+        "#;
+        let errors = Assembler::new(AssemblerOption::default())
+            .assemble_full(source)
+            .unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Only zero fills (.zero, .space, .fill) can reserve space in .bss"
+        );
+    }
+
+    #[test]
+    fn test_assemble_fill_directives() {
+        let source = r#"
+        .globl entrypoint
+        .equ ENTRIES, 3
+        entrypoint:
+            lddw r1, tail
+            exit
+        .rodata
+        table: .fill ENTRIES, 2, 0x1234
+        pad:
+            .space 2, 0xff
+            .fill 2
+        tail: .byte 7
+        .bss
+        scratch: .fill ENTRIES * 2, 8, 0
+        end: .space 1
+        "#;
+        let artifacts = Assembler::new(AssemblerOption::default())
+            .assemble_full(source)
+            .unwrap();
+        let offsets: Vec<(&str, u64)> = artifacts
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.section != SymbolSection::Text)
+            .map(|symbol| (symbol.name.as_str(), symbol.offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                ("table", 0),
+                ("pad", 6),
+                ("tail", 10),
+                ("scratch", 0),
+                ("end", 48)
+            ]
+        );
+        assert_eq!(artifacts.stats.bss_size, 49);
+        assert!(
+            artifacts
+                .elf
+                .windows(11)
+                .any(|w| w == [0x34, 0x12, 0x34, 0x12, 0x34, 0x12, 0xff, 0xff, 0, 0, 7])
+        );
+    }
+
+    #[test]
+    fn test_assemble_fill_errors() {
+        let error = |data: &str| {
+            let source = format!(
+                ".globl entrypoint\nentrypoint:\n    exit\n.rodata\n{}\n",
+                data
+            );
+            Assembler::new(AssemblerOption::default())
+                .assemble_full(&source)
+                .unwrap_err()[0]
+                .to_string()
+        };
+        assert!(error("x: .fill 2, 9, 1").starts_with("Out of range literal"));
+        assert!(error("x: .fill 2, 1, 256").starts_with("Out of range literal"));
+        assert!(error("x: .space -1").starts_with("Out of range literal"));
+        assert_eq!(
+            error("x: .space 4, SIZE"),
+            "Parse error: Undefined constant: SIZE"
+        );
+        assert_eq!(
+            error(".bss\nx: .space 4, 1"),
+            "Only zero fills (.zero, .space, .fill) can reserve space in .bss"
+        );
+    }
+
+    #[test]
+    fn test_assemble_endian_directives() {
+        let source = r#"
+        .globl entrypoint
+        entrypoint:
+            lddw r1, header
+            lddw r2, trailer
+            exit
+        .rodata
+        header:
+            .be16 0x0800
+            .le16 0x0800
+            .be32 0x0a000001, -2
+            .le64 1
+            .be64 0x0102030405060708
+        trailer: .byte 7
+        "#;
+        let artifacts = Assembler::new(AssemblerOption::default())
+            .assemble_full(source)
+            .unwrap();
+        let trailer = artifacts
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == "trailer")
+            .unwrap();
+        assert_eq!(trailer.offset, 28);
+        let expected = [
+            0x08, 0x00, 0x00, 0x08, 0x0a, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfe, 1, 0, 0, 0, 0,
+            0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 7,
+        ];
+        assert!(artifacts.elf.windows(expected.len()).any(|w| w == expected));
+        assert!(artifacts.warnings.is_empty());
+
+        let error = Assembler::new(AssemblerOption::default())
+            .assemble_full(".globl e\ne:\n    exit\n.rodata\nx: .be16 0x10000\n")
+            .unwrap_err();
+        assert!(error[0].to_string().starts_with("Out of range literal"));
+    }
+
+    #[test]
+    fn test_assemble_undefined_symbol_error() {
+        let source = r#"
+        .globl entrypoint
+        entrypoint:
+            mov64 r1, missing
+            exit
+        "#;
+        let errors = assemble(source).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Undefined symbol 'missing'");
+
+        let errors = assemble(".globl e\ne:\n    ldxdw r1, [r1+missing]\n    exit\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "Undefined symbol 'missing'");
+    }
+
+    #[test]
+    fn test_assemble_undefined_entrypoint_error() {
+        let source =
+            ".globl entrypoint\nhelper:\n    exit\nentry_point:\n    call helper\n    exit\n";
+        let errors = assemble(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "Entrypoint 'entrypoint' is declared .globl but never defined"
+        );
+        assert_eq!(errors[0].span(), &(0..17));
+        assert_eq!(errors[0].label(), "Defined labels: entry_point, helper");
+
+        let errors = assemble(".globl entrypoint\n    exit\n").unwrap_err();
+        assert_eq!(errors[0].label(), "No labels are defined");
+    }
+
     #[test]
     fn test_assemble_label_arithmetic_with_offset() {
         // Label arithmetic with additional constant offset
@@ -728,7 +1797,8 @@ mod tests {
             mov64 r2, message_end - message
             call sol_log_
             exit
-            lddw r10, 1
+            lddw r3, 1
+            exit
         .rodata
             message: .ascii "Hello, Solana!"
             message_end:
@@ -792,6 +1862,84 @@ entrypoint:
         assert!(result.is_ok(), "Macro e2e failed: {:?}", result.err());
     }
 
+    #[test]
+    fn test_assemble_layout_e2e() {
+        let source = r#"
+.layout counter
+  bump: u8
+  count: u64
+.endlayout
+
+.globl entrypoint
+entrypoint:
+    COUNTER_LOAD_COUNT r2, r1+0x60
+    add64 r2, 1
+    COUNTER_STORE_COUNT r1+0x60, r2
+    mov64 r0, COUNTER_SIZE
+    exit
+"#;
+        let expected = r#"
+.globl entrypoint
+entrypoint:
+    ldxdw r2, [r1+0x61]
+    add64 r2, 1
+    stxdw [r1+0x61], r2
+    mov64 r0, 9
+    exit
+"#;
+        let assembler = Assembler::new(AssemblerOption::default());
+        let result = assembler.assemble_with_preprocess(source, "test.s", None);
+        assert!(result.is_ok(), "Layout e2e failed: {:?}", result.err());
+        assert_eq!(result.unwrap(), assemble(expected).unwrap());
+    }
+
+    #[test]
+    fn test_assemble_conditional_e2e() {
+        let source = r#"
+.ifndef LOG_LEVEL
+.equ LOG_LEVEL, 0
+.endif
+
+.macro LOG level
+.if LOG_LEVEL >= \level
+    call sol_log_
+.endif
+.endm
+
+.globl entrypoint
+entrypoint:
+    LOG 1
+    LOG 2
+.ifdef DEBUG
+    mov64 r0, DEBUG
+.else
+    mov64 r0, 0
+.endif
+    exit
+"#;
+        let build = |options: AssemblerOption| {
+            Assembler::new(options)
+                .assemble_with_preprocess(source, "test.s", None)
+                .unwrap()
+        };
+        let release = ".globl entrypoint\nentrypoint:\n    mov64 r0, 0\n    exit\n";
+        assert_eq!(
+            build(AssemblerOption::default()),
+            assemble(release).unwrap()
+        );
+
+        let debug =
+            ".globl entrypoint\nentrypoint:\n    call sol_log_\n    mov64 r0, 7\n    exit\n";
+        assert_eq!(
+            build(
+                AssemblerOption::default()
+                    .with_define("DEBUG", 7)
+                    .with_define("LOG_LEVEL", 1)
+            ),
+            assemble(debug).unwrap()
+        );
+    }
+
     #[test]
     fn test_assemble_label_arithmetic_cross_section_error() {
         // Cross-section arithmetic should fail
@@ -827,6 +1975,123 @@ entrypoint:
         assert!(result.is_ok(), "Failed: {:?}", result.err());
     }
 
+    #[test]
+    fn test_assemble_label_addend() {
+        let source = |operand: &str| {
+            format!(
+                ".globl entrypoint\n.rodata\nmsg: .ascii \"Hello, Solana!\"\nmsg_end: .byte 0\n.text\nentrypoint:\n    lddw r1, {operand}\n    exit\n"
+            )
+        };
+        let v0 = Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V0));
+        let relocs = v0
+            .relocations_with_preprocess(&source("msg+8"), "test.s", None)
+            .unwrap()
+            .get_rel_dyns();
+        assert_eq!(
+            relocs,
+            vec![(0, RelocationType::RSbf64Relative, "msg+8".to_string())]
+        );
+
+        // The addend lands in the immediate; the relocation still names the
+        // symbol so the loader rebases it.
+        let imm = |elf: &[u8]| {
+            let lddw = elf
+                .windows(2)
+                .position(|ix| ix == [0x18, 0x01])
+                .expect("lddw r1");
+            u32::from_le_bytes(elf[lddw + 4..lddw + 8].try_into().unwrap())
+        };
+        let plain = v0.assemble(&source("msg")).unwrap();
+        let offset = v0.assemble(&source("msg+8")).unwrap();
+        assert_eq!(imm(&offset), imm(&plain) + 8);
+        let difference = v0.assemble(&source("msg_end - msg + msg")).unwrap();
+        assert_eq!(imm(&difference), imm(&plain) + 14);
+
+        // Differences of labels fold to constants, even inside products.
+        let folded = assemble(&source("(msg_end - msg + 4) * 2")).unwrap();
+        let literal = assemble(&source("36")).unwrap();
+        assert_eq!(folded, literal);
+
+        for operand in ["(msg+4)*2", "msg*2", "msg + msg"] {
+            let errors = assemble(&source(operand)).unwrap_err();
+            assert_eq!(
+                errors[0].to_string(),
+                "Expression is not a link-time constant",
+                "{operand}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_assemble_typed_literals() {
+        let source = |fee: &str, amount: &str| {
+            format!(
+                ".globl entrypoint\n.equ FEE, {fee}\nentrypoint:\n    lddw r1, {amount}\n    mov64 r2, FEE\n    exit\n"
+            )
+        };
+        assert_eq!(
+            assemble(&source("@bp(2.5%)", "@lamports(1.5 SOL)")).unwrap(),
+            assemble(&source("250", "1500000000")).unwrap()
+        );
+        assert_eq!(
+            assemble(&source(
+                "@bp(250) * 2",
+                "@lamports(3 sol) + @lamports(5000)"
+            ))
+            .unwrap(),
+            assemble(&source("500", "3000005000")).unwrap()
+        );
+
+        for (fee, amount, error) in [
+            (
+                "0",
+                "@lamports(1.5)",
+                "Invalid literal '@lamports(1.5)': lamports must be a whole number",
+            ),
+            (
+                "0",
+                "@lamports(0.0000000001 SOL)",
+                "Invalid literal '@lamports(0.0000000001 SOL)': at most 9 decimal places are allowed",
+            ),
+            (
+                "0",
+                "@lamports(10000000000 SOL)",
+                "Invalid literal '@lamports(10000000000 SOL)': does not fit in a 64-bit immediate",
+            ),
+            (
+                "@bp(10001)",
+                "0",
+                "Invalid literal '@bp(10001)': basis points cannot exceed 10000 (100%)",
+            ),
+        ] {
+            let errors = assemble(&source(fee, amount)).unwrap_err();
+            assert_eq!(errors[0].to_string(), error);
+        }
+
+        // A 32-bit immediate is sign-extended, so 3 SOL would wrap.
+        let errors =
+            assemble(".globl entrypoint\nentrypoint:\n    mov64 r2, @lamports(3 SOL)\n    exit\n")
+                .unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Invalid literal '@lamports(3 SOL)': does not fit in a 32-bit immediate, load it with lddw"
+        );
+    }
+
+    #[test]
+    fn test_assemble_lddw_literal_rejected() {
+        for operand in ["=0x1122334455667788", "=msg"] {
+            let source = format!(
+                ".globl entrypoint\n.rodata\nmsg: .ascii \"Hello\"\n.text\nentrypoint:\n    lddw r1, {operand}\n    exit\n"
+            );
+            let errors = assemble(&source).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            let span = errors[0].span();
+            assert_eq!(&source[span.start..span.end], "=");
+            assert!(errors[0].to_string().contains("Literal pool"));
+        }
+    }
+
     #[test]
     fn test_parse_error_column_through_preprocess() {
         // Verify the column offset is correctly computed through the
@@ -926,6 +2191,178 @@ e:
         );
     }
 
+    #[test]
+    fn test_parse_error_inside_macro_body() {
+        let source = r#".macro CHECK_SIGNER acct fail
+    ldxb r2, [\acct+1]
+    .bogus r2
+    jeq r2, 0, \fail
+.endm
+.globl entrypoint
+entrypoint:
+    CHECK_SIGNER r1, abort
+    exit
+abort:
+    exit
+"#;
+        let errors = Assembler::new(AssemblerOption::default())
+            .assemble_with_preprocess(source, "main.s", None)
+            .unwrap_err();
+        let err = &errors.errors[0];
+        let origin = err.origin.as_ref().expect("Expected origin");
+
+        // The error points at the body line, with the invocation attached.
+        assert_eq!(origin.line, 3);
+        let expansion = origin.macro_expansion.as_ref().unwrap();
+        assert_eq!(expansion.macro_name, "CHECK_SIGNER");
+        assert_eq!(expansion.invocation_origin.line, 8);
+        assert_eq!(err.column, Some(4));
+    }
+
+    #[test]
+    fn test_verifier_errors() {
+        let source = ".globl entrypoint\nentrypoint:\n  div64 r1, 0\n  mov64 r0, 1\n";
+        let errors = assemble(source).unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.to_string(), &source[error.span().clone()]))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "Verifier error: Division by zero".to_string(),
+                    "div64 r1, 0"
+                ),
+                (
+                    "Verifier error: Execution falls through the end of the function".to_string(),
+                    "mov64 r0, 1"
+                ),
+            ]
+        );
+
+        let assembler = Assembler::new(AssemblerOption::default().without_verification());
+        assert!(assembler.assemble(source).is_ok());
+    }
+
+    #[test]
+    fn test_unencodable_instruction_is_an_internal_error() {
+        let source = ".globl entrypoint\nentrypoint:\n  mov64 r0, 1\n  exit\n";
+        let mut layout = parse(source, SbpfArch::default()).unwrap();
+        let mut nodes = layout.code_section.get_nodes().clone();
+        let mov = nodes
+            .iter_mut()
+            .find_map(|node| match node {
+                ASTNode::Instruction { instruction, .. } => Some(instruction),
+                _ => None,
+            })
+            .unwrap();
+        mov.imm = Some(either::Either::Left("missing".to_string()));
+        layout.code_section = section::CodeSection::new(nodes, 16);
+
+        let errors = check_encoding(&layout, SbpfArch::default()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .to_string()
+                .starts_with("Internal error: cannot encode instruction")
+        );
+        assert_eq!(&source[errors[0].span().clone()], "mov64 r0, 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Internal error: lost track")]
+    fn test_internal_errors_panic_mode() {
+        let errors = vec![bug!(0..1, "lost track of {}", "entrypoint")];
+        let assembler = Assembler::new(AssemblerOption::default());
+        assert_eq!(assembler.internal_errors(errors.clone()).len(), 1);
+
+        let assembler =
+            Assembler::new(AssemblerOption::default().with_internal_errors(InternalErrors::Panic));
+        assembler.internal_errors(errors);
+    }
+
+    /// Front-end for a language of `return <value>` statements.
+    struct ReturnFrontend;
+
+    impl Frontend for ReturnFrontend {
+        fn name(&self) -> &str {
+            "ret"
+        }
+
+        fn extension(&self) -> &str {
+            "ret"
+        }
+
+        fn translate(&self, source: &str, _path: &str) -> Result<Translation, Vec<FrontendError>> {
+            let mut translation = Translation::new();
+            translation.push_line(".globl entrypoint", 0);
+            translation.push_line("entrypoint:", 0);
+            let mut errors = Vec::new();
+            for (line, text) in (1..).zip(source.lines()) {
+                match text.trim().strip_prefix("return ") {
+                    Some(value) => {
+                        translation.push_line(&format!("  mov64 r0, {}", value), line);
+                        translation.push_line("  exit", line);
+                    }
+                    None if text.trim().is_empty() => {}
+                    None => errors.push(
+                        FrontendError::new("expected `return <value>`")
+                            .with_line(line)
+                            .with_column(text.len() - text.trim_start().len()),
+                    ),
+                }
+            }
+            if errors.is_empty() {
+                Ok(translation)
+            } else {
+                Err(errors)
+            }
+        }
+    }
+
+    #[test]
+    fn test_frontend_translation() {
+        let assembler = Assembler::new(AssemblerOption::default().with_frontend(ReturnFrontend));
+
+        let artifacts = assembler
+            .assemble_full_with_preprocess("\nreturn 7\n", "prog.ret", None)
+            .unwrap();
+        assert_eq!(
+            artifacts
+                .sourcemap
+                .iter()
+                .map(|mapping| (mapping.file.as_str(), mapping.line))
+                .collect::<Vec<_>>(),
+            [("prog.ret", 2), ("prog.ret", 2)]
+        );
+        // Sources without a registered extension are still assembly.
+        assert!(
+            assembler
+                .assemble_with_preprocess("return 7\n", "prog.s", None)
+                .is_err()
+        );
+
+        // Errors from the front-end point at its source.
+        let failure = assembler
+            .assemble_with_preprocess("return 1\n  jump 2\n", "prog.ret", None)
+            .unwrap_err();
+        assert_eq!(failure.errors.len(), 1);
+        let error = &failure.errors[0];
+        assert_eq!(error.to_string(), "ret: expected `return <value>`");
+        let origin = error.origin.as_ref().unwrap();
+        assert_eq!(failure.file_registry.path(origin.file_id), "prog.ret");
+        assert_eq!((origin.line, error.column), (2, Some(2)));
+
+        // So do errors in the assembly it generates.
+        let failure = assembler
+            .assemble_with_preprocess("return 1\nreturn r11\n", "prog.ret", None)
+            .unwrap_err();
+        let origin = failure.errors[0].origin.as_ref().unwrap();
+        assert_eq!(failure.file_registry.path(origin.file_id), "prog.ret");
+        assert_eq!(origin.line, 2);
+    }
+
+    #[cfg(feature = "dwarf")]
     #[test]
     fn test_assemble_with_debug_data() {
         let source = r#".equ MSG_LEN, 14
@@ -0,0 +1,436 @@
+use {
+    crate::{
+        artifacts::PhaseTimings,
+        ast::AST,
+        astnode::ASTNode,
+        errors::CompileError,
+        parser::{
+            Token,
+            common::{split_addend, with_addend},
+        },
+        preprocessor::source_map::{FileRegistry, SourceMap},
+    },
+    std::{
+        collections::{HashMap, HashSet, hash_map::Entry},
+        ops::Range,
+    },
+};
+
+/// One source file parsed into its own symbol scope, ready to be linked with
+/// others by [`crate::Assembler::link`].
+///
+/// Labels are local to the object they are defined in unless it declares them
+/// `.globl`. Names an object declares `.globl` or `.extern` without defining
+/// them are imports, resolved against the other objects' exports.
+#[derive(Debug)]
+pub struct Object {
+    path: String,
+    /// Preprocessed source the AST's spans point into.
+    source: String,
+    source_map: SourceMap,
+    ast: AST,
+    /// Time spent preprocessing and parsing the object.
+    pub(crate) timings: PhaseTimings,
+}
+
+impl Object {
+    pub(crate) fn new(
+        path: &str,
+        source: String,
+        source_map: SourceMap,
+        ast: AST,
+        timings: PhaseTimings,
+    ) -> Self {
+        Self {
+            path: path.to_string(),
+            source,
+            source_map,
+            ast,
+            timings,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Labels this object defines and declares `.globl`.
+    pub fn exports(&self) -> Vec<String> {
+        let symbols = Symbols::of(&self.ast);
+        let mut exports: Vec<String> = symbols
+            .globals
+            .into_iter()
+            .filter(|(name, _)| symbols.defined.contains(name))
+            .map(|(name, _)| name)
+            .collect();
+        exports.dedup();
+        exports
+    }
+
+    /// Names this object expects another object to define.
+    pub fn imports(&self) -> Vec<String> {
+        let symbols = Symbols::of(&self.ast);
+        let mut imports: Vec<String> = symbols
+            .globals
+            .into_iter()
+            .chain(symbols.externs)
+            .filter(|(name, _)| !symbols.defined.contains(name))
+            .map(|(name, _)| name)
+            .collect();
+        imports.dedup();
+        imports
+    }
+}
+
+/// Symbols an object declares, with the spans of their declarations.
+struct Symbols {
+    defined: HashSet<String>,
+    globals: Vec<(String, Range<usize>)>,
+    externs: Vec<(String, Range<usize>)>,
+}
+
+impl Symbols {
+    fn of(ast: &AST) -> Self {
+        let mut defined = HashSet::new();
+        let mut globals = Vec::new();
+        let mut externs = Vec::new();
+        for node in ast
+            .nodes
+            .iter()
+            .chain(&ast.rodata_nodes)
+            .chain(&ast.data_nodes)
+            .chain(&ast.bss_nodes)
+        {
+            match node {
+                ASTNode::Label { label, .. } => {
+                    defined.insert(label.name.clone());
+                }
+                ASTNode::ROData { rodata, .. } if !rodata.is_unlabeled() => {
+                    defined.insert(rodata.name.clone());
+                }
+                ASTNode::GlobalDecl { global_decl } => {
+                    globals.push((global_decl.entry_label.clone(), global_decl.span.clone()));
+                }
+                ASTNode::ExternDecl { extern_decl } => {
+                    externs.extend(extern_decl.args.iter().filter_map(|arg| match arg {
+                        Token::Identifier(name, span) => Some((name.clone(), span.clone())),
+                        _ => None,
+                    }));
+                }
+                _ => {}
+            }
+        }
+        Self {
+            defined,
+            globals,
+            externs,
+        }
+    }
+
+    fn is_global(&self, name: &str) -> bool {
+        self.globals.iter().any(|(global, _)| global == name)
+    }
+}
+
+/// Objects combined into one program, along with everything needed to report
+/// errors against the original files.
+pub(crate) struct Linked {
+    pub ast: AST,
+    /// All objects' preprocessed sources, concatenated.
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+/// Place `objects` one after another and resolve symbols between them.
+///
+/// Local labels defined in more than one object are renamed to
+/// `<label>@<path>` so each object keeps its own. References to another
+/// object's local labels, exports defined twice and imports nobody exports
+/// are reported; anything else left undefined is reported by
+/// [`crate::ast::build_program`] as usual.
+pub(crate) fn link(objects: Vec<Object>) -> (Linked, Vec<CompileError>) {
+    let symbols: Vec<Symbols> = objects
+        .iter()
+        .map(|object| Symbols::of(&object.ast))
+        .collect();
+    let mut errors = Vec::new();
+
+    let mut span_bases = Vec::with_capacity(objects.len());
+    let mut span_base = 0;
+    for object in &objects {
+        span_bases.push(span_base);
+        span_base += object.source.len();
+    }
+    let shifted = |span: &Range<usize>, index: usize| {
+        span.start + span_bases[index]..span.end + span_bases[index]
+    };
+
+    let mut exports: HashMap<&str, usize> = HashMap::new();
+    for (index, object_symbols) in symbols.iter().enumerate() {
+        for (name, span) in &object_symbols.globals {
+            if !object_symbols.defined.contains(name) {
+                continue;
+            }
+            match exports.entry(name.as_str()) {
+                Entry::Occupied(first) if *first.get() != index => {
+                    errors.push(CompileError::DuplicateSymbol {
+                        symbol: name.clone(),
+                        file: objects[*first.get()].path.clone(),
+                        span: shifted(span, index),
+                        custom_label: None,
+                    });
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(index);
+                }
+            }
+        }
+    }
+
+    let mut definitions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, object_symbols) in symbols.iter().enumerate() {
+        for name in &object_symbols.defined {
+            definitions.entry(name).or_default().push(index);
+        }
+    }
+
+    let mut ast = AST::new();
+    let mut source = String::new();
+    let mut source_map = SourceMap::new(FileRegistry::new(), Vec::new());
+    let (mut text_size, mut rodata_size, mut data_size, mut bss_size) = (0, 0, 0, 0);
+    for (index, object) in objects.iter().enumerate() {
+        let object_symbols = &symbols[index];
+        let renames: HashMap<&str, String> = object_symbols
+            .defined
+            .iter()
+            .filter(|name| {
+                !is_numeric_label(name)
+                    && !object_symbols.is_global(name)
+                    && definitions[name.as_str()].len() > 1
+            })
+            .map(|name| (name.as_str(), format!("{}@{}", name, object.path)))
+            .collect();
+        let rename = |name: &mut String| {
+            if let Some(renamed) = renames.get(name.as_str()) {
+                *name = renamed.clone();
+            }
+        };
+
+        for (name, span) in object_symbols.globals.iter().chain(&object_symbols.externs) {
+            if !object_symbols.defined.contains(name)
+                && !exports.contains_key(name.as_str())
+                && !object.ast.syscalls().contains(name)
+            {
+                errors.push(CompileError::UnresolvedExtern {
+                    symbol: name.clone(),
+                    span: shifted(span, index),
+                    custom_label: None,
+                });
+            }
+        }
+
+        let mut nodes = object.ast.nodes.clone();
+        let mut rodata_nodes = object.ast.rodata_nodes.clone();
+        let mut data_nodes = object.ast.data_nodes.clone();
+        for data_node in &mut data_nodes {
+            if let ASTNode::ROData { rodata, .. } = data_node {
+                rename(&mut rodata.name);
+            }
+            data_node.relocate(span_bases[index], text_size, data_size);
+        }
+        let mut bss_nodes = object.ast.bss_nodes.clone();
+        for bss_node in &mut bss_nodes {
+            if let ASTNode::ROData { rodata, .. } = bss_node {
+                rename(&mut rodata.name);
+            }
+            bss_node.relocate(span_bases[index], text_size, bss_size);
+        }
+        for node in nodes.iter_mut().chain(rodata_nodes.iter_mut()) {
+            match node {
+                ASTNode::Label { label, .. } => rename(&mut label.name),
+                ASTNode::ROData { rodata, .. } => rename(&mut rodata.name),
+                ASTNode::Instruction { instruction, .. } => {
+                    let references = [
+                        instruction.off.as_mut().and_then(|off| off.as_mut().left()),
+                        instruction.imm.as_mut().and_then(|imm| imm.as_mut().left()),
+                    ];
+                    for name in references.into_iter().flatten() {
+                        let (symbol, addend) = split_addend(name);
+                        if object_symbols.defined.contains(symbol) {
+                            if let Some(renamed) = renames.get(symbol) {
+                                *name = with_addend(renamed, addend);
+                            }
+                        } else if !is_numeric_label(name)
+                            && !exports.contains_key(name.as_str())
+                            && let Some(owners) = definitions.get(name.as_str())
+                        {
+                            errors.push(CompileError::SymbolNotGlobal {
+                                symbol: name.clone(),
+                                file: objects[owners[0]].path.clone(),
+                                span: shifted(&instruction.span, index),
+                                custom_label: None,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+            node.relocate(span_bases[index], text_size, rodata_size);
+        }
+
+        ast.nodes.extend(nodes);
+        ast.rodata_nodes.extend(rodata_nodes);
+        ast.data_nodes.extend(data_nodes);
+        ast.bss_nodes.extend(bss_nodes);
+        ast.source_locations.extend(
+            object
+                .ast
+                .source_locations
+                .iter()
+                .map(|(position, location)| (position + span_bases[index], location.clone())),
+        );
+        ast.warnings
+            .extend(object.ast.warnings.iter().cloned().map(|mut warning| {
+                *warning.span_mut() = shifted(warning.span(), index);
+                warning
+            }));
+        for (error, span) in &object.ast.custom_errors {
+            if ast
+                .custom_errors
+                .iter()
+                .any(|(other, _)| other.code == error.code)
+            {
+                errors.push(CompileError::DuplicateErrorCode {
+                    code: error.code,
+                    span: shifted(span, index),
+                    custom_label: None,
+                });
+            } else {
+                ast.custom_errors
+                    .push((error.clone(), shifted(span, index)));
+            }
+        }
+        text_size += object.ast.text_size();
+        rodata_size += object.ast.rodata_size();
+        data_size += object.ast.data_size();
+        bss_size += object.ast.bss_size();
+        source.push_str(&object.source);
+        source_map.append(object.source_map.clone());
+    }
+
+    if let Some(first) = objects.first() {
+        ast.set_syscalls(first.ast.syscalls().clone());
+    }
+    ast.set_text_size(text_size);
+    ast.set_rodata_size(rodata_size);
+    ast.set_data_size(data_size);
+    ast.set_bss_size(bss_size);
+    (
+        Linked {
+            ast,
+            source,
+            source_map,
+        },
+        errors,
+    )
+}
+
+/// Whether `name` is a numeric label (`1`) or a reference to one (`1f`, `1b`).
+/// Those are resolved by position, not name, so they are never renamed.
+fn is_numeric_label(name: &str) -> bool {
+    let digits = name.trim_end_matches(['f', 'b']);
+    !digits.is_empty()
+        && digits.len() + 1 >= name.len()
+        && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Assembler, AssemblerOption},
+    };
+
+    fn object(path: &str, source: &str) -> Object {
+        Assembler::new(AssemblerOption::default())
+            .assemble_object(source, path, None)
+            .unwrap()
+    }
+
+    fn link_errors(objects: Vec<Object>) -> Vec<String> {
+        link(objects)
+            .1
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_is_numeric_label() {
+        assert!(is_numeric_label("1"));
+        assert!(is_numeric_label("12f"));
+        assert!(is_numeric_label("3b"));
+        assert!(!is_numeric_label("f"));
+        assert!(!is_numeric_label("1ff"));
+        assert!(!is_numeric_label("loop"));
+    }
+
+    #[test]
+    fn test_exports_and_imports() {
+        let object = object(
+            "main.s",
+            ".globl entrypoint\n.extern helper\nentrypoint:\n  call helper\n  exit\n",
+        );
+        assert_eq!(object.exports(), vec!["entrypoint"]);
+        assert_eq!(object.imports(), vec!["helper"]);
+    }
+
+    #[test]
+    fn test_local_labels_are_renamed_on_collision() {
+        let (linked, errors) = link(vec![
+            object("a.s", ".globl entrypoint\nentrypoint:\nloop:\n  ja loop\n"),
+            object("b.s", ".globl helper\nhelper:\nloop:\n  ja loop\n"),
+        ]);
+        assert!(errors.is_empty());
+        let labels: Vec<&str> = linked
+            .ast
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                ASTNode::Label { label, .. } => Some(label.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, vec!["entrypoint", "loop@a.s", "helper", "loop@b.s"]);
+        assert_eq!(linked.source_map.len(), 8);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        assert_eq!(
+            link_errors(vec![
+                object(
+                    "a.s",
+                    ".globl entrypoint\nentrypoint:\n  call helper\n  exit\n"
+                ),
+                object("b.s", "helper:\n  exit\n"),
+            ]),
+            vec!["'helper' is defined in b.s but not declared .globl there"]
+        );
+        assert_eq!(
+            link_errors(vec![
+                object("a.s", ".globl helper\nhelper:\n  exit\n"),
+                object("b.s", ".globl helper\nhelper:\n  exit\n"),
+            ]),
+            vec!["Symbol 'helper' is also declared .globl in a.s"]
+        );
+        assert_eq!(
+            link_errors(vec![object(
+                "a.s",
+                ".extern helper sol_log_\n.globl entrypoint\nentrypoint:\n  exit\n"
+            )]),
+            vec!["'helper' is declared external but no file defines it as .globl"]
+        );
+    }
+}
@@ -0,0 +1,658 @@
+use {
+    crate::{
+        ast::AST,
+        astnode::{ASTNode, ROData},
+        errors::CompileError,
+        optimizer::{canonicalize_control_flow_targets, resolve_jump_addends},
+        parser::{Token, common::split_addend},
+    },
+    either::Either,
+    sbpf_common::{
+        inst_param::Register,
+        instruction::Instruction,
+        lint::LintConfig,
+        opcode::{LOAD_MEMORY_OPS, Opcode, STORE_IMM_OPS, STORE_REG_OPS},
+    },
+    sbpf_ir::{InputNode, control_flow_graph, graph_engine::DfsEngine},
+    std::{
+        collections::{HashMap, HashSet},
+        ops::Range,
+    },
+};
+
+/// Codes of the warnings the assembler reports, which a [`LintConfig`] can
+/// set the level of.
+pub const LINTS: [&str; 8] = [
+    "byte-swapped-data",
+    "unreachable-code",
+    "unused-label",
+    "unused-constant",
+    "shadowed-constant",
+    "writable-data",
+    "stack-limit-exceeded",
+    "compute-limit-exceeded",
+];
+
+/// Warn about instructions nothing can reach: code after an `exit` or an
+/// unconditional jump that no label names. Labelled blocks count as reached,
+/// since a label may be jumped to, called or loaded as a function pointer;
+/// everything else must be reached by falling through from them. Each run of
+/// unreachable instructions is reported once.
+pub(crate) fn check_unreachable_code(ast: &AST) -> Vec<CompileError> {
+    // Jumps by offset name their target with a temporary label, so that the
+    // target starts a labelled block like any other.
+    let mut nodes = ast.nodes.clone();
+    if !canonicalize_control_flow_targets(&mut nodes)
+        .errors
+        .is_empty()
+    {
+        return Vec::new();
+    }
+    // Labels after the last instruction start no block.
+    let end = nodes
+        .iter()
+        .rposition(|node| matches!(node, ASTNode::Instruction { .. }))
+        .map_or(0, |last| last + 1);
+    let cfg = control_flow_graph(
+        nodes[..end].iter().map(|node| match node {
+            ASTNode::Label { label, .. } => InputNode::Label(label.name.as_str()),
+            ASTNode::Instruction { instruction, .. } => InputNode::Instruction(instruction),
+            _ => InputNode::Other,
+        }),
+        &HashSet::new(),
+        None,
+    );
+
+    let roots = cfg
+        .all_blocks()
+        .filter(|(id, block)| *id == 0 || !block.labels().is_empty())
+        .map(|(id, _)| id);
+    let mut reached = HashSet::new();
+    DfsEngine::new(&cfg).visit_many(roots, &mut |id| {
+        reached.insert(id);
+    });
+
+    let mut warnings = Vec::new();
+    let mut run: Option<Range<usize>> = None;
+    for (id, block) in cfg.all_blocks() {
+        if reached.contains(&id) {
+            warnings.extend(run.take().map(|span| CompileError::UnreachableCode {
+                span,
+                custom_label: None,
+            }));
+            continue;
+        }
+        for &node_id in block.node_ids() {
+            let ASTNode::Instruction { instruction, .. } = &nodes[node_id] else {
+                continue;
+            };
+            let span = &instruction.span;
+            run = match run {
+                Some(run) => Some(run.start..span.end),
+                None => Some(span.clone()),
+            };
+        }
+    }
+    warnings.extend(run.map(|span| CompileError::UnreachableCode {
+        span,
+        custom_label: None,
+    }));
+    warnings
+}
+
+/// Warn about `.data` and `.bss`, which the Solana program loader refuses to
+/// load, so a program using them only runs in `sbpf-vm`. Each is reported
+/// once, at its first directive.
+pub(crate) fn check_writable_data(ast: &AST) -> Vec<CompileError> {
+    [(".data", &ast.data_nodes), (".bss", &ast.bss_nodes)]
+        .into_iter()
+        .filter_map(|(section, nodes)| {
+            nodes.iter().find_map(|node| match node {
+                ASTNode::ROData { rodata, .. } => Some(CompileError::WritableData {
+                    section: section.to_string(),
+                    span: rodata.span.clone(),
+                    custom_label: None,
+                }),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+/// Warn about data stored little-endian that the code reads back with a
+/// byte-swapping load, as when a big-endian field of a network message is
+/// written with `.quad` where it should be `.be64`.
+///
+/// Only the straight-line code after an `lddw` of a data label is followed:
+/// a load through that register, then a `be` of the same width on the loaded
+/// value before it is overwritten.
+pub(crate) fn check_byte_order(ast: &AST) -> Vec<CompileError> {
+    let little_endian: HashMap<&str, (&str, u64)> = ast
+        .rodata_nodes
+        .iter()
+        .chain(&ast.data_nodes)
+        .filter_map(|node| match node {
+            ASTNode::ROData { rodata, .. } if !rodata.is_unlabeled() => {
+                little_endian_width(rodata).map(|width| (rodata.name.as_str(), width))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut reported = Vec::new();
+    for (idx, node) in ast.nodes.iter().enumerate() {
+        let ASTNode::Instruction { instruction, .. } = node else {
+            continue;
+        };
+        let (Opcode::Lddw, Some(base), Some(Either::Left(target))) =
+            (instruction.opcode, &instruction.dst, &instruction.imm)
+        else {
+            continue;
+        };
+        let (label, _) = split_addend(target);
+        let Some(&(directive, width)) = little_endian.get(label) else {
+            continue;
+        };
+        if reported.contains(&label) {
+            continue;
+        }
+        if let Some(span) = find_swapped_load(&ast.nodes[idx + 1..], base, width) {
+            warnings.push(CompileError::ByteSwappedData {
+                label: label.to_string(),
+                directive: directive.to_string(),
+                span,
+                custom_label: Some(format!(
+                    "Declare '{}' with .be{} to store it big-endian",
+                    label,
+                    width * 8
+                )),
+            });
+            reported.push(label);
+        }
+    }
+    warnings
+}
+
+/// Directive and value width of data stored in little-endian order by a
+/// directive that does not say so.
+fn little_endian_width(rodata: &ROData) -> Option<(&str, u64)> {
+    let Some(Token::Directive(directive, _)) = rodata.args.first() else {
+        return None;
+    };
+    let width = match directive.as_str() {
+        "short" | "word" => 2,
+        "int" | "long" => 4,
+        "quad" => 8,
+        _ => return None,
+    };
+    Some((directive.as_str(), width))
+}
+
+/// Span of a `be` reversing a `width`-byte value loaded through `base`, in
+/// the straight-line code at the start of `nodes`.
+fn find_swapped_load(nodes: &[ASTNode], base: &Register, width: u64) -> Option<Range<usize>> {
+    let mut loaded: Vec<Register> = Vec::new();
+    for node in nodes {
+        let instruction = match node {
+            ASTNode::Label { .. } => return None,
+            ASTNode::Instruction { instruction, .. } => instruction,
+            _ => continue,
+        };
+        if instruction.is_jump()
+            || matches!(
+                instruction.opcode,
+                Opcode::Call | Opcode::Callx | Opcode::Exit
+            )
+        {
+            return None;
+        }
+        let Some(dst) = &instruction.dst else {
+            continue;
+        };
+        if instruction.opcode == Opcode::Be
+            && loaded.contains(dst)
+            && swap_bits(instruction) == Some(width * 8)
+        {
+            return Some(instruction.span.clone());
+        }
+        if STORE_IMM_OPS.contains(&instruction.opcode)
+            || STORE_REG_OPS.contains(&instruction.opcode)
+        {
+            continue;
+        }
+        loaded.retain(|register| register != dst);
+        if LOAD_MEMORY_OPS.contains(&instruction.opcode)
+            && instruction.src.as_ref() == Some(base)
+            && load_width(instruction.opcode) == Some(width)
+        {
+            loaded.push(dst.clone());
+        }
+        if dst == base {
+            return None;
+        }
+    }
+    None
+}
+
+fn load_width(opcode: Opcode) -> Option<u64> {
+    match opcode {
+        Opcode::Ldxh => Some(2),
+        Opcode::Ldxw => Some(4),
+        Opcode::Ldxdw => Some(8),
+        _ => None,
+    }
+}
+
+fn swap_bits(instruction: &Instruction) -> Option<u64> {
+    match &instruction.imm {
+        Some(Either::Right(bits)) => Some(bits.to_i64() as u64),
+        _ => None,
+    }
+}
+
+/// Warn about functions over the `max-stack` or `max-compute-units` limits
+/// of `lints`. A function starts at the entrypoint or a called label and runs
+/// up to the next one. Its stack is its deepest access below `r10`, and its
+/// compute units those of its longest path: one per instruction, plus what
+/// the functions it calls use. Backward jumps are not followed, so a loop
+/// counts once.
+pub(crate) fn check_limits(ast: &AST, lints: &LintConfig) -> Vec<CompileError> {
+    if lints.max_stack.is_none() && lints.max_compute_units.is_none() {
+        return Vec::new();
+    }
+    let mut nodes = ast.nodes.clone();
+    if !resolve_jump_addends(&mut nodes).errors.is_empty()
+        || !canonicalize_control_flow_targets(&mut nodes)
+            .errors
+            .is_empty()
+    {
+        return Vec::new();
+    }
+    let functions = Functions::new(&nodes);
+
+    let mut warnings = Vec::new();
+    let mut costs = HashMap::new();
+    for (function, (name, span)) in functions.names.iter().enumerate() {
+        let range = functions.ranges[function].clone();
+        if let Some(limit) = lints.max_stack
+            && let Some((size, instruction)) = functions.instructions[range]
+                .iter()
+                .filter_map(|instruction| Some((stack_depth(instruction)?, instruction)))
+                .max_by_key(|(size, _)| *size)
+            && size > limit
+        {
+            warnings.push(CompileError::StackLimitExceeded {
+                function: name.to_string(),
+                size,
+                limit,
+                span: instruction.span.clone(),
+                custom_label: None,
+            });
+        }
+        if let Some(limit) = lints.max_compute_units {
+            let units = functions.compute_units(function, &mut costs, &mut HashSet::new());
+            if units > limit {
+                warnings.push(CompileError::ComputeLimitExceeded {
+                    function: name.to_string(),
+                    units,
+                    limit,
+                    span: span.clone(),
+                    custom_label: None,
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// The instructions of a program split into functions.
+struct Functions<'a> {
+    instructions: Vec<&'a Instruction>,
+    /// Index of the instruction each label names.
+    labels: HashMap<&'a str, usize>,
+    /// Entry label and its span, by function.
+    names: Vec<(&'a str, Range<usize>)>,
+    /// Instructions of each function.
+    ranges: Vec<Range<usize>>,
+}
+
+impl<'a> Functions<'a> {
+    fn new(nodes: &'a [ASTNode]) -> Self {
+        let mut instructions = Vec::new();
+        let mut labels = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut entries = Vec::new();
+        for node in nodes {
+            match node {
+                ASTNode::Label { label, .. } => {
+                    labels.insert(label.name.as_str(), instructions.len());
+                    spans.insert(label.name.as_str(), label.span.clone());
+                }
+                ASTNode::Instruction { instruction, .. } => {
+                    if instruction.opcode == Opcode::Call
+                        && let Some(Either::Left(target)) = &instruction.imm
+                    {
+                        entries.push(target.as_str());
+                    }
+                    instructions.push(instruction);
+                }
+                ASTNode::GlobalDecl { global_decl } => {
+                    entries.push(global_decl.entry_label.as_str());
+                }
+                _ => {}
+            }
+        }
+
+        // Calls to syscalls name no label.
+        let mut starts: Vec<(usize, &str)> = entries
+            .into_iter()
+            .filter_map(|entry| Some((*labels.get(entry)?, entry)))
+            .filter(|&(start, _)| start < instructions.len())
+            .collect();
+        starts.sort();
+        starts.dedup_by_key(|(start, _)| *start);
+        let ends = starts
+            .iter()
+            .skip(1)
+            .map(|&(start, _)| start)
+            .chain([instructions.len()]);
+        let ranges = starts
+            .iter()
+            .zip(ends)
+            .map(|(&(start, _), end)| start..end)
+            .collect();
+        let names = starts
+            .iter()
+            .map(|&(_, name)| (name, spans[name].clone()))
+            .collect();
+        Self {
+            instructions,
+            labels,
+            names,
+            ranges,
+        }
+    }
+
+    /// Compute units on the longest path through `function`. A recursive
+    /// call counts as the call instruction alone.
+    fn compute_units(
+        &self,
+        function: usize,
+        costs: &mut HashMap<usize, u64>,
+        calling: &mut HashSet<usize>,
+    ) -> u64 {
+        if let Some(&units) = costs.get(&function) {
+            return units;
+        }
+        if !calling.insert(function) {
+            return 0;
+        }
+        let range = self.ranges[function].clone();
+        // Units from each instruction of the function to its end.
+        let mut units = vec![0; range.len()];
+        for index in range.clone().rev() {
+            let instruction = self.instructions[index];
+            let mut own = 1;
+            if instruction.opcode == Opcode::Call
+                && let Some(Either::Left(target)) = &instruction.imm
+                && let Some(callee) = self.function_at(target)
+            {
+                own += self.compute_units(callee, costs, calling);
+            }
+            let from = |target: usize| {
+                if target > index && target < range.end {
+                    units[target - range.start]
+                } else {
+                    0
+                }
+            };
+            let target = match &instruction.off {
+                Some(Either::Left(label)) if instruction.is_jump() => self
+                    .labels
+                    .get(label.as_str())
+                    .map_or(0, |&target| from(target)),
+                _ => 0,
+            };
+            let rest = match instruction.opcode {
+                Opcode::Exit => 0,
+                Opcode::Ja => target,
+                _ if instruction.is_jump() => target.max(from(index + 1)),
+                _ => from(index + 1),
+            };
+            units[index - range.start] = own + rest;
+        }
+        calling.remove(&function);
+        costs.insert(function, units[0]);
+        units[0]
+    }
+
+    fn function_at(&self, label: &str) -> Option<usize> {
+        let start = *self.labels.get(label)?;
+        self.ranges.iter().position(|range| range.start == start)
+    }
+}
+
+/// Bytes below `r10` a load or store reaches.
+fn stack_depth(instruction: &Instruction) -> Option<u64> {
+    let base = if LOAD_MEMORY_OPS.contains(&instruction.opcode) {
+        instruction.src.as_ref()
+    } else if STORE_IMM_OPS.contains(&instruction.opcode)
+        || STORE_REG_OPS.contains(&instruction.opcode)
+    {
+        instruction.dst.as_ref()
+    } else {
+        return None;
+    };
+    match (base, &instruction.off) {
+        (Some(base), Some(Either::Right(off))) if base.n == 10 && *off < 0 => {
+            Some(u64::from(off.unsigned_abs()))
+        }
+        _ => None,
+    }
+}
+
+/// Whether an `sbpf-allow(rule)` comment silences a diagnostic on `line`
+/// (counting from 1) of `source`: one on the line itself, or above it with
+/// only comments and blank lines in between, so it attaches to the next
+/// instruction or label. One comment can name several rules, separated by
+/// commas.
+pub fn is_allowed(source: &str, line: u32, rule: &str) -> bool {
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(index) = (line as usize).checked_sub(1).filter(|&i| i < lines.len()) else {
+        return false;
+    };
+    allows(lines[index], rule)
+        || lines[..index]
+            .iter()
+            .rev()
+            .take_while(|text| is_comment_line(text))
+            .any(|text| allows(text, rule))
+}
+
+/// Whether an `sbpf-allow` comment in `source` silences `warning`, whose
+/// span points into `source`.
+pub(crate) fn is_silenced(warning: &CompileError, source: &str) -> bool {
+    let before = source.get(..warning.span().start).unwrap_or(source);
+    let line = before.matches('\n').count() + 1;
+    is_allowed(source, line as u32, &warning.code())
+}
+
+fn is_comment_line(text: &str) -> bool {
+    let text = text.trim_start();
+    text.is_empty() || text.starts_with(';') || text.starts_with('#') || text.starts_with("//")
+}
+
+fn allows(text: &str, rule: &str) -> bool {
+    text.match_indices("sbpf-allow(").any(|(start, marker)| {
+        text[start + marker.len()..]
+            .split(')')
+            .next()
+            .is_some_and(|names| names.split(',').any(|name| name.trim() == rule))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::is_allowed,
+        crate::{
+            Assembler, AssemblerOption, LintConfig, LintLevel, SbpfArch, errors::CompileError,
+        },
+    };
+
+    fn warnings(source: &str) -> Vec<CompileError> {
+        Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V0))
+            .assemble_full(source)
+            .unwrap()
+            .warnings
+    }
+
+    #[test]
+    fn test_quad_read_with_be64() {
+        let source = ".globl e\n.rodata\nport: .quad 0x1234\n.text\ne:\n  lddw r1, port\n  ldxdw r2, [r1+0]\n  be64 r2\n  exit\n";
+        let found = warnings(source);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].to_string(),
+            "'port' is stored little-endian with .quad but read with a byte swap"
+        );
+        assert_eq!(
+            found[0].label(),
+            "Declare 'port' with .be64 to store it big-endian"
+        );
+    }
+
+    #[test]
+    fn test_writable_data() {
+        let source = ".globl e\ne:\n  exit\n.data\ncounter: .quad 0\n  .quad 1\nother: .byte 2\n.bss\nscratch: .zero 64\n";
+        let found: Vec<_> = warnings(source)
+            .into_iter()
+            .filter(|warning| warning.code() == "writable-data")
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0].to_string(),
+            "The Solana program loader rejects writable section '.data'"
+        );
+        assert_eq!(
+            found[1].to_string(),
+            "The Solana program loader rejects writable section '.bss'"
+        );
+        assert!(
+            warnings(".globl e\ne:\n  exit\n.rodata\nfee: .quad 0\n")
+                .iter()
+                .all(|warning| warning.code() != "writable-data")
+        );
+    }
+
+    #[test]
+    fn test_explicit_or_unswapped_data_is_quiet() {
+        let base =
+            ".globl e\n.rodata\nport: DIRECTIVE 0x1234\n.text\ne:\n  lddw r1, port\nBODY  exit\n";
+        let quiet = [
+            (".be64", "  ldxdw r2, [r1+0]\n  be64 r2\n"),
+            (".quad", "  ldxdw r2, [r1+0]\n"),
+            (".quad", "  ldxdw r2, [r1+0]\n  be32 r2\n"),
+            (".quad", "  ldxdw r2, [r1+0]\n  mov64 r2, 1\n  be64 r2\n"),
+            (".quad", "  ldxdw r2, [r1+0]\n  ja next\nnext:\n  be64 r2\n"),
+            (".quad", "  mov64 r1, 0\n  ldxdw r2, [r1+0]\n  be64 r2\n"),
+        ];
+        for (directive, body) in quiet {
+            let source = base.replace("DIRECTIVE", directive).replace("BODY", body);
+            assert!(warnings(&source).is_empty(), "{}", source);
+        }
+        let int = base
+            .replace("DIRECTIVE", ".int")
+            .replace("BODY", "  ldxw r3, [r1+0]\n  stxw [r10-4], r3\n  be32 r3\n");
+        assert_eq!(warnings(&int).len(), 1);
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let source = ".globl e\ne:\n  jeq r1, 0, other\n  exit\n  mov64 r0, 1\n  add64 r0, 2\nother:\n  ja done\n  mov64 r0, 3\ndone:\n  exit\n";
+        let found = warnings(source);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].to_string(), "Unreachable code");
+        assert_eq!(
+            &source[found[0].span().clone()],
+            "mov64 r0, 1\n  add64 r0, 2"
+        );
+        assert_eq!(&source[found[1].span().clone()], "mov64 r0, 3");
+    }
+
+    #[test]
+    fn test_reachable_code_is_quiet() {
+        let quiet = [
+            // Conditional jumps fall through.
+            ".globl e\ne:\n  jeq r1, 0, done\n  mov64 r0, 1\ndone:\n  exit\n",
+            // Jumps by offset reach their target.
+            ".globl e\ne:\n  jeq r1, 0, +2\n  mov64 r0, 1\n  exit\n  mov64 r0, 2\n  exit\n",
+            // Labelled code may be called or loaded as a function pointer.
+            ".globl e\ne:\n  lddw r1, f\n  callx r1\n  exit\nf:\n  exit\n",
+            // A label after the last instruction starts nothing.
+            ".globl e\ne:\n  exit\nend:\n",
+        ];
+        for source in quiet {
+            assert!(
+                !warnings(source)
+                    .iter()
+                    .any(|warning| matches!(warning, CompileError::UnreachableCode { .. })),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_lint_api() {
+        let assembler = Assembler::new(AssemblerOption::default());
+        let found = assembler.lint(".globl e\ne:\n  exit\n  exit\n").unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(assembler.lint("e:\n  bogus r0\n").is_err());
+    }
+
+    #[test]
+    fn test_sbpf_allow() {
+        let source = ".globl e\n.text\ne:\n  exit\n  ; sbpf-allow(unreachable-code)\n\n  mov64 r0, 1 ; why\n  exit\nunused: ; sbpf-allow(dead-store, unused-label)\n";
+        assert!(warnings(source).is_empty());
+        assert!(is_allowed(source, 7, "unreachable-code"));
+        assert!(!is_allowed(source, 8, "unreachable-code"));
+        assert!(!is_allowed(source, 7, "unused-label"));
+        assert!(is_allowed(source, 9, "unused-label"));
+
+        let source =
+            ".globl e\n.text\ne:\n  exit\n  ; sbpf-allow(unused-label)\n  mov64 r0, 1\n  exit\n";
+        assert_eq!(warnings(source).len(), 1);
+    }
+
+    #[test]
+    fn test_lint_config_limits_and_levels() {
+        let source = ".globl e\ne:\n  stxdw [r10-16], r1\n  call f\n  exit\nf:\n  jeq r1, 0, done\n  mov64 r0, 1\n  mov64 r0, 2\ndone:\n  exit\n";
+        let lints = LintConfig {
+            max_stack: Some(8),
+            max_compute_units: Some(5),
+            ..Default::default()
+        };
+        let found = Assembler::new(AssemblerOption::default().with_lints(lints))
+            .lint(source)
+            .unwrap();
+        let found: Vec<String> = found.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            found,
+            [
+                "Function 'e' may use 7 compute units, more than the 5 allowed",
+                "Function 'e' uses 16 bytes of stack, more than the 8 allowed",
+            ]
+        );
+
+        let source = ".globl e\ne:\n  exit\n  exit\n";
+        let mut lints = LintConfig::default();
+        lints
+            .levels
+            .insert("unreachable-code".to_string(), LintLevel::Allow);
+        let assembler = Assembler::new(AssemblerOption::default().with_lints(lints));
+        assert!(assembler.lint(source).unwrap().is_empty());
+        assert!(assembler.assemble_full(source).unwrap().warnings.is_empty());
+    }
+}
@@ -9,7 +9,7 @@ macro_rules! define_compile_errors {
             }
         ),* $(,)?
     ) => {
-        #[derive(Debug, thiserror::Error)]
+        #[derive(Debug, Clone, thiserror::Error)]
         pub enum CompileError {
             $(
                 #[error($error_msg)]
@@ -18,6 +18,15 @@ macro_rules! define_compile_errors {
         }
 
         impl CompileError {
+            /// Name of the variant, such as `UndefinedLabel`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$variant { .. } => stringify!($variant),
+                    )*
+                }
+            }
+
             pub fn label(&self) -> &str {
                 match self {
                     $(
@@ -33,22 +42,30 @@ macro_rules! define_compile_errors {
                     )*
                 }
             }
+
+            pub fn span_mut(&mut self) -> &mut Range<usize> {
+                match self {
+                    $(
+                        Self::$variant { span, .. } => span,
+                    )*
+                }
+            }
         }
     };
 }
 
-// TODO: make it a hyper link
+/// A [`CompileError::InternalError`](crate::errors::CompileError) on `span`,
+/// for states the assembler should never reach. Whether it is reported or
+/// panics on is up to [`InternalErrors`](crate::InternalErrors).
 #[macro_export]
 macro_rules! bug {
-    ($($arg:tt)*) => {{
-        eprintln!(
-            "\n{}\n{}",
-            "Thanks for abusing the compiler <3 you've hunted a bug!",
-            format!("Please file a bug report at: {}", "https://github.com/blueshift-gg/sbpf/issues/new")
-        );
-
-        panic!("{}", format!("Internal error: {}\n", format!($($arg)*)));
-    }};
+    ($span:expr, $($arg:tt)*) => {
+        $crate::errors::CompileError::InternalError {
+            message: format!($($arg)*),
+            span: $span,
+            custom_label: None,
+        }
+    };
 }
 
 #[cfg(test)]
@@ -71,13 +88,16 @@ mod tests {
         }
 
         // Test creating errors
-        let err1 = CompileError::TestError1 {
+        let mut err1 = CompileError::TestError1 {
             span: 0..10,
             custom_label: None,
         };
+        assert_eq!(err1.name(), "TestError1");
         assert_eq!(err1.label(), "test label 1");
         assert_eq!(err1.span(), &(0..10));
         assert_eq!(err1.to_string(), "Test error 1");
+        *err1.span_mut() = 2..4;
+        assert_eq!(err1.span(), &(2..4));
 
         let err2 = CompileError::TestError2 {
             span: 5..15,
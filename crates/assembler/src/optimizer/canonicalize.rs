@@ -3,6 +3,7 @@ use {
         CompileError,
         ast::AST,
         astnode::{ASTNode, Label},
+        parser::common::split_addend,
     },
     either::Either,
     sbpf_common::opcode::Opcode,
@@ -180,6 +181,101 @@ pub(crate) fn canonicalize_control_flow_targets(nodes: &mut Vec<ASTNode>) -> Can
     }
 }
 
+/// Point jumps to a label plus an addend, such as `ja table+2`, at the
+/// instruction that many slots past the label, through a temporary label.
+/// Runs before any pass moves code, so the target is the instruction the
+/// source laid out there. Jumps from an undefined label are left for label
+/// resolution to report.
+pub(crate) fn resolve_jump_addends(nodes: &mut Vec<ASTNode>) -> CanonicalizedTargets {
+    let mut label_at_offset = HashMap::new();
+    let mut label_offsets = HashMap::new();
+    let mut existing_labels = HashSet::new();
+    let mut numeric_labels = Vec::new();
+    let mut valid_target_offsets = HashSet::new();
+
+    for (idx, node) in nodes.iter().enumerate() {
+        match node {
+            ASTNode::Label { label, offset } => {
+                label_at_offset
+                    .entry(*offset)
+                    .or_insert_with(|| label.name.clone());
+                label_offsets.insert(label.name.clone(), *offset);
+                existing_labels.insert(label.name.clone());
+                numeric_labels.push((label.name.clone(), *offset, idx));
+            }
+            ASTNode::Instruction { offset, .. } => {
+                valid_target_offsets.insert(*offset);
+            }
+            _ => {}
+        }
+    }
+
+    let mut rewrites = Vec::new();
+    let mut labels_to_insert_by_offset = HashMap::new();
+    let mut labels_to_remove = HashSet::new();
+    let mut errors = Vec::new();
+
+    for (idx, node) in nodes.iter().enumerate() {
+        let ASTNode::Instruction { instruction, .. } = node else {
+            continue;
+        };
+        let Some(Either::Left(target)) = &instruction.off else {
+            continue;
+        };
+        let (label, addend) = split_addend(target);
+        if !instruction.is_jump() || addend == 0 || existing_labels.contains(target) {
+            continue;
+        }
+        let Some(label_offset) = label_offsets
+            .get(label)
+            .copied()
+            .or_else(|| AST::resolve_numeric_label(label, idx, &numeric_labels))
+        else {
+            continue;
+        };
+
+        let target_offset = addend
+            .checked_mul(8)
+            .and_then(|displacement| i64::try_from(label_offset).ok()?.checked_add(displacement))
+            .and_then(|target_offset| u64::try_from(target_offset).ok());
+        let target_offset = match target_offset {
+            Some(target_offset) if valid_target_offsets.contains(&target_offset) => target_offset,
+            target_offset => {
+                errors.push(invalid_addend_target_error(
+                    target,
+                    target_offset,
+                    &instruction.span,
+                ));
+                continue;
+            }
+        };
+
+        let canonical_label = canonical_label_for_target(
+            target_offset,
+            &mut label_at_offset,
+            &mut existing_labels,
+            &mut labels_to_insert_by_offset,
+            &mut labels_to_remove,
+        );
+        rewrites.push((idx, canonical_label));
+    }
+
+    for (idx, canonical_label) in rewrites {
+        if let Some(ASTNode::Instruction { instruction, .. }) = nodes.get_mut(idx) {
+            instruction.off = Some(Either::Left(canonical_label));
+        }
+    }
+
+    if !labels_to_insert_by_offset.is_empty() {
+        insert_temp_control_flow_target_labels(nodes, labels_to_insert_by_offset);
+    }
+
+    CanonicalizedTargets {
+        labels_to_remove,
+        errors,
+    }
+}
+
 fn canonical_label_for_target(
     target_offset: u64,
     label_at_offset: &mut HashMap<u64, String>,
@@ -255,6 +351,27 @@ fn invalid_relative_target_error(
     }
 }
 
+fn invalid_addend_target_error(
+    target: &str,
+    target_offset: Option<u64>,
+    span: &std::ops::Range<usize>,
+) -> CompileError {
+    let error = if let Some(target_offset) = target_offset {
+        format!(
+            "Jump target '{target}' resolves to byte offset {target_offset}, which is not an \
+             instruction"
+        )
+    } else {
+        format!("Jump target '{target}' resolves outside the program")
+    };
+
+    CompileError::BytecodeError {
+        error,
+        span: span.clone(),
+        custom_label: None,
+    }
+}
+
 fn insert_temp_control_flow_target_labels(
     nodes: &mut Vec<ASTNode>,
     labels_by_offset: HashMap<u64, Label>,
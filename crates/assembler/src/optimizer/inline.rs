@@ -0,0 +1,306 @@
+use {
+    crate::{ast::AST, astnode::ASTNode, parser::common::split_addend},
+    either::Either,
+    sbpf_common::{
+        instruction::Instruction,
+        opcode::{Opcode, STORE_IMM_OPS, STORE_REG_OPS},
+    },
+    std::collections::{HashMap, HashSet},
+};
+
+/// Largest function body, in bytes and excluding its `exit`, considered for inlining.
+pub const INLINE_SIZE_THRESHOLD: u64 = 32;
+
+/// Size in bytes of a `call` or `exit` instruction.
+const FRAME_INSTRUCTION_SIZE: u64 = 8;
+
+/// A function that can be inlined: a label followed by straight-line code
+/// ending in `exit`.
+struct Candidate {
+    /// Index of the label in `ast.nodes`.
+    label_idx: usize,
+    /// Index of the terminating `exit` in `ast.nodes`.
+    exit_idx: usize,
+    body: Vec<Instruction>,
+    call_sites: Vec<usize>,
+    /// Whether the function can be deleted once every call site is inlined.
+    removable: bool,
+}
+
+impl Candidate {
+    fn body_size(&self) -> u64 {
+        self.body.iter().map(Instruction::get_size).sum()
+    }
+
+    /// Change in `.text` size from inlining every call site. Each call site
+    /// drops a `call` and gains the body; a removable function also loses its
+    /// body and `exit`.
+    fn size_delta(&self) -> i64 {
+        let body_size = self.body_size() as i64;
+        let call_size = FRAME_INSTRUCTION_SIZE as i64;
+        let mut delta = self.call_sites.len() as i64 * (body_size - call_size);
+        if self.removable {
+            delta -= body_size + FRAME_INSTRUCTION_SIZE as i64;
+        }
+        delta
+    }
+}
+
+/// Inline small internal functions where that shrinks the program.
+///
+/// Every inlined call saves the `call` and `exit` and therefore always reduces
+/// CU, so the only question is size: a function is inlined only if its body
+/// fits under [`INLINE_SIZE_THRESHOLD`] and replacing all of its calls (and
+/// deleting it when nothing else refers to it) makes `.text` smaller.
+///
+/// The VM gives every call a fresh frame and restores r6-r9 on `exit`, so
+/// bodies that write r6-r9 or use r10 are never inlined. Bodies may not contain
+/// jumps, calls or labels. Returns the number of call sites inlined.
+pub fn inline_small_functions(ast: &mut AST) -> usize {
+    let mut inlined = 0;
+    while let Some(candidate) = next_candidate(ast) {
+        inlined += candidate.call_sites.len();
+        apply(ast, candidate);
+    }
+    if inlined > 0 {
+        super::assign_offsets(ast);
+    }
+    inlined
+}
+
+fn next_candidate(ast: &AST) -> Option<Candidate> {
+    let labels: HashMap<&str, usize> = ast
+        .nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, node)| match node {
+            ASTNode::Label { label, .. } => Some((label.name.as_str(), idx)),
+            _ => None,
+        })
+        .collect();
+
+    // Internal call sites per label, and labels referenced any other way.
+    let mut call_sites: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut referenced: HashSet<&str> = HashSet::new();
+    for (idx, node) in ast.nodes.iter().enumerate() {
+        match node {
+            ASTNode::Instruction { instruction, .. } => {
+                if instruction.opcode == Opcode::Call
+                    && let Some(Either::Left(name)) = &instruction.imm
+                    && labels.contains_key(name.as_str())
+                {
+                    call_sites.entry(name.as_str()).or_default().push(idx);
+                    continue;
+                }
+                if let Some(Either::Left(name)) = &instruction.off {
+                    referenced.insert(name.as_str());
+                }
+                if let Some(Either::Left(name)) = &instruction.imm {
+                    referenced.insert(split_addend(name).0);
+                }
+            }
+            ASTNode::GlobalDecl { global_decl } => {
+                referenced.insert(global_decl.entry_label.as_str());
+            }
+            _ => {}
+        }
+    }
+
+    let mut names: Vec<&str> = call_sites.keys().copied().collect();
+    names.sort_by_key(|name| labels[name]);
+    names.into_iter().find_map(|name| {
+        let label_idx = labels[name];
+        let (body, exit_idx) = inlinable_body(&ast.nodes, label_idx)?;
+        let falls_through = label_idx > 0
+            && !matches!(
+                &ast.nodes[label_idx - 1],
+                ASTNode::Instruction { instruction, .. }
+                    if matches!(instruction.opcode, Opcode::Exit | Opcode::Ja)
+            );
+        let candidate = Candidate {
+            label_idx,
+            exit_idx,
+            body,
+            call_sites: call_sites[name].clone(),
+            removable: !referenced.contains(name) && !falls_through,
+        };
+        (candidate.body_size() <= INLINE_SIZE_THRESHOLD && candidate.size_delta() < 0)
+            .then_some(candidate)
+    })
+}
+
+/// The straight-line body following the label at `label_idx`, and the index of
+/// its `exit`, if the function is safe to inline.
+fn inlinable_body(nodes: &[ASTNode], label_idx: usize) -> Option<(Vec<Instruction>, usize)> {
+    let mut body = Vec::new();
+    for (idx, node) in nodes.iter().enumerate().skip(label_idx + 1) {
+        let ASTNode::Instruction { instruction, .. } = node else {
+            return None;
+        };
+        if instruction.opcode == Opcode::Exit {
+            return Some((body, idx));
+        }
+        if instruction.is_jump()
+            || matches!(instruction.opcode, Opcode::Call | Opcode::Callx)
+            || !preserves_frame(instruction)
+        {
+            return None;
+        }
+        body.push(instruction.clone());
+    }
+    None
+}
+
+/// Whether `instruction` behaves the same outside its own call frame: it must
+/// not touch r10 and must not write any of the callee-saved r6-r9.
+fn preserves_frame(instruction: &Instruction) -> bool {
+    let uses_frame_pointer = [&instruction.dst, &instruction.src]
+        .into_iter()
+        .flatten()
+        .any(|reg| reg.n == 10);
+    // Stores only read their destination register as a base address.
+    let is_store =
+        STORE_IMM_OPS.contains(&instruction.opcode) || STORE_REG_OPS.contains(&instruction.opcode);
+    let writes_callee_saved = !is_store
+        && instruction
+            .dst
+            .as_ref()
+            .is_some_and(|reg| (6..=9).contains(&reg.n));
+    !uses_frame_pointer && !writes_callee_saved
+}
+
+fn apply(ast: &mut AST, candidate: Candidate) {
+    let call_sites: HashSet<usize> = candidate.call_sites.iter().copied().collect();
+    let removed = candidate
+        .removable
+        .then_some(candidate.label_idx..=candidate.exit_idx);
+
+    let nodes = std::mem::take(&mut ast.nodes);
+    for (idx, node) in nodes.into_iter().enumerate() {
+        if removed.as_ref().is_some_and(|range| range.contains(&idx)) {
+            continue;
+        }
+        if call_sites.contains(&idx) {
+            ast.nodes.extend(
+                candidate
+                    .body
+                    .iter()
+                    .map(|instruction| ASTNode::Instruction {
+                        instruction: instruction.clone(),
+                        offset: 0,
+                    }),
+            );
+            continue;
+        }
+        ast.nodes.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        SbpfArch,
+        ast::{OptLevel, OptimizationConfig},
+        parser::parse_with_optimization,
+        program::Program,
+    };
+
+    fn assemble(source: &str, optimization: OptimizationConfig) -> Vec<u8> {
+        let layout = parse_with_optimization(source, SbpfArch::V3, optimization).unwrap();
+        Program::from_parse_result(layout, None, None).emit_bytecode()
+    }
+
+    fn inlined(source: &str) -> Vec<u8> {
+        assemble(
+            source,
+            OptimizationConfig::enabled().with_level(OptLevel::Aggressive),
+        )
+    }
+
+    fn unoptimized(source: &str) -> Vec<u8> {
+        assemble(source, OptimizationConfig::disabled())
+    }
+
+    #[test]
+    fn test_inlines_single_instruction_helper() {
+        let source = r#".globl entrypoint
+entrypoint:
+  call helper
+  exit
+helper:
+  mov64 r0, 1
+  exit
+"#;
+        let expected = r#".globl entrypoint
+entrypoint:
+  mov64 r0, 1
+  exit
+"#;
+        assert_eq!(inlined(source), unoptimized(expected));
+        // Basic optimization leaves calls alone.
+        assert_eq!(
+            assemble(source, OptimizationConfig::enabled()),
+            unoptimized(source)
+        );
+    }
+
+    #[test]
+    fn test_inlines_only_when_size_shrinks() {
+        let two_callers = |body: &str| {
+            format!(
+                ".globl entrypoint\nentrypoint:\n  call helper\n  call helper\n  exit\nhelper:\n{body}  exit\n"
+            )
+        };
+
+        // 16-byte body at two call sites: +16 at the calls, -24 for the function.
+        let small = two_callers("  mov64 r1, 1\n  add64 r0, r1\n");
+        let expected = ".globl entrypoint\nentrypoint:\n  mov64 r1, 1\n  add64 r0, r1\n  mov64 r1, 1\n  add64 r0, r1\n  exit\n";
+        assert_eq!(inlined(&small), unoptimized(expected));
+
+        // 24-byte body at two call sites: +32 at the calls, -32 for the function.
+        let larger = two_callers("  mov64 r1, 1\n  add64 r0, r1\n  add64 r0, r1\n");
+        assert_eq!(inlined(&larger), unoptimized(&larger));
+    }
+
+    #[test]
+    fn test_respects_call_frame_semantics() {
+        for body in [
+            // r6-r9 are restored by `exit`.
+            "  mov64 r6, 1\n",
+            // r10 is the callee's own frame pointer.
+            "  stxdw [r10-8], r1\n",
+            "  mov64 r0, r10\n",
+            // Control flow inside the body.
+            "  jeq r1, 0, +1\n  mov64 r0, 1\n",
+            "  call sol_log_\n",
+        ] {
+            let source = format!(
+                ".globl entrypoint\nentrypoint:\n  call helper\n  exit\nhelper:\n{body}  exit\n"
+            );
+            assert_eq!(inlined(&source), unoptimized(&source), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_stores_through_callee_saved_register_are_inlined() {
+        let source = ".globl entrypoint\nentrypoint:\n  call helper\n  exit\nhelper:\n  stxdw [r6+0], r1\n  exit\n";
+        let expected = ".globl entrypoint\nentrypoint:\n  stxdw [r6+0], r1\n  exit\n";
+        assert_eq!(inlined(source), unoptimized(expected));
+    }
+
+    #[test]
+    fn test_keeps_referenced_function() {
+        // `helper` is also loaded as an address, so it can't be deleted and
+        // inlining would only grow the program.
+        let source = r#".globl entrypoint
+entrypoint:
+  lddw r2, helper
+  call helper
+  exit
+helper:
+  mov64 r0, 1
+  exit
+"#;
+        assert_eq!(inlined(source), unoptimized(source));
+    }
+}
@@ -0,0 +1,238 @@
+use {
+    crate::{ast::AST, astnode::ASTNode, errors::CompileError},
+    either::Either,
+    sbpf_common::{opcode::Opcode, profile::ExecutionProfile},
+    std::{cmp::Reverse, collections::HashSet},
+};
+
+/// A run of nodes that can be moved as a whole: it starts at a function entry
+/// and nothing falls through into or out of it.
+struct Unit {
+    nodes: Vec<ASTNode>,
+    heat: u64,
+}
+
+impl Unit {
+    /// Whether execution can run off the end of this unit into the next one.
+    fn falls_through(&self) -> bool {
+        !self
+            .nodes
+            .iter()
+            .rev()
+            .find_map(|node| match node {
+                ASTNode::Label { .. } => Some(false),
+                ASTNode::Instruction { instruction, .. } => {
+                    Some(matches!(instruction.opcode, Opcode::Exit | Opcode::Ja))
+                }
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Reorder functions so the most executed ones sit together at the start of
+/// `.text`, hottest first. Functions that were never executed keep their
+/// relative order at the end.
+///
+/// `profile` must come from a build of the same source without any
+/// optimization, so its counts line up with the instructions in `ast`. The
+/// first function, and a trailing one that runs off the end of the program,
+/// stay where they are; functions joined by fall-through move together.
+/// Control flow must already be label based, see
+/// [`super::canonicalize_control_flow_targets`].
+pub fn order_functions_by_profile(
+    ast: &mut AST,
+    profile: &ExecutionProfile,
+) -> Result<(), CompileError> {
+    let instruction_count = ast
+        .nodes
+        .iter()
+        .filter(|node| matches!(node, ASTNode::Instruction { .. }))
+        .count();
+    if instruction_count != profile.instruction_counts.len() {
+        return Err(CompileError::ProfileMismatch {
+            expected: instruction_count,
+            found: profile.instruction_counts.len(),
+            span: 0..0,
+            custom_label: None,
+        });
+    }
+
+    let entries = function_entries(ast);
+    let mut units: Vec<Unit> = Vec::new();
+    let mut pc = 0;
+    for node in std::mem::take(&mut ast.nodes) {
+        let starts_function = matches!(&node, ASTNode::Label { label, .. }
+            if entries.contains(&label.name));
+        let heat = match &node {
+            ASTNode::Instruction { .. } => {
+                pc += 1;
+                profile.count(pc - 1)
+            }
+            _ => 0,
+        };
+        match units.last_mut() {
+            Some(unit) if !starts_function || unit.falls_through() => {
+                unit.nodes.push(node);
+                unit.heat += heat;
+            }
+            _ => units.push(Unit {
+                nodes: vec![node],
+                heat,
+            }),
+        }
+    }
+
+    let last = units
+        .last()
+        .is_some_and(Unit::falls_through)
+        .then(|| units.pop())
+        .flatten();
+    let mut units = units.into_iter();
+    let first = units.next();
+    let mut movable: Vec<Unit> = units.collect();
+    movable.sort_by_key(|unit| Reverse(unit.heat));
+
+    ast.nodes = first
+        .into_iter()
+        .chain(movable)
+        .chain(last)
+        .flat_map(|unit| unit.nodes)
+        .collect();
+    super::assign_offsets(ast);
+    Ok(())
+}
+
+/// Labels that start a function: the entrypoint and the targets of internal
+/// calls.
+fn function_entries(ast: &AST) -> HashSet<String> {
+    ast.nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::GlobalDecl { global_decl } => Some(global_decl.entry_label.clone()),
+            ASTNode::Instruction { instruction, .. } if instruction.opcode == Opcode::Call => {
+                match &instruction.imm {
+                    Some(Either::Left(name)) => Some(name.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            SbpfArch, ast::OptimizationConfig, parser::parse_with_optimization, program::Program,
+        },
+    };
+
+    const SOURCE: &str = r#".globl entrypoint
+entrypoint:
+  call cold
+  call hot
+  call hot
+  exit
+cold:
+  mov64 r0, 1
+  exit
+hot:
+  add64 r0, 1
+  exit
+"#;
+
+    fn assemble(source: &str, optimization: OptimizationConfig) -> Vec<u8> {
+        let layout = parse_with_optimization(source, SbpfArch::V3, optimization).unwrap();
+        Program::from_parse_result(layout, None, None).emit_bytecode()
+    }
+
+    fn profile(counts: &[u64]) -> ExecutionProfile {
+        ExecutionProfile {
+            instruction_counts: counts.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_hot_functions_move_forward() {
+        let optimization =
+            OptimizationConfig::enabled().with_profile(profile(&[1, 1, 1, 1, 0, 0, 2, 2]));
+        let expected = r#".globl entrypoint
+entrypoint:
+  call cold
+  call hot
+  call hot
+  exit
+hot:
+  add64 r0, 1
+  exit
+cold:
+  mov64 r0, 1
+  exit
+"#;
+        assert_eq!(
+            assemble(SOURCE, optimization),
+            assemble(expected, OptimizationConfig::disabled())
+        );
+    }
+
+    #[test]
+    fn test_fall_through_functions_move_together() {
+        let source = r#".globl entrypoint
+entrypoint:
+  call a
+  call b
+  call c
+  exit
+a:
+  mov64 r0, 1
+b:
+  add64 r0, 1
+  exit
+c:
+  exit
+"#;
+        // `a` falls through into `b`, so they move as one unit whose heat is the
+        // sum of both.
+        let optimization =
+            OptimizationConfig::enabled().with_profile(profile(&[1, 1, 1, 1, 1, 2, 2, 3]));
+        let expected = r#".globl entrypoint
+entrypoint:
+  call a
+  call b
+  call c
+  exit
+c:
+  exit
+a:
+  mov64 r0, 1
+b:
+  add64 r0, 1
+  exit
+"#;
+        assert_eq!(
+            assemble(source, optimization),
+            assemble(source, OptimizationConfig::disabled())
+        );
+        let optimization =
+            OptimizationConfig::enabled().with_profile(profile(&[1, 1, 1, 1, 1, 2, 2, 8]));
+        assert_eq!(
+            assemble(source, optimization),
+            assemble(expected, OptimizationConfig::disabled())
+        );
+    }
+
+    #[test]
+    fn test_profile_must_match_program() {
+        let optimization = OptimizationConfig::enabled().with_profile(profile(&[1, 1]));
+        let Err(errors) = parse_with_optimization(SOURCE, SbpfArch::V3, optimization) else {
+            panic!("expected a profile mismatch");
+        };
+        assert_eq!(
+            errors[0].to_string(),
+            "Profile does not match the program: expected 8 instruction counts, found 2"
+        );
+    }
+}
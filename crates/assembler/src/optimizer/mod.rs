@@ -1,7 +1,10 @@
 mod canonicalize;
+mod inline;
+mod layout;
+mod peephole;
 
 pub(crate) use canonicalize::{
-    canonicalize_control_flow_targets, remove_temp_control_flow_target_labels,
+    canonicalize_control_flow_targets, remove_temp_control_flow_target_labels, resolve_jump_addends,
 };
 use {
     crate::{ast::AST, astnode::ASTNode},
@@ -9,6 +12,11 @@ use {
     sbpf_ir::{Cfg, InputNode, control_flow_graph},
     std::collections::HashSet,
 };
+pub use {
+    inline::{INLINE_SIZE_THRESHOLD, inline_small_functions},
+    layout::order_functions_by_profile,
+    peephole::peephole_optimize,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CfgDumpStage {
@@ -102,7 +110,14 @@ fn cfg_for_ast(ast: &AST) -> Cfg {
             None
         }
     });
-    let nodes = ast.nodes.iter().map(|node| match node {
+    // Labels after the last instruction start no block. They keep their
+    // indices, since they come last.
+    let end = ast
+        .nodes
+        .iter()
+        .rposition(|node| matches!(node, ASTNode::Instruction { .. }))
+        .map_or(0, |last| last + 1);
+    let nodes = ast.nodes[..end].iter().map(|node| match node {
         ASTNode::Label { label, .. } => InputNode::Label(label.name.as_str()),
         ASTNode::Instruction { instruction, .. } => InputNode::Instruction(instruction),
         _ => InputNode::Other,
@@ -167,6 +182,29 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_optimizer_keeps_trailing_labels() {
+        let mut ast = AST::new();
+        ast.add_function_entry("entrypoint".to_string());
+        ast.add_function_entry("dead".to_string());
+        ast.nodes = vec![
+            label_node("entrypoint", 0),
+            instruction_node(Opcode::Exit, None, 0, None),
+            label_node("dead", 8),
+            instruction_node(Opcode::Exit, None, 8, None),
+            label_node("text_end", 16),
+        ];
+        ast.set_text_size(16);
+
+        eliminate_unreachable_functions(&mut ast);
+
+        assert_eq!(ast.nodes.len(), 3);
+        assert!(matches!(
+            &ast.nodes[2],
+            ASTNode::Label { label, offset } if label.name == "text_end" && *offset == 8
+        ));
+    }
+
     #[test]
     fn test_optimizer_removes_uncalled_function_only() {
         let mut ast = AST::new();
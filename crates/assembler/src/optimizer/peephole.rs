@@ -0,0 +1,365 @@
+use {
+    crate::{ast::AST, astnode::ASTNode},
+    either::Either,
+    sbpf_common::{
+        inst_param::{Number, Register},
+        instruction::Instruction,
+        opcode::{LOAD_MEMORY_OPS, Opcode},
+    },
+    std::collections::HashSet,
+};
+
+/// Local rewrites of the instruction stream that make it smaller without
+/// changing what it computes:
+///
+/// - `lddw` of a constant that fits in a sign-extended 32-bit immediate
+///   becomes `mov64`.
+/// - Consecutive `add64`/`add32` immediates to the same register are
+///   combined, and an `add64` that nets to 0 is dropped.
+/// - `mov64 rX, rX`, and moves whose register is overwritten before it is
+///   read, are removed.
+/// - Jumps to the instruction right after them are removed.
+///
+/// Rewrites never look across a label, so code reached by a jump sees the
+/// same registers as before. Rewritten instructions keep the span of the
+/// first instruction they replace, so the debug line table still points at
+/// its source line. Returns the number of rewrites.
+pub fn peephole_optimize(ast: &mut AST) -> usize {
+    let mut function_entries = ast.function_entries().clone();
+    function_entries.extend(ast.nodes.iter().filter_map(|node| match node {
+        ASTNode::Instruction { instruction, .. } if instruction.opcode == Opcode::Call => {
+            match &instruction.imm {
+                Some(Either::Left(target)) => Some(target.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }));
+    let mut rewrites = 0;
+    loop {
+        let applied = fold_lddw(&mut ast.nodes)
+            + combine_adds(&mut ast.nodes)
+            + remove_dead_moves(&mut ast.nodes)
+            + remove_jumps_to_next(&mut ast.nodes, &function_entries);
+        if applied == 0 {
+            break;
+        }
+        rewrites += applied;
+    }
+
+    if rewrites > 0 {
+        let spans: HashSet<usize> = ast
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                ASTNode::Instruction { instruction, .. } => Some(instruction.span.start),
+                _ => None,
+            })
+            .collect();
+        ast.source_locations
+            .retain(|position, _| spans.contains(position));
+        super::assign_offsets(ast);
+    }
+    rewrites
+}
+
+fn fold_lddw(nodes: &mut [ASTNode]) -> usize {
+    let mut folded = 0;
+    for node in nodes {
+        if let ASTNode::Instruction { instruction, .. } = node
+            && instruction.opcode == Opcode::Lddw
+            && constant_imm(instruction).is_some_and(|value| i32::try_from(value).is_ok())
+        {
+            instruction.opcode = Opcode::Mov64Imm;
+            folded += 1;
+        }
+    }
+    folded
+}
+
+fn combine_adds(nodes: &mut Vec<ASTNode>) -> usize {
+    let mut removed = HashSet::new();
+    let mut idx = 0;
+    while idx < nodes.len() {
+        let Some(next) = next_instruction(nodes, idx) else {
+            idx += 1;
+            continue;
+        };
+        let (
+            ASTNode::Instruction { instruction: a, .. },
+            ASTNode::Instruction { instruction: b, .. },
+        ) = (&nodes[idx], &nodes[next])
+        else {
+            idx += 1;
+            continue;
+        };
+        let sum = match (a.opcode, b.opcode, constant_imm(a), constant_imm(b)) {
+            _ if a.dst != b.dst => None,
+            (Opcode::Add64Imm, Opcode::Add64Imm, Some(x), Some(y)) => {
+                x.checked_add(y).filter(|sum| i32::try_from(*sum).is_ok())
+            }
+            (Opcode::Add32Imm, Opcode::Add32Imm, Some(x), Some(y)) => {
+                Some((x as i32).wrapping_add(y as i32) as i64)
+            }
+            _ => None,
+        };
+        let Some(sum) = sum else {
+            idx += 1;
+            continue;
+        };
+
+        removed.insert(next);
+        if sum == 0 && a.opcode == Opcode::Add64Imm {
+            removed.insert(idx);
+        } else if let ASTNode::Instruction { instruction, .. } = &mut nodes[idx] {
+            instruction.imm = Some(Either::Right(Number::Int(sum)));
+        }
+        idx = next + 1;
+    }
+    remove_nodes(nodes, &removed)
+}
+
+fn remove_dead_moves(nodes: &mut Vec<ASTNode>) -> usize {
+    let removed: HashSet<usize> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(idx, node)| match node {
+            ASTNode::Instruction { instruction, .. } => {
+                let is_self_move =
+                    instruction.opcode == Opcode::Mov64Reg && instruction.src == instruction.dst;
+                is_self_move || (is_move(instruction) && is_overwritten(nodes, *idx))
+            }
+            _ => false,
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    remove_nodes(nodes, &removed)
+}
+
+/// Whether the register the move at `idx` writes is written again before
+/// anything reads it, on the straight-line code that follows.
+fn is_overwritten(nodes: &[ASTNode], idx: usize) -> bool {
+    let ASTNode::Instruction { instruction, .. } = &nodes[idx] else {
+        return false;
+    };
+    let Some(register) = &instruction.dst else {
+        return false;
+    };
+    for node in &nodes[idx + 1..] {
+        match node {
+            ASTNode::Label { .. } => return false,
+            ASTNode::Instruction { instruction, .. } => {
+                if overwrites(instruction, register) {
+                    return true;
+                }
+                if instruction.is_jump()
+                    || matches!(
+                        instruction.opcode,
+                        Opcode::Call | Opcode::Callx | Opcode::Exit
+                    )
+                    || instruction.dst.as_ref() == Some(register)
+                    || instruction.src.as_ref() == Some(register)
+                {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+fn remove_jumps_to_next(nodes: &mut Vec<ASTNode>, function_entries: &HashSet<String>) -> usize {
+    let mut removed = HashSet::new();
+    for (idx, node) in nodes.iter().enumerate() {
+        let ASTNode::Instruction { instruction, .. } = node else {
+            continue;
+        };
+        let Some(Either::Left(target)) = &instruction.off else {
+            continue;
+        };
+        if !instruction.is_jump() {
+            continue;
+        }
+        // Labels between the jump and the next instruction. Dropping a jump
+        // into another function would make the code fall through into it.
+        let Some(next) = nodes[idx + 1..]
+            .iter()
+            .position(|node| matches!(node, ASTNode::Instruction { .. }))
+        else {
+            continue;
+        };
+        let labels: Vec<&str> = nodes[idx + 1..idx + 1 + next]
+            .iter()
+            .filter_map(|node| match node {
+                ASTNode::Label { label, .. } => Some(label.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        if labels.contains(&target.as_str())
+            && !labels.iter().any(|label| function_entries.contains(*label))
+        {
+            removed.insert(idx);
+        }
+    }
+    remove_nodes(nodes, &removed)
+}
+
+/// Index of the instruction after the one at `idx`, if no label comes first.
+fn next_instruction(nodes: &[ASTNode], idx: usize) -> Option<usize> {
+    for (next, node) in nodes.iter().enumerate().skip(idx + 1) {
+        match node {
+            ASTNode::Label { .. } => return None,
+            ASTNode::Instruction { .. } => return Some(next),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn constant_imm(instruction: &Instruction) -> Option<i64> {
+    match &instruction.imm {
+        Some(Either::Right(value)) => Some(value.to_i64()),
+        _ => None,
+    }
+}
+
+fn is_move(instruction: &Instruction) -> bool {
+    matches!(
+        instruction.opcode,
+        Opcode::Mov32Imm | Opcode::Mov32Reg | Opcode::Mov64Imm | Opcode::Mov64Reg
+    )
+}
+
+/// Whether `instruction` writes `register` without reading it first.
+fn overwrites(instruction: &Instruction, register: &Register) -> bool {
+    let writes = is_move(instruction)
+        || instruction.opcode == Opcode::Lddw
+        || LOAD_MEMORY_OPS.contains(&instruction.opcode);
+    writes
+        && instruction.dst.as_ref() == Some(register)
+        && instruction.src.as_ref() != Some(register)
+}
+
+fn remove_nodes(nodes: &mut Vec<ASTNode>, removed: &HashSet<usize>) -> usize {
+    if !removed.is_empty() {
+        *nodes = std::mem::take(nodes)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| !removed.contains(idx))
+            .map(|(_, node)| node)
+            .collect();
+    }
+    removed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        SbpfArch, ast::OptimizationConfig, astnode::ASTNode, parser::parse_with_optimization,
+        program::Program,
+    };
+
+    fn assemble(source: &str, optimization: OptimizationConfig) -> Vec<u8> {
+        let layout = parse_with_optimization(source, SbpfArch::V3, optimization).unwrap();
+        Program::from_parse_result(layout, None, None).emit_bytecode()
+    }
+
+    fn optimized(source: &str) -> Vec<u8> {
+        assemble(source, OptimizationConfig::enabled())
+    }
+
+    fn unoptimized(source: &str) -> Vec<u8> {
+        assemble(source, OptimizationConfig::disabled())
+    }
+
+    fn program(body: &str) -> String {
+        format!(".globl entrypoint\nentrypoint:\n{body}  exit\n")
+    }
+
+    #[test]
+    fn test_folds_small_lddw() {
+        let source = program("  lddw r1, 0x7fffffff\n  lddw r2, -1\n  lddw r3, 0x80000000\n");
+        let expected = program("  mov64 r1, 0x7fffffff\n  mov64 r2, -1\n  lddw r3, 0x80000000\n");
+        assert_eq!(optimized(&source), unoptimized(&expected));
+    }
+
+    #[test]
+    fn test_combines_adds() {
+        let source = program("  add64 r1, 2\n  add64 r1, 3\n  add64 r2, 1\n  add64 r3, 1\n");
+        let expected = program("  add64 r1, 5\n  add64 r2, 1\n  add64 r3, 1\n");
+        assert_eq!(optimized(&source), unoptimized(&expected));
+
+        let source = program("  add64 r1, 4\n  add64 r1, -4\n  add32 r2, 4\n  add32 r2, -4\n");
+        let expected = program("  add32 r2, 0\n");
+        assert_eq!(optimized(&source), unoptimized(&expected));
+
+        // The sum no longer fits a sign-extended immediate, or a label
+        // separates the adds.
+        for body in [
+            "  add64 r1, 0x7fffffff\n  add64 r1, 1\n",
+            "  add64 r1, 1\nnext:\n  add64 r1, 1\n",
+        ] {
+            let source = program(body);
+            assert_eq!(optimized(&source), unoptimized(&source), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_removes_dead_moves() {
+        let source = program(
+            "  mov64 r1, r1\n  mov64 r0, 1\n  mov64 r2, 3\n  mov64 r0, 2\n  mov32 r3, r3\n",
+        );
+        let expected = program("  mov64 r2, 3\n  mov64 r0, 2\n  mov32 r3, r3\n");
+        assert_eq!(optimized(&source), unoptimized(&expected));
+
+        // The first value is read, or reaches a jump or call before it is
+        // overwritten.
+        for body in [
+            "  mov64 r0, 1\n  add64 r0, 1\n  mov64 r0, 2\n",
+            "  mov64 r0, 1\n  mov32 r0, r0\n",
+            "  mov64 r1, 1\n  ldxdw r1, [r1+0]\n",
+            "  mov64 r0, 1\n  jeq r1, 0, +1\n  mov64 r0, 2\n",
+            "  mov64 r1, 1\n  call sol_log_\n  mov64 r1, 2\n",
+        ] {
+            let source = program(body);
+            assert_eq!(optimized(&source), unoptimized(&source), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_removes_jumps_to_next_instruction() {
+        let source = program("  ja next\nnext:\n  jeq r1, 0, +0\n  mov64 r0, 1\n");
+        let expected = program("  mov64 r0, 1\n");
+        assert_eq!(optimized(&source), unoptimized(&expected));
+
+        // Jumping over code, or into the next function, stays.
+        let source =
+            ".globl entrypoint\nentrypoint:\n  call helper\n  ja helper\nhelper:\n  exit\n";
+        assert_eq!(optimized(source), unoptimized(source));
+        let source = program("  ja end\n  mov64 r0, 1\nend:\n");
+        assert_eq!(optimized(&source), unoptimized(&source));
+    }
+
+    #[test]
+    fn test_keeps_line_map_consistent() {
+        let source = program("  lddw r1, 1\n  mov64 r0, 1\n  mov64 r0, 2\n");
+        let layout = parse_with_optimization(
+            &format!(".file 1 \"a.c\"\n.loc 1 7 0\n{}", source),
+            SbpfArch::V3,
+            OptimizationConfig::enabled(),
+        )
+        .unwrap();
+        let offsets: Vec<u64> = layout
+            .code_section
+            .get_nodes()
+            .iter()
+            .filter_map(|node| match node {
+                ASTNode::Instruction { offset, .. } => Some(*offset),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(offsets, vec![0, 8, 16]);
+        assert_eq!(layout.source_locations.len(), 3);
+    }
+}
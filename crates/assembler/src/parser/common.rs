@@ -28,10 +28,41 @@ pub fn parse_register(pair: Pair<Rule>) -> Result<Register, CompileError> {
     }
 }
 
+/// Parse the operand of an instruction with a 32-bit immediate.
 pub(crate) fn parse_operand(
     pair: Pair<Rule>,
     const_map: &HashMap<String, Number>,
     label_offset_map: &HashMap<String, (Number, Section)>,
+) -> Result<Either<String, Number>, CompileError> {
+    let has_typed_literal = pair
+        .clone()
+        .into_inner()
+        .flatten()
+        .any(|inner| inner.as_rule() == Rule::typed_literal);
+    let span = pair.as_span();
+    let operand = parse_imm64_operand(pair, const_map, label_offset_map)?;
+
+    // The immediate is sign-extended, so a plain number like 0xffffffff may
+    // stand for -1. A unit literal is an exact amount and must not wrap.
+    if let Either::Right(value) = &operand
+        && has_typed_literal
+        && i32::try_from(value.to_i64()).is_err()
+    {
+        return Err(CompileError::InvalidTypedLiteral {
+            literal: span.as_str().to_string(),
+            reason: "does not fit in a 32-bit immediate, load it with lddw".to_string(),
+            span: span.start()..span.end(),
+            custom_label: None,
+        });
+    }
+    Ok(operand)
+}
+
+/// Parse the operand of `lddw`, whose immediate is a full 64 bits.
+pub(crate) fn parse_imm64_operand(
+    pair: Pair<Rule>,
+    const_map: &HashMap<String, Number>,
+    label_offset_map: &HashMap<String, (Number, Section)>,
 ) -> Result<Either<String, Number>, CompileError> {
     let span = pair.as_span();
     let span_range = span.start()..span.end();
@@ -51,10 +82,13 @@ pub(crate) fn parse_operand(
 
 /// Evaluate an expression used as an instruction operand.
 ///
-/// - A bare symbol not found in const_map or label_offset_map is returned as
-///   `Either::Left` for deferred resolution (e.g. `lddw r1, label`).
-/// - Multi-term expressions (arithmetic) resolve all symbols immediately from
-///   const_map and label_offset_map. Labels must be in the same section.
+/// - A bare symbol not found in const_map is returned as `Either::Left` for
+///   deferred resolution (e.g. `lddw r1, label`).
+/// - Multi-term expressions resolve constants immediately. Labels must be in
+///   the same section. Differences of labels, as in `end - start`, fold to a
+///   constant; a label plus a constant, such as `message+8`, stays symbolic
+///   with the addend attached (see [`with_addend`]) so it can be relocated.
+///   Anything else is not a link-time constant.
 fn eval_operand_expression(
     pair: Pair<Rule>,
     const_map: &HashMap<String, Number>,
@@ -63,53 +97,24 @@ fn eval_operand_expression(
     let span = pair.as_span();
     let span_range = span.start()..span.end();
 
-    let mut terms: Vec<Number> = Vec::new();
-    let mut ops: Vec<&str> = Vec::new();
-    let mut is_single_symbol = false;
-    let mut single_symbol_name = String::new();
-    let mut label_sections: Vec<(String, Section)> = Vec::new();
-
-    let inner_pairs: Vec<_> = pair.into_inner().collect();
+    let inner_pairs: Vec<_> = pair.clone().into_inner().collect();
 
     // Check if this is a single bare symbol (no operators)
     if inner_pairs.len() == 1 && inner_pairs[0].as_rule() == Rule::term {
         let term_inners: Vec<_> = inner_pairs[0].clone().into_inner().collect();
         if term_inners.len() == 1 && term_inners[0].as_rule() == Rule::symbol {
-            is_single_symbol = true;
-            single_symbol_name = term_inners[0].as_str().to_string();
-        }
-    }
-
-    // For a bare symbol not in const_map, defer resolution
-    if is_single_symbol {
-        if let Some(value) = const_map.get(&single_symbol_name) {
-            return Ok(Either::Right(value.clone()));
-        }
-        // Not in const_map — return as unresolved for build_program to handle
-        return Ok(Either::Left(single_symbol_name));
-    }
-
-    // Multi-term expression: resolve everything now
-    for inner in inner_pairs {
-        match inner.as_rule() {
-            Rule::term => {
-                let val =
-                    eval_operand_term(inner, const_map, label_offset_map, &mut label_sections)?;
-                terms.push(val);
-            }
-            Rule::bin_op => {
-                ops.push(match inner.as_str() {
-                    "+" => "+",
-                    "-" => "-",
-                    "*" => "*",
-                    "/" => "/",
-                    _ => "+",
-                });
+            let name = term_inners[0].as_str().to_string();
+            if let Some(value) = const_map.get(&name) {
+                return Ok(Either::Right(value.clone()));
             }
-            _ => {}
+            // Not in const_map — return as unresolved for build_program to handle
+            return Ok(Either::Left(name));
         }
     }
 
+    let mut label_sections: Vec<(String, Section)> = Vec::new();
+    let operand = eval_linear_expression(pair, const_map, label_offset_map, &mut label_sections)?;
+
     // Bounds check: all labels in the expression must be from the same section
     if label_sections.len() > 1 {
         let first_section = label_sections[0].1;
@@ -125,42 +130,111 @@ fn eval_operand_expression(
         }
     }
 
-    // Evaluate left-to-right
-    if terms.is_empty() {
-        return Err(CompileError::ParseError {
-            error: "Invalid operand expression".to_string(),
+    match (operand.weight, label_sections.first()) {
+        (Some(0), _) => Ok(Either::Right(operand.value)),
+        // Every label is in the same section, so any of them can anchor the
+        // result: `end - start + buffer` is `buffer` plus the difference.
+        (Some(1), Some((label, _))) => {
+            let (offset, _) = &label_offset_map[label];
+            let addend = operand.value.to_i64() - offset.to_i64();
+            Ok(Either::Left(with_addend(label, addend)))
+        }
+        _ => Err(CompileError::UnrelocatableExpression {
             span: span_range,
             custom_label: None,
-        });
+        }),
     }
+}
 
-    let mut result = terms[0].clone();
-    for (i, op) in ops.iter().enumerate() {
-        if i + 1 < terms.len() {
-            let rhs = &terms[i + 1];
-            let folded = match *op {
-                "+" => result.checked_add(rhs),
-                "-" => result.checked_sub(rhs),
-                "*" => result.checked_mul(rhs),
-                "/" => result.checked_div(rhs),
-                _ => Some(result.clone()),
-            };
-            result = folded.ok_or_else(|| {
-                let detail = if *op == "/" && rhs.to_i64() == 0 {
-                    "division by zero in constant expression".to_string()
-                } else {
-                    format!("arithmetic overflow in constant expression ('{op}')")
-                };
-                CompileError::ArithmeticError {
-                    error: detail,
-                    span: span_range.clone(),
-                    custom_label: None,
-                }
-            })?;
+/// A partially evaluated operand expression: its value with every label
+/// counted at its section offset, and the net number of times a label was
+/// added. A weight of 0, as in `end - start`, is a constant; a weight of 1 is
+/// one label plus a constant. The weight is `None` once a label has been
+/// multiplied by another label or divided, which no relocation can express.
+struct LinearValue {
+    value: Number,
+    weight: Option<i64>,
+}
+
+impl LinearValue {
+    fn combine(self, op: &str, rhs: LinearValue) -> Option<LinearValue> {
+        let value = match op {
+            "+" => self.value.checked_add(&rhs.value),
+            "-" => self.value.checked_sub(&rhs.value),
+            "*" => self.value.checked_mul(&rhs.value),
+            "/" => self.value.checked_div(&rhs.value),
+            _ => Some(self.value.clone()),
+        }?;
+        let weight = match (op, self.weight, rhs.weight) {
+            ("+", Some(left), Some(right)) => left.checked_add(right),
+            ("-", Some(left), Some(right)) => left.checked_sub(right),
+            ("*", Some(left), Some(0)) => left.checked_mul(rhs.value.to_i64()),
+            ("*", Some(0), Some(right)) => right.checked_mul(self.value.to_i64()),
+            (_, Some(0), Some(0)) => Some(0),
+            _ => None,
+        };
+        Some(LinearValue { value, weight })
+    }
+}
+
+fn eval_linear_expression(
+    pair: Pair<Rule>,
+    const_map: &HashMap<String, Number>,
+    label_offset_map: &HashMap<String, (Number, Section)>,
+    label_sections: &mut Vec<(String, Section)>,
+) -> Result<LinearValue, CompileError> {
+    let span = pair.as_span();
+    let span_range = span.start()..span.end();
+
+    let mut terms: Vec<LinearValue> = Vec::new();
+    let mut ops: Vec<&str> = Vec::new();
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::term => {
+                terms.push(eval_operand_term(
+                    inner,
+                    const_map,
+                    label_offset_map,
+                    label_sections,
+                )?);
+            }
+            Rule::bin_op => {
+                ops.push(match inner.as_str() {
+                    "+" => "+",
+                    "-" => "-",
+                    "*" => "*",
+                    "/" => "/",
+                    _ => "+",
+                });
+            }
+            _ => {}
         }
     }
 
-    Ok(Either::Right(result))
+    // Evaluate left-to-right
+    let mut terms = terms.into_iter();
+    let mut result = terms.next().ok_or_else(|| CompileError::ParseError {
+        error: "Invalid operand expression".to_string(),
+        span: span_range.clone(),
+        custom_label: None,
+    })?;
+    for (op, rhs) in ops.into_iter().zip(terms) {
+        let divides_by_zero = op == "/" && rhs.value.to_i64() == 0;
+        result = result.combine(op, rhs).ok_or_else(|| {
+            let detail = if divides_by_zero {
+                "division by zero in constant expression".to_string()
+            } else {
+                format!("arithmetic overflow in constant expression ('{op}')")
+            };
+            CompileError::ArithmeticError {
+                error: detail,
+                span: span_range.clone(),
+                custom_label: None,
+            }
+        })?;
+    }
+
+    Ok(result)
 }
 
 fn eval_operand_term(
@@ -168,38 +242,37 @@ fn eval_operand_term(
     const_map: &HashMap<String, Number>,
     label_offset_map: &HashMap<String, (Number, Section)>,
     label_sections: &mut Vec<(String, Section)>,
-) -> Result<Number, CompileError> {
+) -> Result<LinearValue, CompileError> {
     let span = pair.as_span();
     let span_range = span.start()..span.end();
+    let constant = |value| LinearValue {
+        value,
+        weight: Some(0),
+    };
 
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::expression => {
-                // Parenthesized sub-expression — recurse, but must fully resolve
-                let result = eval_operand_expression(inner, const_map, label_offset_map)?;
-                return match result {
-                    Either::Right(val) => Ok(val),
-                    Either::Left(name) => Err(CompileError::ParseError {
-                        error: format!(
-                            "Cannot use unresolved symbol '{}' in arithmetic expression",
-                            name
-                        ),
-                        span: span_range,
-                        custom_label: None,
-                    }),
-                };
+                // Parenthesized sub-expression
+                return eval_linear_expression(inner, const_map, label_offset_map, label_sections);
             }
             Rule::number => {
-                return parse_number(inner);
+                return parse_number(inner).map(constant);
+            }
+            Rule::typed_literal => {
+                return parse_typed_literal(inner).map(constant);
             }
             Rule::symbol => {
                 let name = inner.as_str().to_string();
                 if let Some(value) = const_map.get(&name) {
-                    return Ok(value.clone());
+                    return Ok(constant(value.clone()));
                 }
                 if let Some((value, section)) = label_offset_map.get(&name) {
-                    label_sections.push((name, *section));
-                    return Ok(value.clone());
+                    label_sections.push((name.clone(), *section));
+                    return Ok(LinearValue {
+                        value: value.clone(),
+                        weight: Some(1),
+                    });
                 }
                 return Err(CompileError::ParseError {
                     error: format!("Undefined symbol '{}' in arithmetic expression", name),
@@ -218,26 +291,81 @@ fn eval_operand_term(
     })
 }
 
+/// Attach an addend to a symbol reference, giving `message+8` or
+/// `message-1`. A zero addend leaves the bare symbol.
+pub(crate) fn with_addend(symbol: &str, addend: i64) -> String {
+    if addend == 0 {
+        symbol.to_string()
+    } else {
+        format!("{}{:+}", symbol, addend)
+    }
+}
+
+/// Split a symbol reference made by [`with_addend`] into the symbol and its
+/// addend. The addend is searched for from the end, since symbols renamed by
+/// the linker carry a file path that may itself contain `-`.
+pub(crate) fn split_addend(reference: &str) -> (&str, i64) {
+    reference
+        .rfind(['+', '-'])
+        .filter(|index| *index > 0)
+        .and_then(|index| {
+            let addend = reference[index..].parse().ok()?;
+            Some((&reference[..index], addend))
+        })
+        .unwrap_or((reference, 0))
+}
+
+/// Parse a jump target. A label may carry an addend of numbers and `.equ`
+/// constants, counted in instructions; it's kept on the label, as in
+/// `table+2`, until the label's offset is known.
 pub fn parse_jump_target(
     pair: Pair<Rule>,
-    _const_map: &HashMap<String, Number>,
+    const_map: &HashMap<String, Number>,
 ) -> Result<Either<String, i16>, CompileError> {
     let span = pair.as_span();
     let span_range = span.start()..span.end();
+    let mut label = None;
+    let mut addend: i64 = 0;
+    let mut sign: i64 = 1;
 
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::symbol | Rule::numeric_label_ref => {
-                return Ok(Either::Left(inner.as_str().to_string()));
+                label = Some(inner.as_str().to_string());
             }
             Rule::number | Rule::signed_number => {
                 let num = parse_number(inner)?;
                 return Ok(Either::Right(num.to_i16()));
             }
+            Rule::memory_op => {
+                sign = if inner.as_str() == "+" { 1 } else { -1 };
+            }
+            Rule::memory_offset => {
+                for offset_inner in inner.into_inner() {
+                    let value = match offset_inner.as_rule() {
+                        Rule::number => parse_number(offset_inner)?.to_i64(),
+                        Rule::symbol => match const_map.get(offset_inner.as_str()) {
+                            Some(value) => value.to_i64(),
+                            None => {
+                                return Err(CompileError::UnrelocatableExpression {
+                                    span: span_range,
+                                    custom_label: None,
+                                });
+                            }
+                        },
+                        _ => continue,
+                    };
+                    addend = addend.wrapping_add(sign.wrapping_mul(value));
+                }
+            }
             _ => {}
         }
     }
 
+    if let Some(label) = label {
+        return Ok(Either::Left(with_addend(&label, addend)));
+    }
+
     Err(CompileError::ParseError {
         error: "Invalid jump target".to_string(),
         span: span_range,
@@ -268,13 +396,13 @@ pub fn parse_memory_ref(
                         Rule::number => {
                             let num = parse_number(offset_inner)?;
                             accumulated_offset =
-                                accumulated_offset.wrapping_add(sign * num.to_i16());
+                                accumulated_offset.wrapping_add(sign.wrapping_mul(num.to_i16()));
                         }
                         Rule::symbol => {
                             let name = offset_inner.as_str().to_string();
                             if let Some(value) = const_map.get(&name) {
-                                accumulated_offset =
-                                    accumulated_offset.wrapping_add(sign * value.to_i16());
+                                accumulated_offset = accumulated_offset
+                                    .wrapping_add(sign.wrapping_mul(value.to_i16()));
                             } else if unresolved_symbol.is_none() {
                                 unresolved_symbol = Some(name);
                             }
@@ -318,7 +446,7 @@ pub fn parse_number(pair: Pair<Rule>) -> Result<Number, CompileError> {
     if value.starts_with("0x") {
         let hex_str = value.trim_start_matches("0x");
         if let Ok(value) = u64::from_str_radix(hex_str, 16) {
-            return Ok(Number::Addr(sign * (value as i64)));
+            return Ok(Number::Addr((value as i64).wrapping_mul(sign)));
         }
     }
 
@@ -329,6 +457,52 @@ pub fn parse_number(pair: Pair<Rule>) -> Result<Number, CompileError> {
     })
 }
 
+/// Evaluate a unit-aware literal. `@lamports(n)` counts lamports and
+/// `@lamports(n SOL)` whole SOL; `@bp(n)` counts basis points and `@bp(n%)`
+/// percent, capped at 100%.
+pub fn parse_typed_literal(pair: Pair<Rule>) -> Result<Number, CompileError> {
+    let span = pair.as_span();
+    let error = |reason: String| CompileError::InvalidTypedLiteral {
+        literal: span.as_str().to_string(),
+        reason,
+        span: span.start()..span.end(),
+        custom_label: None,
+    };
+
+    let mut is_bp = false;
+    let mut amount = "";
+    let mut decimals = 0;
+    for literal in pair.into_inner() {
+        is_bp = literal.as_rule() == Rule::bp_literal;
+        for inner in literal.into_inner() {
+            match inner.as_rule() {
+                Rule::decimal_amount => amount = inner.as_str(),
+                Rule::lamports_unit if inner.as_str().eq_ignore_ascii_case("sol") => decimals = 9,
+                Rule::percent => decimals = 2,
+                _ => {}
+            }
+        }
+    }
+
+    let digits = amount.replace('_', "");
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    if fraction.len() > decimals {
+        return Err(error(match decimals {
+            0 if is_bp => "basis points must be a whole number".to_string(),
+            0 => "lamports must be a whole number".to_string(),
+            _ => format!("at most {} decimal places are allowed", decimals),
+        }));
+    }
+    let scaled = format!("{}{:0<width$}", whole, fraction, width = decimals);
+    let value = scaled
+        .parse::<i64>()
+        .map_err(|_| error("does not fit in a 64-bit immediate".to_string()))?;
+    if is_bp && value > 10_000 {
+        return Err(error("basis points cannot exceed 10000 (100%)".to_string()));
+    }
+    Ok(Number::Int(value))
+}
+
 // Shared process functions.
 
 pub fn process_exit(span: std::ops::Range<usize>) -> Result<Instruction, CompileError> {
@@ -354,7 +528,17 @@ pub(crate) fn process_lddw(
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::register => dst = Some(parse_register(inner)?),
-            Rule::operand => imm = Some(parse_operand(inner, const_map, label_offset_map)?),
+            Rule::operand => imm = Some(parse_imm64_operand(inner, const_map, label_offset_map)?),
+            Rule::lddw_literal => {
+                let span = inner.as_span();
+                return Err(CompileError::ParseError {
+                    error: "Literal pool operands are not supported, lddw takes the 64-bit \
+                            value or label directly"
+                        .to_string(),
+                    span: span.start()..span.end(),
+                    custom_label: Some("Remove the '='".to_string()),
+                });
+            }
             _ => {}
         }
     }
@@ -491,3 +675,30 @@ pub(crate) fn check_arch_v3(pair: &Pair<Rule>, arch: SbpfArch) -> Result<(), Com
 
     Ok(())
 }
+
+/// Reject an instruction outside `arch`'s instruction set, such as the
+/// product/quotient/remainder family before v2 or `mul64` in v2.
+pub(crate) fn check_arch_support(
+    instruction: &Instruction,
+    arch: SbpfArch,
+) -> Result<(), CompileError> {
+    if arch.supports(instruction.opcode) {
+        return Ok(());
+    }
+
+    let error = match SbpfArch::introduced(instruction.opcode) {
+        Some(required) if required > arch => format!(
+            "instruction '{}' requires arch {}",
+            instruction.opcode, required
+        ),
+        _ => format!(
+            "instruction '{}' is not available in arch {}",
+            instruction.opcode, arch
+        ),
+    };
+    Err(CompileError::ParseError {
+        error,
+        span: instruction.span.clone(),
+        custom_label: None,
+    })
+}
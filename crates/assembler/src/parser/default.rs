@@ -42,14 +42,14 @@ pub(crate) fn process_instruction(
             Rule::instr_jump_imm => {
                 return process_jump_imm(inner, const_map, label_offset_map, span_range);
             }
-            Rule::instr_jump_reg => return process_jump_reg(inner, span_range, arch),
+            Rule::instr_jump_reg => return process_jump_reg(inner, const_map, span_range, arch),
             Rule::instr_jump32_imm => {
                 check_arch_v3(&inner, arch)?;
                 return process_jump_imm(inner, const_map, label_offset_map, span_range);
             }
             Rule::instr_jump32_reg => {
                 check_arch_v3(&inner, arch)?;
-                return process_jump_reg(inner, span_range, arch);
+                return process_jump_reg(inner, const_map, span_range, arch);
             }
             Rule::instr_jump_uncond => return process_jump_uncond(inner, const_map, span_range),
             Rule::instr_endian => return process_endian(inner, span_range),
@@ -275,6 +275,7 @@ fn process_jump_imm(
 
 fn process_jump_reg(
     pair: Pair<Rule>,
+    const_map: &HashMap<String, Number>,
     span: std::ops::Range<usize>,
     arch: SbpfArch,
 ) -> Result<Instruction, CompileError> {
@@ -312,7 +313,7 @@ fn process_jump_reg(
                     src = Some(parse_register(inner)?);
                 }
             }
-            Rule::jump_target => off = Some(parse_jump_target(inner, &HashMap::new())?),
+            Rule::jump_target => off = Some(parse_jump_target(inner, const_map)?),
             _ => {}
         }
     }
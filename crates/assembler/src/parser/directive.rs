@@ -1,8 +1,13 @@
 use {
-    super::{ParseContext, Rule, Token, common::parse_number},
+    super::{
+        ParseContext, Rule, Section, Token,
+        common::{parse_number, parse_typed_literal},
+        section_from_directive,
+    },
     crate::{
-        astnode::{ASTNode, ExternDecl, GlobalDecl, ROData, RodataDecl},
+        astnode::{ASTNode, ExternDecl, GlobalDecl, ROData, RodataDecl, SourceLocation},
         errors::CompileError,
+        interface::declared_error,
     },
     pest::iterators::Pair,
     sbpf_common::inst_param::Number,
@@ -59,7 +64,9 @@ pub fn process_directive_inner(pair: Pair<Rule>, ctx: &mut ParseContext) {
                 for equ_inner in inner.into_inner() {
                     match equ_inner.as_rule() {
                         Rule::identifier => {
-                            ident = Some(equ_inner.as_str().to_string());
+                            let span = equ_inner.as_span();
+                            ident =
+                                Some((equ_inner.as_str().to_string(), span.start()..span.end()));
                         }
                         Rule::expression => match eval_expression(equ_inner, ctx.const_map) {
                             Ok(v) => value = Some(v),
@@ -69,61 +76,191 @@ pub fn process_directive_inner(pair: Pair<Rule>, ctx: &mut ParseContext) {
                     }
                 }
 
-                if let (Some(name), Some(val)) = (ident, value) {
+                if let (Some((name, span)), Some(val)) = (ident, value) {
+                    if ctx.const_spans.contains_key(&name) {
+                        ctx.warnings.push(CompileError::ShadowedConstant {
+                            name: name.clone(),
+                            span: span.clone(),
+                            custom_label: None,
+                        });
+                    }
+                    ctx.const_spans.insert(name.clone(), span);
                     ctx.const_map.insert(name, val);
                 }
             }
             Rule::directive_section => {
-                let section_name = inner.as_str().trim_start_matches('.');
-                match section_name {
-                    "text" => ctx.rodata_phase = false,
-                    "rodata" => {
-                        ctx.rodata_phase = true;
-                        let span = inner.as_span();
-                        ctx.ast.nodes.push(ASTNode::RodataDecl {
-                            rodata_decl: RodataDecl {
-                                span: span.start()..span.end(),
-                            },
-                        });
-                    }
-                    _ => {}
+                let Some(section) = section_from_directive(inner.as_str()) else {
+                    continue;
+                };
+                ctx.bind_pending_data_label();
+                ctx.section = section;
+                if section == Section::Rodata {
+                    let span = inner.as_span();
+                    ctx.ast.nodes.push(ASTNode::RodataDecl {
+                        rodata_decl: RodataDecl {
+                            span: span.start()..span.end(),
+                        },
+                    });
+                }
+            }
+            Rule::directive_error => {
+                if let Err(e) = process_error_directive(inner, ctx) {
+                    ctx.errors.push(e);
+                }
+            }
+            Rule::directive_file => {
+                if let Err(e) = process_file_directive(inner, ctx) {
+                    ctx.errors.push(e);
+                }
+            }
+            Rule::directive_loc => {
+                if let Err(e) = process_loc_directive(inner, ctx) {
+                    ctx.errors.push(e);
                 }
             }
-            // Data directives (.ascii, .byte, etc.) — handle as rodata if
-            // we're in the rodata phase and there's a pending label.
+            // Data directives (.ascii, .byte, etc.) — handle as data if
+            // we're in .rodata, .data or .bss, under the pending label if
+            // there is one.
             Rule::directive_ascii
             | Rule::directive_byte
             | Rule::directive_short
             | Rule::directive_word
             | Rule::directive_int
             | Rule::directive_long
-            | Rule::directive_quad => {
-                if ctx.rodata_phase
-                    && let Some((label_name, label_span)) = ctx.pending_rodata_label.take()
-                {
-                    match process_rodata_directive(label_name, label_span, pair_clone) {
-                        Ok(rodata) => {
-                            let size = rodata.get_size();
-                            ctx.ast.rodata_nodes.push(ASTNode::ROData {
-                                rodata,
-                                offset: ctx.rodata_offset,
-                            });
-                            ctx.rodata_offset += size;
-                        }
-                        Err(e) => ctx.errors.push(e),
-                    }
-                    return;
+            | Rule::directive_quad
+            | Rule::directive_endian
+            | Rule::directive_zero
+            | Rule::directive_space
+            | Rule::directive_fill
+                if ctx.section != Section::Text =>
+            {
+                let (label_name, label_span) = ctx.pending_data_label.take().unwrap_or_else(|| {
+                    let span = inner.as_span();
+                    (String::new(), span.start()..span.end())
+                });
+                match process_rodata_directive(label_name, label_span, pair_clone, ctx.const_map) {
+                    Ok(rodata) => ctx.push_data(rodata),
+                    Err(e) => ctx.errors.push(e),
                 }
+                return;
             }
             _ => {}
         }
     }
 }
 
+/// Record a custom error, as `.error <code>, "<message>"`.
+fn process_error_directive(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<(), CompileError> {
+    let span = pair.as_span().start()..pair.as_span().end();
+    let mut inner = pair.into_inner();
+    let (Some(code), Some(message)) = (inner.next(), inner.next()) else {
+        return Ok(());
+    };
+    let code_span = code.as_span().start()..code.as_span().end();
+    let constant = code.as_str().trim().to_string();
+    let value = eval_expression(code, ctx.const_map)?.to_i64();
+    let code = u32::try_from(value).ok().filter(|&code| code != 0).ok_or(
+        CompileError::InvalidErrorCode {
+            code: value,
+            span: code_span,
+            custom_label: None,
+        },
+    )?;
+    if ctx
+        .ast
+        .custom_errors
+        .iter()
+        .any(|(error, _)| error.code == code)
+    {
+        return Err(CompileError::DuplicateErrorCode {
+            code,
+            span,
+            custom_label: None,
+        });
+    }
+
+    let message = message.as_str();
+    ctx.ast.custom_errors.push((
+        declared_error(&constant, code, &message[1..message.len() - 1]),
+        span,
+    ));
+    Ok(())
+}
+
+/// Number a source file for `.loc`, as `.file <number> ["<directory>"]
+/// "<name>"`. The unnumbered form only names the object's source and has no
+/// effect.
+fn process_file_directive(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<(), CompileError> {
+    let mut number = None;
+    let mut strings = Vec::new();
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::number => number = Some(parse_u32(inner)?),
+            Rule::string_literal => {
+                let text = inner.as_str();
+                strings.push(text[1..text.len() - 1].to_string());
+            }
+            _ => {}
+        }
+    }
+    let Some(number) = number else {
+        return Ok(());
+    };
+    let mut strings = strings.into_iter();
+    let (directory, name) = match (strings.next(), strings.next()) {
+        (Some(directory), Some(name)) => (directory, name),
+        (Some(name), None) => (String::new(), name),
+        _ => return Ok(()),
+    };
+    ctx.source_files.insert(number, (directory, name));
+    Ok(())
+}
+
+/// Attribute the instructions that follow to a source line, as `.loc <file>
+/// <line> [<column>]`.
+fn process_loc_directive(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<(), CompileError> {
+    let mut numbers = Vec::new();
+    for inner in pair.into_inner() {
+        if inner.as_rule() == Rule::number {
+            let span = inner.as_span().start()..inner.as_span().end();
+            numbers.push((parse_u32(inner)?, span));
+        }
+    }
+    let [(file, file_span), (line, _), rest @ ..] = numbers.as_slice() else {
+        return Ok(());
+    };
+    let (directory, name) =
+        ctx.source_files
+            .get(file)
+            .cloned()
+            .ok_or_else(|| CompileError::UndeclaredSourceFile {
+                file: *file,
+                span: file_span.clone(),
+                custom_label: None,
+            })?;
+    ctx.location = Some(SourceLocation {
+        directory,
+        file: name,
+        line: *line,
+        column: rest.first().map_or(0, |(column, _)| *column),
+    });
+    Ok(())
+}
+
+/// A `.file` or `.loc` operand, which must fit in a u32.
+fn parse_u32(pair: Pair<Rule>) -> Result<u32, CompileError> {
+    let span = pair.as_span().start()..pair.as_span().end();
+    u32::try_from(parse_number(pair)?.to_i64()).map_err(|_| CompileError::OutOfRangeLiteral {
+        span,
+        custom_label: None,
+    })
+}
+
 pub fn process_rodata_directive(
     label_name: String,
     label_span: std::ops::Range<usize>,
     pair: Pair<Rule>,
+    const_map: &HashMap<String, Number>,
 ) -> Result<ROData, CompileError> {
     let inner_pair = if pair.as_rule() == Rule::directive_inner {
         pair
@@ -172,8 +309,9 @@ pub fn process_rodata_directive(
             | Rule::directive_word
             | Rule::directive_int
             | Rule::directive_long
-            | Rule::directive_quad => {
-                let directive_name = match inner.as_rule() {
+            | Rule::directive_quad
+            | Rule::directive_endian => {
+                let mut directive_name = match inner.as_rule() {
                     Rule::directive_byte => "byte",
                     Rule::directive_short => "short",
                     Rule::directive_word => "word",
@@ -185,13 +323,17 @@ pub fn process_rodata_directive(
 
                 let mut values = Vec::new();
                 for byte_inner in inner.into_inner() {
-                    if byte_inner.as_rule() == Rule::number {
-                        values.push(parse_number(byte_inner)?);
+                    match byte_inner.as_rule() {
+                        Rule::number => values.push(parse_number(byte_inner)?),
+                        Rule::endian_width => {
+                            directive_name = byte_inner.as_str().trim_start_matches('.')
+                        }
+                        _ => {}
                     }
                 }
 
                 let values_span = directive_span.start()..directive_span.end();
-                return Ok(ROData {
+                let rodata = ROData {
                     name: label_name,
                     args: vec![
                         Token::Directive(
@@ -201,7 +343,71 @@ pub fn process_rodata_directive(
                         Token::VectorLiteral(values, values_span),
                     ],
                     span: label_span,
-                });
+                };
+                // A byte order is only asked for when the exact bytes matter,
+                // so values that don't fit are rejected rather than truncated.
+                if ROData::endian_width(directive_name).is_some() {
+                    rodata.verify()?;
+                }
+                return Ok(rodata);
+            }
+            Rule::directive_zero | Rule::directive_space | Rule::directive_fill => {
+                let directive_span = directive_span.start()..directive_span.end();
+                let is_fill = inner.as_rule() == Rule::directive_fill;
+                let operands = inner
+                    .into_inner()
+                    .map(|operand| {
+                        let span = operand.as_span().start()..operand.as_span().end();
+                        eval_expression(operand, const_map).map(|value| (value, span))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (count, count_span) = operands[0].clone();
+                // `.space n, fill` repeats a byte, `.fill count, size, value`
+                // a value of `size` bytes.
+                let (size, value) = match (is_fill, operands.get(1), operands.get(2)) {
+                    (true, size, value) => {
+                        let size = match size {
+                            Some((size, span)) => {
+                                ROData::validate_immediate_range(size, 1, 8, span.clone())?;
+                                size.clone()
+                            }
+                            None => Number::Int(1),
+                        };
+                        (
+                            size,
+                            value.map_or(Number::Int(0), |(value, _)| value.clone()),
+                        )
+                    }
+                    (false, fill, _) => (
+                        Number::Int(1),
+                        fill.map_or(Number::Int(0), |(fill, _)| fill.clone()),
+                    ),
+                };
+
+                let rodata = if value.to_i64() == 0 {
+                    // Zero fill stays a reservation, so it is allowed in .bss.
+                    let bytes = count.checked_mul(&size).unwrap_or(Number::Int(-1));
+                    ROData {
+                        name: label_name,
+                        args: vec![
+                            Token::Directive("zero".to_string(), directive_span),
+                            Token::ImmediateValue(bytes, count_span),
+                        ],
+                        span: label_span,
+                    }
+                } else {
+                    let values_span = operands[0].1.start..operands[operands.len() - 1].1.end;
+                    ROData {
+                        name: label_name,
+                        args: vec![
+                            Token::Directive("fill".to_string(), directive_span),
+                            Token::VectorLiteral(vec![count, size, value], values_span),
+                        ],
+                        span: label_span,
+                    }
+                };
+                rodata.verify()?;
+                return Ok(rodata);
             }
             _ => {}
         }
@@ -213,7 +419,7 @@ pub fn process_rodata_directive(
     })
 }
 
-fn eval_expression(
+pub(crate) fn eval_expression(
     pair: Pair<Rule>,
     const_map: &HashMap<String, Number>,
 ) -> Result<Number, CompileError> {
@@ -286,6 +492,9 @@ fn eval_term(
             Rule::number => {
                 return parse_number(inner);
             }
+            Rule::typed_literal => {
+                return parse_typed_literal(inner);
+            }
             Rule::symbol => {
                 let name = inner.as_str().to_string();
                 if let Some(value) = const_map.get(&name) {
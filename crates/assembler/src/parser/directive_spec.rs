@@ -0,0 +1,478 @@
+use {
+    super::{Rule, SbpfParser},
+    crate::errors::CompileError,
+    pest::Parser,
+    std::ops::Range,
+};
+
+/// Kind of value a directive operand must be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Identifier,
+    Symbol,
+    Expression,
+    Number,
+    String,
+}
+
+impl OperandKind {
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            OperandKind::Identifier => "<identifier>",
+            OperandKind::Symbol => "<symbol>",
+            OperandKind::Expression => "<expression>",
+            OperandKind::Number => "<number>",
+            OperandKind::String => "<string>",
+        }
+    }
+
+    fn rule(&self) -> Rule {
+        match self {
+            OperandKind::Identifier => Rule::identifier,
+            OperandKind::Symbol => Rule::symbol,
+            OperandKind::Expression => Rule::expression,
+            OperandKind::Number => Rule::number,
+            OperandKind::String => Rule::string_literal,
+        }
+    }
+
+    /// Whether `text` is exactly one operand of this kind.
+    fn matches(&self, text: &str) -> bool {
+        SbpfParser::parse(self.rule(), text)
+            .ok()
+            .and_then(|mut pairs| pairs.next())
+            .is_some_and(|pair| pair.as_span().end() == text.len())
+    }
+}
+
+/// Whether the last operand of a directive may be repeated, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    None,
+    /// Additional operands separated by commas.
+    Comma,
+    /// Additional operands separated by whitespace.
+    Space,
+}
+
+/// Operand grammar of a directive, used to validate it and to describe it in
+/// diagnostics.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectiveSpec {
+    /// Directive names, canonical name first.
+    pub names: &'static [&'static str],
+    pub operands: &'static [OperandKind],
+    /// Trailing operands that may be left out, after `operands`.
+    pub optional: &'static [OperandKind],
+    pub repeat: Repeat,
+    pub description: &'static str,
+}
+
+pub const DIRECTIVES: &[DirectiveSpec] = &[
+    DirectiveSpec {
+        names: &[".globl", ".global"],
+        operands: &[OperandKind::Identifier],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Export the program entrypoint",
+    },
+    DirectiveSpec {
+        names: &[".extern"],
+        operands: &[OperandKind::Symbol],
+        optional: &[],
+        repeat: Repeat::Space,
+        description: "Declare symbols defined outside the program",
+    },
+    DirectiveSpec {
+        names: &[".equ"],
+        operands: &[OperandKind::Identifier, OperandKind::Expression],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Define a named constant",
+    },
+    DirectiveSpec {
+        names: &[".error"],
+        operands: &[OperandKind::Expression, OperandKind::String],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Attach a message to a custom error code",
+    },
+    DirectiveSpec {
+        names: &[".text"],
+        operands: &[],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Switch to the code section",
+    },
+    DirectiveSpec {
+        names: &[".data"],
+        operands: &[],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Switch to the data section",
+    },
+    DirectiveSpec {
+        names: &[".rodata"],
+        operands: &[],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Switch to the read-only data section",
+    },
+    DirectiveSpec {
+        names: &[".bss"],
+        operands: &[],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Switch to the zero-initialized data section",
+    },
+    DirectiveSpec {
+        names: &[".ascii"],
+        operands: &[OperandKind::String],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Emit the bytes of a string",
+    },
+    DirectiveSpec {
+        names: &[".byte"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 8-bit values",
+    },
+    DirectiveSpec {
+        names: &[".short", ".half"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 16-bit values",
+    },
+    DirectiveSpec {
+        names: &[".word"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 16-bit values",
+    },
+    DirectiveSpec {
+        names: &[".int"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 32-bit values",
+    },
+    DirectiveSpec {
+        names: &[".long"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 32-bit values",
+    },
+    DirectiveSpec {
+        names: &[".quad"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 64-bit values",
+    },
+    DirectiveSpec {
+        names: &[".le16", ".be16"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 16-bit values in the given byte order",
+    },
+    DirectiveSpec {
+        names: &[".le32", ".be32"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 32-bit values in the given byte order",
+    },
+    DirectiveSpec {
+        names: &[".le64", ".be64"],
+        operands: &[OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Comma,
+        description: "Emit 64-bit values in the given byte order",
+    },
+    DirectiveSpec {
+        names: &[".zero"],
+        operands: &[OperandKind::Expression],
+        optional: &[],
+        repeat: Repeat::None,
+        description: "Reserve zero-filled bytes",
+    },
+    DirectiveSpec {
+        names: &[".space"],
+        operands: &[OperandKind::Expression],
+        optional: &[OperandKind::Expression],
+        repeat: Repeat::None,
+        description: "Reserve bytes, zero or set to a fill byte",
+    },
+    DirectiveSpec {
+        names: &[".fill"],
+        operands: &[OperandKind::Expression],
+        optional: &[OperandKind::Expression, OperandKind::Expression],
+        repeat: Repeat::None,
+        description: "Repeat a value of 1 to 8 bytes",
+    },
+    DirectiveSpec {
+        names: &[".file"],
+        operands: &[OperandKind::Number, OperandKind::String],
+        optional: &[],
+        repeat: Repeat::Space,
+        description: "Number a source file for .loc",
+    },
+    DirectiveSpec {
+        names: &[".loc"],
+        operands: &[OperandKind::Number, OperandKind::Number],
+        optional: &[],
+        repeat: Repeat::Space,
+        description: "Attribute the following code to a source line",
+    },
+];
+
+impl DirectiveSpec {
+    pub fn lookup(name: &str) -> Option<&'static DirectiveSpec> {
+        DIRECTIVES.iter().find(|spec| spec.names.contains(&name))
+    }
+
+    /// Grammar of the directive, e.g. `.byte <number>[, <number>...]`.
+    pub fn usage(&self, name: &str) -> String {
+        let separator = if self.repeat == Repeat::Space {
+            " "
+        } else {
+            ", "
+        };
+        let mut usage = name.to_string();
+        if !self.operands.is_empty() {
+            usage.push(' ');
+            usage.push_str(
+                &self
+                    .operands
+                    .iter()
+                    .map(OperandKind::placeholder)
+                    .collect::<Vec<_>>()
+                    .join(separator),
+            );
+        }
+        if let (Repeat::Comma | Repeat::Space, Some(last)) = (self.repeat, self.operands.last()) {
+            usage.push_str(&format!("[{}{}...]", separator, last.placeholder()));
+        }
+        for kind in self.optional {
+            usage.push_str(&format!("[{}{}", separator, kind.placeholder()));
+        }
+        usage.push_str(&"]".repeat(self.optional.len()));
+        usage
+    }
+
+    /// The kind expected at operand `index`, if the directive takes that many.
+    fn operand(&self, index: usize) -> Option<OperandKind> {
+        match self.operands.get(index) {
+            Some(kind) => Some(*kind),
+            None if self.repeat != Repeat::None => self.operands.last().copied(),
+            None => self.optional.get(index - self.operands.len()).copied(),
+        }
+    }
+}
+
+/// Check the directive statement on `line` (starting at byte `line_start` in
+/// the source) against its spec. Returns an error naming the offending
+/// operand, or `None` if the line is not a directive or the directive is well
+/// formed.
+pub(crate) fn validate_directive_line(line: &str, line_start: usize) -> Option<CompileError> {
+    let code = strip_line_comment(line);
+
+    // Skip a leading label.
+    let mut offset = code.len() - code.trim_start().len();
+    if let Some(colon) = code.find(':')
+        && !code[..colon].contains(['"', '.'])
+        && !code[..colon].trim().is_empty()
+    {
+        offset = colon + 1;
+        offset += code[offset..].len() - code[offset..].trim_start().len();
+    }
+    let statement = code[offset..].trim_end();
+    if !statement.starts_with('.') {
+        return None;
+    }
+
+    let name_len = statement
+        .find(char::is_whitespace)
+        .unwrap_or(statement.len());
+    let name = &statement[..name_len];
+    let name_span = line_start + offset..line_start + offset + name_len;
+    let Some(spec) = DirectiveSpec::lookup(name) else {
+        let known: Vec<&str> = DIRECTIVES.iter().map(|spec| spec.names[0]).collect();
+        return Some(CompileError::InvalidDirective {
+            directive: name.to_string(),
+            span: name_span,
+            custom_label: Some(format!("Expected one of {}", known.join(", "))),
+        });
+    };
+
+    let operands_start = offset + name_len;
+    let operands = split_operands(&code[operands_start..offset + statement.len()], spec.repeat);
+    let error = |expected: &str, span: Range<usize>| CompileError::InvalidDirectiveOperand {
+        directive: name.to_string(),
+        expected: expected.to_string(),
+        usage: spec.usage(name),
+        span,
+        custom_label: Some(format!("Expected {}", expected)),
+    };
+
+    for (index, operand) in operands.iter().enumerate() {
+        let span =
+            line_start + operands_start + operand.start..line_start + operands_start + operand.end;
+        let text = &code[operands_start + operand.start..operands_start + operand.end];
+        match spec.operand(index) {
+            Some(kind) if kind.matches(text) => {}
+            Some(kind) => return Some(error(kind.placeholder(), span)),
+            None => return Some(error("end of line", span)),
+        }
+    }
+
+    if let Some(missing) = spec.operands.get(operands.len()) {
+        let end = line_start + offset + statement.len();
+        return Some(error(missing.placeholder(), end..end + 1));
+    }
+    None
+}
+
+/// Byte ranges of each trimmed operand. Comma-separated operands may be empty,
+/// so `1,,2` reports the missing value.
+fn split_operands(text: &str, repeat: Repeat) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    if text.trim().is_empty() {
+        return pieces;
+    }
+
+    if repeat == Repeat::Space {
+        let mut start = None;
+        for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(i),
+                (true, Some(s)) => {
+                    pieces.push(s..i);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        return pieces;
+    }
+
+    let mut in_string = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices().chain([(text.len(), ',')]) {
+        match c {
+            '"' => in_string = !in_string,
+            ',' if !in_string || i == text.len() => {
+                let piece = &text[start..i];
+                let leading = piece.len() - piece.trim_start().len();
+                let s = start + leading;
+                pieces.push(s..s + piece.trim().len());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces
+}
+
+fn strip_line_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' | '#' if !in_string => return &line[..i],
+            '/' if !in_string && line[i + 1..].starts_with('/') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(line: &str) -> Option<(String, String, Range<usize>)> {
+        validate_directive_line(line, 0)
+            .map(|e| (e.to_string(), e.label().to_string(), e.span().clone()))
+    }
+
+    #[test]
+    fn test_usage() {
+        let usage = |name| DirectiveSpec::lookup(name).unwrap().usage(name);
+        assert_eq!(usage(".equ"), ".equ <identifier>, <expression>");
+        assert_eq!(usage(".half"), ".half <number>[, <number>...]");
+        assert_eq!(usage(".extern"), ".extern <symbol>[ <symbol>...]");
+        assert_eq!(usage(".rodata"), ".rodata");
+        assert_eq!(
+            usage(".fill"),
+            ".fill <expression>[, <expression>[, <expression>]]"
+        );
+    }
+
+    #[test]
+    fn test_valid_directives() {
+        for line in [
+            ".globl entrypoint",
+            "  .equ SIZE, 4 * (2 + 1) ; comment",
+            ".error ERROR_BASE + 1, \"Vault is empty\"",
+            "msg: .ascii \"a, b; c\"",
+            ".byte 1, 0x2, -3",
+            ".extern foo bar",
+            ".space 8, 0xff",
+            "pad: .fill COUNT, 4, -1",
+            ".rodata",
+            "mov64 r1, 1",
+        ] {
+            assert_eq!(validate(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_invalid_operand() {
+        assert_eq!(
+            validate(".byte 1, x, 3"),
+            Some((
+                "Invalid operand for '.byte': expected <number> (usage: .byte <number>[, <number>...])"
+                    .to_string(),
+                "Expected <number>".to_string(),
+                9..10
+            ))
+        );
+        assert_eq!(validate("data: .byte 1,, 3").unwrap().2, 14..14);
+        assert_eq!(validate(".equ 5, 1").unwrap().2, 5..6);
+        assert_eq!(validate(".ascii hello").unwrap().1, "Expected <string>");
+    }
+
+    #[test]
+    fn test_missing_and_extra_operands() {
+        let (message, label, span) = validate(".equ SIZE").unwrap();
+        assert_eq!(
+            message,
+            "Invalid operand for '.equ': expected <expression> (usage: .equ <identifier>, <expression>)"
+        );
+        assert_eq!(label, "Expected <expression>");
+        assert_eq!(span, 9..10);
+
+        let (_, label, span) = validate(".globl a, b").unwrap();
+        assert_eq!(label, "Expected end of line");
+        assert_eq!(span, 10..11);
+
+        let (_, label, span) = validate(".fill 1, 2, 3, 4").unwrap();
+        assert_eq!(label, "Expected end of line");
+        assert_eq!(span, 15..16);
+    }
+
+    #[test]
+    fn test_unknown_directive() {
+        let (message, label, span) = validate(".bogus 1").unwrap();
+        assert_eq!(message, "Invalid directive '.bogus'");
+        assert!(label.starts_with("Expected one of .globl, .extern, .equ"));
+        assert_eq!(span, 0..6);
+    }
+}
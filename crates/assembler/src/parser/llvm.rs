@@ -44,13 +44,15 @@ pub(crate) fn process_instruction(
             Rule::instr_llvm_jump_uncond => {
                 return process_jump_uncond(inner, const_map, span_range);
             }
-            Rule::instr_llvm_jump_reg => return process_jump_reg(inner, span_range, arch),
+            Rule::instr_llvm_jump_reg => {
+                return process_jump_reg(inner, const_map, span_range, arch);
+            }
             Rule::instr_llvm_jump_imm => {
                 return process_jump_imm(inner, const_map, label_offset_map, span_range);
             }
             Rule::instr_llvm_jump32_reg => {
                 check_arch_v3(&inner, arch)?;
-                return process_jump_reg(inner, span_range, arch);
+                return process_jump_reg(inner, const_map, span_range, arch);
             }
             Rule::instr_llvm_jump32_imm => {
                 check_arch_v3(&inner, arch)?;
@@ -372,6 +374,7 @@ fn process_jump_uncond(
 
 fn process_jump_reg(
     pair: Pair<Rule>,
+    const_map: &HashMap<String, Number>,
     span: std::ops::Range<usize>,
     arch: SbpfArch,
 ) -> Result<Instruction, CompileError> {
@@ -391,7 +394,7 @@ fn process_jump_reg(
                 }
             }
             Rule::cmp_op => op = Some(inner.as_str().to_string()),
-            Rule::jump_target => off = Some(parse_jump_target(inner, &HashMap::new())?),
+            Rule::jump_target => off = Some(parse_jump_target(inner, const_map)?),
             _ => {}
         }
     }
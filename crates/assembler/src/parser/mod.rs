@@ -1,26 +1,30 @@
 pub mod common;
 mod default;
-mod directive;
+pub(crate) mod directive;
+pub mod directive_spec;
 mod llvm;
 
 use {
     crate::{
         SbpfArch,
         ast::{AST, OptimizationConfig, build_program},
-        astnode::{ASTNode, Label},
+        astnode::{ASTNode, Label, ROData, SourceLocation},
         dynsym::{DynamicSymbolMap, RelDynMap},
         errors::CompileError,
+        preprocessor::comment::blank_block_comments,
         section::{CodeSection, DataSection, DebugSection},
+        syscalls::SyscallRegistry,
     },
-    directive::{process_directive_statement, process_rodata_directive},
+    directive::{eval_expression, process_directive_statement, process_rodata_directive},
+    directive_spec::validate_directive_line,
     pest::{
         Parser,
         error::{ErrorVariant, InputLocation},
-        iterators::Pair,
+        iterators::{Pair, Pairs},
     },
     pest_derive::Parser,
-    sbpf_common::{inst_param::Number, instruction::Instruction},
-    std::collections::HashMap,
+    sbpf_common::{inst_param::Number, instruction::Instruction, program_error::ErrorRegistry},
+    std::collections::{HashMap, HashSet},
 };
 
 #[derive(Parser)]
@@ -28,10 +32,15 @@ use {
 pub struct SbpfParser;
 
 /// Which section a label belongs to.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub(crate) enum Section {
+    #[default]
     Text,
     Rodata,
+    /// Writable data.
+    Data,
+    /// Zero-initialized writable data, reserved but not stored in the ELF.
+    Bss,
 }
 
 /// Context containing all mutable state during parsing
@@ -41,13 +50,82 @@ pub(crate) struct ParseContext<'a> {
     pub const_map: &'a mut HashMap<String, Number>,
     pub label_spans: &'a mut HashMap<String, std::ops::Range<usize>>,
     pub label_offset_map: &'a mut HashMap<String, (Number, Section)>,
+    /// Where each `.equ` constant was last defined.
+    pub const_spans: HashMap<String, std::ops::Range<usize>>,
     pub errors: Vec<CompileError>,
-    pub rodata_phase: bool,
+    pub warnings: Vec<CompileError>,
+    /// Section statements are currently assembled into.
+    pub section: Section,
     pub text_offset: u64,
     pub rodata_offset: u64,
+    pub data_offset: u64,
+    pub bss_offset: u64,
     pub missing_text_directive: bool,
-    /// A rodata label on its own line, waiting for the next data directive.
-    pub pending_rodata_label: Option<(String, std::ops::Range<usize>)>,
+    /// A data label on its own line, waiting for the next data directive.
+    pub pending_data_label: Option<(String, std::ops::Range<usize>)>,
+    /// Source files declared by `.file`, as (directory, name) by number.
+    pub source_files: HashMap<u32, (String, String)>,
+    /// Location set by the last `.loc`, covering the instructions after it.
+    pub location: Option<SourceLocation>,
+}
+
+impl ParseContext<'_> {
+    /// Offset of the next byte in the current `.rodata`, `.data` or `.bss`
+    /// section.
+    fn data_cursor(&self) -> u64 {
+        match self.section {
+            Section::Data => self.data_offset,
+            Section::Bss => self.bss_offset,
+            _ => self.rodata_offset,
+        }
+    }
+
+    /// Append a data directive to the current `.rodata`, `.data` or `.bss`
+    /// section.
+    pub fn push_data(&mut self, rodata: ROData) {
+        if self.section == Section::Bss && !rodata.is_zero_fill() {
+            self.errors.push(CompileError::InitializedBss {
+                span: rodata.args[0].span().clone(),
+                custom_label: None,
+            });
+            return;
+        }
+        let size = rodata.get_size();
+        let (nodes, offset) = match self.section {
+            Section::Data => (&mut self.ast.data_nodes, &mut self.data_offset),
+            Section::Bss => (&mut self.ast.bss_nodes, &mut self.bss_offset),
+            _ => (&mut self.ast.rodata_nodes, &mut self.rodata_offset),
+        };
+        nodes.push(ASTNode::ROData {
+            rodata,
+            offset: *offset,
+        });
+        *offset += size;
+    }
+
+    /// Give a data label still waiting for a directive an empty entry at
+    /// the current offset, so it stays in its section when the section
+    /// changes or the source ends.
+    fn bind_pending_data_label(&mut self) {
+        if let Some((name, span)) = self.pending_data_label.take() {
+            self.push_data(ROData {
+                name,
+                args: vec![
+                    Token::Directive("zero".to_string(), span.clone()),
+                    Token::ImmediateValue(Number::Int(0), span.clone()),
+                ],
+                span,
+            });
+        }
+    }
+
+    /// Attribute the instruction whose span starts at `position` to the
+    /// location of the last `.loc`, if there was one.
+    fn record_location(&mut self, position: usize) {
+        if let Some(location) = &self.location {
+            self.ast.source_locations.insert(position, location.clone());
+        }
+    }
 }
 
 /// BPF_X flag: Converts immediate variant opcodes to register variant opcodes
@@ -63,12 +141,38 @@ pub enum Token {
     VectorLiteral(Vec<Number>, std::ops::Range<usize>),
 }
 
+impl Token {
+    pub(crate) fn span(&self) -> &std::ops::Range<usize> {
+        match self {
+            Token::Directive(_, span)
+            | Token::Identifier(_, span)
+            | Token::ImmediateValue(_, span)
+            | Token::StringLiteral(_, span)
+            | Token::VectorLiteral(_, span) => span,
+        }
+    }
+
+    pub(crate) fn span_mut(&mut self) -> &mut std::ops::Range<usize> {
+        match self {
+            Token::Directive(_, span)
+            | Token::Identifier(_, span)
+            | Token::ImmediateValue(_, span)
+            | Token::StringLiteral(_, span)
+            | Token::VectorLiteral(_, span) => span,
+        }
+    }
+}
+
 pub struct ProgramLayout {
     // TODO: parse result is basically 1. static part 2. dynamic part of the program
     pub code_section: CodeSection,
 
     pub data_section: DataSection,
 
+    pub writable_data_section: DataSection,
+
+    pub bss_section: DataSection,
+
     pub dynamic_symbols: DynamicSymbolMap,
 
     pub relocation_data: RelDynMap,
@@ -81,6 +185,15 @@ pub struct ProgramLayout {
 
     // Debug sections we came across while byteparsing
     pub debug_sections: Vec<DebugSection>,
+
+    /// Locations set by `.loc`, keyed by instruction span start.
+    pub source_locations: HashMap<usize, SourceLocation>,
+
+    /// Non-fatal diagnostics found while building the program
+    pub warnings: Vec<CompileError>,
+
+    /// Custom errors declared with `.error`.
+    pub custom_errors: ErrorRegistry,
 }
 
 pub fn parse(source: &str, arch: SbpfArch) -> Result<ProgramLayout, Vec<CompileError>> {
@@ -92,47 +205,32 @@ pub fn parse_with_optimization(
     arch: SbpfArch,
     optimization: OptimizationConfig,
 ) -> Result<ProgramLayout, Vec<CompileError>> {
-    let pairs = SbpfParser::parse(Rule::program, source).map_err(|e| {
-        // Extract the actual byte position from the pest error so the source
-        // map can resolve it back to the original file/line.
-        let span = match e.location {
-            InputLocation::Pos(pos) => pos..pos + 1,
-            InputLocation::Span((start, end)) => start..end,
-        };
+    parse_with_syscalls(source, arch, optimization, SyscallRegistry::default())
+}
 
-        // Build a clean message without pest's embedded source context,
-        // which would show expanded-source line numbers.
-        let message = match &e.variant {
-            ErrorVariant::ParsingError {
-                positives,
-                negatives,
-            } => {
-                let pos: Vec<String> = positives.iter().filter_map(rule_display_name).collect();
-                let neg: Vec<String> = negatives.iter().filter_map(rule_display_name).collect();
-                let mut parts = Vec::new();
-                if !pos.is_empty() {
-                    parts.push(format!("expected {}", pos.join(", ")));
-                }
-                if !neg.is_empty() {
-                    parts.push(format!("unexpected {}", neg.join(", ")));
-                }
-                if parts.is_empty() {
-                    "Parse error".to_string()
-                } else {
-                    parts.join("; ")
-                }
-            }
-            ErrorVariant::CustomError { message } => message.clone(),
-        };
+/// Like [`parse_with_optimization`], resolving calls to the syscalls in
+/// `syscalls`.
+pub fn parse_with_syscalls(
+    source: &str,
+    arch: SbpfArch,
+    optimization: OptimizationConfig,
+    syscalls: SyscallRegistry,
+) -> Result<ProgramLayout, Vec<CompileError>> {
+    let ast = parse_ast(source, arch, syscalls)?;
+    build_program(ast, arch, optimization)
+}
 
-        vec![CompileError::ParseError {
-            error: message,
-            span,
-            custom_label: None,
-        }]
-    })?;
+/// Parse `source` into an AST whose label references are still unresolved,
+/// so it can be linked with others before [`build_program`] runs.
+pub(crate) fn parse_ast(
+    source: &str,
+    arch: SbpfArch,
+    syscalls: SyscallRegistry,
+) -> Result<AST, Vec<CompileError>> {
+    let pairs = parse_pairs(source)?;
 
     let mut ast = AST::new();
+    ast.set_syscalls(syscalls);
     let mut const_map = HashMap::<String, Number>::new();
     let mut label_spans = HashMap::<String, std::ops::Range<usize>>::new();
 
@@ -140,20 +238,28 @@ pub fn parse_with_optimization(
     let pairs_clone = pairs.clone();
     let mut label_offset_map = collect_label_offsets(pairs_clone);
 
+    let referenced = referenced_symbols(pairs.clone());
+
     // Pass 2: full processing with label_offset_map already populated.
-    let (text_offset, rodata_offset, errors) = {
+    let (text_offset, rodata_offset, data_offset, bss_offset, errors, mut warnings, const_spans) = {
         let mut ctx = ParseContext {
             arch,
             ast: &mut ast,
             const_map: &mut const_map,
             label_spans: &mut label_spans,
             label_offset_map: &mut label_offset_map,
+            const_spans: HashMap::new(),
             errors: Vec::new(),
-            rodata_phase: false,
+            warnings: Vec::new(),
+            section: Section::Text,
             text_offset: 0,
             rodata_offset: 0,
+            data_offset: 0,
+            bss_offset: 0,
             missing_text_directive: false,
-            pending_rodata_label: None,
+            pending_data_label: None,
+            source_files: HashMap::new(),
+            location: None,
         };
 
         for pair in pairs {
@@ -169,18 +275,156 @@ pub fn parse_with_optimization(
                 _ => {}
             }
         }
-
-        (ctx.text_offset, ctx.rodata_offset, ctx.errors)
+        ctx.bind_pending_data_label();
+
+        (
+            ctx.text_offset,
+            ctx.rodata_offset,
+            ctx.data_offset,
+            ctx.bss_offset,
+            ctx.errors,
+            ctx.warnings,
+            ctx.const_spans,
+        )
     };
 
     if !errors.is_empty() {
         return Err(errors);
     }
 
+    warnings.extend(
+        label_spans
+            .into_iter()
+            .filter(|(label, _)| {
+                !label.bytes().all(|b| b.is_ascii_digit()) && !referenced.contains(label)
+            })
+            .map(|(label, span)| CompileError::UnusedLabel {
+                label,
+                span,
+                custom_label: None,
+            }),
+    );
+    warnings.extend(
+        const_spans
+            .into_iter()
+            .filter(|(name, _)| !referenced.contains(name))
+            .map(|(name, span)| CompileError::UnusedConstant {
+                name,
+                span,
+                custom_label: None,
+            }),
+    );
+    warnings.sort_by_key(|warning| warning.span().start);
+    ast.warnings = warnings;
+
     ast.set_text_size(text_offset);
     ast.set_rodata_size(rodata_offset);
+    ast.set_data_size(data_offset);
+    ast.set_bss_size(bss_offset);
 
-    build_program(ast, arch, optimization)
+    Ok(ast)
+}
+
+/// Run the grammar over `source`, turning a failure into a located parse
+/// error.
+pub(crate) fn parse_pairs(source: &str) -> Result<Pairs<'_, Rule>, Vec<CompileError>> {
+    SbpfParser::parse(Rule::program, source).map_err(|e| {
+        // An unclosed `/*` swallows the rest of the file, so report it directly
+        // rather than whatever pest tripped over afterwards.
+        let source = match blank_block_comments(source) {
+            Ok(source) => source,
+            Err(e) => return vec![e],
+        };
+
+        // Extract the actual byte position from the pest error so the source
+        // map can resolve it back to the original file/line.
+        let span = match e.location {
+            InputLocation::Pos(pos) => pos..pos + 1,
+            InputLocation::Span((start, end)) => start..end,
+        };
+
+        // A malformed directive gets its expected grammar instead of pest's
+        // list of alternatives.
+        let line_start = source[..span.start.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |i| line_start + i);
+        if let Some(e) = validate_directive_line(&source[line_start..line_end], line_start) {
+            return vec![e];
+        }
+
+        // Build a clean message without pest's embedded source context,
+        // which would show expanded-source line numbers.
+        let message = match &e.variant {
+            ErrorVariant::ParsingError {
+                positives,
+                negatives,
+            } => {
+                let pos: Vec<String> = positives.iter().filter_map(rule_display_name).collect();
+                let neg: Vec<String> = negatives.iter().filter_map(rule_display_name).collect();
+                let mut parts = Vec::new();
+                if !pos.is_empty() {
+                    parts.push(format!("expected {}", pos.join(", ")));
+                }
+                if !neg.is_empty() {
+                    parts.push(format!("unexpected {}", neg.join(", ")));
+                }
+                if parts.is_empty() {
+                    "Parse error".to_string()
+                } else {
+                    parts.join("; ")
+                }
+            }
+            ErrorVariant::CustomError { message } => message.clone(),
+        };
+
+        vec![CompileError::ParseError {
+            error: message,
+            span,
+            custom_label: None,
+        }]
+    })
+}
+
+/// Every name the program refers to: operands, jump targets, `.extern` and
+/// `.globl` symbols. Labels and constants missing from it are unused.
+fn referenced_symbols(pairs: Pairs<Rule>) -> HashSet<String> {
+    pairs
+        .flatten()
+        .filter(|pair| matches!(pair.as_rule(), Rule::symbol | Rule::globl_symbol))
+        .map(|pair| pair.as_str().to_string())
+        .collect()
+}
+
+/// Where pass 1 is in the program: the current section, the offset
+/// reached in each and the `.equ` constants defined so far, which can size
+/// `.zero`, `.space` and `.fill`.
+#[derive(Default)]
+struct ScanState {
+    section: Section,
+    text_offset: u64,
+    rodata_offset: u64,
+    data_offset: u64,
+    bss_offset: u64,
+    consts: HashMap<String, Number>,
+}
+
+impl ScanState {
+    fn offset(&mut self) -> &mut u64 {
+        match self.section {
+            Section::Text => &mut self.text_offset,
+            Section::Rodata => &mut self.rodata_offset,
+            Section::Data => &mut self.data_offset,
+            Section::Bss => &mut self.bss_offset,
+        }
+    }
+
+    fn record(&mut self, map: &mut HashMap<String, (Number, Section)>, name: String) {
+        let offset = *self.offset();
+        map.insert(name, (Number::Int(offset as i64), self.section));
+    }
 }
 
 /// Pass 1: lightweight scan of the parse tree to collect all label offsets.
@@ -190,9 +434,7 @@ fn collect_label_offsets(
     pairs: pest::iterators::Pairs<Rule>,
 ) -> HashMap<String, (Number, Section)> {
     let mut map = HashMap::new();
-    let mut rodata_phase = false;
-    let mut text_offset: u64 = 0;
-    let mut rodata_offset: u64 = 0;
+    let mut state = ScanState::default();
 
     for pair in pairs {
         match pair.as_rule() {
@@ -201,13 +443,7 @@ fn collect_label_offsets(
                     if statement.as_rule() == Rule::EOI {
                         continue;
                     }
-                    scan_statement_for_labels(
-                        statement,
-                        &mut map,
-                        &mut rodata_phase,
-                        &mut text_offset,
-                        &mut rodata_offset,
-                    );
+                    scan_statement_for_labels(statement, &mut map, &mut state);
                 }
             }
             _ => {}
@@ -220,14 +456,12 @@ fn collect_label_offsets(
 fn scan_statement_for_labels(
     pair: Pair<Rule>,
     map: &mut HashMap<String, (Number, Section)>,
-    rodata_phase: &mut bool,
-    text_offset: &mut u64,
-    rodata_offset: &mut u64,
+    state: &mut ScanState,
 ) {
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::label_default | Rule::label_llvm => {
-                scan_label(inner, map, rodata_phase, text_offset, rodata_offset);
+                scan_label(inner, map, state);
             }
             Rule::directive => {
                 // Track section switches and standalone data directive sizes
@@ -235,14 +469,13 @@ fn scan_statement_for_labels(
                     let dir_inner_clone = dir_inner.clone();
                     for dir_item in dir_inner.into_inner() {
                         if dir_item.as_rule() == Rule::directive_section {
-                            let section_name = dir_item.as_str().trim_start_matches('.');
-                            match section_name {
-                                "text" => *rodata_phase = false,
-                                "rodata" => *rodata_phase = true,
-                                _ => {}
+                            if let Some(section) = section_from_directive(dir_item.as_str()) {
+                                state.section = section;
                             }
-                        } else if *rodata_phase {
-                            // Standalone data directive in rodata — account for its size
+                        } else if dir_item.as_rule() == Rule::directive_equ {
+                            scan_equ(dir_item, state);
+                        } else if state.section != Section::Text {
+                            // Standalone data directive — account for its size
                             match dir_item.as_rule() {
                                 Rule::directive_ascii
                                 | Rule::directive_byte
@@ -250,8 +483,13 @@ fn scan_statement_for_labels(
                                 | Rule::directive_word
                                 | Rule::directive_int
                                 | Rule::directive_long
-                                | Rule::directive_quad => {
-                                    *rodata_offset += rodata_directive_size(&dir_inner_clone);
+                                | Rule::directive_quad
+                                | Rule::directive_endian
+                                | Rule::directive_zero
+                                | Rule::directive_space
+                                | Rule::directive_fill => {
+                                    *state.offset() +=
+                                        rodata_directive_size(&dir_inner_clone, &state.consts);
                                 }
                                 _ => {}
                             }
@@ -259,9 +497,8 @@ fn scan_statement_for_labels(
                     }
                 }
             }
-            Rule::instr_default | Rule::instr_llvm if !*rodata_phase => {
-                let size = instr_size(&inner);
-                *text_offset += size;
+            Rule::instr_default | Rule::instr_llvm if state.section == Section::Text => {
+                state.text_offset += instr_size(&inner);
             }
             _ => {}
         }
@@ -273,9 +510,7 @@ fn scan_statement_for_labels(
 fn scan_label(
     pair: Pair<Rule>,
     map: &mut HashMap<String, (Number, Section)>,
-    rodata_phase: &mut bool,
-    text_offset: &mut u64,
-    rodata_offset: &mut u64,
+    state: &mut ScanState,
 ) {
     let mut label_name = None;
 
@@ -285,29 +520,21 @@ fn scan_label(
                 label_name = Some(item.as_str().to_string());
             }
             Rule::directive_inner => {
-                // Rodata directive attached to label — compute data size
-                if *rodata_phase {
-                    if let Some(ref name) = label_name {
-                        map.insert(
-                            name.clone(),
-                            (Number::Int(*rodata_offset as i64), Section::Rodata),
-                        );
+                // Data directive attached to label — compute data size
+                if state.section != Section::Text {
+                    if let Some(name) = label_name.take() {
+                        state.record(map, name);
                     }
-                    let size = rodata_directive_size(&item);
-                    *rodata_offset += size;
+                    *state.offset() += rodata_directive_size(&item, &state.consts);
                 }
                 return;
             }
             Rule::instr_default | Rule::instr_llvm => {
-                if !*rodata_phase {
-                    if let Some(ref name) = label_name {
-                        map.insert(
-                            name.clone(),
-                            (Number::Int(*text_offset as i64), Section::Text),
-                        );
+                if state.section == Section::Text {
+                    if let Some(name) = label_name.take() {
+                        state.record(map, name);
                     }
-                    let size = instr_size(&item);
-                    *text_offset += size;
+                    state.text_offset += instr_size(&item);
                 }
                 return;
             }
@@ -317,14 +544,40 @@ fn scan_label(
 
     // Bare label (no directive or instruction attached)
     if let Some(name) = label_name {
-        if *rodata_phase {
-            map.insert(name, (Number::Int(*rodata_offset as i64), Section::Rodata));
-        } else {
-            map.insert(name, (Number::Int(*text_offset as i64), Section::Text));
+        state.record(map, name);
+    }
+}
+
+/// Record the value of an `.equ` constant for sizing later data. Errors are
+/// left for pass 2 to report.
+fn scan_equ(pair: Pair<Rule>, state: &mut ScanState) {
+    let mut name = None;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::identifier => name = Some(inner.as_str().to_string()),
+            Rule::expression => {
+                if let (Some(name), Ok(value)) =
+                    (name.take(), eval_expression(inner, &state.consts))
+                {
+                    state.consts.insert(name, value);
+                }
+            }
+            _ => {}
         }
     }
 }
 
+/// The section a `.text`, `.rodata` or `.data` directive switches to.
+pub(crate) fn section_from_directive(directive: &str) -> Option<Section> {
+    match directive.trim_start_matches('.') {
+        "text" => Some(Section::Text),
+        "rodata" => Some(Section::Rodata),
+        "data" => Some(Section::Data),
+        "bss" => Some(Section::Bss),
+        _ => None,
+    }
+}
+
 /// Determine instruction size from the parse tree (lddw = 16 bytes, all others = 8).
 fn instr_size(pair: &Pair<Rule>) -> u64 {
     for inner in pair.clone().into_inner() {
@@ -337,7 +590,7 @@ fn instr_size(pair: &Pair<Rule>) -> u64 {
 }
 
 /// Determine the byte size of a rodata directive from the parse tree.
-fn rodata_directive_size(pair: &Pair<Rule>) -> u64 {
+fn rodata_directive_size(pair: &Pair<Rule>, consts: &HashMap<String, Number>) -> u64 {
     for inner in pair.clone().into_inner() {
         match inner.as_rule() {
             Rule::directive_ascii => {
@@ -378,6 +631,26 @@ fn rodata_directive_size(pair: &Pair<Rule>) -> u64 {
                     .count() as u64
                     * 8;
             }
+            Rule::directive_endian => {
+                let mut operands = inner.into_inner();
+                let width = operands
+                    .next()
+                    .and_then(|width| ROData::endian_width(width.as_str().trim_start_matches('.')))
+                    .map_or(0, |(width, _)| width);
+                return operands.filter(|p| p.as_rule() == Rule::number).count() as u64 * width;
+            }
+            Rule::directive_zero | Rule::directive_space | Rule::directive_fill => {
+                let is_fill = inner.as_rule() == Rule::directive_fill;
+                let operands: Vec<u64> = inner
+                    .into_inner()
+                    .map(|p| eval_expression(p, consts).map_or(0, |n| n.to_i64().max(0) as u64))
+                    .collect();
+                let size = match operands.get(1) {
+                    Some(&size) if is_fill => size,
+                    _ => 1,
+                };
+                return operands[0].saturating_mul(size);
+            }
             _ => {}
         }
     }
@@ -397,7 +670,7 @@ fn rule_display_name(rule: &Rule) -> Option<String> {
         Rule::directive_globl => ".globl",
         Rule::directive_extern => ".extern",
         Rule::directive_equ => ".equ",
-        Rule::directive_section => "section (.text, .rodata)",
+        Rule::directive_section => "section (.text, .rodata, .data, .bss)",
         Rule::directive_ascii => ".ascii",
         Rule::directive_byte => ".byte",
         Rule::directive_short => ".short",
@@ -405,6 +678,14 @@ fn rule_display_name(rule: &Rule) -> Option<String> {
         Rule::directive_int => ".int",
         Rule::directive_long => ".long",
         Rule::directive_quad => ".quad",
+        Rule::directive_endian | Rule::endian_width => {
+            "byte order directive (.le16, .le32, .le64, .be16, .be32, .be64)"
+        }
+        Rule::directive_zero => ".zero",
+        Rule::directive_space => ".space",
+        Rule::directive_fill => ".fill",
+        Rule::directive_file => ".file",
+        Rule::directive_loc => ".loc",
 
         // Instructions
         Rule::instr_default | Rule::instr_llvm => "instruction",
@@ -417,6 +698,7 @@ fn rule_display_name(rule: &Rule) -> Option<String> {
         Rule::register => "register",
         Rule::operand => "operand",
         Rule::number => "number",
+        Rule::typed_literal => "unit literal",
         Rule::symbol => "symbol",
         Rule::identifier => "identifier",
         Rule::expression => "expression",
@@ -455,7 +737,8 @@ fn process_statement(pair: Pair<Rule>, ctx: &mut ParseContext) {
                     is_llvm,
                 ) {
                     Ok(instruction) => {
-                        if !ctx.rodata_phase {
+                        if ctx.section == Section::Text {
+                            ctx.record_location(instruction.span.start);
                             let size = instruction.get_size();
                             ctx.ast.nodes.push(ASTNode::Instruction {
                                 instruction,
@@ -467,7 +750,7 @@ fn process_statement(pair: Pair<Rule>, ctx: &mut ParseContext) {
                     Err(e) => ctx.errors.push(e),
                 }
 
-                if ctx.rodata_phase && !ctx.missing_text_directive {
+                if ctx.section != Section::Text && !ctx.missing_text_directive {
                     ctx.missing_text_directive = true;
                     ctx.errors.push(CompileError::MissingTextDirective {
                         span: span_range,
@@ -516,24 +799,23 @@ fn process_label(pair: Pair<Rule>, ctx: &mut ParseContext) {
         ctx.label_spans
             .insert(label_name.clone(), label_span.clone());
 
-        if ctx.rodata_phase {
+        if ctx.section != Section::Text {
+            ctx.bind_pending_data_label();
             // Record label offset for expression evaluation
             ctx.label_offset_map.insert(
                 label_name.clone(),
-                (Number::Int(ctx.rodata_offset as i64), Section::Rodata),
+                (Number::Int(ctx.data_cursor() as i64), ctx.section),
             );
 
-            // Handle rodata label with directive
+            // Handle data label with directive
             if let Some(dir_pair) = directive_opt {
-                match process_rodata_directive(label_name.clone(), label_span.clone(), dir_pair) {
-                    Ok(rodata) => {
-                        let size = rodata.get_size();
-                        ctx.ast.rodata_nodes.push(ASTNode::ROData {
-                            rodata,
-                            offset: ctx.rodata_offset,
-                        });
-                        ctx.rodata_offset += size;
-                    }
+                match process_rodata_directive(
+                    label_name.clone(),
+                    label_span.clone(),
+                    dir_pair,
+                    ctx.const_map,
+                ) {
+                    Ok(rodata) => ctx.push_data(rodata),
                     Err(e) => ctx.errors.push(e),
                 }
             } else if let Some(inst_pair) = instruction_opt {
@@ -554,9 +836,9 @@ fn process_label(pair: Pair<Rule>, ctx: &mut ParseContext) {
                     });
                 }
             } else {
-                // Bare rodata label (no directive on same line) — store it
+                // Bare data label (no directive on same line) — store it
                 // so the next data directive can pick it up.
-                ctx.pending_rodata_label = Some((label_name, label_span));
+                ctx.pending_data_label = Some((label_name, label_span));
             }
         } else {
             // Record label offset for expression evaluation
@@ -582,6 +864,7 @@ fn process_label(pair: Pair<Rule>, ctx: &mut ParseContext) {
                     is_llvm,
                 ) {
                     Ok(instruction) => {
+                        ctx.record_location(instruction.span.start);
                         let size = instruction.get_size();
                         ctx.ast.nodes.push(ASTNode::Instruction {
                             instruction,
@@ -603,11 +886,13 @@ fn process_instruction(
     arch: SbpfArch,
     is_llvm: bool,
 ) -> Result<Instruction, CompileError> {
-    if is_llvm {
+    let instruction = if is_llvm {
         llvm::process_instruction(pair, const_map, label_offset_map, arch)
     } else {
         default::process_instruction(pair, const_map, label_offset_map, arch)
-    }
+    }?;
+    common::check_arch_support(&instruction, arch)?;
+    Ok(instruction)
 }
 
 fn extract_label_from_pair(
@@ -0,0 +1,122 @@
+use crate::errors::CompileError;
+
+/// Replace every `/* ... */` block comment with spaces so the line-based
+/// preprocessor passes don't act on commented-out directives.
+///
+/// Block comments nest. Newlines are kept and every other byte is replaced
+/// one-for-one, so line numbers and byte offsets stay valid. Comment markers
+/// inside string literals and line comments are ignored.
+pub(crate) fn blank_block_comments(source: &str) -> Result<String, CompileError> {
+    let bytes = source.as_bytes();
+    let mut output = bytes.to_vec();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' | b'#' => i = skip_line(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'/') => i = skip_line(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let start = i;
+                let mut depth = 0usize;
+                loop {
+                    if i >= bytes.len() {
+                        return Err(CompileError::UnterminatedBlockComment {
+                            span: start..start + 2,
+                            custom_label: None,
+                        });
+                    }
+                    match (bytes[i], bytes.get(i + 1)) {
+                        (b'/', Some(b'*')) => {
+                            depth += 1;
+                            i += 2;
+                        }
+                        (b'*', Some(b'/')) => {
+                            depth -= 1;
+                            i += 2;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => i += 1,
+                    }
+                }
+                for byte in &mut output[start..i] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    // Only whole multi-byte characters are blanked, so this stays valid UTF-8.
+    Ok(String::from_utf8(output).expect("blanking preserves UTF-8"))
+}
+
+fn skip_line(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blank_block_comments() {
+        let source = "mov64 r1, 1 /* one */\n/* .include \"x.s\"\n   exit */ exit\n";
+        let blanked = blank_block_comments(source).unwrap();
+        assert_eq!(blanked.len(), source.len());
+        assert_eq!(
+            blanked,
+            format!(
+                "mov64 r1, 1{}\n{}\n{}exit\n",
+                " ".repeat(10),
+                " ".repeat(17),
+                " ".repeat(11)
+            )
+        );
+    }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let source = "/* outer /* inner */ still outer */exit";
+        assert_eq!(
+            blank_block_comments(source).unwrap(),
+            format!("{}exit", " ".repeat(35))
+        );
+    }
+
+    #[test]
+    fn test_markers_in_strings_and_line_comments() {
+        let source = ".ascii \"/* not a comment\"\n; /* nor this\n// or /* this\nexit";
+        assert_eq!(blank_block_comments(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_multibyte_characters_in_comment() {
+        let source = "/* café ✓ */exit";
+        let blanked = blank_block_comments(source).unwrap();
+        assert_eq!(blanked.len(), source.len());
+        assert!(blanked.trim_start().starts_with("exit"));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let source = "exit\n/* outer /* inner */\nexit";
+        match blank_block_comments(source) {
+            Err(CompileError::UnterminatedBlockComment { span, .. }) => assert_eq!(span, 5..7),
+            other => panic!("expected UnterminatedBlockComment, got {:?}", other),
+        }
+    }
+}
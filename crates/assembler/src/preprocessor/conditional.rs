@@ -0,0 +1,382 @@
+//! Conditional assembly with `.if`, `.ifdef`, `.ifndef`, `.elseif`, `.else`
+//! and `.endif`:
+//!
+//! ```text
+//! .ifdef DEBUG
+//!   call sol_log_
+//! .endif
+//! ```
+//!
+//! Conditions are evaluated while preprocessing, from numbers, `.equ`
+//! constants defined earlier in the source and values passed with
+//! `--define`. Lines of branches that are not taken are dropped. Macro bodies
+//! are left alone until they are expanded, so a condition inside a macro can
+//! test its parameters.
+
+use {
+    super::{
+        SourceLine, is_identifier, layout::strip_comment, parse_number, source_map::SourceOrigin,
+    },
+    crate::errors::CompileError,
+    std::collections::HashMap,
+};
+
+/// Result of resolving conditional blocks
+pub(crate) struct ConditionalResult {
+    /// Lines of the branches that were taken, without the directives
+    pub lines: Vec<SourceLine>,
+    /// Any errors encountered, paired with their source origin
+    pub errors: Vec<(CompileError, SourceOrigin)>,
+}
+
+/// An open `.if` block
+struct Block {
+    directive: String,
+    origin: SourceOrigin,
+    /// Whether the lines around the block are assembled
+    parent_active: bool,
+    /// Whether one of the block's branches has been taken
+    taken: bool,
+    /// Whether the current branch is assembled
+    active: bool,
+    seen_else: bool,
+}
+
+/// Keep the lines of the conditional branches that are taken.
+///
+/// `defines` are known before the first line, as if declared with `.equ`.
+pub(crate) fn resolve_conditionals(
+    lines: Vec<SourceLine>,
+    defines: &[(String, i64)],
+) -> ConditionalResult {
+    // A constant whose value is not known here, such as a difference of
+    // labels, is still defined for `.ifdef` but cannot be used in `.if`.
+    let mut constants: HashMap<String, Option<i64>> = defines
+        .iter()
+        .map(|(name, value)| (name.clone(), Some(*value)))
+        .collect();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut in_macro = false;
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+
+    for line in lines {
+        let (directive, rest) = split_directive(strip_comment(&line.text));
+        if in_macro {
+            in_macro = directive != ".endm";
+            output.push(line);
+            continue;
+        }
+
+        let active = blocks.last().is_none_or(|block| block.active);
+        let mut condition = |directive: &str| {
+            let value = match directive {
+                ".ifdef" | ".ifndef" if is_identifier(rest) => {
+                    Some((constants.contains_key(rest) == (directive == ".ifdef")) as i64)
+                }
+                ".if" | ".elseif" => evaluate(rest, &constants),
+                _ => None,
+            };
+            value.map(|value| value != 0).unwrap_or_else(|| {
+                errors.push((
+                    CompileError::InvalidCondition {
+                        condition: rest.to_string(),
+                        span: 0..0,
+                        custom_label: None,
+                    },
+                    line.origin.clone(),
+                ));
+                false
+            })
+        };
+
+        match directive {
+            ".if" | ".ifdef" | ".ifndef" => {
+                let taken = active && condition(directive);
+                blocks.push(Block {
+                    directive: directive.to_string(),
+                    origin: line.origin.clone(),
+                    parent_active: active,
+                    taken,
+                    active: taken,
+                    seen_else: false,
+                });
+            }
+            ".elseif" | ".else" => match blocks.last_mut() {
+                Some(block) if !block.seen_else => {
+                    let open = block.parent_active && !block.taken;
+                    block.active = open && (directive == ".else" || condition(directive));
+                    block.taken |= block.active;
+                    block.seen_else = directive == ".else";
+                }
+                _ => errors.push((
+                    CompileError::UnmatchedConditional {
+                        directive: directive.to_string(),
+                        span: 0..0,
+                        custom_label: None,
+                    },
+                    line.origin.clone(),
+                )),
+            },
+            // The guard closes the innermost block, if there is one.
+            ".endif" if blocks.pop().is_none() => errors.push((
+                CompileError::UnmatchedConditional {
+                    directive: directive.to_string(),
+                    span: 0..0,
+                    custom_label: None,
+                },
+                line.origin.clone(),
+            )),
+            ".endif" => {}
+            _ if active => {
+                if directive == ".equ"
+                    && let Some((name, value)) = rest.split_once(',')
+                {
+                    constants.insert(name.trim().to_string(), evaluate(value, &constants));
+                }
+                in_macro = directive == ".macro";
+                output.push(line);
+            }
+            _ => {}
+        }
+    }
+
+    for block in blocks {
+        errors.push((
+            CompileError::UnclosedConditional {
+                directive: block.directive,
+                span: 0..0,
+                custom_label: None,
+            },
+            block.origin,
+        ));
+    }
+
+    ConditionalResult {
+        lines: output,
+        errors,
+    }
+}
+
+/// Split a line into its directive and the rest, or an empty directive for
+/// lines that don't start with one.
+fn split_directive(text: &str) -> (&str, &str) {
+    if !text.starts_with('.') {
+        return ("", text);
+    }
+    match text.split_once(char::is_whitespace) {
+        Some((directive, rest)) => (directive, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Number(i64),
+    Name(&'a str),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+/// Binary operators from loosest to tightest binding.
+const PRECEDENCE: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Evaluate a condition. Comparisons and logical operators give 1 or 0, and
+/// any other value is true when it is non-zero. Returns `None` for malformed
+/// conditions, unknown names and arithmetic that overflows.
+fn evaluate(condition: &str, constants: &HashMap<String, Option<i64>>) -> Option<i64> {
+    let tokens = tokenize(condition)?;
+    let mut pos = 0;
+    let value = binary(&tokens, &mut pos, 0, constants)?;
+    (pos == tokens.len()).then_some(value)
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token<'_>>> {
+    const OPS: &[&str] = &[
+        "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            1
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            tokens.push(if c.is_ascii_digit() {
+                Token::Number(parse_number::<u64>(word)? as i64)
+            } else {
+                Token::Name(word)
+            });
+            len
+        } else {
+            let op = OPS.iter().find(|op| rest.starts_with(**op))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Some(tokens)
+}
+
+fn binary(
+    tokens: &[Token],
+    pos: &mut usize,
+    level: usize,
+    constants: &HashMap<String, Option<i64>>,
+) -> Option<i64> {
+    let Some(ops) = PRECEDENCE.get(level) else {
+        return unary(tokens, pos, constants);
+    };
+    let mut lhs = binary(tokens, pos, level + 1, constants)?;
+    while let Some(Token::Op(op)) = tokens.get(*pos).copied() {
+        if !ops.contains(&op) {
+            break;
+        }
+        *pos += 1;
+        let rhs = binary(tokens, pos, level + 1, constants)?;
+        lhs = match op {
+            "||" => ((lhs != 0) || (rhs != 0)) as i64,
+            "&&" => ((lhs != 0) && (rhs != 0)) as i64,
+            "==" => (lhs == rhs) as i64,
+            "!=" => (lhs != rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "<" => (lhs < rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            "+" => lhs.checked_add(rhs)?,
+            "-" => lhs.checked_sub(rhs)?,
+            "*" => lhs.checked_mul(rhs)?,
+            "/" => lhs.checked_div(rhs)?,
+            _ => lhs.checked_rem(rhs)?,
+        };
+    }
+    Some(lhs)
+}
+
+fn unary(
+    tokens: &[Token],
+    pos: &mut usize,
+    constants: &HashMap<String, Option<i64>>,
+) -> Option<i64> {
+    let token = tokens.get(*pos).copied()?;
+    *pos += 1;
+    match token {
+        Token::Number(value) => Some(value),
+        Token::Name(name) => constants.get(name).copied().flatten(),
+        Token::Op("-") => unary(tokens, pos, constants)?.checked_neg(),
+        Token::Op("!") => Some((unary(tokens, pos, constants)? == 0) as i64),
+        Token::Open => {
+            let value = binary(tokens, pos, 0, constants)?;
+            (tokens.get(*pos) == Some(&Token::Close)).then(|| {
+                *pos += 1;
+                value
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::preprocessor::source_map::FileId};
+
+    fn resolve(source: &str, defines: &[(String, i64)]) -> ConditionalResult {
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(index, text)| SourceLine {
+                text: text.to_string(),
+                origin: SourceOrigin::new(FileId(0), index as u32 + 1),
+            })
+            .collect();
+        resolve_conditionals(lines, defines)
+    }
+
+    fn texts(result: &ConditionalResult) -> Vec<&str> {
+        result.lines.iter().map(|line| line.text.trim()).collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let constants = HashMap::from([("DEBUG".to_string(), Some(1)), ("LEN".to_string(), None)]);
+        assert_eq!(evaluate("1 + 2 * 3", &constants), Some(7));
+        assert_eq!(evaluate("(1 + 2) * 3 == 9", &constants), Some(1));
+        assert_eq!(evaluate("DEBUG && !0x0 || 0", &constants), Some(1));
+        assert_eq!(evaluate("-DEBUG < 0", &constants), Some(1));
+        assert_eq!(evaluate("LEN > 0", &constants), None);
+        assert_eq!(evaluate("MISSING", &constants), None);
+        assert_eq!(evaluate("1 / 0", &constants), None);
+        assert_eq!(evaluate("(1", &constants), None);
+        assert_eq!(evaluate("1 2", &constants), None);
+    }
+
+    #[test]
+    fn test_branches() {
+        let source = r#".equ LEVEL, 2
+.if LEVEL > 1 ; verbose
+  a
+.elseif LEVEL == 1
+  b
+.else
+  c
+.endif
+.ifdef DEBUG
+  d
+.ifndef QUIET
+  e
+.endif
+.else
+  f
+.endif"#;
+        let result = resolve(source, &[]);
+        assert!(result.errors.is_empty());
+        assert_eq!(texts(&result), vec![".equ LEVEL, 2", "a", "f"]);
+
+        let result = resolve(
+            source,
+            &[("DEBUG".to_string(), 1), ("LEVEL".to_string(), 0)],
+        );
+        // The source's own `.equ` comes later and wins.
+        assert_eq!(texts(&result), vec![".equ LEVEL, 2", "a", "d", "e"]);
+    }
+
+    #[test]
+    fn test_macro_bodies_are_left_alone() {
+        let source = ".macro LOG msg\n.ifdef DEBUG\n  \\msg\n.endif\n.endm\nafter";
+        let result = resolve(source, &[]);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.lines.len(), 6);
+    }
+
+    #[test]
+    fn test_errors() {
+        let result = resolve(".if MISSING\n.else\n.else\n.endif\n.endif\n.ifdef", &[]);
+        let errors: Vec<(String, u32)> = result
+            .errors
+            .iter()
+            .map(|(error, origin)| (error.to_string(), origin.line))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("Cannot evaluate condition 'MISSING'".to_string(), 1),
+                (".else without a matching .if".to_string(), 3),
+                (".endif without a matching .if".to_string(), 5),
+                ("Cannot evaluate condition ''".to_string(), 6),
+                ("Missing .endif for .ifdef".to_string(), 6),
+            ]
+        );
+    }
+}
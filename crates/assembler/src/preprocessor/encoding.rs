@@ -0,0 +1,109 @@
+use crate::errors::CompileError;
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// Byte order marks of encodings we recognise but do not decode. UTF-32 comes
+/// first since its little-endian mark starts with the UTF-16 one.
+const FOREIGN_BOMS: &[(&[u8], &str)] = &[
+    (&[0xff, 0xfe, 0x00, 0x00], "UTF-32LE"),
+    (&[0x00, 0x00, 0xfe, 0xff], "UTF-32BE"),
+    (&[0xff, 0xfe], "UTF-16LE"),
+    (&[0xfe, 0xff], "UTF-16BE"),
+];
+
+/// Decode the raw bytes of a source file.
+///
+/// A UTF-8 byte order mark is dropped and `\r\n` and lone `\r` line endings
+/// become `\n`, so spans and line numbers computed on the result line up with
+/// what an editor shows. Files in other encodings, or with invalid UTF-8, are
+/// rejected with the offset of the offending bytes in the original file.
+pub fn decode_source(bytes: &[u8]) -> Result<String, CompileError> {
+    if let Some((bom, encoding)) = FOREIGN_BOMS.iter().find(|(bom, _)| bytes.starts_with(bom)) {
+        return Err(CompileError::UnsupportedEncoding {
+            encoding: encoding.to_string(),
+            span: 0..bom.len(),
+            custom_label: None,
+        });
+    }
+
+    let (bom_len, bytes) = match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) => (UTF8_BOM.len(), rest),
+        None => (0, bytes),
+    };
+    let text = std::str::from_utf8(bytes).map_err(|e| {
+        let start = e.valid_up_to();
+        let end = start + e.error_len().unwrap_or(bytes.len() - start);
+        invalid_utf8(bytes, start..end, bom_len)
+    })?;
+
+    if !text.contains('\r') {
+        return Ok(text.to_string());
+    }
+    Ok(normalize_line_endings(text))
+}
+
+fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Describe the invalid bytes at `range`. The reported offset counts from
+/// the start of the file, including any byte order mark.
+fn invalid_utf8(bytes: &[u8], range: std::ops::Range<usize>, bom_len: usize) -> CompileError {
+    let valid = normalize_line_endings(&String::from_utf8_lossy(&bytes[..range.start]));
+    let line_start = valid.rfind('\n').map_or(0, |i| i + 1);
+    let line = valid.matches('\n').count() + 1;
+    let column = valid[line_start..].chars().count() + 1;
+    let offset = range.start + bom_len;
+    CompileError::InvalidUtf8 {
+        bytes: bytes[range.clone()]
+            .iter()
+            .map(|b| format!("0x{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" "),
+        offset,
+        line,
+        column,
+        span: offset..offset + range.len(),
+        custom_label: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_and_line_endings() {
+        assert_eq!(decode_source(b"exit\n").unwrap(), "exit\n");
+        assert_eq!(
+            decode_source(b"\xef\xbb\xbfmov64 r0, 1\r\nexit\r").unwrap(),
+            "mov64 r0, 1\nexit\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_reports_location() {
+        let error = decode_source(b"\xef\xbb\xbfexit\r\n  .ascii \"\xff\xfe\"\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid UTF-8 sequence 0xff at byte 19 (line 2, column 11)"
+        );
+        // A sequence cut off by the end of the file.
+        let error = decode_source(b"exit \xe2\x82").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid UTF-8 sequence 0xe2 0x82 at byte 5 (line 1, column 6)"
+        );
+    }
+
+    #[test]
+    fn test_foreign_encodings_are_named() {
+        let error = decode_source(b"\xff\xfee\x00x\x00").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Source is encoded as UTF-16LE, only UTF-8 is supported"
+        );
+        let error = decode_source(b"\xff\xfe\x00\x00").unwrap_err();
+        assert!(error.to_string().contains("UTF-32LE"));
+    }
+}
@@ -5,17 +5,11 @@ use {
         source_map::SourceOrigin,
     },
     crate::errors::CompileError,
-    std::{
-        collections::HashMap,
-        sync::atomic::{AtomicU64, Ordering},
-    },
+    std::collections::HashMap,
 };
 
 const MAX_EXPANSION_DEPTH: u32 = 100;
 
-/// Global expansion counter for `\@` unique IDs
-static EXPANSION_COUNTER: AtomicU64 = AtomicU64::new(0);
-
 /// An expansion error paired with its source origin
 #[derive(Debug)]
 pub(crate) struct ExpandError {
@@ -25,7 +19,9 @@ pub(crate) struct ExpandError {
 
 /// Expand all macros, `.rept`, and `.irp` directives in the given lines.
 ///
-/// Returns the expanded lines and any errors encountered.
+/// Returns the expanded lines and any errors encountered. `\@` unique IDs are
+/// numbered from zero on every call, so the same source always expands the
+/// same way.
 pub(crate) fn expand_macros(
     lines: Vec<SourceLine>,
 ) -> Result<(Vec<SourceLine>, Vec<ExpandError>), Vec<ExpandError>> {
@@ -46,14 +42,15 @@ pub(crate) fn expand_macros(
     let macros = scan_result.macros;
     let mut errors = Vec::new();
     let mut output = Vec::new();
+    let mut next_id = 0;
 
     // Expand macro invocations in remaining lines
     for line in scan_result.remaining_lines {
-        expand_line(&line, &macros, &mut output, &mut errors, 0);
+        expand_line(&line, &macros, &mut output, &mut errors, &mut next_id, 0);
     }
 
     // Now, handle .rept and .irp on the post-macro output
-    let output = expand_repetitions(output)?;
+    let output = expand_repetitions(output, &mut next_id)?;
 
     Ok((output, errors))
 }
@@ -64,6 +61,7 @@ fn expand_line(
     macros: &HashMap<String, MacroDef>,
     output: &mut Vec<SourceLine>,
     errors: &mut Vec<ExpandError>,
+    next_id: &mut u64,
     depth: u32,
 ) {
     if depth > MAX_EXPANSION_DEPTH {
@@ -88,16 +86,19 @@ fn expand_line(
 
         match bind_args(macro_def, &args) {
             Ok(bindings) => {
-                let expansion_id = EXPANSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+                let expansion_id = *next_id;
+                *next_id += 1;
 
-                // Expand each body line with parameter substitution
+                // Expand each body line with parameter substitution. The
+                // expanded line points at the body line it came from, with
+                // the invocation recorded so diagnostics can show both.
                 for body_line in &macro_def.body_lines {
-                    let expanded_text = substitute(body_line, &bindings, expansion_id);
+                    let expanded_text = substitute(&body_line.text, &bindings, expansion_id);
                     let expanded_line = SourceLine {
                         text: expanded_text,
                         origin: SourceOrigin::with_macro_expansion(
-                            macro_def.defined_at.file_id,
-                            macro_def.defined_at.line,
+                            body_line.origin.file_id,
+                            body_line.origin.line,
                             macro_def.name.clone(),
                             line.origin.clone(),
                             depth + 1,
@@ -105,7 +106,7 @@ fn expand_line(
                     };
 
                     // Rescan for further macro invocations
-                    expand_line(&expanded_line, macros, output, errors, depth + 1);
+                    expand_line(&expanded_line, macros, output, errors, next_id, depth + 1);
                 }
             }
             Err(e) => errors.push(ExpandError {
@@ -329,7 +330,10 @@ fn substitute(line: &str, bindings: &HashMap<String, String>, expansion_id: u64)
 }
 
 /// Expand `.rept` and `.irp` blocks (these are processed before macro expansion).
-fn expand_repetitions(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, Vec<ExpandError>> {
+fn expand_repetitions(
+    lines: Vec<SourceLine>,
+    next_id: &mut u64,
+) -> Result<Vec<SourceLine>, Vec<ExpandError>> {
     let mut output = Vec::new();
     let mut errors = Vec::new();
     let mut i = 0;
@@ -350,7 +354,7 @@ fn expand_repetitions(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, Vec<Exp
             match count_str.parse::<usize>() {
                 Ok(count) => {
                     // Recursively expand nested .rept/.irp inside the body
-                    let expanded_body = expand_repetitions(body)?;
+                    let expanded_body = expand_repetitions(body, next_id)?;
                     for _ in 0..count {
                         output.extend(expanded_body.iter().cloned());
                     }
@@ -390,14 +394,15 @@ fn expand_repetitions(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, Vec<Exp
                     // expand any nested .rept/.irp in the result.
                     let mut iter_lines = Vec::with_capacity(body.len());
                     for body_line in &body {
-                        let expansion_id = EXPANSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+                        let expansion_id = *next_id;
+                        *next_id += 1;
                         let expanded_text = substitute(&body_line.text, &bindings, expansion_id);
                         iter_lines.push(SourceLine {
                             text: expanded_text,
                             origin: body_line.origin.clone(),
                         });
                     }
-                    output.extend(expand_repetitions(iter_lines)?);
+                    output.extend(expand_repetitions(iter_lines, next_id)?);
                 }
             }
 
@@ -579,6 +584,21 @@ mod tests {
         assert_ne!(result[0], result[1]);
     }
 
+    #[test]
+    fn test_unique_ids_are_deterministic() {
+        let lines = || {
+            vec![
+                make_line(".macro LOOP", 1),
+                make_line("loop_\\@:", 2),
+                make_line(".endm", 3),
+                make_line("LOOP", 4),
+            ]
+        };
+        let first = expand_and_collect(lines());
+        assert_eq!(first, vec!["loop_0:"]);
+        assert_eq!(expand_and_collect(lines()), first);
+    }
+
     #[test]
     fn test_macro_concatenation() {
         let lines = vec![
@@ -610,6 +630,34 @@ mod tests {
         assert_eq!(result, vec!["    mov64 r0, 42"]);
     }
 
+    #[test]
+    fn test_expansion_origins_point_at_body_lines() {
+        let lines = vec![
+            make_line(".macro INNER val", 1),
+            make_line("    mov64 r0, \\val", 2),
+            make_line("    exit", 3),
+            make_line(".endm", 4),
+            make_line(".macro OUTER val", 5),
+            make_line("    INNER \\val", 6),
+            make_line(".endm", 7),
+            make_line("OUTER 42", 8),
+        ];
+
+        let (result, _) = expand_macros(lines).unwrap();
+        let origin = &result[1].origin;
+        assert_eq!(origin.line, 3);
+        let expansion = origin.macro_expansion.as_ref().unwrap();
+        assert_eq!(expansion.macro_name, "INNER");
+        assert_eq!(expansion.invocation_origin.line, 6);
+        let outer = expansion
+            .invocation_origin
+            .macro_expansion
+            .as_ref()
+            .unwrap();
+        assert_eq!(outer.macro_name, "OUTER");
+        assert_eq!(outer.invocation_origin.line, 8);
+    }
+
     #[test]
     fn test_recursion_limit() {
         let lines = vec![
@@ -1,6 +1,7 @@
 use {
     super::{
         FileResolver, SourceLine,
+        comment::blank_block_comments,
         source_map::{FileRegistry, SourceOrigin},
     },
     crate::errors::CompileError,
@@ -28,6 +29,12 @@ fn parse_include_directive(line: &str) -> Option<&str> {
     }
 }
 
+/// Paths named by the `.include` directives in `source`, in order. Nested
+/// includes are not followed.
+pub fn include_directives(source: &str) -> impl Iterator<Item = &str> {
+    source.lines().filter_map(parse_include_directive)
+}
+
 /// Resolve all `.include` directives recursively, producing a flat list of source lines.
 ///
 /// Each line tracks its origin (file + line number) for diagnostics.
@@ -75,6 +82,15 @@ fn resolve_recursive(
     output: &mut Vec<SourceLine>,
     errors: &mut Vec<CompileError>,
 ) {
+    let source = match blank_block_comments(source) {
+        Ok(source) => source,
+        Err(e) => {
+            errors.push(e);
+            return;
+        }
+    };
+    let source = source.as_str();
+
     for (line_idx, line_text) in source.lines().enumerate() {
         let line_number = (line_idx + 1) as u32;
 
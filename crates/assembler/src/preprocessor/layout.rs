@@ -0,0 +1,715 @@
+//! `.layout` blocks declare the byte layout of account data:
+//!
+//! ```text
+//! .layout counter
+//!   bump: u8
+//!   count: u64
+//! .endlayout
+//! ```
+//!
+//! Fields are packed in order unless given an explicit `@ offset`. Each block
+//! is replaced with `.equ` constants for the field offsets and the total size
+//! (`COUNTER_BUMP`, `COUNTER_COUNT`, `COUNTER_SIZE`) and, for integer fields,
+//! macros that load and store them (`COUNTER_LOAD_COUNT dst, base` and
+//! `COUNTER_STORE_COUNT base, src`). `base` may carry its own offset, as in
+//! `COUNTER_LOAD_COUNT r2, r1+ACCOUNT_DATA`.
+//!
+//! `.instruction` blocks declare the data of an instruction the same way,
+//! after a one-byte discriminator, along with the accounts it expects:
+//!
+//! ```text
+//! .instruction deposit, 1
+//!   .account owner, signer, writable
+//!   .account vault, writable
+//!   amount: u64
+//! .endinstruction
+//! ```
+//!
+//! They expand like a layout of the instruction data, whose first field sits
+//! at offset 1, plus `DEPOSIT_DISCRIMINATOR` for the discriminator itself.
+
+use {
+    super::{SourceLine, is_identifier, parse_number, source_map::SourceOrigin},
+    crate::errors::CompileError,
+    std::collections::HashSet,
+};
+
+/// Type of a layout field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    /// A 32-byte public key.
+    Pubkey,
+    /// A fixed-size byte array, written `[u8; N]`.
+    Bytes(usize),
+}
+
+impl FieldType {
+    pub fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "pubkey" => Self::Pubkey,
+            _ => {
+                let inner = text.strip_prefix('[')?.strip_suffix(']')?;
+                let (element, len) = inner.split_once(';')?;
+                if element.trim() != "u8" {
+                    return None;
+                }
+                Self::Bytes(parse_number(len.trim())?)
+            }
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+            Self::U64 | Self::I64 => 8,
+            Self::Pubkey => 32,
+            Self::Bytes(len) => *len,
+        }
+    }
+
+    /// Whether the field fits in a register and gets load/store macros.
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, Self::Pubkey | Self::Bytes(_))
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Self::I8 | Self::I16 | Self::I32 | Self::I64)
+    }
+
+    /// Width suffix of the `ldx`/`stx` instructions for integer fields.
+    fn width(&self) -> &'static str {
+        match self.size() {
+            1 => "b",
+            2 => "h",
+            4 => "w",
+            _ => "dw",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutField {
+    pub name: String,
+    pub ty: FieldType,
+    pub offset: usize,
+}
+
+impl LayoutField {
+    /// The field name in upper snake case, as used in generated names.
+    pub fn upper_name(&self) -> String {
+        upper_snake_case(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub name: String,
+    pub fields: Vec<LayoutField>,
+    /// End of the last field.
+    pub size: usize,
+}
+
+impl Layout {
+    /// Prefix of the generated constants and macros, the layout name in
+    /// upper snake case.
+    pub fn prefix(&self) -> String {
+        upper_snake_case(&self.name)
+    }
+
+    /// Name of the `.equ` constant holding a field's offset.
+    pub fn offset_constant(&self, field: &LayoutField) -> String {
+        format!("{}_{}", self.prefix(), field.upper_name())
+    }
+
+    /// The `.equ` constants and macros this block expands to.
+    fn expand(&self) -> Vec<String> {
+        let prefix = self.prefix();
+        let mut lines = Vec::new();
+        for field in &self.fields {
+            lines.push(format!(
+                ".equ {}, {}",
+                self.offset_constant(field),
+                field.offset
+            ));
+        }
+        lines.push(format!(".equ {}_SIZE, {}", prefix, self.size));
+        for field in self.fields.iter().filter(|field| field.ty.is_integer()) {
+            let constant = self.offset_constant(field);
+            let field_name = field.upper_name();
+            let width = field.ty.width();
+            lines.push(format!(".macro {}_LOAD_{} dst, base", prefix, field_name));
+            lines.push(format!("  ldx{} \\dst, [\\base+{}]", width, constant));
+            lines.push(".endm".to_string());
+            lines.push(format!(".macro {}_STORE_{} base, src", prefix, field_name));
+            lines.push(format!("  stx{} [\\base+{}], \\src", width, constant));
+            lines.push(".endm".to_string());
+        }
+        lines
+    }
+}
+
+/// An account an instruction expects, in the order it expects them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionAccount {
+    pub name: String,
+    pub signer: bool,
+    pub writable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// First byte of the instruction data.
+    pub discriminator: u8,
+    pub accounts: Vec<InstructionAccount>,
+    /// The instruction data, named after the instruction. Field offsets count
+    /// the discriminator, so the first field is at 1.
+    pub data: Layout,
+}
+
+impl Instruction {
+    /// Name of the `.equ` constant holding the discriminator.
+    pub fn discriminator_constant(&self) -> String {
+        format!("{}_DISCRIMINATOR", self.data.prefix())
+    }
+
+    fn expand(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            ".equ {}, {}",
+            self.discriminator_constant(),
+            self.discriminator
+        )];
+        lines.extend(self.data.expand());
+        lines
+    }
+}
+
+/// A block being read.
+enum Block {
+    Layout(Layout),
+    Instruction(Instruction),
+}
+
+impl Block {
+    fn layout(&self) -> &Layout {
+        match self {
+            Self::Layout(layout) => layout,
+            Self::Instruction(instruction) => &instruction.data,
+        }
+    }
+
+    fn end_directive(&self) -> &'static str {
+        match self {
+            Self::Layout(_) => ".endlayout",
+            Self::Instruction(_) => ".endinstruction",
+        }
+    }
+}
+
+/// Result of scanning lines for layout blocks.
+pub(crate) struct LayoutScanResult {
+    pub layouts: Vec<Layout>,
+    pub instructions: Vec<Instruction>,
+    /// The input with every block replaced by its expansion.
+    pub lines: Vec<SourceLine>,
+    pub errors: Vec<(CompileError, SourceOrigin)>,
+}
+
+/// Replace `.layout` / `.endlayout` and `.instruction` / `.endinstruction`
+/// blocks with the constants and macros they declare. Runs before macro
+/// expansion so the generated macros can be invoked like any other.
+pub(crate) fn scan_layouts(lines: Vec<SourceLine>) -> LayoutScanResult {
+    let mut layouts: Vec<Layout> = Vec::new();
+    let mut instructions: Vec<Instruction> = Vec::new();
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+    let mut current: Option<(Block, SourceOrigin)> = None;
+    let mut names = HashSet::new();
+
+    for line in lines {
+        let text = strip_comment(&line.text);
+
+        if let Some((block, _)) = current.as_mut() {
+            if text == block.end_directive() {
+                let (block, start) = current.take().unwrap();
+                let name = block.layout().name.clone();
+                if !names.insert(name.clone()) {
+                    errors.push((
+                        CompileError::DuplicateLayout {
+                            name,
+                            span: 0..0,
+                            custom_label: None,
+                        },
+                        start,
+                    ));
+                    continue;
+                }
+                let expansion = match block {
+                    Block::Layout(layout) => {
+                        let expansion = layout.expand();
+                        layouts.push(layout);
+                        expansion
+                    }
+                    Block::Instruction(instruction) => {
+                        if instructions
+                            .iter()
+                            .any(|other| other.discriminator == instruction.discriminator)
+                        {
+                            errors.push((
+                                CompileError::DuplicateDiscriminator {
+                                    name,
+                                    discriminator: instruction.discriminator,
+                                    span: 0..0,
+                                    custom_label: None,
+                                },
+                                start,
+                            ));
+                            continue;
+                        }
+                        let expansion = instruction.expand();
+                        instructions.push(instruction);
+                        expansion
+                    }
+                };
+                let origin = SourceOrigin {
+                    generated: true,
+                    ..start.clone()
+                };
+                output.extend(expansion.into_iter().map(|text| SourceLine {
+                    text,
+                    origin: origin.clone(),
+                }));
+            } else if text.is_empty() {
+                continue;
+            } else if let Block::Instruction(instruction) = block
+                && let Some(account) = directive_argument(text, ".account")
+            {
+                if let Err(error) = parse_account(instruction, account) {
+                    errors.push((error, line.origin.clone()));
+                }
+            } else if let Err(error) = match block {
+                Block::Layout(layout) => parse_field(layout, text),
+                Block::Instruction(instruction) => parse_instruction_field(instruction, text),
+            } {
+                errors.push((error, line.origin.clone()));
+            }
+        } else if let Some(name) = directive_argument(text, ".layout") {
+            if !is_identifier(name) {
+                errors.push((
+                    CompileError::InvalidLayoutField {
+                        field: text.to_string(),
+                        span: 0..0,
+                        custom_label: Some("Layout names must be identifiers".to_string()),
+                    },
+                    line.origin.clone(),
+                ));
+            }
+            let layout = Layout {
+                name: name.to_string(),
+                fields: Vec::new(),
+                size: 0,
+            };
+            current = Some((Block::Layout(layout), line.origin.clone()));
+        } else if let Some(declaration) = directive_argument(text, ".instruction") {
+            let (name, discriminator) = match declaration.split_once(',') {
+                Some((name, discriminator)) => (name.trim(), discriminator.trim()),
+                None => (declaration, ""),
+            };
+            let discriminator = parse_number::<u8>(discriminator);
+            if !is_identifier(name) || discriminator.is_none() {
+                errors.push((
+                    CompileError::InvalidInstructionBlock {
+                        text: text.to_string(),
+                        span: 0..0,
+                        custom_label: (!is_identifier(name))
+                            .then(|| "Instruction names must be identifiers".to_string()),
+                    },
+                    line.origin.clone(),
+                ));
+            }
+            let instruction = Instruction {
+                discriminator: discriminator.unwrap_or_default(),
+                accounts: Vec::new(),
+                data: Layout {
+                    name: name.to_string(),
+                    fields: Vec::new(),
+                    size: 1,
+                },
+            };
+            current = Some((Block::Instruction(instruction), line.origin.clone()));
+        } else {
+            output.push(line);
+        }
+    }
+
+    match current {
+        Some((Block::Layout(layout), start)) => errors.push((
+            CompileError::UnclosedLayout {
+                name: layout.name,
+                span: 0..0,
+                custom_label: None,
+            },
+            start,
+        )),
+        Some((Block::Instruction(instruction), start)) => errors.push((
+            CompileError::UnclosedInstruction {
+                name: instruction.data.name,
+                span: 0..0,
+                custom_label: None,
+            },
+            start,
+        )),
+        None => {}
+    }
+
+    LayoutScanResult {
+        layouts,
+        instructions,
+        lines: output,
+        errors,
+    }
+}
+
+/// What follows `directive` on a line, if the line starts with it.
+fn directive_argument<'a>(text: &'a str, directive: &str) -> Option<&'a str> {
+    text.strip_prefix(directive)
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(str::trim)
+}
+
+/// Parse every `.layout` block in `source`, such as a sidecar file holding
+/// only layouts. Errors carry the 1-based line they were found on.
+pub fn parse_layouts(source: &str) -> Result<Vec<Layout>, Vec<(CompileError, u32)>> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| SourceLine {
+            text: text.to_string(),
+            origin: SourceOrigin::new(super::source_map::FileId(0), index as u32 + 1),
+        })
+        .collect();
+    let result = scan_layouts(lines);
+    if result.errors.is_empty() {
+        Ok(result.layouts)
+    } else {
+        Err(result
+            .errors
+            .into_iter()
+            .map(|(error, origin)| (error, origin.line))
+            .collect())
+    }
+}
+
+/// Parse a `name: type` or `name: type @ offset` field into `layout`.
+fn parse_field(layout: &mut Layout, text: &str) -> Result<(), CompileError> {
+    let invalid = |label: &str| CompileError::InvalidLayoutField {
+        field: text.to_string(),
+        span: 0..0,
+        custom_label: Some(label.to_string()),
+    };
+
+    let (name, rest) = text
+        .split_once(':')
+        .ok_or_else(|| invalid("Expected `name: type`"))?;
+    let name = name.trim();
+    if !is_identifier(name) {
+        return Err(invalid("Field names must be identifiers"));
+    }
+    if layout.fields.iter().any(|field| field.name == name) {
+        return Err(invalid("Field is already declared"));
+    }
+    let (ty, offset) = match rest.split_once('@') {
+        Some((ty, offset)) => (ty, Some(offset.trim())),
+        None => (rest, None),
+    };
+    let ty = FieldType::parse(ty.trim())
+        .ok_or_else(|| invalid("Expected u8-u64, i8-i64, pubkey or [u8; N]"))?;
+    let offset = match offset {
+        Some(offset) => {
+            let offset = parse_number(offset).ok_or_else(|| invalid("Invalid offset"))?;
+            if offset < layout.size {
+                return Err(invalid("Field overlaps the one before it"));
+            }
+            offset
+        }
+        None => layout.size,
+    };
+
+    layout.size = offset + ty.size();
+    layout.fields.push(LayoutField {
+        name: name.to_string(),
+        ty,
+        offset,
+    });
+    Ok(())
+}
+
+/// Parse a field of an instruction's data. `discriminator` would collide
+/// with the constant for the discriminator itself.
+fn parse_instruction_field(instruction: &mut Instruction, text: &str) -> Result<(), CompileError> {
+    if text
+        .split_once(':')
+        .is_some_and(|(name, _)| upper_snake_case(name.trim()) == "DISCRIMINATOR")
+    {
+        return Err(CompileError::InvalidLayoutField {
+            field: text.to_string(),
+            span: 0..0,
+            custom_label: Some("The discriminator is declared by `.instruction`".to_string()),
+        });
+    }
+    parse_field(&mut instruction.data, text)
+}
+
+/// Parse an `.account name[, signer][, writable]` line of an instruction.
+fn parse_account(instruction: &mut Instruction, text: &str) -> Result<(), CompileError> {
+    let invalid = |label: &str| CompileError::InvalidInstructionBlock {
+        text: format!(".account {}", text),
+        span: 0..0,
+        custom_label: Some(label.to_string()),
+    };
+
+    let mut parts = text.split(',').map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    if !is_identifier(name) {
+        return Err(invalid("Account names must be identifiers"));
+    }
+    if instruction
+        .accounts
+        .iter()
+        .any(|account| account.name == name)
+    {
+        return Err(invalid("Account is already declared"));
+    }
+    let mut account = InstructionAccount {
+        name: name.to_string(),
+        signer: false,
+        writable: false,
+    };
+    for flag in parts {
+        match flag {
+            "signer" => account.signer = true,
+            "writable" => account.writable = true,
+            _ => return Err(invalid("Expected `signer` or `writable`")),
+        }
+    }
+    instruction.accounts.push(account);
+    Ok(())
+}
+
+/// Drop a trailing line comment. `;` inside brackets belongs to a `[u8; N]`
+/// type rather than starting a comment.
+pub(super) fn strip_comment(text: &str) -> &str {
+    let mut depth = 0usize;
+    let mut end = text.len();
+    for (index, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ';' if depth > 0 => {}
+            '#' | ';' => {
+                end = index;
+                break;
+            }
+            '/' if text[index..].starts_with("//") => {
+                end = index;
+                break;
+            }
+            _ => {}
+        }
+    }
+    text[..end].trim()
+}
+
+/// `counterState` and `CounterState` both become `COUNTER_STATE`.
+fn upper_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            result.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::preprocessor::source_map::FileId};
+
+    fn make_source_line(text: &str, line: u32) -> SourceLine {
+        SourceLine {
+            text: text.to_string(),
+            origin: SourceOrigin::new(FileId(0), line),
+        }
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let layouts = parse_layouts(
+            ".layout Vault\n  bump: u8\n  owner: pubkey # who may withdraw\n  \
+             amount: u64 @ 40\n  memo: [u8; 0x10]\n.endlayout\n",
+        )
+        .unwrap();
+        assert_eq!(layouts.len(), 1);
+        let vault = &layouts[0];
+        assert_eq!(vault.prefix(), "VAULT");
+        let offsets: Vec<_> = vault
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![("bump", 0), ("owner", 1), ("amount", 40), ("memo", 48)]
+        );
+        assert_eq!(vault.fields[3].ty, FieldType::Bytes(16));
+        assert_eq!(vault.size, 64);
+    }
+
+    #[test]
+    fn test_scan_expands_constants_and_macros() {
+        let lines = vec![
+            make_source_line("before", 1),
+            make_source_line(".layout counterState", 2),
+            make_source_line("  bump: u8", 3),
+            make_source_line("  count: u64", 4),
+            make_source_line(".endlayout", 5),
+            make_source_line("after", 6),
+        ];
+        let result = scan_layouts(lines);
+        assert!(result.errors.is_empty());
+        let text: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(
+            text,
+            vec![
+                "before",
+                ".equ COUNTER_STATE_BUMP, 0",
+                ".equ COUNTER_STATE_COUNT, 1",
+                ".equ COUNTER_STATE_SIZE, 9",
+                ".macro COUNTER_STATE_LOAD_BUMP dst, base",
+                "  ldxb \\dst, [\\base+COUNTER_STATE_BUMP]",
+                ".endm",
+                ".macro COUNTER_STATE_STORE_BUMP base, src",
+                "  stxb [\\base+COUNTER_STATE_BUMP], \\src",
+                ".endm",
+                ".macro COUNTER_STATE_LOAD_COUNT dst, base",
+                "  ldxdw \\dst, [\\base+COUNTER_STATE_COUNT]",
+                ".endm",
+                ".macro COUNTER_STATE_STORE_COUNT base, src",
+                "  stxdw [\\base+COUNTER_STATE_COUNT], \\src",
+                ".endm",
+                "after",
+            ]
+        );
+        assert_eq!(result.lines[1].origin.line, 2);
+    }
+
+    #[test]
+    fn test_layout_errors() {
+        let errors =
+            parse_layouts(".layout a\n  x: u64\n  y: u8 @ 4\n  z: f32\n.endlayout\n").unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|(_, line)| *line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(matches!(
+            &errors[0].0,
+            CompileError::InvalidLayoutField { field, .. } if field == "y: u8 @ 4"
+        ));
+
+        let errors =
+            parse_layouts(".layout a\n.endlayout\n.layout a\n.endlayout\n.layout b\n").unwrap_err();
+        assert!(
+            matches!(&errors[0], (CompileError::DuplicateLayout { name, .. }, 3) if name == "a")
+        );
+        assert!(
+            matches!(&errors[1], (CompileError::UnclosedLayout { name, .. }, 5) if name == "b")
+        );
+    }
+
+    #[test]
+    fn test_instruction_blocks() {
+        let lines = [
+            ".instruction deposit, 2",
+            "  .account owner, signer, writable",
+            "  .account vault, writable",
+            "  amount: u64",
+            ".endinstruction",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, text)| make_source_line(text, index as u32 + 1))
+        .collect();
+        let result = scan_layouts(lines);
+        assert!(result.errors.is_empty());
+        let deposit = &result.instructions[0];
+        assert_eq!(deposit.discriminator, 2);
+        assert_eq!(deposit.accounts.len(), 2);
+        assert!(deposit.accounts[0].signer && !deposit.accounts[1].signer);
+        let text: Vec<_> = result.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(
+            &text[..3],
+            [
+                ".equ DEPOSIT_DISCRIMINATOR, 2",
+                ".equ DEPOSIT_AMOUNT, 1",
+                ".equ DEPOSIT_SIZE, 9",
+            ]
+        );
+
+        let lines = [
+            ".instruction a, 1",
+            "  .account x, mutable",
+            "  discriminator: u8",
+            "  y: u8 @ 0",
+            ".endinstruction",
+            ".instruction b, 1",
+            ".endinstruction",
+            ".instruction c, 256",
+            ".endinstruction",
+            ".instruction d, 3",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, text)| make_source_line(text, index as u32 + 1))
+        .collect();
+        let errors: Vec<_> = scan_layouts(lines)
+            .errors
+            .into_iter()
+            .map(|(error, origin)| (error.to_string(), origin.line))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (
+                    "Invalid instruction declaration '.account x, mutable'".to_string(),
+                    2
+                ),
+                ("Invalid layout field 'discriminator: u8'".to_string(), 3),
+                ("Invalid layout field 'y: u8 @ 0'".to_string(), 4),
+                ("Instruction 'b' reuses discriminator 1".to_string(), 6),
+                (
+                    "Invalid instruction declaration '.instruction c, 256'".to_string(),
+                    8
+                ),
+                ("Instruction 'd' missing .endinstruction".to_string(), 10),
+            ]
+        );
+    }
+}
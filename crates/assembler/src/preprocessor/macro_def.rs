@@ -16,7 +16,8 @@ pub struct Param {
 pub struct MacroDef {
     pub name: String,
     pub params: Vec<Param>,
-    pub body_lines: Vec<String>,
+    /// Body lines, each with the location it was written at
+    pub body_lines: Vec<SourceLine>,
     pub defined_at: SourceOrigin,
 }
 
@@ -39,7 +40,7 @@ pub(crate) fn scan_macro_definitions(lines: Vec<SourceLine>) -> MacroScanResult
     let mut remaining = Vec::new();
     let mut errors = Vec::new();
 
-    let mut current_macro: Option<(String, Vec<Param>, SourceOrigin, Vec<String>)> = None;
+    let mut current_macro: Option<(String, Vec<Param>, SourceOrigin, Vec<SourceLine>)> = None;
 
     // Track the origin of the .macro directive line for error reporting
     let mut macro_start_origin: Option<SourceOrigin> = None;
@@ -75,7 +76,7 @@ pub(crate) fn scan_macro_definitions(lines: Vec<SourceLine>) -> MacroScanResult
                 }
             } else {
                 // Accumulate body lines
-                macro_state.3.push(line.text.clone());
+                macro_state.3.push(line);
             }
         } else if let Some((name, params)) = parse_macro_directive(trimmed) {
             // Validate parameters
@@ -170,12 +171,18 @@ fn parse_macro_directive(line: &str) -> Option<(String, Vec<Param>)> {
     Some((name.to_string(), params))
 }
 
-/// Parse a comma-separated parameter list.
+/// Parse a parameter list, separated by commas or, when there are none, by
+/// whitespace as in `.macro PUSH reg offset`.
 /// Supports: `name`, `name=default`, `name:vararg`
 fn parse_params(params_str: &str) -> Option<Vec<Param>> {
     let mut params = Vec::new();
 
-    for part in params_str.split(',') {
+    let parts: Vec<&str> = if params_str.contains(',') {
+        params_str.split(',').collect()
+    } else {
+        params_str.split_whitespace().collect()
+    };
+    for part in parts {
         let part = part.trim();
         if part.is_empty() {
             continue;
@@ -260,6 +267,16 @@ mod tests {
         assert_eq!(params[1].name, "args");
     }
 
+    #[test]
+    fn test_parse_macro_directive_whitespace_params() {
+        let (name, params) = parse_macro_directive(".macro CHECK_SIGNER acct fail=abort").unwrap();
+        assert_eq!(name, "CHECK_SIGNER");
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].name, "acct");
+        assert_eq!(params[1].name, "fail");
+        assert_eq!(params[1].default.as_deref(), Some("abort"));
+    }
+
     #[test]
     fn test_parse_macro_directive_not_macro() {
         assert!(parse_macro_directive("mov64 r1, 1").is_none());
@@ -287,6 +304,8 @@ mod tests {
         assert_eq!(macro_def.params.len(), 1);
         assert_eq!(macro_def.params[0].name, "reg");
         assert_eq!(macro_def.body_lines.len(), 2);
+        assert_eq!(macro_def.body_lines[0].origin.line, 3);
+        assert_eq!(macro_def.body_lines[1].origin.line, 4);
 
         assert_eq!(result.remaining_lines.len(), 2);
         assert_eq!(result.remaining_lines[0].text, "before");
@@ -1,23 +1,29 @@
+pub mod comment;
+pub mod conditional;
+pub mod encoding;
 pub mod expand;
 pub mod include;
+pub mod layout;
 pub mod macro_def;
 pub mod source_map;
+pub mod vfs;
 
 use {
     crate::errors::CompileError,
-    source_map::{FileRegistry, SourceMap, SourceOrigin},
+    source_map::{FileId, FileRegistry, SourceMap, SourceOrigin},
     std::path::PathBuf,
+    vfs::{FsProvider, IncludeResolver},
 };
 
 /// A line of source with its origin tracking
 #[derive(Debug, Clone)]
-pub(crate) struct SourceLine {
+pub struct SourceLine {
     pub text: String,
     pub origin: SourceOrigin,
 }
 
 /// Trait for resolving `.include` file paths to their contents.
-/// Abstracted as a trait so tests can use an in-memory mock.
+/// [`IncludeResolver`] implements it on top of any [`vfs::FileProvider`].
 pub trait FileResolver {
     /// Resolve an include path relative to the including file's directory.
     /// Returns the file contents.
@@ -25,54 +31,23 @@ pub trait FileResolver {
 }
 
 /// File resolver that reads from the real filesystem.
-#[derive(Debug, Clone)]
-pub struct FsFileResolver {
-    /// Additional directories to search for includes
-    pub include_paths: Vec<PathBuf>,
-}
+pub type FsFileResolver = IncludeResolver<FsProvider>;
 
-impl FsFileResolver {
+impl IncludeResolver<FsProvider> {
     pub fn new() -> Self {
-        Self {
-            include_paths: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn with_include_paths(include_paths: Vec<PathBuf>) -> Self {
-        Self { include_paths }
-    }
-}
-
-impl Default for FsFileResolver {
-    fn default() -> Self {
-        Self::new()
+        Self::with_provider(FsProvider, include_paths)
     }
 }
 
-impl FileResolver for FsFileResolver {
-    fn resolve(&self, path: &str, relative_to: &str) -> Result<String, std::io::Error> {
-        // Try relative to the including file's directory first
-        let base_dir = std::path::Path::new(relative_to)
-            .parent()
-            .unwrap_or(std::path::Path::new("."));
-        let candidate = base_dir.join(path);
-        if candidate.exists() {
-            return std::fs::read_to_string(&candidate);
-        }
-
-        // Try each include path
-        for include_dir in &self.include_paths {
-            let candidate = include_dir.join(path);
-            if candidate.exists() {
-                return std::fs::read_to_string(&candidate);
-            }
-        }
-
-        Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("file not found: {}", path),
-        ))
-    }
+/// Read and decode a source file, see [`encoding::decode_source`].
+pub fn read_source(path: &std::path::Path) -> Result<String, std::io::Error> {
+    let bytes = std::fs::read(path)?;
+    encoding::decode_source(&bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
 }
 
 /// In-memory file resolver for testing
@@ -107,6 +82,10 @@ impl FileResolver for MockFileResolver {
 pub struct PreprocessResult {
     pub expanded_source: String,
     pub source_map: SourceMap,
+    /// The `.layout` blocks the source declares, in order.
+    pub layouts: Vec<layout::Layout>,
+    /// The `.instruction` blocks the source declares, in order.
+    pub instructions: Vec<layout::Instruction>,
 }
 
 /// A preprocessor error paired with its source origin.
@@ -125,7 +104,10 @@ pub struct PreprocessFailure {
 
 /// Run the full preprocessor pipeline:
 /// 1. Resolve `.include` directives (flatten files)
-/// 2. Expand `.macro`/`.endm`, `.rept`/`.endr`, `.irp`/`.endr`
+/// 2. Replace `.layout` and `.instruction` blocks with constants and macros
+/// 3. Keep the taken branches of `.if`/`.ifdef`/`.ifndef` blocks
+/// 4. Expand `.macro`/`.endm`, `.rept`/`.endr`, `.irp`/`.endr`
+/// 5. Resolve the conditional blocks that came out of macro bodies
 ///
 /// The resulting `expanded_source` can be fed directly to the pest parser.
 /// The `source_map` allows remapping pest error spans back to original locations.
@@ -133,6 +115,18 @@ pub fn preprocess(
     source: &str,
     source_path: &str,
     resolver: Option<&dyn FileResolver>,
+) -> Result<PreprocessResult, PreprocessFailure> {
+    preprocess_with_defines(source, source_path, resolver, &[])
+}
+
+/// [`preprocess`] with constants defined up front, as by `--define NAME=VALUE`.
+/// Each is declared with `.equ` ahead of the source and is visible to
+/// conditional directives.
+pub fn preprocess_with_defines(
+    source: &str,
+    source_path: &str,
+    resolver: Option<&dyn FileResolver>,
+    defines: &[(String, i64)],
 ) -> Result<PreprocessResult, PreprocessFailure> {
     let mut registry = FileRegistry::new();
 
@@ -153,8 +147,31 @@ pub fn preprocess(
         }
     };
 
-    // Pass 2: Macro expansion
-    let (expanded_lines, errors) = match expand::expand_macros(lines) {
+    // Pass 2: Layout expansion
+    let layouts = layout::scan_layouts(lines);
+    if !layouts.errors.is_empty() {
+        return Err(PreprocessFailure {
+            errors: layouts
+                .errors
+                .into_iter()
+                .map(|(error, origin)| PreprocessorError {
+                    error,
+                    origin: Some(origin),
+                })
+                .collect(),
+            file_registry: registry,
+        });
+    }
+
+    // Pass 3: Conditional assembly outside macro bodies
+    let conditionals = conditional::resolve_conditionals(layouts.lines, defines);
+    if !conditionals.errors.is_empty() {
+        return Err(conditional_failure(conditionals.errors, registry));
+    }
+    let (instructions, layouts) = (layouts.instructions, layouts.layouts);
+
+    // Pass 4: Macro expansion
+    let (expanded_lines, errors) = match expand::expand_macros(conditionals.lines) {
         Ok(result) => result,
         Err(errors) => {
             return Err(PreprocessFailure {
@@ -183,6 +200,20 @@ pub fn preprocess(
         });
     }
 
+    // Pass 5: Conditional assembly inside expanded macros
+    let conditionals = conditional::resolve_conditionals(expanded_lines, defines);
+    if !conditionals.errors.is_empty() {
+        return Err(conditional_failure(conditionals.errors, registry));
+    }
+
+    // Defines come first so the source can use them like any `.equ`. They
+    // have no line of their own.
+    let defined = defines.iter().map(|(name, value)| SourceLine {
+        text: format!(".equ {}, {}", name, value),
+        origin: SourceOrigin::new(FileId(0), 0),
+    });
+    let expanded_lines: Vec<SourceLine> = defined.chain(conditionals.lines).collect();
+
     // Build the expanded source string and source map
     let mut expanded_source = String::new();
     let mut line_origins = Vec::with_capacity(expanded_lines.len());
@@ -198,5 +229,42 @@ pub fn preprocess(
     Ok(PreprocessResult {
         expanded_source,
         source_map,
+        layouts,
+        instructions,
     })
 }
+
+fn conditional_failure(
+    errors: Vec<(CompileError, SourceOrigin)>,
+    file_registry: FileRegistry,
+) -> PreprocessFailure {
+    PreprocessFailure {
+        errors: errors
+            .into_iter()
+            .map(|(error, origin)| PreprocessorError {
+                error,
+                origin: Some(origin),
+            })
+            .collect(),
+        file_registry,
+    }
+}
+
+/// A name made of ASCII letters, digits and `_`, not starting with a digit.
+pub(super) fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A decimal or `0x` hex number, with `_` separators allowed, that fits `T`.
+pub(super) fn parse_number<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let digits = text.replace('_', "");
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    T::try_from(value).ok()
+}
@@ -100,14 +100,50 @@ pub struct SourceOrigin {
     pub line: u32,
     /// If this line was produced by a macro expansion, the chain of expansions
     pub macro_expansion: Option<Box<MacroExpansionInfo>>,
+    /// Whether this line was generated by a `.layout` or `.instruction` block
+    pub generated: bool,
 }
 
+/// Where spans point when the expanded source has no lines at all: the first
+/// line of the main file.
+static EMPTY_SOURCE_ORIGIN: SourceOrigin = SourceOrigin {
+    file_id: FileId(0),
+    line: 1,
+    macro_expansion: None,
+    generated: false,
+};
+
 impl SourceOrigin {
     pub fn new(file_id: FileId, line: u32) -> Self {
         Self {
             file_id,
             line,
             macro_expansion: None,
+            generated: false,
+        }
+    }
+
+    /// Point this origin, and the invocations it came from, at line
+    /// `lines[line - 1]` of `to` if they are in `from` and that entry is not 0.
+    pub(crate) fn redirect(&mut self, from: FileId, to: FileId, lines: &[u32]) {
+        if self.file_id == from
+            && let Some(&line) = lines.get((self.line as usize).wrapping_sub(1))
+            && line != 0
+        {
+            self.file_id = to;
+            self.line = line;
+        }
+        if let Some(expansion) = &mut self.macro_expansion {
+            expansion.invocation_origin.redirect(from, to, lines);
+        }
+    }
+
+    /// Point this origin, and the invocations it came from, at files
+    /// registered `offset` entries later.
+    fn offset_file_ids(&mut self, offset: u32) {
+        self.file_id.0 += offset;
+        if let Some(expansion) = &mut self.macro_expansion {
+            expansion.invocation_origin.offset_file_ids(offset);
         }
     }
 
@@ -126,6 +162,7 @@ impl SourceOrigin {
                 invocation_origin,
                 depth,
             })),
+            generated: false,
         }
     }
 }
@@ -153,7 +190,9 @@ impl SourceMap {
     pub fn resolve(&self, byte_offset: usize, expanded_source: &str) -> &SourceOrigin {
         let line_index = byte_offset_to_line(byte_offset, expanded_source);
         let clamped = line_index.min(self.line_origins.len().saturating_sub(1));
-        &self.line_origins[clamped]
+        self.line_origins
+            .get(clamped)
+            .unwrap_or(&EMPTY_SOURCE_ORIGIN)
     }
 
     /// Remap a span (byte range) from expanded source to an original origin.
@@ -162,6 +201,42 @@ impl SourceMap {
         self.resolve(span.start, expanded_source)
     }
 
+    /// Append the map of source that was expanded on its own and then placed
+    /// after this map's source.
+    pub fn append(&mut self, other: SourceMap) {
+        let offset = self.file_registry.files.len() as u32;
+        self.file_registry.files.extend(other.file_registry.files);
+        self.line_origins
+            .extend(other.line_origins.into_iter().map(|mut origin| {
+                origin.offset_file_ids(offset);
+                origin
+            }));
+    }
+
+    /// Register `path` and point lines that came from `from` at the lines of
+    /// `path` they were generated from, as `lines` gives them: entry `n` is
+    /// the line of `path` for line `n + 1` of `from`, or 0 to leave that line
+    /// pointing into `from`.
+    pub fn redirect(&mut self, from: FileId, path: &str, content: String, lines: &[u32]) -> FileId {
+        let to = self.file_registry.add(path, content);
+        for origin in &mut self.line_origins {
+            origin.redirect(from, to, lines);
+        }
+        to
+    }
+
+    /// Origin of every expanded line, in order.
+    pub fn origins(&self) -> &[SourceOrigin] {
+        &self.line_origins
+    }
+
+    /// File path and line of every expanded line, in order.
+    pub fn line_locations(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.line_origins
+            .iter()
+            .map(|origin| (self.file_registry.path(origin.file_id), origin.line))
+    }
+
     /// Get the number of tracked output lines
     pub fn len(&self) -> usize {
         self.line_origins.len()
@@ -258,6 +333,43 @@ mod tests {
         assert_eq!(origin.line, 10);
     }
 
+    #[test]
+    fn test_source_map_resolve_empty_source() {
+        let mut reg = FileRegistry::new();
+        let file_id = reg.add("empty.s", String::new());
+        let map = SourceMap::new(reg, vec![]);
+
+        let origin = map.resolve(0, "");
+        assert_eq!((origin.file_id, origin.line), (file_id, 1));
+    }
+
+    #[test]
+    fn test_source_map_redirect() {
+        let mut reg = FileRegistry::new();
+        let generated = reg.add("prog.expr.s", "a\nb\nc".to_string());
+        let origins = vec![
+            SourceOrigin::new(generated, 1),
+            SourceOrigin::new(generated, 2),
+            SourceOrigin::with_macro_expansion(
+                generated,
+                3,
+                "M".to_string(),
+                SourceOrigin::new(generated, 2),
+                1,
+            ),
+        ];
+        let mut map = SourceMap::new(reg, origins);
+
+        let source = map.redirect(generated, "prog.expr", "x = 1".to_string(), &[0, 7, 9]);
+        assert_eq!(map.file_registry.path(source), "prog.expr");
+        assert_eq!(
+            map.line_locations().collect::<Vec<_>>(),
+            [("prog.expr.s", 1), ("prog.expr", 7), ("prog.expr", 9)]
+        );
+        let invocation = &map.origins()[2].macro_expansion.as_ref().unwrap();
+        assert_eq!(invocation.invocation_origin.line, 7);
+    }
+
     #[test]
     fn test_format_location_simple() {
         let mut reg = FileRegistry::new();
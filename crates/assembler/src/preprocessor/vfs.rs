@@ -0,0 +1,244 @@
+use {
+    super::{FileResolver, encoding::decode_source},
+    std::{
+        collections::HashMap,
+        io,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Read access to source files, wherever they live: on disk, in memory for
+/// the browser and editors, or inside a bundle.
+pub trait FileProvider {
+    /// Read the raw bytes of the file at `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.read(path).is_ok()
+    }
+}
+
+impl<P: FileProvider + ?Sized> FileProvider for &P {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+}
+
+/// The real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsProvider;
+
+impl FileProvider for FsProvider {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+/// Files held in memory. Paths are compared after resolving `.` and `..`, so
+/// `src/../lib/a.s` finds `lib/a.s`.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryProvider {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, path: impl AsRef<Path>, content: impl Into<Vec<u8>>) -> &mut Self {
+        self.files.insert(normalize(path.as_ref()), content.into());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl FileProvider for MemoryProvider {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.get(&normalize(path)).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("file not found: {}", path.display()),
+            )
+        })
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize(path))
+    }
+}
+
+/// Files from a zip archive, read into memory up front. Paths are relative to
+/// the root of the archive.
+#[cfg(feature = "zip")]
+#[derive(Debug, Clone, Default)]
+pub struct ZipProvider {
+    files: MemoryProvider,
+}
+
+#[cfg(feature = "zip")]
+impl ZipProvider {
+    /// Unpack `bytes`. Entries whose names would escape the archive root are
+    /// skipped.
+    pub fn new(bytes: &[u8]) -> io::Result<Self> {
+        use std::io::Read;
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+        let mut files = MemoryProvider::new();
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index)?;
+            let Some(path) = entry.enclosed_name().filter(|_| entry.is_file()) else {
+                continue;
+            };
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.add_file(path, content);
+        }
+        Ok(Self { files })
+    }
+}
+
+#[cfg(feature = "zip")]
+impl FileProvider for ZipProvider {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.exists(path)
+    }
+}
+
+/// Resolves `.include` paths against a [`FileProvider`]: first relative to the
+/// including file's directory, then in each include path in order.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeResolver<P> {
+    pub provider: P,
+    /// Additional directories to search for includes
+    pub include_paths: Vec<PathBuf>,
+}
+
+impl<P: FileProvider> IncludeResolver<P> {
+    pub fn with_provider(provider: P, include_paths: Vec<PathBuf>) -> Self {
+        Self {
+            provider,
+            include_paths,
+        }
+    }
+
+    /// Where `path`, included from `relative_to`, is found.
+    pub fn locate(&self, path: &str, relative_to: &str) -> Option<PathBuf> {
+        let base_dir = Path::new(relative_to).parent().unwrap_or(Path::new("."));
+        std::iter::once(base_dir)
+            .chain(self.include_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(path))
+            .find(|candidate| self.provider.exists(candidate))
+    }
+}
+
+impl<P: FileProvider> FileResolver for IncludeResolver<P> {
+    fn resolve(&self, path: &str, relative_to: &str) -> Result<String, io::Error> {
+        let candidate = self.locate(path, relative_to).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("file not found: {}", path))
+        })?;
+        let bytes = self.provider.read(&candidate)?;
+        decode_source(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_provider_normalizes_paths() {
+        let mut provider = MemoryProvider::new();
+        provider.add_file("./lib/a.s", "exit\n");
+        assert!(provider.exists(Path::new("lib/a.s")));
+        assert!(provider.exists(Path::new("src/../lib/a.s")));
+        assert!(!provider.exists(Path::new("a.s")));
+        assert_eq!(provider.read(Path::new("lib/./a.s")).unwrap(), b"exit\n");
+    }
+
+    #[test]
+    fn test_include_resolver_search_order() {
+        let mut provider = MemoryProvider::new();
+        provider
+            .add_file("src/common.s", "; next to main\n")
+            .add_file("lib/common.s", "; library\n")
+            .add_file("lib/only_lib.s", "; library only\n");
+        let resolver = IncludeResolver::with_provider(provider, vec![PathBuf::from("lib")]);
+
+        assert_eq!(
+            resolver.resolve("common.s", "src/main.s").unwrap(),
+            "; next to main\n"
+        );
+        assert_eq!(
+            resolver.resolve("only_lib.s", "src/main.s").unwrap(),
+            "; library only\n"
+        );
+        assert_eq!(
+            resolver.locate("../lib/common.s", "src/main.s"),
+            Some(PathBuf::from("src/../lib/common.s"))
+        );
+        assert_eq!(
+            resolver
+                .resolve("missing.s", "src/main.s")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_provider() {
+        use {std::io::Write, zip::write::SimpleFileOptions};
+
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file("lib/exit.s", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"exit\n").unwrap();
+        writer
+            .add_directory("empty/", SimpleFileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let provider = ZipProvider::new(&bytes).unwrap();
+        assert_eq!(provider.read(Path::new("lib/exit.s")).unwrap(), b"exit\n");
+        assert!(!provider.exists(Path::new("empty")));
+    }
+}
@@ -1,17 +1,27 @@
 use {
     crate::{
-        debug::{self, DebugData, reuse_debug_sections},
+        BuildInfo,
+        debug::{DebugData, calc_name_offset, reuse_debug_sections},
         dynsym::{DynamicSymbol, RelDyn, RelocationType},
         header::{ElfHeader, ProgramHeader},
         parser::ProgramLayout,
         section::{
-            DebugSection, DynStrSection, DynSymSection, DynamicSection, NullSection, RelDynSection,
-            Section, SectionType, ShStrTabSection,
+            DebugSection, DynStrSection, DynSymSection, DynamicSection, NoteSection, NullSection,
+            RelDynSection, Section, SectionType, ShStrTabSection, StrTabSection, SymTabSection,
         },
     },
+    sbpf_common::{
+        build_note::{BUILD_NOTE_SECTION, BuildNote},
+        program_error::ERROR_NOTE_SECTION,
+    },
+    sha2::{Digest, Sha256},
     std::{fs::File, io::Write, path::Path},
 };
 
+/// Length of the build-id, a truncated SHA-256 of `.text`, `.rodata` and
+/// `.data`, in that order.
+const BUILD_ID_LEN: usize = 20;
+
 #[derive(Debug)]
 pub struct Program {
     pub elf_header: ElfHeader,
@@ -24,42 +34,76 @@ impl Program {
         ProgramLayout {
             code_section,
             data_section,
+            writable_data_section,
+            bss_section,
             dynamic_symbols,
             relocation_data,
             prog_is_static,
             arch,
             debug_sections,
+            source_locations: _,
+            warnings: _,
+            custom_errors,
         }: ProgramLayout,
         debug_data: Option<DebugData>,
+        build_info: Option<&BuildInfo>,
     ) -> Self {
         let mut elf_header = ElfHeader::new();
         let mut program_headers = None;
 
         let bytecode_size = code_section.size();
         let rodata_size = data_section.size();
+        let writable_size = writable_data_section.size();
+        // .bss takes no space in the file, only in memory.
+        let bss_size = bss_section.get_size().next_multiple_of(8);
 
         let has_rodata = rodata_size > 0;
+        let has_data = writable_size > 0;
+        let has_bss = bss_size > 0;
+
+        // Note sections, by name: build provenance and the `.error` table.
+        let mut notes = Vec::new();
+        if let Some(info) = build_info {
+            let mut hasher = Sha256::new();
+            hasher.update(code_section.bytecode());
+            hasher.update(data_section.bytecode());
+            hasher.update(writable_data_section.bytecode());
+            let build_note = BuildNote {
+                build_id: hasher.finalize()[..BUILD_ID_LEN].to_vec(),
+                assembler_version: env!("CARGO_PKG_VERSION").to_string(),
+                git_commit: info.git_commit.clone(),
+            };
+            notes.push((BUILD_NOTE_SECTION, build_note.to_bytes()));
+        }
+        if !custom_errors.is_empty() {
+            notes.push((ERROR_NOTE_SECTION, custom_errors.to_note_bytes()));
+        }
+        let has_notes = !notes.is_empty();
+        // Sized symbols for `.rodata` labels, so the data can be found again
+        let rodata_symbols = data_section.symbols();
+        let has_symbols = !rodata_symbols.is_empty();
         let ph_count = if arch.is_v3() {
-            if has_rodata { 2 } else { 1 }
+            1 + has_rodata as u16 + (has_data || has_bss) as u16
         } else if prog_is_static {
             0
         } else {
             3
         };
 
-        elf_header.e_flags = arch.e_flags();
+        elf_header.set_arch(arch);
         elf_header.e_phnum = ph_count;
 
         // save read + execute size for program header before
-        // ownership of code/data sections is transferred
-        let text_size = bytecode_size + rodata_size;
+        // ownership of code/data sections is transferred. v0 loads sections
+        // through the section headers, so .data shares the first segment.
+        let text_size = bytecode_size + rodata_size + writable_size;
 
         // Calculate base offset after ELF header and program headers
         let base_offset = 64 + (ph_count as u64 * 56); // 64 bytes ELF header, 56 bytes per program header
         let mut current_offset = base_offset;
 
-        let text_offset = if arch.is_v3() && has_rodata {
-            rodata_size + base_offset
+        let text_offset = if arch.is_v3() {
+            rodata_size + writable_size + base_offset
         } else {
             base_offset
         };
@@ -89,14 +133,39 @@ impl Program {
         if has_rodata {
             section_names.push(".rodata".to_string());
         }
+        let mut writable_data_section = writable_data_section;
+        if has_data {
+            writable_data_section.set_name_offset(calc_name_offset(&section_names));
+            section_names.push(".data".to_string());
+        }
+        let mut bss_section = bss_section;
+        if has_bss {
+            bss_section.set_name_offset(calc_name_offset(&section_names));
+            section_names.push(".bss".to_string());
+        }
 
-        if arch.is_v3() && has_rodata {
-            // Data section
-            let mut rodata_section = SectionType::Data(data_section);
-            rodata_section.set_offset(current_offset);
-            rodata_section.set_vaddr(ProgramHeader::V3_RODATA_VADDR);
-            current_offset += rodata_section.size();
-            sections.push(rodata_section);
+        if arch.is_v3() && (has_rodata || has_data || has_bss) {
+            // Data sections: .rodata, then .data and .bss right after it
+            if has_rodata {
+                let mut rodata_section = SectionType::Data(data_section);
+                rodata_section.set_offset(current_offset);
+                rodata_section.set_vaddr(ProgramHeader::V3_RODATA_VADDR);
+                current_offset += rodata_section.size();
+                sections.push(rodata_section);
+            }
+            if has_data {
+                let mut writable_section = SectionType::Data(writable_data_section);
+                writable_section.set_offset(current_offset);
+                writable_section.set_vaddr(ProgramHeader::V3_RODATA_VADDR + rodata_size);
+                current_offset += writable_section.size();
+                sections.push(writable_section);
+            }
+            if has_bss {
+                let mut bss = SectionType::Data(bss_section);
+                bss.set_offset(current_offset);
+                bss.set_vaddr(ProgramHeader::V3_RODATA_VADDR + rodata_size + writable_size);
+                sections.push(bss);
+            }
 
             // Code section
             let mut text_section = SectionType::Code(code_section);
@@ -124,6 +193,17 @@ impl Program {
                 current_offset += rodata_section.size();
                 sections.push(rodata_section);
             }
+            if has_data {
+                let mut writable_section = SectionType::Data(writable_data_section);
+                writable_section.set_offset(current_offset);
+                current_offset += writable_section.size();
+                sections.push(writable_section);
+            }
+            if has_bss {
+                let mut bss = SectionType::Data(bss_section);
+                bss.set_offset(current_offset);
+                sections.push(bss);
+            }
         }
 
         let padding = (8 - (current_offset % 8)) % 8;
@@ -132,28 +212,45 @@ impl Program {
         if arch.is_v3() {
             // v3 programs are loaded entirely through program headers; the
             // loader never reads the section header table. Unless the program
-            // is built in debug mode, we omit section headers along with the
-            // .shstrtab and debug sections that exist only to support them,
-            // keeping v3 binaries minimal.
+            // is built in debug mode or has notes or data symbols, we omit
+            // section headers along with the .shstrtab and debug sections that
+            // exist only to support them, keeping v3 binaries minimal.
+            // Up to 3 headers: rodata (PF_R), data and bss (PF_R | PF_W),
+            // then bytecode (PF_X)
+            let mut headers = Vec::new();
             if has_rodata {
-                // 2 headers: rodata (PF_R) then bytecode (PF_X)
-                let rodata_offset = base_offset;
-                let bytecode_offset = base_offset + rodata_size;
-                program_headers = Some(vec![
-                    ProgramHeader::new_load(rodata_offset, rodata_size, false, arch),
-                    ProgramHeader::new_load(bytecode_offset, bytecode_size, true, arch),
-                ]);
-            } else {
-                // 1 header: bytecode only (PF_X)
-                program_headers = Some(vec![ProgramHeader::new_load(
+                headers.push(ProgramHeader::new_load(
                     base_offset,
-                    bytecode_size,
-                    true,
+                    rodata_size,
+                    false,
                     arch,
-                )]);
+                ));
             }
+            if has_data || has_bss {
+                headers.push(
+                    ProgramHeader::new_writable_load(
+                        base_offset + rodata_size,
+                        writable_size,
+                        ProgramHeader::V3_RODATA_VADDR + rodata_size,
+                    )
+                    .with_mem_size(writable_size + bss_size),
+                );
+            }
+            headers.push(ProgramHeader::new_load(
+                text_offset,
+                bytecode_size,
+                true,
+                arch,
+            ));
+            program_headers = Some(headers);
+
+            if has_debug_sections || has_notes || has_symbols {
+                sections.extend(Self::generate_note_sections(
+                    notes,
+                    &mut section_names,
+                    &mut current_offset,
+                ));
 
-            if has_debug_sections {
                 // If debug info is present, generate debug sections
                 let debug_sections = Self::generate_debug_sections(
                     debug_sections,
@@ -167,6 +264,14 @@ impl Program {
                     sections.push(debug_section);
                 }
 
+                let symbol_sections = Self::generate_symbol_sections(
+                    rodata_symbols,
+                    &sections,
+                    &mut section_names,
+                    &mut current_offset,
+                );
+                sections.extend(symbol_sections);
+
                 let mut shstrtab_section = SectionType::ShStrTab(ShStrTabSection::new(
                     (section_names
                         .iter()
@@ -315,33 +420,14 @@ impl Program {
                 dynamic_section.set_dynstr_size(dynstr_section.size());
             }
 
-            // Generate debug sections
-            let debug_sections = Self::generate_debug_sections(
-                debug_sections,
-                &debug_data,
-                text_offset,
-                &mut section_names,
-                &mut current_offset,
-            );
-
-            let mut shstrtab_section = SectionType::ShStrTab(ShStrTabSection::new(
-                (section_names
-                    .iter()
-                    .map(|name| name.len() + 1)
-                    .sum::<usize>()
-                    + 1) as u32,
-                section_names,
-            ));
-            shstrtab_section.set_offset(current_offset);
-            current_offset += shstrtab_section.size();
-
             program_headers = Some(vec![
                 ProgramHeader::new_load(
                     text_offset,
                     text_size,
                     true, // executable
                     arch,
-                ),
+                )
+                .with_mem_size(text_size + bss_size),
                 ProgramHeader::new_load(
                     dynsym_section.offset(),
                     dynsym_section.size() + dynstr_section.size() + rel_dyn_section.size(),
@@ -355,11 +441,44 @@ impl Program {
             sections.push(dynsym_section);
             sections.push(dynstr_section);
             sections.push(rel_dyn_section);
+            sections.extend(Self::generate_note_sections(
+                notes,
+                &mut section_names,
+                &mut current_offset,
+            ));
+
+            // Generate debug sections
+            let debug_sections = Self::generate_debug_sections(
+                debug_sections,
+                &debug_data,
+                text_offset,
+                &mut section_names,
+                &mut current_offset,
+            );
 
             for debug_section in debug_sections {
                 sections.push(debug_section);
             }
 
+            let symbol_sections = Self::generate_symbol_sections(
+                rodata_symbols,
+                &sections,
+                &mut section_names,
+                &mut current_offset,
+            );
+            sections.extend(symbol_sections);
+
+            let mut shstrtab_section = SectionType::ShStrTab(ShStrTabSection::new(
+                (section_names
+                    .iter()
+                    .map(|name| name.len() + 1)
+                    .sum::<usize>()
+                    + 1) as u32,
+                section_names,
+            ));
+            shstrtab_section.set_offset(current_offset);
+            current_offset += shstrtab_section.size();
+
             sections.push(shstrtab_section);
         } else {
             // Create a vector of section names
@@ -368,6 +487,12 @@ impl Program {
                 section_names.push(section.name().to_string());
             }
 
+            sections.extend(Self::generate_note_sections(
+                notes,
+                &mut section_names,
+                &mut current_offset,
+            ));
+
             // Generate debug sections
             let debug_sections = Self::generate_debug_sections(
                 debug_sections,
@@ -381,6 +506,14 @@ impl Program {
                 sections.push(debug_section);
             }
 
+            let symbol_sections = Self::generate_symbol_sections(
+                rodata_symbols,
+                &sections,
+                &mut section_names,
+                &mut current_offset,
+            );
+            sections.extend(symbol_sections);
+
             let mut shstrtab_section = ShStrTabSection::new(
                 section_names
                     .iter()
@@ -394,8 +527,8 @@ impl Program {
         }
 
         // Update section header offset in ELF header. v3 binaries carry no
-        // section header table unless debug info is present.
-        if !arch.is_v3() || has_debug_sections {
+        // section header table unless debug info, a note or a symbol is present.
+        if !arch.is_v3() || has_debug_sections || has_notes || has_symbols {
             let padding = (8 - (current_offset % 8)) % 8;
             elf_header.e_shoff = current_offset + padding;
             elf_header.e_shnum = sections.len() as u16;
@@ -445,21 +578,93 @@ impl Program {
         section_names: &mut Vec<String>,
         current_offset: &mut u64,
     ) -> Vec<SectionType> {
+        #[cfg(feature = "dwarf")]
         if let Some(data) = debug_data {
-            debug::generate_debug_sections(data, text_offset, section_names, current_offset)
-                .into_iter()
-                .enumerate()
-                .map(|(i, s)| match i {
-                    0 => SectionType::DebugAbbrev(s),
-                    1 => SectionType::DebugInfo(s),
-                    2 => SectionType::DebugLine(s),
-                    3 => SectionType::DebugLineStr(s),
-                    _ => unreachable!(),
-                })
-                .collect()
-        } else {
-            reuse_debug_sections(parsed_debug_sections, section_names, current_offset)
+            return crate::debug::generate_debug_sections(
+                data,
+                text_offset,
+                section_names,
+                current_offset,
+            )
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| match i {
+                0 => SectionType::DebugAbbrev(s),
+                1 => SectionType::DebugInfo(s),
+                2 => SectionType::DebugLine(s),
+                3 => SectionType::DebugLineStr(s),
+                _ => unreachable!(),
+            })
+            .collect();
         }
+        #[cfg(not(feature = "dwarf"))]
+        let _ = (debug_data, text_offset);
+        reuse_debug_sections(parsed_debug_sections, section_names, current_offset)
+    }
+
+    fn generate_note_sections(
+        notes: Vec<(&'static str, Vec<u8>)>,
+        section_names: &mut Vec<String>,
+        current_offset: &mut u64,
+    ) -> Vec<SectionType> {
+        notes
+            .into_iter()
+            .map(|(name, bytes)| {
+                let mut note_section =
+                    NoteSection::new(name, calc_name_offset(section_names), bytes);
+                section_names.push(name.to_string());
+                note_section.set_offset(*current_offset);
+                *current_offset += note_section.size();
+                SectionType::Note(note_section)
+            })
+            .collect()
+    }
+
+    /// `.symtab` and `.strtab` naming each `.rodata` label, sized to the data
+    /// it labels. They follow `sections`, which must already hold `.rodata`.
+    fn generate_symbol_sections(
+        symbols: Vec<(String, u64, u64)>,
+        sections: &[SectionType],
+        section_names: &mut Vec<String>,
+        current_offset: &mut u64,
+    ) -> Vec<SectionType> {
+        let Some((rodata_index, SectionType::Data(rodata))) = sections
+            .iter()
+            .enumerate()
+            .find(|(_, section)| section.name() == ".rodata")
+        else {
+            return Vec::new();
+        };
+
+        let mut symbol_names = Vec::new();
+        let mut symtab_entries = Vec::new();
+        let mut str_offset = 1;
+        for (name, offset, size) in symbols {
+            symtab_entries.push(DynamicSymbol::new(
+                str_offset as u32,
+                0x01, // STB_LOCAL | STT_OBJECT
+                0,
+                rodata_index as u16,
+                rodata.get_vaddr() + offset,
+                size,
+            ));
+            str_offset += name.len() + 1;
+            symbol_names.push(name);
+        }
+
+        let mut symtab = SymTabSection::new(calc_name_offset(section_names), symtab_entries);
+        section_names.push(symtab.name().to_string());
+        // .strtab comes right after .symtab
+        symtab.set_link(sections.len() as u32 + 1);
+        symtab.set_offset(*current_offset);
+        *current_offset += symtab.size();
+
+        let mut strtab = StrTabSection::new(calc_name_offset(section_names), symbol_names);
+        section_names.push(strtab.name().to_string());
+        strtab.set_offset(*current_offset);
+        *current_offset += strtab.size();
+
+        vec![SectionType::SymTab(symtab), SectionType::StrTab(strtab)]
     }
 
     pub fn has_rodata(&self) -> bool {
@@ -513,7 +718,7 @@ mod tests {
         let source = "exit";
         for arch in [SbpfArch::V0, SbpfArch::V3] {
             let parse_result = parse(source, arch).unwrap();
-            let program = Program::from_parse_result(parse_result, None);
+            let program = Program::from_parse_result(parse_result, None, None);
 
             // Verify basic structure
             assert!(!program.sections.is_empty());
@@ -526,7 +731,7 @@ mod tests {
         let source = "exit";
         for arch in [SbpfArch::V0, SbpfArch::V3] {
             let parse_result = parse(source, arch).unwrap();
-            let program = Program::from_parse_result(parse_result, None);
+            let program = Program::from_parse_result(parse_result, None, None);
 
             assert!(!program.has_rodata());
         }
@@ -537,7 +742,7 @@ mod tests {
         let source = "exit";
         for arch in [SbpfArch::V0, SbpfArch::V3] {
             let parse_result = parse(source, arch).unwrap();
-            let program = Program::from_parse_result(parse_result, None);
+            let program = Program::from_parse_result(parse_result, None, None);
 
             let bytecode = program.emit_bytecode();
             assert!(!bytecode.is_empty());
@@ -553,7 +758,7 @@ mod tests {
         let mut parse_result = parse(source, SbpfArch::V0).unwrap();
         parse_result.prog_is_static = true;
 
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
         assert!(program.program_headers.is_none());
         assert_eq!(program.elf_header.e_phnum, 0);
     }
@@ -563,7 +768,7 @@ mod tests {
         let source = r"exit";
         for arch in [SbpfArch::V0, SbpfArch::V3] {
             let parse_result = parse(source, arch).unwrap();
-            let program = Program::from_parse_result(parse_result, None);
+            let program = Program::from_parse_result(parse_result, None, None);
 
             // First section should be null
             assert_eq!(program.sections[0].name(), "");
@@ -572,6 +777,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "dwarf")]
     #[test]
     fn test_program_sections_debug() {
         let source = "exit";
@@ -580,12 +786,13 @@ mod tests {
             let debug_data = Some(DebugData {
                 filename: "test.s".to_string(),
                 directory: "/test".to_string(),
+                files: vec![],
                 lines: vec![],
                 labels: vec![],
                 code_start: 0,
                 code_end: 8,
             });
-            let program = Program::from_parse_result(parse_result, debug_data);
+            let program = Program::from_parse_result(parse_result, debug_data, None);
 
             let debug_section_names: Vec<&str> = program
                 .sections
@@ -605,7 +812,7 @@ mod tests {
     fn test_v3_e_flags() {
         let source = "exit";
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
         assert_eq!(program.elf_header.e_flags, 3);
     }
 
@@ -613,7 +820,7 @@ mod tests {
     fn test_v3_no_rodata_one_header() {
         let source = "exit";
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
 
         let headers = program.program_headers.as_ref().unwrap();
         assert_eq!(headers.len(), 1);
@@ -632,7 +839,7 @@ entrypoint:
     exit
         "#;
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
 
         let headers = program.program_headers.as_ref().unwrap();
         assert_eq!(headers.len(), 2);
@@ -652,7 +859,7 @@ entrypoint:
     exit
         "#;
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
 
         // v3: e_entry must be >= V3_BYTECODE_VADDR (1 << 32)
         assert!(program.elf_header.e_entry >= ProgramHeader::V3_BYTECODE_VADDR,);
@@ -669,7 +876,7 @@ entrypoint:
     exit
         "#;
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
 
         let headers = program.program_headers.as_ref().unwrap();
         let expected_first_offset = 64 + (program.elf_header.e_phnum as u64) * 56;
@@ -680,6 +887,50 @@ entrypoint:
         );
     }
 
+    #[test]
+    fn test_rodata_labels_get_sized_symbols() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+    exit
+.rodata
+msg: .ascii "hello"
+    .byte 0
+fee: .quad 5
+        "#;
+        for arch in [SbpfArch::V0, SbpfArch::V3] {
+            let program = Program::from_parse_result(parse(source, arch).unwrap(), None, None);
+            let names: Vec<&str> = program.sections.iter().map(|s| s.name()).collect();
+            let symtab = names.iter().position(|n| *n == ".symtab").unwrap();
+            assert_eq!(names[symtab + 1], ".strtab");
+            assert_eq!(names.last(), Some(&".s"));
+            assert_ne!(program.elf_header.e_shoff, 0);
+
+            let SectionType::SymTab(symtab) = &program.sections[symtab] else {
+                unreachable!()
+            };
+            let rodata_vaddr = if arch.is_v3() {
+                ProgramHeader::V3_RODATA_VADDR
+            } else {
+                program.sections[names.iter().position(|n| *n == ".rodata").unwrap()].offset()
+            };
+            let bytes = symtab.bytecode();
+            assert_eq!(bytes.len(), 3 * 24);
+            let entry = |i: usize, field: std::ops::Range<usize>| {
+                u64::from_le_bytes(
+                    bytes[i * 24 + field.start..i * 24 + field.end]
+                        .try_into()
+                        .unwrap(),
+                )
+            };
+            // msg: 6 bytes including its unlabeled continuation
+            assert_eq!(entry(1, 8..16), rodata_vaddr);
+            assert_eq!(entry(1, 16..24), 6);
+            assert_eq!(entry(2, 8..16), rodata_vaddr + 6);
+            assert_eq!(entry(2, 16..24), 8);
+        }
+    }
+
     #[test]
     fn test_v3_no_dynamic_sections() {
         let source = r#"
@@ -689,7 +940,7 @@ entrypoint:
     exit
         "#;
         let parse_result = parse(source, SbpfArch::V3).unwrap();
-        let program = Program::from_parse_result(parse_result, None);
+        let program = Program::from_parse_result(parse_result, None, None);
 
         // v3 should not have any dynamic sections
         let section_names: Vec<&str> = program.sections.iter().map(|s| s.name()).collect();
@@ -0,0 +1,424 @@
+use {
+    crate::{
+        CompileError,
+        ast::AST,
+        astnode::{ASTNode, Label},
+        optimizer,
+    },
+    either::Either,
+    sbpf_common::{instruction::Instruction, opcode::Opcode},
+    std::{
+        collections::{HashMap, HashSet},
+        ops::Range,
+    },
+};
+
+/// Farthest a jump reaches, in instructions, either way.
+const JUMP_RANGE: i64 = i16::MAX as i64;
+
+/// Room left when placing an island for the instructions inserted along
+/// with it, which move the jump further away when the island is behind it.
+const ISLAND_MARGIN: i64 = 8;
+
+/// Give up after this many rewrites; every one brings a jump closer to its
+/// target, so only a pathological program gets here.
+const MAX_REWRITES: usize = 1 << 16;
+
+const RELAX_LABEL_PREFIX: &str = "temp_relax_";
+
+#[derive(Default)]
+pub(crate) struct Relaxation {
+    /// Labels inserted for the rewritten jumps, to remove once they are
+    /// resolved.
+    pub(crate) labels_to_remove: HashSet<String>,
+    pub(crate) errors: Vec<CompileError>,
+}
+
+/// Rewrite jumps whose target is further than an `i16` offset reaches.
+///
+/// A conditional jump becomes the inverted condition skipping over a `ja` to
+/// the target (or, for `jset`, which has no inverse, the condition jumping to
+/// that `ja` and a `ja` past it). A `ja` that is still out of range is sent to
+/// an island: a `ja` to the target placed as far towards it as the jump
+/// reaches, behind a `ja` over it unless the code before already ends in
+/// `exit` or `ja`. Islands chain until the target is reached. Offsets are
+/// recomputed after every rewrite.
+pub(crate) fn relax_branches(ast: &mut AST) -> Relaxation {
+    if out_of_range_jump(ast).is_none() {
+        return Relaxation::default();
+    }
+
+    // Relative offsets would go stale as instructions are inserted, so
+    // every jump gets a label first.
+    let canonicalized = optimizer::canonicalize_control_flow_targets(&mut ast.nodes);
+    let mut relaxation = Relaxation {
+        labels_to_remove: canonicalized.labels_to_remove,
+        errors: canonicalized.errors,
+    };
+    if !relaxation.errors.is_empty() {
+        return relaxation;
+    }
+
+    for _ in 0..MAX_REWRITES {
+        optimizer::assign_offsets(ast);
+        let Some(idx) = out_of_range_jump(ast) else {
+            return relaxation;
+        };
+        if let Err(error) = relax(ast, idx, &mut relaxation.labels_to_remove) {
+            relaxation.errors.push(error);
+            return relaxation;
+        }
+    }
+
+    optimizer::assign_offsets(ast);
+    if let Some(idx) = out_of_range_jump(ast)
+        && let ASTNode::Instruction { instruction, .. } = &ast.nodes[idx]
+    {
+        relaxation.errors.push(out_of_range_error(instruction));
+    }
+    relaxation
+}
+
+/// Index of the first jump whose target is out of reach.
+fn out_of_range_jump(ast: &AST) -> Option<usize> {
+    let mut labels = HashMap::new();
+    let mut numeric_labels = Vec::new();
+    for (idx, node) in ast.nodes.iter().enumerate() {
+        if let ASTNode::Label { label, offset } = node {
+            labels.insert(label.name.as_str(), *offset);
+            numeric_labels.push((label.name.clone(), *offset, idx));
+        }
+    }
+
+    ast.nodes.iter().enumerate().position(|(idx, node)| {
+        let ASTNode::Instruction {
+            instruction,
+            offset,
+        } = node
+        else {
+            return false;
+        };
+        let Some(Either::Left(target)) = &instruction.off else {
+            return false;
+        };
+        if !instruction.is_jump() {
+            return false;
+        }
+        let target = labels
+            .get(target.as_str())
+            .copied()
+            .or_else(|| AST::resolve_numeric_label(target, idx, &numeric_labels));
+        target.is_some_and(|target| !in_range(*offset, target, 0))
+    })
+}
+
+/// Whether a jump at `from` reaches `to` with `margin` instructions to spare.
+fn in_range(from: u64, to: u64, margin: i64) -> bool {
+    let distance = (to as i64 - from as i64) / 8 - 1;
+    (-JUMP_RANGE - 1 + margin..=JUMP_RANGE - margin).contains(&distance)
+}
+
+fn relax(ast: &mut AST, idx: usize, labels: &mut HashSet<String>) -> Result<(), CompileError> {
+    let ASTNode::Instruction { instruction, .. } = &ast.nodes[idx] else {
+        return Ok(());
+    };
+    let instruction = instruction.clone();
+    let Some(Either::Left(target)) = &instruction.off else {
+        return Ok(());
+    };
+
+    if instruction.opcode == Opcode::Ja {
+        return place_island(ast, idx, &instruction, labels);
+    }
+
+    let span = &instruction.span;
+    let skip = fresh_label(ast, labels);
+    let replacement = match inverse(instruction.opcode) {
+        Some(opcode) => vec![
+            jump(
+                Instruction {
+                    opcode,
+                    ..instruction.clone()
+                },
+                &skip,
+            ),
+            ja(target, span),
+            label(&skip),
+        ],
+        None => {
+            let take = fresh_label(ast, labels);
+            vec![
+                jump(instruction.clone(), &take),
+                ja(&skip, span),
+                label(&take),
+                ja(target, span),
+                label(&skip),
+            ]
+        }
+    };
+    ast.nodes.splice(idx..=idx, replacement);
+    Ok(())
+}
+
+/// Send the out-of-range `ja` at `idx` to a new `ja` to its target, placed as
+/// close to the target as the jump reaches.
+fn place_island(
+    ast: &mut AST,
+    idx: usize,
+    instruction: &Instruction,
+    labels: &mut HashSet<String>,
+) -> Result<(), CompileError> {
+    let Some(Either::Left(target)) = &instruction.off else {
+        return Ok(());
+    };
+    let Some(target_offset) = ast.nodes.iter().find_map(|node| match node {
+        ASTNode::Label { label, offset } if label.name == *target => Some(*offset),
+        _ => None,
+    }) else {
+        return Err(out_of_range_error(instruction));
+    };
+
+    // Offset of each node, and whether the code before it falls through.
+    let mut boundaries = Vec::with_capacity(ast.nodes.len());
+    let mut cursor = 0;
+    let mut falls_through = false;
+    for node in &ast.nodes {
+        boundaries.push((cursor, falls_through));
+        if let ASTNode::Instruction { instruction, .. } = node {
+            cursor += instruction.get_size();
+            falls_through = !matches!(instruction.opcode, Opcode::Exit | Opcode::Ja);
+        }
+    }
+    let from = boundaries[idx].0;
+    let forward = target_offset > from;
+
+    // The farthest node boundary towards the target still in reach, moved
+    // back over any labels so they keep pointing at their instruction.
+    let reachable = |k: &usize| {
+        let (offset, _) = boundaries[*k];
+        offset != from && in_range(from, offset, ISLAND_MARGIN)
+    };
+    let k = if forward {
+        (idx + 1..ast.nodes.len()).rev().find(reachable)
+    } else {
+        (0..idx).find(reachable)
+    };
+    let Some(mut k) = k else {
+        return Err(out_of_range_error(instruction));
+    };
+    while k > 0 && matches!(ast.nodes[k - 1], ASTNode::Label { .. }) {
+        k -= 1;
+    }
+
+    let span = &instruction.span;
+    let island = fresh_label(ast, labels);
+    let mut inserted = Vec::new();
+    if boundaries[k].1 {
+        let after = fresh_label(ast, labels);
+        inserted.push(ja(&after, span));
+        inserted.push(label(&island));
+        inserted.push(ja(target, span));
+        inserted.push(label(&after));
+    } else {
+        inserted.push(label(&island));
+        inserted.push(ja(target, span));
+    }
+
+    let jump_idx = if k <= idx { idx + inserted.len() } else { idx };
+    ast.nodes.splice(k..k, inserted);
+    if let ASTNode::Instruction { instruction, .. } = &mut ast.nodes[jump_idx] {
+        instruction.off = Some(Either::Left(island));
+    }
+    Ok(())
+}
+
+/// The jump taken exactly when `opcode`'s is not, if there is one.
+fn inverse(opcode: Opcode) -> Option<Opcode> {
+    use Opcode::*;
+    let pairs = [
+        (JeqImm, JneImm),
+        (JgtImm, JleImm),
+        (JgeImm, JltImm),
+        (JsgtImm, JsleImm),
+        (JsgeImm, JsltImm),
+        (JeqReg, JneReg),
+        (JgtReg, JleReg),
+        (JgeReg, JltReg),
+        (JsgtReg, JsleReg),
+        (JsgeReg, JsltReg),
+        (Jeq32Imm, Jne32Imm),
+        (Jgt32Imm, Jle32Imm),
+        (Jge32Imm, Jlt32Imm),
+        (Jsgt32Imm, Jsle32Imm),
+        (Jsge32Imm, Jslt32Imm),
+        (Jeq32Reg, Jne32Reg),
+        (Jgt32Reg, Jle32Reg),
+        (Jge32Reg, Jlt32Reg),
+        (Jsgt32Reg, Jsle32Reg),
+        (Jsge32Reg, Jslt32Reg),
+    ];
+    pairs.into_iter().find_map(|(a, b)| match opcode {
+        _ if opcode == a => Some(b),
+        _ if opcode == b => Some(a),
+        _ => None,
+    })
+}
+
+fn fresh_label(ast: &AST, labels: &mut HashSet<String>) -> String {
+    let existing: HashSet<&str> = ast
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Label { label, .. } => Some(label.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut n = labels.len();
+    let name = loop {
+        let name = format!("{RELAX_LABEL_PREFIX}{n}");
+        if !existing.contains(name.as_str()) && !labels.contains(&name) {
+            break name;
+        }
+        n += 1;
+    };
+    labels.insert(name.clone());
+    name
+}
+
+fn jump(instruction: Instruction, target: &str) -> ASTNode {
+    ASTNode::Instruction {
+        instruction: Instruction {
+            off: Some(Either::Left(target.to_string())),
+            ..instruction
+        },
+        offset: 0,
+    }
+}
+
+fn ja(target: &str, span: &Range<usize>) -> ASTNode {
+    jump(
+        Instruction {
+            opcode: Opcode::Ja,
+            dst: None,
+            src: None,
+            off: None,
+            imm: None,
+            span: span.clone(),
+        },
+        target,
+    )
+}
+
+fn label(name: &str) -> ASTNode {
+    ASTNode::Label {
+        label: Label {
+            name: name.to_string(),
+            span: 0..0,
+        },
+        offset: 0,
+    }
+}
+
+fn out_of_range_error(instruction: &Instruction) -> CompileError {
+    let target = match &instruction.off {
+        Some(Either::Left(target)) => target.clone(),
+        _ => String::new(),
+    };
+    CompileError::JumpOutOfRange {
+        label: target,
+        span: instruction.span.clone(),
+        custom_label: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SbpfArch, ast::OptimizationConfig, parser::parse_with_optimization};
+
+    /// Bytecode of `source`'s `.text`, one 8-byte slot per entry.
+    fn text(source: &str) -> Vec<[u8; 8]> {
+        let layout =
+            parse_with_optimization(source, SbpfArch::V3, OptimizationConfig::disabled()).unwrap();
+        layout
+            .code_section
+            .get_nodes()
+            .iter()
+            .flat_map(|node| node.bytecode().unwrap_or_default())
+            .collect::<Vec<u8>>()
+            .chunks(8)
+            .map(|slot| slot.try_into().unwrap())
+            .collect()
+    }
+
+    /// Follow taken jumps from `pc` until an instruction that is not one.
+    fn follow(text: &[[u8; 8]], mut pc: usize) -> usize {
+        while text[pc][0] & 0x07 == 0x05 && text[pc][0] != 0x85 && text[pc][0] != 0x95 {
+            let off = i16::from_le_bytes([text[pc][2], text[pc][3]]);
+            pc = (pc as i64 + 1 + off as i64) as usize;
+        }
+        pc
+    }
+
+    fn filler(count: usize) -> String {
+        "  mov64 r0, 0\n".repeat(count)
+    }
+
+    #[test]
+    fn test_in_range_jumps_are_untouched() {
+        let source = format!(
+            ".globl entrypoint\nentrypoint:\n  jeq r1, 0, far\n{}far:\n  exit\n",
+            filler(32767)
+        );
+        let text = text(&source);
+        assert_eq!(text.len(), 32769);
+        assert_eq!(text[0][0], 0x15);
+        assert_eq!(i16::from_le_bytes([text[0][2], text[0][3]]), i16::MAX);
+    }
+
+    #[test]
+    fn test_relaxes_conditional_jump() {
+        let source = format!(
+            ".globl entrypoint\nentrypoint:\n  jeq r1, 0, far\n  jset r1, 1, far\n{}far:\n  exit\n",
+            filler(40000)
+        );
+        let text = text(&source);
+        let exit = text.len() - 1;
+        assert_eq!(text[exit][0], 0x95);
+
+        // jne r1, 0, +1; ja far
+        assert_eq!(text[0][0], 0x55);
+        assert_eq!(i16::from_le_bytes([text[0][2], text[0][3]]), 1);
+        assert_eq!(follow(&text, 1), exit);
+        // jset r1, 1, +1; ja +1; ja far
+        assert_eq!(text[2][0], 0x45);
+        assert_eq!(follow(&text, 2), exit);
+        assert_eq!(follow(&text, 3), 5);
+        assert_eq!(text[5][0], 0xb7);
+    }
+
+    #[test]
+    fn test_chains_islands_for_long_jumps() {
+        let source = format!(
+            ".globl entrypoint\nentrypoint:\n  ja far\nback:\n  exit\n{}far:\n  mov64 r0, 1\n  ja back\n",
+            filler(70000)
+        );
+        let text = text(&source);
+        let far = text.len() - 2;
+        assert_eq!(text[far][0], 0xb7);
+        assert!(text.len() > 70004);
+        assert_eq!(follow(&text, 0), far);
+        assert_eq!(follow(&text, far + 1), 1);
+
+        // Running through the filler executes every mov and no island.
+        let (mut pc, mut movs) = (2, 0);
+        while pc != far {
+            if text[pc][0] == 0xb7 {
+                movs += 1;
+                pc += 1;
+            } else {
+                pc = follow(&text, pc);
+            }
+        }
+        assert_eq!(movs, 70000);
+    }
+}
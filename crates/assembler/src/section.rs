@@ -1,4 +1,5 @@
 use crate::{
+    SbpfArch,
     astnode::{ASTNode, ROData},
     dynsym::{DynamicSymbol, RelDyn},
     header::SectionHeader,
@@ -33,6 +34,7 @@ pub struct CodeSection {
     size: u64,
     offset: u64,
     vaddr: u64,
+    arch: SbpfArch,
 }
 
 impl CodeSection {
@@ -43,9 +45,16 @@ impl CodeSection {
             size,
             offset: 0,
             vaddr: 0,
+            arch: SbpfArch::default(),
         }
     }
 
+    /// Encode instructions for `arch`.
+    pub fn with_arch(mut self, arch: SbpfArch) -> Self {
+        self.arch = arch;
+        self
+    }
+
     pub fn get_nodes(&self) -> &Vec<ASTNode> {
         &self.nodes
     }
@@ -89,7 +98,13 @@ impl Section for CodeSection {
     fn bytecode(&self) -> Vec<u8> {
         let mut bytecode = Vec::new();
         for node in &self.nodes {
-            if let Some(node_bytes) = node.bytecode() {
+            if let ASTNode::Instruction { instruction, .. } = node {
+                bytecode.extend(
+                    instruction
+                        .to_bytes_for(self.arch)
+                        .expect("instructions are checked to encode before emitting"),
+                );
+            } else if let Some(node_bytes) = node.bytecode() {
                 bytecode.extend(node_bytes);
             }
         }
@@ -105,6 +120,10 @@ impl Section for CodeSection {
 #[derive(Debug)]
 pub struct DataSection {
     name: String,
+    name_offset: u32,
+    writable: bool,
+    /// Reserved memory with no bytes in the file, for `.bss`.
+    zero_fill: bool,
     nodes: Vec<ASTNode>,
     size: u64,
     offset: u64,
@@ -115,6 +134,9 @@ impl DataSection {
     pub fn new(nodes: Vec<ASTNode>, size: u64) -> Self {
         Self {
             name: String::from(".rodata"),
+            name_offset: 7,
+            writable: false,
+            zero_fill: false,
             nodes,
             size,
             offset: 0,
@@ -122,6 +144,36 @@ impl DataSection {
         }
     }
 
+    /// The writable `.data` section. The Solana loader rejects it in v0
+    /// programs too, so it only runs in `sbpf-vm`.
+    pub fn writable(nodes: Vec<ASTNode>, size: u64) -> Self {
+        Self {
+            name: String::from(".data"),
+            writable: true,
+            ..Self::new(nodes, size)
+        }
+    }
+
+    /// The zero-initialized `.bss` section. Its `size()` in the file is
+    /// zero; `get_size()` is the memory it reserves. Like `.data`, only
+    /// `sbpf-vm` loads it.
+    pub fn bss(nodes: Vec<ASTNode>, size: u64) -> Self {
+        Self {
+            name: String::from(".bss"),
+            writable: true,
+            zero_fill: true,
+            ..Self::new(nodes, size)
+        }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    pub fn set_name_offset(&mut self, name_offset: u32) {
+        self.name_offset = name_offset;
+    }
+
     pub fn get_nodes(&self) -> &Vec<ASTNode> {
         &self.nodes
     }
@@ -130,6 +182,10 @@ impl DataSection {
         self.size
     }
 
+    pub fn get_vaddr(&self) -> u64 {
+        self.vaddr
+    }
+
     pub fn set_offset(&mut self, offset: u64) {
         self.offset = offset;
         self.vaddr = offset;
@@ -146,6 +202,7 @@ impl DataSection {
                 rodata: ROData { name, args, .. },
                 offset,
             } = node
+                && !name.is_empty()
                 && let Some(Token::StringLiteral(str_literal, _)) = args.get(1)
             {
                 ro_data_labels.push((name.clone(), *offset as usize, str_literal.clone()));
@@ -154,11 +211,37 @@ impl DataSection {
         ro_data_labels
     }
 
+    /// Each label with the offset and size of the data it names, which runs
+    /// until the next label.
+    pub fn symbols(&self) -> Vec<(String, u64, u64)> {
+        let mut symbols: Vec<(String, u64, u64)> = Vec::new();
+        for node in &self.nodes {
+            if let ASTNode::ROData { rodata, offset } = node {
+                if !rodata.is_unlabeled() {
+                    symbols.push((rodata.name.clone(), *offset, 0));
+                }
+                if let Some((_, _, size)) = symbols.last_mut() {
+                    *size += rodata.get_size();
+                }
+            }
+        }
+        symbols
+    }
+
     pub fn section_header_bytecode(&self) -> Vec<u8> {
-        let flags = SectionHeader::SHF_ALLOC; // Read-only data
+        let flags = if self.writable {
+            SectionHeader::SHF_ALLOC | SectionHeader::SHF_WRITE
+        } else {
+            SectionHeader::SHF_ALLOC // Read-only data
+        };
+        let section_type = if self.zero_fill {
+            SectionHeader::SHT_NOBITS
+        } else {
+            SectionHeader::SHT_PROGBITS
+        };
         SectionHeader::new(
-            7,
-            SectionHeader::SHT_PROGBITS,
+            self.name_offset,
+            section_type,
             flags,
             self.vaddr,
             self.offset,
@@ -178,12 +261,18 @@ impl Section for DataSection {
     }
 
     fn size(&self) -> u64 {
+        if self.zero_fill {
+            return 0;
+        }
         // Return 8-byte aligned size
         (self.size + 7) & !7
     }
 
     fn bytecode(&self) -> Vec<u8> {
         let mut bytecode = Vec::new();
+        if self.zero_fill {
+            return bytecode;
+        }
         for node in &self.nodes {
             if let Some(node_bytes) = node.bytecode() {
                 bytecode.extend(node_bytes);
@@ -585,6 +674,140 @@ impl Section for DynSymSection {
     }
 }
 
+/// Non-allocated `.symtab`, naming the data the program defines so that
+/// tools like `sbpf patch` can find it. The loader never reads it.
+#[derive(Debug)]
+pub struct SymTabSection {
+    name: String,
+    name_offset: u32,
+    offset: u64,
+    link: u32,
+    symbols: Vec<DynamicSymbol>,
+}
+
+impl SymTabSection {
+    pub fn new(name_offset: u32, symbols: Vec<DynamicSymbol>) -> Self {
+        Self {
+            name: String::from(".symtab"),
+            name_offset,
+            offset: 0,
+            link: 0,
+            symbols,
+        }
+    }
+
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    pub fn set_link(&mut self, link: u32) {
+        self.link = link;
+    }
+
+    pub fn section_header_bytecode(&self) -> Vec<u8> {
+        // All symbols are local, so the first global one would follow them.
+        SectionHeader::new(
+            self.name_offset,
+            SectionHeader::SHT_SYMTAB,
+            0,
+            0,
+            self.offset,
+            self.size(),
+            self.link,
+            self.symbols.len() as u32 + 1,
+            8,
+            24,
+        )
+        .bytecode()
+    }
+}
+
+impl Section for SymTabSection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> u64 {
+        // The null symbol, then 24 bytes per symbol
+        (self.symbols.len() as u64 + 1) * 24
+    }
+
+    fn bytecode(&self) -> Vec<u8> {
+        let mut bytes = vec![0; 24];
+        for symbol in &self.symbols {
+            bytes.extend(symbol.bytecode());
+        }
+        bytes
+    }
+}
+
+/// The `.strtab` holding the names of `.symtab` symbols.
+#[derive(Debug)]
+pub struct StrTabSection {
+    name: String,
+    name_offset: u32,
+    symbol_names: Vec<String>,
+    offset: u64,
+}
+
+impl StrTabSection {
+    pub fn new(name_offset: u32, symbol_names: Vec<String>) -> Self {
+        Self {
+            name: String::from(".strtab"),
+            name_offset,
+            symbol_names,
+            offset: 0,
+        }
+    }
+
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    pub fn section_header_bytecode(&self) -> Vec<u8> {
+        SectionHeader::new(
+            self.name_offset,
+            SectionHeader::SHT_STRTAB,
+            0,
+            0,
+            self.offset,
+            self.size(),
+            0,
+            0,
+            1,
+            0,
+        )
+        .bytecode()
+    }
+}
+
+impl Section for StrTabSection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bytecode(&self) -> Vec<u8> {
+        let mut bytes = vec![0];
+        for name in &self.symbol_names {
+            bytes.extend(name.as_bytes());
+            bytes.push(0);
+        }
+        while bytes.len() % 8 != 0 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn size(&self) -> u64 {
+        (1 + self
+            .symbol_names
+            .iter()
+            .map(|name| name.len() + 1)
+            .sum::<usize>())
+        .next_multiple_of(8) as u64
+    }
+}
+
 #[derive(Debug)]
 pub struct RelDynSection {
     name: String,
@@ -721,6 +944,64 @@ impl DebugSection {
     }
 }
 
+/// Non-allocated note section carrying build provenance or custom errors.
+#[derive(Debug)]
+pub struct NoteSection {
+    name: String,
+    name_offset: u32,
+    data: Vec<u8>,
+    offset: u64,
+}
+
+impl NoteSection {
+    pub fn new(name: &str, name_offset: u32, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            name_offset,
+            data,
+            offset: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u64 {
+        self.data.len().next_multiple_of(8) as u64
+    }
+
+    pub fn bytecode(&self) -> Vec<u8> {
+        let mut bytes = self.data.clone();
+        bytes.resize(self.size() as usize, 0);
+        bytes
+    }
+
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn section_header_bytecode(&self) -> Vec<u8> {
+        SectionHeader::new(
+            self.name_offset,
+            SectionHeader::SHT_NOTE,
+            0,
+            0,
+            self.offset,
+            self.data.len() as u64,
+            0,
+            0,
+            4,
+            0,
+        )
+        .bytecode()
+    }
+}
+
 #[derive(Debug)]
 pub enum SectionType {
     Code(CodeSection),
@@ -729,8 +1010,11 @@ pub enum SectionType {
     Dynamic(DynamicSection),
     DynStr(DynStrSection),
     DynSym(DynSymSection),
+    SymTab(SymTabSection),
+    StrTab(StrTabSection),
     Default(NullSection),
     RelDyn(RelDynSection),
+    Note(NoteSection),
     DebugAbbrev(DebugSection),
     DebugInfo(DebugSection),
     DebugLine(DebugSection),
@@ -750,8 +1034,11 @@ impl SectionType {
             SectionType::Dynamic(ds) => &ds.name,
             SectionType::DynStr(ds) => &ds.name,
             SectionType::DynSym(ds) => &ds.name,
+            SectionType::SymTab(ss) => &ss.name,
+            SectionType::StrTab(ss) => &ss.name,
             SectionType::Default(ds) => &ds.name,
             SectionType::RelDyn(ds) => &ds.name,
+            SectionType::Note(ns) => ns.name(),
             SectionType::DebugAbbrev(ds) => ds.name(),
             SectionType::DebugInfo(ds) => ds.name(),
             SectionType::DebugLine(ds) => ds.name(),
@@ -771,8 +1058,11 @@ impl SectionType {
             SectionType::Dynamic(ds) => ds.bytecode(),
             SectionType::DynStr(ds) => ds.bytecode(),
             SectionType::DynSym(ds) => ds.bytecode(),
+            SectionType::SymTab(ss) => ss.bytecode(),
+            SectionType::StrTab(ss) => ss.bytecode(),
             SectionType::Default(ds) => ds.bytecode(),
             SectionType::RelDyn(ds) => ds.bytecode(),
+            SectionType::Note(ns) => ns.bytecode(),
             SectionType::DebugAbbrev(ds) => ds.bytecode(),
             SectionType::DebugInfo(ds) => ds.bytecode(),
             SectionType::DebugLine(ds) => ds.bytecode(),
@@ -792,8 +1082,11 @@ impl SectionType {
             SectionType::Dynamic(ds) => ds.size(),
             SectionType::DynStr(ds) => ds.size(),
             SectionType::DynSym(ds) => ds.size(),
+            SectionType::SymTab(ss) => ss.size(),
+            SectionType::StrTab(ss) => ss.size(),
             SectionType::Default(ds) => ds.size(),
             SectionType::RelDyn(ds) => ds.size(),
+            SectionType::Note(ns) => ns.size(),
             SectionType::DebugAbbrev(ds) => ds.size(),
             SectionType::DebugInfo(ds) => ds.size(),
             SectionType::DebugLine(ds) => ds.size(),
@@ -813,8 +1106,11 @@ impl SectionType {
             SectionType::Dynamic(ds) => ds.section_header_bytecode(),
             SectionType::DynStr(ds) => ds.section_header_bytecode(),
             SectionType::DynSym(ds) => ds.section_header_bytecode(),
+            SectionType::SymTab(ss) => ss.section_header_bytecode(),
+            SectionType::StrTab(ss) => ss.section_header_bytecode(),
             SectionType::Default(ds) => ds.section_header_bytecode(),
             SectionType::RelDyn(ds) => ds.section_header_bytecode(),
+            SectionType::Note(ns) => ns.section_header_bytecode(),
             SectionType::DebugAbbrev(ds) => ds.section_header_bytecode(),
             SectionType::DebugInfo(ds) => ds.section_header_bytecode(),
             SectionType::DebugLine(ds) => ds.section_header_bytecode(),
@@ -834,7 +1130,10 @@ impl SectionType {
             SectionType::Dynamic(ds) => ds.set_offset(offset),
             SectionType::DynStr(ds) => ds.set_offset(offset),
             SectionType::DynSym(ds) => ds.set_offset(offset),
+            SectionType::SymTab(ss) => ss.set_offset(offset),
+            SectionType::StrTab(ss) => ss.set_offset(offset),
             SectionType::RelDyn(ds) => ds.set_offset(offset),
+            SectionType::Note(ns) => ns.set_offset(offset),
             SectionType::Default(_) => (), // NullSection doesn't need offset
             SectionType::DebugAbbrev(ds) => ds.set_offset(offset),
             SectionType::DebugInfo(ds) => ds.set_offset(offset),
@@ -863,8 +1162,11 @@ impl SectionType {
             SectionType::Dynamic(ds) => ds.offset,
             SectionType::DynStr(ds) => ds.offset,
             SectionType::DynSym(ds) => ds.offset,
+            SectionType::SymTab(ss) => ss.offset,
+            SectionType::StrTab(ss) => ss.offset,
             SectionType::Default(ns) => ns.offset,
             SectionType::RelDyn(rs) => rs.offset,
+            SectionType::Note(ns) => ns.offset(),
             SectionType::DebugAbbrev(ds) => ds.offset(),
             SectionType::DebugInfo(ds) => ds.offset(),
             SectionType::DebugLine(ds) => ds.offset(),
@@ -941,6 +1243,16 @@ mod tests {
         assert_eq!(section.get_size(), 2);
     }
 
+    #[test]
+    fn test_data_section_writable() {
+        let section = DataSection::writable(Vec::new(), 8);
+        assert_eq!(section.name(), ".data");
+        assert!(section.is_writable());
+        // sh_flags: SHF_WRITE | SHF_ALLOC
+        assert_eq!(section.section_header_bytecode()[8], 0x3);
+        assert!(!DataSection::new(Vec::new(), 8).is_writable());
+    }
+
     #[test]
     fn test_data_section_rodata() {
         let rodata = ROData {
@@ -0,0 +1,105 @@
+use {
+    either::Either,
+    sbpf_common::{instruction::Instruction, opcode::Opcode, syscalls::REGISTERED_SYSCALLS},
+    std::{
+        fmt,
+        hash::{Hash, Hasher},
+        sync::{Arc, LazyLock},
+    },
+    syscall_map::{DynamicSyscallMap, murmur3_32},
+};
+
+/// The syscalls a program may `call` by name. Any other call target is
+/// resolved as a label.
+///
+/// Defaults to the syscalls registered with the Solana runtime. Cloning is
+/// cheap, so one registry can back any number of assemblers and threads.
+#[derive(Clone)]
+pub struct SyscallRegistry {
+    map: Arc<DynamicSyscallMap>,
+}
+
+impl SyscallRegistry {
+    /// A registry of exactly `names`. Fails if two names share a murmur3 hash,
+    /// since static syscalls are encoded by hash.
+    pub fn new<I, S>(names: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let map = DynamicSyscallMap::new(names.into_iter().map(Into::into).collect())?;
+        Ok(Self { map: Arc::new(map) })
+    }
+
+    /// This registry with `name` added, e.g. a syscall provided by a custom
+    /// runtime.
+    pub fn with_syscall(self, name: impl Into<String>) -> Result<Self, String> {
+        let mut map = DynamicSyscallMap::new(self.names().map(str::to_string).collect())?;
+        map.add(name.into())?;
+        Ok(Self { map: Arc::new(map) })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.get(murmur3_32(name)) == Some(name)
+    }
+
+    /// Registered names, ordered by hash.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.entries.iter().map(|(_, name)| name.as_str())
+    }
+
+    /// Whether `instruction` calls a registered syscall. Only meaningful before
+    /// call targets are resolved.
+    pub fn is_syscall(&self, instruction: &Instruction) -> bool {
+        instruction.opcode == Opcode::Call
+            && matches!(&instruction.imm, Some(Either::Left(name)) if self.contains(name))
+    }
+}
+
+static REGISTERED: LazyLock<SyscallRegistry> = LazyLock::new(|| {
+    SyscallRegistry::new(REGISTERED_SYSCALLS.iter().copied())
+        .expect("registered syscalls have distinct hashes")
+});
+
+impl Default for SyscallRegistry {
+    fn default() -> Self {
+        REGISTERED.clone()
+    }
+}
+
+impl Hash for SyscallRegistry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for name in self.names() {
+            name.hash(state);
+        }
+    }
+}
+
+impl fmt::Debug for SyscallRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_registry() {
+        let registry = SyscallRegistry::default();
+        assert!(registry.contains("sol_log_"));
+        assert!(!registry.contains("my_syscall"));
+        assert_eq!(registry.names().count(), REGISTERED_SYSCALLS.len());
+    }
+
+    #[test]
+    fn test_custom_syscalls() {
+        let registry = SyscallRegistry::default()
+            .with_syscall("my_syscall")
+            .unwrap();
+        assert!(registry.contains("my_syscall"));
+        assert!(registry.contains("sol_log_"));
+        assert!(SyscallRegistry::default().with_syscall("sol_log_").is_err());
+    }
+}
@@ -1,8 +1,8 @@
 use {
-    crate::{SbpfArch, parser, program::Program},
+    crate::{Assembler, AssemblerOption, MemoryProvider, SbpfArch},
     serde::Serialize,
-    serde_wasm_bindgen::to_value,
-    std::ops::Range,
+    serde_wasm_bindgen::{from_value, to_value},
+    std::{collections::HashMap, ops::Range},
     wasm_bindgen::prelude::*,
 };
 
@@ -11,6 +11,9 @@ struct CompileErrorInfo {
     error: String,
     line: String,
     col: String,
+    /// File the error is in, for multi-file assembly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
 }
 
 // Helper function to convert byte span to line/column numbers
@@ -37,14 +40,29 @@ fn span_to_line_col(source_code: &str, span: &Range<usize>) -> (usize, usize) {
 
 #[wasm_bindgen]
 pub fn assemble(source: &str, arch: u32) -> Result<Vec<u8>, JsValue> {
-    // TODO: Make this a bit less hacky
-    let arch = if arch == 0 {
-        SbpfArch::V0
-    } else {
-        SbpfArch::V3
-    };
-    let parse_result = match parser::parse(source, arch) {
-        Ok(result) => result,
+    let arch = SbpfArch::from_e_flags(arch).unwrap_or_default();
+    assemble_with(source, AssemblerOption::default().with_arch(arch))
+}
+
+/// Like `assemble`, with DWARF line info naming the source `filename`, so a
+/// stepping session can show which line runs next.
+#[cfg(feature = "dwarf")]
+#[wasm_bindgen]
+pub fn assemble_debug(source: &str, arch: u32, filename: &str) -> Result<Vec<u8>, JsValue> {
+    let arch = SbpfArch::from_e_flags(arch).unwrap_or_default();
+    let options = AssemblerOption::default()
+        .with_arch(arch)
+        .with_debug_mode(crate::DebugMode {
+            filename: filename.to_string(),
+            directory: String::new(),
+        });
+    assemble_with(source, options)
+}
+
+fn assemble_with(source: &str, options: AssemblerOption) -> Result<Vec<u8>, JsValue> {
+    let assembler = Assembler::new(options);
+    match assembler.assemble_full(source) {
+        Ok(artifacts) => Ok(artifacts.elf),
         Err(errors) => {
             let compile_errors: Vec<CompileErrorInfo> = errors
                 .iter()
@@ -54,13 +72,47 @@ pub fn assemble(source: &str, arch: u32) -> Result<Vec<u8>, JsValue> {
                         error: e.to_string(),
                         line: line.to_string(),
                         col: col.to_string(),
+                        file: None,
                     }
                 })
                 .collect();
-            return Err(to_value(&compile_errors).unwrap());
+            Err(to_value(&compile_errors).unwrap())
         }
-    };
-    let program = Program::from_parse_result(parse_result, None);
-    let bytecode = program.emit_bytecode();
-    Ok(bytecode)
+    }
+}
+
+/// Assemble `entry` out of `files`, an object mapping paths to source text.
+/// `.include` directives resolve between the files like they do on disk.
+#[wasm_bindgen]
+pub fn assemble_files(files: JsValue, entry: &str, arch: u32) -> Result<Vec<u8>, JsValue> {
+    let arch = SbpfArch::from_e_flags(arch).unwrap_or_default();
+    let files: HashMap<String, String> = from_value(files)?;
+    let mut provider = MemoryProvider::new();
+    for (path, source) in files {
+        provider.add_file(path, source);
+    }
+
+    let assembler = Assembler::new(AssemblerOption::default().with_arch(arch));
+    assembler
+        .assemble_file_from(&provider, entry.as_ref())
+        .map_err(|failure| {
+            let compile_errors: Vec<CompileErrorInfo> = failure
+                .errors
+                .iter()
+                .map(|e| CompileErrorInfo {
+                    error: e.error.to_string(),
+                    line: e
+                        .origin
+                        .as_ref()
+                        .map_or(0, |origin| origin.line)
+                        .to_string(),
+                    col: (e.column.unwrap_or(0) + 1).to_string(),
+                    file: e
+                        .origin
+                        .as_ref()
+                        .map(|origin| failure.file_registry.path(origin.file_id).to_string()),
+                })
+                .collect();
+            to_value(&compile_errors).unwrap()
+        })
 }
@@ -0,0 +1,95 @@
+//! Replays inputs that once crashed or misassembled through the lexer, the
+//! preprocessor, the parser, the optimizer and the encoder, for every
+//! architecture, so the fixes stay fixed.
+//!
+//! Each file in `tests/corpus` is one input, assembled as is. Files that
+//! aren't UTF-8, such as raw fuzzer findings, are read lossily. A first line
+//! of `// expect: ok` or `// expect: error <message>` also pins what the
+//! default architecture makes of it; without one, not panicking is enough.
+
+use {
+    sbpf_assembler::{
+        Assembler, AssemblerOption, SbpfArch, ast::OptimizationConfig, lexer::tokenize,
+    },
+    std::{
+        fs,
+        panic::{self, AssertUnwindSafe},
+        path::{Path, PathBuf},
+    },
+};
+
+const ARCHES: [SbpfArch; 4] = [SbpfArch::V0, SbpfArch::V1, SbpfArch::V2, SbpfArch::V3];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Error(String),
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut inputs: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+fn expected(source: &str) -> Option<Outcome> {
+    let expect = source.lines().next()?.strip_prefix("// expect:")?.trim();
+    Some(match expect.strip_prefix("error") {
+        Some(message) => Outcome::Error(message.trim().to_string()),
+        None if expect == "ok" => Outcome::Ok,
+        None => panic!("unknown expectation '{}'", expect),
+    })
+}
+
+/// Run `source` through every stage for `arch`, returning what assembling it
+/// as is gave.
+fn replay(source: &str, arch: SbpfArch) -> Outcome {
+    let _ = tokenize(source);
+    let assembler = Assembler::new(AssemblerOption::default().with_arch(arch));
+    let _ = assembler.lint(source);
+    let _ = assembler.assemble_with_preprocess(source, "corpus.s", None);
+    let optimizing = Assembler::new(AssemblerOption {
+        optimization: OptimizationConfig::enabled(),
+        ..AssemblerOption::default().with_arch(arch)
+    });
+    let _ = optimizing.assemble(source);
+    match assembler.assemble(source) {
+        Ok(_) => Outcome::Ok,
+        Err(errors) => Outcome::Error(errors[0].to_string()),
+    }
+}
+
+#[test]
+fn test_corpus_replays_cleanly() {
+    let inputs = corpus();
+    assert!(!inputs.is_empty(), "the corpus is empty");
+
+    let mut failures = Vec::new();
+    for path in inputs {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let bytes = fs::read(&path).unwrap();
+        let source = String::from_utf8_lossy(&bytes);
+        let expected = expected(&source);
+        for arch in ARCHES {
+            match panic::catch_unwind(AssertUnwindSafe(|| replay(&source, arch))) {
+                Ok(outcome) => {
+                    if arch == SbpfArch::default()
+                        && let Some(expected) = &expected
+                        && outcome != *expected
+                    {
+                        failures.push(format!(
+                            "{} ({}): expected {:?}, got {:?}",
+                            name, arch, expected, outcome
+                        ));
+                    }
+                }
+                Err(_) => failures.push(format!("{} ({}): panicked", name, arch)),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
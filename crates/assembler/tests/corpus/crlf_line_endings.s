@@ -0,0 +1,5 @@
+// expect: ok
+.globl entrypoint
+entrypoint:
+  mov64 r0, 0
+  exit
@@ -0,0 +1,5 @@
+// expect: ok
+.globl entrypoint
+entrypoint:
+  lddw r1, -0x8000000000000000
+  exit
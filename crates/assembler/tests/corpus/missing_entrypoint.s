@@ -0,0 +1 @@
+.globl entrypoint
@@ -0,0 +1,5 @@
+// expect: error Undefined label '1b'
+.globl entrypoint
+entrypoint:
+  ja 1b
+  exit
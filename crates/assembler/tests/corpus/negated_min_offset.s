@@ -0,0 +1,5 @@
+// expect: ok
+.globl entrypoint
+entrypoint:
+  ldxdw r0, [r10--32768]
+  exit
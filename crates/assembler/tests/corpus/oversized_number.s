@@ -0,0 +1,5 @@
+// expect: error Invalid number '0xffffffffffffffffff'
+.globl entrypoint
+entrypoint:
+  mov64 r1, 0xffffffffffffffffff
+  exit
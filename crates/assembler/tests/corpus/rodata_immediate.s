@@ -0,0 +1,7 @@
+// expect: ok
+.globl entrypoint
+entrypoint:
+  mov64 r1, message
+  exit
+.rodata
+message: .ascii "hi"
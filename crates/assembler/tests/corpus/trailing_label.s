@@ -0,0 +1,5 @@
+// expect: ok
+.globl entrypoint
+entrypoint:
+  exit
+text_end:
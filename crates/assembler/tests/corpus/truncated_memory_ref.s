@@ -0,0 +1,3 @@
+.globl entrypoint
+entrypoint:
+  ldxdw r1, [r1+
@@ -0,0 +1,3 @@
+.globl entrypoint
+entrypoint:
+  lddw r1,
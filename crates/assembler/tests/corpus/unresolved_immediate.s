@@ -0,0 +1,5 @@
+// expect: error Undefined symbol 'missing'
+.globl entrypoint
+entrypoint:
+  mov64 r1, missing
+  exit
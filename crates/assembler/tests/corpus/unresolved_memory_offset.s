@@ -0,0 +1,5 @@
+// expect: error Undefined symbol 'x4'
+.globl entrypoint
+entrypoint:
+  ldxdw r5, [r1+0-x4]
+  exit
@@ -0,0 +1,5 @@
+// expect: error Undefined symbol 'missing'
+.globl entrypoint
+entrypoint:
+  stxdw [r1+missing], r2
+  exit
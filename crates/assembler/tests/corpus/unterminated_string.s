@@ -0,0 +1,6 @@
+.globl entrypoint
+entrypoint:
+  lddw r1, message
+  exit
+.rodata
+message: .ascii "unterminated
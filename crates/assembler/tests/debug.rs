@@ -159,3 +159,162 @@ test_2:              // line 17
         );
     }
 }
+
+/// The (file, line, column) of each row in the line tables, in order.
+fn line_rows(file_data: &[u8]) -> Vec<(String, u32, u32)> {
+    let object = object::File::parse(file_data).expect("Failed to parse ELF");
+    let load_section = |id: SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+        Ok(object
+            .section_by_name(id.name())
+            .and_then(|section| section.uncompressed_data().ok())
+            .unwrap_or(Cow::Borrowed(&[])))
+    };
+    let dwarf_sections =
+        gimli::DwarfSections::load(&load_section).expect("Failed to load DWARF sections");
+    let dwarf = dwarf_sections.borrow(|section| EndianSlice::new(section, RunTimeEndian::Little));
+
+    let mut result = Vec::new();
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let unit = dwarf.unit(header).expect("Failed to parse unit");
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+        let mut rows = program.rows();
+        while let Ok(Some((header, row))) = rows.next_row() {
+            if row.end_sequence() {
+                continue;
+            }
+            let file = row
+                .file(header)
+                .and_then(|file| dwarf.attr_string(&unit, file.path_name()).ok())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let line = row.line().map_or(0, |line| line.get()) as u32;
+            let column = match row.column() {
+                gimli::ColumnType::LeftEdge => 0,
+                gimli::ColumnType::Column(column) => column.get() as u32,
+            };
+            result.push((file, line, column));
+        }
+    }
+    result
+}
+
+#[test]
+fn test_loc_directives_drive_line_table() {
+    const TEST_SOURCE: &str = r#"	.file	"lib.rs"
+	.file	1 "/src" "lib.rs"
+	.file	2 "/src" "helper.rs"
+.globl entrypoint
+entrypoint:
+	.loc	1 10 5 prologue_end
+  mov64 r0, 1
+  add64 r0, 2
+	.loc	2 3 0 is_stmt 0
+  exit
+"#;
+    let options =
+        sbpf_assembler::AssemblerOption::default().with_debug_mode(sbpf_assembler::DebugMode {
+            filename: "lib.s".to_string(),
+            directory: "/build".to_string(),
+        });
+    let bytecode = sbpf_assembler::Assembler::new(options)
+        .assemble(TEST_SOURCE)
+        .expect("Failed to assemble with debug data");
+
+    let row = |file: &str, line, column| (file.to_string(), line, column);
+    assert_eq!(
+        line_rows(&bytecode),
+        vec![
+            row("lib.rs", 10, 5),
+            row("lib.rs", 10, 5),
+            row("helper.rs", 3, 0),
+        ]
+    );
+
+    let errors = sbpf_assembler::Assembler::new(sbpf_assembler::AssemblerOption::default())
+        .assemble(".loc 3 1 0\nexit\n")
+        .unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "File number 3 has not been declared with .file"
+    );
+}
+
+#[test]
+fn test_frontend_lines_drive_line_table() {
+    struct Lines;
+
+    impl sbpf_assembler::Frontend for Lines {
+        fn name(&self) -> &str {
+            "lines"
+        }
+
+        fn extension(&self) -> &str {
+            "lines"
+        }
+
+        fn translate(
+            &self,
+            source: &str,
+            _path: &str,
+        ) -> Result<sbpf_assembler::Translation, Vec<sbpf_assembler::FrontendError>> {
+            let mut translation = sbpf_assembler::Translation::new();
+            translation.push_line(".globl entrypoint", 0);
+            translation.push_line("entrypoint:", 0);
+            for (line, text) in (1..).zip(source.lines()) {
+                translation.push_line(text, line);
+            }
+            Ok(translation)
+        }
+    }
+
+    let options = sbpf_assembler::AssemblerOption::default()
+        .with_debug_mode(sbpf_assembler::DebugMode {
+            filename: "prog.lines".to_string(),
+            directory: "/src".to_string(),
+        })
+        .with_frontend(Lines);
+    let bytecode = sbpf_assembler::Assembler::new(options)
+        .assemble_with_preprocess("mov64 r0, 1\n\nexit\n", "src/prog.lines", None)
+        .expect("Failed to assemble with debug data");
+
+    let row = |line, column| ("prog.lines".to_string(), line, column);
+    assert_eq!(line_rows(&bytecode), vec![row(1, 0), row(3, 0)]);
+}
+
+#[test]
+fn test_preprocessed_lines_drive_line_table() {
+    const TEST_SOURCE: &str = r#".globl entrypoint
+.macro zero reg
+  mov64 \reg, 0
+.endm
+
+.instruction hello, 0
+  .account payer, signer, writable
+.endinstruction
+
+entrypoint:
+  zero r0
+  exit
+"#;
+    let options =
+        sbpf_assembler::AssemblerOption::default().with_debug_mode(sbpf_assembler::DebugMode {
+            filename: "prog.s".to_string(),
+            directory: "/src".to_string(),
+        });
+    let bytecode = sbpf_assembler::Assembler::new(options)
+        .assemble_with_preprocess(TEST_SOURCE, "prog.s", None)
+        .expect("Failed to assemble with debug data");
+
+    // The macro's instruction is where its body wrote it.
+    let row = |line| ("prog.s".to_string(), line, 0);
+    assert_eq!(line_rows(&bytecode), vec![row(3), row(12)]);
+    let (_, labels) = parse_dwarf_info(&bytecode);
+    let entrypoint = labels
+        .iter()
+        .find(|label| label.name == "entrypoint")
+        .unwrap();
+    assert_eq!(entrypoint.line, Some(10));
+}
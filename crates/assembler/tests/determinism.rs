@@ -0,0 +1,194 @@
+//! Permuting declarations that don't depend on each other must not change
+//! the dynamic symbol table or the order of relocations, so the same program
+//! always links to the same layout.
+
+use {
+    object::{Object, ObjectSymbol, RelocationFlags, RelocationTarget},
+    sbpf_assembler::{Assembler, AssemblerOption, SbpfArch},
+};
+
+const HELPERS: usize = 5;
+const CONSTANTS: usize = 6;
+const MESSAGES: usize = 6;
+const PERMUTATIONS: u64 = 48;
+
+/// xorshift64*, so every run shuffles the same way.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// The declarations of the main file, one per line, in the order given.
+struct Declarations {
+    externs: Vec<String>,
+    constants: Vec<String>,
+    messages: Vec<String>,
+}
+
+impl Declarations {
+    fn new() -> Self {
+        Self {
+            externs: (0..HELPERS)
+                .map(|i| format!(".extern helper_{}", i))
+                .collect(),
+            constants: (0..CONSTANTS)
+                .map(|i| format!(".equ LEN_{}, {}", i, 8 + i))
+                .collect(),
+            messages: (0..MESSAGES)
+                .map(|i| format!("message_{}: .ascii \"message {:02}\"", i, i))
+                .collect(),
+        }
+    }
+
+    fn shuffled(&self, rng: &mut Rng) -> Self {
+        let mut shuffled = Self {
+            externs: self.externs.clone(),
+            constants: self.constants.clone(),
+            messages: self.messages.clone(),
+        };
+        rng.shuffle(&mut shuffled.externs);
+        rng.shuffle(&mut shuffled.constants);
+        rng.shuffle(&mut shuffled.messages);
+        shuffled
+    }
+
+    fn source(&self) -> String {
+        let mut lines = vec![".globl entrypoint".to_string()];
+        lines.extend(self.externs.iter().cloned());
+        lines.extend(self.constants.iter().cloned());
+        lines.push("entrypoint:".to_string());
+        for i in 0..MESSAGES {
+            lines.push(format!("  lddw r1, message_{}", i));
+            lines.push(format!("  mov64 r2, LEN_{}", i % CONSTANTS));
+            lines.push("  call sol_log_".to_string());
+        }
+        for i in 0..HELPERS {
+            lines.push(format!("  call helper_{}", i));
+        }
+        lines.push("  call sol_log_64_".to_string());
+        lines.push("  call sol_log_compute_units_".to_string());
+        lines.push("  exit".to_string());
+        lines.push(".rodata".to_string());
+        lines.extend(self.messages.iter().cloned());
+        lines.join("\n") + "\n"
+    }
+}
+
+fn helpers() -> String {
+    (0..HELPERS)
+        .map(|i| {
+            format!(
+                ".globl helper_{}\nhelper_{}:\n  mov64 r0, {}\n  exit\n",
+                i, i, i
+            )
+        })
+        .collect()
+}
+
+/// What the loader sees of a program's dynamic linking: the dynamic symbols
+/// in table order, and each relocation's offset, type and symbol in order.
+#[derive(Debug, PartialEq)]
+struct DynamicLayout {
+    symbols: Vec<(String, u64)>,
+    relocations: Vec<(u64, u32, Option<String>)>,
+}
+
+fn dynamic_layout(elf: &[u8]) -> DynamicLayout {
+    let file = object::File::parse(elf).expect("Failed to parse ELF");
+    let symbols = file
+        .dynamic_symbols()
+        .map(|symbol| (symbol.name().unwrap().to_string(), symbol.address()))
+        .collect();
+    let relocations = file
+        .dynamic_relocations()
+        .into_iter()
+        .flatten()
+        .map(|(offset, relocation)| {
+            let RelocationFlags::Elf { r_type } = relocation.flags() else {
+                panic!("expected an ELF relocation");
+            };
+            let symbol = match relocation.target() {
+                RelocationTarget::Symbol(index) => file
+                    .dynamic_symbols()
+                    .find(|symbol| symbol.index() == index)
+                    .map(|symbol| symbol.name().unwrap().to_string()),
+                _ => None,
+            };
+            (offset, r_type, symbol)
+        })
+        .collect();
+    DynamicLayout {
+        symbols,
+        relocations,
+    }
+}
+
+fn link(arch: SbpfArch, declarations: &Declarations) -> Vec<u8> {
+    let assembler = Assembler::new(AssemblerOption::default().with_arch(arch));
+    let objects = vec![
+        assembler
+            .assemble_object(&declarations.source(), "main.s", None)
+            .unwrap(),
+        assembler
+            .assemble_object(&helpers(), "helpers.s", None)
+            .unwrap(),
+    ];
+    assembler.link(objects).unwrap().elf
+}
+
+#[test]
+fn test_permuted_declarations_keep_dynamic_layout() {
+    for arch in [SbpfArch::V0, SbpfArch::V3] {
+        let declarations = Declarations::new();
+        let baseline = link(arch, &declarations);
+        let layout = dynamic_layout(&baseline);
+        if arch == SbpfArch::V0 {
+            assert!(
+                layout.symbols.iter().any(|(name, _)| name == "sol_log_64_"),
+                "{:?}",
+                layout
+            );
+            assert!(!layout.relocations.is_empty());
+        }
+
+        let mut rng = Rng(0x5eed);
+        for _ in 0..PERMUTATIONS {
+            let shuffled = declarations.shuffled(&mut rng);
+            let elf = link(arch, &shuffled);
+            assert_eq!(
+                dynamic_layout(&elf),
+                layout,
+                "{:?} layout changed for:\n{}",
+                arch,
+                shuffled.source()
+            );
+
+            // Externs and constants occupy no space, so only moving rodata
+            // may change the bytes.
+            let reordered = Declarations {
+                messages: declarations.messages.clone(),
+                ..shuffled
+            };
+            assert_eq!(
+                link(arch, &reordered),
+                baseline,
+                "{:?} output changed for:\n{}",
+                arch,
+                reordered.source()
+            );
+        }
+    }
+}
@@ -71,6 +71,7 @@ fn expected_hash_mut(case: &mut Case, arch: sbpf_assembler::SbpfArch) -> &mut St
     match arch {
         sbpf_assembler::SbpfArch::V0 => &mut case.hash_v0,
         sbpf_assembler::SbpfArch::V3 => &mut case.hash,
+        other => panic!("no regression hashes are recorded for {}", other),
     }
 }
 
@@ -78,6 +79,7 @@ fn expected_debug_hash_mut(case: &mut Case, arch: sbpf_assembler::SbpfArch) -> &
     match arch {
         sbpf_assembler::SbpfArch::V0 => &mut case.debug_hash_v0,
         sbpf_assembler::SbpfArch::V3 => &mut case.debug_hash,
+        other => panic!("no regression hashes are recorded for {}", other),
     }
 }
 
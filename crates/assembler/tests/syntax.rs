@@ -1,5 +1,5 @@
 use {
-    sbpf_assembler::{Assembler, AssemblerOption},
+    sbpf_assembler::{Assembler, AssemblerOption, format::format_source},
     std::{env, fs, path::PathBuf},
 };
 
@@ -46,3 +46,80 @@ entrypoint:
         "mixed syntax should produce a parse error"
     );
 }
+
+#[test]
+fn test_block_comments() {
+    let commented = r#"
+.globl entrypoint
+/* Everything in here is ignored, including
+   .include "missing.s"
+   /* nested */ and bogus_instruction r99
+*/
+entrypoint:
+    mov64 r0, /* inline */ 0
+    exit
+"#;
+    let plain = r#"
+.globl entrypoint
+entrypoint:
+    mov64 r0, 0
+    exit
+"#;
+    let assembler = Assembler::new(AssemblerOption::default());
+    let expected = assembler.assemble(plain).unwrap();
+    assert_eq!(assembler.assemble(commented).unwrap(), expected);
+    let preprocessed = assembler
+        .assemble_with_preprocess(commented, "<input>", None)
+        .unwrap();
+    assert_eq!(preprocessed, expected);
+}
+
+#[test]
+fn test_unterminated_block_comment() {
+    let source = ".globl entrypoint\nentrypoint:\n    exit\n/* never closed\n";
+    let assembler = Assembler::new(AssemblerOption::default());
+    let errors = assembler.assemble(source).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "Unterminated block comment");
+    assert_eq!(errors[0].span(), &(39..41));
+}
+
+#[test]
+fn test_malformed_directive_reports_usage() {
+    let source = ".globl entrypoint\nentrypoint:\n    exit\n.rodata\nvalues: .quad 1, two, 3\n";
+    let assembler = Assembler::new(AssemblerOption::default());
+    let errors = assembler.assemble(source).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].to_string(),
+        "Invalid operand for '.quad': expected <number> (usage: .quad <number>[, <number>...])"
+    );
+    assert_eq!(&source[errors[0].span().clone()], "two");
+}
+
+#[test]
+fn test_formatting_preserves_bytecode() {
+    let assembler = Assembler::new(AssemblerOption::default());
+    let mut fixtures: Vec<_> = fs::read_dir(fixtures_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "s"))
+        .collect();
+    fixtures.sort();
+    for path in fixtures {
+        let source = fs::read_to_string(&path).unwrap();
+        let formatted = format_source(&source).unwrap();
+        assert_eq!(
+            assembler.assemble(&formatted).ok(),
+            assembler.assemble(&source).ok(),
+            "formatting changed the program built from {}",
+            path.display()
+        );
+        assert_eq!(
+            format_source(&formatted).unwrap(),
+            formatted,
+            "formatting {} twice changed it",
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Build programs from a Cargo build script, so Rust tests can embed fresh
+//! bytecode with [`include_sbpf!`](crate::include_sbpf) instead of a `.so`
+//! someone remembered to rebuild:
+//!
+//! ```no_run
+//! // build.rs
+//! use sbpf_build::{Options, cargo};
+//!
+//! fn main() {
+//!     if let Err(e) = cargo::build_programs("src", &Options::new()) {
+//!         panic!("{}", e);
+//!     }
+//! }
+//! ```
+//!
+//! ```ignore
+//! // tests
+//! let elf: &[u8] = sbpf_build::include_sbpf!("counter");
+//! ```
+
+use {
+    crate::{BuildError, Options, build_program, program_entry},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Directory under `OUT_DIR` the programs are written to, as `<name>.so`.
+pub const OUT_SUBDIR: &str = "sbpf";
+
+/// Build every program under `dir`, each `<dir>/<name>` with an entry file,
+/// into `$OUT_DIR/sbpf/<name>.so`. Cargo is told to rerun the build script
+/// when anything in `dir` or the include paths changes, and shown the
+/// assembler's warnings. Returns the paths written.
+pub fn build_programs(
+    dir: impl AsRef<Path>,
+    options: &Options,
+) -> Result<Vec<PathBuf>, BuildError> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(BuildError::MissingOutDir)?;
+    let (written, directives) =
+        build_into(dir.as_ref(), &Path::new(&out_dir).join(OUT_SUBDIR), options)?;
+    for directive in directives {
+        println!("{}", directive);
+    }
+    Ok(written)
+}
+
+/// [`build_programs`] into `out`, returning the `cargo:` directives to print
+/// rather than printing them.
+pub(crate) fn build_into(
+    dir: &Path,
+    out: &Path,
+    options: &Options,
+) -> Result<(Vec<PathBuf>, Vec<String>), BuildError> {
+    let mut directives = vec![format!("cargo:rerun-if-changed={}", dir.display())];
+    for path in &options.assembler.include_paths {
+        directives.push(format!("cargo:rerun-if-changed={}", path.display()));
+    }
+
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| BuildError::Io { path, source }
+    };
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(dir).map_err(io_error(dir))? {
+        let path = entry.map_err(io_error(dir))?.path();
+        if path.is_dir()
+            && let Some(name) = path.file_name().and_then(|name| name.to_str())
+            && program_entry(&path, &options.assembler.frontends).is_some()
+        {
+            programs.push((name.to_string(), path));
+        }
+    }
+    programs.sort();
+
+    fs::create_dir_all(out).map_err(io_error(out))?;
+    let mut written = Vec::new();
+    for (name, path) in programs {
+        let artifacts = build_program(&path, options)?;
+        for warning in &artifacts.warnings {
+            directives.push(format!("cargo:warning={}: {}", name, warning));
+        }
+        let output = out.join(format!("{}.so", name));
+        fs::write(&output, &artifacts.elf).map_err(io_error(&output))?;
+        written.push(output);
+    }
+    Ok((written, directives))
+}
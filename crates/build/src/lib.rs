@@ -0,0 +1,439 @@
+//! Build sBPF assembly programs from Rust, the way `sbpf build` does, for
+//! build scripts and frameworks that would otherwise shell out to the CLI.
+//!
+//! ```no_run
+//! use sbpf_build::{Options, SbpfArch, build_program};
+//!
+//! let artifacts = build_program("src/counter", &Options::new().with_arch(SbpfArch::V3))?;
+//! std::fs::write("counter.so", &artifacts.elf)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod cargo;
+
+pub use sbpf_assembler::{
+    Artifacts, AssembleErrors, BuildInfo, FrontendRegistry, OptLevel, OptimizationConfig,
+    ParseCache, SbpfArch, SyscallRegistry,
+};
+use {
+    sbpf_assembler::{
+        Assembler, AssemblerOption, DebugMode, Frontend, FsFileResolver,
+        preprocessor::include::include_directives, read_source,
+    },
+    std::{
+        collections::HashSet,
+        fmt, fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Why a program couldn't be built.
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("Failed to read '{}': {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error(
+        "No entry file in '{}'; expected {}.s or a file a front-end translates",
+        dir.display(),
+        dir.file_name().map(|name| name.to_string_lossy()).unwrap_or_default()
+    )]
+    MissingEntry { dir: PathBuf },
+    #[error("{}", Diagnostics(.0))]
+    Assemble(Vec<AssembleErrors>),
+    #[error(
+        "{count} warning{} denied: {}",
+        if *count == 1 { "" } else { "s" },
+        warnings.join("; ")
+    )]
+    DeniedWarnings { count: usize, warnings: Vec<String> },
+    #[error("OUT_DIR is not set; build programs for Cargo from a build script")]
+    MissingOutDir,
+}
+
+/// The ELF of a program built by [`cargo::build_programs`] in the package's
+/// build script, as a `&'static [u8; N]`.
+#[macro_export]
+macro_rules! include_sbpf {
+    ($program:literal) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/sbpf/", $program, ".so"))
+    };
+}
+
+/// Renders assembler errors one per line as `file:line: message`.
+struct Diagnostics<'a>(&'a [AssembleErrors]);
+
+impl fmt::Display for Diagnostics<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for failure in self.0 {
+            for error in &failure.errors {
+                if !first {
+                    writeln!(f)?;
+                }
+                first = false;
+                match &error.origin {
+                    Some(origin) => write!(
+                        f,
+                        "{}:{}: {}",
+                        failure.file_registry.path(origin.file_id),
+                        origin.line,
+                        error
+                    )?,
+                    None => write!(f, "{}", error)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How to build a program. The defaults match `sbpf build` without flags.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    assembler: AssemblerOption,
+    debug: bool,
+    deny_warnings: bool,
+    cache: Option<ParseCache>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the target architecture
+    pub fn with_arch(mut self, arch: SbpfArch) -> Self {
+        self.assembler.arch = arch;
+        self
+    }
+
+    /// Optimize as `-O` does
+    pub fn with_optimization(mut self, optimization: OptimizationConfig) -> Self {
+        self.assembler.optimization = optimization;
+        self
+    }
+
+    /// Emit DWARF for the entry file, as `--debug` does
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Search `path` for `.include` files, as `-I` does
+    pub fn with_include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.assembler.include_paths.push(path.into());
+        self
+    }
+
+    /// Define `name` as `value` in preprocessed sources, as `--define` does
+    pub fn with_define(mut self, name: impl Into<String>, value: i64) -> Self {
+        self.assembler.defines.push((name.into(), value));
+        self
+    }
+
+    /// Resolve calls against `syscalls` instead of the runtime's syscalls
+    pub fn with_syscalls(mut self, syscalls: SyscallRegistry) -> Self {
+        self.assembler.syscalls = syscalls;
+        self
+    }
+
+    /// Translate sources with `frontend`'s extension through it, and accept
+    /// it for the entry file
+    pub fn with_frontend(mut self, frontend: impl Frontend + 'static) -> Self {
+        self.assembler.frontends.register(frontend);
+        self
+    }
+
+    /// Embed a build provenance note
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.assembler.build_info = Some(build_info);
+        self
+    }
+
+    /// Fail on warnings, as `--deny-warnings` does
+    pub fn with_deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+
+    /// Reuse parses across builds that share `cache`
+    pub fn with_cache(mut self, cache: ParseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+}
+
+/// Build the program at `path`: either a program directory, whose entry is
+/// `<dir>/<dir>.s`, or the entry file itself. Every other source under the
+/// entry's directory that isn't `.include`d is linked with it, as
+/// `sbpf build` does for `src/<program>`.
+pub fn build_program(path: impl AsRef<Path>, options: &Options) -> Result<Artifacts, BuildError> {
+    let path = path.as_ref();
+    let frontends = &options.assembler.frontends;
+    let (dir, entry) = if path.is_dir() {
+        let entry = program_entry(path, frontends).ok_or_else(|| BuildError::MissingEntry {
+            dir: path.to_path_buf(),
+        })?;
+        (path, entry)
+    } else {
+        (path.parent().unwrap_or(Path::new(".")), path.to_path_buf())
+    };
+    let include_paths = &options.assembler.include_paths;
+    let files = program_sources(dir, &entry, include_paths, frontends)?;
+    let sources = files
+        .iter()
+        .map(|file| {
+            let source = read_source(file).map_err(|source| BuildError::Io {
+                path: file.clone(),
+                source,
+            })?;
+            Ok((file.to_string_lossy().to_string(), source))
+        })
+        .collect::<Result<Vec<_>, BuildError>>()?;
+
+    let mut assembler_options = options.assembler.clone();
+    if options.debug {
+        assembler_options.debug_mode = Some(debug_mode(&entry));
+    }
+    let mut assembler = Assembler::new(assembler_options);
+    if let Some(cache) = &options.cache {
+        assembler = assembler.with_cache(cache.clone());
+    }
+    let resolver = FsFileResolver::with_include_paths(include_paths.clone());
+
+    let artifacts = if let [(src, source)] = sources.as_slice() {
+        assembler
+            .assemble_full_with_preprocess(source, src, Some(&resolver))
+            .map_err(|errors| BuildError::Assemble(vec![errors]))?
+    } else {
+        let mut objects = Vec::new();
+        let mut failures = Vec::new();
+        for (src, source) in &sources {
+            match assembler.assemble_object(source, src, Some(&resolver)) {
+                Ok(object) => objects.push(object),
+                Err(errors) => failures.push(errors),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(BuildError::Assemble(failures));
+        }
+        assembler
+            .link(objects)
+            .map_err(|errors| BuildError::Assemble(vec![errors]))?
+    };
+
+    if options.deny_warnings && !artifacts.warnings.is_empty() {
+        return Err(BuildError::DeniedWarnings {
+            count: artifacts.warnings.len(),
+            warnings: artifacts.warnings.iter().map(ToString::to_string).collect(),
+        });
+    }
+    Ok(artifacts)
+}
+
+/// The entry file of the program in `dir`: `<dir>/<dir>.s`, or the same
+/// name with the extension of a registered front-end.
+pub fn program_entry(dir: &Path, frontends: &FrontendRegistry) -> Option<PathBuf> {
+    let name = dir.file_name()?.to_str()?;
+    std::iter::once("s")
+        .chain(frontends.extensions())
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|file| file.exists())
+}
+
+/// Every `.s` file, and every file a front-end is registered for, under a
+/// program's directory, entry file first and the rest sorted. Files that
+/// another file in the program `.include`s are left out, since they are
+/// assembled as part of it.
+pub fn program_sources(
+    dir: &Path,
+    entry: &Path,
+    include_paths: &[PathBuf],
+    frontends: &FrontendRegistry,
+) -> Result<Vec<PathBuf>, BuildError> {
+    fn collect(
+        dir: &Path,
+        frontends: &FrontendRegistry,
+        files: &mut Vec<PathBuf>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(&path, frontends, files)?;
+            } else if path.extension().is_some_and(|ext| ext == "s")
+                || frontends.for_path(&path.to_string_lossy()).is_some()
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(dir, frontends, &mut files).map_err(|source| BuildError::Io {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    files.sort();
+
+    let resolver = FsFileResolver::with_include_paths(include_paths.to_vec());
+    let mut included = HashSet::new();
+    for file in files
+        .iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "s"))
+    {
+        let source = read_source(file).map_err(|source| BuildError::Io {
+            path: file.clone(),
+            source,
+        })?;
+        for include in include_directives(&source) {
+            if let Some(path) = resolver.locate(include, &file.to_string_lossy())
+                && let Ok(path) = path.canonicalize()
+            {
+                included.insert(path);
+            }
+        }
+    }
+
+    let mut sources = vec![entry.to_path_buf()];
+    for file in files {
+        let canonical = file.canonicalize().map_err(|source| BuildError::Io {
+            path: file.clone(),
+            source,
+        })?;
+        if file != entry && !included.contains(&canonical) {
+            sources.push(file);
+        }
+    }
+    Ok(sources)
+}
+
+/// DWARF settings naming `entry` as the compilation unit.
+fn debug_mode(entry: &Path) -> DebugMode {
+    let filename = entry
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown.s");
+    let directory = entry
+        .parent()
+        .and_then(|p| p.canonicalize().ok())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| ".".to_string());
+    DebugMode {
+        filename: filename.to_string(),
+        directory,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("sbpf-build-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, path: &str, contents: &str) -> &Self {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+            self
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_build_program_links_sources_that_are_not_included() {
+        let project = TempDir::new("link");
+        project
+            .write(
+                "counter/counter.s",
+                ".include \"log.s\"\n.globl entrypoint\nentrypoint:\n  LOG\n  call helper\n  exit\n",
+            )
+            .write("counter/log.s", ".macro LOG\n  call sol_log_64_\n.endm\n")
+            .write(
+                "counter/lib/helper.s",
+                ".globl helper\nhelper:\n  mov64 r0, 0\n  exit\n",
+            );
+        let dir = project.0.join("counter");
+
+        let sources = program_sources(
+            &dir,
+            &dir.join("counter.s"),
+            &[],
+            &FrontendRegistry::default(),
+        )
+        .unwrap();
+        assert_eq!(sources, [dir.join("counter.s"), dir.join("lib/helper.s")]);
+
+        let artifacts = build_program(&dir, &Options::new()).unwrap();
+        assert!(artifacts.elf.starts_with(b"\x7fELF"));
+        assert_eq!(
+            build_program(dir.join("counter.s"), &Options::new())
+                .unwrap()
+                .elf,
+            artifacts.elf
+        );
+    }
+
+    #[test]
+    fn test_build_program_errors() {
+        let project = TempDir::new("errors");
+        project.write("empty/readme.txt", "").write(
+            "broken/broken.s",
+            ".globl entrypoint\nentrypoint:\n  bogus r1\n  exit\n",
+        );
+
+        let err = build_program(project.0.join("empty"), &Options::new()).unwrap_err();
+        assert!(matches!(err, BuildError::MissingEntry { .. }), "{}", err);
+
+        let err = build_program(project.0.join("broken"), &Options::new()).unwrap_err();
+        assert!(matches!(err, BuildError::Assemble(_)), "{}", err);
+        assert!(err.to_string().contains("broken.s:3: "), "{}", err);
+    }
+
+    #[test]
+    fn test_build_into_out_dir() {
+        let project = TempDir::new("cargo");
+        project
+            .write(
+                "src/hello/hello.s",
+                ".globl entrypoint\nentrypoint:\n  mov64 r0, 0\n  exit\n",
+            )
+            .write("src/notes/readme.txt", "");
+        let out = project.0.join("out");
+
+        let (written, directives) = cargo::build_into(
+            &project.0.join("src"),
+            &out,
+            &Options::new().with_include_path("lib"),
+        )
+        .unwrap();
+        assert_eq!(written, [out.join("hello.so")]);
+        assert!(fs::read(&written[0]).unwrap().starts_with(b"\x7fELF"));
+        assert_eq!(
+            directives,
+            [
+                format!("cargo:rerun-if-changed={}", project.0.join("src").display()),
+                "cargo:rerun-if-changed=lib".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+/// Name of the non-allocated ELF section holding the build note.
+pub const BUILD_NOTE_SECTION: &str = ".note.sbpf.build";
+/// Owner name written into every note entry.
+pub const BUILD_NOTE_OWNER: &str = "sbpf";
+
+pub const NT_SBPF_BUILD_ID: u32 = 1;
+pub const NT_SBPF_ASSEMBLER_VERSION: u32 = 2;
+pub const NT_SBPF_GIT_COMMIT: u32 = 3;
+
+/// Provenance metadata embedded in a built program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildNote {
+    /// Content hash of the program's `.text` and `.rodata`.
+    pub build_id: Vec<u8>,
+    pub assembler_version: String,
+    pub git_commit: Option<String>,
+}
+
+impl BuildNote {
+    pub fn build_id_hex(&self) -> String {
+        self.build_id.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Encode as a sequence of ELF note entries.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_note(&mut bytes, NT_SBPF_BUILD_ID, &self.build_id);
+        write_note(
+            &mut bytes,
+            NT_SBPF_ASSEMBLER_VERSION,
+            self.assembler_version.as_bytes(),
+        );
+        if let Some(commit) = &self.git_commit {
+            write_note(&mut bytes, NT_SBPF_GIT_COMMIT, commit.as_bytes());
+        }
+        bytes
+    }
+
+    /// Decode the contents of a build note section. Entries from other owners
+    /// and unknown note types are skipped.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut build_id = None;
+        let mut assembler_version = None;
+        let mut git_commit = None;
+
+        for (note_type, desc) in read_notes(data)? {
+            match note_type {
+                NT_SBPF_BUILD_ID => build_id = Some(desc.to_vec()),
+                NT_SBPF_ASSEMBLER_VERSION => {
+                    assembler_version = Some(String::from_utf8(desc.to_vec()).ok()?)
+                }
+                NT_SBPF_GIT_COMMIT => git_commit = Some(String::from_utf8(desc.to_vec()).ok()?),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            build_id: build_id?,
+            assembler_version: assembler_version?,
+            git_commit,
+        })
+    }
+}
+
+/// The type and descriptor of each note entry owned by `sbpf` in a note
+/// section, or `None` if the section is truncated.
+pub(crate) fn read_notes(mut data: &[u8]) -> Option<Vec<(u32, &[u8])>> {
+    let mut notes = Vec::new();
+    while data.len() >= 12 {
+        let namesz = u32::from_le_bytes(data[0..4].try_into().ok()?) as usize;
+        let descsz = u32::from_le_bytes(data[4..8].try_into().ok()?) as usize;
+        let note_type = u32::from_le_bytes(data[8..12].try_into().ok()?);
+        let name_end = 12 + namesz.next_multiple_of(4);
+        let desc_end = name_end.checked_add(descsz.next_multiple_of(4))?;
+        if desc_end > data.len() {
+            return None;
+        }
+
+        let name = &data[12..12 + namesz];
+        if name.strip_suffix(&[0]) == Some(BUILD_NOTE_OWNER.as_bytes()) {
+            notes.push((note_type, &data[name_end..name_end + descsz]));
+        }
+        data = &data[desc_end..];
+    }
+    Some(notes)
+}
+
+pub(crate) fn write_note(bytes: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    let name = [BUILD_NOTE_OWNER.as_bytes(), &[0]].concat();
+    bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&note_type.to_le_bytes());
+    for field in [&name[..], desc] {
+        bytes.extend_from_slice(field);
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(git_commit: Option<&str>) -> BuildNote {
+        BuildNote {
+            build_id: vec![0xde, 0xad, 0xbe, 0xef, 0x01],
+            assembler_version: "0.2.4".to_string(),
+            git_commit: git_commit.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_build_note_round_trip() {
+        for git_commit in [None, Some("a26ee2f")] {
+            let note = note(git_commit);
+            let bytes = note.to_bytes();
+            assert!(bytes.len().is_multiple_of(4));
+            assert_eq!(BuildNote::from_bytes(&bytes), Some(note));
+        }
+    }
+
+    #[test]
+    fn test_build_note_layout() {
+        let bytes = note(None).to_bytes();
+        // namesz, descsz, type, "sbpf\0" padded to 8, build id padded to 8.
+        assert_eq!(&bytes[0..12], &[5, 0, 0, 0, 5, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(&bytes[12..20], b"sbpf\0\0\0\0");
+        assert_eq!(&bytes[20..28], &[0xde, 0xad, 0xbe, 0xef, 0x01, 0, 0, 0]);
+        assert_eq!(note(None).build_id_hex(), "deadbeef01");
+    }
+
+    #[test]
+    fn test_build_note_rejects_truncated() {
+        let bytes = note(None).to_bytes();
+        assert_eq!(BuildNote::from_bytes(&bytes[..bytes.len() - 4]), None);
+        assert_eq!(BuildNote::from_bytes(&[]), None);
+    }
+}
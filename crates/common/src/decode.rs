@@ -11,8 +11,23 @@ use {
 
 // TODO: passing span for error reporting (not sure if it's necessary)
 
+/// The 8 bytes every instruction starts with, or an error if `bytes` is cut
+/// short.
+#[inline]
+pub(crate) fn word(bytes: &[u8]) -> Result<&[u8; 8], SBPFError> {
+    bytes.first_chunk().ok_or_else(|| SBPFError::BytecodeError {
+        error: format!(
+            "an instruction needs 8 bytes but only {} remain",
+            bytes.len()
+        ),
+        span: 0..bytes.len(),
+        custom_label: None,
+    })
+}
+
 #[inline]
 fn parse_bytes(bytes: &[u8]) -> Result<(Opcode, u8, u8, i16, i32), SBPFError> {
+    let bytes = word(bytes)?;
     let opcode: Opcode = bytes[0].try_into()?;
     let reg = bytes[1];
     let dst = reg & 0x0f;
@@ -24,6 +39,7 @@ fn parse_bytes(bytes: &[u8]) -> Result<(Opcode, u8, u8, i16, i32), SBPFError> {
 
 #[inline]
 fn parse_bytes_v3(bytes: &[u8]) -> Result<(Opcode, u8, u8, i16, i32), SBPFError> {
+    let bytes = word(bytes)?;
     let opcode: Opcode = Opcode::try_from_sbpf_v3(bytes[0])?;
     let reg = bytes[1];
     let dst = reg & 0x0f;
@@ -34,8 +50,14 @@ fn parse_bytes_v3(bytes: &[u8]) -> Result<(Opcode, u8, u8, i16, i32), SBPFError>
 }
 
 pub fn decode_load_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 16);
     let (opcode, dst, src, off, imm_low) = parse_bytes(bytes)?;
+    if bytes.len() < 16 {
+        return Err(SBPFError::BytecodeError {
+            error: format!("{} needs 16 bytes but only {} remain", opcode, bytes.len()),
+            span: 0..bytes.len(),
+            custom_label: None,
+        });
+    }
     if src != 0 || off != 0 {
         return Err(SBPFError::BytecodeError {
             error: format!(
@@ -59,7 +81,6 @@ pub fn decode_load_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_load_memory(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -82,7 +103,6 @@ pub fn decode_load_memory(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_store_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if src != 0 {
         return Err(SBPFError::BytecodeError {
@@ -105,7 +125,6 @@ pub fn decode_store_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_store_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -128,7 +147,6 @@ pub fn decode_store_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_binary_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if src != 0 || off != 0 {
         return Err(SBPFError::BytecodeError {
@@ -151,7 +169,6 @@ pub fn decode_binary_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_endian(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if src != 0 || off != 0 {
         return Err(SBPFError::BytecodeError {
@@ -163,6 +180,16 @@ pub fn decode_endian(bytes: &[u8]) -> Result<Instruction, SBPFError> {
             custom_label: None,
         });
     }
+    if !matches!(imm, 16 | 32 | 64) {
+        return Err(SBPFError::BytecodeError {
+            error: format!(
+                "{} instruction requires immediate value of 16, 32, or 64",
+                opcode
+            ),
+            span: 0..8,
+            custom_label: None,
+        });
+    }
     Ok(Instruction {
         opcode,
         dst: Some(Register { n: dst }),
@@ -174,7 +201,6 @@ pub fn decode_endian(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_binary_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if off != 0 || imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -197,7 +223,6 @@ pub fn decode_binary_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_unary(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if src != 0 || off != 0 || imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -220,7 +245,6 @@ pub fn decode_unary(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_jump(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if dst != 0 || src != 0 || imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -243,7 +267,6 @@ pub fn decode_jump(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_jump_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if src != 0 {
         return Err(SBPFError::BytecodeError {
@@ -266,7 +289,6 @@ pub fn decode_jump_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_jump_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -289,7 +311,6 @@ pub fn decode_jump_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_call_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
 
     if dst != 0 || off != 0 {
@@ -333,7 +354,6 @@ pub fn decode_call_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_call_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     // Handle SBPF Callx normalization
     let (dst, imm) = if dst == 0 && imm != 0 {
@@ -364,7 +384,6 @@ pub fn decode_call_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_exit(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes(bytes)?;
     if dst != 0 || src != 0 || off != 0 || imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -387,7 +406,6 @@ pub fn decode_exit(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_jump32_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes_v3(bytes)?;
     if src != 0 {
         return Err(SBPFError::BytecodeError {
@@ -410,7 +428,6 @@ pub fn decode_jump32_immediate(bytes: &[u8]) -> Result<Instruction, SBPFError> {
 }
 
 pub fn decode_jump32_register(bytes: &[u8]) -> Result<Instruction, SBPFError> {
-    assert!(bytes.len() >= 8);
     let (opcode, dst, src, off, imm) = parse_bytes_v3(bytes)?;
     if imm != 0 {
         return Err(SBPFError::BytecodeError {
@@ -468,6 +485,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_decode_truncated_input() {
+        let lddw = [0x18, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        match decode_load_immediate(&lddw) {
+            Err(SBPFError::BytecodeError { error, span, .. }) => {
+                assert_eq!(error, "lddw needs 16 bytes but only 8 remain");
+                assert_eq!(span, 0..8);
+            }
+            other => panic!("expected a BytecodeError, got {:?}", other),
+        }
+        assert!(decode_exit(&[0x95, 0x00, 0x00]).is_err());
+        assert!(Instruction::from_bytes(&[]).is_err());
+        assert!(Instruction::from_bytes_sbpf_v2(&[0x8d]).is_err());
+        assert!(Instruction::from_bytes_sbpf_v3(&[]).is_err());
+    }
+
     #[test]
     fn test_decode_load_memory_valid() {
         // ldxw r2, [r3+10]
@@ -654,6 +687,14 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_decode_endian_error_invalid_width() {
+        let bytes = vec![0xdc, 0x01, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00];
+
+        let result = decode_endian(&bytes);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_decode_jump_valid() {
         // ja +10
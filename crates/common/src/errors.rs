@@ -31,6 +31,9 @@ pub enum ExecutionError {
     #[error("Division by zero")]
     DivisionByZero,
 
+    #[error("Division overflow")]
+    DivisionOverflow,
+
     #[error("Invalid operand")]
     InvalidOperand,
 
@@ -24,10 +24,12 @@ pub fn execute_call_immediate(vm: &mut dyn Vm, inst: &Instruction) -> ExecutionR
             let saved_frame_pointer = vm.get_register(10);
             let return_pc = vm.get_pc() + 1;
             vm.push_frame(return_pc, saved_registers, saved_frame_pointer)?;
-            vm.set_register(
-                10,
-                saved_frame_pointer.wrapping_add(vm.get_stack_frame_size()),
-            );
+            if !vm.dynamic_stack_frames() {
+                vm.set_register(
+                    10,
+                    saved_frame_pointer.wrapping_add(vm.get_stack_frame_size()),
+                );
+            }
             let target_pc = ((vm.get_pc() as i64) + 1 + *target) as usize;
             vm.set_pc(target_pc);
             Ok(())
@@ -59,10 +61,12 @@ pub fn execute_call_register(vm: &mut dyn Vm, inst: &Instruction) -> ExecutionRe
     let saved_frame_pointer = vm.get_register(10);
     let return_pc = vm.get_pc() + 1;
     vm.push_frame(return_pc, saved_registers, saved_frame_pointer)?;
-    vm.set_register(
-        10,
-        saved_frame_pointer.wrapping_add(vm.get_stack_frame_size()),
-    );
+    if !vm.dynamic_stack_frames() {
+        vm.set_register(
+            10,
+            saved_frame_pointer.wrapping_add(vm.get_stack_frame_size()),
+        );
+    }
     vm.set_pc(target);
     Ok(())
 }
@@ -137,6 +141,30 @@ mod tests {
         assert_eq!(vm.call_stack[0].1[1], 200); // r7
     }
 
+    #[test]
+    fn test_internal_call_dynamic_stack_frames() {
+        // call 10
+        let inst = make_test_instruction(
+            Opcode::Call,
+            None,
+            None,
+            None,
+            Some(Either::Right(Number::Int(10))),
+        );
+        let mut vm = MockVm::new();
+        vm.registers[10] = 0x8000;
+        vm.dynamic_stack_frames = true;
+
+        execute_call_immediate(&mut vm, &inst).unwrap();
+        assert_eq!(vm.registers[10], 0x8000);
+
+        // The callee's own adjustment is undone on return.
+        vm.registers[10] = 0x7f00;
+        execute_exit(&mut vm, &inst).unwrap();
+        assert_eq!(vm.registers[10], 0x8000);
+        assert_eq!(vm.pc, 1);
+    }
+
     #[test]
     fn test_internal_call_depth_exceeded() {
         // call 10
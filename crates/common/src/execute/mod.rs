@@ -5,6 +5,7 @@ mod endian;
 mod helpers;
 mod jump;
 mod load;
+mod pqr;
 mod store;
 #[cfg(test)]
 mod test_utils;
@@ -16,6 +17,7 @@ use {
     alu32::{execute_alu32_imm, execute_alu32_reg, execute_neg32},
     alu64::{execute_alu64_imm, execute_alu64_reg, execute_neg64},
     load::{execute_lddw, execute_ldxb, execute_ldxdw, execute_ldxh, execute_ldxw},
+    pqr::execute_pqr,
     store::{
         execute_stb, execute_stdw, execute_sth, execute_stw, execute_stxb, execute_stxdw,
         execute_stxh, execute_stxw,
@@ -49,6 +51,12 @@ pub trait Vm {
     fn write_u32(&mut self, addr: u64, value: u32) -> ExecutionResult<()>;
     fn write_u64(&mut self, addr: u64, value: u64) -> ExecutionResult<()>;
 
+    /// The `len` bytes at `addr`, read in one bounds check.
+    fn read_slice(&self, addr: u64, len: usize) -> ExecutionResult<&[u8]>;
+    /// The `len` bytes at `addr`, to be written in place. Fails like a store
+    /// would if any of them is read-only.
+    fn write_slice(&mut self, addr: u64, len: usize) -> ExecutionResult<&mut [u8]>;
+
     fn get_call_depth(&self) -> usize;
     fn max_call_depth(&self) -> usize;
     fn push_frame(
@@ -62,6 +70,8 @@ pub trait Vm {
     fn halt(&mut self, exit_code: u64);
 
     fn get_stack_frame_size(&self) -> u64;
+    /// Calls leave r10 alone, for versions with dynamic stack frames.
+    fn dynamic_stack_frames(&self) -> bool;
 
     fn handle_syscall(&mut self, name: &str) -> ExecutionResult<u64>;
 }
@@ -92,7 +102,7 @@ pub fn execute_binary_immediate(vm: &mut dyn Vm, inst: &Instruction) -> Executio
         | Opcode::Xor32Imm
         | Opcode::Mov32Imm
         | Opcode::Arsh32Imm => execute_alu32_imm(vm, inst),
-        _ => Err(ExecutionError::InvalidInstruction),
+        _ => execute_pqr(vm, inst),
     }
 }
 
@@ -122,7 +132,7 @@ pub fn execute_binary_register(vm: &mut dyn Vm, inst: &Instruction) -> Execution
         | Opcode::Xor32Reg
         | Opcode::Mov32Reg
         | Opcode::Arsh32Reg => execute_alu32_reg(vm, inst),
-        _ => Err(ExecutionError::InvalidInstruction),
+        _ => execute_pqr(vm, inst),
     }
 }
 
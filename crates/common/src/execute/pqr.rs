@@ -0,0 +1,160 @@
+use {
+    super::{ExecutionResult, Vm, helpers::*},
+    crate::{errors::ExecutionError, instruction::Instruction, opcode::Opcode},
+};
+
+/// The product/quotient/remainder family and `hor64`, which take the place
+/// of mul, div and mod in sBPF v2. 32-bit results are zero-extended.
+pub fn execute_pqr(vm: &mut dyn Vm, inst: &Instruction) -> ExecutionResult<()> {
+    let dst = get_dst(inst)?;
+    let operand = match inst.src {
+        Some(_) => vm.get_register(get_src(inst)?),
+        None => get_imm_i64(inst)? as u64,
+    };
+    let dst_val = vm.get_register(dst);
+
+    let result = match inst.opcode {
+        Opcode::Hor64Imm => dst_val | (operand << 32),
+        Opcode::Lmul32Imm | Opcode::Lmul32Reg => {
+            (dst_val as u32).wrapping_mul(operand as u32) as u64
+        }
+        Opcode::Udiv32Imm | Opcode::Udiv32Reg => (dst_val as u32)
+            .checked_div(operand as u32)
+            .ok_or(ExecutionError::DivisionByZero)?
+            as u64,
+        Opcode::Urem32Imm | Opcode::Urem32Reg => (dst_val as u32)
+            .checked_rem(operand as u32)
+            .ok_or(ExecutionError::DivisionByZero)?
+            as u64,
+        Opcode::Sdiv32Imm | Opcode::Sdiv32Reg => {
+            signed_32(dst_val, operand, i32::checked_div)? as u32 as u64
+        }
+        Opcode::Srem32Imm | Opcode::Srem32Reg => {
+            signed_32(dst_val, operand, i32::checked_rem)? as u32 as u64
+        }
+        Opcode::Lmul64Imm | Opcode::Lmul64Reg => dst_val.wrapping_mul(operand),
+        Opcode::Uhmul64Imm | Opcode::Uhmul64Reg => {
+            ((dst_val as u128 * operand as u128) >> 64) as u64
+        }
+        Opcode::Shmul64Imm | Opcode::Shmul64Reg => {
+            ((dst_val as i64 as i128 * operand as i64 as i128) >> 64) as u64
+        }
+        Opcode::Udiv64Imm | Opcode::Udiv64Reg => dst_val
+            .checked_div(operand)
+            .ok_or(ExecutionError::DivisionByZero)?,
+        Opcode::Urem64Imm | Opcode::Urem64Reg => dst_val
+            .checked_rem(operand)
+            .ok_or(ExecutionError::DivisionByZero)?,
+        Opcode::Sdiv64Imm | Opcode::Sdiv64Reg => {
+            signed_64(dst_val, operand, i64::checked_div)? as u64
+        }
+        Opcode::Srem64Imm | Opcode::Srem64Reg => {
+            signed_64(dst_val, operand, i64::checked_rem)? as u64
+        }
+        _ => return Err(ExecutionError::InvalidInstruction),
+    };
+
+    vm.set_register(dst, result);
+    vm.advance_pc();
+    Ok(())
+}
+
+fn signed_32(dst_val: u64, operand: u64, op: fn(i32, i32) -> Option<i32>) -> ExecutionResult<i32> {
+    if operand as i32 == 0 {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    op(dst_val as i32, operand as i32).ok_or(ExecutionError::DivisionOverflow)
+}
+
+fn signed_64(dst_val: u64, operand: u64, op: fn(i64, i64) -> Option<i64>) -> ExecutionResult<i64> {
+    if operand == 0 {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    op(dst_val as i64, operand as i64).ok_or(ExecutionError::DivisionOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            execute::{MockVm, make_test_instruction},
+            inst_param::{Number, Register},
+        },
+        either::Either,
+    };
+
+    fn run_imm(opcode: Opcode, dst_val: u64, imm: i64) -> ExecutionResult<u64> {
+        let inst = make_test_instruction(
+            opcode,
+            Some(Register { n: 1 }),
+            None,
+            None,
+            Some(Either::Right(Number::Int(imm))),
+        );
+        let mut vm = MockVm::new();
+        vm.registers[1] = dst_val;
+        execute_pqr(&mut vm, &inst)?;
+        assert_eq!(vm.pc, 1);
+        Ok(vm.registers[1])
+    }
+
+    fn run_reg(opcode: Opcode, dst_val: u64, src_val: u64) -> ExecutionResult<u64> {
+        let inst = make_test_instruction(
+            opcode,
+            Some(Register { n: 1 }),
+            Some(Register { n: 2 }),
+            None,
+            None,
+        );
+        let mut vm = MockVm::new();
+        vm.registers[1] = dst_val;
+        vm.registers[2] = src_val;
+        execute_pqr(&mut vm, &inst)?;
+        Ok(vm.registers[1])
+    }
+
+    #[test]
+    fn test_hor64() {
+        assert_eq!(
+            run_imm(Opcode::Hor64Imm, 0x1234_5678, 0x7fff_0001).unwrap(),
+            0x7fff_0001_1234_5678
+        );
+    }
+
+    #[test]
+    fn test_multiply() {
+        assert_eq!(
+            run_imm(Opcode::Lmul32Imm, 0xffff_ffff_0000_0003, -2).unwrap(),
+            0xffff_fffa
+        );
+        assert_eq!(run_reg(Opcode::Lmul64Reg, 7, 6).unwrap(), 42);
+        assert_eq!(run_reg(Opcode::Uhmul64Reg, u64::MAX, 4).unwrap(), 3);
+        assert_eq!(
+            run_imm(Opcode::Shmul64Imm, -4i64 as u64, 3).unwrap(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_divide() {
+        assert_eq!(run_imm(Opcode::Udiv32Imm, 100, 7).unwrap(), 14);
+        assert_eq!(run_reg(Opcode::Urem64Reg, 100, 7).unwrap(), 2);
+        assert_eq!(
+            run_imm(Opcode::Sdiv32Imm, -100i64 as u64, 7).unwrap(),
+            -14i32 as u32 as u64
+        );
+        assert_eq!(
+            run_reg(Opcode::Srem64Reg, -100i64 as u64, 7).unwrap(),
+            -2i64 as u64
+        );
+        assert!(matches!(
+            run_reg(Opcode::Udiv64Reg, 1, 0),
+            Err(ExecutionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            run_imm(Opcode::Sdiv64Imm, i64::MIN as u64, -1),
+            Err(ExecutionError::DivisionOverflow)
+        ));
+    }
+}
@@ -18,6 +18,7 @@ pub struct MockVm {
     pub syscall_logs: Vec<String>,
     pub call_depth_limit: usize,
     stack_frame_size: u64,
+    pub dynamic_stack_frames: bool,
 }
 
 impl Default for MockVm {
@@ -38,6 +39,7 @@ impl MockVm {
             syscall_logs: Vec::new(),
             call_depth_limit: 64,
             stack_frame_size: 4096,
+            dynamic_stack_frames: false,
         }
     }
 
@@ -171,6 +173,20 @@ impl super::Vm for MockVm {
         }
     }
 
+    fn read_slice(&self, addr: u64, len: usize) -> ExecutionResult<&[u8]> {
+        let idx = addr as usize;
+        self.memory
+            .get(idx..idx.saturating_add(len))
+            .ok_or(ExecutionError::InvalidMemoryAccess(addr))
+    }
+
+    fn write_slice(&mut self, addr: u64, len: usize) -> ExecutionResult<&mut [u8]> {
+        let idx = addr as usize;
+        self.memory
+            .get_mut(idx..idx.saturating_add(len))
+            .ok_or(ExecutionError::InvalidMemoryAccess(addr))
+    }
+
     fn get_call_depth(&self) -> usize {
         self.call_stack.len()
     }
@@ -203,6 +219,10 @@ impl super::Vm for MockVm {
         self.stack_frame_size
     }
 
+    fn dynamic_stack_frames(&self) -> bool {
+        self.dynamic_stack_frames
+    }
+
     fn handle_syscall(&mut self, name: &str) -> ExecutionResult<u64> {
         self.syscall_logs.push(name.to_string());
         Ok(0)
@@ -1,10 +1,12 @@
 use {
     crate::{
+        decode::word,
         errors::SBPFError,
         inst_handler::{OPCODE_TO_HANDLER, OPCODE_TO_TYPE},
         inst_param::{Number, Register},
         opcode::{Opcode, OperationType},
         syscalls::REGISTERED_SYSCALLS,
+        version::SbpfVersion,
     },
     core::ops::Range,
     either::Either,
@@ -87,7 +89,7 @@ impl Instruction {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, SBPFError> {
-        let opcode: Opcode = bytes[0].try_into()?;
+        let opcode: Opcode = word(bytes)?[0].try_into()?;
         if let Some(handler) = OPCODE_TO_HANDLER.get(&opcode) {
             (handler.decode)(bytes)
         } else {
@@ -100,6 +102,7 @@ impl Instruction {
     }
 
     pub fn from_bytes_sbpf_v2(bytes: &[u8]) -> Result<Self, SBPFError> {
+        word(bytes)?;
         // Preprocess the opcode byte for SBPF v2 (e_flags == 0x02)
         let mut processed_bytes = bytes.to_vec();
 
@@ -137,7 +140,7 @@ impl Instruction {
     }
 
     pub fn from_bytes_sbpf_v3(bytes: &[u8]) -> Result<Self, SBPFError> {
-        let opcode = Opcode::try_from_sbpf_v3(bytes[0])?;
+        let opcode = Opcode::try_from_sbpf_v3(word(bytes)?[0])?;
         OPCODE_TO_HANDLER
             .get(&opcode)
             .ok_or_else(|| SBPFError::BytecodeError {
@@ -148,19 +151,71 @@ impl Instruction {
             .and_then(|handler| (handler.decode)(bytes))
     }
 
+    /// Decode the instruction at the start of `bytes` as `version` encodes it.
+    pub fn from_bytes_for(bytes: &[u8], version: SbpfVersion) -> Result<Self, SBPFError> {
+        match version {
+            SbpfVersion::V0 | SbpfVersion::V1 => Self::from_bytes(bytes),
+            SbpfVersion::V2 => Self::from_bytes_sbpf_v2(bytes),
+            SbpfVersion::V3 => Self::from_bytes_sbpf_v3(bytes),
+        }
+    }
+
+    /// Encode the instruction for `version`. Only v2 differs from
+    /// [`Self::to_bytes`]: memory instructions move to their v2 opcodes,
+    /// `lddw` becomes a pair ending in `hor64` and `callx` names its target
+    /// in the src field. This is the inverse of [`Self::from_bytes_sbpf_v2`].
+    pub fn to_bytes_for(&self, version: SbpfVersion) -> Result<Vec<u8>, SBPFError> {
+        let mut b = self.to_bytes()?;
+        if version.move_memory_instruction_classes() {
+            b[0] = match self.opcode {
+                Opcode::Ldxw => 0x8C,
+                Opcode::Stxw => 0x8F,
+                Opcode::Ldxb => 0x2C,
+                Opcode::Ldxh => 0x3C,
+                Opcode::Ldxdw => 0x9C,
+                Opcode::Stb => 0x27,
+                Opcode::Stxb => 0x2F,
+                Opcode::Sth => 0x37,
+                Opcode::Stxh => 0x3F,
+                Opcode::Stw => 0x87,
+                Opcode::Stdw => 0x97,
+                Opcode::Stxdw => 0x9F,
+                _ => b[0],
+            };
+        }
+        if version.disable_lddw() && self.opcode == Opcode::Lddw {
+            b[0] = 0x21;
+            b[8] = Opcode::Hor64Imm.into();
+            b[9] = b[1];
+        }
+        if version.callx_uses_src_reg() && self.opcode == Opcode::Callx {
+            b[1] = b[4] << 4;
+            b[4..8].fill(0);
+        }
+        Ok(b)
+    }
+
     pub fn to_bytes(&self) -> Result<Vec<u8>, SBPFError> {
         let dst_val = self.dst.as_ref().map(|r| r.n).unwrap_or(0);
         let src_val = self.src.as_ref().map(|r| r.n).unwrap_or(0);
         let off_val = match &self.off {
             Some(Either::Left(ident)) => {
-                unreachable!("Identifier '{}' should have been resolved earlier", ident)
+                return Err(SBPFError::BytecodeError {
+                    error: format!("Unresolved symbol '{}'", ident),
+                    span: self.span.clone(),
+                    custom_label: Some("Undefined symbol".to_string()),
+                });
             }
             Some(Either::Right(off)) => *off,
             None => 0,
         };
         let imm_val = match &self.imm {
             Some(Either::Left(ident)) => {
-                unreachable!("Identifier '{}' should have been resolved earlier", ident)
+                return Err(SBPFError::BytecodeError {
+                    error: format!("Unresolved symbol '{}'", ident),
+                    span: self.span.clone(),
+                    custom_label: Some("Undefined symbol".to_string()),
+                });
             }
             Some(Either::Right(Number::Int(imm))) | Some(Either::Right(Number::Addr(imm))) => *imm,
             None => 0,
@@ -188,76 +243,77 @@ impl Instruction {
         }
     }
 
-    fn to_default_asm(&self) -> Result<String, SBPFError> {
-        if let Some(handler) = OPCODE_TO_HANDLER.get(&self.opcode) {
-            match (handler.validate)(self) {
-                Ok(()) => {
-                    let mut asm = if self.opcode == Opcode::Le || self.opcode == Opcode::Be {
-                        self.op_imm_bits()?
-                    } else {
-                        format!("{}", self.opcode)
-                    };
-                    let mut param = vec![];
-
-                    fn fmt_mem_off(r: &Register, off: &Either<String, i16>) -> String {
-                        format!("[r{}{}]", r.n, fmt_off(off))
-                    }
-
-                    if self.get_opcode_type() == OperationType::LoadMemory {
-                        param.push(format!("r{}", self.dst.as_ref().unwrap().n));
-                        param.push(fmt_mem_off(
-                            self.src.as_ref().unwrap(),
-                            self.off.as_ref().unwrap(),
-                        ));
-                    } else if self.get_opcode_type() == OperationType::StoreImmediate {
-                        param.push(fmt_mem_off(
-                            self.dst.as_ref().unwrap(),
-                            self.off.as_ref().unwrap(),
-                        ));
-                        param.push(fmt_imm(self.imm.as_ref().unwrap()));
-                    } else if self.get_opcode_type() == OperationType::StoreRegister {
-                        param.push(fmt_mem_off(
-                            self.dst.as_ref().unwrap(),
-                            self.off.as_ref().unwrap(),
-                        ));
-                        param.push(format!("r{}", self.src.as_ref().unwrap().n));
-                    } else {
-                        if let Some(dst) = &self.dst {
-                            param.push(format!("r{}", dst.n));
-                        }
-                        if let Some(src) = &self.src
-                            && self.opcode != Opcode::Call
-                        {
-                            param.push(format!("r{}", src.n));
-                        }
-                        if let Some(imm) = &self.imm
-                            && self.opcode != Opcode::Le
-                            && self.opcode != Opcode::Be
-                        {
-                            param.push(fmt_imm(imm));
-                        }
-                        if let Some(off) = &self.off {
-                            param.push(fmt_off(off));
-                        }
-                    }
-                    if !param.is_empty() {
-                        asm.push(' ');
-                        asm.push_str(&param.join(", "));
-                    }
-                    Ok(asm)
-                }
-                Err(e) => Err(e),
-            }
-        } else {
-            Err(SBPFError::BytecodeError {
+    /// Check the instruction has the operands its opcode takes.
+    fn validate(&self) -> Result<(), SBPFError> {
+        match OPCODE_TO_HANDLER.get(&self.opcode) {
+            Some(handler) => (handler.validate)(self),
+            None => Err(SBPFError::BytecodeError {
                 error: format!("no validate handler for opcode {}", self.opcode),
                 span: self.span.clone(),
                 custom_label: None,
-            })
+            }),
+        }
+    }
+
+    fn to_default_asm(&self) -> Result<String, SBPFError> {
+        self.validate()?;
+        let mut asm = if self.opcode == Opcode::Le || self.opcode == Opcode::Be {
+            self.op_imm_bits()?
+        } else {
+            format!("{}", self.opcode)
+        };
+        let mut param = vec![];
+
+        fn fmt_mem_off(r: &Register, off: &Either<String, i16>) -> String {
+            format!("[r{}{}]", r.n, fmt_off(off))
+        }
+
+        if self.get_opcode_type() == OperationType::LoadMemory {
+            param.push(format!("r{}", self.dst.as_ref().unwrap().n));
+            param.push(fmt_mem_off(
+                self.src.as_ref().unwrap(),
+                self.off.as_ref().unwrap(),
+            ));
+        } else if self.get_opcode_type() == OperationType::StoreImmediate {
+            param.push(fmt_mem_off(
+                self.dst.as_ref().unwrap(),
+                self.off.as_ref().unwrap(),
+            ));
+            param.push(fmt_imm(self.imm.as_ref().unwrap()));
+        } else if self.get_opcode_type() == OperationType::StoreRegister {
+            param.push(fmt_mem_off(
+                self.dst.as_ref().unwrap(),
+                self.off.as_ref().unwrap(),
+            ));
+            param.push(format!("r{}", self.src.as_ref().unwrap().n));
+        } else {
+            if let Some(dst) = &self.dst {
+                param.push(format!("r{}", dst.n));
+            }
+            if let Some(src) = &self.src
+                && self.opcode != Opcode::Call
+            {
+                param.push(format!("r{}", src.n));
+            }
+            if let Some(imm) = &self.imm
+                && self.opcode != Opcode::Le
+                && self.opcode != Opcode::Be
+            {
+                param.push(fmt_imm(imm));
+            }
+            if let Some(off) = &self.off {
+                param.push(fmt_off(off));
+            }
+        }
+        if !param.is_empty() {
+            asm.push(' ');
+            asm.push_str(&param.join(", "));
         }
+        Ok(asm)
     }
 
     fn to_llvm_asm(&self) -> Result<String, SBPFError> {
+        self.validate()?;
         let op_type = self.get_opcode_type();
 
         fn fmt_mem_off(off: &Either<String, i16>) -> String {
@@ -394,7 +450,7 @@ fn fmt_imm(imm: &Either<String, Number>) -> String {
         Either::Left(label) => label.clone(),
         Either::Right(Number::Int(v)) | Either::Right(Number::Addr(v)) => {
             if *v < 0 {
-                format!("-0x{:x}", -v)
+                format!("-0x{:x}", v.unsigned_abs())
             } else {
                 format!("0x{:x}", v)
             }
@@ -437,6 +493,7 @@ mod test {
             inst_param::{Number, Register},
             instruction::{AsmFormat, Instruction},
             opcode::Opcode,
+            version::SbpfVersion,
         },
         either::Either,
         hex_literal::hex,
@@ -843,7 +900,6 @@ mod test {
     }
 
     #[test]
-    #[should_panic(expected = "should have been resolved earlier")]
     fn test_to_bytes_call_with_identifier() {
         let inst = Instruction {
             opcode: Opcode::Call,
@@ -853,8 +909,11 @@ mod test {
             imm: Some(Either::Left("function".to_string())),
             span: 0..8,
         };
-        // This should panic because "function" does not exist
-        let _ = inst.to_bytes().unwrap();
+        // "function" was never resolved to an offset
+        assert_eq!(
+            inst.to_bytes().unwrap_err().to_string(),
+            "Bytecode error: Unresolved symbol 'function'"
+        );
     }
 
     #[test]
@@ -876,6 +935,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_to_asm_lddw_min_imm() {
+        let mut bytes = [0; 16];
+        bytes[0] = 0x18;
+        bytes[1] = 0x01;
+        bytes[15] = 0x80;
+        let inst = Instruction::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            inst.to_asm(AsmFormat::Default).unwrap(),
+            "lddw r1, -0x8000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_to_asm_missing_operands() {
+        for opcode in [Opcode::Add64Imm, Opcode::Ldxdw, Opcode::JeqReg, Opcode::Ja] {
+            let inst = Instruction {
+                opcode,
+                dst: None,
+                src: None,
+                off: None,
+                imm: None,
+                span: 0..8,
+            };
+            assert!(inst.to_asm(AsmFormat::Default).is_err(), "{}", opcode);
+            assert!(inst.to_asm(AsmFormat::Llvm).is_err(), "{}", opcode);
+        }
+    }
+
     #[test]
     fn test_from_bytes_sbpf_v2() {
         // Test all v2 opcode mappings and repurposed opcodes
@@ -954,6 +1042,45 @@ mod test {
         assert_eq!(lddw_inst.opcode, Opcode::Lddw);
     }
 
+    #[test]
+    fn test_to_bytes_for_sbpf_v2() {
+        let round_trip = |bytes: &[u8]| {
+            let inst = Instruction::from_bytes(bytes).unwrap();
+            let encoded = inst.to_bytes_for(SbpfVersion::V2).unwrap();
+            assert_eq!(
+                Instruction::from_bytes_for(&encoded, SbpfVersion::V2).unwrap(),
+                inst
+            );
+            encoded
+        };
+
+        assert_eq!(
+            round_trip(&hex!("7912080000000000")),
+            hex!("9c12080000000000")
+        );
+        assert_eq!(
+            round_trip(&hex!("7a01040064000000")),
+            hex!("9701040064000000")
+        );
+        assert_eq!(
+            round_trip(&hex!("8d00000005000000")),
+            hex!("8d50000000000000")
+        );
+        assert_eq!(
+            round_trip(&hex!("1803000078563412 0000000021436587")),
+            hex!("2103000078563412 f703000021436587")
+        );
+        // Everything else keeps its v0 encoding.
+        assert_eq!(
+            round_trip(&hex!("0701000064000000")),
+            hex!("0701000064000000")
+        );
+        assert_eq!(
+            round_trip(&hex!("3e12000000000000")),
+            hex!("3e12000000000000")
+        );
+    }
+
     #[test]
     fn test_is_syscall() {
         let test_cases = vec![
@@ -1,10 +1,16 @@
+pub mod build_note;
 pub mod decode;
 pub mod errors;
 pub mod execute;
 pub mod inst_handler;
 pub mod inst_param;
 pub mod instruction;
+pub mod lint;
 pub mod opcode;
+pub mod profile;
+pub mod program_error;
 pub mod syscalls;
 pub mod syscalls_map;
 pub mod validate;
+pub mod verifier;
+pub mod version;
@@ -0,0 +1,69 @@
+use {serde::Deserialize, std::collections::BTreeMap};
+
+/// How a lint is reported: dropped, shown as a warning, or treated as an
+/// error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+/// A project's lint policy, the `[lint]` table of `sbpf.toml`: a level per
+/// lint code, and the limits the `stack-limit-exceeded` and
+/// `compute-limit-exceeded` lints check functions against.
+///
+/// ```toml
+/// [lint]
+/// max-stack = 512
+/// max-compute-units = 2000
+/// unused-label = "allow"
+/// unreachable-code = "deny"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LintConfig {
+    /// Most bytes a function may use below its frame pointer.
+    pub max_stack: Option<u64>,
+    /// Most compute units a function may spend on its longest path.
+    pub max_compute_units: Option<u64>,
+    #[serde(flatten)]
+    pub levels: BTreeMap<String, LintLevel>,
+}
+
+impl LintConfig {
+    /// The level `lint` is reported at; lints not named are warnings.
+    pub fn level(&self, lint: &str) -> LintLevel {
+        self.levels.get(lint).copied().unwrap_or_default()
+    }
+
+    pub fn is_allowed(&self, lint: &str) -> bool {
+        self.level(lint) == LintLevel::Allow
+    }
+
+    pub fn is_denied(&self, lint: &str) -> bool {
+        self.level(lint) == LintLevel::Deny
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_config() {
+        let config: LintConfig = toml::from_str(
+            "max-stack = 512\nunused-label = \"allow\"\nunreachable-code = \"deny\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.max_stack, Some(512));
+        assert_eq!(config.max_compute_units, None);
+        assert!(config.is_allowed("unused-label"));
+        assert!(config.is_denied("unreachable-code"));
+        assert_eq!(config.level("unused-constant"), LintLevel::Warn);
+
+        assert!(toml::from_str::<LintConfig>("unused-label = \"error\"\n").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Write,
+        time::Duration,
+    },
+};
+
+/// How often each instruction of a program was executed.
+///
+/// Counts are indexed by instruction, in `.text` order, the same way the VM
+/// indexes its program counter: a 16-byte `lddw` is a single entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExecutionProfile {
+    pub instruction_counts: Vec<u64>,
+}
+
+impl ExecutionProfile {
+    pub fn new(instruction_count: usize) -> Self {
+        Self {
+            instruction_counts: vec![0; instruction_count],
+        }
+    }
+
+    /// Record one execution of the instruction at `pc`. Out of range program
+    /// counters are ignored, the VM reports those itself.
+    pub fn record(&mut self, pc: usize) {
+        if let Some(count) = self.instruction_counts.get_mut(pc) {
+            *count += 1;
+        }
+    }
+
+    pub fn count(&self, pc: usize) -> u64 {
+        self.instruction_counts.get(pc).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.instruction_counts.iter().sum()
+    }
+
+    /// Add the counts of another run of the same program.
+    pub fn merge(&mut self, other: &ExecutionProfile) {
+        if self.instruction_counts.len() < other.instruction_counts.len() {
+            self.instruction_counts
+                .resize(other.instruction_counts.len(), 0);
+        }
+        for (count, other) in self
+            .instruction_counts
+            .iter_mut()
+            .zip(&other.instruction_counts)
+        {
+            *count += other;
+        }
+    }
+}
+
+/// Compute units a program used, charged to the instruction that used them
+/// under the stack of internal calls it ran in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComputeProfile {
+    /// Units per stack: the first instruction of each function on the call
+    /// stack, outermost first, followed by the instruction charged.
+    pub stacks: HashMap<Vec<usize>, u64>,
+    /// The functions on the call stack of the run being recorded.
+    frames: Vec<usize>,
+}
+
+impl ComputeProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charge `units` to the instruction at `pc`, run at call depth `depth`.
+    /// `callee` is the first instruction of the function it called, if it
+    /// made an internal call.
+    pub fn record(&mut self, pc: usize, units: u64, depth: usize, callee: Option<usize>) {
+        // Returns drop the frames above `depth`. Frames entered before
+        // recording started are named after the first instruction seen in them.
+        self.frames.truncate(depth + 1);
+        if self.frames.len() <= depth {
+            self.frames.resize(depth + 1, pc);
+        }
+        self.frames.push(pc);
+        match self.stacks.get_mut(self.frames.as_slice()) {
+            Some(total) => *total += units,
+            None => {
+                self.stacks.insert(self.frames.clone(), units);
+            }
+        }
+        self.frames.pop();
+        if let Some(callee) = callee {
+            self.frames.push(callee);
+        }
+    }
+
+    /// Forget the call stack, for the next instruction recorded to start a
+    /// new run.
+    pub fn restart(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn total(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// Units charged to each instruction, under any stack.
+    pub fn instruction_units(&self) -> HashMap<usize, u64> {
+        let mut units = HashMap::new();
+        for (stack, total) in &self.stacks {
+            if let Some(pc) = stack.last() {
+                *units.entry(*pc).or_default() += total;
+            }
+        }
+        units
+    }
+
+    /// Add the units of another run of the same program.
+    pub fn merge(&mut self, other: &ComputeProfile) {
+        for (stack, total) in &other.stacks {
+            *self.stacks.entry(stack.clone()).or_default() += total;
+        }
+    }
+
+    /// One stack per line, sorted, as `0;12;15 40`: the instruction indices
+    /// of the stack joined by `;`, then its units.
+    pub fn to_text(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        let mut out = String::new();
+        for (stack, total) in stacks {
+            let stack: Vec<String> = stack.iter().map(ToString::to_string).collect();
+            writeln!(out, "{} {}", stack.join(";"), total)
+                .expect("writing to a String cannot fail");
+        }
+        out
+    }
+
+    /// Read a profile written by [`ComputeProfile::to_text`], or `None` if
+    /// a line is malformed.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let mut profile = Self::new();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let (stack, total) = line.rsplit_once(' ')?;
+            let stack = stack
+                .split(';')
+                .map(str::parse)
+                .collect::<Result<Vec<usize>, _>>()
+                .ok()?;
+            *profile.stacks.entry(stack).or_default() += total.parse::<u64>().ok()?;
+        }
+        Some(profile)
+    }
+}
+
+/// How often each syscall was called, with the compute units charged for it
+/// and the host time it took.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyscallStats {
+    pub syscalls: BTreeMap<String, SyscallUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallUsage {
+    pub calls: u64,
+    pub compute_units: u64,
+    pub time: Duration,
+}
+
+impl SyscallStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one call of `name` that was charged `compute_units` and took
+    /// `time`.
+    pub fn record(&mut self, name: &str, compute_units: u64, time: Duration) {
+        let usage = match self.syscalls.get_mut(name) {
+            Some(usage) => usage,
+            None => self.syscalls.entry(name.to_string()).or_default(),
+        };
+        usage.calls += 1;
+        usage.compute_units += compute_units;
+        usage.time += time;
+    }
+
+    /// Compute units charged for syscalls in all.
+    pub fn compute_units(&self) -> u64 {
+        self.syscalls
+            .values()
+            .map(|usage| usage.compute_units)
+            .sum()
+    }
+
+    /// The syscalls charged the most compute units first, then by name.
+    pub fn by_compute_units(&self) -> Vec<(&str, &SyscallUsage)> {
+        let mut syscalls: Vec<_> = self
+            .syscalls
+            .iter()
+            .map(|(name, usage)| (name.as_str(), usage))
+            .collect();
+        syscalls.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.compute_units));
+        syscalls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_merge() {
+        let mut profile = ExecutionProfile::new(3);
+        profile.record(0);
+        profile.record(2);
+        profile.record(2);
+        profile.record(7);
+        assert_eq!(profile.instruction_counts, vec![1, 0, 2]);
+
+        let mut other = ExecutionProfile::new(4);
+        other.record(3);
+        profile.merge(&other);
+        assert_eq!(profile.instruction_counts, vec![1, 0, 2, 1]);
+        assert_eq!(profile.total(), 4);
+        assert_eq!(profile.count(9), 0);
+    }
+
+    #[test]
+    fn test_compute_profile_stacks() {
+        // An entrypoint at 0 calls a function at 5 from 1, which returns
+        // from 6 after a syscall costing 100 units.
+        let mut profile = ComputeProfile::new();
+        profile.record(0, 1, 0, None);
+        profile.record(1, 1, 0, Some(5));
+        profile.record(5, 101, 1, None);
+        profile.record(6, 1, 1, None);
+        profile.record(2, 1, 0, None);
+        assert_eq!(profile.total(), 105);
+        assert_eq!(profile.stacks[&vec![0, 5, 5]], 101);
+        assert_eq!(profile.instruction_units()[&2], 1);
+
+        // A second run through the same stacks adds to them.
+        profile.restart();
+        profile.record(0, 1, 0, None);
+        assert_eq!(profile.stacks[&vec![0, 0]], 2);
+
+        let text = profile.to_text();
+        assert_eq!(text, "0;0 2\n0;1 1\n0;2 1\n0;5;5 101\n0;5;6 1\n");
+        let mut read = ComputeProfile::from_text(&text).unwrap();
+        assert_eq!(read.stacks, profile.stacks);
+        read.merge(&profile);
+        assert_eq!(read.total(), 212);
+        assert_eq!(ComputeProfile::from_text("0;x 1"), None);
+    }
+
+    #[test]
+    fn test_syscall_stats() {
+        let mut stats = SyscallStats::new();
+        stats.record("sol_log_", 100, Duration::from_micros(3));
+        stats.record("sol_sha256", 85, Duration::from_micros(10));
+        stats.record("sol_log_", 104, Duration::from_micros(2));
+        assert_eq!(
+            stats.syscalls["sol_log_"],
+            SyscallUsage {
+                calls: 2,
+                compute_units: 204,
+                time: Duration::from_micros(5),
+            }
+        );
+        assert_eq!(stats.compute_units(), 289);
+        let order: Vec<&str> = stats
+            .by_compute_units()
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        assert_eq!(order, ["sol_log_", "sol_sha256"]);
+    }
+}
@@ -0,0 +1,229 @@
+use {
+    crate::build_note::{read_notes, write_note},
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// Name of the non-allocated ELF section holding the errors a program
+/// declares with `.error`.
+pub const ERROR_NOTE_SECTION: &str = ".note.sbpf.errors";
+
+pub const NT_SBPF_ERROR: u32 = 1;
+
+/// Names of the errors the runtime defines, by the upper 32 bits of the exit
+/// code they are returned as: `InvalidArgument` is `2 << 32`. `Custom(0)` is
+/// `1 << 32` since an exit code of 0 means success.
+const BUILTIN_ERRORS: [&str; 26] = [
+    "Custom(0)",
+    "InvalidArgument",
+    "InvalidInstructionData",
+    "InvalidAccountData",
+    "AccountDataTooSmall",
+    "InsufficientFunds",
+    "IncorrectProgramId",
+    "MissingRequiredSignature",
+    "AccountAlreadyInitialized",
+    "UninitializedAccount",
+    "NotEnoughAccountKeys",
+    "AccountBorrowFailed",
+    "MaxSeedLengthExceeded",
+    "InvalidSeeds",
+    "BorshIoError",
+    "AccountNotRentExempt",
+    "UnsupportedSysvar",
+    "IllegalOwner",
+    "MaxAccountsDataAllocationsExceeded",
+    "InvalidRealloc",
+    "MaxInstructionTraceLengthExceeded",
+    "BuiltinProgramsMustConsumeComputeUnits",
+    "InvalidAccountOwner",
+    "ArithmeticOverflow",
+    "Immutable",
+    "IncorrectAuthority",
+];
+
+/// Name of the builtin `ProgramError` a program exited with, if `exit_code`
+/// is one.
+pub fn builtin_error_name(exit_code: u64) -> Option<&'static str> {
+    if exit_code & u64::from(u32::MAX) != 0 {
+        return None;
+    }
+    let index = (exit_code >> 32).checked_sub(1)?;
+    BUILTIN_ERRORS.get(index as usize).copied()
+}
+
+/// A custom error a program defines, in the shape of an entry of an
+/// Anchor IDL's `errors`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorDefinition {
+    pub code: u32,
+    pub name: String,
+    #[serde(default, rename = "msg", skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// The custom errors of a program, by code, used to name the
+/// `ProgramError::Custom(n)` it exits with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorRegistry {
+    errors: BTreeMap<u32, ErrorDefinition>,
+}
+
+impl ErrorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an error, replacing any with the same code.
+    pub fn insert(&mut self, error: ErrorDefinition) {
+        self.errors.insert(error.code, error);
+    }
+
+    pub fn get(&self, code: u32) -> Option<&ErrorDefinition> {
+        self.errors.get(&code)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ErrorDefinition> {
+        self.errors.values()
+    }
+
+    /// What a non-zero exit code means: the name and message of a custom
+    /// error in the registry, `Custom(n)` for one that isn't, or the name of
+    /// a builtin error. `None` for success and codes that are neither.
+    pub fn describe(&self, exit_code: u64) -> Option<String> {
+        if exit_code == 0 {
+            return None;
+        }
+        if let Some(name) = builtin_error_name(exit_code) {
+            return Some(name.to_string());
+        }
+        let code = u32::try_from(exit_code).ok()?;
+        Some(match self.get(code) {
+            Some(ErrorDefinition {
+                name,
+                message: Some(message),
+                ..
+            }) => format!("{}: {}", name, message),
+            Some(error) => error.name.clone(),
+            None => format!("Custom({})", code),
+        })
+    }
+
+    /// Encode as ELF note entries, one per error: its code as a
+    /// little-endian `u32`, its name and its message, each NUL-terminated.
+    pub fn to_note_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for error in self.iter() {
+            let mut desc = error.code.to_le_bytes().to_vec();
+            desc.extend_from_slice(error.name.as_bytes());
+            desc.push(0);
+            desc.extend_from_slice(error.message.as_deref().unwrap_or_default().as_bytes());
+            desc.push(0);
+            write_note(&mut bytes, NT_SBPF_ERROR, &desc);
+        }
+        bytes
+    }
+
+    /// Decode the contents of an error note section. An empty message reads
+    /// back as none.
+    pub fn from_note_bytes(data: &[u8]) -> Option<Self> {
+        let mut registry = Self::new();
+        for (note_type, desc) in read_notes(data)? {
+            if note_type != NT_SBPF_ERROR {
+                continue;
+            }
+            let code = u32::from_le_bytes(desc.get(..4)?.try_into().ok()?);
+            let mut strings = desc[4..].split(|&b| b == 0);
+            let name = String::from_utf8(strings.next()?.to_vec()).ok()?;
+            let message = String::from_utf8(strings.next()?.to_vec()).ok()?;
+            registry.insert(ErrorDefinition {
+                code,
+                name,
+                message: (!message.is_empty()).then_some(message),
+            });
+        }
+        Some(registry)
+    }
+}
+
+impl FromIterator<ErrorDefinition> for ErrorRegistry {
+    fn from_iter<I: IntoIterator<Item = ErrorDefinition>>(errors: I) -> Self {
+        let mut registry = Self::new();
+        for error in errors {
+            registry.insert(error);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_exit_codes() {
+        let registry: ErrorRegistry = [
+            ErrorDefinition {
+                code: 12,
+                name: "InvalidPda".to_string(),
+                message: Some("Vault address does not match its seeds".to_string()),
+            },
+            ErrorDefinition {
+                code: 13,
+                name: "Overflow".to_string(),
+                message: None,
+            },
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(registry.describe(0), None);
+        assert_eq!(
+            registry.describe(12).as_deref(),
+            Some("InvalidPda: Vault address does not match its seeds")
+        );
+        assert_eq!(registry.describe(13).as_deref(), Some("Overflow"));
+        assert_eq!(registry.describe(14).as_deref(), Some("Custom(14)"));
+        assert_eq!(registry.describe(1 << 32).as_deref(), Some("Custom(0)"));
+        assert_eq!(
+            registry.describe(8 << 32).as_deref(),
+            Some("MissingRequiredSignature")
+        );
+        assert_eq!(registry.describe(100 << 32), None);
+        assert_eq!(registry.describe((2 << 32) | 1), None);
+    }
+
+    #[test]
+    fn test_error_note_round_trip() {
+        let registry: ErrorRegistry = [
+            ErrorDefinition {
+                code: 6000,
+                name: "InvalidPda".to_string(),
+                message: Some("Vault address does not match its seeds".to_string()),
+            },
+            ErrorDefinition {
+                code: 7,
+                name: "Overflow".to_string(),
+                message: None,
+            },
+        ]
+        .into_iter()
+        .collect();
+
+        let bytes = registry.to_note_bytes();
+        assert!(bytes.len().is_multiple_of(4));
+        assert_eq!(ErrorRegistry::from_note_bytes(&bytes), Some(registry));
+        assert_eq!(
+            ErrorRegistry::from_note_bytes(&bytes[..bytes.len() - 4]),
+            None
+        );
+        assert_eq!(
+            ErrorRegistry::from_note_bytes(&[]),
+            Some(ErrorRegistry::new())
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use {
+    crate::{
+        instruction::Instruction,
+        opcode::{
+            BIN_IMM_OPS, BIN_REG_OPS, ENDIAN_OPS, LOAD_IMM_OPS, LOAD_MEMORY_OPS, Opcode, UNARY_OPS,
+        },
+        version::SbpfVersion,
+    },
+    std::collections::BTreeSet,
+    thiserror::Error,
+};
+
+/// Why the loader would reject a program. `pc` is the index of the offending
+/// instruction in 8-byte slots from the start of `.text`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VerifierError {
+    #[error("Program is empty")]
+    NoProgram,
+
+    #[error("Program length {0} is not a multiple of 8")]
+    ProgramLengthNotMultiple(usize),
+
+    #[error("Unknown opcode {opcode:#04x}")]
+    UnknownOpcode { pc: usize, opcode: u8 },
+
+    #[error("lddw is missing its second slot")]
+    IncompleteLddw { pc: usize },
+
+    #[error("Jump to instruction {target} is outside the program")]
+    JumpOutOfCode { pc: usize, target: i64 },
+
+    #[error("Jump to instruction {target} lands in the middle of an lddw")]
+    JumpToMiddleOfLddw { pc: usize, target: usize },
+
+    #[error("Call to instruction {target} is outside the program")]
+    CallOutOfCode { pc: usize, target: i64 },
+
+    #[error("Division by zero")]
+    DivisionByZero { pc: usize },
+
+    #[error("Shift by {shift} overflows a {bits}-bit register")]
+    ShiftWithOverflow { pc: usize, shift: i32, bits: u32 },
+
+    #[error("r{register} is not a register")]
+    InvalidRegister { pc: usize, register: u8 },
+
+    #[error("r{register} is read-only")]
+    CannotWriteRegister { pc: usize, register: u8 },
+
+    #[error("Execution falls through the end of the function")]
+    FallthroughOffFunction { pc: usize },
+}
+
+impl VerifierError {
+    /// The offending instruction, for errors about one.
+    pub fn pc(&self) -> Option<usize> {
+        match self {
+            Self::NoProgram | Self::ProgramLengthNotMultiple(_) => None,
+            Self::UnknownOpcode { pc, .. }
+            | Self::IncompleteLddw { pc }
+            | Self::JumpOutOfCode { pc, .. }
+            | Self::JumpToMiddleOfLddw { pc, .. }
+            | Self::CallOutOfCode { pc, .. }
+            | Self::DivisionByZero { pc }
+            | Self::ShiftWithOverflow { pc, .. }
+            | Self::InvalidRegister { pc, .. }
+            | Self::CannotWriteRegister { pc, .. }
+            | Self::FallthroughOffFunction { pc } => Some(*pc),
+        }
+    }
+}
+
+/// Check `bytecode`, the contents of `.text`, the way the loader does before
+/// a program is deployed, returning every problem found.
+///
+/// Jumps and calls must land on an instruction, constant divisors must be
+/// non-zero, constant shifts must fit the register, only r0-r9 may be
+/// written and the program must not run off its end. Programs for v3 must
+/// also not run from one function into the next.
+pub fn verify(bytecode: &[u8], version: SbpfVersion) -> Result<(), Vec<VerifierError>> {
+    if bytecode.is_empty() {
+        return Err(vec![VerifierError::NoProgram]);
+    }
+    if !bytecode.len().is_multiple_of(8) {
+        return Err(vec![VerifierError::ProgramLengthNotMultiple(
+            bytecode.len(),
+        )]);
+    }
+
+    let slots = bytecode.len() / 8;
+    let mut errors = Vec::new();
+    // (pc, opcode) of every instruction, in order
+    let mut instructions = Vec::new();
+    // Second slots of lddw, which nothing may jump to
+    let mut lddw_tails = BTreeSet::new();
+    // (pc, target) of jumps within the program
+    let mut jumps = Vec::new();
+    let mut functions = BTreeSet::from([0]);
+
+    let mut pc = 0;
+    while pc < slots {
+        let bytes = &bytecode[pc * 8..];
+        if bytes[0] == Opcode::Lddw.into() && bytes.len() < 16 {
+            errors.push(VerifierError::IncompleteLddw { pc });
+            break;
+        }
+        let instruction = match Instruction::from_bytes_for(bytes, version) {
+            Ok(instruction) => instruction,
+            Err(_) => {
+                errors.push(VerifierError::UnknownOpcode {
+                    pc,
+                    opcode: bytes[0],
+                });
+                pc += 1;
+                continue;
+            }
+        };
+        let opcode = instruction.opcode;
+        let (dst, src) = (bytes[1] & 0x0f, bytes[1] >> 4);
+        let off = i16::from_le_bytes([bytes[2], bytes[3]]) as i64;
+        let imm = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        instructions.push((pc, opcode));
+
+        for register in [dst, src] {
+            if register > 10 {
+                errors.push(VerifierError::InvalidRegister { pc, register });
+            }
+        }
+        if dst == 10 && writes_dst(opcode) && !adjusts_stack(opcode, version) {
+            errors.push(VerifierError::CannotWriteRegister { pc, register: dst });
+        }
+
+        match opcode {
+            // v2 splits lddw into two instructions, either of which may be
+            // jumped to.
+            Opcode::Lddw if !version.disable_lddw() => {
+                lddw_tails.insert(pc + 1);
+            }
+            Opcode::Call if src == 1 => {
+                let target = pc as i64 + 1 + imm as i64;
+                match usize::try_from(target) {
+                    Ok(target) if target < slots => {
+                        functions.insert(target);
+                    }
+                    _ => errors.push(VerifierError::CallOutOfCode { pc, target }),
+                }
+            }
+            Opcode::Div32Imm
+            | Opcode::Div64Imm
+            | Opcode::Mod32Imm
+            | Opcode::Mod64Imm
+            | Opcode::Udiv32Imm
+            | Opcode::Udiv64Imm
+            | Opcode::Urem32Imm
+            | Opcode::Urem64Imm
+            | Opcode::Sdiv32Imm
+            | Opcode::Sdiv64Imm
+            | Opcode::Srem32Imm
+            | Opcode::Srem64Imm
+                if imm == 0 =>
+            {
+                errors.push(VerifierError::DivisionByZero { pc });
+            }
+            Opcode::Lsh32Imm | Opcode::Rsh32Imm | Opcode::Arsh32Imm if !(0..32).contains(&imm) => {
+                errors.push(VerifierError::ShiftWithOverflow {
+                    pc,
+                    shift: imm,
+                    bits: 32,
+                });
+            }
+            Opcode::Lsh64Imm | Opcode::Rsh64Imm | Opcode::Arsh64Imm if !(0..64).contains(&imm) => {
+                errors.push(VerifierError::ShiftWithOverflow {
+                    pc,
+                    shift: imm,
+                    bits: 64,
+                });
+            }
+            _ if instruction.is_jump() => {
+                let target = pc as i64 + 1 + off;
+                match usize::try_from(target) {
+                    Ok(target) if target < slots => jumps.push((pc, target)),
+                    _ => errors.push(VerifierError::JumpOutOfCode { pc, target }),
+                }
+            }
+            _ => {}
+        }
+        pc += instruction.get_size() as usize / 8;
+    }
+
+    for (pc, target) in jumps {
+        if lddw_tails.contains(&target) {
+            errors.push(VerifierError::JumpToMiddleOfLddw { pc, target });
+        }
+    }
+
+    // The last instruction of each function, and of the program, has to
+    // leave it.
+    let ends_function = |opcode| matches!(opcode, Opcode::Exit | Opcode::Ja);
+    for window in instructions.windows(2) {
+        let [(pc, opcode), (next, _)] = window else {
+            continue;
+        };
+        if version.is_v3() && functions.contains(next) && !ends_function(*opcode) {
+            errors.push(VerifierError::FallthroughOffFunction { pc: *pc });
+        }
+    }
+    if let Some(&(pc, opcode)) = instructions.last()
+        && !ends_function(opcode)
+    {
+        errors.push(VerifierError::FallthroughOffFunction { pc });
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        errors.sort_by_key(VerifierError::pc);
+        Err(errors)
+    }
+}
+
+/// Whether `opcode` writes its destination register.
+fn writes_dst(opcode: Opcode) -> bool {
+    [
+        LOAD_IMM_OPS,
+        LOAD_MEMORY_OPS,
+        BIN_IMM_OPS,
+        BIN_REG_OPS,
+        ENDIAN_OPS,
+        UNARY_OPS,
+    ]
+    .iter()
+    .any(|ops| ops.contains(&opcode))
+}
+
+/// `add64 r10, imm` grows or shrinks the stack frame in versions with
+/// dynamic stack frames.
+fn adjusts_stack(opcode: Opcode, version: SbpfVersion) -> bool {
+    opcode == Opcode::Add64Imm && version.dynamic_stack_frames()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(opcode: u8, dst: u8, src: u8, off: i16, imm: i32) -> Vec<u8> {
+        let mut bytes = vec![opcode, src << 4 | dst];
+        bytes.extend(off.to_le_bytes());
+        bytes.extend(imm.to_le_bytes());
+        bytes
+    }
+
+    fn program(slots: &[Vec<u8>]) -> Vec<u8> {
+        slots.concat()
+    }
+
+    const EXIT: u8 = 0x95;
+    const JA: u8 = 0x05;
+    const JEQ_IMM: u8 = 0x15;
+    const MOV64_IMM: u8 = 0xb7;
+    const ADD64_IMM: u8 = 0x07;
+    const DIV64_IMM: u8 = 0x37;
+    const LSH32_IMM: u8 = 0x64;
+    const LDDW: u8 = 0x18;
+    const CALL: u8 = 0x85;
+
+    #[test]
+    fn test_valid_program() {
+        let bytecode = program(&[
+            slot(LDDW, 1, 0, 0, 1),
+            slot(0, 0, 0, 0, 0),
+            slot(JEQ_IMM, 1, 0, 1, 0),
+            slot(DIV64_IMM, 1, 0, 0, 3),
+            slot(EXIT, 0, 0, 0, 0),
+        ]);
+        assert_eq!(verify(&bytecode, SbpfVersion::V0), Ok(()));
+    }
+
+    #[test]
+    fn test_program_shape() {
+        assert_eq!(
+            verify(&[], SbpfVersion::V0),
+            Err(vec![VerifierError::NoProgram])
+        );
+        assert_eq!(
+            verify(&[EXIT, 0, 0, 0], SbpfVersion::V0),
+            Err(vec![VerifierError::ProgramLengthNotMultiple(4)])
+        );
+        assert_eq!(
+            verify(&program(&[slot(LDDW, 1, 0, 0, 1)]), SbpfVersion::V0),
+            Err(vec![VerifierError::IncompleteLddw { pc: 0 }])
+        );
+        assert_eq!(
+            verify(&program(&[slot(0xff, 0, 0, 0, 0)]), SbpfVersion::V0),
+            Err(vec![VerifierError::UnknownOpcode {
+                pc: 0,
+                opcode: 0xff
+            }])
+        );
+    }
+
+    #[test]
+    fn test_jump_targets() {
+        let bytecode = program(&[
+            slot(JEQ_IMM, 1, 0, 5, 0),
+            slot(JA, 0, 0, -3, 0),
+            slot(JA, 0, 0, 1, 0),
+            slot(LDDW, 1, 0, 0, 1),
+            slot(0, 0, 0, 0, 0),
+            slot(EXIT, 0, 0, 0, 0),
+        ]);
+        assert_eq!(
+            verify(&bytecode, SbpfVersion::V0),
+            Err(vec![
+                VerifierError::JumpOutOfCode { pc: 0, target: 6 },
+                VerifierError::JumpOutOfCode { pc: 1, target: -1 },
+                VerifierError::JumpToMiddleOfLddw { pc: 2, target: 4 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_constant_operands() {
+        let bytecode = program(&[
+            slot(DIV64_IMM, 1, 0, 0, 0),
+            slot(LSH32_IMM, 1, 0, 0, 32),
+            slot(LSH32_IMM, 1, 0, 0, 31),
+            slot(EXIT, 0, 0, 0, 0),
+        ]);
+        assert_eq!(
+            verify(&bytecode, SbpfVersion::V0),
+            Err(vec![
+                VerifierError::DivisionByZero { pc: 0 },
+                VerifierError::ShiftWithOverflow {
+                    pc: 1,
+                    shift: 32,
+                    bits: 32
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_registers() {
+        let bytecode = program(&[
+            slot(MOV64_IMM, 10, 0, 0, 1),
+            slot(MOV64_IMM, 11, 0, 0, 1),
+            slot(ADD64_IMM, 10, 0, 0, -64),
+            slot(EXIT, 0, 0, 0, 0),
+        ]);
+        assert_eq!(
+            verify(&bytecode, SbpfVersion::V0),
+            Err(vec![
+                VerifierError::CannotWriteRegister {
+                    pc: 0,
+                    register: 10
+                },
+                VerifierError::InvalidRegister {
+                    pc: 1,
+                    register: 11
+                },
+                VerifierError::CannotWriteRegister {
+                    pc: 2,
+                    register: 10
+                },
+            ])
+        );
+        // Dynamic stack frames let r10 move.
+        assert_eq!(
+            verify(&bytecode[8..], SbpfVersion::V1).unwrap_err(),
+            [VerifierError::InvalidRegister {
+                pc: 0,
+                register: 11
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fallthrough() {
+        let bytecode = program(&[
+            slot(CALL, 0, 1, 0, 1),
+            slot(EXIT, 0, 0, 0, 0),
+            slot(MOV64_IMM, 0, 0, 0, 1),
+            slot(MOV64_IMM, 0, 0, 0, 2),
+        ]);
+        assert_eq!(
+            verify(&bytecode, SbpfVersion::V0),
+            Err(vec![VerifierError::FallthroughOffFunction { pc: 3 }])
+        );
+
+        // In v3, running from one function into the next is rejected too.
+        let bytecode = program(&[
+            slot(CALL, 0, 1, 0, 1),
+            slot(MOV64_IMM, 0, 0, 0, 1),
+            slot(MOV64_IMM, 0, 0, 0, 2),
+            slot(EXIT, 0, 0, 0, 0),
+        ]);
+        assert_eq!(verify(&bytecode, SbpfVersion::V0), Ok(()));
+        assert_eq!(
+            verify(&bytecode, SbpfVersion::V3),
+            Err(vec![VerifierError::FallthroughOffFunction { pc: 1 }])
+        );
+        assert_eq!(
+            verify(&program(&[slot(CALL, 0, 1, 0, 7)]), SbpfVersion::V0).unwrap_err()[0],
+            VerifierError::CallOutOfCode { pc: 0, target: 8 }
+        );
+    }
+}
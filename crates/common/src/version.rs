@@ -0,0 +1,203 @@
+use {
+    crate::opcode::{JUMP32_IMM_OPS, JUMP32_REG_OPS, Opcode},
+    core::{fmt, str::FromStr},
+};
+
+/// sBPF instruction set version, recorded in a program's ELF `e_flags`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SbpfVersion {
+    V0,
+    /// v0's instruction set with dynamic stack frames.
+    V1,
+    /// Moves the memory instruction classes, replaces mul/div/mod with the
+    /// product/quotient/remainder family and drops `lddw`, `neg` and `le`.
+    V2,
+    /// v0's instruction set with 32-bit jumps, static syscalls and a fixed
+    /// ELF layout.
+    #[default]
+    V3,
+}
+
+impl SbpfVersion {
+    pub fn from_e_flags(e_flags: u32) -> Option<Self> {
+        match e_flags {
+            0 => Some(Self::V0),
+            1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            3 => Some(Self::V3),
+            _ => None,
+        }
+    }
+
+    pub fn e_flags(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn is_v3(&self) -> bool {
+        matches!(self, Self::V3)
+    }
+
+    /// The product/quotient/remainder opcodes and `hor64` replace mul, div
+    /// and mod.
+    pub fn enable_pqr(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    /// Loads and stores use the ALU classes' old mul/div/mod/neg slots.
+    pub fn move_memory_instruction_classes(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    /// `lddw` is encoded as its low word followed by `hor64` for the high
+    /// word.
+    pub fn disable_lddw(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    pub fn disable_neg(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    pub fn disable_le(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    /// `callx` takes its target register in the src field rather than imm.
+    pub fn callx_uses_src_reg(&self) -> bool {
+        matches!(self, Self::V2)
+    }
+
+    /// `add64 r10, imm` moves the frame pointer, and calls leave it where
+    /// the caller put it.
+    pub fn dynamic_stack_frames(&self) -> bool {
+        !matches!(self, Self::V0)
+    }
+
+    pub fn enable_jmp32(&self) -> bool {
+        matches!(self, Self::V3)
+    }
+
+    /// Whether `opcode` is part of this version's instruction set.
+    pub fn supports(&self, opcode: Opcode) -> bool {
+        match opcode {
+            _ if JUMP32_IMM_OPS.contains(&opcode) || JUMP32_REG_OPS.contains(&opcode) => {
+                self.enable_jmp32()
+            }
+            Opcode::Hor64Imm
+            | Opcode::Lmul32Imm
+            | Opcode::Lmul32Reg
+            | Opcode::Udiv32Imm
+            | Opcode::Udiv32Reg
+            | Opcode::Urem32Imm
+            | Opcode::Urem32Reg
+            | Opcode::Sdiv32Imm
+            | Opcode::Sdiv32Reg
+            | Opcode::Srem32Imm
+            | Opcode::Srem32Reg
+            | Opcode::Lmul64Imm
+            | Opcode::Lmul64Reg
+            | Opcode::Uhmul64Imm
+            | Opcode::Uhmul64Reg
+            | Opcode::Udiv64Imm
+            | Opcode::Udiv64Reg
+            | Opcode::Urem64Imm
+            | Opcode::Urem64Reg
+            | Opcode::Shmul64Imm
+            | Opcode::Shmul64Reg
+            | Opcode::Sdiv64Imm
+            | Opcode::Sdiv64Reg
+            | Opcode::Srem64Imm
+            | Opcode::Srem64Reg => self.enable_pqr(),
+            Opcode::Mul32Imm
+            | Opcode::Mul32Reg
+            | Opcode::Div32Imm
+            | Opcode::Div32Reg
+            | Opcode::Mod32Imm
+            | Opcode::Mod32Reg
+            | Opcode::Mul64Imm
+            | Opcode::Mul64Reg
+            | Opcode::Div64Imm
+            | Opcode::Div64Reg
+            | Opcode::Mod64Imm
+            | Opcode::Mod64Reg => !self.enable_pqr(),
+            Opcode::Neg32 | Opcode::Neg64 => !self.disable_neg(),
+            Opcode::Le => !self.disable_le(),
+            _ => true,
+        }
+    }
+
+    /// The oldest version whose instruction set includes `opcode`.
+    pub fn introduced(opcode: Opcode) -> Option<Self> {
+        [Self::V0, Self::V1, Self::V2, Self::V3]
+            .into_iter()
+            .find(|version| version.supports(opcode))
+    }
+}
+
+impl fmt::Display for SbpfVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.e_flags())
+    }
+}
+
+impl FromStr for SbpfVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "v0" => Ok(Self::V0),
+            "v1" => Ok(Self::V1),
+            "v2" => Ok(Self::V2),
+            "v3" => Ok(Self::V3),
+            _ => Err(format!("unknown sBPF version '{}', expected v0-v3", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_e_flags_round_trip() {
+        for version in [
+            SbpfVersion::V0,
+            SbpfVersion::V1,
+            SbpfVersion::V2,
+            SbpfVersion::V3,
+        ] {
+            assert_eq!(SbpfVersion::from_e_flags(version.e_flags()), Some(version));
+            assert_eq!(version.to_string().parse::<SbpfVersion>(), Ok(version));
+        }
+        assert_eq!(SbpfVersion::from_e_flags(4), None);
+        assert!("v4".parse::<SbpfVersion>().is_err());
+    }
+
+    #[test]
+    fn test_supports() {
+        assert!(SbpfVersion::V0.supports(Opcode::Mul64Imm));
+        assert!(!SbpfVersion::V0.supports(Opcode::Lmul64Imm));
+        assert!(!SbpfVersion::V0.supports(Opcode::Jeq32Imm));
+        assert!(SbpfVersion::V2.supports(Opcode::Lmul64Imm));
+        assert!(SbpfVersion::V2.supports(Opcode::Hor64Imm));
+        assert!(SbpfVersion::V2.supports(Opcode::Lddw));
+        assert!(!SbpfVersion::V2.supports(Opcode::Mul64Imm));
+        assert!(!SbpfVersion::V2.supports(Opcode::Neg64));
+        assert!(!SbpfVersion::V2.supports(Opcode::Le));
+        assert!(SbpfVersion::V3.supports(Opcode::Jeq32Imm));
+        assert!(!SbpfVersion::V3.supports(Opcode::Udiv64Reg));
+
+        assert_eq!(
+            SbpfVersion::introduced(Opcode::Add64Imm),
+            Some(SbpfVersion::V0)
+        );
+        assert_eq!(
+            SbpfVersion::introduced(Opcode::Srem32Reg),
+            Some(SbpfVersion::V2)
+        );
+        assert_eq!(
+            SbpfVersion::introduced(Opcode::Jne32Reg),
+            Some(SbpfVersion::V3)
+        );
+    }
+}
@@ -31,6 +31,31 @@ pub enum DebugMode {
     Continue,
 }
 
+/// Where a breakpoint stops: a source line, a byte offset into `.text`, or
+/// the start of a label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointLocation {
+    Line(usize),
+    Address(u64),
+    Label(String),
+}
+
+impl BreakpointLocation {
+    /// Decimal numbers are lines, `0x` numbers are addresses and anything
+    /// else is a label.
+    pub fn parse(spec: &str) -> Self {
+        if let Some(hex) = spec.strip_prefix("0x")
+            && let Ok(address) = u64::from_str_radix(hex, 16)
+        {
+            Self::Address(address)
+        } else if let Ok(line) = spec.parse() {
+            Self::Line(line)
+        } else {
+            Self::Label(spec.to_string())
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DebugEvent {
     Stopped(u64, Option<usize>),
@@ -43,6 +68,10 @@ pub struct Debugger {
     pub runtime: Runtime,
     pub breakpoints: HashSet<u64>,
     pub line_breakpoints: HashSet<usize>,
+    /// Breakpoints set by address or label rather than by line.
+    pub address_breakpoints: HashSet<u64>,
+    /// `.text` labels with their byte offsets.
+    pub labels: Vec<(String, u64)>,
     pub dwarf_line_map: Option<LineMap>,
     pub rodata: Option<Vec<RODataSymbol>>,
     pub last_breakpoint: Option<u64>,
@@ -73,6 +102,8 @@ impl Debugger {
             runtime,
             breakpoints: HashSet::new(),
             line_breakpoints: HashSet::new(),
+            address_breakpoints: HashSet::new(),
+            labels: Vec::new(),
             dwarf_line_map: None,
             rodata: None,
             last_breakpoint: None,
@@ -94,10 +125,72 @@ impl Debugger {
         self.rodata = Some(rodata);
     }
 
+    pub fn set_labels(&mut self, labels: Vec<(String, u64)>) {
+        self.labels = labels;
+    }
+
+    pub fn get_pc_for_label(&self, name: &str) -> Option<u64> {
+        self.labels
+            .iter()
+            .find(|(label, _)| label == name)
+            .map(|(_, pc)| *pc)
+    }
+
+    pub fn get_label_for_pc(&self, pc: u64) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, offset)| *offset == pc)
+            .map(|(label, _)| label.as_str())
+    }
+
     pub fn set_breakpoint(&mut self, pc: u64) {
         self.breakpoints.insert(pc);
     }
 
+    pub fn set_breakpoint_at(&mut self, location: &BreakpointLocation) -> Result<(), String> {
+        match location {
+            BreakpointLocation::Line(line) => self.set_breakpoint_at_line(*line),
+            BreakpointLocation::Address(pc) => self.set_breakpoint_at_address(*pc),
+            BreakpointLocation::Label(name) => {
+                let pc = self
+                    .get_pc_for_label(name)
+                    .ok_or_else(|| format!("No label named '{}'", name))?;
+                self.set_breakpoint_at_address(pc)
+            }
+        }
+    }
+
+    pub fn remove_breakpoint_at(&mut self, location: &BreakpointLocation) -> Result<(), String> {
+        let pc = match location {
+            BreakpointLocation::Line(line) => return self.remove_breakpoint_at_line(*line),
+            BreakpointLocation::Address(pc) => *pc,
+            BreakpointLocation::Label(name) => self
+                .get_pc_for_label(name)
+                .ok_or_else(|| format!("No label named '{}'", name))?,
+        };
+        if !self.address_breakpoints.remove(&pc) {
+            return Err(format!("No breakpoint at 0x{:04x}", pc));
+        }
+        // Keep the stop if a line breakpoint still covers it.
+        if !self
+            .line_breakpoints
+            .iter()
+            .any(|line| self.get_pcs_for_line(*line).contains(&pc))
+        {
+            self.breakpoints.remove(&pc);
+        }
+        Ok(())
+    }
+
+    fn set_breakpoint_at_address(&mut self, pc: u64) -> Result<(), String> {
+        if self.instruction_offsets.binary_search(&pc).is_err() {
+            return Err(format!("No instruction at 0x{:04x}", pc));
+        }
+        self.address_breakpoints.insert(pc);
+        self.breakpoints.insert(pc);
+        Ok(())
+    }
+
     pub fn set_breakpoint_at_line(&mut self, line: usize) -> Result<(), String> {
         if let Some(dwarf_map) = &self.dwarf_line_map {
             let pcs = dwarf_map.get_pcs_for_line(line);
@@ -149,17 +242,22 @@ impl Debugger {
     }
 
     pub fn get_breakpoints_info(&self) -> String {
-        if self.line_breakpoints.is_empty() {
+        if self.line_breakpoints.is_empty() && self.address_breakpoints.is_empty() {
             return "No breakpoints set".to_string();
         }
         let mut lines: Vec<_> = self.line_breakpoints.iter().copied().collect();
         lines.sort();
-        let lines_str = lines
+        let mut addresses: Vec<_> = self.address_breakpoints.iter().copied().collect();
+        addresses.sort();
+        let entries: Vec<String> = lines
             .iter()
-            .map(|l| l.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
-        format!("Breakpoints: {}", lines_str)
+            .map(|line| format!("line {}", line))
+            .chain(addresses.iter().map(|&pc| match self.get_label_for_pc(pc) {
+                Some(label) => format!("0x{:04x} ({})", pc, label),
+                None => format!("0x{:04x}", pc),
+            }))
+            .collect();
+        format!("Breakpoints: {}", entries.join(", "))
     }
 
     pub fn set_debug_mode(&mut self, debug_mode: DebugMode) {
@@ -377,6 +475,9 @@ impl Debugger {
     }
 
     pub fn clear_breakpoints(&mut self) {
+        for pc in self.address_breakpoints.drain() {
+            self.breakpoints.remove(&pc);
+        }
         if let Some(dwarf_map) = &self.dwarf_line_map {
             let lines: Vec<usize> = self.line_breakpoints.iter().copied().collect();
             for line in lines {
@@ -631,3 +732,28 @@ impl DebuggerInterface for Debugger {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_breakpoint_location() {
+        assert_eq!(
+            BreakpointLocation::parse("12"),
+            BreakpointLocation::Line(12)
+        );
+        assert_eq!(
+            BreakpointLocation::parse("0x28"),
+            BreakpointLocation::Address(0x28)
+        );
+        assert_eq!(
+            BreakpointLocation::parse("helper"),
+            BreakpointLocation::Label("helper".to_string())
+        );
+        assert_eq!(
+            BreakpointLocation::parse("0xend"),
+            BreakpointLocation::Label("0xend".to_string())
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use {
     crate::error::{DebuggerError, DebuggerResult},
+    sbpf_runtime::config::ClockWarp,
     serde::Deserialize,
     solana_account::Account,
     solana_address::Address,
     solana_instruction::{AccountMeta, Instruction},
-    std::{fs, path::Path, str::FromStr},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
 };
 
 #[derive(Deserialize)]
@@ -13,6 +18,18 @@ struct DebuggerInput {
     accounts: Vec<AccountJson>,
     #[serde(default)]
     programs: Vec<ProgramJson>,
+    #[serde(default)]
+    clock: ClockJson,
+}
+
+#[derive(Deserialize)]
+struct TransactionInput {
+    instructions: Vec<InstructionJson>,
+    accounts: Vec<AccountJson>,
+    #[serde(default)]
+    programs: Vec<ProgramJson>,
+    #[serde(default)]
+    clock: ClockJson,
 }
 
 #[derive(Deserialize)]
@@ -41,6 +58,13 @@ struct AccountJson {
     executable: bool,
 }
 
+#[derive(Deserialize, Default)]
+struct ClockJson {
+    slot: Option<u64>,
+    epoch: Option<u64>,
+    unix_timestamp: Option<i64>,
+}
+
 #[derive(Deserialize)]
 struct ProgramJson {
     program_id: String,
@@ -51,6 +75,16 @@ pub struct ParsedInput {
     pub instruction: Instruction,
     pub accounts: Vec<(Address, Account)>,
     pub programs: Vec<(Address, Vec<u8>)>,
+    pub clock: ClockWarp,
+}
+
+/// The instructions of a transaction, run in order against the same
+/// accounts.
+pub struct ParsedTransaction {
+    pub instructions: Vec<Instruction>,
+    pub accounts: Vec<(Address, Account)>,
+    pub programs: Vec<(Address, Vec<u8>)>,
+    pub clock: ClockWarp,
 }
 
 pub fn parse_input(input: &str) -> DebuggerResult<ParsedInput> {
@@ -61,26 +95,63 @@ pub fn parse_input(input: &str) -> DebuggerResult<ParsedInput> {
             instruction: Instruction::new_with_bytes(program_id, &[], vec![]),
             accounts: Vec::new(),
             programs: Vec::new(),
+            clock: ClockWarp::default(),
         });
     }
 
-    // Handle both JSON file path or JSON string.
+    let (json_str, base_dir) = read_input(input)?;
+    let debugger_input: DebuggerInput =
+        serde_json::from_str(&json_str).map_err(|e| DebuggerError::InvalidInput(e.to_string()))?;
+
+    Ok(ParsedInput {
+        instruction: parse_instruction(&debugger_input.instruction)?,
+        accounts: parse_accounts(&debugger_input.accounts)?,
+        programs: read_programs(&debugger_input.programs, &base_dir)?,
+        clock: debugger_input.clock.into(),
+    })
+}
+
+/// Parse a transaction: the same JSON as [`parse_input`], with a list of
+/// `instructions` in place of the single `instruction`.
+pub fn parse_transaction(input: &str) -> DebuggerResult<ParsedTransaction> {
+    let (json_str, base_dir) = read_input(input.trim())?;
+    let transaction: TransactionInput =
+        serde_json::from_str(&json_str).map_err(|e| DebuggerError::InvalidInput(e.to_string()))?;
+    if transaction.instructions.is_empty() {
+        return Err(DebuggerError::InvalidInput(
+            "The transaction has no instructions".to_string(),
+        ));
+    }
+
+    Ok(ParsedTransaction {
+        instructions: transaction
+            .instructions
+            .iter()
+            .map(parse_instruction)
+            .collect::<DebuggerResult<Vec<_>>>()?,
+        accounts: parse_accounts(&transaction.accounts)?,
+        programs: read_programs(&transaction.programs, &base_dir)?,
+        clock: transaction.clock.into(),
+    })
+}
+
+/// The JSON of `input`, a file path or a JSON string, and the directory
+/// paths in it are relative to.
+fn read_input(input: &str) -> DebuggerResult<(String, PathBuf)> {
     let input_path = Path::new(input);
-    let (json_str, base_dir) = if input_path.exists() {
+    if input_path.exists() {
         let base = input_path.parent().unwrap_or(Path::new(".")).to_path_buf();
-        (fs::read_to_string(input)?, base)
+        Ok((fs::read_to_string(input)?, base))
     } else {
-        (input.to_string(), Path::new(".").to_path_buf())
-    };
-
-    let debugger_input: DebuggerInput =
-        serde_json::from_str(&json_str).map_err(|e| DebuggerError::InvalidInput(e.to_string()))?;
+        Ok((input.to_string(), Path::new(".").to_path_buf()))
+    }
+}
 
-    let program_id = Address::from_str(&debugger_input.instruction.program_id)
+fn parse_instruction(instruction: &InstructionJson) -> DebuggerResult<Instruction> {
+    let program_id = Address::from_str(&instruction.program_id)
         .map_err(|e| DebuggerError::InvalidInput(format!("Invalid program_id: {}", e)))?;
 
-    let account_metas: Vec<AccountMeta> = debugger_input
-        .instruction
+    let account_metas: Vec<AccountMeta> = instruction
         .accounts
         .iter()
         .map(|a| {
@@ -94,20 +165,23 @@ pub fn parse_input(input: &str) -> DebuggerResult<ParsedInput> {
         })
         .collect::<DebuggerResult<Vec<_>>>()?;
 
-    let instruction_data = if debugger_input.instruction.data.is_empty() {
+    let instruction_data = if instruction.data.is_empty() {
         Vec::new()
     } else {
-        bs58::decode(&debugger_input.instruction.data)
-            .into_vec()
-            .map_err(|e| {
-                DebuggerError::InvalidInput(format!("Invalid base58 instruction data: {}", e))
-            })?
+        bs58::decode(&instruction.data).into_vec().map_err(|e| {
+            DebuggerError::InvalidInput(format!("Invalid base58 instruction data: {}", e))
+        })?
     };
 
-    let instruction = Instruction::new_with_bytes(program_id, &instruction_data, account_metas);
+    Ok(Instruction::new_with_bytes(
+        program_id,
+        &instruction_data,
+        account_metas,
+    ))
+}
 
-    let accounts: Vec<(Address, Account)> = debugger_input
-        .accounts
+fn parse_accounts(accounts: &[AccountJson]) -> DebuggerResult<Vec<(Address, Account)>> {
+    accounts
         .iter()
         .map(|a| {
             let pubkey = Address::from_str(&a.pubkey)
@@ -132,10 +206,15 @@ pub fn parse_input(input: &str) -> DebuggerResult<ParsedInput> {
                 },
             ))
         })
-        .collect::<DebuggerResult<Vec<_>>>()?;
+        .collect()
+}
 
-    let programs: Vec<(Address, Vec<u8>)> = debugger_input
-        .programs
+/// Read the ELF of each program, relative to `base_dir`.
+fn read_programs(
+    programs: &[ProgramJson],
+    base_dir: &Path,
+) -> DebuggerResult<Vec<(Address, Vec<u8>)>> {
+    programs
         .iter()
         .map(|p| {
             let program_id = Address::from_str(&p.program_id)
@@ -150,13 +229,17 @@ pub fn parse_input(input: &str) -> DebuggerResult<ParsedInput> {
             })?;
             Ok((program_id, elf_bytes))
         })
-        .collect::<DebuggerResult<Vec<_>>>()?;
+        .collect()
+}
 
-    Ok(ParsedInput {
-        instruction,
-        accounts,
-        programs,
-    })
+impl From<ClockJson> for ClockWarp {
+    fn from(clock: ClockJson) -> Self {
+        ClockWarp {
+            slot: clock.slot,
+            epoch: clock.epoch,
+            unix_timestamp: clock.unix_timestamp,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,5 +284,55 @@ mod tests {
         assert_eq!(parsed.instruction.program_id, program_id);
         assert_eq!(parsed.accounts.len(), 1);
         assert_eq!(parsed.accounts[0].0, account_pubkey);
+        assert!(parsed.clock.slot.is_none());
+    }
+
+    #[test]
+    fn test_parse_clock() {
+        let json = format!(
+            r#"{{
+                "instruction": {{ "program_id": "{}", "accounts": [] }},
+                "accounts": [],
+                "clock": {{ "slot": 1000, "unix_timestamp": 1700000000 }}
+            }}"#,
+            Address::new_unique()
+        );
+
+        let parsed = parse_input(&json).unwrap();
+        assert_eq!(parsed.clock.slot, Some(1000));
+        assert_eq!(parsed.clock.epoch, None);
+        assert_eq!(parsed.clock.unix_timestamp, Some(1_700_000_000));
+    }
+
+    #[test]
+    fn test_parse_transaction() {
+        let (counter, payer) = (Address::new_unique(), Address::new_unique());
+        let json = format!(
+            r#"{{
+                "instructions": [
+                    {{ "program_id": "{0}", "accounts": [{{ "pubkey": "{1}", "is_signer": true, "is_writable": true }}], "data": "1" }},
+                    {{ "program_id": "{0}", "accounts": [{{ "pubkey": "{1}", "is_signer": true, "is_writable": true }}], "data": "2" }}
+                ],
+                "accounts": [
+                    {{ "pubkey": "{1}", "owner": "{0}", "lamports": 1000000 }}
+                ]
+            }}"#,
+            counter, payer
+        );
+
+        let parsed = parse_transaction(&json).unwrap();
+        assert_eq!(parsed.instructions.len(), 2);
+        assert!(
+            parsed
+                .instructions
+                .iter()
+                .all(|ix| ix.program_id == counter)
+        );
+        assert_eq!(parsed.instructions[0].data, vec![0]);
+        assert_eq!(parsed.instructions[1].data, vec![1]);
+        assert_eq!(parsed.accounts[0].0, payer);
+
+        let empty = r#"{ "instructions": [], "accounts": [] }"#;
+        assert!(parse_transaction(empty).is_err());
     }
 }
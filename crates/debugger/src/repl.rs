@@ -1,18 +1,29 @@
 use {
     crate::{
-        debugger::{DebugEvent, DebugMode},
+        debugger::{BreakpointLocation, DebugEvent, DebugMode},
         runner::DebuggerSession,
     },
-    std::io::{self, Write},
+    std::{
+        collections::HashMap,
+        io::{self, Write},
+    },
 };
 
+/// Bytes `x` shows when no length is given.
+const DEFAULT_EXAMINE_LEN: usize = 32;
+
 pub struct Repl {
     pub session: DebuggerSession,
+    /// Lines of each source file shown so far, or `None` if it can't be read.
+    sources: HashMap<String, Option<Vec<String>>>,
 }
 
 impl Repl {
     pub fn new(session: DebuggerSession) -> Self {
-        Self { session }
+        Self {
+            session,
+            sources: HashMap::new(),
+        }
     }
 
     pub fn start(&mut self) {
@@ -23,14 +34,7 @@ impl Repl {
         }
 
         // Print the first instruction.
-        if let Some(line) = self.session.debugger.get_current_line() {
-            let asm = self
-                .session
-                .debugger
-                .get_instruction_asm()
-                .unwrap_or_default();
-            println!("{}\t{}", line, asm);
-        }
+        self.show_location();
 
         let stdin = io::stdin();
         loop {
@@ -48,41 +52,27 @@ impl Repl {
                 "continue" | "c" => self.run_and_display(DebugMode::Continue),
                 cmd if cmd.starts_with("break ") || cmd.starts_with("b ") => {
                     if let Some(arg) = cmd.split_whitespace().nth(1) {
-                        if let Ok(line) = arg.parse::<usize>() {
-                            match self.session.debugger.set_breakpoint_at_line(line) {
-                                Ok(()) => println!("Breakpoint set at line {}", line),
-                                Err(e) => println!("Error: {}", e),
-                            }
-                        } else {
-                            println!("Error: Invalid line number.");
+                        let location = BreakpointLocation::parse(arg);
+                        match self.session.debugger.set_breakpoint_at(&location) {
+                            Ok(()) => println!("Breakpoint set at {}", describe(&location)),
+                            Err(e) => println!("Error: {}", e),
                         }
                     }
                 }
                 cmd if cmd.starts_with("delete ") || cmd.starts_with("d ") => {
                     if let Some(arg) = cmd.split_whitespace().nth(1) {
-                        if let Ok(line) = arg.parse::<usize>() {
-                            match self.session.debugger.remove_breakpoint_at_line(line) {
-                                Ok(()) => println!("Breakpoint removed from line {}", line),
-                                Err(e) => println!("Error: {}", e),
-                            }
-                        } else {
-                            println!("Error: Invalid line number for delete command.");
+                        let location = BreakpointLocation::parse(arg);
+                        match self.session.debugger.remove_breakpoint_at(&location) {
+                            Ok(()) => println!("Breakpoint removed from {}", describe(&location)),
+                            Err(e) => println!("Error: {}", e),
                         }
                     }
                 }
+                cmd if cmd.starts_with("x ") => self.examine(cmd),
                 "info breakpoints" | "info b" => {
                     println!("{}", self.session.debugger.get_breakpoints_info());
                 }
-                "info line" => {
-                    if let Some(line) = self.session.debugger.get_current_line() {
-                        let asm = self
-                            .session
-                            .debugger
-                            .get_instruction_asm()
-                            .unwrap_or_default();
-                        println!("{}\t{}", line, asm);
-                    }
-                }
+                "info line" => self.show_location(),
                 "quit" | "q" => break,
                 "regs" => {
                     let regs = self.session.debugger.get_registers();
@@ -161,13 +151,16 @@ impl Repl {
                     println!("  next (n)                     - Step over");
                     println!("  finish (f)                   - Step out");
                     println!("  continue (c)                 - Continue execution");
-                    println!("  break (b) <line>             - Set breakpoint at line number");
-                    println!("  delete (d) <line>            - Remove breakpoint at line");
+                    println!(
+                        "  break (b) <where>            - Set breakpoint at a line, 0x address or label"
+                    );
+                    println!("  delete (d) <where>           - Remove breakpoint");
                     println!("  info breakpoints (info b)    - Show all breakpoints");
                     println!("  info line                    - Show current line info");
                     println!("  regs                         - Show all registers");
                     println!("  reg <idx>                    - Show single register");
                     println!("  setreg <idx> <value>         - Set register value");
+                    println!("  x <addr|rN|label> [len]      - Show memory as hex bytes");
                     println!("  compute                      - Show compute unit information");
                     println!("  help                         - Show this help");
                     println!("  quit (q)                     - Exit debugger");
@@ -181,26 +174,13 @@ impl Repl {
         self.session.debugger.set_debug_mode(mode);
         match self.session.debugger.run() {
             Ok(event) => match event {
-                DebugEvent::Stopped(_pc, line) => {
-                    if let Some(line_num) = line {
-                        let asm = self
-                            .session
-                            .debugger
-                            .get_instruction_asm()
-                            .unwrap_or_default();
-                        println!("{}\t{}", line_num, asm);
-                    }
-                }
-                DebugEvent::Breakpoint(_pc, line) => {
-                    if let Some(line_num) = line {
-                        let asm = self
-                            .session
-                            .debugger
-                            .get_instruction_asm()
-                            .unwrap_or_default();
-                        println!("Breakpoint hit at line {}", line_num);
-                        println!("{}\t{}", line_num, asm);
+                DebugEvent::Stopped(_, _) => self.show_location(),
+                DebugEvent::Breakpoint(pc, line) => {
+                    match line {
+                        Some(line_num) => println!("Breakpoint hit at line {}", line_num),
+                        None => println!("Breakpoint hit at 0x{:04x}", pc),
                     }
+                    self.show_location();
                 }
                 DebugEvent::Exit(code) => {
                     println!("Program exited with code: {}", code);
@@ -212,4 +192,111 @@ impl Repl {
             Err(e) => println!("Debugger error: {:?}", e),
         }
     }
+
+    /// Print where execution is stopped: the source line when the program
+    /// has debug info and its source can be read, the disassembled
+    /// instruction otherwise.
+    fn show_location(&mut self) {
+        let debugger = &self.session.debugger;
+        let pc = debugger.get_pc();
+        let asm = debugger.get_instruction_asm().unwrap_or_default();
+        if let Some(label) = debugger.get_label_for_pc(pc) {
+            println!("{}:", label);
+        }
+        let Some((file, line, _)) = debugger.get_source_location(pc) else {
+            println!("0x{:04x}\t{}", pc, asm);
+            return;
+        };
+        let file = file.to_string();
+        let lines = self.sources.entry(file.clone()).or_insert_with(|| {
+            std::fs::read_to_string(&file)
+                .ok()
+                .map(|source| source.lines().map(String::from).collect())
+        });
+        match lines
+            .as_ref()
+            .and_then(|lines| lines.get(line.checked_sub(1)?))
+        {
+            Some(source) => println!("{}\t{}", line, source.trim()),
+            None => println!("{}\t{}", line, asm),
+        }
+    }
+
+    /// `x <addr|rN|label> [len]`: dump memory starting at a number, the value
+    /// of a register, or a rodata label.
+    fn examine(&self, cmd: &str) {
+        let mut parts = cmd.split_whitespace().skip(1);
+        let Some(target) = parts.next() else {
+            println!("Usage: x <addr|rN|label> [len]");
+            return;
+        };
+        let debugger = &self.session.debugger;
+        let address = if let Some(index) = target.strip_prefix('r')
+            && let Ok(index) = index.parse::<usize>()
+        {
+            debugger.get_register(index)
+        } else if let Some(hex) = target.strip_prefix("0x") {
+            u64::from_str_radix(hex, 16).ok()
+        } else if let Ok(address) = target.parse::<u64>() {
+            Some(address)
+        } else {
+            debugger.get_rodata().and_then(|symbols| {
+                symbols
+                    .iter()
+                    .find(|symbol| symbol.name == target)
+                    .map(|symbol| symbol.address)
+            })
+        };
+        let Some(address) = address else {
+            println!("Unknown address '{}'", target);
+            return;
+        };
+        let len = match parts.next().map(str::parse::<usize>) {
+            Some(Ok(len)) => len,
+            Some(Err(_)) => {
+                println!("Invalid length");
+                return;
+            }
+            None => DEFAULT_EXAMINE_LEN,
+        };
+        match debugger.get_memory(address, len) {
+            Some(bytes) => print!("{}", hex_dump(address, &bytes)),
+            None => println!("Cannot read {} bytes at 0x{:016x}", len, address),
+        }
+    }
+}
+
+/// How `break` and `delete` echo a location back.
+fn describe(location: &BreakpointLocation) -> String {
+    match location {
+        BreakpointLocation::Line(line) => format!("line {}", line),
+        BreakpointLocation::Address(pc) => format!("0x{:04x}", pc),
+        BreakpointLocation::Label(label) => label.clone(),
+    }
+}
+
+/// Sixteen bytes per row, each row led by its address and followed by the
+/// printable ASCII characters.
+fn hex_dump(address: u64, bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        output.push_str(&format!(
+            "0x{:016x}  {:<47}  {}\n",
+            address + row as u64 * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    output
 }
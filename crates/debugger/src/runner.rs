@@ -5,10 +5,10 @@ use {
         input::ParsedInput,
         parser::{LineMap, rodata_from_section},
     },
-    sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+    sbpf_assembler::{Assembler, AssemblerOption, DebugMode, SymbolSection},
     sbpf_disassembler::program::Program,
     sbpf_runtime::{Runtime, config::RuntimeConfig},
-    sbpf_vm::memory::Memory,
+    sbpf_vm::{loader::load_elf, memory::Memory},
     std::path::{Path, PathBuf},
 };
 
@@ -49,11 +49,17 @@ pub fn load_session_from_asm(
         directory,
     });
     let assembler = Assembler::new(options);
-    let bytecode = assembler
-        .assemble(&source_code)
+    let artifacts = assembler
+        .assemble_full(&source_code)
         .map_err(|errors| crate::error::DebuggerError::Assembler(format!("{:?}", errors)))?;
+    let labels = artifacts
+        .symbols
+        .into_iter()
+        .filter(|symbol| symbol.section == SymbolSection::Text)
+        .map(|symbol| (symbol.name, symbol.offset))
+        .collect();
 
-    load_session_from_bytes(bytecode, parsed, config, None)
+    load_session_from_bytes(artifacts.elf, parsed, config, None, Some(labels))
 }
 
 pub fn load_session_from_elf(
@@ -62,7 +68,7 @@ pub fn load_session_from_elf(
     config: RuntimeConfig,
 ) -> DebuggerResult<DebuggerSession> {
     let elf_bytes = std::fs::read(elf_path)?;
-    load_session_from_bytes(elf_bytes, parsed, config, Some(elf_path.into()))
+    load_session_from_bytes(elf_bytes, parsed, config, Some(elf_path.into()), None)
 }
 
 fn load_session_from_bytes(
@@ -70,11 +76,13 @@ fn load_session_from_bytes(
     parsed: ParsedInput,
     config: RuntimeConfig,
     elf_path: Option<PathBuf>,
+    labels: Option<Vec<(String, u64)>>,
 ) -> DebuggerResult<DebuggerSession> {
     let mut runtime = Runtime::new(parsed.instruction.program_id, elf_bytes.clone(), config)?;
     for (program_id, elf) in &parsed.programs {
         runtime.add_program(program_id, elf.clone());
     }
+    runtime.sysvars_mut().apply_warp(&parsed.clock);
     runtime.prepare(&parsed.instruction, &parsed.accounts)?;
 
     let mut debugger = Debugger::new(runtime);
@@ -82,6 +90,20 @@ fn load_session_from_bytes(
         debugger.set_dwarf_line_map(line_map);
     }
 
+    // Without the source, fall back to the function symbols in the ELF.
+    let labels = labels.unwrap_or_else(|| match load_elf(&elf_bytes) {
+        Ok(program) => program
+            .symbols
+            .into_iter()
+            .filter_map(|(name, index)| {
+                let offset = *debugger.instruction_offsets.get(index)?;
+                Some((name, offset))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    });
+    debugger.set_labels(labels);
+
     // Best effort rodata symbol extraction: decode errors are ignored here
     // since the runtime already validated the program.
     if let Ok(program) = Program::from_bytes(&elf_bytes)
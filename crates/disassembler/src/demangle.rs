@@ -0,0 +1,124 @@
+/// Turns mangled symbol names into readable ones, for programs built by
+/// toolchains that mangle, such as rustc and clang.
+pub trait Demangler {
+    /// The readable form of `name`, or `None` to keep it as is.
+    fn demangle(&self, name: &str) -> Option<String>;
+}
+
+impl<F: Fn(&str) -> Option<String>> Demangler for F {
+    fn demangle(&self, name: &str) -> Option<String> {
+        self(name)
+    }
+}
+
+/// Demangles the legacy Rust scheme, `_ZN` followed by length-prefixed path
+/// segments and `E`, which rustc uses by default. The trailing hash segment
+/// and any `.llvm.*` suffix are dropped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustDemangler;
+
+impl Demangler for RustDemangler {
+    fn demangle(&self, name: &str) -> Option<String> {
+        let name = name.split_once(".llvm.").map_or(name, |(name, _)| name);
+        let mut rest = name
+            .strip_prefix("_ZN")
+            .or_else(|| name.strip_prefix("__ZN"))
+            .or_else(|| name.strip_prefix("ZN"))?;
+
+        let mut segments = Vec::new();
+        while !rest.starts_with('E') {
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            let len: usize = rest[..digits].parse().ok()?;
+            let end = digits.checked_add(len)?;
+            segments.push(rest.get(digits..end)?);
+            rest = &rest[end..];
+        }
+        if rest != "E" || segments.is_empty() {
+            return None;
+        }
+
+        if segments.len() > 1
+            && let Some(hash) = segments.last().and_then(|last| last.strip_prefix('h'))
+            && hash.len() == 16
+            && hash.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            segments.pop();
+        }
+        let segments: Option<Vec<String>> = segments.into_iter().map(unescape).collect();
+        Some(segments?.join("::"))
+    }
+}
+
+/// Expand the `$..$` escapes and `..` separators rustc uses for characters
+/// that are not valid in symbols.
+fn unescape(segment: &str) -> Option<String> {
+    let mut segment = segment;
+    if segment.starts_with("_$") {
+        segment = &segment[1..];
+    }
+    let mut output = String::with_capacity(segment.len());
+    while let Some(c) = segment.chars().next() {
+        if c == '$' {
+            let end = segment[1..].find('$')? + 1;
+            let escape = &segment[1..end];
+            output.push(match escape {
+                "SP" => '@',
+                "BP" => '*',
+                "RF" => '&',
+                "LT" => '<',
+                "GT" => '>',
+                "LP" => '(',
+                "RP" => ')',
+                "C" => ',',
+                _ => {
+                    let code = escape.strip_prefix('u')?;
+                    char::from_u32(u32::from_str_radix(code, 16).ok()?)?
+                }
+            });
+            segment = &segment[end + 1..];
+        } else if let Some(after) = segment.strip_prefix("..") {
+            output.push_str("::");
+            segment = after;
+        } else {
+            output.push(c);
+            segment = &segment[c.len_utf8()..];
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_demangler() {
+        let demangler = RustDemangler;
+        assert_eq!(
+            demangler
+                .demangle("_ZN4core9panicking5panic17h0123456789abcdefE")
+                .as_deref(),
+            Some("core::panicking::panic")
+        );
+        assert_eq!(
+            demangler
+                .demangle("_ZN58_$LT$alloc..string..String$u20$as$u20$core..fmt..Debug$GT$3fmt17hfedcba9876543210E.llvm.1234")
+                .as_deref(),
+            Some("<alloc::string::String as core::fmt::Debug>::fmt")
+        );
+        assert_eq!(
+            demangler.demangle("_ZN3foo3barE").as_deref(),
+            Some("foo::bar")
+        );
+        assert_eq!(demangler.demangle("entrypoint"), None);
+        assert_eq!(demangler.demangle("_ZN3fooE3bar"), None);
+        assert_eq!(demangler.demangle("_ZN12truncatedE"), None);
+    }
+
+    #[test]
+    fn test_closure_demangler() {
+        let demangler = |name: &str| name.strip_prefix("my_").map(str::to_uppercase);
+        assert_eq!(demangler.demangle("my_func").as_deref(), Some("FUNC"));
+        assert_eq!(demangler.demangle("sol_log_"), None);
+    }
+}
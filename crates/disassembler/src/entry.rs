@@ -0,0 +1,226 @@
+use {
+    crate::{demangle::Demangler, errors::DisassemblerError, program::Program},
+    either::Either,
+    sbpf_common::{inst_param::Number, opcode::Opcode},
+    serde::Serialize,
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+/// How the entry address of a program was determined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntrySource {
+    /// `e_entry` in the ELF header points at an instruction in `.text`.
+    ElfHeader,
+    /// `e_entry` is unusable, so the `entrypoint` symbol was used instead.
+    Symbol(String),
+    /// Neither is usable, so execution is assumed to start at `.text` offset 0.
+    Heuristic,
+}
+
+/// Why an offset was recognized as the start of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctionOrigin {
+    Entry,
+    Symbol,
+    CallTarget,
+    /// Offset 0 of `.text`.
+    TextStart,
+    /// Follows an `exit` and is not the target of any jump.
+    AfterExit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionStart {
+    /// Byte offset into `.text`.
+    pub offset: u64,
+    pub name: Option<String>,
+    pub origins: BTreeSet<FunctionOrigin>,
+}
+
+/// Report explaining where execution of a program starts.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryAnalysis {
+    pub e_entry: u64,
+    /// Virtual address of `.text` offset zero.
+    pub text_vaddr: Option<u64>,
+    /// Resolved entry offset into `.text`, if any.
+    pub entry_offset: Option<u64>,
+    pub source: EntrySource,
+    pub functions: Vec<FunctionStart>,
+    pub warnings: Vec<String>,
+}
+
+impl EntryAnalysis {
+    pub fn from_bytes(b: &[u8]) -> Result<Self, Vec<DisassemblerError>> {
+        let program = Program::from_bytes(b)?;
+
+        let e_entry = program.elf_header.e_entry;
+        let text_vaddr = program.text_vaddr();
+        let e_entry_offset = program.get_entrypoint_offset();
+        let text_len = program
+            .section_header_entries
+            .iter()
+            .find(|e| e.label.eq(".text\0"))
+            .map(|e| e.data.len() as u64)
+            .unwrap_or(0);
+
+        // Symbols that land inside .text, keyed by offset.
+        let symbols = program.text_symbols();
+        let entrypoint_symbol = symbols
+            .iter()
+            .find(|(_, name)| name.as_str() == "entrypoint")
+            .map(|(offset, _)| *offset);
+
+        // Raw offsets, still in the 8-byte slots the ELF encodes them in.
+        let disassembly = program.to_ixs_raw()?.value;
+
+        // Instruction boundaries, jump targets and internal call targets.
+        let positions: Vec<u64> = disassembly
+            .instructions
+            .iter()
+            .scan(0u64, |pos, ix| {
+                let current = *pos;
+                *pos += match ix {
+                    Either::Left(ix) => ix.get_size(),
+                    Either::Right(_) => 8,
+                };
+                Some(current)
+            })
+            .collect();
+        // Offsets count slots from the next slot, so an `lddw` in between
+        // counts twice. Targets off an instruction boundary are dropped.
+        let target_of = |idx: usize, off: i64| {
+            let target = (positions[idx] as i64 + 8 + off * 8) as u64;
+            positions.binary_search(&target).ok().map(|_| target)
+        };
+        let mut jump_targets = BTreeSet::new();
+        let mut call_targets = BTreeSet::new();
+        let mut after_exit = BTreeSet::new();
+        for (idx, ix) in disassembly.instructions.iter().enumerate() {
+            let Either::Left(ix) = ix else { continue };
+            if ix.is_jump()
+                && let Some(Either::Right(off)) = &ix.off
+                && let Some(target) = target_of(idx, *off as i64)
+            {
+                jump_targets.insert(target);
+            }
+            if ix.opcode == Opcode::Call
+                && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+                && let Some(target) = target_of(idx, *imm)
+            {
+                call_targets.insert(target);
+            }
+            if ix.opcode == Opcode::Exit
+                && let Some(&next) = positions.get(idx + 1)
+            {
+                after_exit.insert(next);
+            }
+        }
+        after_exit.retain(|offset| !jump_targets.contains(offset));
+
+        let mut warnings = Vec::new();
+        let e_entry_valid = match e_entry_offset {
+            Some(offset) if offset >= text_len => {
+                warnings.push(format!(
+                    "e_entry {:#x} lies outside .text (offset {:#x}, size {:#x})",
+                    e_entry, offset, text_len
+                ));
+                false
+            }
+            Some(offset) if positions.binary_search(&offset).is_err() => {
+                warnings.push(format!(
+                    "e_entry {:#x} (offset {:#x}) is not on an instruction boundary",
+                    e_entry, offset
+                ));
+                false
+            }
+            Some(_) => true,
+            None => {
+                warnings.push(format!("e_entry {:#x} lies before .text", e_entry));
+                false
+            }
+        };
+
+        let (source, entry_offset) = if e_entry_valid {
+            (EntrySource::ElfHeader, e_entry_offset)
+        } else if let Some(offset) = entrypoint_symbol {
+            (EntrySource::Symbol("entrypoint".to_string()), Some(offset))
+        } else if text_len > 0 {
+            (EntrySource::Heuristic, Some(0))
+        } else {
+            (EntrySource::Heuristic, None)
+        };
+
+        if let (true, Some(offset)) = (e_entry_valid, e_entry_offset) {
+            if let Some(symbol_offset) = entrypoint_symbol
+                && symbol_offset != offset
+            {
+                warnings.push(format!(
+                    "e_entry points at offset {:#x} but the entrypoint symbol is at offset {:#x}",
+                    offset, symbol_offset
+                ));
+            }
+            if offset != 0
+                && !symbols.contains_key(&offset)
+                && !call_targets.contains(&offset)
+                && !after_exit.contains(&offset)
+            {
+                warnings.push(format!(
+                    "e_entry (offset {:#x}) does not point at a recognized function",
+                    offset
+                ));
+            }
+        }
+
+        let mut functions: BTreeMap<u64, FunctionStart> = BTreeMap::new();
+        let mut add = |offset: u64, origin: FunctionOrigin| {
+            functions
+                .entry(offset)
+                .or_insert_with(|| FunctionStart {
+                    offset,
+                    name: symbols.get(&offset).cloned(),
+                    origins: BTreeSet::new(),
+                })
+                .origins
+                .insert(origin);
+        };
+        if let Some(offset) = entry_offset {
+            add(offset, FunctionOrigin::Entry);
+        }
+        for &offset in symbols.keys() {
+            add(offset, FunctionOrigin::Symbol);
+        }
+        for &offset in &call_targets {
+            add(offset, FunctionOrigin::CallTarget);
+        }
+        if text_len > 0 {
+            add(0, FunctionOrigin::TextStart);
+        }
+        for &offset in &after_exit {
+            add(offset, FunctionOrigin::AfterExit);
+        }
+
+        Ok(Self {
+            e_entry,
+            text_vaddr,
+            entry_offset,
+            source,
+            functions: functions.into_values().collect(),
+            warnings,
+        })
+    }
+
+    /// Rewrite function names with `demangler`. Done after the analysis,
+    /// which looks the entrypoint up by its symbol name.
+    pub fn demangle(&mut self, demangler: &impl Demangler) {
+        for function in &mut self.functions {
+            if let Some(name) = &function.name
+                && let Some(demangled) = demangler.demangle(name)
+            {
+                function.name = Some(demangled);
+            }
+        }
+    }
+}
@@ -68,6 +68,28 @@ pub enum DisassemblerError {
     },
 }
 
+impl DisassemblerError {
+    /// Stable identifier of the error for tools, such as `bytecode-error`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidElfFile { .. } => "invalid-elf-file",
+            Self::NonStandardElfHeader { .. } => "non-standard-elf-header",
+            Self::InvalidProgramType(_) => "invalid-program-type",
+            Self::InvalidSectionHeaderType(_) => "invalid-section-header-type",
+            Self::InvalidRelocationType(_) => "invalid-relocation-type",
+            Self::SectionDataError { .. } => "section-data-error",
+            Self::InvalidDataLength(_) => "invalid-data-length",
+            Self::BytecodeError { .. } => "bytecode-error",
+            Self::MissingTextSection { .. } => "missing-text-section",
+            Self::InvalidDynstrOffset { .. } => "invalid-dynstr-offset",
+            Self::InvalidUtf8InDynstr(_) => "invalid-utf8-in-dynstr",
+            Self::InvalidShstrndx { .. } => "invalid-shstrndx",
+            Self::InvalidSectionName { .. } => "invalid-section-name",
+            Self::SectionDataOutOfBounds { .. } => "section-data-out-of-bounds",
+        }
+    }
+}
+
 impl From<SBPFError> for DisassemblerError {
     fn from(err: SBPFError) -> Self {
         match err {
@@ -151,6 +173,10 @@ mod tests {
             .to_string(),
             "Bytecode error at bytes 8..16: custom"
         );
+        assert_eq!(
+            DisassemblerError::InvalidDataLength(13).code(),
+            "invalid-data-length"
+        );
         assert_eq!(
             DisassemblerError::MissingTextSection {
                 sections: vec![".rodata".to_string(), ".shstrtab".to_string()],
@@ -1,11 +1,15 @@
+pub mod demangle;
 pub mod elf_header;
+pub mod entry;
 pub mod errors;
+pub mod line_table;
 pub mod program;
 pub mod program_header;
 pub mod relocation;
 pub mod rodata;
 pub mod section_header;
 pub mod section_header_entry;
+pub mod symbol;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
@@ -0,0 +1,141 @@
+use {
+    gimli::{EndianSlice, RunTimeEndian, SectionId},
+    object::{Object, ObjectSection},
+    serde::Serialize,
+    std::{
+        borrow::Cow,
+        collections::BTreeMap,
+        path::{Component, PathBuf},
+    },
+};
+
+/// Where an instruction was written, as recorded in `.debug_line`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLine {
+    pub file: String,
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based column, or 0 if unknown
+    pub column: u32,
+}
+
+/// The DWARF line table of a program built with debug info, keyed by
+/// `.text` offset.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    /// Each row covers the instructions up to the next; `None` ends a
+    /// sequence.
+    rows: BTreeMap<u64, Option<SourceLine>>,
+}
+
+impl LineTable {
+    /// Read the line table of an ELF, or `None` if it has no debug info or
+    /// the info can't be read.
+    pub fn from_elf_bytes(bytes: &[u8]) -> Option<Self> {
+        let object = object::File::parse(bytes).ok()?;
+        let text_address = object.section_by_name(".text")?.address();
+        let endian = if object.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let load_section = |id: SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+            Ok(object
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or(Cow::Borrowed(&[])))
+        };
+        let sections = gimli::DwarfSections::load(&load_section).ok()?;
+        let dwarf = sections.borrow(|section| EndianSlice::new(Cow::as_ref(section), endian));
+
+        let mut rows = BTreeMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next().ok()? {
+            let unit = dwarf.unit(header).ok()?;
+            let unit = unit.unit_ref(&dwarf);
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let comp_dir = unit
+                .comp_dir
+                .map(|dir| PathBuf::from(dir.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            let mut program_rows = program.rows();
+            while let Some((header, row)) = program_rows.next_row().ok()? {
+                let offset = row.address().saturating_sub(text_address);
+                if row.end_sequence() {
+                    rows.entry(offset).or_insert(None);
+                    continue;
+                }
+                let mut path = comp_dir.clone();
+                if let Some(file) = row.file(header) {
+                    if let Some(dir) = file.directory(header)
+                        && let Ok(dir) = unit.attr_string(dir)
+                    {
+                        path.push(dir.to_string_lossy().as_ref());
+                    }
+                    if let Ok(name) = unit.attr_string(file.path_name()) {
+                        path.push(name.to_string_lossy().as_ref());
+                    }
+                }
+                // Drop the `.` the assembler records for the working
+                // directory.
+                let path: PathBuf = path
+                    .components()
+                    .filter(|component| *component != Component::CurDir)
+                    .collect();
+                rows.insert(
+                    offset,
+                    Some(SourceLine {
+                        file: path.to_string_lossy().into_owned(),
+                        line: row.line().map_or(0, |line| line.get() as u32),
+                        column: match row.column() {
+                            gimli::ColumnType::LeftEdge => 0,
+                            gimli::ColumnType::Column(column) => column.get() as u32,
+                        },
+                    }),
+                );
+            }
+        }
+        (!rows.is_empty()).then_some(Self { rows })
+    }
+
+    /// Where the instruction at `.text` offset `offset` was written.
+    pub fn locate(&self, offset: u64) -> Option<&SourceLine> {
+        self.rows.range(..=offset).next_back()?.1.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+    };
+
+    #[test]
+    fn test_locate_instructions() {
+        let source = ".globl entrypoint\nentrypoint:\n  lddw r1, 1\n  mov64 r0, 0\n  exit\n";
+        let options = AssemblerOption {
+            debug_mode: Some(DebugMode {
+                filename: "prog.s".to_string(),
+                directory: "/src".to_string(),
+            }),
+            ..Default::default()
+        };
+        let elf = Assembler::new(options).assemble(source).unwrap();
+        let table = LineTable::from_elf_bytes(&elf).unwrap();
+
+        let lines: Vec<_> = [0, 16, 24]
+            .into_iter()
+            .map(|offset| table.locate(offset).map(|line| line.line))
+            .collect();
+        assert_eq!(lines, [Some(3), Some(4), Some(5)]);
+        assert_eq!(table.locate(0).unwrap().file, "/src/prog.s");
+
+        let plain = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        assert!(LineTable::from_elf_bytes(&plain).is_none());
+    }
+}
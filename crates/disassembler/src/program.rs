@@ -1,5 +1,6 @@
 use {
     crate::{
+        demangle::Demangler,
         elf_header::{E_MACHINE, E_MACHINE_SBPF, ELFHeader},
         errors::DisassemblerError,
         program_header::ProgramHeader,
@@ -7,14 +8,21 @@ use {
         rodata::RodataSection,
         section_header::SectionHeader,
         section_header_entry::SectionHeaderEntry,
+        symbol::Symbol,
     },
     either::Either,
     object::{Endianness, read::elf::ElfFile64},
     sbpf_common::{
-        errors::SBPFError, inst_param::Number, instruction::Instruction, opcode::Opcode,
+        build_note::{BUILD_NOTE_SECTION, BuildNote},
+        errors::SBPFError,
+        inst_param::Number,
+        instruction::Instruction,
+        opcode::Opcode,
+        program_error::{ERROR_NOTE_SECTION, ErrorRegistry},
+        version::SbpfVersion,
     },
     serde::{Deserialize, Serialize},
-    std::collections::{BTreeSet, HashMap},
+    std::collections::{BTreeMap, BTreeSet, HashMap},
 };
 
 /// Outcome of an error-tolerant operation, the value `T` plus every error found while producing it.
@@ -42,6 +50,8 @@ pub struct Disassembly {
     pub instructions: Vec<Either<Instruction, DisassemblerError>>,
     pub rodata: Option<RodataSection>,
     pub entrypoint: Option<usize>,
+    /// Names of the symbols that start instructions, by instruction index
+    pub symbols: BTreeMap<usize, String>,
 }
 
 pub type DisassembleResult = Result<Parsed<Disassembly>, Vec<DisassemblerError>>;
@@ -53,6 +63,7 @@ pub struct Program {
     pub section_headers: Vec<SectionHeader>,
     pub section_header_entries: Vec<SectionHeaderEntry>,
     pub relocations: Vec<Relocation>,
+    pub symbols: Vec<Symbol>,
 }
 
 impl Program {
@@ -83,6 +94,9 @@ impl Program {
         // Parse relocations.
         let relocations = Relocation::from_elf_file(&elf_file)?;
 
+        // Parse .symtab and .dynsym.
+        let symbols = Symbol::from_elf_file(&elf_file);
+
         // v3 binaries omit the section header table; reconstruct the .text and
         // .rodata section views from the program (segment) headers so the rest
         // of the disassembler can locate them by name.
@@ -98,6 +112,7 @@ impl Program {
             section_headers,
             section_header_entries,
             relocations,
+            symbols,
         })
     }
 
@@ -106,13 +121,15 @@ impl Program {
     /// the executable segment becomes `.text` and the read-only,
     /// non-executable segment becomes `.rodata`. The synthesized section
     /// headers mirror what the assembler used to emit (`sh_addr == sh_offset ==
-    /// file offset`) so downstream offset resolution is unchanged.
+    /// file offset`) so downstream offset resolution is unchanged. A writable
+    /// segment becomes `.data` and, past its file contents, `.bss`; these keep
+    /// their load address in `sh_addr`.
     fn synthesize_sections_from_segments(
         data: &[u8],
         program_headers: &[ProgramHeader],
     ) -> Result<(Vec<SectionHeader>, Vec<SectionHeaderEntry>), DisassemblerError> {
         use crate::{
-            program_header::{PF_X, ProgramType},
+            program_header::{PF_W, PF_X, ProgramType},
             section_header::SectionHeaderType,
         };
 
@@ -144,12 +161,13 @@ impl Program {
 
         let is_load = |ph: &&ProgramHeader| matches!(ph.p_type, ProgramType::PT_LOAD);
         let is_exec = |ph: &ProgramHeader| ph.p_flags.0 & PF_X as u32 == PF_X as u32;
+        let is_writable = |ph: &ProgramHeader| ph.p_flags.0 & PF_W as u32 == PF_W as u32;
 
         // .rodata: read-only, non-executable loadable segment (if present).
         if let Some(ph) = program_headers
             .iter()
             .filter(is_load)
-            .find(|ph| !is_exec(ph))
+            .find(|ph| !is_exec(ph) && !is_writable(ph))
         {
             headers.push(make_header(ph.p_offset, ph.p_filesz, false));
             entries.push(SectionHeaderEntry::new(
@@ -173,6 +191,51 @@ impl Program {
             )?);
         }
 
+        // .data and .bss: writable loadable segment.
+        if let Some(ph) = program_headers
+            .iter()
+            .filter(is_load)
+            .find(|ph| is_writable(ph))
+        {
+            let bss_size = ph.p_memsz.saturating_sub(ph.p_filesz);
+            for (name, sh_type, offset, addr, size) in [
+                (
+                    ".data\0",
+                    SectionHeaderType::SHT_PROGBITS,
+                    ph.p_offset,
+                    ph.p_vaddr,
+                    ph.p_filesz,
+                ),
+                (
+                    ".bss\0",
+                    SectionHeaderType::SHT_NOBITS,
+                    ph.p_offset + ph.p_filesz,
+                    ph.p_vaddr + ph.p_filesz,
+                    bss_size,
+                ),
+            ] {
+                if size == 0 {
+                    continue;
+                }
+                let bytes = if matches!(sh_type, SectionHeaderType::SHT_NOBITS) {
+                    Vec::new()
+                } else {
+                    segment_bytes(offset, size)
+                };
+                headers.push(SectionHeader {
+                    sh_type,
+                    sh_flags: 0x3, // SHF_WRITE | SHF_ALLOC
+                    sh_addr: addr,
+                    ..make_header(offset, size, false)
+                });
+                entries.push(SectionHeaderEntry::new(
+                    name.to_string(),
+                    offset as usize,
+                    bytes,
+                )?);
+            }
+        }
+
         Ok((headers, entries))
     }
 
@@ -212,9 +275,7 @@ impl Program {
             errors.push(DisassemblerError::InvalidDataLength(data.len()));
         }
 
-        let is_sbpf_v2 =
-            self.elf_header.e_flags == 0x02 && self.elf_header.e_machine == E_MACHINE_SBPF;
-        let is_sbpf_v3 = self.elf_header.e_flags == 0x03 && self.elf_header.e_machine == E_MACHINE;
+        let version = self.version();
 
         // Get rodata info
         let rodata_info = self.get_rodata_info();
@@ -224,8 +285,9 @@ impl Program {
             .unwrap_or((0, 0));
 
         // Parse instructions and build slot mappings
-        let mut ixs: Vec<Either<Instruction, DisassemblerError>> = Vec::new();
-        let mut idx_to_slot: Vec<usize> = Vec::new();
+        let mut ixs: Vec<Either<Instruction, DisassemblerError>> =
+            Vec::with_capacity(data.len() / 8);
+        let mut idx_to_slot: Vec<usize> = Vec::with_capacity(data.len() / 8);
         let mut pos: usize = 0;
         let mut slot: usize = 0;
 
@@ -235,25 +297,7 @@ impl Program {
                 break;
             }
 
-            // lddw (0x18) is the only 16-byte instruction; sbpf-common's
-            // decoder asserts on shorter input, so report the truncation
-            // here instead of panicking.
-            let decoded = if remaining.len() < 16 && remaining[0] == 0x18 {
-                Err(SBPFError::BytecodeError {
-                    error: format!("lddw needs 16 bytes but only {} remain", remaining.len()),
-                    span: 0..8, // Spans are relative to `remaining` and rebased by `pos` below, in the Err(e) arm.
-                    custom_label: None,
-                })
-            } else if is_sbpf_v2 {
-                // ugly v2 shit we need to fix goes here:
-                Instruction::from_bytes_sbpf_v2(remaining)
-            } else if is_sbpf_v3 {
-                Instruction::from_bytes_sbpf_v3(remaining)
-            } else {
-                Instruction::from_bytes(remaining)
-            };
-
-            let mut ix = match decoded {
+            let mut ix = match Instruction::from_bytes_for(remaining, version) {
                 Ok(ix) => ix,
                 // A word that fails to decode doesn't affect the rest of the
                 // stream, instead we record the error and keep it inline in the stream,
@@ -317,8 +361,32 @@ impl Program {
         let text_end_addr = text_sh_addr + text_section.data.len() as u64;
 
         let mut rodata_refs = BTreeSet::new();
+        let mut symbols = BTreeMap::new();
+        let mut data_symbols = BTreeMap::new();
 
         if resolve_offsets {
+            // Name instructions and data after the symbols that point at them.
+            for (offset, name) in self.text_symbols() {
+                let slot = (offset / 8) as usize;
+                if offset.is_multiple_of(8)
+                    && let Some(&idx) = slot_to_idx.get(slot)
+                    && idx_to_slot[idx] == slot
+                {
+                    symbols.insert(idx, name);
+                }
+            }
+            for symbol in self.symbols.iter().filter(|symbol| symbol.is_data()) {
+                if rodata_info.is_some()
+                    && symbol.address >= rodata_base
+                    && symbol.address < rodata_end
+                {
+                    rodata_refs.insert(symbol.address);
+                    data_symbols
+                        .entry(symbol.address - rodata_base)
+                        .or_insert_with(|| symbol.name.clone());
+                }
+            }
+
             // Resolve jump/call labels and collect rodata references
 
             for (idx, ix) in ixs.iter_mut().enumerate() {
@@ -374,6 +442,11 @@ impl Program {
         // Parse rodata section
         let rodata = if let Some((data, base_addr)) = rodata_info {
             let mut section = RodataSection::parse(data, base_addr, &rodata_refs);
+            for item in &mut section.items {
+                if let Some(name) = data_symbols.get(&item.offset) {
+                    item.label = name.clone();
+                }
+            }
             let (data_relocs, text_relocs) = self.classify_relocations(
                 &section.data,
                 base_addr,
@@ -404,6 +477,7 @@ impl Program {
                 instructions: ixs,
                 rodata,
                 entrypoint: entrypoint_idx,
+                symbols,
             },
             errors,
         })
@@ -487,6 +561,35 @@ impl Program {
         Some((rodata_entry.data.clone(), vaddr))
     }
 
+    /// The writable `.data` bytes followed by the zero-filled `.bss`, and the
+    /// virtual address they are loaded at.
+    pub fn get_writable_data(&self) -> Option<(Vec<u8>, u64)> {
+        use crate::section_header::SectionHeaderType;
+
+        let sections: Vec<_> = self
+            .section_headers
+            .iter()
+            .zip(&self.section_header_entries)
+            .filter(|(_, entry)| matches!(entry.label.trim_end_matches('\0'), ".data" | ".bss"))
+            .collect();
+        let start = sections.iter().map(|(header, _)| header.sh_addr).min()?;
+        let end = sections
+            .iter()
+            .map(|(header, _)| header.sh_addr + header.sh_size)
+            .max()?;
+
+        let mut image = vec![0u8; (end - start) as usize];
+        for (header, entry) in sections {
+            if matches!(header.sh_type, SectionHeaderType::SHT_NOBITS) {
+                continue;
+            }
+            let at = (header.sh_addr - start) as usize;
+            let len = entry.data.len().min(image.len() - at);
+            image[at..at + len].copy_from_slice(&entry.data[..len]);
+        }
+        Some((image, start))
+    }
+
     /// Classify relocations into data and text relocations.
     fn classify_relocations(
         &self,
@@ -532,35 +635,108 @@ impl Program {
         (data_relocs, text_relocs)
     }
 
+    /// Rewrite the names of called symbols with `demangler`, so that
+    /// disassembly shows them readably.
+    pub fn demangle(&mut self, demangler: &impl Demangler) {
+        for relocation in &mut self.relocations {
+            if let Some(name) = &relocation.symbol_name
+                && let Some(demangled) = demangler.demangle(name)
+            {
+                relocation.symbol_name = Some(demangled);
+            }
+        }
+        for symbol in &mut self.symbols {
+            if let Some(demangled) = demangler.demangle(&symbol.name) {
+                symbol.name = demangled;
+            }
+        }
+    }
+
+    /// Names of the symbols defined in `.text`, by byte offset into it. Of
+    /// several symbols at one offset, the first in `.symtab` then `.dynsym`
+    /// names it.
+    pub fn text_symbols(&self) -> BTreeMap<u64, String> {
+        let mut symbols = BTreeMap::new();
+        let Some(text_vaddr) = self.text_vaddr() else {
+            return symbols;
+        };
+        let text_len = self
+            .section_header_entries
+            .iter()
+            .find(|e| e.label.eq(".text\0"))
+            .map_or(0, |e| e.data.len() as u64);
+        for symbol in self.symbols.iter().filter(|symbol| symbol.is_code()) {
+            if let Some(offset) = symbol.address.checked_sub(text_vaddr)
+                && offset < text_len
+            {
+                symbols.entry(offset).or_insert_with(|| symbol.name.clone());
+            }
+        }
+        symbols
+    }
+
     /// Get the entrypoint offset
     pub fn get_entrypoint_offset(&self) -> Option<u64> {
-        let e_entry = self.elf_header.e_entry;
+        self.elf_header.e_entry.checked_sub(self.text_vaddr()?)
+    }
+
+    /// Build provenance embedded by the assembler, if present.
+    pub fn build_note(&self) -> Option<BuildNote> {
+        self.section_header_entries
+            .iter()
+            .find(|e| e.label.trim_end_matches('\0') == BUILD_NOTE_SECTION)
+            .and_then(|e| BuildNote::from_bytes(&e.data))
+    }
+
+    /// Custom errors the program declares with `.error`, empty if it
+    /// declares none.
+    pub fn error_table(&self) -> ErrorRegistry {
+        self.section_header_entries
+            .iter()
+            .find(|e| e.label.trim_end_matches('\0') == ERROR_NOTE_SECTION)
+            .and_then(|e| ErrorRegistry::from_note_bytes(&e.data))
+            .unwrap_or_default()
+    }
 
+    /// Virtual address that `.text` offset zero is loaded at.
+    pub fn text_vaddr(&self) -> Option<u64> {
         if self.is_v3() {
             const V3_BYTECODE_VADDR: u64 = 1 << 32;
-            if e_entry >= V3_BYTECODE_VADDR {
-                Some(e_entry - V3_BYTECODE_VADDR)
-            } else {
-                None
-            }
+            Some(V3_BYTECODE_VADDR)
         } else {
             let text_header = self.section_headers.iter().find(|h| {
                 self.section_header_entries
                     .iter()
                     .any(|e| e.label.eq(".text\0") && e.offset == h.sh_offset as usize)
             })?;
-            let text_sh_addr = text_header.sh_addr;
+            Some(text_header.sh_addr)
+        }
+    }
 
-            if e_entry >= text_sh_addr {
-                Some(e_entry - text_sh_addr)
-            } else {
-                None
-            }
+    /// The sBPF version the ELF header records. v2 needs the SBPF machine
+    /// and v3 the BPF one; anything unrecognised decodes as v0.
+    pub fn version(&self) -> SbpfVersion {
+        match (self.elf_header.e_flags, self.elf_header.e_machine) {
+            (0x01, _) => SbpfVersion::V1,
+            (0x02, E_MACHINE_SBPF) => SbpfVersion::V2,
+            (0x03, E_MACHINE) => SbpfVersion::V3,
+            _ => SbpfVersion::V0,
         }
     }
 
+    /// Decode as `version` whatever the ELF header records, for binaries
+    /// with missing or wrong flags.
+    pub fn with_version(mut self, version: SbpfVersion) -> Self {
+        self.elf_header.e_flags = version.e_flags();
+        self.elf_header.e_machine = match version {
+            SbpfVersion::V2 => E_MACHINE_SBPF,
+            _ => E_MACHINE,
+        };
+        self
+    }
+
     fn is_v3(&self) -> bool {
-        self.elf_header.e_flags == 0x03 && self.elf_header.e_machine == E_MACHINE
+        self.version().is_v3()
     }
 }
 
@@ -573,6 +749,7 @@ mod tests {
             section_header_entry::SectionHeaderEntry,
         },
         hex_literal::hex,
+        sbpf_common::{opcode::Opcode, version::SbpfVersion},
     };
 
     #[test]
@@ -659,6 +836,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, vec![0x95, 0x00, 0x00]).unwrap(), // Only 3 bytes
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -706,6 +884,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, lddw_bytes).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -752,6 +931,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, v2_bytes).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -797,6 +977,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, v3_bytes).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -809,6 +990,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_with_version_overrides_header() {
+        // 0x46 is udiv32 imm in v0 and jset32 imm in v3.
+        let bytes = [0x46, 0x01, 0x00, 0x00, 0x7f, 0x00, 0x00, 0x00];
+
+        let program = || Program {
+            elf_header: ELFHeader {
+                ei_magic: [127, 69, 76, 70],
+                ei_class: 2,
+                ei_data: 1,
+                ei_version: 1,
+                ei_osabi: 0,
+                ei_abiversion: 0,
+                ei_pad: [0; 7],
+                e_type: 0,
+                e_machine: E_MACHINE,
+                e_version: 0,
+                e_entry: 0,
+                e_phoff: 0,
+                e_shoff: 0,
+                e_flags: 0x00,
+                e_ehsize: 0,
+                e_phentsize: 0,
+                e_phnum: 0,
+                e_shentsize: 0,
+                e_shnum: 0,
+                e_shstrndx: 0,
+            },
+            program_headers: vec![],
+            section_headers: vec![],
+            section_header_entries: vec![
+                SectionHeaderEntry::new(".text\0".to_string(), 0, bytes.to_vec()).unwrap(),
+            ],
+            relocations: vec![],
+            symbols: vec![],
+        };
+        assert_eq!(program().version(), SbpfVersion::V0);
+
+        let opcode = |program: Program| {
+            program.to_ixs().unwrap().value.instructions[0]
+                .as_ref()
+                .unwrap_left()
+                .opcode
+        };
+        assert_eq!(opcode(program()), Opcode::Udiv32Imm);
+
+        let program = program().with_version(SbpfVersion::V3);
+        assert_eq!(program.version(), SbpfVersion::V3);
+        assert_eq!(opcode(program), Opcode::Jset32Imm);
+    }
+
     #[test]
     fn test_to_ixs_skips_undecodable_instruction() {
         // .text: [8 bytes of garbage][exit]. The garbage word is reported
@@ -846,6 +1078,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, text).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -904,6 +1137,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, text).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
@@ -959,6 +1193,7 @@ mod tests {
                 SectionHeaderEntry::new(".text\0".to_string(), 0, text).unwrap(),
             ],
             relocations: vec![],
+            symbols: vec![],
         };
 
         let parsed = program.to_ixs().unwrap();
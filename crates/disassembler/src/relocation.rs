@@ -2,6 +2,7 @@ use {
     crate::errors::DisassemblerError,
     object::{Endianness, Object, ObjectSection, read::elf::ElfFile64},
     serde::{Deserialize, Serialize},
+    std::collections::HashMap,
 };
 
 #[allow(non_camel_case_types)]
@@ -66,7 +67,10 @@ impl Relocation {
             .section_by_name(".dynstr")
             .and_then(|s| s.data().ok());
 
-        let mut relocations = Vec::new();
+        let mut relocations = Vec::with_capacity(rel_dyn_data.len() / 16);
+        // Every call to a syscall has its own relocation, so names are
+        // resolved once per symbol.
+        let mut names: HashMap<u32, Option<String>> = HashMap::new();
 
         // Parse relocation entries
         for chunk in rel_dyn_data.chunks_exact(16) {
@@ -83,12 +87,15 @@ impl Relocation {
 
             // Resolve symbol name if this is a syscall relocation
             let symbol_name = if rel_type == RelocationType::R_BPF_64_32 {
-                match (&dynsym_data, &dynstr_data) {
-                    (Some(dynsym), Some(dynstr)) => {
-                        resolve_symbol_name(dynsym, dynstr, symbol_index as usize).ok()
-                    }
-                    _ => None,
-                }
+                names
+                    .entry(symbol_index)
+                    .or_insert_with(|| match (&dynsym_data, &dynstr_data) {
+                        (Some(dynsym), Some(dynstr)) => {
+                            resolve_symbol_name(dynsym, dynstr, symbol_index as usize).ok()
+                        }
+                        _ => None,
+                    })
+                    .clone()
             } else {
                 None
             };
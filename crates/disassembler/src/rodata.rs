@@ -53,7 +53,7 @@ impl RodataItem {
 pub struct RodataSection {
     pub base_address: u64,            // base virtual address of the rodata section
     pub data: Vec<u8>,                // raw section data bytes
-    pub items: Vec<RodataItem>,       // parsed rodata items
+    pub items: Vec<RodataItem>,       // parsed rodata items, in offset order
     pub data_relocations: Vec<usize>, // byte offsets into data where 8-byte rodata pointers need VM address fixup
     pub text_relocations: Vec<(usize, usize)>, // (byte_offset_in_data, instruction_index) for function pointers
 }
@@ -87,15 +87,43 @@ impl RodataSection {
         output
     }
 
+    /// Source that assembles back to exactly the section's bytes. Strings
+    /// the assembler can't quote are written with `.byte`, and the zeros
+    /// `parse` trims from the end come back as `.space`.
+    pub fn to_exact_asm(&self) -> String {
+        if self.data.is_empty() {
+            return String::new();
+        }
+
+        let mut output = String::from(".rodata\n");
+        let mut end = 0;
+        for item in &self.items {
+            let data_type = match &item.data_type {
+                RodataType::Ascii(s) if !is_quotable(s) => {
+                    RodataType::Byte(item.data.iter().map(|&b| b as i8).collect())
+                }
+                data_type => data_type.clone(),
+            };
+            output.push_str(&format!("  {}: {}\n", item.label, data_type.to_asm()));
+            end = item.offset + item.size;
+        }
+        let padding = self.data.len() as u64 - end;
+        if padding > 0 {
+            output.push_str(&format!("  .space {}\n", padding));
+        }
+        output
+    }
+
     pub fn get_label(&self, address: u64) -> Option<&str> {
         if address < self.base_address {
             return None;
         }
         let offset = address - self.base_address;
+        // Called once per lddw, so search rather than scan the items.
         self.items
-            .iter()
-            .find(|item| item.offset == offset)
-            .map(|item| item.label.as_str())
+            .binary_search_by_key(&offset, |item| item.offset)
+            .ok()
+            .map(|index| self.items[index].label.as_str())
     }
 
     #[inline]
@@ -196,6 +224,13 @@ fn is_ascii(s: &str) -> bool {
         .all(|c| c.is_ascii_graphic() || c == ' ' || c == '\t' || c == '\n' || c == '\r')
 }
 
+/// Whether `.ascii "s"` assembles to the bytes of `s`: the assembler reads
+/// strings verbatim, with no escapes, up to the next quote.
+fn is_quotable(s: &str) -> bool {
+    s.chars()
+        .all(|c| (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\')
+}
+
 fn generate_label(offset: u64, data_type: &RodataType) -> String {
     match data_type {
         RodataType::Ascii(_) => format!("str_{:04x}", offset),
@@ -341,6 +376,42 @@ mod tests {
         assert!(!section_empty.has_items());
     }
 
+    #[test]
+    fn test_rodata_section_get_label() {
+        let references = BTreeSet::from([0x100, 0x104, 0x106]);
+        let section = RodataSection::parse(b"abcdefgh".to_vec(), 0x100, &references);
+        let labels: Vec<_> = [0x100, 0x104, 0x106]
+            .into_iter()
+            .map(|address| section.get_label(address))
+            .collect();
+        assert!(labels.iter().all(Option::is_some));
+        assert_eq!(labels[1], Some(section.items[1].label.as_str()));
+        assert_eq!(section.get_label(0x102), None);
+        assert_eq!(section.get_label(0x99), None);
+    }
+
+    #[test]
+    fn test_rodata_section_to_exact_asm() {
+        let mut data = b"ab\"c".to_vec();
+        data.extend_from_slice(b"hi\0\0\0");
+        let section = RodataSection::parse(data, 0x100, &BTreeSet::from([0x100, 0x104]));
+        assert_eq!(
+            section.to_exact_asm(),
+            ".rodata\n  str_0000: .byte 0x61, 0x62, 0x22, 0x63\n  str_0004: .ascii \"hi\"\n  .space 3\n"
+        );
+
+        let section = RodataSection::parse(b"a\nb".to_vec(), 0, &BTreeSet::new());
+        assert_eq!(
+            section.to_exact_asm(),
+            ".rodata\n  str_0000: .byte 0x61, 0x0a, 0x62\n"
+        );
+        assert!(
+            RodataSection::parse(vec![], 0, &BTreeSet::new())
+                .to_exact_asm()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_trim_trailing_zeros() {
         assert_eq!(trim_trailing_zeros(&[1, 2, 3, 0, 0]), &[1, 2, 3]);
@@ -199,8 +199,13 @@ impl SectionHeader {
                 }
             };
 
+            // SHT_NOBITS sections (.bss) occupy memory but no file bytes.
             let data_start = s.sh_offset as usize;
-            let data_end = data_start.saturating_add(s.sh_size as usize);
+            let data_end = if matches!(s.sh_type, SectionHeaderType::SHT_NOBITS) {
+                data_start
+            } else {
+                data_start.saturating_add(s.sh_size as usize)
+            };
             let section_data = match data.get(data_start..data_end) {
                 Some(d) => d.to_vec(),
                 None => {
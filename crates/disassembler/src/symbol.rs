@@ -0,0 +1,116 @@
+use {
+    object::{
+        Endianness, Object, ObjectSection, ObjectSymbol, SymbolKind, SymbolSection,
+        read::elf::ElfFile64,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolType {
+    Function,
+    Object,
+    Section,
+    File,
+    Unknown,
+}
+
+impl SymbolType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Function => "function",
+            Self::Object => "object",
+            Self::Section => "section",
+            Self::File => "file",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<SymbolKind> for SymbolType {
+    fn from(kind: SymbolKind) -> Self {
+        match kind {
+            SymbolKind::Text => Self::Function,
+            SymbolKind::Data => Self::Object,
+            SymbolKind::Section => Self::Section,
+            SymbolKind::File => Self::File,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// An entry of `.symtab` or `.dynsym`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    /// Virtual address, or 0 for undefined symbols such as syscalls.
+    pub address: u64,
+    pub size: u64,
+    pub symbol_type: SymbolType,
+    pub global: bool,
+    /// Whether the symbol is from `.dynsym` rather than `.symtab`.
+    pub dynamic: bool,
+    /// Name of the section the symbol is defined in, or `None` if undefined.
+    pub section: Option<String>,
+}
+
+impl Symbol {
+    /// Named symbols of `.symtab` followed by those of `.dynsym`. Entries
+    /// that can't be read are skipped.
+    pub fn from_elf_file(elf_file: &ElfFile64<Endianness>) -> Vec<Self> {
+        elf_file
+            .symbols()
+            .filter_map(|symbol| Self::read(elf_file, symbol, false))
+            .chain(
+                elf_file
+                    .dynamic_symbols()
+                    .filter_map(|symbol| Self::read(elf_file, symbol, true)),
+            )
+            .collect()
+    }
+
+    fn read<'data>(
+        elf_file: &ElfFile64<'data, Endianness>,
+        symbol: impl ObjectSymbol<'data>,
+        dynamic: bool,
+    ) -> Option<Self> {
+        let name = symbol.name().ok().filter(|name| !name.is_empty())?;
+        let section = match symbol.section() {
+            SymbolSection::Section(index) => Some(
+                elf_file
+                    .section_by_index(index)
+                    .and_then(|section| section.name().map(str::to_string))
+                    .unwrap_or_default(),
+            ),
+            SymbolSection::Absolute => Some("ABS".to_string()),
+            _ => None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            address: symbol.address(),
+            size: symbol.size(),
+            symbol_type: symbol.kind().into(),
+            global: symbol.is_global(),
+            dynamic,
+            section,
+        })
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        self.section.is_none()
+    }
+
+    /// Whether this can name code: a defined function, or a symbol of no
+    /// particular type such as the assembler's `entrypoint`.
+    pub fn is_code(&self) -> bool {
+        !self.is_undefined()
+            && matches!(self.symbol_type, SymbolType::Function | SymbolType::Unknown)
+    }
+
+    /// Whether this can name data: a defined object, or a symbol of no
+    /// particular type.
+    pub fn is_data(&self) -> bool {
+        !self.is_undefined() && matches!(self.symbol_type, SymbolType::Object | SymbolType::Unknown)
+    }
+}
@@ -0,0 +1,121 @@
+use {
+    crate::text,
+    lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range},
+    sbpf_assembler::{AssembleErrors, LintConfig, Severity},
+    std::{collections::BTreeMap, path::PathBuf},
+};
+
+/// Errors or warnings of one assembly as LSP diagnostics, grouped by the
+/// file they point into. Those without a location are put at the top of
+/// `root`, the file that was assembled. Warnings `lints` denies are shown
+/// as errors.
+pub fn by_file(
+    errors: &AssembleErrors,
+    root: &str,
+    lints: &LintConfig,
+) -> BTreeMap<PathBuf, Vec<Diagnostic>> {
+    let registry = &errors.file_registry;
+    let mut diagnostics = BTreeMap::<PathBuf, Vec<Diagnostic>>::new();
+    for assembler_error in &errors.errors {
+        let error = &assembler_error.error;
+        // Lines from `--define` are numbered 0 and have no text.
+        let (path, range) = match &assembler_error.origin {
+            Some(origin) if origin.line > 0 => {
+                let number = origin.line - 1;
+                let line = text::line(registry.content(origin.file_id), number);
+                // Highlight from the column to the end of the line, or the
+                // whole line, the same way the command line does.
+                let start = assembler_error.column.unwrap_or(0);
+                (
+                    registry.path(origin.file_id),
+                    Range::new(
+                        text::position(line, number, start),
+                        text::position(line, number, line.len()),
+                    ),
+                )
+            }
+            Some(origin) => (registry.path(origin.file_id), Range::default()),
+            None => (root, Range::new(Position::new(0, 0), Position::new(0, 0))),
+        };
+        let message = match error.label() {
+            label if label.is_empty() || label == error.to_string() => error.to_string(),
+            label => format!("{}\n{}", error, label),
+        };
+        diagnostics
+            .entry(PathBuf::from(path))
+            .or_default()
+            .push(Diagnostic {
+                range,
+                severity: Some(match error.severity() {
+                    Severity::Warning if lints.is_denied(&error.code()) => {
+                        DiagnosticSeverity::ERROR
+                    }
+                    other => severity(other),
+                }),
+                source: Some("sbpf".to_string()),
+                message,
+                ..Default::default()
+            });
+    }
+    diagnostics
+}
+
+fn severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Note => DiagnosticSeverity::INFORMATION,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, MockFileResolver},
+        std::path::Path,
+    };
+
+    #[test]
+    fn test_errors_become_diagnostics_in_their_file() {
+        let mut resolver = MockFileResolver::new();
+        resolver.add_file("lib.s", "helper:\n  mov64 r0, missing\n  exit\n");
+        let source = ".globl entrypoint\nentrypoint:\n  call helper\n  exit\n.include \"lib.s\"\n";
+        let errors = Assembler::new(AssemblerOption::default())
+            .assemble_full_with_preprocess(source, "main.s", Some(&resolver))
+            .unwrap_err();
+
+        let diagnostics = by_file(&errors, "main.s", &LintConfig::default());
+        let (path, in_lib) = diagnostics.iter().next().unwrap();
+        assert!(path.ends_with(Path::new("lib.s")), "{:?}", path);
+        assert_eq!(in_lib.len(), 1);
+        assert_eq!(in_lib[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(in_lib[0].range.start.line, 1);
+        assert_eq!(
+            in_lib[0].range.end.character,
+            "  mov64 r0, missing".len() as u32
+        );
+        assert!(in_lib[0].message.contains("missing"));
+    }
+
+    #[test]
+    fn test_warnings_keep_their_severity() {
+        let artifacts = Assembler::new(AssemblerOption::default())
+            .assemble_full_with_preprocess(
+                ".equ UNUSED, 1\n.globl entrypoint\nentrypoint:\n  exit\n",
+                "main.s",
+                None,
+            )
+            .unwrap();
+        let diagnostics = by_file(
+            artifacts.located_warnings.as_ref().unwrap(),
+            "main.s",
+            &LintConfig::default(),
+        );
+        let warnings = &diagnostics[Path::new("main.s")];
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(warnings[0].range.start, Position::new(0, 5));
+        assert!(warnings[0].message.starts_with("Unused constant 'UNUSED'"));
+    }
+}
@@ -0,0 +1,296 @@
+use {sbpf_common::opcode::Opcode, std::str::FromStr};
+
+/// What each registered syscall does and where its arguments go.
+const SYSCALLS: &[(&str, &str)] = &[
+    ("abort", "Abort the program."),
+    (
+        "sol_panic_",
+        "Abort the program with a panic location: `r1` file name, `r2` its length, `r3` line, `r4` column.",
+    ),
+    (
+        "sol_log_",
+        "Log a message: `r1` UTF-8 text, `r2` its length.",
+    ),
+    ("sol_log_64_", "Log `r1` to `r5` as hexadecimal numbers."),
+    ("sol_log_compute_units_", "Log the compute units left."),
+    (
+        "sol_log_pubkey",
+        "Log the 32-byte address at `r1` in base58.",
+    ),
+    (
+        "sol_create_program_address",
+        "Derive a program address: `r1` seeds, `r2` seed count, `r3` program id, `r4` 32-byte result. Fails if the address lies on the curve.",
+    ),
+    (
+        "sol_try_find_program_address",
+        "Find a program address and its bump seed: `r1` seeds, `r2` seed count, `r3` program id, `r4` 32-byte result, `r5` bump byte.",
+    ),
+    (
+        "sol_sha256",
+        "SHA-256 of byte slices: `r1` slices, `r2` slice count, `r3` 32-byte result.",
+    ),
+    (
+        "sol_keccak256",
+        "Keccak-256 of byte slices: `r1` slices, `r2` slice count, `r3` 32-byte result.",
+    ),
+    (
+        "sol_secp256k1_recover",
+        "Recover a secp256k1 public key: `r1` 32-byte hash, `r2` recovery id, `r3` 64-byte signature, `r4` 64-byte result.",
+    ),
+    (
+        "sol_blake3",
+        "BLAKE3 of byte slices: `r1` slices, `r2` slice count, `r3` 32-byte result.",
+    ),
+    (
+        "sol_curve_validate_point",
+        "Check a curve point: `r1` curve id, `r2` point. Returns 0 if it is valid.",
+    ),
+    (
+        "sol_curve_group_op",
+        "Add, subtract or multiply curve points: `r1` curve id, `r2` operation, `r3` left operand, `r4` right operand, `r5` result.",
+    ),
+    ("sol_get_clock_sysvar", "Copy the Clock sysvar to `r1`."),
+    (
+        "sol_get_epoch_schedule_sysvar",
+        "Copy the EpochSchedule sysvar to `r1`.",
+    ),
+    ("sol_get_fees_sysvar", "Copy the Fees sysvar to `r1`."),
+    ("sol_get_rent_sysvar", "Copy the Rent sysvar to `r1`."),
+    (
+        "sol_get_last_restart_slot_sysvar",
+        "Copy the LastRestartSlot sysvar to `r1`.",
+    ),
+    (
+        "sol_memcpy_",
+        "Copy `r3` bytes from `r2` to `r1`. The ranges must not overlap.",
+    ),
+    (
+        "sol_memmove_",
+        "Copy `r3` bytes from `r2` to `r1`. The ranges may overlap.",
+    ),
+    (
+        "sol_memcmp_",
+        "Compare `r3` bytes at `r1` and `r2`, writing the result as an `i32` to `r4`.",
+    ),
+    ("sol_memset_", "Fill `r3` bytes at `r1` with the byte `r2`."),
+    (
+        "sol_invoke_signed_c",
+        "Invoke a program with C structures: `r1` instruction, `r2` account infos, `r3` account info count, `r4` signer seeds, `r5` signer count.",
+    ),
+    (
+        "sol_invoke_signed_rust",
+        "Invoke a program with Rust structures: `r1` instruction, `r2` account infos, `r3` account info count, `r4` signer seeds, `r5` signer count.",
+    ),
+    (
+        "sol_alloc_free_",
+        "Deprecated heap allocator: returns `r1` bytes of heap in `r0`.",
+    ),
+    (
+        "sol_set_return_data",
+        "Set the return data: `r1` data, `r2` its length.",
+    ),
+    (
+        "sol_get_return_data",
+        "Copy up to `r2` bytes of return data to `r1` and the program that set it to `r3`. Returns the full length.",
+    ),
+    (
+        "sol_log_data",
+        "Log byte slices as base64: `r1` slices, `r2` slice count.",
+    ),
+    (
+        "sol_get_processed_sibling_instruction",
+        "Read an instruction processed at the current stack height: `r1` index from the most recent, `r2` meta, `r3` program id, `r4` data, `r5` accounts.",
+    ),
+    (
+        "sol_get_stack_height",
+        "Returns the invocation stack height in `r0`.",
+    ),
+    (
+        "sol_sha512",
+        "SHA-512 of byte slices: `r1` slices, `r2` slice count, `r3` 64-byte result.",
+    ),
+    (
+        "sol_curve_multiscalar_mul",
+        "Multiscalar multiplication: `r1` curve id, `r2` scalars, `r3` points, `r4` point count, `r5` result.",
+    ),
+    (
+        "sol_curve_decompress",
+        "Decompress a curve point: `r1` curve id, `r2` compressed point, `r3` result.",
+    ),
+    (
+        "sol_curve_pairing_map",
+        "Pairing map of curve points: `r1` curve id, `r2` input, `r3` result.",
+    ),
+    (
+        "sol_get_epoch_rewards_sysvar",
+        "Copy the EpochRewards sysvar to `r1`.",
+    ),
+    (
+        "sol_alt_bn128_group_op",
+        "alt_bn128 addition, scalar multiplication or pairing: `r1` operation, `r2` input, `r3` its length, `r4` result.",
+    ),
+    (
+        "sol_big_mod_exp",
+        "Modular exponentiation of big integers: `r1` parameters, `r2` result.",
+    ),
+    (
+        "sol_poseidon",
+        "Poseidon hash: `r1` parameters, `r2` endianness, `r3` slices, `r4` slice count, `r5` 32-byte result.",
+    ),
+    (
+        "sol_remaining_compute_units",
+        "Returns the compute units left in `r0`.",
+    ),
+    (
+        "sol_alt_bn128_compression",
+        "Compress or decompress alt_bn128 points: `r1` operation, `r2` input, `r3` its length, `r4` result.",
+    ),
+    (
+        "sol_get_sysvar",
+        "Copy part of a sysvar: `r1` sysvar id, `r2` result, `r3` offset, `r4` length.",
+    ),
+    (
+        "sol_get_epoch_stake",
+        "Returns in `r0` the stake delegated to the vote account at `r1`, or the total stake if `r1` is 0.",
+    ),
+];
+
+/// Markdown documenting the syscall `name`.
+pub fn syscall(name: &str) -> Option<String> {
+    SYSCALLS
+        .iter()
+        .find(|(syscall, _)| *syscall == name)
+        .map(|(name, doc)| format!("`call {}`\n\nSyscall. {}", name, doc))
+}
+
+/// Markdown documenting the mnemonic `word`.
+pub fn opcode(word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    if let Some((order, width)) = endian(&word) {
+        return Some(format!(
+            "`{} dst`\n\nConvert the low {} bits of `dst` to {} byte order, clearing the bits above.",
+            word, width, order
+        ));
+    }
+
+    let opcode = Opcode::from_str(&word).ok()?;
+    let name = opcode.to_str();
+    let doc = match opcode {
+        Opcode::Lddw => "`lddw dst, imm`\n\nLoad a 64-bit immediate or the address of a label: `dst = imm`. Takes two instruction slots.".to_string(),
+        Opcode::Ldxb | Opcode::Ldxh | Opcode::Ldxw | Opcode::Ldxdw => {
+            let size = opcode.to_size()?;
+            format!("`{name} dst, [src + off]`\n\nLoad a `{size}`: `dst = *({size} *)(src + off)`.")
+        }
+        Opcode::Stb | Opcode::Sth | Opcode::Stw | Opcode::Stdw => {
+            let size = opcode.to_size()?;
+            format!("`{name} [dst + off], imm`\n\nStore an immediate as a `{size}`: `*({size} *)(dst + off) = imm`.")
+        }
+        Opcode::Stxb | Opcode::Stxh | Opcode::Stxw | Opcode::Stxdw => {
+            let size = opcode.to_size()?;
+            format!("`{name} [dst + off], src`\n\nStore a register as a `{size}`: `*({size} *)(dst + off) = src`.")
+        }
+        Opcode::Neg32 | Opcode::Neg64 => format!("`{name} dst`\n\nNegate: `dst = -dst`{}.", width(&opcode)),
+        Opcode::Ja => "`ja off`\n\nJump unconditionally: `goto +off`.".to_string(),
+        Opcode::Call => "`call target`\n\nCall a syscall by name or a function by label. `r1` to `r5` pass arguments, `r0` holds the result and `r6` to `r9` are preserved.".to_string(),
+        Opcode::Callx => "`callx src`\n\nCall the function whose address is in `src`.".to_string(),
+        Opcode::Exit => "`exit`\n\nReturn from the current function, or end the program with `r0` as its result.".to_string(),
+        Opcode::Hor64Imm => "`hor64 dst, imm`\n\nOr an immediate into the upper 32 bits: `dst |= imm << 32`.".to_string(),
+        _ if name.starts_with('j') => {
+            let operator = opcode.to_operator()?;
+            let condition = match operator {
+                "&" => "dst & src != 0".to_string(),
+                _ => format!("dst {operator} src"),
+            };
+            let signed = if operator.starts_with('s') { ", signed" } else { "" };
+            format!(
+                "`{name} dst, src, off`\n\nJump if `{condition}`{}{signed}: `if {condition} goto +off`. `src` may be an immediate.",
+                width(&opcode)
+            )
+        }
+        _ => {
+            let operation = match opcode.to_operator() {
+                Some(operator) => format!("`dst {operator} src`"),
+                None => product(name)?.to_string(),
+            };
+            let zero = if name.contains("div") || name.contains("mod") || name.contains("rem") {
+                " Dividing by zero aborts the program."
+            } else {
+                ""
+            };
+            format!("`{name} dst, src`\n\n{operation}{}. `src` may be an immediate.{zero}", width(&opcode))
+        }
+    };
+    Some(doc)
+}
+
+/// The byte order and width of `le16` to `be64`.
+fn endian(word: &str) -> Option<(&'static str, &str)> {
+    let (order, width) = match word.split_at_checked(2)? {
+        ("le", width) => ("little-endian", width),
+        ("be", width) => ("big-endian", width),
+        _ => return None,
+    };
+    matches!(width, "16" | "32" | "64").then_some((order, width))
+}
+
+fn width(opcode: &Opcode) -> &'static str {
+    if opcode.is_32bit() || opcode.to_str().ends_with("32") {
+        " on the low 32 bits"
+    } else {
+        ""
+    }
+}
+
+/// The product, quotient and remainder instructions, which have no
+/// operator of their own.
+fn product(name: &str) -> Option<&'static str> {
+    Some(match name.trim_end_matches("32").trim_end_matches("64") {
+        "lmul" => "Multiply, keeping the low half: `dst *= src`",
+        "uhmul" => "High half of the unsigned product: `dst = (dst * src) >> 64`",
+        "shmul" => "High half of the signed product: `dst = (dst * src) s>> 64`",
+        "udiv" => "Unsigned divide: `dst /= src`",
+        "urem" => "Unsigned remainder: `dst %= src`",
+        "sdiv" => "Signed divide: `dst s/= src`",
+        "srem" => "Signed remainder: `dst s%= src`",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, sbpf_common::syscalls::REGISTERED_SYSCALLS};
+
+    #[test]
+    fn test_every_syscall_is_documented() {
+        for name in REGISTERED_SYSCALLS {
+            assert!(syscall(name).is_some(), "{} has no documentation", name);
+        }
+        assert_eq!(SYSCALLS.len(), REGISTERED_SYSCALLS.len());
+        assert!(syscall("sol_log_").unwrap().contains("`r2` its length"));
+        assert!(syscall("mov64").is_none());
+    }
+
+    #[test]
+    fn test_opcode_docs() {
+        assert!(opcode("add64").unwrap().contains("`dst += src`"));
+        assert!(
+            opcode("ADD32")
+                .unwrap()
+                .contains("`dst += src` on the low 32 bits")
+        );
+        assert!(opcode("ldxdw").unwrap().contains("*(u64 *)(src + off)"));
+        assert!(opcode("stxb").unwrap().contains("*(u8 *)(dst + off) = src"));
+        assert!(
+            opcode("jsgt")
+                .unwrap()
+                .contains("`if dst s> src goto +off`")
+        );
+        assert!(opcode("jset32").unwrap().contains("dst & src != 0"));
+        assert!(opcode("udiv64").unwrap().contains("Dividing by zero"));
+        assert!(opcode("uhmul64").unwrap().contains(">> 64"));
+        assert!(opcode("be32").unwrap().contains("big-endian"));
+        assert!(opcode("exit").unwrap().contains("`r0`"));
+        assert!(opcode("be8").is_none());
+        assert!(opcode("entrypoint").is_none());
+    }
+}
@@ -0,0 +1,39 @@
+//! Language server for sBPF assembly, speaking LSP over stdin and stdout.
+
+mod diagnostics;
+mod docs;
+mod server;
+mod text;
+
+use {
+    anyhow::Result,
+    lsp_server::Connection,
+    lsp_types::InitializeParams,
+    server::{Options, Server},
+};
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let params: InitializeParams = serde_json::from_value(
+        connection.initialize(serde_json::to_value(server::capabilities())?)?,
+    )?;
+    let options: Options = match params.initialization_options {
+        Some(options) => serde_json::from_value(options)?,
+        None => Options::default(),
+    };
+    // `sbpf.toml` sits in the project root, the first workspace folder.
+    let root = match params
+        .workspace_folders
+        .iter()
+        .flatten()
+        .find_map(|folder| text::uri_to_path(&folder.uri))
+    {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let options = options.with_manifest(&root)?;
+    Server::new(options)?.run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,597 @@
+use {
+    crate::{diagnostics, docs, text},
+    anyhow::{Result, anyhow},
+    lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response},
+    lsp_types::{
+        DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse, Hover, HoverContents,
+        HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+        PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind,
+        TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+        notification::{
+            DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+            PublishDiagnostics,
+        },
+        request::{DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+    },
+    sbpf_assembler::{
+        Assembler, AssemblerOption, DefinitionKind, FsFileResolver, LintConfig, ParseCache,
+        SbpfArch, SymbolDefinition, SymbolIndex, SymbolLocation, SymbolSection,
+    },
+    serde::Deserialize,
+    std::{
+        collections::{BTreeSet, HashMap},
+        fs, io,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Settings a client passes as `initializationOptions`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Options {
+    /// Target architecture, `v0` to `v3`.
+    pub arch: Option<String>,
+    /// Directories searched for `.include`d files.
+    pub include_paths: Vec<PathBuf>,
+    /// Lint levels and limits, from the workspace's `sbpf.toml` rather than
+    /// the client.
+    #[serde(skip)]
+    pub lints: LintConfig,
+}
+
+/// The part of `sbpf.toml` the server reads; the rest is the command line's.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    lint: LintConfig,
+}
+
+impl Options {
+    /// Take the `[lint]` table of `sbpf.toml` in `root`, if there is one.
+    pub fn with_manifest(mut self, root: &Path) -> Result<Self> {
+        let path = root.join("sbpf.toml");
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(anyhow!("Failed to read '{}': {}", path.display(), e)),
+        };
+        let manifest: Manifest = toml::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid '{}': {}", path.display(), e))?;
+        self.lints = manifest.lint;
+        Ok(self)
+    }
+}
+
+/// An open document.
+struct Document {
+    text: String,
+    /// The index of the last version that parsed, so that navigation keeps
+    /// working while the document is being edited.
+    index: SymbolIndex,
+    /// Files this document last published diagnostics for.
+    published: BTreeSet<PathBuf>,
+}
+
+pub struct Server {
+    assembler: Assembler,
+    resolver: FsFileResolver,
+    lints: LintConfig,
+    documents: HashMap<Uri, Document>,
+}
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+impl Server {
+    pub fn new(options: Options) -> Result<Self> {
+        let mut assembler_options = AssemblerOption::default();
+        if let Some(arch) = &options.arch {
+            assembler_options =
+                assembler_options.with_arch(SbpfArch::from_str(arch).map_err(|e| anyhow!(e))?);
+        }
+        for path in &options.include_paths {
+            assembler_options = assembler_options.with_include_path(path);
+        }
+        assembler_options = assembler_options.with_lints(options.lints.clone());
+        Ok(Self {
+            // Reassembling after every keystroke only parses what changed.
+            assembler: Assembler::new(assembler_options).with_cache(ParseCache::new()),
+            resolver: FsFileResolver::with_include_paths(options.include_paths),
+            lints: options.lints,
+            documents: HashMap::new(),
+        })
+    }
+
+    /// Serve `connection` until the client shuts the server down.
+    pub fn run(&mut self, connection: &Connection) -> Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    connection.sender.send(self.respond(request).into())?;
+                }
+                Message::Notification(notification) => {
+                    for params in self.notify(notification)? {
+                        connection.sender.send(
+                            Notification::new(PublishDiagnostics::METHOD.to_string(), params)
+                                .into(),
+                        )?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => handle::<GotoDefinition>(request, |params| {
+                let position = params.text_document_position_params;
+                self.definition(&position.text_document.uri, position.position)
+                    .map(GotoDefinitionResponse::Scalar)
+            }),
+            HoverRequest::METHOD => handle::<HoverRequest>(request, |params| {
+                let position = params.text_document_position_params;
+                self.hover(&position.text_document.uri, position.position)
+            }),
+            DocumentSymbolRequest::METHOD => handle::<DocumentSymbolRequest>(request, |params| {
+                self.document_symbols(&params.text_document.uri)
+                    .map(DocumentSymbolResponse::Nested)
+            }),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request '{}'", method),
+            ),
+        }
+    }
+
+    /// Apply a notification, returning the diagnostics to publish.
+    fn notify(&mut self, notification: Notification) -> Result<Vec<PublishDiagnosticsParams>> {
+        Ok(match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = params::<DidOpenTextDocument>(notification)?;
+                self.update(params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = params::<DidChangeTextDocument>(notification)?;
+                // With full sync the last change holds the whole document.
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(params.text_document.uri, change.text),
+                    None => Vec::new(),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = params::<DidCloseTextDocument>(notification)?;
+                self.close(&params.text_document.uri)
+            }
+            _ => Vec::new(),
+        })
+    }
+
+    /// Store the new text of `uri`, reassemble it and index it.
+    pub fn update(&mut self, uri: Uri, text: String) -> Vec<PublishDiagnosticsParams> {
+        let root = source_path(&uri);
+        let (result, index) =
+            self.assembler
+                .assemble_and_index_with_preprocess(&text, &root, Some(&self.resolver));
+        let errors = match result {
+            Ok(artifacts) => artifacts.located_warnings,
+            Err(errors) => Some(errors),
+        };
+
+        let mut diagnostics = errors
+            .map(|errors| diagnostics::by_file(&errors, &root, &self.lints))
+            .unwrap_or_default();
+        // The document itself always gets its diagnostics, even none.
+        diagnostics.entry(PathBuf::from(&root)).or_default();
+        let document = self.documents.entry(uri.clone()).or_insert(Document {
+            text: String::new(),
+            index: SymbolIndex::default(),
+            published: BTreeSet::new(),
+        });
+        document.text = text;
+        if let Some(index) = index {
+            document.index = index;
+        }
+        // Files that had diagnostics last time and have none now are cleared.
+        for path in &document.published {
+            diagnostics.entry(path.clone()).or_default();
+        }
+        document.published = diagnostics
+            .iter()
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        diagnostics
+            .into_iter()
+            .filter_map(|(path, diagnostics)| {
+                let uri = if path == Path::new(&root) {
+                    uri.clone()
+                } else {
+                    text::path_to_uri(&self.file_path(&path.to_string_lossy(), &root)?)?
+                };
+                Some(PublishDiagnosticsParams::new(uri, diagnostics, None))
+            })
+            .collect()
+    }
+
+    /// Forget `uri`, clearing the diagnostics it published.
+    pub fn close(&mut self, uri: &Uri) -> Vec<PublishDiagnosticsParams> {
+        let Some(document) = self.documents.remove(uri) else {
+            return Vec::new();
+        };
+        let root = source_path(uri);
+        document
+            .published
+            .iter()
+            .filter_map(|path| {
+                let uri = if path == Path::new(&root) {
+                    uri.clone()
+                } else {
+                    text::path_to_uri(&self.file_path(&path.to_string_lossy(), &root)?)?
+                };
+                Some(PublishDiagnosticsParams::new(uri, Vec::new(), None))
+            })
+            .collect()
+    }
+
+    /// Where the label or constant under `position` is defined.
+    pub fn definition(&self, uri: &Uri, position: Position) -> Option<Location> {
+        let document = self.documents.get(uri)?;
+        let (_, word) = text::word_at(&document.text, text::offset(&document.text, position)?)?;
+        let definition = document.index.definition(word)?;
+        self.location(uri, &definition.location, word.len())
+    }
+
+    /// Documentation of the name, mnemonic or syscall under `position`.
+    pub fn hover(&self, uri: &Uri, position: Position) -> Option<Hover> {
+        let document = self.documents.get(uri)?;
+        let (start, word) = text::word_at(&document.text, text::offset(&document.text, position)?)?;
+        let value = match document.index.definition(word) {
+            Some(definition) => describe(definition),
+            None => docs::syscall(word).or_else(|| docs::opcode(word))?,
+        };
+        let line_start = document.text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line = text::line(&document.text, position.line);
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(Range::new(
+                text::position(line, position.line, start - line_start),
+                text::position(line, position.line, start - line_start + word.len()),
+            )),
+        })
+    }
+
+    /// The labels and constants defined in `uri` itself.
+    pub fn document_symbols(&self, uri: &Uri) -> Option<Vec<DocumentSymbol>> {
+        let document = self.documents.get(uri)?;
+        let root = source_path(uri);
+        Some(
+            document
+                .index
+                .definitions_in(&root)
+                .map(|definition| {
+                    let number = definition.location.line - 1;
+                    let line = text::line(&document.text, number);
+                    let column = definition.location.column;
+                    #[allow(deprecated)]
+                    DocumentSymbol {
+                        name: definition.name.clone(),
+                        detail: Some(detail(definition)),
+                        kind: match definition.kind {
+                            DefinitionKind::Label(SymbolSection::Text) => SymbolKind::FUNCTION,
+                            DefinitionKind::Label(_) => SymbolKind::VARIABLE,
+                            DefinitionKind::Constant => SymbolKind::CONSTANT,
+                        },
+                        tags: None,
+                        deprecated: None,
+                        range: Range::new(
+                            text::position(line, number, 0),
+                            text::position(line, number, line.len()),
+                        ),
+                        selection_range: Range::new(
+                            text::position(line, number, column),
+                            text::position(line, number, column + definition.name.len()),
+                        ),
+                        children: None,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// `location` in an LSP client's terms, with the text of the file read
+    /// from the open documents or else from disk.
+    fn location(&self, uri: &Uri, location: &SymbolLocation, len: usize) -> Option<Location> {
+        let root = source_path(uri);
+        let (uri, contents) = if location.file == root {
+            (uri.clone(), self.documents.get(uri)?.text.clone())
+        } else {
+            let path = self.file_path(&location.file, &root)?;
+            let uri = text::path_to_uri(&path)?;
+            let contents = match self.documents.get(&uri) {
+                Some(document) => document.text.clone(),
+                None => fs::read_to_string(&path).ok()?,
+            };
+            (uri, contents)
+        };
+        let number = location.line - 1;
+        let line = text::line(&contents, number);
+        Some(Location::new(
+            uri,
+            Range::new(
+                text::position(line, number, location.column),
+                text::position(line, number, location.column + len),
+            ),
+        ))
+    }
+
+    /// Where a file the assembler names `file`, included from `root`, is
+    /// found.
+    fn file_path(&self, file: &str, root: &str) -> Option<PathBuf> {
+        if file == root {
+            return Some(PathBuf::from(root));
+        }
+        self.resolver.locate(file, root)
+    }
+}
+
+/// The parameters of a notification of type `N`.
+fn params<N: lsp_types::notification::Notification>(
+    notification: Notification,
+) -> Result<N::Params> {
+    Ok(notification.extract(N::METHOD)?)
+}
+
+/// Run `handler` on the parameters of `request`.
+fn handle<R: lsp_types::request::Request>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    let id: RequestId = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => Response::new_ok(id, handler(params)),
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+/// The path the assembler knows the document at `uri` by. Documents that
+/// aren't files, such as unsaved buffers, go by their URI.
+fn source_path(uri: &Uri) -> String {
+    text::uri_to_path(uri)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|| uri.as_str().to_string())
+}
+
+fn detail(definition: &SymbolDefinition) -> String {
+    match definition.kind {
+        DefinitionKind::Label(section) => format!(".{}", section_name(section)),
+        DefinitionKind::Constant => definition.value.clone().unwrap_or_default(),
+    }
+}
+
+fn section_name(section: SymbolSection) -> &'static str {
+    match section {
+        SymbolSection::Text => "text",
+        SymbolSection::Rodata => "rodata",
+        SymbolSection::Data => "data",
+        SymbolSection::Bss => "bss",
+    }
+}
+
+fn describe(definition: &SymbolDefinition) -> String {
+    let file = Path::new(&definition.location.file)
+        .file_name()
+        .map_or(definition.location.file.as_str(), |name| {
+            name.to_str().unwrap_or_default()
+        });
+    let defined = format!("{}:{}", file, definition.location.line);
+    match definition.kind {
+        DefinitionKind::Label(section) => format!(
+            "`{}`\n\nLabel in `.{}`, defined at `{}`.",
+            definition.name,
+            section_name(section),
+            defined
+        ),
+        DefinitionKind::Constant => format!(
+            "`.equ {}, {}`\n\nConstant defined at `{}`.",
+            definition.name,
+            definition.value.as_deref().unwrap_or_default(),
+            defined
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        lsp_types::{
+            DiagnosticSeverity, DidOpenTextDocumentParams, HoverParams, TextDocumentIdentifier,
+            TextDocumentItem, TextDocumentPositionParams,
+        },
+        std::thread,
+    };
+
+    const PROGRAM: &str = ".equ LEN, 12\n.globl entrypoint\nentrypoint:\n  lddw r1, message\n  mov64 r2, LEN\n  call sol_log_\n  exit\n.rodata\nmessage: .ascii \"Hello world!\"\n";
+
+    fn uri() -> Uri {
+        Uri::from_str("file:///tmp/sbpf-lsp/main.s").unwrap()
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut server = Server::new(Options::default()).unwrap();
+        let published = server.update(uri(), PROGRAM.to_string());
+        assert_eq!(published.len(), 1);
+        assert!(published[0].diagnostics.is_empty());
+
+        let location = server.definition(&uri(), Position::new(3, 14)).unwrap();
+        assert_eq!(location.uri, uri());
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(8, 0), Position::new(8, 7))
+        );
+        let constant = server.definition(&uri(), Position::new(4, 13)).unwrap();
+        assert_eq!(constant.range.start, Position::new(0, 5));
+        assert!(server.definition(&uri(), Position::new(5, 8)).is_none());
+
+        let symbols = server.document_symbols(&uri()).unwrap();
+        let summary: Vec<_> = symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("LEN", SymbolKind::CONSTANT),
+                ("entrypoint", SymbolKind::FUNCTION),
+                ("message", SymbolKind::VARIABLE)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut server = Server::new(Options::default()).unwrap();
+        server.update(uri(), PROGRAM.to_string());
+        let hover = |line, character| {
+            let hover = server.hover(&uri(), Position::new(line, character))?;
+            match hover.contents {
+                HoverContents::Markup(markup) => Some((markup.value, hover.range.unwrap())),
+                _ => None,
+            }
+        };
+
+        let (value, range) = hover(5, 9).unwrap();
+        assert!(value.contains("Syscall"), "{}", value);
+        assert_eq!(range, Range::new(Position::new(5, 7), Position::new(5, 15)));
+        assert!(hover(4, 3).unwrap().0.contains("`dst = src`"));
+        assert!(hover(4, 12).unwrap().0.contains("`.equ LEN, 12`"));
+        assert!(hover(3, 12).unwrap().0.contains("Label in `.rodata`"));
+        assert!(hover(3, 7).is_none());
+    }
+
+    #[test]
+    fn test_diagnostics_are_cleared_once_fixed() {
+        let mut server = Server::new(Options::default()).unwrap();
+        let program = ".globl entrypoint\nentrypoint:\n  ja done\ndone:\n  exit\n";
+        assert!(
+            server.update(uri(), program.to_string())[0]
+                .diagnostics
+                .is_empty()
+        );
+
+        let broken = server.update(uri(), program.replace("exit", "mov64 r1,"));
+        assert_eq!(broken[0].diagnostics.len(), 1);
+        assert_eq!(broken[0].diagnostics[0].range.start.line, 4);
+        // Navigation keeps working from the last version that parsed.
+        let done = server.definition(&uri(), Position::new(2, 6)).unwrap();
+        assert_eq!(done.range.start, Position::new(3, 0));
+
+        let fixed = server.update(uri(), program.to_string());
+        assert_eq!(fixed.len(), 1);
+        assert!(fixed[0].diagnostics.is_empty());
+        assert!(server.close(&uri()).is_empty());
+    }
+
+    #[test]
+    fn test_lint_policy_from_manifest() {
+        let dir = std::env::temp_dir().join("sbpf_lsp_test_lint_policy");
+        fs::create_dir_all(&dir).unwrap();
+        let program = ".equ UNUSED, 1\n.globl entrypoint\nentrypoint:\n  exit\n  exit\n";
+        fs::write(
+            dir.join("sbpf.toml"),
+            "[build]\narch = \"v3\"\n\n[lint]\nunused-constant = \"allow\"\nunreachable-code = \"deny\"\n",
+        )
+        .unwrap();
+        let options = Options::default().with_manifest(&dir).unwrap();
+        let mut server = Server::new(options).unwrap();
+        let published = server.update(uri(), program.to_string());
+        let diagnostics = &published[0].diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].range.start.line, 4);
+
+        fs::write(dir.join("sbpf.toml"), "[lint]\nunused-label = \"never\"\n").unwrap();
+        assert!(Options::default().with_manifest(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Options::default().with_manifest(&dir).is_ok());
+    }
+
+    #[test]
+    fn test_serves_a_connection() {
+        let (server_side, client) = Connection::memory();
+        let handle = thread::spawn(move || {
+            Server::new(Options::default())
+                .unwrap()
+                .run(&server_side)
+                .unwrap()
+        });
+
+        let open = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri(),
+                "sbpf".to_string(),
+                1,
+                "entrypoint:\n  mov64 r1,\n".to_string(),
+            ),
+        };
+        client
+            .sender
+            .send(Notification::new(DidOpenTextDocument::METHOD.to_string(), open).into())
+            .unwrap();
+        let Message::Notification(published) = client.receiver.recv().unwrap() else {
+            panic!("expected diagnostics");
+        };
+        let params: PublishDiagnosticsParams = serde_json::from_value(published.params).unwrap();
+        assert_eq!(params.diagnostics.len(), 1);
+
+        let hover = HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri()),
+                Position::new(1, 3),
+            ),
+            work_done_progress_params: Default::default(),
+        };
+        client
+            .sender
+            .send(Request::new(RequestId::from(1), HoverRequest::METHOD.to_string(), hover).into())
+            .unwrap();
+        let Message::Response(response) = client.receiver.recv().unwrap() else {
+            panic!("expected a hover");
+        };
+        let hover: Hover = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(matches!(hover.contents, HoverContents::Markup(_)));
+
+        client
+            .sender
+            .send(Request::new(RequestId::from(2), "shutdown".to_string(), ()).into())
+            .unwrap();
+        assert!(matches!(
+            client.receiver.recv().unwrap(),
+            Message::Response(_)
+        ));
+        client
+            .sender
+            .send(Notification::new("exit".to_string(), ()).into())
+            .unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use {
+    lsp_types::{Position, Uri},
+    std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Byte offset of `position` in `text`. LSP counts characters in UTF-16
+/// code units; a position past the end of its line is clamped to the end.
+pub fn offset(text: &str, position: Position) -> Option<usize> {
+    let line_start = if position.line == 0 {
+        0
+    } else {
+        text.match_indices('\n')
+            .nth(position.line as usize - 1)
+            .map(|(i, _)| i + 1)?
+    };
+    let line = line_text(&text[line_start..]);
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line.len())
+}
+
+/// Position of byte `column` in `line`, the text of 0-based line `number`.
+pub fn position(line: &str, number: u32, column: usize) -> Position {
+    let column = column.min(line.len());
+    let prefix = line.get(..column).unwrap_or(line);
+    Position::new(number, prefix.encode_utf16().count() as u32)
+}
+
+/// The text of 0-based line `number` of `text`, without its line ending.
+pub fn line(text: &str, number: u32) -> &str {
+    text.split('\n')
+        .nth(number as usize)
+        .map(line_text)
+        .unwrap_or_default()
+}
+
+fn line_text(rest: &str) -> &str {
+    let line = rest.split('\n').next().unwrap_or_default();
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// The name, mnemonic or directive under byte `offset`, with where it starts.
+pub fn word_at(text: &str, offset: usize) -> Option<(usize, &str)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let offset = offset.min(text.len());
+    let start = text[..offset].rfind(|c| !is_word(c)).map_or(0, |i| i + 1);
+    let end = text[offset..]
+        .find(|c| !is_word(c))
+        .map_or(text.len(), |i| offset + i);
+    (start < end).then(|| (start, &text[start..end]))
+}
+
+/// The file a `file:` URI names.
+pub fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = uri.as_str().strip_prefix("file://")?;
+    let path = path.find('/').map(|i| &path[i..])?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // `file:///C:/x` names `C:/x` on Windows.
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// The `file:` URI of `path`.
+pub fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path.to_str()?.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Uri::from_str(&uri).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        lsp_types::{Position, Uri},
+        std::{path::Path, str::FromStr},
+    };
+
+    #[test]
+    fn test_offsets_count_utf16() {
+        let text = "; é 🦀\n  mov64 r1, 2\r\n";
+        assert_eq!(offset(text, Position::new(0, 2)), Some(2));
+        // `é` is one UTF-16 unit and two bytes, the crab two units and four.
+        assert_eq!(offset(text, Position::new(0, 4)), Some(5));
+        assert_eq!(
+            offset(text, Position::new(0, 6)),
+            Some(text.find('\n').unwrap())
+        );
+        assert_eq!(
+            offset(text, Position::new(1, 99)),
+            Some(text.find('\r').unwrap())
+        );
+        assert_eq!(offset(text, Position::new(5, 0)), None);
+
+        assert_eq!(line(text, 0), "; é 🦀");
+        assert_eq!(line(text, 1), "  mov64 r1, 2");
+        assert_eq!(position(line(text, 0), 0, 5), Position::new(0, 4));
+    }
+
+    #[test]
+    fn test_word_at() {
+        let text = "  call sol_log_\n  .equ LEN, 12";
+        assert_eq!(word_at(text, 3), Some((2, "call")));
+        assert_eq!(word_at(text, 15), Some((7, "sol_log_")));
+        assert_eq!(word_at(text, 20), Some((18, ".equ")));
+        assert_eq!(word_at(text, 6), Some((2, "call")));
+        assert_eq!(word_at(text, 1), None);
+    }
+
+    #[test]
+    fn test_uri_paths() {
+        let uri = Uri::from_str("file:///home/me/my%20program/main.s").unwrap();
+        assert_eq!(
+            uri_to_path(&uri).unwrap(),
+            Path::new("/home/me/my program/main.s")
+        );
+        assert_eq!(
+            path_to_uri(Path::new("/home/me/my program/main.s")).unwrap(),
+            uri
+        );
+        let windows = Uri::from_str("file:///C:/src/main.s").unwrap();
+        assert_eq!(uri_to_path(&windows).unwrap(), Path::new("C:/src/main.s"));
+        assert!(uri_to_path(&Uri::from_str("untitled:Untitled-1").unwrap()).is_none());
+    }
+}
@@ -1,6 +1,11 @@
 use {
-    solana_clock::Clock, solana_epoch_schedule::EpochSchedule,
-    solana_last_restart_slot::LastRestartSlot, solana_rent::Rent,
+    sbpf_common::program_error::ErrorRegistry,
+    sbpf_vm::{compute_profile::PROFILE_DIR_ENV, coverage::COVERAGE_DIR_ENV},
+    solana_clock::Clock,
+    solana_epoch_schedule::EpochSchedule,
+    solana_last_restart_slot::LastRestartSlot,
+    solana_rent::Rent,
+    std::path::PathBuf,
 };
 
 #[derive(Debug, Clone)]
@@ -9,6 +14,21 @@ pub struct RuntimeConfig {
     pub max_call_depth: usize,
     pub heap_size: usize,
     pub max_cpi_depth: usize,
+    /// Expected net lamport change per run: positive for a mint, negative for a burn.
+    pub expected_lamport_change: i64,
+    /// Reject runs that leave a writable account below its rent-exempt minimum.
+    /// Off by default, as not every test sets up rent-exempt balances.
+    pub check_rent_exemption: bool,
+    /// Custom errors of the program, named in the log of a failed run.
+    pub errors: ErrorRegistry,
+    /// Record which instructions each program executes, for
+    /// [`Runtime::write_coverage`](crate::Runtime::write_coverage) to write
+    /// to this directory.
+    pub coverage_dir: Option<PathBuf>,
+    /// Record the compute units each program uses per instruction and call
+    /// stack, for [`Runtime::write_compute_profiles`](crate::Runtime::write_compute_profiles)
+    /// to write to this directory.
+    pub profile_dir: Option<PathBuf>,
 }
 
 impl Default for RuntimeConfig {
@@ -18,6 +38,24 @@ impl Default for RuntimeConfig {
             max_call_depth: 64,
             heap_size: 32 * 1024,
             max_cpi_depth: 4,
+            expected_lamport_change: 0,
+            check_rent_exemption: false,
+            errors: ErrorRegistry::new(),
+            coverage_dir: None,
+            profile_dir: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// The default configuration, recording coverage when
+    /// `$SBPF_COVERAGE_DIR` is set and compute profiles when
+    /// `$SBPF_PROFILE_DIR` is, as `sbpf test` sets them.
+    pub fn from_env() -> Self {
+        Self {
+            coverage_dir: std::env::var_os(COVERAGE_DIR_ENV).map(PathBuf::from),
+            profile_dir: std::env::var_os(PROFILE_DIR_ENV).map(PathBuf::from),
+            ..Self::default()
         }
     }
 }
@@ -30,6 +68,65 @@ pub struct SysvarContext {
     pub last_restart_slot: LastRestartSlot,
 }
 
+/// Clock overrides applied to a `SysvarContext` before a run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClockWarp {
+    pub slot: Option<u64>,
+    pub epoch: Option<u64>,
+    pub unix_timestamp: Option<i64>,
+}
+
+impl SysvarContext {
+    /// Minimum lamport balance for an account with `data_len` bytes of data to
+    /// be rent-exempt under the current rent sysvar.
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        self.rent.minimum_balance(data_len)
+    }
+
+    /// Warp the clock to `slot`, deriving the epoch from the epoch schedule.
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        let epoch = self.epoch_schedule.get_epoch(slot);
+        if epoch != self.clock.epoch {
+            self.clock.epoch_start_timestamp = self.clock.unix_timestamp;
+        }
+        self.clock.slot = slot;
+        self.clock.epoch = epoch;
+        self.clock.leader_schedule_epoch = self.epoch_schedule.get_leader_schedule_epoch(slot);
+    }
+
+    /// Warp the clock to the first slot of `epoch`.
+    pub fn warp_to_epoch(&mut self, epoch: u64) {
+        self.warp_to_slot(self.epoch_schedule.get_first_slot_in_epoch(epoch));
+    }
+
+    /// Advance the clock by `slots` slots.
+    pub fn advance_slots(&mut self, slots: u64) {
+        self.warp_to_slot(self.clock.slot.saturating_add(slots));
+    }
+
+    /// Set the clock's unix timestamp.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        self.clock.unix_timestamp = unix_timestamp;
+    }
+
+    /// Advance the clock's unix timestamp by `seconds`.
+    pub fn advance_unix_timestamp(&mut self, seconds: i64) {
+        self.clock.unix_timestamp = self.clock.unix_timestamp.saturating_add(seconds);
+    }
+
+    /// Apply clock overrides. An explicit slot takes precedence over an epoch.
+    pub fn apply_warp(&mut self, warp: &ClockWarp) {
+        if let Some(unix_timestamp) = warp.unix_timestamp {
+            self.set_unix_timestamp(unix_timestamp);
+        }
+        match (warp.slot, warp.epoch) {
+            (Some(slot), _) => self.warp_to_slot(slot),
+            (None, Some(epoch)) => self.warp_to_epoch(epoch),
+            (None, None) => {}
+        }
+    }
+}
+
 /// Reference: https://github.com/anza-xyz/agave/blob/master/program-runtime/src/execution_budget.rs
 #[derive(Debug, Clone)]
 pub struct ExecutionCost {
@@ -123,3 +220,77 @@ impl Default for ExecutionCost {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimum_balance_uses_rent_sysvar() {
+        let mut sysvars = SysvarContext::default();
+        assert_eq!(sysvars.minimum_balance(0), 890_880);
+        assert_eq!(
+            sysvars.minimum_balance(165),
+            sysvars.rent.minimum_balance(165)
+        );
+        sysvars.rent = Rent::with_lamports_per_byte(1);
+        assert_eq!(sysvars.minimum_balance(0), 128);
+    }
+
+    #[test]
+    fn warp_to_slot_derives_epoch() {
+        let mut sysvars = SysvarContext::default();
+        let slot = sysvars.epoch_schedule.get_first_slot_in_epoch(20) + 7;
+        sysvars.warp_to_slot(slot);
+        assert_eq!(sysvars.clock.slot, slot);
+        assert_eq!(sysvars.clock.epoch, 20);
+        assert_eq!(
+            sysvars.clock.leader_schedule_epoch,
+            sysvars.epoch_schedule.get_leader_schedule_epoch(slot)
+        );
+    }
+
+    #[test]
+    fn warp_to_epoch_starts_at_first_slot() {
+        let mut sysvars = SysvarContext::default();
+        sysvars.set_unix_timestamp(1_000);
+        sysvars.warp_to_epoch(3);
+        assert_eq!(
+            sysvars.clock.slot,
+            sysvars.epoch_schedule.get_first_slot_in_epoch(3)
+        );
+        assert_eq!(sysvars.clock.epoch, 3);
+        assert_eq!(sysvars.clock.epoch_start_timestamp, 1_000);
+    }
+
+    #[test]
+    fn advance_slots_and_timestamp() {
+        let mut sysvars = SysvarContext::default();
+        sysvars.warp_to_slot(100);
+        sysvars.advance_slots(50);
+        assert_eq!(sysvars.clock.slot, 150);
+
+        sysvars.set_unix_timestamp(10);
+        sysvars.advance_unix_timestamp(86_400);
+        assert_eq!(sysvars.clock.unix_timestamp, 86_410);
+    }
+
+    #[test]
+    fn apply_warp_prefers_slot_over_epoch() {
+        let mut sysvars = SysvarContext::default();
+        sysvars.apply_warp(&ClockWarp {
+            slot: Some(1_000),
+            epoch: Some(500),
+            unix_timestamp: Some(42),
+        });
+        assert_eq!(sysvars.clock.slot, 1_000);
+        assert_eq!(sysvars.clock.unix_timestamp, 42);
+
+        sysvars.apply_warp(&ClockWarp {
+            epoch: Some(30),
+            ..ClockWarp::default()
+        });
+        assert_eq!(sysvars.clock.epoch, 30);
+        assert_eq!(sysvars.clock.unix_timestamp, 42);
+    }
+}
@@ -0,0 +1,154 @@
+use {
+    crate::errors::{RuntimeError, RuntimeResult},
+    solana_account::Account,
+    solana_address::Address,
+    std::{collections::HashMap, fmt},
+};
+
+/// Lamport balance of a single account before and after a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LamportDelta {
+    pub pubkey: Address,
+    pub pre: u64,
+    pub post: u64,
+}
+
+impl LamportDelta {
+    pub fn change(&self) -> i128 {
+        self.post as i128 - self.pre as i128
+    }
+}
+
+impl fmt::Display for LamportDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} ({:+})",
+            self.pubkey,
+            self.pre,
+            self.post,
+            self.change()
+        )
+    }
+}
+
+pub(crate) fn format_deltas(deltas: &[LamportDelta]) -> String {
+    deltas
+        .iter()
+        .map(|delta| delta.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Record the lamport balance of every account in the store.
+pub fn lamport_snapshot(accounts: &HashMap<Address, Account>) -> HashMap<Address, u64> {
+    accounts
+        .iter()
+        .map(|(pubkey, account)| (*pubkey, account.lamports))
+        .collect()
+}
+
+/// Check that the total lamports across all accounts changed by exactly
+/// `expected_change` (zero unless a mint or burn is configured). On failure the
+/// error lists every account whose balance changed, sorted by address.
+pub fn check_lamport_conservation(
+    pre: &HashMap<Address, u64>,
+    accounts: &HashMap<Address, Account>,
+    expected_change: i64,
+) -> RuntimeResult<()> {
+    let mut deltas: Vec<LamportDelta> = pre
+        .keys()
+        .chain(accounts.keys().filter(|pubkey| !pre.contains_key(*pubkey)))
+        .map(|pubkey| LamportDelta {
+            pubkey: *pubkey,
+            pre: pre.get(pubkey).copied().unwrap_or(0),
+            post: accounts.get(pubkey).map(|a| a.lamports).unwrap_or(0),
+        })
+        .filter(|delta| delta.pre != delta.post)
+        .collect();
+
+    let pre_total: u128 = pre.values().map(|&lamports| lamports as u128).sum();
+    let post_total: u128 = accounts.values().map(|a| a.lamports as u128).sum();
+    if post_total as i128 - pre_total as i128 == expected_change as i128 {
+        return Ok(());
+    }
+
+    deltas.sort_by(|a, b| a.pubkey.as_ref().cmp(b.pubkey.as_ref()));
+    Err(RuntimeError::UnbalancedInstruction(
+        pre_total, post_total, deltas,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCT_1: Address = Address::new_from_array([1u8; 32]);
+    const ACCT_2: Address = Address::new_from_array([2u8; 32]);
+    const ACCT_3: Address = Address::new_from_array([3u8; 32]);
+
+    fn account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            ..Account::default()
+        }
+    }
+
+    fn store(list: &[(Address, u64)]) -> HashMap<Address, Account> {
+        list.iter()
+            .map(|(pubkey, lamports)| (*pubkey, account(*lamports)))
+            .collect()
+    }
+
+    #[test]
+    fn balanced_transfer_passes() {
+        let pre = lamport_snapshot(&store(&[(ACCT_1, 100), (ACCT_2, 0)]));
+        let post = store(&[(ACCT_1, 40), (ACCT_2, 60)]);
+        assert!(check_lamport_conservation(&pre, &post, 0).is_ok());
+    }
+
+    #[test]
+    fn unbalanced_reports_changed_accounts() {
+        let pre = lamport_snapshot(&store(&[(ACCT_1, 100), (ACCT_2, 0), (ACCT_3, 5)]));
+        let post = store(&[(ACCT_1, 40), (ACCT_2, 70), (ACCT_3, 5)]);
+
+        match check_lamport_conservation(&pre, &post, 0) {
+            Err(RuntimeError::UnbalancedInstruction(pre_total, post_total, deltas)) => {
+                assert_eq!(pre_total, 105);
+                assert_eq!(post_total, 115);
+                assert_eq!(
+                    deltas,
+                    vec![
+                        LamportDelta {
+                            pubkey: ACCT_1,
+                            pre: 100,
+                            post: 40,
+                        },
+                        LamportDelta {
+                            pubkey: ACCT_2,
+                            pre: 0,
+                            post: 70,
+                        },
+                    ]
+                );
+            }
+            other => panic!("expected UnbalancedInstruction, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn configured_burn_passes() {
+        let pre = lamport_snapshot(&store(&[(ACCT_1, 100)]));
+        let post = store(&[(ACCT_1, 75)]);
+        assert!(check_lamport_conservation(&pre, &post, -25).is_ok());
+        assert!(check_lamport_conservation(&pre, &post, 0).is_err());
+    }
+
+    #[test]
+    fn new_account_counts_towards_total() {
+        let pre = lamport_snapshot(&store(&[(ACCT_1, 100)]));
+        let post = store(&[(ACCT_1, 100), (ACCT_2, 10)]);
+        let err = check_lamport_conservation(&pre, &post, 0).unwrap_err();
+        assert!(err.to_string().contains("+10"));
+    }
+}
@@ -6,7 +6,7 @@ pub mod validate;
 use {
     crate::{
         config::{ExecutionCost, RuntimeConfig, SysvarContext},
-        elf::load_elf,
+        elf::{LoadedElf, load_elf},
         errors::{RuntimeError, RuntimeResult},
         runtime::LogCollector,
         serialize,
@@ -16,7 +16,8 @@ use {
     request::CpiRequest,
     sbpf_vm::{
         compute::ComputeMeter,
-        memory::Memory,
+        cost::CostModel,
+        memory::{Memory, MemoryRegion},
         vm::{SbpfVm, SbpfVmConfig},
     },
     solana_account::Account,
@@ -97,7 +98,14 @@ fn execute_elf_cpi(ctx: &mut CpiContext) -> CpiExecResult {
         .get(&ctx.request.program_id)
         .ok_or_else(|| RuntimeError::ProgramNotFound(ctx.request.program_id.to_string()))?;
 
-    let (instructions, rodata, entrypoint) = load_elf(elf_bytes)?;
+    let LoadedElf {
+        instructions,
+        rodata,
+        writable_data,
+        entrypoint,
+        version,
+        ..
+    } = load_elf(elf_bytes)?;
 
     let account_metas: Vec<AccountMeta> = ctx
         .request
@@ -121,6 +129,11 @@ fn execute_elf_cpi(ctx: &mut CpiContext) -> CpiExecResult {
         compute_unit_limit: ctx.compute_remaining,
         max_call_depth: ctx.config.max_call_depth,
         heap_size: ctx.config.heap_size,
+        cost_model: CostModel {
+            charge_syscalls: false,
+            ..CostModel::default()
+        },
+        dynamic_stack_frames: version.dynamic_stack_frames(),
     };
 
     let handler = RuntimeSyscallHandler::new(
@@ -131,6 +144,9 @@ fn execute_elf_cpi(ctx: &mut CpiContext) -> CpiExecResult {
     );
 
     let mut callee_vm = SbpfVm::new_with_config(instructions, input, rodata, handler, vm_config);
+    if let Some((data, start)) = writable_data {
+        callee_vm.memory.map_data(start, data);
+    }
     callee_vm.compute_meter = ComputeMeter::new(ctx.compute_remaining);
     callee_vm.set_entrypoint(entrypoint);
     callee_vm.registers[2] = Memory::INPUT_START + instruction_data_offset as u64;
@@ -222,7 +238,7 @@ fn execute_elf_cpi(ctx: &mut CpiContext) -> CpiExecResult {
     serialize::deserialize_parameters(
         ctx.accounts,
         &account_metas,
-        &callee_vm.memory.input,
+        callee_vm.memory.region(MemoryRegion::Input),
         &pre_lens,
         &ctx.request.program_id,
     )?;
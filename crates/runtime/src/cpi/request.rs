@@ -50,14 +50,14 @@ pub fn parse_cpi_c(
     let data_ptr = memory.read_u64(instruction_addr + 24)?;
     let data_len = memory.read_u64(instruction_addr + 32)?;
 
-    let program_id_bytes = memory.read_bytes(program_id_ptr, 32)?;
+    let program_id_bytes = memory.read_slice(program_id_ptr, 32)?;
     let program_id = Address::new_from_array(program_id_bytes.try_into().unwrap());
 
     let mut accounts = Vec::with_capacity(accounts_len as usize);
     for i in 0..accounts_len {
         let meta_addr = accounts_ptr + i * 16;
         let pubkey_ptr = memory.read_u64(meta_addr)?;
-        let pubkey_bytes = memory.read_bytes(pubkey_ptr, 32)?;
+        let pubkey_bytes = memory.read_slice(pubkey_ptr, 32)?;
         let is_writable = memory.read_u8(meta_addr + 8)? != 0;
         let is_signer = memory.read_u8(meta_addr + 9)? != 0;
         accounts.push(CpiAccountMeta {
@@ -67,7 +67,7 @@ pub fn parse_cpi_c(
         });
     }
 
-    let data = memory.read_bytes(data_ptr, data_len as usize)?.to_vec();
+    let data = memory.read_slice(data_ptr, data_len as usize)?.to_vec();
 
     let caller_accounts = parse_account_infos_c(memory, account_infos_addr, account_infos_len)?;
 
@@ -105,13 +105,13 @@ pub fn parse_cpi_rust(
     let data_ptr = memory.read_u64(instruction_addr + 24)?;
     let _data_cap = memory.read_u64(instruction_addr + 32)?;
     let data_len = memory.read_u64(instruction_addr + 40)?;
-    let program_id_bytes = memory.read_bytes(instruction_addr + 48, 32)?;
+    let program_id_bytes = memory.read_slice(instruction_addr + 48, 32)?;
     let program_id = Address::new_from_array(program_id_bytes.try_into().unwrap());
 
     let mut accounts = Vec::with_capacity(accounts_len as usize);
     for i in 0..accounts_len {
         let meta_addr = accounts_ptr + i * 34;
-        let pubkey_bytes = memory.read_bytes(meta_addr, 32)?;
+        let pubkey_bytes = memory.read_slice(meta_addr, 32)?;
         let is_signer = memory.read_u8(meta_addr + 32)? != 0;
         let is_writable = memory.read_u8(meta_addr + 33)? != 0;
         accounts.push(CpiAccountMeta {
@@ -121,7 +121,7 @@ pub fn parse_cpi_rust(
         });
     }
 
-    let data = memory.read_bytes(data_ptr, data_len as usize)?.to_vec();
+    let data = memory.read_slice(data_ptr, data_len as usize)?.to_vec();
     let caller_accounts = parse_account_infos_rust(memory, account_infos_addr, account_infos_len)?;
     let signers = parse_signers(
         memory,
@@ -157,7 +157,7 @@ fn parse_account_infos_c(
         let _is_signer = memory.read_u8(info_addr + 48)? != 0;
         let is_writable = memory.read_u8(info_addr + 49)? != 0;
 
-        let key_bytes = memory.read_bytes(key_ptr, 32)?;
+        let key_bytes = memory.read_slice(key_ptr, 32)?;
         caller_accounts.push(CallerAccountInfo {
             pubkey: Address::new_from_array(key_bytes.try_into().unwrap()),
             lamports_addr: lamports_ptr,
@@ -184,7 +184,7 @@ fn parse_account_infos_rust(
         let info_addr = account_infos_addr + i * RUST_ACCOUNT_INFO_SIZE;
 
         let key_ptr = memory.read_u64(info_addr)?;
-        let key_bytes = memory.read_bytes(key_ptr, 32)?;
+        let key_bytes = memory.read_slice(key_ptr, 32)?;
 
         let lamports_rc_ptr = memory.read_u64(info_addr + 8)?;
         let lamports_addr = memory.read_u64(lamports_rc_ptr + 24)?;
@@ -253,7 +253,7 @@ fn parse_signers(
                 ));
             }
 
-            let seed_bytes = memory.read_bytes(seed_data_ptr, seed_data_len as usize)?;
+            let seed_bytes = memory.read_slice(seed_data_ptr, seed_data_len as usize)?;
             seeds.push(seed_bytes.to_vec());
         }
 
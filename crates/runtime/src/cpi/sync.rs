@@ -18,9 +18,9 @@ pub fn sync_from_caller(
     for info in caller_accounts {
         let lamports = memory.read_u64(info.lamports_addr)?;
         let data = memory
-            .read_bytes(info.data_addr, info.data_len as usize)?
+            .read_slice(info.data_addr, info.data_len as usize)?
             .to_vec();
-        let owner_bytes = memory.read_bytes(info.owner_addr, 32)?;
+        let owner_bytes = memory.read_slice(info.owner_addr, 32)?;
         let owner = Address::new_from_array(owner_bytes.try_into().unwrap());
 
         let account = accounts.entry(info.pubkey).or_default();
@@ -2,6 +2,7 @@ use {
     crate::{
         cpi::request::CpiRequest,
         errors::{RuntimeError, RuntimeResult},
+        serialize::merged_privileges,
     },
     solana_account::Account,
     solana_address::Address,
@@ -13,16 +14,15 @@ pub fn check_privileges(
     request: &CpiRequest,
     caller_account_metas: &[AccountMeta],
 ) -> RuntimeResult<()> {
-    for cpi_meta in &request.accounts {
-        let caller_meta = caller_account_metas
-            .iter()
-            .find(|m| m.pubkey == cpi_meta.pubkey);
+    // Duplicate caller accounts share the union of their privileges.
+    let caller_privileges = merged_privileges(caller_account_metas);
 
-        match caller_meta {
-            Some(cm) => {
+    for cpi_meta in &request.accounts {
+        match caller_privileges.get(&cpi_meta.pubkey) {
+            Some(&(caller_is_signer, caller_is_writable)) => {
                 // Check signer.
                 if cpi_meta.is_signer
-                    && !cm.is_signer
+                    && !caller_is_signer
                     && !request.signers.contains(&cpi_meta.pubkey)
                 {
                     return Err(RuntimeError::PrivilegeEscalation(
@@ -32,7 +32,7 @@ pub fn check_privileges(
                 }
 
                 // Check writable.
-                if cpi_meta.is_writable && !cm.is_writable {
+                if cpi_meta.is_writable && !caller_is_writable {
                     return Err(RuntimeError::PrivilegeEscalation(
                         "writable".to_string(),
                         cpi_meta.pubkey.to_string(),
@@ -47,46 +47,60 @@ pub fn check_privileges(
     Ok(())
 }
 
-/// Validates that a single account change follows ownership rules.
+/// Validates that a single account change follows the runtime's ownership,
+/// writability and executable rules.
 pub fn check_account_change(
     callee_program_id: &Address,
     pubkey: &Address,
     account: &Account,
+    is_writable: bool,
     new_owner: &Address,
     new_lamports: u64,
     new_data: &[u8],
 ) -> RuntimeResult<()> {
     let is_owner = account.owner == *callee_program_id;
+    let data_changed = new_data != account.data.as_slice();
 
-    // Only the owner can change the owner.
-    if *new_owner != account.owner && !is_owner {
-        return Err(RuntimeError::PrivilegeEscalation(
-            "non-owner changed owner".to_string(),
-            pubkey.to_string(),
-        ));
+    // Only the owner of a writable, non-executable account can reassign it,
+    // and only while its data is zero-initialized.
+    if *new_owner != account.owner
+        && (!is_owner
+            || !is_writable
+            || account.executable
+            || new_data.iter().any(|&byte| byte != 0))
+    {
+        return Err(RuntimeError::ModifiedProgramId(pubkey.to_string()));
     }
 
-    // Only the owner can debit lamports.
-    if new_lamports < account.lamports && !is_owner {
-        return Err(RuntimeError::ExternalAccountLamportSpend(
-            pubkey.to_string(),
-        ));
-    }
-
-    // Only the owner can modify data.
-    if new_data != account.data.as_slice() && !is_owner {
-        return Err(RuntimeError::PrivilegeEscalation(
-            "non-owner modified data".to_string(),
-            pubkey.to_string(),
-        ));
+    if new_lamports != account.lamports {
+        // Read-only and executable balances cannot change.
+        if !is_writable {
+            return Err(RuntimeError::ReadonlyLamportChange(pubkey.to_string()));
+        }
+        if account.executable {
+            return Err(RuntimeError::ExecutableLamportChange(pubkey.to_string()));
+        }
+        // Only the owner can debit lamports.
+        if new_lamports < account.lamports && !is_owner {
+            return Err(RuntimeError::ExternalAccountLamportSpend(
+                pubkey.to_string(),
+            ));
+        }
     }
 
-    // Executable accounts cannot have their data modified.
-    if account.executable && new_data != account.data.as_slice() {
-        return Err(RuntimeError::PrivilegeEscalation(
-            "executable account modified".to_string(),
-            pubkey.to_string(),
-        ));
+    if data_changed {
+        if !is_writable {
+            return Err(RuntimeError::ReadonlyDataModified(pubkey.to_string()));
+        }
+        if account.executable {
+            return Err(RuntimeError::ExecutableDataModified(pubkey.to_string()));
+        }
+        // Only the owner can modify data.
+        if !is_owner {
+            return Err(RuntimeError::ExternalAccountDataModified(
+                pubkey.to_string(),
+            ));
+        }
     }
 
     Ok(())
@@ -199,6 +213,32 @@ mod tests {
         assert!(check_privileges(&request, &caller_metas).is_err());
     }
 
+    #[test]
+    fn check_privileges_duplicate_caller_accounts() {
+        let request = make_request(
+            vec![CpiAccountMeta {
+                pubkey: ACCT_1,
+                is_signer: true,
+                is_writable: true,
+            }],
+            vec![],
+        );
+        // Signer and writable privileges come from different occurrences.
+        let caller_metas = vec![
+            AccountMeta {
+                pubkey: ACCT_1,
+                is_signer: true,
+                is_writable: false,
+            },
+            AccountMeta {
+                pubkey: ACCT_1,
+                is_signer: false,
+                is_writable: true,
+            },
+        ];
+        assert!(check_privileges(&request, &caller_metas).is_ok());
+    }
+
     #[test]
     fn check_privileges_missing_account() {
         let request = make_request(
@@ -223,7 +263,8 @@ mod tests {
     fn check_account_change_owner_modifies_data() {
         let acct = make_account(PROGRAM_A, 100, b"hello");
         assert!(
-            check_account_change(&PROGRAM_A, &ACCT_1, &acct, &PROGRAM_A, 100, b"world").is_ok()
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_A, 100, b"world")
+                .is_ok()
         );
     }
 
@@ -231,38 +272,49 @@ mod tests {
     fn check_account_change_non_owner_modifies_data() {
         let acct = make_account(PROGRAM_A, 100, b"hello");
         assert!(
-            check_account_change(&PROGRAM_B, &ACCT_1, &acct, &PROGRAM_A, 100, b"world").is_err()
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 100, b"world")
+                .is_err()
         );
     }
 
     #[test]
     fn check_account_change_owner_changes_owner() {
         let acct = make_account(PROGRAM_A, 100, b"");
-        assert!(check_account_change(&PROGRAM_A, &ACCT_1, &acct, &PROGRAM_B, 100, b"").is_ok());
+        assert!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_B, 100, b"").is_ok()
+        );
     }
 
     #[test]
     fn check_account_change_non_owner_changes_owner() {
         let acct = make_account(PROGRAM_A, 100, b"");
-        assert!(check_account_change(&PROGRAM_B, &ACCT_1, &acct, &PROGRAM_B, 100, b"").is_err());
+        assert!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_B, 100, b"").is_err()
+        );
     }
 
     #[test]
     fn check_account_change_owner_debits_lamports() {
         let acct = make_account(PROGRAM_A, 100, b"");
-        assert!(check_account_change(&PROGRAM_A, &ACCT_1, &acct, &PROGRAM_A, 50, b"").is_ok());
+        assert!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_A, 50, b"").is_ok()
+        );
     }
 
     #[test]
     fn check_account_change_non_owner_debits_lamports() {
         let acct = make_account(PROGRAM_A, 100, b"");
-        assert!(check_account_change(&PROGRAM_B, &ACCT_1, &acct, &PROGRAM_A, 50, b"").is_err());
+        assert!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 50, b"").is_err()
+        );
     }
 
     #[test]
     fn check_account_change_non_owner_credits_lamports() {
         let acct = make_account(PROGRAM_A, 100, b"");
-        assert!(check_account_change(&PROGRAM_B, &ACCT_1, &acct, &PROGRAM_A, 200, b"").is_ok());
+        assert!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 200, b"").is_ok()
+        );
     }
 
     #[test]
@@ -270,13 +322,74 @@ mod tests {
         let mut acct = make_account(PROGRAM_A, 100, b"code");
         acct.executable = true;
         assert!(
-            check_account_change(&PROGRAM_A, &ACCT_1, &acct, &PROGRAM_A, 100, b"hack").is_err()
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_A, 100, b"hack")
+                .is_err()
         );
     }
 
     #[test]
     fn check_account_change_no_modifications() {
         let acct = make_account(PROGRAM_A, 100, b"data");
-        assert!(check_account_change(&PROGRAM_B, &ACCT_1, &acct, &PROGRAM_A, 100, b"data").is_ok());
+        assert!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 100, b"data")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn check_account_change_owner_change_requires_zeroed_data() {
+        let acct = make_account(PROGRAM_A, 100, b"data");
+        assert!(matches!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_B, 100, b"data"),
+            Err(RuntimeError::ModifiedProgramId(_))
+        ));
+        assert!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, true, &PROGRAM_B, 100, &[0; 4])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn check_account_change_read_only_rejected() {
+        let acct = make_account(PROGRAM_A, 100, b"data");
+        assert!(matches!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, false, &PROGRAM_A, 200, b"data"),
+            Err(RuntimeError::ReadonlyLamportChange(_))
+        ));
+        assert!(matches!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, false, &PROGRAM_A, 100, b"hack"),
+            Err(RuntimeError::ReadonlyDataModified(_))
+        ));
+        assert!(matches!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, false, &PROGRAM_B, 100, &[0; 4]),
+            Err(RuntimeError::ModifiedProgramId(_))
+        ));
+        assert!(
+            check_account_change(&PROGRAM_A, &ACCT_1, &acct, false, &PROGRAM_A, 100, b"data")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn check_account_change_executable_lamports_rejected() {
+        let mut acct = make_account(PROGRAM_A, 100, b"code");
+        acct.executable = true;
+        assert!(matches!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 200, b"code"),
+            Err(RuntimeError::ExecutableLamportChange(_))
+        ));
+    }
+
+    #[test]
+    fn check_account_change_precise_errors() {
+        let acct = make_account(PROGRAM_A, 100, b"hello");
+        assert!(matches!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 100, b"world"),
+            Err(RuntimeError::ExternalAccountDataModified(_))
+        ));
+        assert!(matches!(
+            check_account_change(&PROGRAM_B, &ACCT_1, &acct, true, &PROGRAM_A, 50, b"hello"),
+            Err(RuntimeError::ExternalAccountLamportSpend(_))
+        ));
     }
 }
@@ -1,4 +1,8 @@
-use {sbpf_vm::errors::SbpfVmError, thiserror::Error};
+use {
+    crate::conservation::{LamportDelta, format_deltas},
+    sbpf_vm::errors::SbpfVmError,
+    thiserror::Error,
+};
 
 #[derive(Error, Debug)]
 pub enum RuntimeError {
@@ -35,8 +39,34 @@ pub enum RuntimeError {
     #[error("External account lamport spend: {0}")]
     ExternalAccountLamportSpend(String),
 
-    #[error("Unbalanced lamports: pre={0}, post={1}")]
-    UnbalancedInstruction(u64, u64),
+    #[error("External account data modified: {0}")]
+    ExternalAccountDataModified(String),
+
+    #[error("Program id modified: {0}")]
+    ModifiedProgramId(String),
+
+    #[error("Read-only account lamports changed: {0}")]
+    ReadonlyLamportChange(String),
+
+    #[error("Read-only account data modified: {0}")]
+    ReadonlyDataModified(String),
+
+    #[error("Executable account lamports changed: {0}")]
+    ExecutableLamportChange(String),
+
+    #[error("Executable account data modified: {0}")]
+    ExecutableDataModified(String),
+
+    #[error("Insufficient funds for rent: {0} has {1} lamports, minimum balance is {2}")]
+    InsufficientFundsForRent(String, u64, u64),
+
+    #[error(
+        "Unbalanced lamports: pre={}, post={}; changed accounts: {}",
+        .0,
+        .1,
+        format_deltas(.2)
+    )]
+    UnbalancedInstruction(u128, u128, Vec<LamportDelta>),
 }
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
@@ -1,13 +1,15 @@
 pub mod config;
+pub mod conservation;
 pub mod cpi;
 pub mod elf;
 pub mod errors;
+pub mod rent;
 pub mod runtime;
 pub mod serialize;
 pub mod syscalls;
 
 pub use {
-    runtime::{ElfSource, ExecutionResult, LogCollector, Runtime},
+    runtime::{ElfSource, ExecutionResult, LogCollector, Runtime, TransactionResult},
     sbpf_common::instruction::Instruction,
     sbpf_vm::vm::CallFrame,
 };
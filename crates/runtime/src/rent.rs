@@ -0,0 +1,237 @@
+use {
+    crate::{
+        errors::{RuntimeError, RuntimeResult},
+        serialize::merged_privileges,
+    },
+    solana_account::Account,
+    solana_address::Address,
+    solana_instruction::AccountMeta,
+    solana_rent::Rent,
+    std::collections::HashMap,
+};
+
+/// Rent state of an account.
+///
+/// Reference: https://github.com/anza-xyz/agave/blob/master/svm/src/rent_calculator.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RentState {
+    /// The account holds no lamports.
+    Uninitialized,
+    /// The account holds fewer lamports than its rent-exempt minimum.
+    RentPaying { lamports: u64, data_size: usize },
+    /// The account holds at least its rent-exempt minimum.
+    RentExempt,
+}
+
+impl RentState {
+    pub fn from_account(account: &Account, rent: &Rent) -> Self {
+        if account.lamports == 0 {
+            RentState::Uninitialized
+        } else if rent.is_exempt(account.lamports, account.data.len()) {
+            RentState::RentExempt
+        } else {
+            RentState::RentPaying {
+                lamports: account.lamports,
+                data_size: account.data.len(),
+            }
+        }
+    }
+
+    /// An account may only end up rent-paying if it was already rent-paying
+    /// with the same data size and its balance did not grow.
+    pub fn transition_allowed_from(&self, pre: &RentState) -> bool {
+        match (pre, self) {
+            (_, RentState::Uninitialized | RentState::RentExempt) => true,
+            (
+                RentState::RentPaying {
+                    lamports: pre_lamports,
+                    data_size: pre_size,
+                },
+                RentState::RentPaying {
+                    lamports: post_lamports,
+                    data_size: post_size,
+                },
+            ) => post_size == pre_size && post_lamports <= pre_lamports,
+            _ => false,
+        }
+    }
+}
+
+/// Record the rent state of every writable account referenced by an instruction.
+pub fn rent_state_snapshot(
+    accounts: &HashMap<Address, Account>,
+    account_metas: &[AccountMeta],
+    rent: &Rent,
+) -> HashMap<Address, RentState> {
+    merged_privileges(account_metas)
+        .into_iter()
+        .filter(|(_, (_, is_writable))| *is_writable)
+        .filter_map(|(pubkey, _)| {
+            accounts
+                .get(&pubkey)
+                .map(|account| (pubkey, RentState::from_account(account, rent)))
+        })
+        .collect()
+}
+
+/// A writable account left below its rent-exempt minimum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RentViolation {
+    pub address: Address,
+    pub lamports: u64,
+    pub minimum_balance: u64,
+}
+
+/// Every writable account left below its rent-exempt minimum that was not
+/// already rent-paying before the instruction ran, in the order the
+/// instruction lists them.
+pub fn rent_violations(
+    pre: &HashMap<Address, RentState>,
+    accounts: &HashMap<Address, Account>,
+    account_metas: &[AccountMeta],
+    rent: &Rent,
+) -> Vec<RentViolation> {
+    let mut violations: Vec<RentViolation> = Vec::new();
+    for meta in account_metas {
+        let (Some(pre_state), Some(account)) = (pre.get(&meta.pubkey), accounts.get(&meta.pubkey))
+        else {
+            continue;
+        };
+        if !RentState::from_account(account, rent).transition_allowed_from(pre_state)
+            && !violations.iter().any(|v| v.address == meta.pubkey)
+        {
+            violations.push(RentViolation {
+                address: meta.pubkey,
+                lamports: account.lamports,
+                minimum_balance: rent.minimum_balance(account.data.len()),
+            });
+        }
+    }
+    violations
+}
+
+/// Check that no writable account was left below its rent-exempt minimum,
+/// unless it was already rent-paying before the instruction ran.
+pub fn check_rent_state(
+    pre: &HashMap<Address, RentState>,
+    accounts: &HashMap<Address, Account>,
+    account_metas: &[AccountMeta],
+    rent: &Rent,
+) -> RuntimeResult<()> {
+    match rent_violations(pre, accounts, account_metas, rent).first() {
+        Some(violation) => Err(RuntimeError::InsufficientFundsForRent(
+            violation.address.to_string(),
+            violation.lamports,
+            violation.minimum_balance,
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCT_1: Address = Address::new_from_array([1u8; 32]);
+    const ACCT_2: Address = Address::new_from_array([2u8; 32]);
+
+    fn account(lamports: u64, data_len: usize) -> Account {
+        Account {
+            lamports,
+            data: vec![0; data_len],
+            ..Account::default()
+        }
+    }
+
+    fn meta(pubkey: Address, is_writable: bool) -> AccountMeta {
+        AccountMeta {
+            pubkey,
+            is_signer: false,
+            is_writable,
+        }
+    }
+
+    #[test]
+    fn rent_state_from_account() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(10);
+        assert_eq!(
+            RentState::from_account(&account(0, 10), &rent),
+            RentState::Uninitialized
+        );
+        assert_eq!(
+            RentState::from_account(&account(minimum, 10), &rent),
+            RentState::RentExempt
+        );
+        assert_eq!(
+            RentState::from_account(&account(minimum - 1, 10), &rent),
+            RentState::RentPaying {
+                lamports: minimum - 1,
+                data_size: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn rent_state_transitions() {
+        let paying = |lamports, data_size| RentState::RentPaying {
+            lamports,
+            data_size,
+        };
+        assert!(RentState::RentExempt.transition_allowed_from(&RentState::Uninitialized));
+        assert!(RentState::Uninitialized.transition_allowed_from(&RentState::RentExempt));
+        assert!(!paying(10, 0).transition_allowed_from(&RentState::Uninitialized));
+        assert!(!paying(10, 0).transition_allowed_from(&RentState::RentExempt));
+        assert!(paying(5, 0).transition_allowed_from(&paying(10, 0)));
+        assert!(!paying(15, 0).transition_allowed_from(&paying(10, 0)));
+        assert!(!paying(5, 1).transition_allowed_from(&paying(10, 0)));
+    }
+
+    #[test]
+    fn account_left_below_rent_exemption_rejected() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(0);
+        let metas = vec![meta(ACCT_1, true), meta(ACCT_2, true)];
+        let pre_accounts: HashMap<_, _> =
+            [(ACCT_1, account(minimum * 2, 0)), (ACCT_2, account(0, 0))]
+                .into_iter()
+                .collect();
+        let pre = rent_state_snapshot(&pre_accounts, &metas, &rent);
+
+        let funded: HashMap<_, _> = [(ACCT_1, account(minimum, 0)), (ACCT_2, account(minimum, 0))]
+            .into_iter()
+            .collect();
+        assert!(check_rent_state(&pre, &funded, &metas, &rent).is_ok());
+
+        let underfunded: HashMap<_, _> = [
+            (ACCT_1, account(minimum * 2 - 1, 0)),
+            (ACCT_2, account(1, 0)),
+        ]
+        .into_iter()
+        .collect();
+        match check_rent_state(&pre, &underfunded, &metas, &rent) {
+            Err(RuntimeError::InsufficientFundsForRent(pubkey, lamports, minimum_balance)) => {
+                assert_eq!(pubkey, ACCT_2.to_string());
+                assert_eq!(lamports, 1);
+                assert_eq!(minimum_balance, minimum);
+            }
+            other => panic!("expected InsufficientFundsForRent, got {:?}", other),
+        }
+        assert_eq!(
+            rent_violations(&pre, &underfunded, &metas, &rent),
+            [RentViolation {
+                address: ACCT_2,
+                lamports: 1,
+                minimum_balance: minimum,
+            }]
+        );
+    }
+
+    #[test]
+    fn read_only_accounts_not_checked() {
+        let rent = Rent::default();
+        let accounts: HashMap<_, _> = [(ACCT_1, account(1, 0))].into_iter().collect();
+        let pre = rent_state_snapshot(&accounts, &[meta(ACCT_1, false)], &rent);
+        assert!(pre.is_empty());
+    }
+}
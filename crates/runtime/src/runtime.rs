@@ -1,23 +1,27 @@
 use {
     crate::{
         config::{ExecutionCost, RuntimeConfig, SysvarContext},
-        cpi,
-        elf::load_elf,
+        conservation,
+        cpi::{self, builtins, request::CpiAccountMeta},
+        elf::{LoadedElf, load_elf},
         errors::{RuntimeError, RuntimeResult},
-        serialize,
+        rent, serialize,
         syscalls::RuntimeSyscallHandler,
     },
     base64::{Engine, engine::general_purpose::STANDARD as BASE64},
-    sbpf_common::{execute::Vm, instruction::Instruction},
+    sbpf_common::{execute::Vm, instruction::Instruction, profile::ComputeProfile},
     sbpf_vm::{
         compute::ComputeMeter,
-        memory::Memory,
+        compute_profile,
+        cost::CostModel,
+        coverage::{self, Coverage, program_key},
+        memory::{Memory, MemoryRegion},
         vm::{CallFrame, SbpfVm, SbpfVmConfig},
     },
     solana_account::Account,
     solana_address::Address,
     solana_instruction::{AccountMeta, Instruction as SolanaInstruction},
-    std::{cell::RefCell, collections::HashMap, rc::Rc},
+    std::{cell::RefCell, collections::HashMap, io, path::Path, rc::Rc},
 };
 
 pub type LogCollector = Rc<RefCell<Vec<String>>>;
@@ -51,11 +55,38 @@ pub struct ExecutionResult {
     pub logs: Vec<String>,
 }
 
+/// Per-instruction results of a transaction run, in execution order.
+/// Execution stops at the first failing instruction, so a failed transaction
+/// ends with the result of the instruction that failed.
+pub struct TransactionResult {
+    pub results: Vec<ExecutionResult>,
+}
+
+impl TransactionResult {
+    pub fn is_success(&self) -> bool {
+        self.results
+            .iter()
+            .all(|result| result.exit_code.unwrap_or(0) == 0)
+    }
+
+    pub fn compute_units_consumed(&self) -> u64 {
+        self.results
+            .iter()
+            .map(|result| result.compute_units_consumed)
+            .sum()
+    }
+
+    pub fn logs(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .flat_map(|result| result.logs.iter().cloned())
+            .collect()
+    }
+}
+
 pub struct Runtime {
     program_id: Address,
-    instructions: Vec<Instruction>,
-    rodata: Vec<u8>,
-    entrypoint: usize,
+    program: LoadedElf,
     programs: HashMap<Address, Vec<u8>>,
     config: RuntimeConfig,
     sysvars: SysvarContext,
@@ -64,6 +95,12 @@ pub struct Runtime {
     account_metas: Vec<AccountMeta>,
     pre_lens: Vec<usize>, // original account data lengths at serialization
     log_collector: LogCollector,
+    /// Instructions executed per program, keyed by [`program_key`], while
+    /// `config.coverage_dir` is set.
+    coverage: HashMap<String, Coverage>,
+    /// Compute units used per program, keyed by [`program_key`], while
+    /// `config.profile_dir` is set.
+    compute_profiles: HashMap<String, ComputeProfile>,
 }
 
 impl Runtime {
@@ -77,14 +114,12 @@ impl Runtime {
             ElfSource::Bytes(bytes) => bytes,
         };
 
-        let (instructions, rodata, entrypoint) = load_elf(&elf_bytes)?;
+        let program = load_elf(&elf_bytes)?;
 
         Ok(Self {
             program_id,
-            instructions,
-            rodata,
-            entrypoint,
-            programs: HashMap::new(),
+            program,
+            programs: HashMap::from([(program_id, elf_bytes)]),
             config,
             sysvars: SysvarContext::default(),
             vm: None,
@@ -92,6 +127,8 @@ impl Runtime {
             account_metas: Vec::new(),
             pre_lens: Vec::new(),
             log_collector: Rc::new(RefCell::new(Vec::new())),
+            coverage: HashMap::new(),
+            compute_profiles: HashMap::new(),
         })
     }
 
@@ -103,11 +140,31 @@ impl Runtime {
         self.programs.insert(*program_id, elf_bytes);
     }
 
+    /// Make `program_id` the program executed by `run`, `prepare` and `step`.
+    fn activate_program(&mut self, program_id: &Address) -> RuntimeResult<()> {
+        if *program_id == self.program_id {
+            return Ok(());
+        }
+        let elf_bytes = self
+            .programs
+            .get(program_id)
+            .ok_or_else(|| RuntimeError::ProgramNotFound(program_id.to_string()))?;
+        let program = load_elf(elf_bytes)?;
+
+        self.collect_recordings();
+        self.program_id = *program_id;
+        self.program = program;
+        self.vm = None;
+        Ok(())
+    }
+
     fn setup_vm(
         &mut self,
         instruction: &SolanaInstruction,
         accounts: &[(Address, Account)],
     ) -> RuntimeResult<()> {
+        self.collect_recordings();
+
         // Setup accounts (merge with existing account state).
         for (address, account) in accounts.iter() {
             self.accounts
@@ -127,6 +184,12 @@ impl Runtime {
             compute_unit_limit: self.config.compute_budget,
             max_call_depth: self.config.max_call_depth,
             heap_size: self.config.heap_size,
+            // The syscall handler charges syscalls from `ExecutionCost`.
+            cost_model: CostModel {
+                charge_syscalls: false,
+                ..CostModel::default()
+            },
+            dynamic_stack_frames: self.program.version.dynamic_stack_frames(),
         };
 
         let handler = RuntimeSyscallHandler::new(
@@ -137,27 +200,59 @@ impl Runtime {
         );
 
         let mut vm = SbpfVm::new_with_config(
-            self.instructions.clone(),
+            self.program.instructions.clone(),
             input,
-            self.rodata.clone(),
+            self.program.rodata.clone(),
             handler,
             vm_config,
         );
         vm.compute_meter = ComputeMeter::new(self.config.compute_budget);
-        vm.set_entrypoint(self.entrypoint);
+        self.program.map_data(&mut vm.memory);
+        vm.set_entrypoint(self.program.entrypoint);
         vm.registers[2] = Memory::INPUT_START + instruction_data_offset as u64;
+        if self.config.coverage_dir.is_some() {
+            vm.enable_coverage();
+        }
+        if self.config.profile_dir.is_some() {
+            vm.enable_compute_profiling();
+        }
 
         self.pre_lens = pre_lens;
         self.vm = Some(vm);
         Ok(())
     }
 
+    /// Merge the instructions the current VM executed into the coverage of
+    /// its program, and the compute units it used into its profile.
+    fn collect_recordings(&mut self) {
+        let Some(vm) = self.vm.as_mut() else {
+            return;
+        };
+        let (coverage, profile) = (vm.take_coverage(), vm.take_compute_profile());
+        if coverage.is_none() && profile.is_none() {
+            return;
+        }
+        let key = program_key(&self.programs[&self.program_id]);
+        if let Some(coverage) = coverage {
+            self.coverage
+                .entry(key.clone())
+                .or_insert_with(|| Coverage::new(coverage.len()))
+                .merge(&coverage);
+        }
+        if let Some(profile) = profile {
+            self.compute_profiles
+                .entry(key)
+                .or_default()
+                .merge(&profile);
+        }
+    }
+
     fn sync_accounts(&mut self) -> RuntimeResult<()> {
         if let Some(ref vm) = self.vm {
             serialize::deserialize_parameters(
                 &mut self.accounts,
                 &self.account_metas,
-                &vm.memory.input,
+                vm.memory.region(MemoryRegion::Input),
                 &self.pre_lens,
                 &self.program_id,
             )?;
@@ -174,15 +269,9 @@ impl Runtime {
         self.setup_vm(instruction, accounts)?;
 
         // Get pre-execution lamports from the account state.
-        let pre_lamports: HashMap<Address, u64> = self
-            .account_metas
-            .iter()
-            .filter_map(|meta| {
-                self.accounts
-                    .get(&meta.pubkey)
-                    .map(|a| (meta.pubkey, a.lamports))
-            })
-            .collect();
+        let pre_lamports = conservation::lamport_snapshot(&self.accounts);
+        let pre_rent_states =
+            rent::rent_state_snapshot(&self.accounts, &self.account_metas, &self.sysvars.rent);
 
         self.log_collector
             .borrow_mut()
@@ -214,15 +303,20 @@ impl Runtime {
 
         self.sync_accounts()?;
 
-        // Verify total lamport balance is conserved across all instruction accounts.
-        let pre_total: u64 = pre_lamports.values().sum();
-        let post_total: u64 = pre_lamports
-            .keys()
-            .filter_map(|pk| self.accounts.get(pk))
-            .map(|a| a.lamports)
-            .sum();
-        if pre_total != post_total {
-            return Err(RuntimeError::UnbalancedInstruction(pre_total, post_total));
+        // Verify total lamport balance is conserved across all accounts.
+        conservation::check_lamport_conservation(
+            &pre_lamports,
+            &self.accounts,
+            self.config.expected_lamport_change,
+        )?;
+
+        if self.config.check_rent_exemption {
+            rent::check_rent_state(
+                &pre_rent_states,
+                &self.accounts,
+                &self.account_metas,
+                &self.sysvars.rent,
+            )?;
         }
 
         let vm = self.vm.as_ref().unwrap();
@@ -250,9 +344,9 @@ impl Runtime {
                 .push(format!("Program {} success", self.program_id));
         } else {
             self.log_collector.borrow_mut().push(format!(
-                "Program {} failed: exit code {}",
+                "Program {} failed: {}",
                 self.program_id,
-                exit_code.unwrap_or(0)
+                self.describe_exit_code(exit_code.unwrap_or(0))
             ));
         }
 
@@ -265,6 +359,117 @@ impl Runtime {
         })
     }
 
+    /// Execute an ordered list of instructions against shared account state, as a
+    /// single transaction. Instructions may target the main program, any program
+    /// registered with `add_program`, or a builtin. Execution stops at the first
+    /// failing instruction and all account changes made by the transaction are
+    /// rolled back.
+    pub fn run_transaction(
+        &mut self,
+        instructions: &[SolanaInstruction],
+        accounts: &[(Address, Account)],
+    ) -> RuntimeResult<TransactionResult> {
+        let main_program_id = self.program_id;
+        let snapshot = self.accounts.clone();
+
+        let result = self.execute_instructions(instructions, accounts);
+        self.activate_program(&main_program_id)?;
+
+        match result {
+            Ok(transaction) if transaction.is_success() => Ok(transaction),
+            Ok(transaction) => {
+                self.accounts = snapshot;
+                Ok(transaction)
+            }
+            Err(e) => {
+                self.accounts = snapshot;
+                Err(e)
+            }
+        }
+    }
+
+    fn execute_instructions(
+        &mut self,
+        instructions: &[SolanaInstruction],
+        accounts: &[(Address, Account)],
+    ) -> RuntimeResult<TransactionResult> {
+        let mut results = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            let result = if builtins::is_builtin(&instruction.program_id) {
+                self.run_builtin(instruction, accounts)?
+            } else {
+                self.activate_program(&instruction.program_id)?;
+                self.run(instruction, accounts)?
+            };
+
+            let failed = result.exit_code.unwrap_or(0) != 0;
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        Ok(TransactionResult { results })
+    }
+
+    fn run_builtin(
+        &mut self,
+        instruction: &SolanaInstruction,
+        accounts: &[(Address, Account)],
+    ) -> RuntimeResult<ExecutionResult> {
+        self.log_collector.borrow_mut().clear();
+        for (address, account) in accounts.iter() {
+            self.accounts
+                .entry(*address)
+                .or_insert_with(|| account.clone());
+        }
+
+        let request = cpi::request::CpiRequest {
+            program_id: instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| CpiAccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: instruction.data.clone(),
+            caller_accounts: Vec::new(),
+            signers: Vec::new(),
+        };
+        let signers: Vec<Address> = instruction
+            .accounts
+            .iter()
+            .filter(|meta| meta.is_signer)
+            .map(|meta| meta.pubkey)
+            .collect();
+
+        self.log_collector
+            .borrow_mut()
+            .push(format!("Program {} invoke [1]", instruction.program_id));
+        let consumed = builtins::execute_builtin(
+            &instruction.program_id,
+            &mut self.accounts,
+            &request,
+            &signers,
+            self.config.compute_budget,
+        )?;
+        self.log_collector.borrow_mut().push(format!(
+            "Program {} consumed {} of {} compute units",
+            instruction.program_id, consumed, self.config.compute_budget
+        ));
+        self.log_collector
+            .borrow_mut()
+            .push(format!("Program {} success", instruction.program_id));
+
+        Ok(ExecutionResult {
+            exit_code: Some(0),
+            compute_units_consumed: consumed,
+            logs: self.log_collector.borrow().clone(),
+        })
+    }
+
     pub fn prepare(
         &mut self,
         instruction: &SolanaInstruction,
@@ -328,9 +533,9 @@ impl Runtime {
                     .push(format!("Program {} success", self.program_id));
             } else {
                 self.log_collector.borrow_mut().push(format!(
-                    "Program {} failed: exit code {}",
+                    "Program {} failed: {}",
                     self.program_id,
-                    exit_code.unwrap_or(0)
+                    self.describe_exit_code(exit_code.unwrap_or(0))
                 ));
             }
         }
@@ -441,7 +646,7 @@ impl Runtime {
     pub fn read_memory(&self, addr: u64, size: usize) -> Option<Vec<u8>> {
         self.vm
             .as_ref()
-            .and_then(|vm| vm.memory.read_bytes(addr, size).ok().map(|s| s.to_vec()))
+            .and_then(|vm| vm.memory.read_slice(addr, size).ok().map(|s| s.to_vec()))
     }
 
     pub fn get_instruction(&self) -> Option<&Instruction> {
@@ -450,7 +655,7 @@ impl Runtime {
     }
 
     pub fn get_program(&self) -> &[Instruction] {
-        &self.instructions
+        &self.program.instructions
     }
 
     pub fn get_call_stack(&self) -> Option<&[CallFrame]> {
@@ -461,6 +666,15 @@ impl Runtime {
         &self.config
     }
 
+    /// An exit code as a failed run's log shows it, named from the
+    /// configured errors where possible: `exit code 12 (InvalidPda)`.
+    fn describe_exit_code(&self, exit_code: u64) -> String {
+        match self.config.errors.describe(exit_code) {
+            Some(error) => format!("exit code {} ({})", exit_code, error),
+            None => format!("exit code {}", exit_code),
+        }
+    }
+
     pub fn sysvars(&self) -> &SysvarContext {
         &self.sysvars
     }
@@ -469,6 +683,28 @@ impl Runtime {
         &mut self.sysvars
     }
 
+    /// Minimum lamport balance for a rent-exempt account with `data_len` bytes.
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        self.sysvars.minimum_balance(data_len)
+    }
+
+    /// Writable accounts of `instructions` that running them left below their
+    /// rent-exempt minimum, judged against `accounts`, the state before they
+    /// ran, the way `config.check_rent_exemption` judges each run.
+    pub fn rent_violations(
+        &self,
+        instructions: &[SolanaInstruction],
+        accounts: &[(Address, Account)],
+    ) -> Vec<rent::RentViolation> {
+        let metas: Vec<AccountMeta> = instructions
+            .iter()
+            .flat_map(|instruction| instruction.accounts.iter().cloned())
+            .collect();
+        let pre_accounts: HashMap<Address, Account> = accounts.iter().cloned().collect();
+        let pre = rent::rent_state_snapshot(&pre_accounts, &metas, &self.sysvars.rent);
+        rent::rent_violations(&pre, &self.accounts, &metas, &self.sysvars.rent)
+    }
+
     pub fn log_collector(&self) -> &LogCollector {
         &self.log_collector
     }
@@ -476,11 +712,47 @@ impl Runtime {
     pub fn drain_logs(&self) -> Vec<String> {
         self.log_collector.borrow_mut().drain(..).collect()
     }
+
+    /// The instructions each program executed while `config.coverage_dir`
+    /// was set, keyed by [`program_key`].
+    pub fn take_coverage(&mut self) -> HashMap<String, Coverage> {
+        self.collect_recordings();
+        std::mem::take(&mut self.coverage)
+    }
+
+    /// Write the instructions each program executed while
+    /// `config.coverage_dir` was set to a new file per program in `dir`, for
+    /// `sbpf test --coverage` to merge. What is written is cleared, so a
+    /// later call only writes later runs.
+    pub fn write_coverage(&mut self, dir: &Path) -> io::Result<()> {
+        for (key, coverage) in self.take_coverage() {
+            coverage::write_coverage(dir, &key, &coverage)?;
+        }
+        Ok(())
+    }
+
+    /// The compute units each program used while `config.profile_dir` was
+    /// set, keyed by [`program_key`].
+    pub fn take_compute_profiles(&mut self) -> HashMap<String, ComputeProfile> {
+        self.collect_recordings();
+        std::mem::take(&mut self.compute_profiles)
+    }
+
+    /// Write the compute units each program used while `config.profile_dir`
+    /// was set to a new file per program in `dir`, for `sbpf test
+    /// --cu-profile` to merge. What is written is cleared, like
+    /// [`Runtime::write_coverage`].
+    pub fn write_compute_profiles(&mut self, dir: &Path) -> io::Result<()> {
+        for (key, profile) in self.take_compute_profiles() {
+            compute_profile::write_compute_profile(dir, &key, &profile)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use {super::*, std::path::PathBuf};
+    use {super::*, crate::rent::RentViolation, std::path::PathBuf};
 
     const PROGRAM_ID: Address =
         Address::from_str_const("22222222222222222222222222222222222222222222");
@@ -510,6 +782,38 @@ mod tests {
         }
     }
 
+    fn system_account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            data: Vec::new(),
+            owner: solana_system_interface::program::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn transfer_instruction(from: Address, to: Address, lamports: u64) -> SolanaInstruction {
+        SolanaInstruction {
+            program_id: solana_system_interface::program::ID,
+            accounts: vec![
+                AccountMeta {
+                    pubkey: from,
+                    is_signer: true,
+                    is_writable: true,
+                },
+                AccountMeta {
+                    pubkey: to,
+                    is_signer: false,
+                    is_writable: true,
+                },
+            ],
+            data: wincode::serialize(
+                &solana_system_interface::instruction::SystemInstruction::Transfer { lamports },
+            )
+            .unwrap(),
+        }
+    }
+
     #[test]
     fn elf_source_from_str() {
         let path = escrow_elf_path();
@@ -673,4 +977,167 @@ mod tests {
         assert!(exec.logs.iter().any(|l| l.contains("consumed")));
         assert!(exec.logs.iter().any(|l| l.contains("failed: exit code")));
     }
+
+    #[test]
+    fn write_coverage_records_executed_instructions() {
+        let dir =
+            std::env::temp_dir().join(format!("sbpf-runtime-coverage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        // Coverage is only recorded when asked for.
+        assert_eq!(RuntimeConfig::default().coverage_dir, None);
+        let config = RuntimeConfig {
+            coverage_dir: Some(dir.clone()),
+            ..RuntimeConfig::default()
+        };
+        let elf = std::fs::read(escrow_elf_path()).unwrap();
+        let mut rt = Runtime::new(PROGRAM_ID, elf.as_slice(), config).unwrap();
+        rt.run(&empty_instruction(), &[]).unwrap();
+        rt.run(&empty_instruction(), &[]).unwrap();
+        let instructions = rt.get_program().len();
+        rt.write_coverage(&dir).unwrap();
+        // Nothing ran since, so nothing more is written.
+        rt.write_coverage(&dir).unwrap();
+        drop(rt);
+
+        let key = program_key(&elf);
+        let coverage = sbpf_vm::coverage::read_coverage(&dir, &key, instructions)
+            .unwrap()
+            .unwrap();
+        assert!(coverage.covered() > 0 && coverage.covered() < instructions);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_compute_profiles_records_compute_units() {
+        let dir = std::env::temp_dir().join(format!("sbpf-runtime-profile-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(RuntimeConfig::default().profile_dir, None);
+        let config = RuntimeConfig {
+            profile_dir: Some(dir.clone()),
+            ..RuntimeConfig::default()
+        };
+        let elf = std::fs::read(escrow_elf_path()).unwrap();
+        let mut rt = Runtime::new(PROGRAM_ID, elf.as_slice(), config).unwrap();
+        let exec = rt.run(&empty_instruction(), &[]).unwrap();
+        rt.write_compute_profiles(&dir).unwrap();
+
+        let profile = sbpf_vm::compute_profile::read_compute_profile(&dir, &program_key(&elf))
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile.total(), exec.compute_units_consumed);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_transaction_shares_account_state() {
+        let mut rt = new_runtime();
+        let (a, b, c) = (
+            Address::new_unique(),
+            Address::new_unique(),
+            Address::new_unique(),
+        );
+        let accounts = [
+            (a, system_account(1_000)),
+            (b, system_account(0)),
+            (c, system_account(0)),
+        ];
+
+        // The second transfer spends lamports received by the first.
+        let tx = rt
+            .run_transaction(
+                &[
+                    transfer_instruction(a, b, 600),
+                    transfer_instruction(b, c, 400),
+                ],
+                &accounts,
+            )
+            .unwrap();
+
+        assert!(tx.is_success());
+        assert_eq!(tx.results.len(), 2);
+        assert_eq!(rt.get_account(&a).unwrap().lamports, 400);
+        assert_eq!(rt.get_account(&b).unwrap().lamports, 200);
+        assert_eq!(rt.get_account(&c).unwrap().lamports, 400);
+    }
+
+    #[test]
+    fn rent_violations_flag_accounts_left_below_rent_exemption() {
+        let mut rt = new_runtime();
+        let (a, b) = (Address::new_unique(), Address::new_unique());
+        let minimum = rt.minimum_balance(0);
+        let accounts = [(a, system_account(minimum * 2)), (b, system_account(0))];
+        let instructions = [transfer_instruction(a, b, 600)];
+
+        let tx = rt.run_transaction(&instructions, &accounts).unwrap();
+        assert!(tx.is_success());
+        assert_eq!(
+            rt.rent_violations(&instructions, &accounts),
+            [RentViolation {
+                address: b,
+                lamports: 600,
+                minimum_balance: minimum,
+            }]
+        );
+    }
+
+    #[test]
+    fn run_transaction_rolls_back_on_failure() {
+        let mut rt = new_runtime();
+        let (a, b) = (Address::new_unique(), Address::new_unique());
+        let accounts = [(a, system_account(1_000)), (b, system_account(0))];
+
+        // The escrow program rejects an empty instruction, failing the transaction.
+        let tx = rt
+            .run_transaction(
+                &[
+                    transfer_instruction(a, b, 600),
+                    empty_instruction(),
+                    transfer_instruction(a, b, 100),
+                ],
+                &accounts,
+            )
+            .unwrap();
+
+        assert!(!tx.is_success());
+        assert_eq!(tx.results.len(), 2);
+        assert!(tx.logs().iter().any(|l| l.contains("failed: exit code")));
+        assert!(rt.get_account(&a).is_none());
+        assert!(rt.get_account(&b).is_none());
+    }
+
+    #[test]
+    fn run_transaction_targets_registered_programs() {
+        let mut rt = new_runtime();
+        let other = Address::new_unique();
+        rt.add_program(&other, escrow_elf_path().as_str());
+
+        let instruction = SolanaInstruction {
+            program_id: other,
+            accounts: Vec::new(),
+            data: Vec::new(),
+        };
+        let tx = rt.run_transaction(&[instruction], &[]).unwrap();
+
+        assert_eq!(tx.results.len(), 1);
+        assert!(tx.logs().iter().any(|l| l.contains(&other.to_string())));
+        // The main program is active again once the transaction completes.
+        assert_eq!(*rt.current_program_id(), PROGRAM_ID);
+    }
+
+    #[test]
+    fn run_transaction_unknown_program_errors() {
+        let mut rt = new_runtime();
+        let instruction = SolanaInstruction {
+            program_id: Address::new_unique(),
+            accounts: Vec::new(),
+            data: Vec::new(),
+        };
+        match rt.run_transaction(&[instruction], &[]) {
+            Err(RuntimeError::ProgramNotFound(_)) => {}
+            Err(other) => panic!("expected ProgramNotFound, got {other:?}"),
+            Ok(_) => panic!("expected ProgramNotFound error"),
+        }
+        assert_eq!(*rt.current_program_id(), PROGRAM_ID);
+    }
 }
@@ -51,28 +51,45 @@ enum SerializeAccount {
     Duplicate(u8),
 }
 
+/// Merge the signer and writable flags of every occurrence of each account.
+///
+/// Instruction accounts are deduplicated by the runtime, so a key listed more
+/// than once carries the union of the privileges requested for it.
+pub fn merged_privileges(account_metas: &[AccountMeta]) -> HashMap<Address, (bool, bool)> {
+    let mut privileges: HashMap<Address, (bool, bool)> = HashMap::new();
+    for meta in account_metas {
+        let (is_signer, is_writable) = privileges.entry(meta.pubkey).or_default();
+        *is_signer |= meta.is_signer;
+        *is_writable |= meta.is_writable;
+    }
+    privileges
+}
+
 pub fn serialize_parameters(
     accounts: &HashMap<Address, Account>,
     account_metas: &[AccountMeta],
     instruction_data: &[u8],
     program_id: &Address,
 ) -> RuntimeResult<(Vec<u8>, Vec<usize>, usize)> {
+    let privileges = merged_privileges(account_metas);
     let mut seen: HashMap<Address, usize> = HashMap::new();
     let mut serialize_accounts = Vec::with_capacity(account_metas.len());
 
     for (i, meta) in account_metas.iter().enumerate() {
         if let Some(&first_idx) = seen.get(&meta.pubkey) {
+            // Duplicates reference the index of the first occurrence.
             serialize_accounts.push(SerializeAccount::Duplicate(first_idx as u8));
         } else {
             seen.insert(meta.pubkey, i);
             let account = accounts.get(&meta.pubkey).ok_or_else(|| {
                 RuntimeError::MissingAccount(format!("Missing account data for {}", meta.pubkey))
             })?;
+            let (is_signer, is_writable) = privileges[&meta.pubkey];
             serialize_accounts.push(SerializeAccount::Account(
                 meta.pubkey,
                 account.clone(),
-                meta.is_signer,
-                meta.is_writable,
+                is_signer,
+                is_writable,
             ));
         }
     }
@@ -162,6 +179,7 @@ pub fn deserialize_parameters(
 ) -> RuntimeResult<()> {
     let mut d = Deserializer::new(input);
     let _num_accounts = d.read_u64();
+    let privileges = merged_privileges(account_metas);
     let mut seen: HashMap<Address, usize> = HashMap::new();
     let mut pre_len_idx = 0;
 
@@ -183,20 +201,24 @@ pub fn deserialize_parameters(
         let data = d.read_account_data(data_len, pre_len);
         let _rent_epoch = d.read_u64();
 
-        if meta.is_writable
-            && let Some(account) = accounts.get_mut(&meta.pubkey)
-        {
+        let (_, is_writable) = privileges[&meta.pubkey];
+        if let Some(account) = accounts.get_mut(&meta.pubkey) {
+            // Read-only accounts are still validated so writes to them are
+            // reported instead of being silently dropped.
             check_account_change(
                 callee_program_id,
                 &meta.pubkey,
                 account,
+                is_writable,
                 &owner,
                 lamports,
                 data,
             )?;
-            account.lamports = lamports;
-            account.data = data.to_vec();
-            account.owner = owner;
+            if is_writable {
+                account.lamports = lamports;
+                account.data = data.to_vec();
+                account.owner = owner;
+            }
         }
     }
     Ok(())
@@ -221,6 +243,14 @@ mod tests {
         }
     }
 
+    fn meta(pubkey: Address, is_signer: bool, is_writable: bool) -> AccountMeta {
+        AccountMeta {
+            pubkey,
+            is_signer,
+            is_writable,
+        }
+    }
+
     fn accounts_map(list: &[(Address, Account)]) -> HashMap<Address, Account> {
         list.iter().cloned().collect()
     }
@@ -254,7 +284,7 @@ mod tests {
     }
 
     #[test]
-    fn read_only_account_not_updated() {
+    fn read_only_account_write_rejected() {
         let mut accounts = accounts_map(&[(ACCT_1, make_account(PROGRAM, 100, b"data"))]);
         let metas = vec![AccountMeta {
             pubkey: ACCT_1,
@@ -268,7 +298,11 @@ mod tests {
         let lamport_offset = 8 + 8 + 32 + 32;
         input[lamport_offset..lamport_offset + 8].copy_from_slice(&999u64.to_le_bytes());
 
-        deserialize_parameters(&mut accounts, &metas, &input, &pre_lens, &PROGRAM).unwrap();
+        let result = deserialize_parameters(&mut accounts, &metas, &input, &pre_lens, &PROGRAM);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::ReadonlyLamportChange(_))
+        ));
         assert_eq!(accounts[&ACCT_1].lamports, 100); // lamports unchanged
     }
 
@@ -311,6 +345,62 @@ mod tests {
         assert_eq!(accounts[&ACCT_1].lamports, 100);
     }
 
+    #[test]
+    fn duplicate_account_serialized_as_index() {
+        let accounts = accounts_map(&[
+            (ACCT_1, make_account(PROGRAM, 100, b"")),
+            (ACCT_2, make_account(PROGRAM, 200, b"")),
+        ]);
+        let metas = vec![
+            meta(ACCT_1, false, false),
+            meta(ACCT_2, false, false),
+            meta(ACCT_1, true, true),
+        ];
+        let (input, pre_lens, _) = serialize_parameters(&accounts, &metas, b"", &PROGRAM).unwrap();
+        assert_eq!(pre_lens, vec![0, 0]);
+
+        // Account count includes the duplicate entry.
+        assert_eq!(u64::from_le_bytes(input[0..8].try_into().unwrap()), 3);
+
+        // The first occurrence carries the merged signer/writable privileges.
+        assert_eq!(&input[8..12], &[NON_DUP_MARKER, 1, 1, 0]);
+
+        // Each unique entry: header(8) + keys(64) + lamports(8) + len(8) +
+        // data(0 + realloc padding) + rent_epoch(8).
+        let entry_len = 8 + 64 + 8 + 8 + MAX_PERMITTED_DATA_INCREASE + 8;
+        let dup_offset = 8 + 2 * entry_len;
+        assert_eq!(
+            &input[dup_offset..dup_offset + 8],
+            &[0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn writable_duplicate_updates_account() {
+        let mut accounts = accounts_map(&[(ACCT_1, make_account(PROGRAM, 100, b""))]);
+        let metas = vec![meta(ACCT_1, false, false), meta(ACCT_1, false, true)];
+        let (mut input, pre_lens, _) =
+            serialize_parameters(&accounts, &metas, b"", &PROGRAM).unwrap();
+
+        let lamport_offset = 8 + 8 + 32 + 32;
+        input[lamport_offset..lamport_offset + 8].copy_from_slice(&150u64.to_le_bytes());
+
+        deserialize_parameters(&mut accounts, &metas, &input, &pre_lens, &PROGRAM).unwrap();
+        assert_eq!(accounts[&ACCT_1].lamports, 150);
+    }
+
+    #[test]
+    fn merged_privileges_union_flags() {
+        let metas = vec![
+            meta(ACCT_1, true, false),
+            meta(ACCT_1, false, true),
+            meta(ACCT_2, false, false),
+        ];
+        let privileges = merged_privileges(&metas);
+        assert_eq!(privileges[&ACCT_1], (true, true));
+        assert_eq!(privileges[&ACCT_2], (false, false));
+    }
+
     #[test]
     fn missing_account_errors() {
         let accounts = accounts_map(&[]);
@@ -84,7 +84,7 @@ fn hash_slices<H: Hasher>(
                 .mem_op_base_cost
                 .max(costs.sha256_byte_cost.saturating_mul(len / 2));
             compute.consume(cost)?;
-            hasher.update(memory.read_bytes(ptr, len as usize)?);
+            hasher.update(memory.read_slice(ptr, len as usize)?);
         }
     }
 
@@ -184,7 +184,7 @@ mod tests {
         let registers = [slices_addr, 1, result_addr, 0, 0];
         sol_sha256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_sha256(b"hello").as_slice());
     }
 
@@ -196,7 +196,7 @@ mod tests {
         let registers = [0, 0, result_addr, 0, 0];
         sol_sha256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_sha256(b"").as_slice());
     }
 
@@ -218,7 +218,7 @@ mod tests {
         let registers = [slices_addr, 2, result_addr, 0, 0];
         sol_sha256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_sha256(b"hello").as_slice());
     }
 
@@ -252,7 +252,7 @@ mod tests {
         let registers = [slices_addr, 1, result_addr, 0, 0];
         sol_keccak256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_keccak256(b"hello").as_slice());
     }
 
@@ -263,7 +263,7 @@ mod tests {
         let registers = [0, 0, result_addr, 0, 0];
         sol_keccak256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_keccak256(b"").as_slice());
     }
 
@@ -275,7 +275,7 @@ mod tests {
         let registers = [slices_addr, 1, result_addr, 0, 0];
         sol_keccak256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let keccak_result = memory.read_bytes(result_addr, 32).unwrap().to_vec();
+        let keccak_result = memory.read_slice(result_addr, 32).unwrap().to_vec();
         assert_ne!(keccak_result, reference_sha256(b"hello"));
     }
 
@@ -287,7 +287,7 @@ mod tests {
         let registers = [slices_addr, 1, result_addr, 0, 0];
         sol_blake3(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_blake3(b"hello").as_slice());
     }
 
@@ -298,7 +298,7 @@ mod tests {
         let registers = [0, 0, result_addr, 0, 0];
         sol_blake3(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        let result = memory.read_bytes(result_addr, 32).unwrap();
+        let result = memory.read_slice(result_addr, 32).unwrap();
         assert_eq!(result, reference_blake3(b"").as_slice());
     }
 
@@ -310,13 +310,13 @@ mod tests {
         let registers = [slices_addr, 1, result_addr, 0, 0];
 
         sol_sha256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        let sha_out = memory.read_bytes(result_addr, 32).unwrap().to_vec();
+        let sha_out = memory.read_slice(result_addr, 32).unwrap().to_vec();
 
         sol_keccak256(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        let keccak_out = memory.read_bytes(result_addr, 32).unwrap().to_vec();
+        let keccak_out = memory.read_slice(result_addr, 32).unwrap().to_vec();
 
         sol_blake3(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        let blake_out = memory.read_bytes(result_addr, 32).unwrap().to_vec();
+        let blake_out = memory.read_slice(result_addr, 32).unwrap().to_vec();
 
         assert_ne!(sha_out, keccak_out);
         assert_ne!(sha_out, blake_out);
@@ -15,7 +15,7 @@ pub fn sol_log(
 
     compute.consume(costs.syscall_base_cost.max(msg_len))?;
 
-    let msg_bytes = memory.read_bytes(msg_ptr, msg_len as usize)?;
+    let msg_bytes = memory.read_slice(msg_ptr, msg_len as usize)?;
     let msg = String::from_utf8_lossy(msg_bytes);
     log_collector
         .borrow_mut()
@@ -46,7 +46,7 @@ pub fn sol_log_pubkey(
 ) -> SbpfVmResult<u64> {
     compute.consume(costs.log_pubkey_units)?;
 
-    let pubkey_bytes = memory.read_bytes(registers[0], 32)?;
+    let pubkey_bytes = memory.read_slice(registers[0], 32)?;
     let pubkey_base58 = bs58::encode(pubkey_bytes).into_string();
     log_collector
         .borrow_mut()
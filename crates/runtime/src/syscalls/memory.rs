@@ -38,8 +38,7 @@ pub fn sol_memcpy(
         return Err(SbpfVmError::OverlappingMemoryRegions);
     }
 
-    let data = memory.read_bytes(src, n as usize)?.to_vec();
-    memory.write_bytes(dst, &data)?;
+    memory.copy(dst, src, n as usize)?;
     Ok(0)
 }
 
@@ -55,8 +54,7 @@ pub fn sol_memmove(
 
     mem_op_consume(n, compute, costs)?;
 
-    let data = memory.read_bytes(src, n as usize)?.to_vec();
-    memory.write_bytes(dst, &data)?;
+    memory.copy(dst, src, n as usize)?;
     Ok(0)
 }
 
@@ -72,8 +70,7 @@ pub fn sol_memset(
 
     mem_op_consume(n, compute, costs)?;
 
-    let data = vec![c; n as usize];
-    memory.write_bytes(dst, &data)?;
+    memory.write_slice(dst, n as usize)?.fill(c);
     Ok(0)
 }
 
@@ -90,8 +87,8 @@ pub fn sol_memcmp(
 
     mem_op_consume(n, compute, costs)?;
 
-    let s1_bytes = memory.read_bytes(s1, n as usize)?;
-    let s2_bytes = memory.read_bytes(s2, n as usize)?;
+    let s1_bytes = memory.read_slice(s1, n as usize)?;
+    let s2_bytes = memory.read_slice(s2, n as usize)?;
 
     let mut result: i32 = 0;
     for i in 0..n as usize {
@@ -123,7 +120,7 @@ mod tests {
         let registers = [dst, src, 5, 0, 0];
         sol_memcpy(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        assert_eq!(memory.read_bytes(dst, 5).unwrap(), &[1, 2, 3, 4, 5]);
+        assert_eq!(memory.read_slice(dst, 5).unwrap(), &[1, 2, 3, 4, 5]);
     }
 
     #[test]
@@ -150,7 +147,7 @@ mod tests {
 
         let registers = [dst, src, 5, 0, 0];
         sol_memcpy(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        assert_eq!(memory.read_bytes(dst, 5).unwrap(), &[10, 20, 30, 40, 50]);
+        assert_eq!(memory.read_slice(dst, 5).unwrap(), &[10, 20, 30, 40, 50]);
     }
 
     #[test]
@@ -214,7 +211,7 @@ mod tests {
 
         let registers = [dst, src, 5, 0, 0];
         sol_memmove(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        assert_eq!(memory.read_bytes(dst, 5).unwrap(), &[5, 4, 3, 2, 1]);
+        assert_eq!(memory.read_slice(dst, 5).unwrap(), &[5, 4, 3, 2, 1]);
     }
 
     #[test]
@@ -247,7 +244,7 @@ mod tests {
         let registers = [dst, 0xAB, 8, 0, 0];
         sol_memset(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
 
-        assert_eq!(memory.read_bytes(dst, 8).unwrap(), &[0xAB; 8]);
+        assert_eq!(memory.read_slice(dst, 8).unwrap(), &[0xAB; 8]);
     }
 
     #[test]
@@ -258,7 +255,7 @@ mod tests {
 
         let registers = [dst, 0x00, 4, 0, 0];
         sol_memset(registers, &mut memory, &meter(1_000_000), &costs()).unwrap();
-        assert_eq!(memory.read_bytes(dst, 4).unwrap(), &[0u8; 4]);
+        assert_eq!(memory.read_slice(dst, 4).unwrap(), &[0u8; 4]);
     }
 
     #[test]
@@ -26,7 +26,7 @@ fn read_seeds(memory: &Memory, seeds_addr: u64, seeds_len: u64) -> SbpfVmResult<
             return Err(SbpfVmError::MaxSeedLengthExceeded);
         }
 
-        seeds.push(memory.read_bytes(ptr, len as usize)?.to_vec());
+        seeds.push(memory.read_slice(ptr, len as usize)?.to_vec());
     }
     Ok(seeds)
 }
@@ -46,7 +46,7 @@ pub fn sol_create_program_address(
 
     let seeds = read_seeds(memory, seeds_addr, seeds_len)?;
     let program_id = Address::from(
-        <[u8; 32]>::try_from(memory.read_bytes(program_id_addr, 32)?)
+        <[u8; 32]>::try_from(memory.read_slice(program_id_addr, 32)?)
             .map_err(|_| SbpfVmError::InvalidSliceConversion)?,
     );
 
@@ -78,7 +78,7 @@ pub fn sol_try_find_program_address(
 
     let seeds = read_seeds(memory, seeds_addr, seeds_len)?;
     let program_id = Address::from(
-        <[u8; 32]>::try_from(memory.read_bytes(program_id_addr, 32)?)
+        <[u8; 32]>::try_from(memory.read_slice(program_id_addr, 32)?)
             .map_err(|_| SbpfVmError::InvalidSliceConversion)?,
     );
 
@@ -163,7 +163,7 @@ mod tests {
 
         assert_eq!(ret, 0, "should return 0 for a valid PDA");
         let written_addr: [u8; 32] = memory
-            .read_bytes(address_out_addr, 32)
+            .read_slice(address_out_addr, 32)
             .unwrap()
             .try_into()
             .unwrap();
@@ -241,7 +241,7 @@ mod tests {
 
         let written_bump = memory.read_u8(bump_out_addr).unwrap();
         let written_addr: [u8; 32] = memory
-            .read_bytes(address_out_addr, 32)
+            .read_slice(address_out_addr, 32)
             .unwrap()
             .try_into()
             .unwrap();
@@ -32,7 +32,7 @@ pub fn sol_set_return_data(
     let data = if len == 0 {
         Vec::new()
     } else {
-        memory.read_bytes(addr, len as usize)?.to_vec()
+        memory.read_slice(addr, len as usize)?.to_vec()
     };
 
     Ok((0, Some((*program_id, data))))
@@ -170,11 +170,11 @@ mod tests {
 
         assert_eq!(ret, data.len() as u64);
         assert_eq!(
-            memory.read_bytes(buf_addr, data.len()).unwrap(),
+            memory.read_slice(buf_addr, data.len()).unwrap(),
             data.as_slice()
         );
         assert_eq!(
-            memory.read_bytes(pid_addr, 32).unwrap(),
+            memory.read_slice(pid_addr, 32).unwrap(),
             program_id.as_ref()
         );
     }
@@ -200,7 +200,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(ret, 5, "returns full data length even when buf is smaller");
-        assert_eq!(memory.read_bytes(buf_addr, 3).unwrap(), &[10, 20, 30]);
+        assert_eq!(memory.read_slice(buf_addr, 3).unwrap(), &[10, 20, 30]);
     }
 
     #[test]
@@ -111,7 +111,7 @@ mod tests {
         )
         .unwrap();
 
-        let written = memory.read_bytes(addr, size_of::<Clock>()).unwrap();
+        let written = memory.read_slice(addr, size_of::<Clock>()).unwrap();
         assert_eq!(written, raw_bytes(&sysvars.clock).as_slice());
     }
 
@@ -146,7 +146,7 @@ mod tests {
         )
         .unwrap();
 
-        let written = memory.read_bytes(addr, size_of::<Rent>()).unwrap();
+        let written = memory.read_slice(addr, size_of::<Rent>()).unwrap();
         assert_eq!(written, raw_bytes(&sysvars.rent).as_slice());
     }
 
@@ -167,7 +167,7 @@ mod tests {
         )
         .unwrap();
 
-        let written = memory.read_bytes(addr, size_of::<EpochSchedule>()).unwrap();
+        let written = memory.read_slice(addr, size_of::<EpochSchedule>()).unwrap();
         assert_eq!(written, raw_bytes(&sysvars.epoch_schedule).as_slice());
     }
 
@@ -189,7 +189,7 @@ mod tests {
         .unwrap();
 
         let written = memory
-            .read_bytes(addr, size_of::<LastRestartSlot>())
+            .read_slice(addr, size_of::<LastRestartSlot>())
             .unwrap();
         assert_eq!(written, raw_bytes(&sysvars.last_restart_slot).as_slice());
     }
@@ -0,0 +1,120 @@
+//! Cost of resolving a syscall hash, the lookup the VM does on every
+//! `call` to a syscall.
+//!
+//! ```sh
+//! cargo bench -p sbpf-syscall-map
+//! ```
+
+use {
+    criterion::{Criterion, criterion_group, criterion_main},
+    std::{collections::HashMap, hint::black_box},
+    syscall_map::{
+        DynamicSyscallMap, StaticSyscallMap, SyscallMap, compute_syscall_entries_const,
+        murmur3_32, static_syscall_map,
+    },
+};
+
+/// The syscalls the Solana runtime registers.
+const SYSCALLS: &[&str; 40] = &[
+    "abort",
+    "sol_panic_",
+    "sol_log_",
+    "sol_log_64_",
+    "sol_log_compute_units_",
+    "sol_log_pubkey",
+    "sol_create_program_address",
+    "sol_try_find_program_address",
+    "sol_sha256",
+    "sol_keccak256",
+    "sol_secp256k1_recover",
+    "sol_blake3",
+    "sol_curve_validate_point",
+    "sol_curve_group_op",
+    "sol_get_clock_sysvar",
+    "sol_get_epoch_schedule_sysvar",
+    "sol_get_fees_sysvar",
+    "sol_get_rent_sysvar",
+    "sol_get_last_restart_slot_sysvar",
+    "sol_memcpy_",
+    "sol_memmove_",
+    "sol_memcmp_",
+    "sol_memset_",
+    "sol_invoke_signed_c",
+    "sol_invoke_signed_rust",
+    "sol_alloc_free_",
+    "sol_set_return_data",
+    "sol_get_return_data",
+    "sol_log_data",
+    "sol_get_processed_sibling_instruction",
+    "sol_get_stack_height",
+    "sol_sha512",
+    "sol_curve_multiscalar_mul",
+    "sol_curve_decompress",
+    "sol_curve_pairing_map",
+    "sol_get_epoch_rewards_sysvar",
+    "sol_alt_bn128_group_op",
+    "sol_big_mod_exp",
+    "sol_poseidon",
+    "sol_remaining_compute_units",
+];
+
+const ENTRIES: &[(u32, &str); 40] = &compute_syscall_entries_const(SYSCALLS);
+static MAP: SyscallMap<'static> = SyscallMap::from_entries(ENTRIES);
+
+static HANDLERS: StaticSyscallMap<u32> = static_syscall_map! {
+    "sol_log_" => 0,
+    "sol_log_64_" => 1,
+    "sol_memcpy_" => 2,
+    "sol_memset_" => 3,
+    "sol_memcmp_" => 4,
+    "sol_sha256" => 5,
+    "sol_keccak256" => 6,
+};
+
+fn bench_lookup(c: &mut Criterion) {
+    assert!(MAP.is_perfect());
+    let hashes: Vec<u32> = SYSCALLS.iter().map(|name| murmur3_32(name)).collect();
+    let dynamic = DynamicSyscallMap::from(&MAP);
+    let hash_map: HashMap<u32, &str> = ENTRIES.iter().copied().collect();
+
+    let mut group = c.benchmark_group("lookup");
+    group.bench_function("perfect_hash", |b| {
+        b.iter(|| {
+            for hash in &hashes {
+                black_box(MAP.get(black_box(*hash)));
+            }
+        })
+    });
+    group.bench_function("binary_search", |b| {
+        b.iter(|| {
+            for hash in &hashes {
+                black_box(dynamic.get(black_box(*hash)));
+            }
+        })
+    });
+    group.bench_function("hash_map", |b| {
+        b.iter(|| {
+            for hash in &hashes {
+                black_box(hash_map.get(black_box(hash)));
+            }
+        })
+    });
+    group.bench_function("static_syscall_map", |b| {
+        b.iter(|| {
+            for hash in &hashes {
+                black_box(HANDLERS.get(black_box(*hash)));
+            }
+        })
+    });
+    group.bench_function("hash_name", |b| {
+        b.iter(|| {
+            for name in SYSCALLS {
+                black_box(murmur3_32(black_box(name)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
@@ -0,0 +1,267 @@
+use crate::{hash::perfect_slot, murmur3_32};
+
+/// Seeds tried before giving up on a collision-free layout
+const MAX_SEEDS: u32 = 1 << 16;
+
+/// Marks a slot with no entry
+const EMPTY: u16 = u16::MAX;
+
+/// Values keyed by syscall, laid out at compile time by
+/// [`static_syscall_map!`](crate::static_syscall_map) in a perfect-hash
+/// table: the murmur3 hash a `call` immediate carries picks exactly one slot,
+/// so a lookup is a multiply, a shift and one comparison, with no hashing of
+/// names and no probing.
+pub struct StaticSyscallMap<V: 'static> {
+    seed: u32,
+    shift: u32,
+    slots: &'static [u16],
+    entries: &'static [(u32, &'static str, V)],
+}
+
+impl<V> StaticSyscallMap<V> {
+    /// The map laid out in `table`.
+    pub const fn from_table<const N: usize, const S: usize>(
+        table: &'static SyscallTable<V, N, S>,
+    ) -> Self {
+        Self {
+            seed: table.seed,
+            shift: table.shift,
+            slots: &table.slots,
+            entries: &table.entries,
+        }
+    }
+
+    /// The value for the syscall whose name hashes to `hash`.
+    pub const fn get(&self, hash: u32) -> Option<&V> {
+        match self.entry(hash) {
+            Some((_, _, value)) => Some(value),
+            None => None,
+        }
+    }
+
+    /// The name of the syscall whose name hashes to `hash`.
+    pub const fn name(&self, hash: u32) -> Option<&'static str> {
+        match self.entry(hash) {
+            Some((_, name, _)) => Some(name),
+            None => None,
+        }
+    }
+
+    /// The value for the syscall `name`. This hashes `name`; dispatch from
+    /// a `call` immediate should use [`StaticSyscallMap::get`].
+    pub fn get_by_name(&self, name: &str) -> Option<&V> {
+        self.get(murmur3_32(name))
+    }
+
+    /// Names and values of the syscalls, in the order they were given.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &V)> {
+        self.entries.iter().map(|(_, name, value)| (*name, value))
+    }
+
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    const fn entry(&self, hash: u32) -> Option<&(u32, &'static str, V)> {
+        let slot = self.slots[perfect_slot(hash, self.seed, self.shift)];
+        if slot == EMPTY {
+            return None;
+        }
+        let entry = &self.entries[slot as usize];
+        if entry.0 == hash { Some(entry) } else { None }
+    }
+}
+
+/// Backing storage of a [`StaticSyscallMap`], with `N` entries in `S`
+/// slots. `S` is a power of two; [`static_syscall_map!`](crate::static_syscall_map)
+/// uses at least eight slots per entry so that a seed is found quickly.
+pub struct SyscallTable<V, const N: usize, const S: usize> {
+    seed: u32,
+    shift: u32,
+    slots: [u16; S],
+    entries: [(u32, &'static str, V); N],
+}
+
+impl<V: Copy, const N: usize, const S: usize> SyscallTable<V, N, S> {
+    /// Lay out `values` under `names`, searching for a seed that gives every
+    /// syscall a slot of its own. Panics, at compile time when evaluated in
+    /// a constant, if two names hash alike or no seed works.
+    pub const fn build(names: [&'static str; N], values: [V; N]) -> Self {
+        assert!(N > 0, "A syscall map needs at least one syscall");
+        assert!(N < EMPTY as usize, "Too many syscalls for one map");
+        assert!(
+            S.is_power_of_two() && S >= N && S <= 1 << 31,
+            "The slot count must be a power of two no smaller than the syscall count"
+        );
+
+        let mut entries = [(0, "", values[0]); N];
+        let mut i = 0;
+        while i < N {
+            entries[i] = (murmur3_32(names[i]), names[i], values[i]);
+            let mut j = 0;
+            while j < i {
+                if entries[j].0 == entries[i].0 {
+                    panic!("Hash conflict detected between syscalls");
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
+        let shift = 32 - S.trailing_zeros();
+        let mut seed = 0;
+        while seed < MAX_SEEDS {
+            let mut slots = [EMPTY; S];
+            let mut placed = 0;
+            while placed < N {
+                let index = perfect_slot(entries[placed].0, seed, shift);
+                if slots[index] != EMPTY {
+                    break;
+                }
+                slots[index] = placed as u16;
+                placed += 1;
+            }
+            if placed == N {
+                return Self {
+                    seed,
+                    shift,
+                    slots,
+                    entries,
+                };
+            }
+            seed += 1;
+        }
+        panic!("No collision-free seed found for the syscall map");
+    }
+}
+
+/// Build a [`StaticSyscallMap`] at compile time from `name => value` pairs,
+/// typically syscall handlers:
+///
+/// ```
+/// use syscall_map::{StaticSyscallMap, murmur3_32, static_syscall_map};
+///
+/// fn sol_log(message: &str) -> u64 {
+///     message.len() as u64
+/// }
+///
+/// fn abort(_: &str) -> u64 {
+///     u64::MAX
+/// }
+///
+/// static SYSCALLS: StaticSyscallMap<fn(&str) -> u64> = static_syscall_map! {
+///     "sol_log_" => sol_log,
+///     "abort" => abort,
+/// };
+///
+/// let handler = SYSCALLS.get(murmur3_32("sol_log_")).unwrap();
+/// assert_eq!(handler("hello"), 5);
+/// ```
+///
+/// Values must be `Copy`. Two names with the same hash fail the build.
+#[macro_export]
+macro_rules! static_syscall_map {
+    ($($name:literal => $value:expr),+ $(,)?) => {
+        $crate::StaticSyscallMap::from_table(
+            &const {
+                $crate::SyscallTable::<
+                    _,
+                    { $crate::static_syscall_map!(@count $($name)+) },
+                    { ($crate::static_syscall_map!(@count $($name)+) * 8).next_power_of_two() },
+                >::build([$($name),+], [$($value),+])
+            },
+        )
+    };
+    (@count $($name:literal)+) => {
+        0usize $(+ { let _ = $name; 1usize })+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StaticSyscallMap, SyscallTable, murmur3_32};
+
+    fn one() -> u64 {
+        1
+    }
+
+    fn two() -> u64 {
+        2
+    }
+
+    static HANDLERS: StaticSyscallMap<fn() -> u64> = static_syscall_map! {
+        "sol_log_" => one,
+        "sol_log_64_" => two,
+    };
+
+    #[test]
+    fn test_static_syscall_map() {
+        assert_eq!(HANDLERS.len(), 2);
+        assert_eq!(HANDLERS.get(murmur3_32("sol_log_")).unwrap()(), 1);
+        assert_eq!(HANDLERS.get_by_name("sol_log_64_").unwrap()(), 2);
+        assert_eq!(
+            HANDLERS.name(murmur3_32("sol_log_64_")),
+            Some("sol_log_64_")
+        );
+        assert!(HANDLERS.get(murmur3_32("abort")).is_none());
+        assert!(HANDLERS.get(0).is_none());
+        assert_eq!(
+            HANDLERS.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["sol_log_", "sol_log_64_"]
+        );
+    }
+
+    #[test]
+    fn test_static_syscall_map_in_expression() {
+        let costs = static_syscall_map! { "sol_sha256" => 85u64, "sol_memcpy_" => 10, };
+        assert_eq!(costs.get_by_name("sol_sha256"), Some(&85));
+        assert_eq!(costs.get_by_name("sol_memcpy_"), Some(&10));
+        assert_eq!(costs.get_by_name("sol_memset_"), None);
+    }
+
+    #[test]
+    fn test_every_syscall_has_its_own_slot() {
+        let names = [
+            "abort",
+            "sol_panic_",
+            "sol_log_",
+            "sol_log_64_",
+            "sol_log_compute_units_",
+            "sol_log_pubkey",
+            "sol_log_data",
+            "sol_create_program_address",
+            "sol_try_find_program_address",
+            "sol_sha256",
+            "sol_keccak256",
+            "sol_secp256k1_recover",
+            "sol_memcpy_",
+            "sol_memmove_",
+            "sol_memcmp_",
+            "sol_memset_",
+            "sol_invoke_signed_c",
+            "sol_invoke_signed_rust",
+            "sol_set_return_data",
+            "sol_get_return_data",
+            "sol_get_clock_sysvar",
+            "sol_get_rent_sysvar",
+            "sol_remaining_compute_units",
+        ];
+        let table: &'static SyscallTable<usize, 23, 256> = Box::leak(Box::new(
+            SyscallTable::build(names, core::array::from_fn(|i| i)),
+        ));
+        let map = StaticSyscallMap::from_table(table);
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(map.get(murmur3_32(name)), Some(&i), "{}", name);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Hash conflict")]
+    fn test_duplicate_syscall_panics() {
+        let _ = SyscallTable::<u8, 2, 16>::build(["abort", "abort"], [0, 1]);
+    }
+}
@@ -44,6 +44,17 @@ pub const fn murmur3_32(buf: &str) -> u32 {
     hash
 }
 
+/// Multiplier of [`perfect_slot`], the 32-bit golden ratio
+const SLOT_MULTIPLIER: u32 = 0x9e37_79b1;
+
+/// Slot of `hash` in a perfect-hash table of `1 << (32 - shift)` slots laid
+/// out with `seed`: a multiply and a shift, cheap enough to sit on the
+/// syscall dispatch path.
+#[inline(always)]
+pub(crate) const fn perfect_slot(hash: u32, seed: u32, shift: u32) -> usize {
+    ((hash ^ seed).wrapping_mul(SLOT_MULTIPLIER) >> shift) as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,11 @@
 mod dynamic_map;
+mod handler_map;
 mod hash;
 mod static_map;
 
 pub use {
     dynamic_map::DynamicSyscallMap,
+    handler_map::{StaticSyscallMap, SyscallTable},
     hash::murmur3_32,
     static_map::{SyscallMap, compute_syscall_entries, compute_syscall_entries_const},
 };
@@ -1,9 +1,27 @@
-use crate::murmur3_32;
+use crate::{hash::perfect_slot, murmur3_32};
+
+/// Slots of the perfect-hash index, enough for eight per syscall in maps of
+/// up to 64 syscalls
+const INDEX_SLOTS: usize = 512;
+
+/// Seeds tried before a map falls back to binary search
+const MAX_SEEDS: u32 = 1 << 12;
 
 /// Static syscall map using lifetimes for compile-time and borrowed data
 /// Supports both static (compile-time) and dynamic (runtime) syscall lists via lifetimes
+///
+/// Lookups go through a perfect-hash index built alongside the entries: a
+/// seed is searched for so that every syscall hash lands in a slot of its
+/// own, and [`SyscallMap::get`] is then a multiply, a shift and one hash
+/// comparison with no branch on the data. Empty slots point at the first
+/// entry, whose hash can't match. Maps that are too large for the index, or
+/// for which no seed is found, fall back to binary search.
 pub struct SyscallMap<'a> {
     pub(crate) entries: &'a [(u32, &'a str)],
+    seed: u32,
+    shift: u32,
+    indexed: bool,
+    slots: [u8; INDEX_SLOTS],
 }
 
 impl<'a> SyscallMap<'a> {
@@ -12,17 +30,65 @@ impl<'a> SyscallMap<'a> {
     pub const fn from_entries(entries: &'a [(u32, &'a str)]) -> Self {
         // Check for hash conflicts
         let mut i = 0;
-        while i < entries.len() - 1 {
+        while i + 1 < entries.len() {
             if entries[i].0 == entries[i + 1].0 {
                 panic!("Hash conflict detected between syscalls");
             }
             i += 1;
         }
 
-        Self { entries }
+        let mut map = Self {
+            entries,
+            seed: 0,
+            shift: 0,
+            indexed: false,
+            slots: [0; INDEX_SLOTS],
+        };
+        if entries.is_empty() || entries.len() > u8::MAX as usize + 1 {
+            return map;
+        }
+
+        let mut size = (entries.len() * 8).next_power_of_two();
+        if size > INDEX_SLOTS {
+            size = INDEX_SLOTS;
+        }
+        map.shift = 32 - size.trailing_zeros();
+        let mut occupied = [false; INDEX_SLOTS];
+        while map.seed < MAX_SEEDS {
+            let mut placed = 0;
+            while placed < entries.len() {
+                let slot = perfect_slot(entries[placed].0, map.seed, map.shift);
+                if occupied[slot] {
+                    break;
+                }
+                occupied[slot] = true;
+                map.slots[slot] = placed as u8;
+                placed += 1;
+            }
+            if placed == entries.len() {
+                map.indexed = true;
+                return map;
+            }
+            // Clear only the slots this seed used
+            while placed > 0 {
+                placed -= 1;
+                let slot = perfect_slot(entries[placed].0, map.seed, map.shift);
+                occupied[slot] = false;
+                map.slots[slot] = 0;
+            }
+            map.seed += 1;
+        }
+        map
     }
 
+    #[inline]
     pub const fn get(&self, hash: u32) -> Option<&'a str> {
+        if self.indexed {
+            let slot = self.slots[perfect_slot(hash, self.seed, self.shift)];
+            let (found, name) = self.entries[slot as usize];
+            return if found == hash { Some(name) } else { None };
+        }
+
         // Binary search in const context
         let mut left = 0;
         let mut right = self.entries.len();
@@ -40,6 +106,11 @@ impl<'a> SyscallMap<'a> {
         None
     }
 
+    /// Whether lookups use the perfect-hash index rather than binary search.
+    pub const fn is_perfect(&self) -> bool {
+        self.indexed
+    }
+
     pub const fn len(&self) -> usize {
         self.entries.len()
     }
@@ -227,6 +298,38 @@ mod tests {
         assert_eq!(MULTI_MAP.get(murmur3_32("e")), Some("e"));
     }
 
+    #[test]
+    fn test_perfect_hash_index() {
+        // Every syscall the runtime registers gets a slot of its own
+        const SYSCALLS: &[&str; 8] = &[
+            "abort",
+            "sol_panic_",
+            "sol_log_",
+            "sol_log_64_",
+            "sol_memcpy_",
+            "sol_sha256",
+            "sol_invoke_signed_c",
+            "sol_get_clock_sysvar",
+        ];
+        const ENTRIES: &[(u32, &str); 8] = &compute_syscall_entries_const(SYSCALLS);
+        const MAP: SyscallMap<'static> = SyscallMap::from_entries(ENTRIES);
+
+        assert!(MAP.is_perfect());
+        for name in SYSCALLS {
+            assert_eq!(MAP.get(murmur3_32(name)), Some(*name));
+        }
+        assert_eq!(MAP.get(murmur3_32("sol_memset_")), None);
+        assert_eq!(MAP.get(0), None);
+
+        // Maps too large for the index are still searchable
+        let names: Vec<String> = (0..300).map(|i| format!("syscall_{}", i)).collect();
+        let entries = compute_syscall_entries(&names);
+        let map = SyscallMap::from_entries(&entries);
+        assert!(!map.is_perfect());
+        assert_eq!(map.get(murmur3_32("syscall_299")), Some("syscall_299"));
+        assert_eq!(map.get(murmur3_32("syscall_300")), None);
+    }
+
     #[test]
     #[should_panic(expected = "Hash conflict")]
     fn test_compute_syscall_entries_hash_conflict_panic() {
@@ -0,0 +1,88 @@
+//! Cost of the loads and stores the interpreter makes, alone and inside a
+//! running program.
+//!
+//! ```sh
+//! cargo bench -p sbpf-vm
+//! ```
+
+use {
+    criterion::{Criterion, criterion_group, criterion_main},
+    sbpf_assembler::{Assembler, AssemblerOption},
+    sbpf_vm::{memory::Memory, syscalls::MockSyscallHandler, vm::SbpfVm},
+    std::hint::black_box,
+};
+
+/// Copies an account field to the stack and back, 1000 times.
+const PROGRAM: &str = r#".globl entrypoint
+entrypoint:
+  mov64 r2, 0
+loop:
+  ldxdw r3, [r1+8]
+  stxdw [r10-8], r3
+  ldxdw r4, [r10-8]
+  stxdw [r1+16], r4
+  add64 r2, 1
+  jlt r2, 1000, loop
+  exit
+"#;
+
+fn bench_memory(c: &mut Criterion) {
+    let mut memory = Memory::new(vec![0; 1024], vec![0; 256], 64 * 1024, 32 * 1024);
+    let fp = memory.initial_frame_pointer();
+
+    let mut group = c.benchmark_group("memory");
+    group.bench_function("stack", |b| {
+        b.iter(|| {
+            for offset in (8..=256).step_by(8) {
+                let addr = black_box(fp - offset);
+                memory.write_u64(addr, offset).unwrap();
+                black_box(memory.read_u64(addr).unwrap());
+            }
+        })
+    });
+    group.bench_function("alternating", |b| {
+        b.iter(|| {
+            for offset in (0..256).step_by(8) {
+                let value = memory
+                    .read_u64(black_box(Memory::INPUT_START + offset))
+                    .unwrap();
+                memory.write_u64(black_box(fp - 8 - offset), value).unwrap();
+                black_box(
+                    memory
+                        .read_u64(black_box(Memory::RODATA_START + offset))
+                        .unwrap(),
+                );
+            }
+        })
+    });
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            for offset in (0..1024).step_by(32) {
+                black_box(
+                    memory
+                        .read_slice(black_box(Memory::INPUT_START + offset), 32)
+                        .unwrap(),
+                );
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_interpreter(c: &mut Criterion) {
+    let elf = Assembler::new(AssemblerOption::default())
+        .assemble(PROGRAM)
+        .unwrap();
+    let mut vm = SbpfVm::from_elf(&elf, vec![0; 64], MockSyscallHandler::default()).unwrap();
+
+    c.bench_function("interpreter/load_store_loop", |b| {
+        b.iter(|| {
+            vm.reset();
+            vm.run().unwrap();
+            black_box(vm.registers[4]);
+        })
+    });
+}
+
+criterion_group!(benches, bench_memory, bench_interpreter);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+use {
+    crate::coverage::{recorded_files, write_record},
+    sbpf_common::profile::ComputeProfile,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Environment variable naming a directory for runs to record the compute
+/// units they used in, as `sbpf test --cu-profile` sets it.
+pub const PROFILE_DIR_ENV: &str = "SBPF_PROFILE_DIR";
+
+/// Extension of the files compute profiles are recorded in.
+const PROFILE_EXTENSION: &str = "folded";
+
+/// Record `profile` of the program named `key`, as
+/// [`crate::coverage::program_key`] names it, in a new file in `dir`.
+pub fn write_compute_profile(
+    dir: &Path,
+    key: &str,
+    profile: &ComputeProfile,
+) -> io::Result<PathBuf> {
+    write_record(dir, key, PROFILE_EXTENSION, profile.to_text().as_bytes())
+}
+
+/// Merge the compute profiles recorded in `dir` for the program named
+/// `key`, or `None` if it never ran.
+pub fn read_compute_profile(dir: &Path, key: &str) -> io::Result<Option<ComputeProfile>> {
+    let mut merged: Option<ComputeProfile> = None;
+    for path in recorded_files(dir, key, PROFILE_EXTENSION)? {
+        let profile = ComputeProfile::from_text(&fs::read_to_string(&path)?).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' is not a compute profile", path.display()),
+            )
+        })?;
+        merged.get_or_insert_default().merge(&profile);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::coverage::program_key};
+
+    #[test]
+    fn test_write_and_read_compute_profile() {
+        let dir = std::env::temp_dir().join(format!("sbpf-profile-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let key = program_key(b"elf");
+
+        let mut profile = ComputeProfile::new();
+        profile.record(0, 3, 0, None);
+        write_compute_profile(&dir, &key, &profile).unwrap();
+        write_compute_profile(&dir, &key, &profile).unwrap();
+        write_compute_profile(&dir, &program_key(b"other"), &profile).unwrap();
+
+        let merged = read_compute_profile(&dir, &key).unwrap().unwrap();
+        assert_eq!(merged.to_text(), "0;0 6\n");
+        assert_eq!(
+            read_compute_profile(&dir, &program_key(b"none")).unwrap(),
+            None
+        );
+
+        fs::write(dir.join(format!("{}-0-0.folded", key)), "0;0 x\n").unwrap();
+        assert!(read_compute_profile(&dir, &key).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use {
+    crate::memory::Memory,
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// Compute units charged for instructions and syscalls, defaulting to the
+/// costs of the Solana runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostModel {
+    /// Units charged for every instruction executed.
+    pub instruction_cost: u64,
+    /// Charge syscalls from this model before handing them to the syscall
+    /// handler. Turn this off for handlers that meter syscalls themselves.
+    pub charge_syscalls: bool,
+    pub syscall_base_cost: u64,
+    pub log_64_units: u64,
+    pub log_pubkey_units: u64,
+    pub create_program_address_units: u64,
+    /// Base cost of a CPI, before the instruction and account data it passes.
+    pub invoke_units: u64,
+    pub cpi_bytes_per_unit: u64,
+    pub sha256_base_cost: u64,
+    pub sha256_byte_cost: u64,
+    pub sysvar_base_cost: u64,
+    pub mem_op_base_cost: u64,
+    pub get_remaining_compute_units_cost: u64,
+    /// Fixed costs by syscall name, replacing the ones computed above.
+    pub syscall_costs: BTreeMap<String, u64>,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            instruction_cost: 1,
+            charge_syscalls: true,
+            syscall_base_cost: 100,
+            log_64_units: 100,
+            log_pubkey_units: 100,
+            create_program_address_units: 1500,
+            invoke_units: 946,
+            cpi_bytes_per_unit: 250,
+            sha256_base_cost: 85,
+            sha256_byte_cost: 1,
+            sysvar_base_cost: 100,
+            mem_op_base_cost: 10,
+            get_remaining_compute_units_cost: 100,
+            syscall_costs: BTreeMap::new(),
+        }
+    }
+}
+
+impl CostModel {
+    /// Units charged for calling `name` with `registers` holding r1-r5.
+    pub fn syscall_cost(&self, name: &str, registers: [u64; 5], memory: &Memory) -> u64 {
+        if let Some(cost) = self.syscall_costs.get(name) {
+            return *cost;
+        }
+        match name {
+            "sol_log_" => self.syscall_base_cost.max(registers[1]),
+            "sol_log_64_" => self.log_64_units,
+            "sol_log_pubkey" => self.log_pubkey_units,
+            "sol_remaining_compute_units" => self.get_remaining_compute_units_cost,
+            "sol_memcpy_" | "sol_memmove_" | "sol_memset_" | "sol_memcmp_" => {
+                self.mem_op_cost(registers[2])
+            }
+            "sol_sha256" | "sol_keccak256" | "sol_blake3" => {
+                self.hash_cost(registers[0], registers[1], memory)
+            }
+            "sol_create_program_address" | "sol_try_find_program_address" => {
+                self.create_program_address_units
+            }
+            "sol_get_clock_sysvar" | "sol_get_epoch_schedule_sysvar" => {
+                self.sysvar_base_cost.saturating_add(40)
+            }
+            "sol_get_rent_sysvar" => self.sysvar_base_cost.saturating_add(24),
+            "sol_get_last_restart_slot_sysvar" => self.sysvar_base_cost.saturating_add(8),
+            "sol_set_return_data" => self
+                .syscall_base_cost
+                .saturating_add(self.per_byte(registers[1])),
+            "sol_invoke_signed_c" | "sol_invoke_signed_rust" => self.invoke_units,
+            "sol_panic_" => registers[1],
+            "abort" => 0,
+            _ => self.syscall_base_cost,
+        }
+    }
+
+    fn per_byte(&self, len: u64) -> u64 {
+        len.checked_div(self.cpi_bytes_per_unit).unwrap_or(u64::MAX)
+    }
+
+    fn mem_op_cost(&self, len: u64) -> u64 {
+        self.mem_op_base_cost.max(self.per_byte(len))
+    }
+
+    /// The base cost plus a per-byte cost for each `(ptr, len)` slice. Slices
+    /// that cannot be read are left to the handler to reject.
+    fn hash_cost(&self, vals_addr: u64, vals_len: u64, memory: &Memory) -> u64 {
+        let mut cost = self.sha256_base_cost;
+        for i in 0..vals_len {
+            let Ok(len) = memory.read_u64(vals_addr.saturating_add(i * 16 + 8)) else {
+                break;
+            };
+            cost = cost.saturating_add(
+                self.mem_op_base_cost
+                    .max(self.sha256_byte_cost.saturating_mul(len / 2)),
+            );
+        }
+        cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syscall_cost() {
+        let memory = Memory::new(vec![], vec![], 4096, 1024);
+        let model = CostModel::default();
+        assert_eq!(
+            model.syscall_cost("sol_log_", [0, 5, 0, 0, 0], &memory),
+            100
+        );
+        assert_eq!(
+            model.syscall_cost("sol_log_", [0, 500, 0, 0, 0], &memory),
+            500
+        );
+        assert_eq!(
+            model.syscall_cost("sol_memcpy_", [0, 0, 8, 0, 0], &memory),
+            10
+        );
+        assert_eq!(
+            model.syscall_cost("sol_memset_", [0, 0, 10_000, 0, 0], &memory),
+            40
+        );
+        assert_eq!(
+            model.syscall_cost("sol_invoke_signed_c", [0; 5], &memory),
+            946
+        );
+        assert_eq!(model.syscall_cost("custom", [0; 5], &memory), 100);
+
+        let model = CostModel {
+            syscall_costs: BTreeMap::from([("sol_log_".to_string(), 7)]),
+            ..CostModel::default()
+        };
+        assert_eq!(
+            model.syscall_cost("sol_log_", [0, 500, 0, 0, 0], &memory),
+            7
+        );
+    }
+
+    #[test]
+    fn test_hash_cost() {
+        let mut memory = Memory::new(vec![], vec![], 4096, 1024);
+        let slices = Memory::HEAP_START;
+        memory.write_u64(slices + 8, 4).unwrap();
+        memory.write_u64(slices + 24, 100).unwrap();
+        let model = CostModel::default();
+        // 85 base, then 10 for the short slice and 50 for the long one.
+        assert_eq!(
+            model.syscall_cost("sol_sha256", [slices, 2, 0, 0, 0], &memory),
+            145
+        );
+    }
+}
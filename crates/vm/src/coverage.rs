@@ -0,0 +1,215 @@
+use {
+    sha2::{Digest, Sha256},
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Environment variable naming a directory for runs to record their
+/// coverage in, as `sbpf test --coverage` sets it.
+pub const COVERAGE_DIR_ENV: &str = "SBPF_COVERAGE_DIR";
+
+/// Extension of the files coverage is recorded in.
+const COVERAGE_EXTENSION: &str = "cov";
+
+/// Files written by this process so far, to keep their names unique.
+static FILES_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Which instructions have executed, one bit per instruction index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl Coverage {
+    pub fn new(instruction_count: usize) -> Self {
+        Self {
+            bits: vec![0; instruction_count.div_ceil(8)],
+            len: instruction_count,
+        }
+    }
+
+    /// Read a bitmap written by [`Coverage::to_bytes`] for a program of
+    /// `instruction_count` instructions. Missing bytes read as unexecuted and
+    /// extra ones are ignored.
+    pub fn from_bytes(bytes: &[u8], instruction_count: usize) -> Self {
+        let mut coverage = Self::new(instruction_count);
+        for (byte, other) in coverage.bits.iter_mut().zip(bytes) {
+            *byte = *other;
+        }
+        coverage.clear_padding();
+        coverage
+    }
+
+    /// The bitmap, instruction `pc` at bit `pc % 8` of byte `pc / 8`.
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Mark the instruction at `pc` executed. Out of range program counters
+    /// are ignored, the VM reports those itself.
+    pub fn record(&mut self, pc: usize) {
+        if pc < self.len {
+            self.bits[pc / 8] |= 1 << (pc % 8);
+        }
+    }
+
+    pub fn is_covered(&self, pc: usize) -> bool {
+        pc < self.len && self.bits[pc / 8] & (1 << (pc % 8)) != 0
+    }
+
+    /// Number of instructions tracked.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of instructions executed.
+    pub fn covered(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Add the instructions another run of the same program executed.
+    pub fn merge(&mut self, other: &Coverage) {
+        if self.len < other.len {
+            self.len = other.len;
+            self.bits.resize(other.bits.len(), 0);
+        }
+        for (byte, other) in self.bits.iter_mut().zip(&other.bits) {
+            *byte |= other;
+        }
+    }
+
+    fn clear_padding(&mut self) {
+        if let Some(last) = self.bits.last_mut()
+            && !self.len.is_multiple_of(8)
+        {
+            *last &= (1 << (self.len % 8)) - 1;
+        }
+    }
+}
+
+/// The name coverage of a program is recorded under: the SHA-256 of its
+/// ELF, in hex.
+pub fn program_key(elf_bytes: &[u8]) -> String {
+    Sha256::digest(elf_bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Record `coverage` of the program named `key` in a new file in `dir`, so
+/// runs in other threads and processes don't overwrite each other.
+pub fn write_coverage(dir: &Path, key: &str, coverage: &Coverage) -> io::Result<PathBuf> {
+    write_record(dir, key, COVERAGE_EXTENSION, coverage.to_bytes())
+}
+
+/// Merge the coverage recorded in `dir` for the program named `key`, of
+/// `instruction_count` instructions, or `None` if it never ran.
+pub fn read_coverage(
+    dir: &Path,
+    key: &str,
+    instruction_count: usize,
+) -> io::Result<Option<Coverage>> {
+    let mut merged: Option<Coverage> = None;
+    for path in recorded_files(dir, key, COVERAGE_EXTENSION)? {
+        let coverage = Coverage::from_bytes(&fs::read(&path)?, instruction_count);
+        merged
+            .get_or_insert_with(|| Coverage::new(instruction_count))
+            .merge(&coverage);
+    }
+    Ok(merged)
+}
+
+/// Write `contents` to a new `<key>-<pid>-<n>.<extension>` file in `dir`.
+pub(crate) fn write_record(
+    dir: &Path,
+    key: &str,
+    extension: &str,
+    contents: &[u8],
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "{}-{}-{}.{}",
+        key,
+        std::process::id(),
+        FILES_WRITTEN.fetch_add(1, Ordering::Relaxed),
+        extension
+    ));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// The files [`write_record`] wrote to `dir` for `key` and `extension`.
+pub(crate) fn recorded_files(dir: &Path, key: &str, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let recorded = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(key))
+            .is_some_and(|rest| rest.starts_with('-'))
+            && path.extension().is_some_and(|ext| ext == extension);
+        if recorded {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_merge_and_round_trip() {
+        let mut coverage = Coverage::new(10);
+        coverage.record(0);
+        coverage.record(9);
+        coverage.record(9);
+        coverage.record(12);
+        assert!(coverage.is_covered(9) && !coverage.is_covered(1));
+        assert_eq!(coverage.covered(), 2);
+        assert_eq!(coverage.to_bytes(), [0b1, 0b10]);
+
+        let mut other = Coverage::new(12);
+        other.record(11);
+        coverage.merge(&other);
+        assert_eq!((coverage.len(), coverage.covered()), (12, 3));
+
+        let read = Coverage::from_bytes(&[0xff, 0xff, 0xff], 10);
+        assert_eq!((read.len(), read.covered()), (10, 10));
+        assert_eq!(Coverage::from_bytes(&[0b100], 12).covered(), 1);
+    }
+
+    #[test]
+    fn test_write_and_read_coverage() {
+        let dir = std::env::temp_dir().join(format!("sbpf-coverage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let key = program_key(b"elf");
+        assert_eq!(key.len(), 64);
+        assert!(read_coverage(&dir, &key, 4).is_err());
+
+        for pc in [0, 2] {
+            let mut coverage = Coverage::new(4);
+            coverage.record(pc);
+            write_coverage(&dir, &key, &coverage).unwrap();
+        }
+        write_coverage(&dir, &program_key(b"other"), &Coverage::new(4)).unwrap();
+
+        let merged = read_coverage(&dir, &key, 4).unwrap().unwrap();
+        assert_eq!(merged.to_bytes(), [0b101]);
+        assert_eq!(read_coverage(&dir, &program_key(b"none"), 4).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
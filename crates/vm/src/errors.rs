@@ -6,6 +6,9 @@ pub enum SbpfVmError {
     #[error("Division by zero")]
     DivisionByZero,
 
+    #[error("Division overflow")]
+    DivisionOverflow,
+
     #[error("Invalid memory access at address {0:#x}")]
     InvalidMemoryAccess(u64),
 
@@ -24,9 +27,6 @@ pub enum SbpfVmError {
     #[error("Call depth exceeded (max {0})")]
     CallDepthExceeded(usize),
 
-    #[error("Execution limit reached ({0} steps)")]
-    ExecutionLimitReached(u64),
-
     #[error("Syscall error: {0}")]
     SyscallError(String),
 
@@ -47,6 +47,15 @@ pub enum SbpfVmError {
 
     #[error("Invalid slice conversion")]
     InvalidSliceConversion,
+
+    #[error("Invalid ELF: {0}")]
+    InvalidElf(String),
+
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("Too many arguments: {0} given, at most 5 fit in r1-r5")]
+    TooManyArguments(usize),
 }
 
 pub type SbpfVmResult<T> = Result<T, SbpfVmError>;
@@ -55,6 +64,7 @@ impl From<ExecutionError> for SbpfVmError {
     fn from(err: ExecutionError) -> Self {
         match err {
             ExecutionError::DivisionByZero => SbpfVmError::DivisionByZero,
+            ExecutionError::DivisionOverflow => SbpfVmError::DivisionOverflow,
             ExecutionError::InvalidOperand => SbpfVmError::InvalidOperand,
             ExecutionError::InvalidInstruction => SbpfVmError::InvalidInstruction,
             ExecutionError::CallDepthExceeded(n) => SbpfVmError::CallDepthExceeded(n),
@@ -0,0 +1,231 @@
+/// Room left after each account's data in the aligned format, so programs
+/// can grow an account in place.
+pub const MAX_PERMITTED_DATA_INCREASE: usize = 10240;
+
+const NON_DUP_MARKER: u8 = 0xff;
+
+/// Layout of the input region, which depends on the loader that owns the
+/// program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// The deprecated loader v1: fields packed without padding and no room
+    /// to grow account data.
+    Unaligned,
+    /// Loader v2 and the upgradeable loader: account data padded to grow by
+    /// [`MAX_PERMITTED_DATA_INCREASE`] bytes and fields aligned to 8 bytes.
+    #[default]
+    Aligned,
+}
+
+/// An account as the program sees it in its input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputAccount {
+    pub key: [u8; 32],
+    pub owner: [u8; 32],
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+/// Serializes a program id, accounts and instruction data into the input
+/// region the Solana loaders hand a program in r1.
+#[derive(Debug, Clone, Default)]
+pub struct InputBuilder {
+    program_id: [u8; 32],
+    accounts: Vec<InputAccount>,
+    instruction_data: Vec<u8>,
+    format: InputFormat,
+}
+
+impl InputBuilder {
+    pub fn new(program_id: [u8; 32]) -> Self {
+        Self {
+            program_id,
+            ..Self::default()
+        }
+    }
+
+    /// Add an account. One whose key is already listed is written as a
+    /// duplicate of the first, which gets the union of their privileges.
+    pub fn with_account(mut self, account: InputAccount) -> Self {
+        self.accounts.push(account);
+        self
+    }
+
+    pub fn with_instruction_data(mut self, data: &[u8]) -> Self {
+        self.instruction_data = data.to_vec();
+        self
+    }
+
+    pub fn with_format(mut self, format: InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The serialized input region.
+    pub fn build(&self) -> Vec<u8> {
+        self.serialize().0
+    }
+
+    /// Offset of each account's data in the input region, in the order the
+    /// accounts were added. Add [`crate::memory::Memory::INPUT_START`] for
+    /// its address in the VM.
+    pub fn data_offsets(&self) -> Vec<usize> {
+        self.serialize().1
+    }
+
+    fn serialize(&self) -> (Vec<u8>, Vec<usize>) {
+        let aligned = self.format == InputFormat::Aligned;
+        let mut buffer = Vec::new();
+        let mut data_offsets: Vec<usize> = Vec::with_capacity(self.accounts.len());
+        buffer.extend_from_slice(&(self.accounts.len() as u64).to_le_bytes());
+
+        for (index, account) in self.accounts.iter().enumerate() {
+            if let Some(first) = self.accounts[..index]
+                .iter()
+                .position(|earlier| earlier.key == account.key)
+            {
+                data_offsets.push(data_offsets[first]);
+                buffer.push(first as u8);
+                if aligned {
+                    buffer.extend_from_slice(&[0; 7]);
+                }
+                continue;
+            }
+
+            let (is_signer, is_writable) = self.accounts[index..]
+                .iter()
+                .filter(|later| later.key == account.key)
+                .fold((false, false), |(signer, writable), later| {
+                    (signer | later.is_signer, writable | later.is_writable)
+                });
+            buffer.extend_from_slice(&[NON_DUP_MARKER, is_signer as u8, is_writable as u8]);
+            if aligned {
+                buffer.push(account.executable as u8);
+                buffer.extend_from_slice(&[0; 4]);
+                buffer.extend_from_slice(&account.key);
+                buffer.extend_from_slice(&account.owner);
+            } else {
+                buffer.extend_from_slice(&account.key);
+            }
+            buffer.extend_from_slice(&account.lamports.to_le_bytes());
+            buffer.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+            data_offsets.push(buffer.len());
+            buffer.extend_from_slice(&account.data);
+            if aligned {
+                buffer.resize(buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+                buffer.resize(buffer.len().next_multiple_of(8), 0);
+            } else {
+                buffer.extend_from_slice(&account.owner);
+                buffer.push(account.executable as u8);
+            }
+            buffer.extend_from_slice(&account.rent_epoch.to_le_bytes());
+        }
+
+        buffer.extend_from_slice(&(self.instruction_data.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&self.instruction_data);
+        buffer.extend_from_slice(&self.program_id);
+        (buffer, data_offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{loader::load_elf, syscalls::MockSyscallHandler},
+        sbpf_assembler::{Assembler, AssemblerOption},
+    };
+
+    fn account(key: u8, data: &[u8]) -> InputAccount {
+        InputAccount {
+            key: [key; 32],
+            owner: [9; 32],
+            lamports: 1000 + key as u64,
+            data: data.to_vec(),
+            ..InputAccount::default()
+        }
+    }
+
+    #[test]
+    fn test_unaligned_layout() {
+        let builder = InputBuilder::new([7; 32])
+            .with_account(InputAccount {
+                is_signer: true,
+                ..account(1, &[0xaa, 0xbb])
+            })
+            .with_account(InputAccount {
+                is_writable: true,
+                ..account(1, &[])
+            })
+            .with_instruction_data(&[5])
+            .with_format(InputFormat::Unaligned);
+
+        let mut expected = 2u64.to_le_bytes().to_vec();
+        expected.extend([0xff, 1, 1]);
+        expected.extend([1; 32]);
+        expected.extend(1001u64.to_le_bytes());
+        expected.extend(2u64.to_le_bytes());
+        expected.extend([0xaa, 0xbb]);
+        expected.extend([9; 32]);
+        expected.push(0);
+        expected.extend(0u64.to_le_bytes());
+        expected.push(0);
+        expected.extend(1u64.to_le_bytes());
+        expected.push(5);
+        expected.extend([7; 32]);
+        assert_eq!(builder.build(), expected);
+        assert_eq!(builder.data_offsets(), vec![59, 59]);
+    }
+
+    #[test]
+    fn test_aligned_layout() {
+        let builder = InputBuilder::new([7; 32])
+            .with_account(account(1, &[0xaa; 3]))
+            .with_account(account(2, &[]))
+            .with_account(account(1, &[]))
+            .with_instruction_data(&[5, 6]);
+        let input = builder.build();
+
+        let offsets = builder.data_offsets();
+        assert_eq!(offsets[0], 8 + 8 + 64 + 16);
+        assert_eq!(&input[offsets[0]..offsets[0] + 4], &[0xaa, 0xaa, 0xaa, 0]);
+        // Data, growth room and padding to 8 bytes, then the rent epoch.
+        let second = offsets[0] + (3 + MAX_PERMITTED_DATA_INCREASE).next_multiple_of(8) + 8;
+        assert_eq!(input[second], 0xff);
+        assert_eq!(&input[second + 8..second + 40], &[2; 32]);
+        assert_eq!(offsets[1], second + 8 + 64 + 16);
+        assert_eq!(offsets[2], offsets[0]);
+
+        let duplicate = offsets[1] + MAX_PERMITTED_DATA_INCREASE + 8;
+        assert_eq!(&input[duplicate..duplicate + 8], &[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&input[duplicate + 8..duplicate + 16], &2u64.to_le_bytes());
+        assert_eq!(&input[duplicate + 16..], &[&[5, 6][..], &[7; 32]].concat());
+    }
+
+    #[test]
+    fn test_program_reads_input() {
+        // Exit with the first account's lamports plus its first data byte.
+        let source = r#".globl entrypoint
+entrypoint:
+  ldxdw r0, [r1+80]
+  ldxb r2, [r1+96]
+  add64 r0, r2
+  exit
+"#;
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        let builder = InputBuilder::new([7; 32]).with_account(account(4, &[40]));
+        assert_eq!(builder.data_offsets(), vec![96]);
+
+        let mut vm = load_elf(&elf)
+            .unwrap()
+            .into_vm(builder.build(), MockSyscallHandler::default());
+        vm.run().unwrap();
+        assert_eq!(vm.exit_code, Some(1044));
+    }
+}
@@ -1,5 +1,16 @@
 pub mod compute;
+pub mod compute_profile;
+pub mod cost;
+pub mod coverage;
 pub mod errors;
+pub mod input;
+pub mod loader;
 pub mod memory;
+pub mod session;
+pub mod snapshot;
+pub mod solana_syscalls;
 pub mod syscalls;
+pub mod trace;
 pub mod vm;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
@@ -0,0 +1,377 @@
+use {
+    crate::{
+        errors::{SbpfVmError, SbpfVmResult},
+        memory::Memory,
+        syscalls::SyscallHandler,
+        vm::{SbpfVm, SbpfVmConfig},
+    },
+    either::Either,
+    sbpf_common::{
+        inst_param::Number, instruction::Instruction, opcode::Opcode, version::SbpfVersion,
+    },
+    sbpf_disassembler::{
+        entry::EntryAnalysis,
+        program::{Disassembly, Parsed, Program},
+        rodata::RodataSection,
+    },
+};
+
+/// A program decoded from its ELF into the form the VM runs.
+#[derive(Debug, Clone)]
+pub struct LoadedProgram {
+    pub instructions: Vec<Instruction>,
+    /// `.rodata`, relocated to be mapped at [`Memory::RODATA_START`].
+    pub rodata: Vec<u8>,
+    /// `.data` followed by the zero-filled `.bss`, and the address the VM
+    /// maps it at.
+    pub writable_data: Option<(Vec<u8>, u64)>,
+    /// Index of the entrypoint instruction.
+    pub entrypoint: usize,
+    /// Named functions from the symbol tables, with the index of their first
+    /// instruction, in `.text` order.
+    pub symbols: Vec<(String, usize)>,
+    /// The sBPF version the ELF header records.
+    pub version: SbpfVersion,
+}
+
+impl LoadedProgram {
+    /// Map the program's writable data into a fresh VM's memory.
+    pub fn map_data(&self, memory: &mut Memory) {
+        if let Some((data, start)) = &self.writable_data {
+            memory.map_data(*start, data.clone());
+        }
+    }
+
+    /// Index of the first instruction of the function named `name`.
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, index)| *index)
+    }
+
+    /// Name the functions starting at the given `.text` byte offsets, such
+    /// as the labels the assembler reports, for programs whose ELF has no
+    /// symbol for them. Offsets that do not start an instruction are skipped.
+    pub fn add_symbols(&mut self, symbols: impl IntoIterator<Item = (String, u64)>) {
+        let offsets = instruction_offsets(&self.instructions);
+        for (name, offset) in symbols {
+            if self.symbol(&name).is_none()
+                && let Ok(index) = offsets.binary_search(&offset)
+            {
+                self.symbols.push((name, index));
+            }
+        }
+    }
+
+    /// A VM positioned at the entrypoint with `input` in the input region.
+    pub fn into_vm<H: SyscallHandler>(self, input: Vec<u8>, syscall_handler: H) -> SbpfVm<H> {
+        self.into_vm_with_config(input, syscall_handler, SbpfVmConfig::default())
+    }
+
+    /// A VM positioned at the entrypoint, with the stack frames of the
+    /// program's version whatever `config` asks for.
+    pub fn into_vm_with_config<H: SyscallHandler>(
+        self,
+        input: Vec<u8>,
+        syscall_handler: H,
+        config: SbpfVmConfig,
+    ) -> SbpfVm<H> {
+        let config = SbpfVmConfig {
+            dynamic_stack_frames: self.version.dynamic_stack_frames(),
+            ..config
+        };
+        let mut vm = SbpfVm::new_with_config(
+            self.instructions,
+            input,
+            self.rodata,
+            syscall_handler,
+            config,
+        );
+        if let Some((data, start)) = self.writable_data {
+            vm.memory.map_data(start, data);
+        }
+        vm.set_entrypoint(self.entrypoint);
+        vm.symbols = self.symbols;
+        vm
+    }
+}
+
+impl<H: SyscallHandler> SbpfVm<H> {
+    /// Load a program from its ELF, ready to run from the entrypoint.
+    pub fn from_elf(elf_bytes: &[u8], input: Vec<u8>, syscall_handler: H) -> SbpfVmResult<Self> {
+        Ok(load_elf(elf_bytes)?.into_vm(input, syscall_handler))
+    }
+}
+
+/// Parse an ELF binary into the instructions, rodata, writable data,
+/// entrypoint and symbols the VM runs.
+pub fn load_elf(elf_bytes: &[u8]) -> SbpfVmResult<LoadedProgram> {
+    let program =
+        Program::from_bytes(elf_bytes).map_err(|e| SbpfVmError::InvalidElf(format!("{:?}", e)))?;
+    // Writable data keeps its ELF address, so references to it need no
+    // relocation.
+    let writable_data = program.get_writable_data();
+    let version = program.version();
+
+    let Disassembly {
+        instructions,
+        rodata: rodata_section,
+        entrypoint: entrypoint_idx,
+        ..
+    } = program
+        .to_ixs()
+        .and_then(Parsed::into_strict)
+        .map_err(|e| SbpfVmError::InvalidElf(format!("{:?}", e)))?;
+    let entrypoint = entrypoint_idx.unwrap_or(0);
+
+    // into_strict fails on any decode error, so every entry is an instruction.
+    let mut instructions: Vec<Instruction> = instructions
+        .into_iter()
+        .map(|ix| ix.expect_left("strict disassembly contains no decode errors"))
+        .collect();
+
+    let mut rodata = rodata_section
+        .as_ref()
+        .map(|s| s.data.clone())
+        .unwrap_or_default();
+
+    if let Some(ref section) = rodata_section {
+        apply_relocations(&mut instructions, &mut rodata, section);
+    }
+
+    let symbols = symbols(elf_bytes, &instructions);
+
+    Ok(LoadedProgram {
+        instructions,
+        rodata,
+        writable_data,
+        entrypoint,
+        symbols,
+        version,
+    })
+}
+
+/// Named function starts, located by instruction index. Symbols that do
+/// not land on an instruction are dropped.
+fn symbols(elf_bytes: &[u8], instructions: &[Instruction]) -> Vec<(String, usize)> {
+    let Ok(analysis) = EntryAnalysis::from_bytes(elf_bytes) else {
+        return Vec::new();
+    };
+    let offsets = instruction_offsets(instructions);
+    analysis
+        .functions
+        .into_iter()
+        .filter_map(|function| {
+            let name = function.name?;
+            let index = offsets.binary_search(&function.offset).ok()?;
+            Some((name, index))
+        })
+        .collect()
+}
+
+/// Byte offset of each instruction in `.text`.
+pub fn instruction_offsets(instructions: &[Instruction]) -> Vec<u64> {
+    instructions
+        .iter()
+        .scan(0, |offset, ix| {
+            let current = *offset;
+            *offset += ix.get_size();
+            Some(current)
+        })
+        .collect()
+}
+
+/// Apply all relocations.
+fn apply_relocations(instructions: &mut [Instruction], rodata: &mut [u8], section: &RodataSection) {
+    let elf_base = section.base_address;
+    let elf_end = elf_base + section.data.len() as u64;
+
+    // 1. Relocate lddw immediates that reference rodata addresses (for v0 only).
+    if elf_base != Memory::RODATA_START {
+        for ix in instructions.iter_mut() {
+            if ix.opcode == Opcode::Lddw
+                && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+            {
+                let addr = *imm as u64;
+                if addr >= elf_base && addr < elf_end {
+                    ix.imm = Some(Either::Right(Number::Int(
+                        (Memory::RODATA_START + addr - elf_base) as i64,
+                    )));
+                }
+            }
+        }
+    }
+
+    // 2. Apply data relocations.
+    for &offset in &section.data_relocations {
+        if offset + 8 <= rodata.len() {
+            let ptr = u64::from_le_bytes(rodata[offset..offset + 8].try_into().unwrap());
+            if ptr >= elf_base && ptr < elf_end {
+                let relocated = Memory::RODATA_START + (ptr - elf_base);
+                rodata[offset..offset + 8].copy_from_slice(&relocated.to_le_bytes());
+            }
+        }
+    }
+
+    // 3. Apply text relocations.
+    for &(offset, ix_idx) in &section.text_relocations {
+        if offset + 8 <= rodata.len() {
+            rodata[offset..offset + 8].copy_from_slice(&(ix_idx as u64).to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::syscalls::MockSyscallHandler,
+        sbpf_assembler::{Assembler, AssemblerOption, SbpfArch, SymbolSection},
+    };
+
+    const SOURCE: &str = r#".globl entrypoint
+helper:
+  ldxb r0, [r1+0]
+  exit
+entrypoint:
+  lddw r1, message
+  call helper
+  exit
+.rodata
+message: .ascii "*"
+"#;
+
+    #[test]
+    fn test_load_and_run() {
+        for arch in [SbpfArch::V0, SbpfArch::V3] {
+            let elf = Assembler::new(AssemblerOption::default().with_arch(arch))
+                .assemble(SOURCE)
+                .unwrap();
+            let program = load_elf(&elf).unwrap();
+            assert_eq!(program.entrypoint, 2);
+            assert_eq!(program.rodata[0], b'*');
+            // Only v0 programs carry a dynamic symbol table.
+            if matches!(arch, SbpfArch::V0) {
+                assert_eq!(program.symbol("entrypoint"), Some(2));
+            }
+
+            let mut vm = SbpfVm::from_elf(&elf, Vec::new(), MockSyscallHandler::default()).unwrap();
+            vm.run().unwrap();
+            assert_eq!(vm.exit_code, Some(b'*' as u64));
+        }
+    }
+
+    #[test]
+    fn test_run_sbpf_v2() {
+        // Loads, stores and lddw use their v2 encodings, and the product
+        // and quotient instructions replace mul and div.
+        let source = r#".globl entrypoint
+entrypoint:
+  lddw r1, message
+  ldxb r2, [r1+0]
+  stxdw [r10-8], r2
+  ldxdw r0, [r10-8]
+  lmul64 r0, 3
+  mov64 r3, 2
+  udiv64 r0, r3
+  exit
+.rodata
+message: .ascii "*"
+"#;
+        let elf = Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V2))
+            .assemble(source)
+            .unwrap();
+        let mut vm = load_elf(&elf)
+            .unwrap()
+            .into_vm(Vec::new(), MockSyscallHandler::default());
+        vm.run().unwrap();
+        assert_eq!(vm.exit_code, Some(b'*' as u64 * 3 / 2));
+    }
+
+    #[test]
+    fn test_run_sbpf_v1_dynamic_stack_frames() {
+        // The caller moves r10 down past its slot before the call, and the
+        // callee starts where the caller left r10 rather than a frame up.
+        let source = r#".globl entrypoint
+entrypoint:
+  mov64 r1, 7
+  stxdw [r10-8], r1
+  add64 r10, -64
+  call helper
+  add64 r10, 64
+  ldxdw r0, [r10-8]
+  exit
+helper:
+  mov64 r3, r10
+  mov64 r1, 9
+  stxdw [r10-8], r1
+  add64 r10, -8
+  exit
+"#;
+        let elf = Assembler::new(AssemblerOption::default().with_arch(SbpfArch::V1))
+            .assemble(source)
+            .unwrap();
+        let mut vm = load_elf(&elf)
+            .unwrap()
+            .into_vm(Vec::new(), MockSyscallHandler::default());
+        let frame_pointer = vm.memory.initial_frame_pointer();
+        assert_eq!(
+            frame_pointer,
+            Memory::STACK_START + Memory::stack_size(vm.config.max_call_depth) as u64
+        );
+        vm.run().unwrap();
+        assert_eq!(vm.exit_code, Some(7));
+        assert_eq!(vm.registers[3], frame_pointer - 64);
+        assert_eq!(vm.registers[10], frame_pointer);
+        assert_eq!(vm.memory.read_u64(frame_pointer - 72).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_invalid_elf() {
+        assert!(matches!(
+            load_elf(b"not an elf"),
+            Err(SbpfVmError::InvalidElf(_))
+        ));
+    }
+
+    #[test]
+    fn test_call_function() {
+        let source = r#".globl entrypoint
+entrypoint:
+  mov64 r1, 1
+  mov64 r2, 2
+  call add
+  exit
+add:
+  mov64 r0, r1
+  add64 r0, r2
+  exit
+"#;
+        let artifacts = Assembler::new(AssemblerOption::default())
+            .assemble_full(source)
+            .unwrap();
+        let mut program = load_elf(&artifacts.elf).unwrap();
+        program.add_symbols(
+            artifacts
+                .symbols
+                .into_iter()
+                .filter(|symbol| symbol.section == SymbolSection::Text)
+                .map(|symbol| (symbol.name, symbol.offset)),
+        );
+        let mut vm = program.into_vm(Vec::new(), MockSyscallHandler::default());
+
+        assert_eq!(vm.call_function("add", &[40, 2]).unwrap(), 42);
+        assert_eq!(vm.call_function("add", &[u64::MAX, 1]).unwrap(), 0);
+        assert_eq!(vm.compute_meter.get_consumed(), 3);
+        assert_eq!(vm.call_function("entrypoint", &[]).unwrap(), 3);
+        assert!(matches!(
+            vm.call_function("missing", &[]),
+            Err(SbpfVmError::UnknownFunction(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            vm.call_function("add", &[0; 6]),
+            Err(SbpfVmError::TooManyArguments(6))
+        ));
+    }
+}
@@ -1,25 +1,81 @@
 use {
     crate::errors::{SbpfVmError, SbpfVmResult},
     serde::{Deserialize, Serialize},
+    std::cell::Cell,
 };
 
 /// Memory region
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MemoryRegion {
     Input,
     Rodata,
+    /// Writable program data: `.data` followed by the zero-filled `.bss`.
+    Data,
     Stack,
     Heap,
 }
 
-/// Memory layout
+/// Regions in the order their bytes sit in the arena, which is also their
+/// declaration order, so `region as usize` indexes per-region tables.
+const REGIONS: [MemoryRegion; 5] = [
+    MemoryRegion::Input,
+    MemoryRegion::Rodata,
+    MemoryRegion::Data,
+    MemoryRegion::Stack,
+    MemoryRegion::Heap,
+];
+
+/// A mapped address range and where its bytes sit in the arena.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Mapping {
+    region: MemoryRegion,
+    vm_start: u64,
+    len: u64,
+    arena_start: usize,
+}
+
+impl Default for Mapping {
+    /// A mapping of no bytes, which no address hits.
+    fn default() -> Self {
+        Self {
+            region: MemoryRegion::Rodata,
+            vm_start: 0,
+            len: 0,
+            arena_start: 0,
+        }
+    }
+}
+
+/// Memory layout: every region in one arena, found through a table of
+/// mappings sorted by address. The mapping the last access hit is tried
+/// first, since loads and stores tend to stay in one region.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Memory {
-    pub rodata: Vec<u8>,
-    pub stack: Vec<u8>,
-    pub heap: Vec<u8>,
-    pub input: Vec<u8>,
+    arena: Vec<u8>,
+    /// Where each region starts in the arena and how long it is, in the
+    /// order of [`REGIONS`].
+    extents: [(usize, usize); 5],
+    mappings: Vec<Mapping>,
+    /// The mapping the last access hit.
+    #[serde(skip)]
+    last_hit: Cell<Mapping>,
     pub heap_ptr: usize,
+    /// Bytes of stack in use at the deepest write so far. Frames grow down
+    /// from their top, so a write uses the bytes from it to the top of its
+    /// frame, plus the frames of its callers below.
+    #[serde(default)]
+    pub stack_high_water: usize,
+    /// The program moves r10 itself, so the stack is one frame growing down
+    /// from its top rather than a fixed frame per call.
+    #[serde(default)]
+    dynamic_stack_frames: bool,
+    /// Address writable program data is mapped at in the program region.
+    #[serde(default)]
+    data_start: u64,
+    /// Address and length of each write since the log was last taken, kept
+    /// while tracing.
+    #[serde(skip)]
+    write_log: Option<Vec<(u64, usize)>>,
 }
 
 impl Memory {
@@ -33,173 +89,304 @@ impl Memory {
     pub const STACK_FRAME_SIZE: u64 = 4096; // 4KB
 
     pub fn new(input: Vec<u8>, rodata: Vec<u8>, stack_size: usize, heap_size: usize) -> Self {
-        Self {
+        let mut memory = Self {
+            arena: Vec::new(),
+            extents: [(0, 0); 5],
+            mappings: Vec::new(),
+            last_hit: Cell::default(),
+            heap_ptr: 0,
+            stack_high_water: 0,
+            dynamic_stack_frames: false,
+            data_start: 0,
+            write_log: None,
+        };
+        memory.lay_out([
             input,
             rodata,
-            stack: vec![0u8; stack_size],
-            heap: vec![0u8; heap_size],
-            heap_ptr: 0,
+            Vec::new(),
+            vec![0u8; stack_size],
+            vec![0u8; heap_size],
+        ]);
+        memory
+    }
+
+    /// Map the program's writable data at `start`, the address the ELF loads
+    /// `.data` at. Reads there hit `data` instead of rodata.
+    pub fn map_data(&mut self, start: u64, data: Vec<u8>) {
+        self.data_start = start;
+        self.set_region(MemoryRegion::Data, data);
+    }
+
+    /// Address the writable program data is mapped at.
+    pub fn data_start(&self) -> u64 {
+        self.data_start
+    }
+
+    /// The bytes of `region`.
+    pub fn region(&self, region: MemoryRegion) -> &[u8] {
+        let (start, len) = self.extents[region as usize];
+        &self.arena[start..start + len]
+    }
+
+    /// The bytes of `region`, for writing. This bypasses the read-only check
+    /// on rodata and the stack high-water mark.
+    pub fn region_mut(&mut self, region: MemoryRegion) -> &mut [u8] {
+        let (start, len) = self.extents[region as usize];
+        &mut self.arena[start..start + len]
+    }
+
+    /// Replace the bytes of `region`. The arena is only laid out again when
+    /// the size changes.
+    pub fn set_region(&mut self, region: MemoryRegion, bytes: Vec<u8>) {
+        if bytes.len() == self.extents[region as usize].1 {
+            self.region_mut(region).copy_from_slice(&bytes);
+            return;
         }
+        let mut regions = REGIONS.map(|other| self.region(other).to_vec());
+        regions[region as usize] = bytes;
+        self.lay_out(regions);
     }
 
+    /// Use one frame spanning the whole stack, for versions with dynamic
+    /// stack frames.
+    pub fn set_dynamic_stack_frames(&mut self, enabled: bool) {
+        self.dynamic_stack_frames = enabled;
+    }
+
+    /// The top of the first frame, or of the whole stack with dynamic stack
+    /// frames.
     pub fn initial_frame_pointer(&self) -> u64 {
-        Self::STACK_START + Self::STACK_FRAME_SIZE
+        if self.dynamic_stack_frames {
+            Self::STACK_START + self.extents[MemoryRegion::Stack as usize].1 as u64
+        } else {
+            Self::STACK_START + Self::STACK_FRAME_SIZE
+        }
     }
 
     pub fn stack_size(max_call_depth: usize) -> usize {
         Self::STACK_FRAME_SIZE as usize * max_call_depth
     }
 
-    // Translate virtual address to region and offset
-    fn translate(&self, addr: u64) -> SbpfVmResult<(MemoryRegion, usize)> {
-        if addr >= Self::INPUT_START {
-            let offset = (addr - Self::INPUT_START) as usize;
-            if offset < self.input.len() {
-                Ok((MemoryRegion::Input, offset))
-            } else {
-                Err(SbpfVmError::MemoryOutOfBounds(addr, 0))
-            }
-        } else if addr >= Self::HEAP_START {
-            let offset = (addr - Self::HEAP_START) as usize;
-            if offset < self.heap.len() {
-                Ok((MemoryRegion::Heap, offset))
-            } else {
-                Err(SbpfVmError::MemoryOutOfBounds(addr, 0))
-            }
-        } else if addr >= Self::STACK_START {
-            let offset = (addr - Self::STACK_START) as usize;
-            if offset < self.stack.len() {
-                Ok((MemoryRegion::Stack, offset))
-            } else {
-                Err(SbpfVmError::MemoryOutOfBounds(addr, 0))
-            }
-        } else {
-            let offset = addr as usize;
-            if offset < self.rodata.len() {
-                Ok((MemoryRegion::Rodata, offset))
-            } else {
-                Err(SbpfVmError::MemoryOutOfBounds(addr, 0))
-            }
+    /// Copy the regions, in the order of [`REGIONS`], into a new arena and
+    /// rebuild the mapping table.
+    fn lay_out(&mut self, regions: [Vec<u8>; 5]) {
+        self.arena = Vec::with_capacity(regions.iter().map(Vec::len).sum());
+        for (extent, bytes) in self.extents.iter_mut().zip(&regions) {
+            *extent = (self.arena.len(), bytes.len());
+            self.arena.extend_from_slice(bytes);
         }
-    }
 
-    fn get_slice(&self, region: MemoryRegion, offset: usize, len: usize) -> SbpfVmResult<&[u8]> {
-        let data = match region {
-            MemoryRegion::Input => &self.input,
-            MemoryRegion::Rodata => &self.rodata,
-            MemoryRegion::Stack => &self.stack,
-            MemoryRegion::Heap => &self.heap,
+        let mapping = |region: MemoryRegion, vm_start: u64, skip: usize, len: usize| Mapping {
+            region,
+            vm_start,
+            len: len as u64,
+            arena_start: self.extents[region as usize].0 + skip,
         };
-
-        if offset + len > data.len() {
-            return Err(SbpfVmError::MemoryOutOfBounds(offset as u64, len));
+        let (_, rodata_len) = self.extents[MemoryRegion::Rodata as usize];
+        let (_, data_len) = self.extents[MemoryRegion::Data as usize];
+        let mut mappings = Vec::new();
+        if data_len == 0 {
+            mappings.push(mapping(
+                MemoryRegion::Rodata,
+                Self::RODATA_START,
+                0,
+                rodata_len,
+            ));
+        } else {
+            // Data shadows the part of rodata it overlaps.
+            let data_start = self.data_start as usize;
+            let data_end = data_start + data_len;
+            mappings.push(mapping(
+                MemoryRegion::Rodata,
+                Self::RODATA_START,
+                0,
+                rodata_len.min(data_start),
+            ));
+            mappings.push(mapping(MemoryRegion::Data, self.data_start, 0, data_len));
+            if rodata_len > data_end {
+                mappings.push(mapping(
+                    MemoryRegion::Rodata,
+                    data_end as u64,
+                    data_end,
+                    rodata_len - data_end,
+                ));
+            }
+        }
+        for (region, vm_start) in [
+            (MemoryRegion::Stack, Self::STACK_START),
+            (MemoryRegion::Heap, Self::HEAP_START),
+            (MemoryRegion::Input, Self::INPUT_START),
+        ] {
+            mappings.push(mapping(
+                region,
+                vm_start,
+                0,
+                self.extents[region as usize].1,
+            ));
+        }
+        mappings.retain(|mapping| mapping.len > 0);
+        mappings.sort_by_key(|mapping| mapping.vm_start);
+        // A region never runs into the next one's addresses.
+        for i in 1..mappings.len() {
+            let next_start = mappings[i].vm_start;
+            let previous = &mut mappings[i - 1];
+            previous.len = previous.len.min(next_start - previous.vm_start);
         }
 
-        Ok(&data[offset..offset + len])
+        self.mappings = mappings;
+        self.last_hit.set(Mapping::default());
     }
 
-    fn get_slice_mut(
-        &mut self,
-        region: MemoryRegion,
-        offset: usize,
-        len: usize,
-    ) -> SbpfVmResult<&mut [u8]> {
-        // Rodata region is read-only
-        if region == MemoryRegion::Rodata {
-            return Err(SbpfVmError::InvalidMemoryAccess(
-                Self::RODATA_START + offset as u64,
-            ));
+    /// Arena offset and region of `len` bytes at `addr`, which must all lie
+    /// in one region.
+    #[inline]
+    fn translate(&self, addr: u64, len: usize) -> SbpfVmResult<(usize, MemoryRegion)> {
+        let mut mapping = self.last_hit.get();
+        if addr.wrapping_sub(mapping.vm_start) >= mapping.len {
+            mapping = self.find_mapping(addr)?;
+        }
+        let offset = addr - mapping.vm_start;
+        if len as u64 > mapping.len - offset {
+            return Err(SbpfVmError::MemoryOutOfBounds(addr, len));
         }
+        Ok((mapping.arena_start + offset as usize, mapping.region))
+    }
 
-        let data = match region {
-            MemoryRegion::Input => &mut self.input,
-            MemoryRegion::Stack => &mut self.stack,
-            MemoryRegion::Heap => &mut self.heap,
-            MemoryRegion::Rodata => unreachable!(),
-        };
+    /// The mapping holding `addr`, remembered for the next access. There are
+    /// at most six mappings, so a scan beats a binary search.
+    fn find_mapping(&self, addr: u64) -> SbpfVmResult<Mapping> {
+        for mapping in &self.mappings {
+            if addr.wrapping_sub(mapping.vm_start) < mapping.len {
+                self.last_hit.set(*mapping);
+                return Ok(*mapping);
+            }
+        }
+        Err(SbpfVmError::MemoryOutOfBounds(addr, 0))
+    }
 
-        if offset + len > data.len() {
-            return Err(SbpfVmError::MemoryOutOfBounds(offset as u64, len));
+    /// Arena offset of `len` bytes at `addr`, checked for writing.
+    #[inline]
+    fn translate_mut(&mut self, addr: u64, len: usize) -> SbpfVmResult<usize> {
+        let (start, region) = self.translate(addr, len)?;
+        if let Some(log) = &mut self.write_log
+            && len > 0
+        {
+            log.push((addr, len));
         }
+        match region {
+            MemoryRegion::Rodata => Err(SbpfVmError::InvalidMemoryAccess(addr)),
+            MemoryRegion::Stack => {
+                let (stack_start, stack_len) = self.extents[MemoryRegion::Stack as usize];
+                let offset = start - stack_start;
+                let frame_size = if self.dynamic_stack_frames {
+                    stack_len
+                } else {
+                    Self::STACK_FRAME_SIZE as usize
+                };
+                let frame_start = offset - offset % frame_size;
+                let frame_end = (frame_start + frame_size).min(stack_len);
+                let used = frame_start + (frame_end - offset);
+                self.stack_high_water = self.stack_high_water.max(used);
+                Ok(start)
+            }
+            _ => Ok(start),
+        }
+    }
 
-        Ok(&mut data[offset..offset + len])
+    #[inline]
+    fn read_array<const N: usize>(&self, addr: u64) -> SbpfVmResult<[u8; N]> {
+        let (start, _) = self.translate(addr, N)?;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.arena[start..start + N]);
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn write_array<const N: usize>(&mut self, addr: u64, bytes: [u8; N]) -> SbpfVmResult<()> {
+        let start = self.translate_mut(addr, N)?;
+        self.arena[start..start + N].copy_from_slice(&bytes);
+        Ok(())
     }
 
     pub fn read_u8(&self, addr: u64) -> SbpfVmResult<u8> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice(region, offset, 1)?;
-        Ok(slice[0])
+        Ok(self.read_array::<1>(addr)?[0])
     }
 
     pub fn read_u16(&self, addr: u64) -> SbpfVmResult<u16> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice(region, offset, 2)?;
-        Ok(u16::from_le_bytes([slice[0], slice[1]]))
+        self.read_array(addr).map(u16::from_le_bytes)
     }
 
     pub fn read_u32(&self, addr: u64) -> SbpfVmResult<u32> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice(region, offset, 4)?;
-        Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+        self.read_array(addr).map(u32::from_le_bytes)
     }
 
     pub fn read_u64(&self, addr: u64) -> SbpfVmResult<u64> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice(region, offset, 8)?;
-        Ok(u64::from_le_bytes([
-            slice[0], slice[1], slice[2], slice[3], slice[4], slice[5], slice[6], slice[7],
-        ]))
+        self.read_array(addr).map(u64::from_le_bytes)
     }
 
-    pub fn read_bytes(&self, addr: u64, len: usize) -> SbpfVmResult<&[u8]> {
-        let (region, offset) = self.translate(addr)?;
-        self.get_slice(region, offset, len)
+    /// The `len` bytes at `addr`, which must lie in one region.
+    pub fn read_slice(&self, addr: u64, len: usize) -> SbpfVmResult<&[u8]> {
+        let (start, _) = self.translate(addr, len)?;
+        Ok(&self.arena[start..start + len])
     }
 
-    pub fn write_u8(&mut self, addr: u64, value: u8) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, 1)?;
-        slice[0] = value;
+    /// The `len` bytes at `addr`, to be written in place. They must lie in
+    /// one writable region, so not in rodata.
+    pub fn write_slice(&mut self, addr: u64, len: usize) -> SbpfVmResult<&mut [u8]> {
+        let start = self.translate_mut(addr, len)?;
+        Ok(&mut self.arena[start..start + len])
+    }
+
+    /// Copy `len` bytes from `src` to `dst`. The ranges may overlap, as with
+    /// `memmove`.
+    pub fn copy(&mut self, dst: u64, src: u64, len: usize) -> SbpfVmResult<()> {
+        let (from, _) = self.translate(src, len)?;
+        let to = self.translate_mut(dst, len)?;
+        self.arena.copy_within(from..from + len, to);
         Ok(())
     }
 
+    pub fn write_u8(&mut self, addr: u64, value: u8) -> SbpfVmResult<()> {
+        self.write_array(addr, [value])
+    }
+
     pub fn write_u16(&mut self, addr: u64, value: u16) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, 2)?;
-        slice.copy_from_slice(&value.to_le_bytes());
-        Ok(())
+        self.write_array(addr, value.to_le_bytes())
     }
 
     pub fn write_u32(&mut self, addr: u64, value: u32) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, 4)?;
-        slice.copy_from_slice(&value.to_le_bytes());
-        Ok(())
+        self.write_array(addr, value.to_le_bytes())
     }
 
     pub fn write_u64(&mut self, addr: u64, value: u64) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, 8)?;
-        slice.copy_from_slice(&value.to_le_bytes());
-        Ok(())
+        self.write_array(addr, value.to_le_bytes())
     }
 
     pub fn write_i64(&mut self, addr: u64, value: i64) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, 8)?;
-        slice.copy_from_slice(&value.to_le_bytes());
-        Ok(())
+        self.write_array(addr, value.to_le_bytes())
     }
 
     pub fn write_bytes(&mut self, addr: u64, bytes: &[u8]) -> SbpfVmResult<()> {
-        let (region, offset) = self.translate(addr)?;
-        let slice = self.get_slice_mut(region, offset, bytes.len())?;
-        slice.copy_from_slice(bytes);
+        self.write_slice(addr, bytes.len())?.copy_from_slice(bytes);
         Ok(())
     }
 
+    /// Start or stop logging where memory is written.
+    pub fn log_writes(&mut self, enabled: bool) {
+        self.write_log = enabled.then(Vec::new);
+    }
+
+    /// The address and length of each write since logging started or this
+    /// was last called.
+    pub fn take_writes(&mut self) -> Vec<(u64, usize)> {
+        self.write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     pub fn alloc(&mut self, size: usize) -> SbpfVmResult<u64> {
-        if self.heap_ptr + size > self.heap.len() {
+        if self.heap_ptr + size > self.region(MemoryRegion::Heap).len() {
             return Err(SbpfVmError::MemoryOutOfBounds(
                 Self::HEAP_START + self.heap_ptr as u64,
                 size,
@@ -212,7 +399,7 @@ impl Memory {
 
     pub fn reset_heap(&mut self) {
         self.heap_ptr = 0;
-        self.heap.fill(0);
+        self.region_mut(MemoryRegion::Heap).fill(0);
     }
 }
 
@@ -234,6 +421,20 @@ mod tests {
         assert_eq!(memory.read_u8(Memory::RODATA_START + 3).unwrap(), 8);
     }
 
+    #[test]
+    fn test_data_region() {
+        let mut memory = Memory::new(vec![], vec![5, 6, 7, 8], 1024, 1024);
+        memory.map_data(8, vec![0; 16]);
+
+        memory.write_u64(8, 41).unwrap();
+        assert_eq!(memory.read_u64(8).unwrap(), 41);
+        memory.write_u8(23, 1).unwrap();
+        // Rodata stays read-only, and the data region ends where it ends.
+        assert_eq!(memory.read_u8(Memory::RODATA_START).unwrap(), 5);
+        assert!(memory.write_u8(Memory::RODATA_START, 1).is_err());
+        assert!(memory.write_u8(24, 1).is_err());
+    }
+
     #[test]
     fn test_read_write() {
         let mut memory = Memory::new(
@@ -288,4 +489,90 @@ mod tests {
         let result = memory.write_u8(Memory::RODATA_START, 12);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_region_table() {
+        let mut memory = Memory::new(vec![1; 8], (0..32).collect(), 1024, 1024);
+        // Data in the middle of rodata splits it in two.
+        memory.map_data(8, vec![0xaa; 8]);
+        assert_eq!(memory.read_u8(7).unwrap(), 7);
+        assert_eq!(memory.read_u8(8).unwrap(), 0xaa);
+        assert_eq!(memory.read_u8(16).unwrap(), 16);
+        assert!(memory.write_u8(16, 0).is_err());
+        // An access must fit in one region, whichever was hit last.
+        assert!(memory.read_u64(12).is_err());
+        assert!(memory.read_u64(Memory::INPUT_START + 1).is_err());
+        assert!(memory.read_slice(Memory::INPUT_START, usize::MAX).is_err());
+        assert!(memory.read_u8(Memory::STACK_START + 1024).is_err());
+
+        memory.write_u64(Memory::INPUT_START, 2).unwrap();
+        assert_eq!(memory.region(MemoryRegion::Input), [2, 0, 0, 0, 0, 0, 0, 0]);
+        memory.set_region(MemoryRegion::Input, vec![3; 16]);
+        assert_eq!(memory.read_u8(Memory::INPUT_START + 15).unwrap(), 3);
+        assert_eq!(memory.read_u8(8).unwrap(), 0xaa);
+        assert_eq!(memory.region(MemoryRegion::Stack).len(), 1024);
+
+        // Measured down from the top of the 1024-byte stack.
+        memory.write_u64(Memory::STACK_START + 1000, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 24);
+        memory.write_u8(Memory::STACK_START + 1020, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 24);
+    }
+
+    #[test]
+    fn test_stack_high_water() {
+        let mut memory = Memory::new(vec![], vec![], Memory::stack_size(2), 0);
+        memory.write_u8(Memory::STACK_START + 4095, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 1);
+        // The first byte below the frame pointer of a call counts the
+        // caller's whole frame too.
+        memory.write_u8(Memory::STACK_START + 8191, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 4097);
+        memory.write_u64(Memory::STACK_START, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 4097);
+
+        // With dynamic frames the whole stack is one frame below r10.
+        let mut memory = Memory::new(vec![], vec![], Memory::stack_size(2), 0);
+        memory.set_dynamic_stack_frames(true);
+        assert_eq!(memory.initial_frame_pointer(), Memory::STACK_START + 8192);
+        memory.write_u8(Memory::STACK_START + 8191, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 1);
+        memory.write_u8(Memory::STACK_START + 4095, 1).unwrap();
+        assert_eq!(memory.stack_high_water, 4097);
+    }
+
+    #[test]
+    fn test_slices() {
+        let mut memory = Memory::new(vec![0; 16], vec![1, 2, 3, 4], 1024, 1024);
+
+        memory
+            .write_slice(Memory::INPUT_START + 4, 4)
+            .unwrap()
+            .fill(7);
+        assert_eq!(
+            memory.read_slice(Memory::INPUT_START, 10).unwrap(),
+            [0, 0, 0, 0, 7, 7, 7, 7, 0, 0]
+        );
+        // Overlapping copies behave like memmove.
+        memory
+            .copy(Memory::INPUT_START + 6, Memory::INPUT_START + 4, 4)
+            .unwrap();
+        assert_eq!(
+            memory.read_slice(Memory::INPUT_START + 4, 6).unwrap(),
+            [7, 7, 7, 7, 7, 7]
+        );
+        memory
+            .copy(Memory::STACK_START, Memory::RODATA_START, 4)
+            .unwrap();
+        assert_eq!(memory.read_u32(Memory::STACK_START).unwrap(), 0x04030201);
+        assert_eq!(memory.stack_high_water, 1024);
+
+        assert!(memory.write_slice(Memory::RODATA_START, 1).is_err());
+        assert!(
+            memory
+                .copy(Memory::RODATA_START, Memory::INPUT_START, 1)
+                .is_err()
+        );
+        assert!(memory.read_slice(Memory::INPUT_START + 8, 9).is_err());
+    }
 }
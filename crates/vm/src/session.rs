@@ -0,0 +1,254 @@
+use {
+    crate::{
+        errors::SbpfVmResult,
+        loader::{LoadedProgram, instruction_offsets, load_elf},
+        solana_syscalls::SolanaSyscalls,
+        vm::SbpfVm,
+    },
+    sbpf_common::instruction::AsmFormat,
+    sbpf_disassembler::line_table::{LineTable, SourceLine},
+    serde::{Serialize, Serializer, ser::SerializeSeq},
+};
+
+/// A program loaded to be stepped through one instruction at a time, the
+/// way a visual debugger drives it. Every call that moves the program on
+/// returns a [`SessionState`] to render.
+pub struct Session {
+    program: LoadedProgram,
+    input: Vec<u8>,
+    vm: SbpfVm<SolanaSyscalls>,
+    /// `.text` byte offset of each instruction.
+    offsets: Vec<u64>,
+    /// Source lines, when the program was built with debug info.
+    lines: Option<LineTable>,
+    /// The error the program stopped on, if it faulted.
+    fault: Option<String>,
+}
+
+/// Whether a session can step further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    Paused,
+    Exited,
+    Faulted,
+}
+
+/// Everything a debugger view shows after a step, serializable to JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionState {
+    pub status: SessionStatus,
+    /// Index of the next instruction to run.
+    pub pc: usize,
+    /// `.text` byte offset of the next instruction.
+    pub offset: u64,
+    /// The next instruction as assembly.
+    pub instruction: Option<String>,
+    /// Serialized as `0x` hex strings, as JavaScript numbers can't hold
+    /// every `u64`.
+    #[serde(serialize_with = "hex_registers")]
+    pub registers: [u64; 11],
+    pub compute_units: u64,
+    pub call_depth: usize,
+    /// Where the next instruction was written.
+    pub source: Option<SourceLine>,
+    pub exit_code: Option<u64>,
+    pub error: Option<String>,
+    /// Program logs since the session was loaded or reset.
+    pub logs: Vec<String>,
+}
+
+impl Session {
+    /// Load an ELF to run from its entrypoint with `input` in the input
+    /// region. Source lines are read from its debug info, if any.
+    pub fn load(elf_bytes: &[u8], input: Vec<u8>) -> SbpfVmResult<Self> {
+        let program = load_elf(elf_bytes)?;
+        let vm = program
+            .clone()
+            .into_vm(input.clone(), SolanaSyscalls::default());
+        Ok(Self {
+            offsets: instruction_offsets(&program.instructions),
+            program,
+            input,
+            vm,
+            lines: LineTable::from_elf_bytes(elf_bytes),
+            fault: None,
+        })
+    }
+
+    /// Start again from the entrypoint with fresh memory.
+    pub fn reset(&mut self) {
+        self.vm = self
+            .program
+            .clone()
+            .into_vm(self.input.clone(), SolanaSyscalls::default());
+        self.fault = None;
+    }
+
+    /// Run one instruction. Does nothing once the program has exited or
+    /// faulted.
+    pub fn step(&mut self) -> SessionState {
+        self.advance();
+        self.state()
+    }
+
+    /// Step until the program exits or faults, or the next instruction is
+    /// at one of the `.text` byte offsets in `breakpoints`. At least one
+    /// instruction runs, so continuing from a breakpoint moves past it.
+    pub fn run(&mut self, breakpoints: &[u64]) -> SessionState {
+        while self.advance() && !breakpoints.contains(&self.offset()) {}
+        self.state()
+    }
+
+    /// Step once, returning whether the program can step further.
+    fn advance(&mut self) -> bool {
+        if self.vm.halted || self.fault.is_some() {
+            return false;
+        }
+        if let Err(e) = self.vm.step() {
+            self.fault = Some(e.to_string());
+            return false;
+        }
+        !self.vm.halted
+    }
+
+    pub fn registers(&self) -> [u64; 11] {
+        self.vm.registers
+    }
+
+    /// `len` bytes of VM memory from virtual address `address`.
+    pub fn memory(&self, address: u64, len: usize) -> SbpfVmResult<&[u8]> {
+        self.vm.memory.read_slice(address, len)
+    }
+
+    /// Where the next instruction was written.
+    pub fn source_line(&self) -> Option<&SourceLine> {
+        self.lines.as_ref()?.locate(self.offset())
+    }
+
+    fn offset(&self) -> u64 {
+        self.offsets.get(self.vm.pc).copied().unwrap_or_default()
+    }
+
+    pub fn state(&self) -> SessionState {
+        let status = if self.fault.is_some() {
+            SessionStatus::Faulted
+        } else if self.vm.halted {
+            SessionStatus::Exited
+        } else {
+            SessionStatus::Paused
+        };
+        SessionState {
+            status,
+            pc: self.vm.pc,
+            offset: self.offset(),
+            instruction: self
+                .vm
+                .program
+                .get(self.vm.pc)
+                .and_then(|instruction| instruction.to_asm(AsmFormat::Default).ok()),
+            registers: self.vm.registers,
+            compute_units: self.vm.compute_meter.get_consumed(),
+            call_depth: self.vm.call_stack.len(),
+            source: self.source_line().cloned(),
+            exit_code: self.vm.exit_code,
+            error: self.fault.clone(),
+            logs: self.vm.syscall_handler.logs.clone(),
+        }
+    }
+
+    pub fn vm(&self) -> &SbpfVm<SolanaSyscalls> {
+        &self.vm
+    }
+}
+
+fn hex_registers<S: Serializer>(registers: &[u64; 11], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(registers.len()))?;
+    for value in registers {
+        seq.serialize_element(&format!("{:#x}", value))?;
+    }
+    seq.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+    };
+
+    const SOURCE: &str = r#".globl entrypoint
+entrypoint:
+  lddw r1, message
+  mov64 r2, 2
+  call sol_log_
+  ldxb r0, [r1+0]
+  call helper
+  exit
+helper:
+  add64 r0, 1
+  exit
+.rodata
+message: .ascii "hi"
+"#;
+
+    fn session() -> Session {
+        let options = AssemblerOption::default().with_debug_mode(DebugMode {
+            filename: "prog.s".to_string(),
+            directory: String::new(),
+        });
+        let elf = Assembler::new(options).assemble(SOURCE).unwrap();
+        Session::load(&elf, Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn test_step_through_program() {
+        let mut session = session();
+        let state = session.state();
+        assert_eq!(state.status, SessionStatus::Paused);
+        assert_eq!(state.instruction.as_deref(), Some("lddw r1, 0x0"));
+        assert_eq!(state.source.as_ref().map(|source| source.line), Some(3));
+
+        let state = session.step();
+        assert_eq!((state.pc, state.offset), (1, 16));
+        assert_eq!(state.source.as_ref().map(|source| source.line), Some(4));
+        assert_eq!(session.memory(state.registers[1], 2).unwrap(), b"hi");
+
+        // Stop on entering the helper, then run to the end.
+        let state = session.run(&[56]);
+        assert_eq!((state.offset, state.call_depth), (56, 1));
+        assert_eq!(state.logs, ["Program log: hi"]);
+        let state = session.run(&[]);
+        assert_eq!(state.status, SessionStatus::Exited);
+        assert_eq!(state.exit_code, Some(b'h' as u64 + 1));
+        assert_eq!(session.step(), state);
+
+        session.reset();
+        assert_eq!(session.state().pc, 0);
+        assert!(session.state().logs.is_empty());
+    }
+
+    #[test]
+    fn test_state_json() {
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(".globl e\ne:\n  lddw r0, -1\n  ldxb r0, [r0+0]\n  exit\n")
+            .unwrap();
+        let mut session = Session::load(&elf, Vec::new()).unwrap();
+        session.step();
+        let state = session.step();
+        assert_eq!(state.status, SessionStatus::Faulted);
+        assert!(state.source.is_none());
+
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["status"], "faulted");
+        assert_eq!(json["registers"][0], "0xffffffffffffffff");
+        assert_eq!(json["callDepth"], 0);
+        assert!(
+            json["error"]
+                .as_str()
+                .unwrap()
+                .contains("0xffffffffffffffff")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use {
+    crate::memory::{Memory, MemoryRegion},
+    serde::{Deserialize, Serialize},
+    std::fmt,
+};
+
+/// Unchanged bytes allowed between two changes before they are reported as
+/// separate ranges.
+const MERGE_GAP: usize = 8;
+
+/// Writable VM state at the end of a run, saved so another run can be compared
+/// against it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub pc: usize,
+    pub registers: [u64; 11],
+    pub exit_code: Option<u64>,
+    pub compute_units: u64,
+    pub input: Vec<u8>,
+    pub stack: Vec<u8>,
+    pub heap: Vec<u8>,
+    /// Bytes of stack in use at the deepest write, measured down from the
+    /// top of the frame it was in.
+    pub stack_high_water: usize,
+}
+
+/// A contiguous range of memory that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRange {
+    pub region: MemoryRegion,
+    /// Virtual address of the first byte.
+    pub address: u64,
+    /// Bytes in the baseline, shorter than `after` if the region grew.
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// Differences between a baseline snapshot and a later run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Registers that differ, as `(register, before, after)`.
+    pub registers: Vec<(usize, u64, u64)>,
+    pub exit_code: (Option<u64>, Option<u64>),
+    pub compute_units: (u64, u64),
+    pub stack_high_water: (usize, usize),
+    pub ranges: Vec<ChangedRange>,
+}
+
+impl Snapshot {
+    /// Compare `self` against `baseline`.
+    pub fn diff(&self, baseline: &Snapshot) -> SnapshotDiff {
+        let registers = baseline
+            .registers
+            .iter()
+            .zip(&self.registers)
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(reg, (before, after))| (reg, *before, *after))
+            .collect();
+
+        let mut ranges = Vec::new();
+        for (region, start, before, after) in [
+            (
+                MemoryRegion::Input,
+                Memory::INPUT_START,
+                &baseline.input,
+                &self.input,
+            ),
+            (
+                MemoryRegion::Stack,
+                Memory::STACK_START,
+                &baseline.stack,
+                &self.stack,
+            ),
+            (
+                MemoryRegion::Heap,
+                Memory::HEAP_START,
+                &baseline.heap,
+                &self.heap,
+            ),
+        ] {
+            ranges.extend(changed_ranges(before, after).into_iter().map(|(from, to)| {
+                ChangedRange {
+                    region,
+                    address: start + from as u64,
+                    before: before[from.min(before.len())..to.min(before.len())].to_vec(),
+                    after: after[from.min(after.len())..to.min(after.len())].to_vec(),
+                }
+            }));
+        }
+
+        SnapshotDiff {
+            registers,
+            exit_code: (baseline.exit_code, self.exit_code),
+            compute_units: (baseline.compute_units, self.compute_units),
+            stack_high_water: (baseline.stack_high_water, self.stack_high_water),
+            ranges,
+        }
+    }
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+            && self.exit_code.0 == self.exit_code.1
+            && self.compute_units.0 == self.compute_units.1
+            && self.stack_high_water.0 == self.stack_high_water.1
+            && self.ranges.is_empty()
+    }
+}
+
+/// Byte ranges where `before` and `after` differ, including bytes present in
+/// only one of them. Changes closer than [`MERGE_GAP`] are merged.
+fn changed_ranges(before: &[u8], after: &[u8]) -> Vec<(usize, usize)> {
+    let len = before.len().max(after.len());
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in (0..len).filter(|&i| before.get(i) != after.get(i)) {
+        match ranges.last_mut() {
+            Some((_, end)) if i - *end < MERGE_GAP => *end = i + 1,
+            _ => ranges.push((i, i + 1)),
+        }
+    }
+    ranges
+}
+
+impl fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MemoryRegion::Input => "input",
+            MemoryRegion::Rodata => "rodata",
+            MemoryRegion::Data => "data",
+            MemoryRegion::Stack => "stack",
+            MemoryRegion::Heap => "heap",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        if self.exit_code.0 != self.exit_code.1 {
+            writeln!(
+                f,
+                "exit code: {:?} -> {:?}",
+                self.exit_code.0, self.exit_code.1
+            )?;
+        }
+        if self.compute_units.0 != self.compute_units.1 {
+            writeln!(
+                f,
+                "compute units: {} -> {}",
+                self.compute_units.0, self.compute_units.1
+            )?;
+        }
+        if self.stack_high_water.0 != self.stack_high_water.1 {
+            writeln!(
+                f,
+                "stack high-water mark: {} -> {} bytes",
+                self.stack_high_water.0, self.stack_high_water.1
+            )?;
+        }
+        for (reg, before, after) in &self.registers {
+            writeln!(f, "r{}: {:#x} -> {:#x}", reg, before, after)?;
+        }
+        for range in &self.ranges {
+            let len = range.before.len().max(range.after.len());
+            writeln!(
+                f,
+                "{} {:#x}..{:#x} ({} byte{})",
+                range.region,
+                range.address,
+                range.address + len as u64,
+                len,
+                if len == 1 { "" } else { "s" }
+            )?;
+            writeln!(f, "  - {}", hex(&range.before))?;
+            writeln!(f, "  + {}", hex(&range.after))?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(input: Vec<u8>) -> Snapshot {
+        Snapshot {
+            pc: 0,
+            registers: [0; 11],
+            exit_code: Some(0),
+            compute_units: 10,
+            input,
+            stack: vec![0; 32],
+            heap: vec![0; 16],
+            stack_high_water: 0,
+        }
+    }
+
+    #[test]
+    fn test_identical_snapshots() {
+        let a = snapshot(vec![1, 2, 3]);
+        let diff = a.diff(&a);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn test_changed_ranges_merge_nearby_bytes() {
+        let before = [0u8; 32];
+        let mut after = before;
+        after[1] = 1;
+        after[4] = 1;
+        after[20] = 1;
+        assert_eq!(changed_ranges(&before, &after), vec![(1, 5), (20, 21)]);
+        // Bytes only present on one side count as changed.
+        assert_eq!(changed_ranges(&[1, 2], &[1, 2, 3]), vec![(2, 3)]);
+    }
+
+    #[test]
+    fn test_diff_report() {
+        let baseline = snapshot(vec![0; 4]);
+        let mut run = snapshot(vec![0, 0, 7, 0, 9]);
+        run.registers[0] = 1;
+        run.compute_units = 12;
+        run.stack[24] = 0xff;
+        run.stack_high_water = 32;
+
+        let diff = run.diff(&baseline);
+        assert_eq!(diff.registers, vec![(0, 0, 1)]);
+        assert_eq!(diff.ranges.len(), 2);
+        assert_eq!(
+            diff.to_string(),
+            "compute units: 10 -> 12\n\
+             stack high-water mark: 0 -> 32 bytes\n\
+             r0: 0x0 -> 0x1\n\
+             input 0x400000002..0x400000005 (3 bytes)\n  - 00 00\n  + 07 00 09\n\
+             stack 0x200000018..0x200000019 (1 byte)\n  - 00\n  + ff\n"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use {
+    crate::{
+        compute::ComputeMeter,
+        errors::{SbpfVmError, SbpfVmResult},
+        memory::Memory,
+        syscalls::SyscallHandler,
+    },
+    sha2::Sha256,
+    sha3::Keccak256,
+    std::collections::HashMap,
+    syscall_map::{StaticSyscallMap, murmur3_32, static_syscall_map},
+};
+
+/// A syscall implementation, given r1-r5 and returning the value for r0.
+pub type SyscallFn = fn(&mut SolanaSyscalls, [u64; 5], &mut Memory) -> SbpfVmResult<u64>;
+
+/// The syscalls implemented in this module, laid out at compile time.
+pub static SOLANA_SYSCALLS: StaticSyscallMap<SyscallFn> = static_syscall_map! {
+    "sol_log_" => sol_log,
+    "sol_log_64_" => sol_log_64,
+    "sol_memcpy_" => sol_memcpy,
+    "sol_memset_" => sol_memset,
+    "sol_memcmp_" => sol_memcmp,
+    "sol_sha256" => sol_sha256,
+    "sol_keccak256" => sol_keccak256,
+};
+
+/// Syscall implementations keyed by the murmur3 hash of their name, the
+/// same key the loader uses to resolve `call` immediates. A static table of
+/// built-in syscalls is consulted after any registered at runtime.
+#[derive(Clone)]
+pub struct SyscallRegistry {
+    builtins: Option<&'static StaticSyscallMap<SyscallFn>>,
+    functions: HashMap<u32, (&'static str, SyscallFn)>,
+}
+
+impl SyscallRegistry {
+    /// A registry with no syscalls.
+    pub fn empty() -> Self {
+        Self {
+            builtins: None,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// A registry of the syscalls in `builtins`, as built by
+    /// [`static_syscall_map!`].
+    pub fn from_static(builtins: &'static StaticSyscallMap<SyscallFn>) -> Self {
+        Self {
+            builtins: Some(builtins),
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Register `function` as `name`, replacing any earlier implementation.
+    pub fn register(&mut self, name: &'static str, function: SyscallFn) {
+        self.functions.insert(murmur3_32(name), (name, function));
+    }
+
+    pub fn get(&self, name: &str) -> Option<SyscallFn> {
+        self.get_by_hash(murmur3_32(name))
+    }
+
+    pub fn get_by_hash(&self, hash: u32) -> Option<SyscallFn> {
+        if let Some((_, function)) = self.functions.get(&hash) {
+            return Some(*function);
+        }
+        self.builtins
+            .and_then(|builtins| builtins.get(hash).copied())
+    }
+
+    /// Names of the registered syscalls, sorted.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.functions.values().map(|(name, _)| *name).collect();
+        if let Some(builtins) = self.builtins {
+            names.extend(builtins.iter().map(|(name, _)| name));
+        }
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+impl Default for SyscallRegistry {
+    /// The syscalls implemented in this module.
+    fn default() -> Self {
+        Self::from_static(&SOLANA_SYSCALLS)
+    }
+}
+
+impl std::fmt::Debug for SyscallRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// A syscall handler running the common Solana syscalls against VM memory,
+/// so programs can run without a validator. Log output is collected and any
+/// syscall missing from the registry fails. Compute units are left to the
+/// VM's cost model.
+#[derive(Debug, Default, Clone)]
+pub struct SolanaSyscalls {
+    pub logs: Vec<String>,
+    pub registry: SyscallRegistry,
+}
+
+impl SyscallHandler for SolanaSyscalls {
+    fn handle(
+        &mut self,
+        name: &str,
+        registers: [u64; 5],
+        memory: &mut Memory,
+        _compute: ComputeMeter,
+    ) -> SbpfVmResult<u64> {
+        let function = self.registry.get(name).ok_or_else(|| {
+            SbpfVmError::SyscallError(format!("'{}' is not available in the local VM", name))
+        })?;
+        function(self, registers, memory)
+    }
+}
+
+pub fn sol_log(
+    syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    let bytes = memory.read_slice(registers[0], registers[1] as usize)?;
+    syscalls
+        .logs
+        .push(format!("Program log: {}", String::from_utf8_lossy(bytes)));
+    Ok(0)
+}
+
+pub fn sol_log_64(
+    syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    _memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    let values: Vec<String> = registers.iter().map(|r| format!("{:#x}", r)).collect();
+    syscalls
+        .logs
+        .push(format!("Program log: {}", values.join(", ")));
+    Ok(0)
+}
+
+pub fn sol_memcpy(
+    _syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    let [dst, src, n, ..] = registers;
+    let overlapping = if src > dst {
+        src - dst < n
+    } else {
+        dst - src < n
+    };
+    if overlapping {
+        return Err(SbpfVmError::OverlappingMemoryRegions);
+    }
+    memory.copy(dst, src, n as usize)?;
+    Ok(0)
+}
+
+pub fn sol_memset(
+    _syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    let [dst, c, n, ..] = registers;
+    memory.write_slice(dst, n as usize)?.fill(c as u8);
+    Ok(0)
+}
+
+/// Compares like `memcmp`, writing the difference of the first unequal
+/// bytes as an i32 to r4.
+pub fn sol_memcmp(
+    _syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    let [s1, s2, n, result_ptr, _] = registers;
+    let s1 = memory.read_slice(s1, n as usize)?;
+    let s2 = memory.read_slice(s2, n as usize)?;
+    let result = s1
+        .iter()
+        .zip(s2)
+        .find(|(a, b)| a != b)
+        .map_or(0, |(a, b)| *a as i32 - *b as i32);
+    memory.write_u32(result_ptr, result as u32)?;
+    Ok(0)
+}
+
+pub fn sol_sha256(
+    _syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    hash_slices::<Sha256>(registers, memory)
+}
+
+pub fn sol_keccak256(
+    _syscalls: &mut SolanaSyscalls,
+    registers: [u64; 5],
+    memory: &mut Memory,
+) -> SbpfVmResult<u64> {
+    hash_slices::<Keccak256>(registers, memory)
+}
+
+/// The most slices a single hash syscall accepts.
+const MAX_HASH_SLICES: u64 = 20_000;
+
+/// Hash the `(ptr, len)` slices listed at r1, r2 of them, into the 32 bytes
+/// at r3.
+fn hash_slices<D: sha2::Digest>(registers: [u64; 5], memory: &mut Memory) -> SbpfVmResult<u64> {
+    let [vals_addr, vals_len, result_addr, ..] = registers;
+    if vals_len > MAX_HASH_SLICES {
+        return Err(SbpfVmError::TooManySlices);
+    }
+    let mut hasher = D::new();
+    for i in 0..vals_len {
+        let slice = vals_addr.saturating_add(i * 16);
+        let ptr = memory.read_u64(slice)?;
+        let len = memory.read_u64(slice.saturating_add(8))?;
+        hasher.update(memory.read_slice(ptr, len as usize)?);
+    }
+    memory.write_bytes(result_addr, &hasher.finalize())?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{loader::load_elf, vm::SbpfVm},
+        sbpf_assembler::{Assembler, AssemblerOption},
+    };
+
+    fn run(source: &str) -> SbpfVm<SolanaSyscalls> {
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        let mut vm = load_elf(&elf)
+            .unwrap()
+            .into_vm(Vec::new(), SolanaSyscalls::default());
+        vm.run().unwrap();
+        vm
+    }
+
+    #[test]
+    fn test_log() {
+        let vm = run(r#".globl entrypoint
+entrypoint:
+  lddw r1, message
+  mov64 r2, 5
+  call sol_log_
+  mov64 r1, 1
+  mov64 r5, 255
+  call sol_log_64_
+  exit
+.rodata
+message: .ascii "hello"
+"#);
+        assert_eq!(
+            vm.syscall_handler.logs,
+            vec![
+                "Program log: hello",
+                "Program log: 0x1, 0x5, 0x0, 0x0, 0xff"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memory_syscalls() {
+        // Fill 8 bytes on the stack, copy them next to it and compare.
+        let vm = run(r#".globl entrypoint
+entrypoint:
+  mov64 r1, r10
+  sub64 r1, 32
+  mov64 r2, 7
+  mov64 r3, 8
+  call sol_memset_
+  mov64 r1, r10
+  sub64 r1, 24
+  mov64 r2, r10
+  sub64 r2, 32
+  mov64 r3, 8
+  call sol_memcpy_
+  stb [r10-17], 9
+  mov64 r1, r10
+  sub64 r1, 24
+  mov64 r2, r10
+  sub64 r2, 32
+  mov64 r3, 8
+  mov64 r4, r10
+  sub64 r4, 8
+  call sol_memcmp_
+  ldxw r0, [r10-8]
+  ldxb r1, [r10-24]
+  lsh64 r1, 8
+  add64 r0, r1
+  exit
+"#);
+        // The copy differs in its last byte, 9 against 7, and starts with
+        // a byte of the fill.
+        assert_eq!(vm.exit_code, Some(2 + (7 << 8)));
+    }
+
+    #[test]
+    fn test_sha256() {
+        let vm = run(r#".globl entrypoint
+entrypoint:
+  lddw r1, message
+  stxdw [r10-16], r1
+  mov64 r1, 3
+  stxdw [r10-8], r1
+  mov64 r1, r10
+  sub64 r1, 16
+  mov64 r2, 1
+  mov64 r3, r10
+  sub64 r3, 48
+  call sol_sha256
+  exit
+.rodata
+message: .ascii "abc"
+"#);
+        let result = vm.memory.initial_frame_pointer() - 48;
+        assert_eq!(
+            vm.memory.read_slice(result, 32).unwrap(),
+            <Sha256 as sha2::Digest>::digest(b"abc").as_slice()
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = SyscallRegistry::default();
+        assert!(registry.get("sol_keccak256").is_some());
+        assert!(registry.get_by_hash(murmur3_32("sol_memcmp_")).is_some());
+        assert!(registry.get("sol_invoke_signed_c").is_none());
+        assert_eq!(registry.names().len(), SOLANA_SYSCALLS.len());
+
+        let mut overridden = registry.clone();
+        overridden.register("sol_memcmp_", sol_log_64);
+        assert_eq!(
+            overridden
+                .get("sol_memcmp_")
+                .map(|function| function as usize),
+            Some(sol_log_64 as SyscallFn as usize)
+        );
+        assert_eq!(overridden.names().len(), SOLANA_SYSCALLS.len());
+
+        let mut syscalls = SolanaSyscalls::default();
+        let mut memory = Memory::new(vec![], vec![], 4096, 1024);
+        assert!(matches!(
+            syscalls.handle(
+                "sol_invoke_signed_c",
+                [0; 5],
+                &mut memory,
+                ComputeMeter::new(0)
+            ),
+            Err(SbpfVmError::SyscallError(_))
+        ));
+    }
+}
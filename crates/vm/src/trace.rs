@@ -0,0 +1,308 @@
+use {
+    crate::snapshot::hex,
+    either::Either,
+    sbpf_common::instruction::Instruction,
+    serde::{Serialize, Serializer},
+    std::{
+        fmt::{self, Write as _},
+        io::{self, Write},
+        sync::mpsc::Sender,
+    },
+};
+
+/// Bytes of a memory write shown in a text trace before it's cut short.
+const TEXT_WRITE_BYTES: usize = 16;
+
+/// Receives a [`TraceEvent`] for every instruction the VM steps through
+/// once tracing is enabled with [`crate::vm::SbpfVm::enable_tracing`].
+pub trait TraceSink {
+    fn record(&mut self, event: &TraceEvent);
+
+    /// Flush buffered output, reporting any error recording hit.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What one instruction did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceEvent {
+    /// Index of the instruction.
+    pub pc: usize,
+    pub opcode: &'static str,
+    /// The instruction as assembly.
+    pub instruction: String,
+    /// Operand values before the instruction ran.
+    pub operands: Operands,
+    /// Registers the instruction changed.
+    pub registers: Vec<RegisterChange>,
+    /// Memory the instruction, or the syscall it made, wrote.
+    pub memory_writes: Vec<MemoryWrite>,
+    pub compute_units: u64,
+    /// The error the instruction failed with, if it did.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Operands {
+    pub dst: Option<RegisterValue>,
+    pub src: Option<RegisterValue>,
+    pub off: Option<i16>,
+    pub imm: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegisterValue {
+    pub register: u8,
+    pub value: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegisterChange {
+    pub register: u8,
+    pub before: u64,
+    pub after: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemoryWrite {
+    pub address: u64,
+    /// Serialized as a hex string.
+    #[serde(serialize_with = "hex_bytes")]
+    pub bytes: Vec<u8>,
+}
+
+impl Operands {
+    /// The operands of `instruction` with the values `registers` held.
+    /// Offsets and immediates still naming symbols are left out.
+    pub fn new(instruction: &Instruction, registers: &[u64; 11]) -> Self {
+        let value = |register: u8| RegisterValue {
+            register,
+            value: registers
+                .get(register as usize)
+                .copied()
+                .unwrap_or_default(),
+        };
+        Self {
+            dst: instruction.dst.as_ref().map(|register| value(register.n)),
+            src: instruction.src.as_ref().map(|register| value(register.n)),
+            off: match instruction.off {
+                Some(Either::Right(off)) => Some(off),
+                _ => None,
+            },
+            imm: match &instruction.imm {
+                Some(Either::Right(imm)) => Some(imm.to_i64()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl RegisterChange {
+    /// The registers that differ between `before` and `after`.
+    pub fn diff(before: &[u64; 11], after: &[u64; 11]) -> Vec<Self> {
+        (0..before.len())
+            .filter(|&register| before[register] != after[register])
+            .map(|register| Self {
+                register: register as u8,
+                before: before[register],
+                after: after[register],
+            })
+            .collect()
+    }
+}
+
+/// One line: the instruction, the registers it read, then what it changed.
+/// Compute units are shown for instructions that cost more than one.
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = format!("{:>6}  {:<28}", self.pc, self.instruction);
+        let inputs = [self.operands.dst, self.operands.src];
+        for input in inputs.iter().flatten() {
+            write!(line, " r{}={:#x}", input.register, input.value)?;
+        }
+        for change in &self.registers {
+            write!(
+                line,
+                " | r{}: {:#x} -> {:#x}",
+                change.register, change.before, change.after
+            )?;
+        }
+        for write in &self.memory_writes {
+            let shown = &write.bytes[..write.bytes.len().min(TEXT_WRITE_BYTES)];
+            write!(line, " | [{:#x}] <- {}", write.address, hex(shown))?;
+            if shown.len() < write.bytes.len() {
+                write!(line, " ... ({} bytes)", write.bytes.len())?;
+            }
+        }
+        if self.compute_units > 1 {
+            write!(line, " | {} CU", self.compute_units)?;
+        }
+        if let Some(error) = &self.error {
+            write!(line, " | error: {}", error)?;
+        }
+        f.write_str(line.trim_end())
+    }
+}
+
+/// Collects events in memory.
+impl TraceSink for Vec<TraceEvent> {
+    fn record(&mut self, event: &TraceEvent) {
+        self.push(event.clone());
+    }
+}
+
+/// Streams events to another thread. Events sent after the receiver is
+/// dropped are discarded.
+impl TraceSink for Sender<TraceEvent> {
+    fn record(&mut self, event: &TraceEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Writes each event as a line of JSON.
+pub struct JsonTraceWriter<W: Write> {
+    writer: W,
+    result: io::Result<()>,
+}
+
+impl<W: Write> JsonTraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            result: Ok(()),
+        }
+    }
+}
+
+impl<W: Write> TraceSink for JsonTraceWriter<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.result.is_ok() {
+            self.result = serde_json::to_writer(&mut self.writer, event)
+                .map_err(io::Error::from)
+                .and_then(|()| self.writer.write_all(b"\n"));
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        std::mem::replace(&mut self.result, Ok(()))?;
+        self.writer.flush()
+    }
+}
+
+/// Writes each event as a line of text, as [`TraceEvent`] displays.
+pub struct TextTraceWriter<W: Write> {
+    writer: W,
+    result: io::Result<()>,
+}
+
+impl<W: Write> TextTraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            result: Ok(()),
+        }
+    }
+}
+
+impl<W: Write> TraceSink for TextTraceWriter<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.result.is_ok() {
+            self.result = writeln!(self.writer, "{}", event);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        std::mem::replace(&mut self.result, Ok(()))?;
+        self.writer.flush()
+    }
+}
+
+fn hex_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(
+        &bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> TraceEvent {
+        TraceEvent {
+            pc: 3,
+            opcode: "stxdw",
+            instruction: "stxdw [r10-8], r1".to_string(),
+            operands: Operands {
+                dst: Some(RegisterValue {
+                    register: 10,
+                    value: 0x200001000,
+                }),
+                src: Some(RegisterValue {
+                    register: 1,
+                    value: 42,
+                }),
+                off: Some(-8),
+                imm: None,
+            },
+            registers: Vec::new(),
+            memory_writes: vec![MemoryWrite {
+                address: 0x200000ff8,
+                bytes: vec![42, 0, 0, 0, 0, 0, 0, 0],
+            }],
+            compute_units: 1,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_text_and_json_lines() {
+        let mut text = TextTraceWriter::new(Vec::new());
+        text.record(&event());
+        text.flush().unwrap();
+        assert_eq!(
+            String::from_utf8(text.writer).unwrap(),
+            "     3  stxdw [r10-8], r1            r10=0x200001000 r1=0x2a | [0x200000ff8] <- 2a \
+             00 00 00 00 00 00 00\n"
+        );
+
+        let mut json = JsonTraceWriter::new(Vec::new());
+        json.record(&event());
+        json.record(&event());
+        json.flush().unwrap();
+        let output = String::from_utf8(json.writer).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["operands"]["src"]["value"], 42);
+        assert_eq!(lines[0]["memory_writes"][0]["bytes"], "2a00000000000000");
+    }
+
+    #[test]
+    fn test_register_changes() {
+        let before = [0; 11];
+        let mut after = before;
+        after[0] = 1;
+        after[6] = 2;
+        assert_eq!(
+            RegisterChange::diff(&before, &after),
+            [
+                RegisterChange {
+                    register: 0,
+                    before: 0,
+                    after: 1,
+                },
+                RegisterChange {
+                    register: 6,
+                    before: 0,
+                    after: 2,
+                },
+            ]
+        );
+    }
+}
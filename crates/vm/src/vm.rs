@@ -1,15 +1,25 @@
 use {
     crate::{
         compute::ComputeMeter,
+        cost::CostModel,
+        coverage::Coverage,
         errors::{SbpfVmError, SbpfVmResult},
-        memory::Memory,
+        memory::{Memory, MemoryRegion},
+        snapshot::Snapshot,
         syscalls::SyscallHandler,
+        trace::{MemoryWrite, Operands, RegisterChange, TraceEvent, TraceSink},
     },
+    either::Either,
     sbpf_common::{
-        errors::ExecutionError, execute::Vm, inst_handler::OPCODE_TO_HANDLER,
-        instruction::Instruction,
+        errors::ExecutionError,
+        execute::Vm,
+        inst_handler::OPCODE_TO_HANDLER,
+        instruction::{AsmFormat, Instruction},
+        opcode::Opcode,
+        profile::{ComputeProfile, ExecutionProfile, SyscallStats},
     },
     serde::{Deserialize, Serialize},
+    std::time::Instant,
 };
 
 /// VM configuration
@@ -18,6 +28,12 @@ pub struct SbpfVmConfig {
     pub max_call_depth: usize,
     pub compute_unit_limit: u64,
     pub heap_size: usize,
+    #[serde(default)]
+    pub cost_model: CostModel,
+    /// Calls leave r10 alone and it starts at the top of the whole stack,
+    /// as in sBPF v1 and later.
+    #[serde(default)]
+    pub dynamic_stack_frames: bool,
 }
 
 impl Default for SbpfVmConfig {
@@ -26,6 +42,8 @@ impl Default for SbpfVmConfig {
             max_call_depth: 64,
             compute_unit_limit: 1_400_000,
             heap_size: Memory::DEFAULT_HEAP_SIZE,
+            cost_model: CostModel::default(),
+            dynamic_stack_frames: false,
         }
     }
 }
@@ -50,6 +68,22 @@ pub struct SbpfVm<H: SyscallHandler> {
     pub exit_code: Option<u64>,
     pub compute_meter: ComputeMeter,
     pub syscall_handler: H,
+    /// Execution counts, collected once profiling is enabled.
+    pub profile: Option<ExecutionProfile>,
+    /// Instructions executed, collected once coverage is enabled.
+    pub coverage: Option<Coverage>,
+    /// Compute units per instruction and call stack, collected once compute
+    /// profiling is enabled.
+    pub compute_profile: Option<ComputeProfile>,
+    /// Calls, compute units and host time per syscall, collected once
+    /// syscall stats are enabled.
+    pub syscall_stats: Option<SyscallStats>,
+    /// Where each instruction executed is reported, once tracing is
+    /// enabled.
+    pub trace_sink: Option<Box<dyn TraceSink + Send>>,
+    /// Function names with the index of their first instruction, as read
+    /// from the ELF by [`crate::loader`].
+    pub symbols: Vec<(String, usize)>,
 }
 
 impl<H: SyscallHandler> SbpfVm<H> {
@@ -75,12 +109,13 @@ impl<H: SyscallHandler> SbpfVm<H> {
         syscall_handler: H,
         config: SbpfVmConfig,
     ) -> Self {
-        let memory = Memory::new(
+        let mut memory = Memory::new(
             input,
             rodata,
             Memory::stack_size(config.max_call_depth),
             config.heap_size,
         );
+        memory.set_dynamic_stack_frames(config.dynamic_stack_frames);
 
         let mut registers = [0u64; 11];
         registers[1] = Memory::INPUT_START;
@@ -97,6 +132,12 @@ impl<H: SyscallHandler> SbpfVm<H> {
             compute_meter: ComputeMeter::new(config.compute_unit_limit),
             syscall_handler,
             config,
+            profile: None,
+            coverage: None,
+            compute_profile: None,
+            syscall_stats: None,
+            trace_sink: None,
+            symbols: Vec::new(),
         }
     }
 
@@ -110,6 +151,7 @@ impl<H: SyscallHandler> SbpfVm<H> {
         self.exit_code = None;
         self.compute_meter.reset();
         self.memory.reset_heap();
+        self.restart_compute_profile();
     }
 
     pub fn current_instruction(&self) -> SbpfVmResult<&Instruction> {
@@ -120,6 +162,39 @@ impl<H: SyscallHandler> SbpfVm<H> {
 
     pub fn set_entrypoint(&mut self, pc: usize) {
         self.pc = pc;
+        self.restart_compute_profile();
+    }
+
+    /// Index of the first instruction of the function named `name`.
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, index)| *index)
+    }
+
+    /// Run just the function `name`, with `args` in r1 onwards, and return
+    /// r0 once it returns. The call starts on a fresh stack frame with the
+    /// compute meter reset, while memory is kept from earlier runs so
+    /// functions can be called in turn against the same state.
+    pub fn call_function(&mut self, name: &str, args: &[u64]) -> SbpfVmResult<u64> {
+        let pc = self
+            .symbol(name)
+            .ok_or_else(|| SbpfVmError::UnknownFunction(name.to_string()))?;
+        if args.len() > 5 {
+            return Err(SbpfVmError::TooManyArguments(args.len()));
+        }
+        self.registers = [0u64; 11];
+        self.registers[1..=args.len()].copy_from_slice(args);
+        self.registers[10] = self.memory.initial_frame_pointer();
+        self.pc = pc;
+        self.call_stack.clear();
+        self.halted = false;
+        self.exit_code = None;
+        self.compute_meter.reset();
+        self.restart_compute_profile();
+        self.run()?;
+        Ok(self.registers[0])
     }
 
     pub fn is_pc_valid(&self) -> bool {
@@ -130,6 +205,107 @@ impl<H: SyscallHandler> SbpfVm<H> {
         self.compute_meter.get_remaining()
     }
 
+    /// Start counting how often each instruction executes. Counts accumulate
+    /// across `reset`, so several runs can be profiled together.
+    pub fn enable_profiling(&mut self) {
+        if self.profile.is_none() {
+            self.profile = Some(ExecutionProfile::new(self.program.len()));
+        }
+    }
+
+    pub fn take_profile(&mut self) -> Option<ExecutionProfile> {
+        self.profile.take()
+    }
+
+    /// Start recording which instructions execute. Like profiling, coverage
+    /// accumulates across `reset`.
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(Coverage::new(self.program.len()));
+        }
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Start charging the compute units each instruction uses to it and the
+    /// stack of internal calls it ran in. Like profiling, units accumulate
+    /// across `reset`.
+    pub fn enable_compute_profiling(&mut self) {
+        if self.compute_profile.is_none() {
+            self.compute_profile = Some(ComputeProfile::new());
+        }
+    }
+
+    pub fn take_compute_profile(&mut self) -> Option<ComputeProfile> {
+        self.compute_profile.take()
+    }
+
+    /// Start counting the calls, compute units and host time of each
+    /// syscall. Time is read from [`Instant`], which needs a host clock.
+    pub fn enable_syscall_stats(&mut self) {
+        if self.syscall_stats.is_none() {
+            self.syscall_stats = Some(SyscallStats::new());
+        }
+    }
+
+    pub fn take_syscall_stats(&mut self) -> Option<SyscallStats> {
+        self.syscall_stats.take()
+    }
+
+    /// Report every instruction executed from now on to `sink`, with the
+    /// registers and memory it changed.
+    pub fn enable_tracing(&mut self, sink: impl TraceSink + Send + 'static) {
+        self.trace_sink = Some(Box::new(sink));
+        self.memory.log_writes(true);
+    }
+
+    pub fn take_trace_sink(&mut self) -> Option<Box<dyn TraceSink + Send>> {
+        self.memory.log_writes(false);
+        self.trace_sink.take()
+    }
+
+    fn restart_compute_profile(&mut self) {
+        if let Some(profile) = &mut self.compute_profile {
+            profile.restart();
+        }
+    }
+
+    /// Save the registers and writable memory, typically after a run, to
+    /// compare against another run or to restore later.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pc: self.pc,
+            registers: self.registers,
+            exit_code: self.exit_code,
+            compute_units: self.compute_meter.get_consumed(),
+            input: self.memory.region(MemoryRegion::Input).to_vec(),
+            stack: self.memory.region(MemoryRegion::Stack).to_vec(),
+            heap: self.memory.region(MemoryRegion::Heap).to_vec(),
+            stack_high_water: self.memory.stack_high_water,
+        }
+    }
+
+    /// Restore state saved with [`SbpfVm::snapshot`]. The call stack is
+    /// cleared, so restore a snapshot taken at the top level of a run.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.pc = snapshot.pc;
+        self.registers = snapshot.registers;
+        self.exit_code = snapshot.exit_code;
+        self.halted = snapshot.exit_code.is_some();
+        self.call_stack.clear();
+        self.restart_compute_profile();
+        self.compute_meter.borrow_mut().consumed = snapshot.compute_units;
+        self.memory
+            .set_region(MemoryRegion::Input, snapshot.input.clone());
+        self.memory
+            .set_region(MemoryRegion::Stack, snapshot.stack.clone());
+        self.memory
+            .set_region(MemoryRegion::Heap, snapshot.heap.clone());
+        self.memory.stack_high_water = snapshot.stack_high_water;
+    }
+
     pub fn step(&mut self) -> SbpfVmResult<()> {
         if self.halted {
             return Ok(());
@@ -139,9 +315,102 @@ impl<H: SyscallHandler> SbpfVm<H> {
             return Err(SbpfVmError::PcOutOfBounds(self.pc));
         }
 
-        self.compute_meter.consume(1)?;
+        if self.compute_profile.is_none()
+            && self.syscall_stats.is_none()
+            && self.trace_sink.is_none()
+        {
+            return self.execute_step();
+        }
+        let (pc, depth, registers) = (self.pc, self.call_stack.len(), self.registers);
+        // Writes made between steps aren't this instruction's.
+        self.memory.take_writes();
+        let consumed = self.compute_meter.get_consumed();
+        let syscall_start =
+            (self.syscall_stats.is_some() && is_syscall(&self.program[pc])).then(Instant::now);
+        let result = self.execute_step();
+        let units = self.compute_meter.get_consumed().saturating_sub(consumed);
+        if let Some(profile) = &mut self.compute_profile {
+            let callee = (self.call_stack.len() > depth).then_some(self.pc);
+            profile.record(pc, units, depth, callee);
+        }
+        if let (Some(stats), Some(start), Some(Either::Left(name))) = (
+            &mut self.syscall_stats,
+            syscall_start,
+            &self.program[pc].imm,
+        ) {
+            // The call instruction itself costs what any instruction does.
+            let units = units.saturating_sub(self.config.cost_model.instruction_cost);
+            stats.record(name, units, start.elapsed());
+        }
+        if self.trace_sink.is_some() {
+            let event = self.trace_event(pc, &registers, units, &result);
+            if let Some(sink) = &mut self.trace_sink {
+                sink.record(&event);
+            }
+        }
+        result
+    }
+
+    /// What the instruction at `pc` did, given the registers before it ran.
+    fn trace_event(
+        &mut self,
+        pc: usize,
+        registers: &[u64; 11],
+        compute_units: u64,
+        result: &SbpfVmResult<()>,
+    ) -> TraceEvent {
+        let instruction = &self.program[pc];
+        let memory_writes = self
+            .memory
+            .take_writes()
+            .into_iter()
+            .map(|(address, len)| MemoryWrite {
+                address,
+                bytes: self
+                    .memory
+                    .read_slice(address, len)
+                    .map(<[u8]>::to_vec)
+                    .unwrap_or_default(),
+            })
+            .collect();
+        TraceEvent {
+            pc,
+            opcode: instruction.opcode.to_str(),
+            instruction: instruction
+                .to_asm(AsmFormat::Default)
+                .unwrap_or_else(|_| instruction.opcode.to_string()),
+            operands: Operands::new(instruction, registers),
+            registers: RegisterChange::diff(registers, &self.registers),
+            memory_writes,
+            compute_units,
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+
+    /// Charge for and execute the instruction at `pc`, which must be valid.
+    fn execute_step(&mut self) -> SbpfVmResult<()> {
+        self.compute_meter
+            .consume(self.config.cost_model.instruction_cost)?;
+
+        if let Some(profile) = &mut self.profile {
+            profile.record(self.pc);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(self.pc);
+        }
 
         let inst = self.current_instruction()?.clone();
+        if inst.opcode == Opcode::Call
+            && self.config.cost_model.charge_syscalls
+            && let Some(Either::Left(name)) = &inst.imm
+        {
+            let registers = self.registers[1..6].try_into().unwrap();
+            let cost = self
+                .config
+                .cost_model
+                .syscall_cost(name, registers, &self.memory);
+            self.compute_meter.consume(cost)?;
+        }
         self.execute_instruction(&inst)?;
 
         Ok(())
@@ -156,24 +425,21 @@ impl<H: SyscallHandler> SbpfVm<H> {
         }
     }
 
+    /// Run until the program exits, or fails with
+    /// [`SbpfVmError::ComputeBudgetExceeded`] once it uses up its budget.
     pub fn run(&mut self) -> SbpfVmResult<()> {
-        let mut steps = 0;
-
-        while !self.halted && steps < self.config.compute_unit_limit {
+        while !self.halted {
             self.step()?;
-            steps += 1;
-        }
-
-        if !self.halted && steps >= self.config.compute_unit_limit {
-            return Err(SbpfVmError::ExecutionLimitReached(
-                self.config.compute_unit_limit,
-            ));
         }
-
         Ok(())
     }
 }
 
+/// Whether `instruction` calls a syscall rather than a function.
+fn is_syscall(instruction: &Instruction) -> bool {
+    instruction.opcode == Opcode::Call && matches!(instruction.imm, Some(Either::Left(_)))
+}
+
 impl<H: SyscallHandler> Vm for SbpfVm<H> {
     fn get_register(&self, reg: usize) -> u64 {
         self.registers[reg]
@@ -239,6 +505,18 @@ impl<H: SyscallHandler> Vm for SbpfVm<H> {
             .map_err(|_| ExecutionError::InvalidMemoryAccess(addr))
     }
 
+    fn read_slice(&self, addr: u64, len: usize) -> Result<&[u8], ExecutionError> {
+        self.memory
+            .read_slice(addr, len)
+            .map_err(|_| ExecutionError::InvalidMemoryAccess(addr))
+    }
+
+    fn write_slice(&mut self, addr: u64, len: usize) -> Result<&mut [u8], ExecutionError> {
+        self.memory
+            .write_slice(addr, len)
+            .map_err(|_| ExecutionError::InvalidMemoryAccess(addr))
+    }
+
     fn get_call_depth(&self) -> usize {
         self.call_stack.len()
     }
@@ -280,6 +558,10 @@ impl<H: SyscallHandler> Vm for SbpfVm<H> {
         Memory::STACK_FRAME_SIZE
     }
 
+    fn dynamic_stack_frames(&self) -> bool {
+        self.config.dynamic_stack_frames
+    }
+
     fn handle_syscall(&mut self, name: &str) -> Result<u64, ExecutionError> {
         let registers = [
             self.registers[1],
@@ -660,6 +942,98 @@ mod tests {
         assert_eq!(vm.compute_meter.get_consumed(), 5);
     }
 
+    #[test]
+    fn test_profiling_counts_executions() {
+        // mov64 r1, 3
+        // loop:
+        // sub64 r1, 1
+        // jne r1, 0, loop
+        // exit
+        let program = vec![
+            make_test_instruction(
+                Opcode::Mov64Imm,
+                Some(Register { n: 1 }),
+                None,
+                None,
+                Some(Either::Right(Number::Int(3))),
+            ),
+            make_test_instruction(
+                Opcode::Sub64Imm,
+                Some(Register { n: 1 }),
+                None,
+                None,
+                Some(Either::Right(Number::Int(1))),
+            ),
+            make_test_instruction(
+                Opcode::JneImm,
+                Some(Register { n: 1 }),
+                None,
+                Some(Either::Right(-2)),
+                Some(Either::Right(Number::Int(0))),
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+
+        let mut vm = SbpfVm::new(program, vec![], vec![], MockSyscallHandler::default());
+        vm.enable_profiling();
+        vm.run().unwrap();
+        vm.reset();
+        vm.run().unwrap();
+
+        let profile = vm.take_profile().unwrap();
+        assert_eq!(profile.instruction_counts, vec![2, 6, 6, 2]);
+    }
+
+    #[test]
+    fn test_snapshot_diff_and_restore() {
+        // ldxb r0, [r1+0]
+        // stxb [r10-1], r0
+        // exit
+        let program = vec![
+            make_test_instruction(
+                Opcode::Ldxb,
+                Some(Register { n: 0 }),
+                Some(Register { n: 1 }),
+                Some(Either::Right(0)),
+                None,
+            ),
+            make_test_instruction(
+                Opcode::Stxb,
+                Some(Register { n: 10 }),
+                Some(Register { n: 0 }),
+                Some(Either::Right(-1)),
+                None,
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+
+        let run = |input: Vec<u8>| {
+            let mut vm = SbpfVm::new(
+                program.clone(),
+                input,
+                vec![],
+                MockSyscallHandler::default(),
+            );
+            vm.run().unwrap();
+            vm
+        };
+        let baseline = run(vec![1]).snapshot();
+        let mut vm = run(vec![2]);
+        let diff = vm.snapshot().diff(&baseline);
+
+        assert_eq!(diff.registers, vec![(0, 1, 2)]);
+        // A single byte below r10.
+        assert_eq!(diff.stack_high_water, (1, 1));
+        let addresses: Vec<u64> = diff.ranges.iter().map(|range| range.address).collect();
+        assert_eq!(
+            addresses,
+            vec![Memory::INPUT_START, Memory::STACK_START + 4095]
+        );
+
+        vm.restore(&baseline);
+        assert!(vm.snapshot().diff(&baseline).is_empty());
+    }
+
     #[test]
     fn test_program_with_input() {
         // ldxdw r2, [r1 + 0]
@@ -715,6 +1089,140 @@ mod tests {
         assert_eq!(vm.compute_meter.get_consumed(), 5);
     }
 
+    #[test]
+    fn test_syscall_costs() {
+        let program = vec![
+            make_test_instruction(
+                Opcode::Call,
+                None,
+                None,
+                None,
+                Some(Either::Left("sol_log_64_".to_string())),
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+        let mut vm = SbpfVm::new(
+            program.clone(),
+            vec![],
+            vec![],
+            MockSyscallHandler::default(),
+        );
+        vm.run().unwrap();
+        assert_eq!(vm.compute_meter.get_consumed(), 102);
+
+        let config = SbpfVmConfig {
+            compute_unit_limit: 100,
+            ..SbpfVmConfig::default()
+        };
+        let mut vm = SbpfVm::new_with_config(
+            program.clone(),
+            vec![],
+            vec![],
+            MockSyscallHandler::default(),
+            config.clone(),
+        );
+        assert!(matches!(
+            vm.run(),
+            Err(SbpfVmError::ComputeBudgetExceeded {
+                limit: 100,
+                consumed: 101
+            })
+        ));
+        assert!(vm.syscall_handler.logs.is_empty());
+
+        let config = SbpfVmConfig {
+            cost_model: CostModel {
+                charge_syscalls: false,
+                ..CostModel::default()
+            },
+            ..config
+        };
+        let mut vm = SbpfVm::new_with_config(
+            program,
+            vec![],
+            vec![],
+            MockSyscallHandler::default(),
+            config,
+        );
+        vm.run().unwrap();
+        assert_eq!(vm.compute_meter.get_consumed(), 2);
+    }
+
+    #[test]
+    fn test_syscall_stats() {
+        let log = make_test_instruction(
+            Opcode::Call,
+            None,
+            None,
+            None,
+            Some(Either::Left("sol_log_64_".to_string())),
+        );
+        let program = vec![
+            log.clone(),
+            log,
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+        let mut vm = SbpfVm::new(program, vec![], vec![], MockSyscallHandler::default());
+        vm.enable_syscall_stats();
+        vm.run().unwrap();
+
+        let stats = vm.take_syscall_stats().unwrap();
+        let usage = stats.syscalls["sol_log_64_"];
+        assert_eq!((usage.calls, usage.compute_units), (2, 200));
+        assert_eq!(stats.syscalls.len(), 1);
+    }
+
+    #[test]
+    fn test_trace_registers_and_memory_writes() {
+        let program = vec![
+            make_test_instruction(
+                Opcode::Mov64Imm,
+                Some(Register { n: 1 }),
+                None,
+                None,
+                Some(Either::Right(Number::Int(42))),
+            ),
+            make_test_instruction(
+                Opcode::Stxdw,
+                Some(Register { n: 10 }),
+                Some(Register { n: 1 }),
+                Some(Either::Right(-8)),
+                None,
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+        let mut vm = SbpfVm::new(program, vec![], vec![], MockSyscallHandler::default());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        vm.enable_tracing(sender);
+        vm.run().unwrap();
+        drop(vm.take_trace_sink());
+
+        let events: Vec<TraceEvent> = receiver.iter().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].opcode, "mov64");
+        assert_eq!(
+            events[0].registers,
+            [RegisterChange {
+                register: 1,
+                before: Memory::INPUT_START,
+                after: 42,
+            }]
+        );
+        let frame_pointer = vm.memory.initial_frame_pointer();
+        assert_eq!(events[1].operands.dst.unwrap().value, frame_pointer);
+        assert_eq!(events[1].operands.off, Some(-8));
+        assert_eq!(
+            events[1].memory_writes,
+            [MemoryWrite {
+                address: frame_pointer - 8,
+                bytes: 42u64.to_le_bytes().to_vec(),
+            }]
+        );
+        assert!(events[1].registers.is_empty());
+        assert_eq!(events[2].compute_units, 1);
+        assert!(vm.memory.take_writes().is_empty());
+    }
+
     #[test]
     fn test_program_with_internal_function_call() {
         // call test
@@ -759,6 +1267,55 @@ mod tests {
         assert_eq!(vm.registers[2], 0x2);
     }
 
+    #[test]
+    fn test_compute_profile_follows_calls() {
+        // call sol_log_64_
+        // call test
+        // exit
+        //
+        // test:
+        //   lddw r1, 0x1
+        //   exit
+        let program = vec![
+            make_test_instruction(
+                Opcode::Call,
+                None,
+                None,
+                None,
+                Some(Either::Left("sol_log_64_".to_string())),
+            ),
+            make_test_instruction(
+                Opcode::Call,
+                None,
+                None,
+                None,
+                Some(Either::Right(Number::Int(1))),
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+            make_test_instruction(
+                Opcode::Lddw,
+                Some(Register { n: 1 }),
+                None,
+                None,
+                Some(Either::Right(Number::Int(0x1))),
+            ),
+            make_test_instruction(Opcode::Exit, None, None, None, None),
+        ];
+
+        let mut vm = SbpfVm::new(program, vec![], vec![], MockSyscallHandler::default());
+        vm.enable_compute_profiling();
+        vm.run().unwrap();
+        vm.reset();
+        vm.run().unwrap();
+
+        let profile = vm.take_compute_profile().unwrap();
+        assert_eq!(profile.total(), 2 * vm.compute_meter.get_consumed());
+        assert_eq!(
+            profile.to_text(),
+            "0;0 202\n0;1 2\n0;2 2\n0;3;3 2\n0;3;4 2\n"
+        );
+    }
+
     #[test]
     fn test_vm_jmp32_imm() {
         // lddw r1, 0x00000001_00000005
@@ -0,0 +1,71 @@
+use {crate::session::Session as VmSession, wasm_bindgen::prelude::*};
+
+/// A program loaded for stepping, for web UIs to build a visual debugger
+/// on. Methods that move the program on return the state after them as
+/// JSON text, ready for `JSON.parse`.
+#[wasm_bindgen]
+pub struct Session {
+    inner: VmSession,
+}
+
+#[wasm_bindgen]
+impl Session {
+    /// Load an ELF to run from its entrypoint with `input` in the input
+    /// region.
+    #[wasm_bindgen]
+    pub fn load(elf: &[u8], input: &[u8]) -> Result<Session, JsValue> {
+        VmSession::load(elf, input.to_vec())
+            .map(|inner| Session { inner })
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen]
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    #[wasm_bindgen]
+    pub fn step(&mut self) -> Result<String, JsValue> {
+        let state = self.inner.step();
+        to_json(&state)
+    }
+
+    /// Step until the program stops or reaches one of the `.text` byte
+    /// offsets in `breakpoints`.
+    #[wasm_bindgen]
+    pub fn run(&mut self, breakpoints: &[u64]) -> Result<String, JsValue> {
+        let state = self.inner.run(breakpoints);
+        to_json(&state)
+    }
+
+    #[wasm_bindgen]
+    pub fn state(&self) -> Result<String, JsValue> {
+        to_json(&self.inner.state())
+    }
+
+    /// r0 to r10 as a `BigUint64Array`.
+    #[wasm_bindgen]
+    pub fn registers(&self) -> Vec<u64> {
+        self.inner.registers().to_vec()
+    }
+
+    /// `len` bytes of VM memory from virtual address `address`.
+    #[wasm_bindgen]
+    pub fn memory(&self, address: u64, len: usize) -> Result<Vec<u8>, JsValue> {
+        self.inner
+            .memory(address, len)
+            .map(<[u8]>::to_vec)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// The file, line and column of the next instruction as JSON, or `null`
+    /// without debug info.
+    #[wasm_bindgen(js_name = sourceLine)]
+    pub fn source_line(&self) -> Result<String, JsValue> {
+        to_json(&self.inner.source_line())
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, JsValue> {
+    serde_json::to_string(value).map_err(|e| JsValue::from_str(&e.to_string()))
+}
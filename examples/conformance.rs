@@ -0,0 +1,129 @@
+//! Shared by the example tests: runs every scenario in Mollusk and again in
+//! the sbpf runtime, and fails if the two disagree.
+
+use {
+    mollusk_svm::{
+        result::{Check, InstructionResult, ProgramResult},
+        Mollusk,
+    },
+    sbpf_runtime::{config::RuntimeConfig, Runtime},
+    solana_account::Account,
+    solana_address::Address,
+    solana_instruction::Instruction,
+};
+
+/// Process `instruction` with Mollusk and validate `checks`, then replay it
+/// in the sbpf runtime and compare the outcome and every resulting account.
+pub fn process_and_validate_instruction(
+    mollusk: &Mollusk,
+    elf_path: &str,
+    instruction: &Instruction,
+    accounts: &[(Address, Account)],
+    checks: &[Check],
+) -> InstructionResult {
+    replay(mollusk, elf_path, instruction, accounts, checks).0
+}
+
+/// Like [`process_and_validate_instruction`], but also fail if the sbpf
+/// runtime's compute units differ from Mollusk's by more than `tolerance`.
+/// Both counts are reported against the budget, so a failure shows how much
+/// headroom each side thinks the program has.
+#[allow(dead_code)] // Not every example checks compute units.
+pub fn process_and_compare_compute_units(
+    mollusk: &Mollusk,
+    elf_path: &str,
+    instruction: &Instruction,
+    accounts: &[(Address, Account)],
+    checks: &[Check],
+    tolerance: u64,
+) -> InstructionResult {
+    let (expected, consumed) = replay(mollusk, elf_path, instruction, accounts, checks);
+    // A fault in the sbpf runtime has no compute count to compare.
+    if let Some(consumed) = consumed {
+        let budget = RuntimeConfig::default().compute_budget;
+        assert!(
+            expected.compute_units_consumed.abs_diff(consumed) <= tolerance,
+            "compute units differ by more than {}: Mollusk consumed {} and the sbpf runtime {} of {}",
+            tolerance,
+            expected.compute_units_consumed,
+            consumed,
+            budget
+        );
+    }
+    expected
+}
+
+/// Run both sides and compare them, returning Mollusk's result and the
+/// compute units the sbpf runtime consumed, if it ran to completion.
+fn replay(
+    mollusk: &Mollusk,
+    elf_path: &str,
+    instruction: &Instruction,
+    accounts: &[(Address, Account)],
+    checks: &[Check],
+) -> (InstructionResult, Option<u64>) {
+    let expected = mollusk.process_and_validate_instruction(instruction, accounts, checks);
+
+    // Records what ran when `sbpf test --coverage` or `--cu-profile` asks for it.
+    let config = RuntimeConfig::from_env();
+    let (coverage_dir, profile_dir) = (config.coverage_dir.clone(), config.profile_dir.clone());
+    let mut runtime = Runtime::new(instruction.program_id, format!("{}.so", elf_path), config)
+        .expect("failed to load program into the sbpf runtime");
+    let result = runtime.run(instruction, accounts);
+    if let Some(dir) = &coverage_dir {
+        runtime.write_coverage(dir).expect("failed to write coverage");
+    }
+    if let Some(dir) = &profile_dir {
+        runtime
+            .write_compute_profiles(dir)
+            .expect("failed to write compute profiles");
+    }
+
+    match (&expected.program_result, &result) {
+        (ProgramResult::Success, Ok(result)) => {
+            assert_eq!(
+                result.exit_code.unwrap_or(0),
+                0,
+                "Mollusk succeeded but the sbpf runtime failed:\n{}",
+                result.logs.join("\n")
+            );
+            for (address, account) in &expected.resulting_accounts {
+                let actual = runtime
+                    .get_account(address)
+                    .unwrap_or_else(|| panic!("account {} missing in the sbpf runtime", address));
+                assert_eq!(
+                    actual.lamports, account.lamports,
+                    "lamports of {} differ",
+                    address
+                );
+                assert_eq!(actual.owner, account.owner, "owner of {} differs", address);
+                assert_eq!(actual.data, account.data, "data of {} differs", address);
+            }
+        }
+        (ProgramResult::Success, Err(e)) => {
+            panic!("Mollusk succeeded but the sbpf runtime failed: {}", e)
+        }
+        (ProgramResult::Failure(error), Ok(result)) => {
+            assert_eq!(
+                result.exit_code,
+                Some(u64::from(error.clone())),
+                "Mollusk failed with {:?} but the sbpf runtime exited differently:\n{}",
+                error,
+                result.logs.join("\n")
+            );
+        }
+        // Faults have no exit code to compare; failing in both is enough.
+        (_, Err(_)) => {}
+        (ProgramResult::UnknownError(error), Ok(result)) => {
+            assert_ne!(
+                result.exit_code.unwrap_or(0),
+                0,
+                "Mollusk failed with {:?} but the sbpf runtime succeeded",
+                error
+            );
+        }
+    }
+
+    let consumed = result.ok().map(|result| result.compute_units_consumed);
+    (expected, consumed)
+}
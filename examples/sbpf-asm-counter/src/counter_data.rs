@@ -0,0 +1,30 @@
+// Generated by `sbpf layout` from sbpf-asm-counter.s. Do not edit.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterData {
+    pub bump: u8,
+    pub count: u64,
+}
+
+impl CounterData {
+    pub const LEN: usize = 9;
+    pub const BUMP_OFFSET: usize = 0;
+    pub const COUNT_OFFSET: usize = 1;
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::LEN {
+            return None;
+        }
+        Some(Self {
+            bump: u8::from_le_bytes(data[0..1].try_into().unwrap()),
+            count: u64::from_le_bytes(data[1..9].try_into().unwrap()),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; Self::LEN];
+        data[0..1].copy_from_slice(&self.bump.to_le_bytes());
+        data[1..9].copy_from_slice(&self.count.to_le_bytes());
+        data
+    }
+}
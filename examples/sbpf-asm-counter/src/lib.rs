@@ -1,6 +1,15 @@
+#[cfg(all(test, feature = "example-tests"))]
+#[path = "../../conformance.rs"]
+mod conformance;
+
+#[cfg(all(test, feature = "example-tests"))]
+#[allow(dead_code)] // Generated, so not every accessor is used.
+mod counter_data;
+
 #[cfg(all(test, feature = "example-tests"))]
 mod tests {
     use {
+        crate::{conformance, counter_data::CounterData},
         mollusk_svm::{program, result::Check, Mollusk},
         solana_account::Account,
         solana_address::Address,
@@ -9,7 +18,7 @@ mod tests {
     };
 
     const BASE_LAMPORTS: u64 = 10 * LAMPORTS_PER_SOL;
-    const COUNTER_SIZE: usize = 9;
+    const COMPUTE_UNIT_TOLERANCE: u64 = 0;
 
     pub fn get_program_id() -> Address {
         let program_id_keypair_bytes = std::fs::read("deploy/sbpf-asm-counter-keypair.json")
@@ -45,13 +54,17 @@ mod tests {
             ],
         );
 
-        let mut expected_data = Vec::with_capacity(9);
-        expected_data.push(counter_bump);
-        expected_data.extend_from_slice(&0u64.to_le_bytes());
+        let expected_data = CounterData {
+            bump: counter_bump,
+            count: 0,
+        }
+        .to_bytes();
 
-        let expected_lamports = mollusk.sysvars.rent.minimum_balance(COUNTER_SIZE);
+        let expected_lamports = mollusk.sysvars.rent.minimum_balance(CounterData::LEN);
 
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-counter",
             &instruction,
             &[
                 (owner_pubkey, owner_account),
@@ -82,15 +95,16 @@ mod tests {
         let (counter_pda, counter_bump) =
             Address::find_program_address(&[b"counter", &owner_pubkey.to_bytes()], &program_id);
         let mut counter_account = Account::new(
-            mollusk.sysvars.rent.minimum_balance(COUNTER_SIZE),
-            COUNTER_SIZE,
+            mollusk.sysvars.rent.minimum_balance(CounterData::LEN),
+            CounterData::LEN,
             &&program_id.into(),
         );
 
-        let mut counter_data = Vec::with_capacity(9);
-        counter_data.push(counter_bump);
-        counter_data.extend_from_slice(&0u64.to_le_bytes()); // Initial count -> 0
-        counter_account.data = counter_data;
+        counter_account.data = CounterData {
+            bump: counter_bump,
+            count: 0, // Initial count -> 0
+        }
+        .to_bytes();
 
         let mut instruction_data = vec![1]; // 1 -> Increment
         instruction_data.extend_from_slice(&counter_bump.to_le_bytes());
@@ -105,11 +119,15 @@ mod tests {
             ],
         );
 
-        let mut expected_data = Vec::with_capacity(9);
-        expected_data.push(counter_bump);
-        expected_data.extend_from_slice(&1u64.to_le_bytes()); // Expected count -> 1
+        let expected_data = CounterData {
+            bump: counter_bump,
+            count: 1, // Expected count -> 1
+        }
+        .to_bytes();
 
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_compare_compute_units(
+            &mollusk,
+            "deploy/sbpf-asm-counter",
             &instruction,
             &[
                 (owner_pubkey, owner_account),
@@ -120,6 +138,7 @@ mod tests {
                 Check::success(),
                 Check::account(&counter_pda).data(&expected_data).build(),
             ],
+            COMPUTE_UNIT_TOLERANCE,
         );
     }
 }
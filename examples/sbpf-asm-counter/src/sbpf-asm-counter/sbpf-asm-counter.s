@@ -30,7 +30,12 @@
 .equ PROGRAM_ID, 0x7942
 
 .equ COUNTER_SEED, 0x7265746e756f63
-.equ COUNTER_DATA_SIZE, 0x9                                       # 9 (u8 bump + u64 counter)
+# Counter account data: defines COUNTER_DATA_BUMP, COUNTER_DATA_COUNT,
+# COUNTER_DATA_SIZE and the COUNTER_DATA_LOAD_*/COUNTER_DATA_STORE_* macros
+.layout counter_data
+  bump: u8
+  count: u64
+.endlayout
 
 .equ ACCOUNT_STORAGE_OVERHEAD, 0x80                               # 128
 
@@ -285,9 +290,9 @@ initialize:
 
   # Write data to the newly created account
   ldxb r2, [r8 + INSTRUCTION_DATA + 1]
-  stxb [r8 + COUNTER_DATA], r2                                    # Bump
+  COUNTER_DATA_STORE_BUMP r8 + COUNTER_DATA, r2
   lddw r2, 0
-  stxdw [r8 + COUNTER_DATA + 1], r2                               # Initial counter value (0)
+  COUNTER_DATA_STORE_COUNT r8 + COUNTER_DATA, r2                  # Initial counter value (0)
 
   exit
 
@@ -298,9 +303,9 @@ increment:
   jeq r3, 0, error_invalid_signature
 
   # Increment count by 1
-  ldxdw r2, [r1 + COUNTER_DATA + 1]
+  COUNTER_DATA_LOAD_COUNT r2, r1 + COUNTER_DATA
   add64 r2, 1
-  stxdw [r1 + COUNTER_DATA + 1], r2
+  COUNTER_DATA_STORE_COUNT r1 + COUNTER_DATA, r2
 
   exit
 
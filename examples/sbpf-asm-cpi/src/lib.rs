@@ -1,6 +1,11 @@
+#[cfg(all(test, feature = "example-tests"))]
+#[path = "../../conformance.rs"]
+mod conformance;
+
 #[cfg(all(test, feature = "example-tests"))]
 mod tests {
     use {
+        crate::conformance,
         mollusk_svm::{program, result::Check, Mollusk},
         solana_account::Account,
         solana_address::Address,
@@ -37,7 +42,9 @@ mod tests {
             &[],
             vec![AccountMeta::new(sender_pubkey, true)],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[(
                 sender_pubkey,
@@ -57,7 +64,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[
                 (
@@ -96,7 +105,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[
                 (
@@ -128,7 +139,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[
                 (
@@ -165,7 +178,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[
                 (
@@ -201,7 +216,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-cpi",
             &instruction,
             &[
                 (
@@ -1,6 +1,11 @@
+#[cfg(all(test, feature = "example-tests"))]
+#[path = "../../conformance.rs"]
+mod conformance;
+
 #[cfg(all(test, feature = "example-tests"))]
 mod tests {
     use {
+        crate::conformance,
         mollusk_svm::{program, result::Check, Mollusk},
         solana_account::Account,
         solana_address::Address,
@@ -49,7 +54,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-vault",
             &instruction,
             &[
                 (owner_pubkey, owner_account),
@@ -86,7 +93,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-vault",
             &instruction,
             &[
                 (owner_pubkey, owner_account),
@@ -131,7 +140,9 @@ mod tests {
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
-        mollusk.process_and_validate_instruction(
+        conformance::process_and_validate_instruction(
+            &mollusk,
+            "deploy/sbpf-asm-vault",
             &instruction,
             &[
                 (owner_pubkey, owner_account),
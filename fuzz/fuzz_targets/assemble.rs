@@ -0,0 +1,30 @@
+//! Runs arbitrary source through the lexer, the parser and the ELF emitter
+//! for every architecture. Whatever assembles must read back as a program
+//! that disassembles without errors.
+
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    sbpf_assembler::{Assembler, AssemblerOption, SbpfArch},
+    sbpf_disassembler::program::Program,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    for arch in [SbpfArch::V0, SbpfArch::V1, SbpfArch::V2, SbpfArch::V3] {
+        let assembler = Assembler::new(AssemblerOption::default().with_arch(arch));
+        let _ = assembler.lint(&source);
+        let Ok(elf) = assembler.assemble(&source) else {
+            continue;
+        };
+        let program = Program::from_bytes(&elf).expect("emitted ELF reads back");
+        assert_eq!(program.version(), arch);
+        let disassembly = program.to_ixs().expect("emitted ELF has a .text");
+        assert!(
+            disassembly.errors.is_empty(),
+            "emitted .text fails to decode: {:?}",
+            disassembly.errors
+        );
+    }
+});
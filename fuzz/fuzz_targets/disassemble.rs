@@ -0,0 +1,11 @@
+//! Reads arbitrary bytes as an ELF and disassembles whatever loads.
+
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, sbpf_disassembler::program::Program};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(program) = Program::from_bytes(data) {
+        let _ = program.to_ixs();
+    }
+});
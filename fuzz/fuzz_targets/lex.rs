@@ -0,0 +1,10 @@
+//! Tokenizes arbitrary input. The lexer must reject what it can't read
+//! rather than panic.
+
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, sbpf_assembler::lexer::tokenize};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = tokenize(&String::from_utf8_lossy(data));
+});
@@ -0,0 +1,70 @@
+//! Decodes arbitrary words as instructions, prints each as assembly and
+//! assembles it again, which must give back the same instruction. The first
+//! byte picks the architecture.
+
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    sbpf_assembler::{Assembler, AssemblerOption, SbpfArch},
+    sbpf_common::{
+        instruction::{AsmFormat, Instruction},
+        opcode::Opcode,
+    },
+    sbpf_disassembler::program::Program,
+};
+
+const ARCHES: [SbpfArch; 4] = [SbpfArch::V0, SbpfArch::V1, SbpfArch::V2, SbpfArch::V3];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&arch, mut words)) = data.split_first() else {
+        return;
+    };
+    let arch = ARCHES[arch as usize % ARCHES.len()];
+    let assembler = Assembler::new(
+        AssemblerOption::default()
+            .with_arch(arch)
+            .without_verification(),
+    );
+    while words.len() >= 8 {
+        let Ok(instruction) = Instruction::from_bytes_for(words, arch) else {
+            words = &words[8..];
+            continue;
+        };
+        words = &words[(instruction.get_size() as usize).min(words.len())..];
+        // The decoder takes any 4-bit register and every version's opcodes;
+        // assembly only names r0-r10 and what `arch` supports. Calls decode
+        // to a number or a syscall's name, and only resolve once linked.
+        if !arch.supports(instruction.opcode)
+            || instruction.opcode == Opcode::Call
+            || [&instruction.dst, &instruction.src]
+                .into_iter()
+                .flatten()
+                .any(|register| register.n > 10)
+        {
+            continue;
+        }
+        let encoded = instruction
+            .to_bytes_for(arch)
+            .expect("decoded instructions encode");
+        let asm = instruction
+            .to_asm(AsmFormat::Default)
+            .expect("decoded instructions print");
+        let source = format!(".globl entrypoint\nentrypoint:\n  {}\n", asm);
+        let elf = assembler
+            .assemble(&source)
+            .unwrap_or_else(|errors| panic!("`{}` does not assemble: {:?}", asm, errors));
+        let reassembled = Program::from_bytes(&elf)
+            .expect("emitted ELF reads back")
+            .to_ixs_raw()
+            .expect("emitted ELF has a .text")
+            .value
+            .instructions
+            .remove(0)
+            .left()
+            .expect("reassembled instruction decodes")
+            .to_bytes_for(arch)
+            .unwrap();
+        assert_eq!(reassembled, encoded, "`{}` reassembles differently", asm);
+    }
+});
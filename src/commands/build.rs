@@ -1,21 +1,40 @@
 use {
+    crate::commands::{
+        common::{ErrorFormat, JsonDiagnostic, JsonLabel, OutputFormat, print_json},
+        config::{CONFIG_FILE, ProjectConfig},
+        error_codes::write_error_constants,
+        incremental::{BuildState, Freshness, ProgramRecord},
+        keys::ensure_keypair,
+        verify::{ManifestInput, VerifiableManifest, executable_hash, manifest_path, sha256_hex},
+        watch::watch,
+    },
     anyhow::{Error, Result},
     clap::{Args, ValueEnum},
     codespan_reporting::{
-        diagnostic::{Diagnostic, Label},
+        diagnostic::{self, Diagnostic, Label},
         files::SimpleFiles,
         term,
     },
-    ed25519_dalek::SigningKey,
     sbpf_assembler::{
-        AssembleErrors, Assembler, AssemblerOption, DebugMode, FileRegistry, FsFileResolver,
-        SbpfArch, SourceOrigin, errors::CompileError,
+        Artifacts, AssembleErrors, Assembler, AssemblerError, AssemblerOption, AssemblyStats,
+        BuildInfo, DebugMode, FileRegistry, Frontend, FrontendError, FrontendRegistry,
+        FsFileResolver, InternalErrors, LintConfig, OptLevel, OptimizationConfig, ParseCache,
+        PhaseTimings, SbpfArch, SourceOrigin, SyscallRegistry, Translation, decode_source,
+        errors::{CompileError, Severity},
+        preprocessor::include::include_directives,
+        read_source,
     },
+    sbpf_build::{program_entry, program_sources},
+    sbpf_common::profile::ExecutionProfile,
+    serde::{Deserialize, Serialize},
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         fs::{self, create_dir_all},
-        path::Path,
-        time::Instant,
+        io::{self, Read, Write},
+        ops::Range,
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+        time::{Duration, Instant},
     },
     termcolor::{ColorChoice, StandardStream},
 };
@@ -24,28 +43,444 @@ use {
 pub struct BuildArgs {
     #[arg(short = 'g', long, help = "Include debug information")]
     pub debug: bool,
+    #[arg(short = 'a', long, help = "Target architecture (v0-v3) [default: v3]")]
+    pub arch: Option<ArchArg>,
+    #[arg(short = 'd', long, help = "Output deploy directory")]
+    pub deploy_dir: Option<String>,
     #[arg(
-        short = 'a',
+        short = 'O',
         long,
-        default_value = "v3",
-        help = "Target architecture (v0 or v3)"
+        help = "Optimization level: 0 (none), 1 (peephole rewrites and dead function elimination) or 2 (also inline small functions) [default: 0]"
     )]
-    arch: ArchArg,
-    #[arg(short = 'd', long, help = "Output deploy directory")]
-    pub deploy_dir: Option<String>,
+    pub opt_level: Option<OptLevelArg>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Order functions by an execution profile; applies to the program named by the file stem"
+    )]
+    pub profile: Vec<String>,
+    #[arg(
+        long,
+        help = "Embed a build-id, the assembler version and the git commit in a note section"
+    )]
+    pub metadata: bool,
+    #[arg(
+        long,
+        requires = "metadata",
+        help = "Git commit to record instead of the current HEAD"
+    )]
+    pub git_commit: Option<String>,
+    #[arg(
+        long,
+        requires = "stdout",
+        conflicts_with_all = ["deploy_dir", "profile"],
+        help = "Read a single program from stdin instead of the project's src directory"
+    )]
+    pub stdin: bool,
+    #[arg(
+        long,
+        conflicts_with = "deploy_dir",
+        help = "Write the ELF to stdout instead of the deploy directory; diagnostics go to stderr"
+    )]
+    pub stdout: bool,
+    #[arg(
+        short = 'I',
+        long = "include-path",
+        value_name = "DIR",
+        help = "Search DIR for .include files after the including file's directory"
+    )]
+    pub include_paths: Vec<PathBuf>,
+    #[arg(
+        short = 'D',
+        long = "define",
+        value_name = "NAME[=VALUE]",
+        value_parser = parse_define,
+        help = "Define a constant for .if/.ifdef and .equ use; VALUE defaults to 1"
+    )]
+    pub defines: Vec<(String, i64)>,
+    #[arg(
+        long = "syscall",
+        value_name = "NAME",
+        help = "Only let `call` name this syscall; repeat for each allowed one [default: every runtime syscall]"
+    )]
+    pub syscalls: Vec<String>,
+    #[arg(long, help = "Fail the build if the assembler reports any warning")]
+    pub deny_warnings: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "error",
+        help = "On a bug in the assembler itself: report it as an error, or panic with a backtrace"
+    )]
+    pub internal_errors: InternalErrorsArg,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "human",
+        help = "How errors and warnings are printed on stderr: human, or json with one object per line"
+    )]
+    pub error_format: ErrorFormat,
+    #[arg(
+        long,
+        help = "Report the time spent in each phase of each program's build"
+    )]
+    pub timings: bool,
+    #[arg(
+        long = "frontend",
+        value_name = "EXT=COMMAND",
+        value_parser = parse_frontend,
+        conflicts_with = "verifiable",
+        help = "Translate .EXT sources to assembly by piping them through COMMAND"
+    )]
+    pub frontends: Vec<(String, String)>,
+    #[arg(
+        long,
+        conflicts_with_all = ["stdin", "stdout", "debug"],
+        help = "Record a manifest of the build for `sbpf verify-onchain`"
+    )]
+    pub verifiable: bool,
+    #[arg(
+        long,
+        requires = "verifiable",
+        help = "Run the verifiable build in this container image, pinned by digest (name@sha256:...)"
+    )]
+    pub image: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        conflicts_with = "stdout",
+        help = "Output format: text, or json describing each built program"
+    )]
+    pub output: OutputFormat,
+    #[arg(
+        long,
+        help = "Build every program, including those none of whose inputs changed since their last build"
+    )]
+    pub force: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["stdin", "stdout", "verifiable"],
+        help = "Rebuild whenever a source changes, reusing what was assembled from unchanged files"
+    )]
+    pub watch: bool,
+    #[arg(
+        long,
+        requires = "watch",
+        help = "Run `sbpf test` after each successful rebuild"
+    )]
+    pub test: bool,
+    /// Entry files of programs laid out differently from `src/<name>/<name>.s`,
+    /// from `sbpf.toml`.
+    #[arg(skip)]
+    pub entries: Vec<(String, PathBuf)>,
+    /// Lint levels and limits from `sbpf.toml`.
+    #[arg(skip)]
+    pub lints: LintConfig,
+}
+
+impl BuildArgs {
+    pub fn arch(&self) -> ArchArg {
+        self.arch.unwrap_or_default()
+    }
+
+    pub fn opt_level(&self) -> OptLevelArg {
+        self.opt_level.unwrap_or_default()
+    }
+}
+
+/// One program as `sbpf build --output json` reports it.
+#[derive(Serialize)]
+pub struct BuiltProgram {
+    pub name: String,
+    pub path: String,
+    pub duration_ms: f64,
+    pub executable_hash: String,
+    /// Manifest written by `--verifiable`.
+    pub manifest: Option<String>,
+    pub stats: BuildStats,
+    /// Per-phase breakdown of `duration_ms`, with `--timings`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<BuildTimings>,
+    /// Whether the build was skipped since no input changed; the other
+    /// fields then describe the existing output.
+    pub up_to_date: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildStats {
+    pub instructions: usize,
+    pub text_size: u64,
+    pub rodata_size: u64,
+    pub data_size: u64,
+    pub bss_size: u64,
+    pub relocations: usize,
+    pub elf_size: usize,
+}
+
+/// Milliseconds spent in each phase of a build. The assembler's phases are
+/// bracketed by reading the sources and writing the ELF out.
+#[derive(Serialize)]
+pub struct BuildTimings {
+    pub read_ms: f64,
+    pub preprocess_ms: f64,
+    pub parse_ms: f64,
+    pub link_ms: f64,
+    pub layout_ms: f64,
+    pub verify_ms: f64,
+    pub encode_ms: f64,
+    pub elf_ms: f64,
+    pub write_ms: f64,
 }
 
-#[derive(Clone, Copy, ValueEnum, Default)]
+impl BuildTimings {
+    fn new(read: Duration, phases: &PhaseTimings, write: Duration) -> Self {
+        Self {
+            read_ms: millis(read),
+            preprocess_ms: millis(phases.preprocess),
+            parse_ms: millis(phases.parse),
+            link_ms: millis(phases.link),
+            layout_ms: millis(phases.layout),
+            verify_ms: millis(phases.verify),
+            encode_ms: millis(phases.encode),
+            elf_ms: millis(phases.elf),
+            write_ms: millis(write),
+        }
+    }
+
+    /// Each phase with its name, in the order they run.
+    fn phases(&self) -> [(&'static str, f64); 9] {
+        [
+            ("read", self.read_ms),
+            ("preprocess", self.preprocess_ms),
+            ("parse", self.parse_ms),
+            ("link", self.link_ms),
+            ("layout", self.layout_ms),
+            ("verify", self.verify_ms),
+            ("encode", self.encode_ms),
+            ("elf", self.elf_ms),
+            ("write", self.write_ms),
+        ]
+    }
+}
+
+impl std::fmt::Display for BuildTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<String> = self
+            .phases()
+            .iter()
+            .map(|(phase, ms)| format!("   {:<10} {:>9.3}ms", phase, ms))
+            .collect();
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+/// `duration` in milliseconds, to the microsecond.
+pub(crate) fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+impl From<&AssemblyStats> for BuildStats {
+    fn from(stats: &AssemblyStats) -> Self {
+        Self {
+            instructions: stats.instruction_count,
+            text_size: stats.text_size,
+            rodata_size: stats.rodata_size,
+            data_size: stats.data_size,
+            bss_size: stats.bss_size,
+            relocations: stats.relocation_count,
+            elf_size: stats.elf_size,
+        }
+    }
+}
+
+/// Parse `NAME=VALUE`, or a bare `NAME` meaning 1.
+fn parse_define(text: &str) -> Result<(String, i64), String> {
+    let (name, value) = text.split_once('=').unwrap_or((text, "1"));
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return Err(format!("'{}' is not a valid constant name", name));
+    }
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).map(|value| value as i64),
+        None => value.parse(),
+    };
+    let value = parsed.map_err(|_| format!("'{}' is not a number", value))?;
+    Ok((name.to_string(), value))
+}
+
+/// Parse `EXT=COMMAND`.
+fn parse_frontend(text: &str) -> Result<(String, String), String> {
+    let (extension, command) = text
+        .split_once('=')
+        .ok_or_else(|| format!("'{}' is not of the form EXT=COMMAND", text))?;
+    let extension = extension.trim_start_matches('.');
+    if extension.is_empty() || command.trim().is_empty() {
+        return Err(format!("'{}' is not of the form EXT=COMMAND", text));
+    }
+    if extension == "s" {
+        return Err(".s sources are always assembled as they are".to_string());
+    }
+    Ok((extension.to_string(), command.to_string()))
+}
+
+/// A front-end run as an external command. The source is piped to the
+/// command, which is passed the source path as its last argument, and the
+/// assembly it prints is built in its place. On failure, whatever it printed
+/// to stderr is reported as the error.
+///
+/// Lines of the output aren't mapped back to the source, so diagnostics
+/// point into the generated assembly; `.file` and `.loc` directives in it
+/// still drive the debug line table.
+struct CommandFrontend {
+    extension: String,
+    command: String,
+}
+
+impl Frontend for CommandFrontend {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn translate(&self, source: &str, path: &str) -> Result<Translation, Vec<FrontendError>> {
+        let fail = |message: String| vec![FrontendError::new(message)];
+        let mut words = self.command.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(words)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| fail(format!("failed to run: {}", e)))?;
+        // A command that exits without reading its input is judged by its
+        // exit status instead.
+        if let Some(mut stdin) = child.stdin.take()
+            && let Err(e) = stdin.write_all(source.as_bytes())
+            && e.kind() != io::ErrorKind::BrokenPipe
+        {
+            return Err(fail(format!("failed to write source: {}", e)));
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| fail(format!("failed to run: {}", e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(fail(match stderr.trim() {
+                "" => format!("exited with {}", output.status),
+                message => message.to_string(),
+            }));
+        }
+        let assembly = String::from_utf8(output.stdout)
+            .map_err(|_| fail("printed assembly that is not UTF-8".to_string()))?;
+        Ok(Translation::from_assembly(assembly))
+    }
+}
+
+/// Front-ends for `--frontend` arguments.
+/// The syscalls `call` may name: exactly `names`, or every runtime syscall if
+/// none are given.
+fn syscall_registry(names: &[String]) -> Result<SyscallRegistry> {
+    if names.is_empty() {
+        return Ok(SyscallRegistry::default());
+    }
+    SyscallRegistry::new(names.iter().cloned()).map_err(Error::msg)
+}
+
+fn frontend_registry(frontends: &[(String, String)]) -> FrontendRegistry {
+    let mut registry = FrontendRegistry::new();
+    for (extension, command) in frontends {
+        registry.register(CommandFrontend {
+            extension: extension.clone(),
+            command: command.clone(),
+        });
+    }
+    registry
+}
+
+/// Name given to source read from stdin in diagnostics and debug info.
+const STDIN_PATH: &str = "<stdin>";
+
+/// Set inside the container of a verifiable build to the image it runs in,
+/// so the manifest written there records it.
+const VERIFIABLE_IMAGE_ENV: &str = "SBPF_VERIFIABLE_IMAGE";
+
+#[derive(Debug, Clone, Copy, ValueEnum, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ArchArg {
     V0,
+    V1,
+    V2,
     #[default]
     V3,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum, Default, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum OptLevelArg {
+    #[default]
+    #[value(name = "0")]
+    O0,
+    #[value(name = "1")]
+    O1,
+    #[value(name = "2")]
+    O2,
+}
+
+impl TryFrom<u8> for OptLevelArg {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, String> {
+        match level {
+            0 => Ok(Self::O0),
+            1 => Ok(Self::O1),
+            2 => Ok(Self::O2),
+            _ => Err(format!(
+                "invalid optimization level {}, expected 0, 1 or 2",
+                level
+            )),
+        }
+    }
+}
+
+impl From<OptLevelArg> for OptimizationConfig {
+    fn from(arg: OptLevelArg) -> Self {
+        match arg {
+            OptLevelArg::O0 => OptimizationConfig::disabled(),
+            OptLevelArg::O1 => OptimizationConfig::enabled(),
+            OptLevelArg::O2 => OptimizationConfig::enabled().with_level(OptLevel::Aggressive),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, Default)]
+pub enum InternalErrorsArg {
+    #[default]
+    Error,
+    Panic,
+}
+
+impl From<InternalErrorsArg> for InternalErrors {
+    fn from(arg: InternalErrorsArg) -> Self {
+        match arg {
+            InternalErrorsArg::Error => InternalErrors::Error,
+            InternalErrorsArg::Panic => InternalErrors::Panic,
+        }
+    }
+}
+
 impl From<ArchArg> for SbpfArch {
     fn from(arg: ArchArg) -> Self {
         match arg {
             ArchArg::V0 => SbpfArch::V0,
+            ArchArg::V1 => SbpfArch::V1,
+            ArchArg::V2 => SbpfArch::V2,
             ArchArg::V3 => SbpfArch::V3,
         }
     }
@@ -55,6 +490,14 @@ pub trait AsDiagnostic<FileId> {
     fn to_diagnostic(&self) -> Diagnostic<FileId>;
 }
 
+fn codespan_severity(severity: Severity) -> diagnostic::Severity {
+    match severity {
+        Severity::Error => diagnostic::Severity::Error,
+        Severity::Warning => diagnostic::Severity::Warning,
+        Severity::Note => diagnostic::Severity::Note,
+    }
+}
+
 impl AsDiagnostic<()> for CompileError {
     fn to_diagnostic(&self) -> Diagnostic<()> {
         match self {
@@ -62,14 +505,14 @@ impl AsDiagnostic<()> for CompileError {
                 span,
                 original_span,
                 ..
-            } => Diagnostic::error()
+            } => Diagnostic::new(codespan_severity(self.severity()))
                 .with_message(self.to_string())
                 .with_labels(vec![
                     Label::primary((), span.start..span.end).with_message(self.label()),
                     Label::secondary((), original_span.start..original_span.end)
                         .with_message("previous definition is here"),
                 ]),
-            _ => Diagnostic::error()
+            _ => Diagnostic::new(codespan_severity(self.severity()))
                 .with_message(self.to_string())
                 .with_labels(vec![
                     Label::primary((), self.span().start..self.span().end)
@@ -79,12 +522,22 @@ impl AsDiagnostic<()> for CompileError {
     }
 }
 
-/// Render assembly errors against original source files using the FileRegistry.
+/// Render assembly errors and warnings against original source files using
+/// the FileRegistry, each at its own severity, or print them as JSON.
 ///
 /// Each error's `SourceOrigin` tells us which original file and line the error
 /// came from, even if it was in a macro expansion or an included file.
-fn emit_assembler_errors(assemble_errors: &AssembleErrors) -> Result<()> {
+pub(crate) fn emit_assembler_errors(
+    assemble_errors: &AssembleErrors,
+    format: ErrorFormat,
+) -> Result<()> {
     let registry = &assemble_errors.file_registry;
+    if format == ErrorFormat::Json {
+        for assembler_error in &assemble_errors.errors {
+            json_diagnostic(assembler_error, registry).emit()?;
+        }
+        return Ok(());
+    }
 
     // Build a codespan SimpleFiles from the FileRegistry
     let mut files = SimpleFiles::new();
@@ -104,49 +557,78 @@ fn emit_assembler_errors(assemble_errors: &AssembleErrors) -> Result<()> {
     for assembler_error in &assemble_errors.errors {
         let error = &assembler_error.error;
 
-        if let Some(ref origin) = assembler_error.origin {
-            // We have a resolved source origin -- point into the original file
-            if let Some(&cs_file_id) = file_id_map.get(&(origin.file_id.index())) {
-                let line_start = registry.line_byte_offset(origin.file_id, origin.line);
-                let line_len = registry.line_length(origin.file_id, origin.line);
-                let line_end = line_start + line_len;
-
-                // If we have column info, highlight from that column to end of line.
-                // Otherwise highlight the whole line.
-                let highlight_start = if let Some(col) = assembler_error.column {
-                    (line_start + col).min(line_end)
-                } else {
-                    line_start
-                };
-
-                let mut diagnostic = Diagnostic::error()
-                    .with_message(error.to_string())
-                    .with_labels(vec![
-                        Label::primary(cs_file_id, highlight_start..line_end)
-                            .with_message(error.label()),
-                    ]);
-
-                // Add macro expansion chain as notes
-                let mut notes = Vec::new();
-                build_expansion_notes(origin, registry, &mut notes);
-                if !notes.is_empty() {
-                    diagnostic = diagnostic.with_notes(notes);
-                }
-
-                term::emit_to_write_style(&mut writer.lock(), &config, &files, &diagnostic)?;
-            } else {
-                // File not in registry (shouldn't happen), fall back to text-only
-                eprintln!("error: {}", error);
-            }
-        } else {
+        let Some((origin, span)) = locate(assembler_error, registry) else {
             // No origin -- preprocessor error without file context, just print the message
-            eprintln!("error: {}", error);
+            eprintln!("{}: {}", error.severity(), error);
+            continue;
+        };
+
+        // Add macro expansion chain as notes
+        let mut notes = Vec::new();
+        build_expansion_notes(origin, registry, &mut notes);
+        if let Some(suggestion) = error.suggestion() {
+            notes.push(format!("help: {}", suggestion));
         }
+
+        let diagnostic = Diagnostic::new(codespan_severity(error.severity()))
+            .with_message(error.to_string())
+            .with_labels(vec![
+                Label::primary(file_id_map[&origin.file_id.index()], span)
+                    .with_message(error.label()),
+            ])
+            .with_notes(notes);
+
+        term::emit_to_write_style(&mut writer.lock(), &config, &files, &diagnostic)?;
     }
 
     Ok(())
 }
 
+/// Where an error points in the original files: its origin, and the bytes
+/// from its column to the end of its line, or the whole line without a
+/// column. None for errors without file context.
+fn locate<'a>(
+    assembler_error: &'a AssemblerError,
+    registry: &FileRegistry,
+) -> Option<(&'a SourceOrigin, Range<usize>)> {
+    let origin = assembler_error.origin.as_ref()?;
+    // A file missing from the registry shouldn't happen; treat it as no context.
+    registry.file_ids().find(|&id| id == origin.file_id)?;
+    let line_start = registry.line_byte_offset(origin.file_id, origin.line);
+    let line_end = line_start + registry.line_length(origin.file_id, origin.line);
+    let highlight_start = match assembler_error.column {
+        Some(col) => (line_start + col).min(line_end),
+        None => line_start,
+    };
+    Some((origin, highlight_start..line_end))
+}
+
+/// An assembler error as `--error-format json` prints it.
+fn json_diagnostic(assembler_error: &AssemblerError, registry: &FileRegistry) -> JsonDiagnostic {
+    let error = &assembler_error.error;
+    let mut diagnostic = JsonDiagnostic {
+        code: error.code(),
+        severity: error.severity().as_str(),
+        message: error.to_string(),
+        suggestions: error.suggestion().into_iter().collect(),
+        ..Default::default()
+    };
+    if let Some((origin, span)) = locate(assembler_error, registry) {
+        let line_start = registry.line_byte_offset(origin.file_id, origin.line);
+        diagnostic.file = Some(registry.path(origin.file_id).to_string());
+        diagnostic.line = Some(origin.line);
+        diagnostic.column = Some(span.start - line_start + 1);
+        diagnostic.labels = vec![JsonLabel {
+            message: error.label().to_string(),
+            span: span.clone(),
+            primary: true,
+        }];
+        diagnostic.span = Some(span);
+        build_expansion_notes(origin, registry, &mut diagnostic.notes);
+    }
+    diagnostic
+}
+
 /// Build notes describing the macro expansion chain for an error.
 fn build_expansion_notes(origin: &SourceOrigin, registry: &FileRegistry, notes: &mut Vec<String>) {
     if let Some(ref expansion) = origin.macro_expansion {
@@ -165,102 +647,170 @@ fn build_expansion_notes(origin: &SourceOrigin, registry: &FileRegistry, notes:
     }
 }
 
-pub fn build(args: BuildArgs) -> Result<()> {
-    // Set src/out directory
-    let src = "src";
-    let deploy = args.deploy_dir.as_deref().unwrap_or("deploy");
-
-    // Create necessary directories
-    create_dir_all(deploy)?;
-    // Function to compile assembly with preprocessing (includes + macros)
-    fn compile_assembly(src: &str, deploy: &str, debug: bool, arch: SbpfArch) -> Result<()> {
-        let source_code = std::fs::read_to_string(src)
-            .map_err(|e| Error::msg(format!("Failed to read '{}': {}", src, e)))?;
-
-        // Build assembler options
-        let debug_mode = if debug {
-            let filename = Path::new(src)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown.s");
-            let directory = Path::new(src)
-                .parent()
-                .and_then(|p| p.canonicalize().ok())
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_else(|| ".".to_string());
-            Some(DebugMode {
-                filename: filename.to_string(),
-                directory,
-            })
-        } else {
-            None
-        };
-
-        let options = AssemblerOption {
-            arch,
-            debug_mode,
-            ..AssemblerOption::default()
-        };
-        let assembler = Assembler::new(options);
-        let resolver = FsFileResolver::new();
+/// Assemble one program with preprocessing (includes + macros), printing
+/// diagnostics to stderr on failure. `sources` are `(path, source)` pairs; a
+/// program with more than one is assembled file by file and linked, with the
+/// first providing the entrypoint. Sources with a front-end's extension are
+/// translated by it first.
+#[allow(clippy::too_many_arguments)]
+fn compile_assembly(
+    sources: &[(String, String)],
+    debug: bool,
+    arch: SbpfArch,
+    optimization: OptimizationConfig,
+    build_info: Option<BuildInfo>,
+    include_paths: &[PathBuf],
+    defines: &[(String, i64)],
+    frontends: FrontendRegistry,
+    syscalls: SyscallRegistry,
+    deny_warnings: bool,
+    lints: &LintConfig,
+    internal_errors: InternalErrors,
+    error_format: ErrorFormat,
+    cache: Option<&ParseCache>,
+) -> Result<Artifacts> {
+    let src = &sources[0].0;
+    // Build assembler options
+    let debug_mode = if debug {
+        let filename = Path::new(src)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown.s");
+        let directory = Path::new(src)
+            .parent()
+            .and_then(|p| p.canonicalize().ok())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string());
+        Some(DebugMode {
+            filename: filename.to_string(),
+            directory,
+        })
+    } else {
+        None
+    };
 
-        let result = assembler.assemble_with_preprocess(&source_code, src, Some(&resolver));
+    let options = AssemblerOption {
+        arch,
+        debug_mode,
+        build_info,
+        optimization,
+        include_paths: include_paths.to_vec(),
+        defines: defines.to_vec(),
+        frontends,
+        syscalls,
+        internal_errors,
+        lints: lints.clone(),
+        ..Default::default()
+    };
+    let mut assembler = Assembler::new(options);
+    if let Some(cache) = cache {
+        assembler = assembler.with_cache(cache.clone());
+    }
+    let resolver = FsFileResolver::with_include_paths(include_paths.to_vec());
 
-        let bytecode = match result {
-            Ok(bytecode) => bytecode,
-            Err(assemble_errors) => {
-                emit_assembler_errors(&assemble_errors)?;
-                return Err(Error::msg("Compilation failed"));
+    let result = if let [(src, source_code)] = sources {
+        assembler.assemble_full_with_preprocess(source_code, src, Some(&resolver))
+    } else {
+        let mut objects = Vec::new();
+        let mut failed = false;
+        for (src, source_code) in sources {
+            match assembler.assemble_object(source_code, src, Some(&resolver)) {
+                Ok(object) => objects.push(object),
+                Err(assemble_errors) => {
+                    emit_assembler_errors(&assemble_errors, error_format)?;
+                    failed = true;
+                }
             }
-        };
-
-        // write bytecode to <filename>.so
-        let output_path = Path::new(deploy).join(
-            Path::new(src)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .replace(".s", ".so"),
-        );
+        }
+        if failed {
+            return Err(Error::msg("Compilation failed"));
+        }
+        assembler.link(objects)
+    };
 
-        std::fs::write(output_path, bytecode)?;
-        Ok(())
-    }
-
-    // Function to check if keypair file exists.
-    fn has_keypair_file(dir: &Path) -> bool {
-        if dir.exists() && dir.is_dir() {
-            match fs::read_dir(dir) {
-                Ok(entries) => entries.filter_map(Result::ok).any(|entry| {
-                    entry
-                        .path()
-                        .file_name()
-                        .and_then(|name| name.to_str())
-                        .map(|name| name.ends_with("-keypair.json"))
-                        .unwrap_or(false)
-                }),
-                Err(_) => false,
+    match result {
+        Ok(artifacts) => {
+            if let Some(warnings) = &artifacts.located_warnings {
+                emit_assembler_errors(warnings, error_format)?;
             }
-        } else {
-            false
+            let count = artifacts.warnings.len();
+            if deny_warnings && count > 0 {
+                return Err(Error::msg(format!(
+                    "Compilation failed: {} warning{} denied by --deny-warnings",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                )));
+            }
+            let denied = artifacts
+                .warnings
+                .iter()
+                .filter(|warning| lints.is_denied(&warning.code()))
+                .count();
+            if denied > 0 {
+                return Err(Error::msg(format!(
+                    "Compilation failed: {} warning{} denied by {}",
+                    denied,
+                    if denied == 1 { "" } else { "s" },
+                    CONFIG_FILE
+                )));
+            }
+            Ok(artifacts)
+        }
+        Err(assemble_errors) => {
+            emit_assembler_errors(&assemble_errors, error_format)?;
+            Err(Error::msg("Compilation failed"))
         }
     }
+}
 
-    // Check if keypair file exists. If not, create one.
-    let deploy_path = Path::new(deploy);
-    if !has_keypair_file(deploy_path) {
-        let project_path = std::env::current_dir()?;
-        let project_name = project_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("program");
-        let mut rng = rand::rng();
-        fs::write(
-            deploy_path.join(format!("{}-keypair.json", project_name)),
-            serde_json::json!(SigningKey::generate(&mut rng).to_keypair_bytes()[..]).to_string(),
-        )?;
+pub fn build(mut args: BuildArgs) -> Result<()> {
+    ProjectConfig::load()?.apply_build(&mut args);
+    if args.stdin {
+        return build_stdin(&args, build_info(&args));
+    }
+    if let Some(image) = &args.image {
+        return build_in_container(&args, image, build_info(&args));
+    }
+    if args.watch {
+        return watch(&args);
     }
+    let programs = build_programs(&args)?;
+    if args.output.is_json() {
+        print_json(&serde_json::json!({ "programs": programs }))?;
+    }
+    Ok(())
+}
+
+fn build_info(args: &BuildArgs) -> Option<BuildInfo> {
+    args.metadata.then(|| BuildInfo {
+        git_commit: args.git_commit.clone().or_else(current_git_commit),
+    })
+}
+
+/// Build every program under `src`, returning what was built. Progress goes
+/// to stderr when stdout is taken by the ELF or by JSON output.
+pub fn build_programs(args: &BuildArgs) -> Result<Vec<BuiltProgram>> {
+    build_programs_cached(args, None)
+}
+
+/// Like [`build_programs`], reusing assembly results from `cache` for
+/// sources and objects that haven't changed since they were cached.
+pub fn build_programs_cached(
+    args: &BuildArgs,
+    cache: Option<&ParseCache>,
+) -> Result<Vec<BuiltProgram>> {
+    if !args.profile.is_empty() && matches!(args.opt_level(), OptLevelArg::O0) {
+        return Err(Error::msg("--profile requires -O1 or -O2"));
+    }
+    let build_info = build_info(args);
+
+    // Set src/out directory
+    let src = "src";
+    let deploy = args.deploy_dir.as_deref().unwrap_or("deploy");
+
+    let mut profiles = load_profiles(&args.profile)?;
+    let frontends = frontend_registry(&args.frontends);
+    let syscalls = syscall_registry(&args.syscalls)?;
 
     // Processing directories
     let src_path = Path::new(src);
@@ -271,30 +821,441 @@ pub fn build(args: BuildArgs) -> Result<()> {
             src, e
         ))
     })?;
+    let mut programs = Vec::new();
+    for (name, entry_file) in &args.entries {
+        if !entry_file.is_file() {
+            return Err(Error::msg(format!(
+                "Entry file '{}' of program \"{}\" in {} not found",
+                entry_file.display(),
+                name,
+                CONFIG_FILE
+            )));
+        }
+        let dir = entry_file.parent().unwrap_or(Path::new("."));
+        let sources = program_sources(dir, entry_file, &args.include_paths, &frontends)?;
+        programs.push((name.clone(), sources));
+    }
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir()
             && let Some(subdir) = path.file_name().and_then(|name| name.to_str())
+            && !args.entries.iter().any(|(name, _)| name == subdir)
+            && let Some(entry_file) = program_entry(&path, &frontends)
         {
-            let asm_file = format!("{}/{}/{}.s", src, subdir, subdir);
-            if Path::new(&asm_file).exists() {
-                println!(
-                    "⚡️ Building \"{}\"{}",
-                    subdir,
-                    if args.debug { " (debug)" } else { "" }
-                );
-                let start = Instant::now();
-                compile_assembly(&asm_file, deploy, args.debug, args.arch.into())?;
-                let duration = start.elapsed();
-                println!(
-                    "✅ \"{}\" built successfully in {}ms!",
-                    subdir,
-                    duration.as_micros() as f64 / 1000.0
-                );
+            let sources = program_sources(&path, &entry_file, &args.include_paths, &frontends)?;
+            programs.push((subdir.to_string(), sources));
+        }
+    }
+
+    if args.stdout {
+        if programs.len() != 1 {
+            return Err(Error::msg(format!(
+                "--stdout needs exactly one program, found {}",
+                programs.len()
+            )));
+        }
+    } else {
+        // Create necessary directories
+        create_dir_all(deploy)?;
+
+        // Every program needs a keypair to deploy to.
+        for (name, _) in &programs {
+            ensure_keypair(Path::new(deploy), name)?;
+        }
+    }
+
+    // Programs are skipped when nothing they read, including files they
+    // include from shared libraries, changed since their last build.
+    // Verifiable builds always run so the manifest describes this build.
+    let incremental = !args.force && !args.stdout && !args.verifiable;
+    let project = Path::new(".");
+    let mut state = BuildState::load(project);
+    let options = options_fingerprint(args, &build_info);
+
+    let mut built = Vec::new();
+    for (name, files) in programs {
+        let status = |message: String| {
+            if args.stdout || args.output.is_json() {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+        };
+        let mut optimization = OptimizationConfig::from(args.opt_level());
+        if let Some(profile) = profiles.remove(&name) {
+            optimization = optimization.with_profile(profile);
+        }
+        let output = Path::new(deploy).join(format!("{}.so", name));
+        let inputs = build_inputs(&files, &args.include_paths, &args.profile)?;
+        let reason = match state.freshness(&name, &options, &inputs, &output) {
+            Freshness::UpToDate(record) if incremental => {
+                status(format!(
+                    "⏭️  Skipping \"{}\": no input changed since its last build",
+                    name
+                ));
+                built.push(BuiltProgram {
+                    name,
+                    path: record.output.clone(),
+                    duration_ms: 0.0,
+                    executable_hash: record.executable_hash.clone(),
+                    manifest: None,
+                    stats: record.stats.clone(),
+                    timings: None,
+                    up_to_date: true,
+                });
+                continue;
             }
+            Freshness::Stale(reason) if incremental => format!(" ({})", reason),
+            _ => String::new(),
+        };
+        status(format!(
+            "⚡️ Building \"{}\"{}{}",
+            name,
+            if args.debug { " (debug)" } else { "" },
+            reason
+        ));
+        let start = Instant::now();
+        let sources = files
+            .iter()
+            .map(|file| {
+                let source = read_source(file).map_err(|e| {
+                    Error::msg(format!("Failed to read '{}': {}", file.display(), e))
+                })?;
+                Ok((file.to_string_lossy().to_string(), source))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let read_time = start.elapsed();
+        let artifacts = compile_assembly(
+            &sources,
+            args.debug,
+            args.arch().into(),
+            optimization,
+            build_info.clone(),
+            &args.include_paths,
+            &args.defines,
+            frontends.clone(),
+            syscalls.clone(),
+            args.deny_warnings,
+            &args.lints,
+            args.internal_errors.into(),
+            args.error_format,
+            cache,
+        )?;
+        let write_start = Instant::now();
+        let path = if args.stdout {
+            write_stdout(&artifacts.elf)?;
+            "<stdout>".to_string()
+        } else {
+            // write bytecode to <filename>.so
+            fs::write(&output, &artifacts.elf)?;
+            write_error_constants(Path::new(deploy), &name, &artifacts.errors)?;
+            output.to_string_lossy().into_owned()
+        };
+        let write_time = write_start.elapsed();
+        let mut manifest_file = None;
+        if args.verifiable {
+            let manifest = VerifiableManifest {
+                program: name.clone(),
+                sbpf_version: env!("CARGO_PKG_VERSION").to_string(),
+                arch: value_name(args.arch()),
+                opt_level: value_name(args.opt_level()),
+                defines: args.defines.clone(),
+                image: std::env::var(VERIFIABLE_IMAGE_ENV).ok(),
+                inputs: inputs.clone(),
+                executable_hash: executable_hash(&artifacts.elf),
+            };
+            let path = manifest_path(Path::new(deploy), &name);
+            fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+            status(format!(
+                "🔒 Recorded executable hash {} in {}",
+                manifest.executable_hash,
+                path.display()
+            ));
+            manifest_file = Some(path.to_string_lossy().into_owned());
+        }
+        let duration = start.elapsed();
+        status(format!(
+            "✅ \"{}\" built successfully in {}ms! ({} instructions, {} bytes)",
+            name,
+            millis(duration),
+            artifacts.stats.instruction_count,
+            artifacts.stats.elf_size
+        ));
+        let timings = args
+            .timings
+            .then(|| BuildTimings::new(read_time, &artifacts.timings, write_time));
+        if let Some(timings) = &timings {
+            status(timings.to_string());
         }
+        let stats = BuildStats::from(&artifacts.stats);
+        if !args.stdout {
+            state.record(
+                &name,
+                ProgramRecord {
+                    options: options.clone(),
+                    inputs,
+                    output: path.clone(),
+                    output_sha256: sha256_hex(&artifacts.elf),
+                    executable_hash: executable_hash(&artifacts.elf),
+                    stats: stats.clone(),
+                },
+            );
+            state.save(project)?;
+        }
+        built.push(BuiltProgram {
+            name,
+            path,
+            duration_ms: millis(duration),
+            executable_hash: executable_hash(&artifacts.elf),
+            manifest: manifest_file,
+            stats,
+            timings,
+            up_to_date: false,
+        });
+    }
+
+    if let Some(name) = profiles.keys().next() {
+        return Err(Error::msg(format!(
+            "No program named \"{}\" to apply its profile to",
+            name
+        )));
     }
 
+    Ok(built)
+}
+
+/// Assemble a program read from stdin. Includes resolve relative to the
+/// current directory and nothing in the project is read or written.
+fn build_stdin(args: &BuildArgs, build_info: Option<BuildInfo>) -> Result<()> {
+    let start = Instant::now();
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    let source_code =
+        decode_source(&bytes).map_err(|e| Error::msg(format!("Failed to read stdin: {}", e)))?;
+    let read_time = start.elapsed();
+
+    let artifacts = compile_assembly(
+        &[(STDIN_PATH.to_string(), source_code)],
+        args.debug,
+        args.arch().into(),
+        args.opt_level().into(),
+        build_info,
+        &args.include_paths,
+        &args.defines,
+        FrontendRegistry::new(),
+        syscall_registry(&args.syscalls)?,
+        args.deny_warnings,
+        &args.lints,
+        args.internal_errors.into(),
+        args.error_format,
+        None,
+    )?;
+    let write_start = Instant::now();
+    write_stdout(&artifacts.elf)?;
+    if args.timings {
+        eprintln!(
+            "{}",
+            BuildTimings::new(read_time, &artifacts.timings, write_start.elapsed())
+        );
+    }
     Ok(())
 }
+
+/// Rerun this build with `--verifiable` inside `image`, with the project
+/// mounted as the working directory, so the ELF and manifest come from the
+/// image's toolchain rather than the host's.
+fn build_in_container(args: &BuildArgs, image: &str, build_info: Option<BuildInfo>) -> Result<()> {
+    if !image.contains("@sha256:") {
+        return Err(Error::msg(format!(
+            "Image '{}' is not pinned; refer to it by digest, as in name@sha256:<digest>",
+            image
+        )));
+    }
+    let project = std::env::current_dir()?;
+    let mut command = std::process::Command::new("docker");
+    command
+        .args(["run", "--rm", "-v"])
+        .arg(format!("{}:/work", project.display()))
+        .args(["-w", "/work", "-e"])
+        .arg(format!("{}={}", VERIFIABLE_IMAGE_ENV, image))
+        .args([image, "sbpf", "build", "--verifiable", "-a"])
+        .arg(value_name(args.arch()))
+        .arg("-O")
+        .arg(value_name(args.opt_level()))
+        .arg("--output")
+        .arg(value_name(args.output));
+    for (name, value) in &args.defines {
+        command.arg("-D").arg(format!("{}={}", name, value));
+    }
+    for path in &args.include_paths {
+        command.arg("-I").arg(path);
+    }
+    for syscall in &args.syscalls {
+        command.arg("--syscall").arg(syscall);
+    }
+    if args.deny_warnings {
+        command.arg("--deny-warnings");
+    }
+    if args.timings {
+        command.arg("--timings");
+    }
+    for profile in &args.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if let Some(deploy_dir) = &args.deploy_dir {
+        command.arg("-d").arg(deploy_dir);
+    }
+    // git is not needed in the image: the commit is resolved here.
+    if let Some(build_info) = build_info {
+        command.arg("--metadata");
+        if let Some(commit) = build_info.git_commit {
+            command.arg("--git-commit").arg(commit);
+        }
+    }
+
+    args.output.status(format!("🐳 Building in {}", image));
+    let status = command
+        .status()
+        .map_err(|e| Error::msg(format!("Failed to run docker: {}", e)))?;
+    if !status.success() {
+        return Err(Error::msg("❌ Containerized build failed"));
+    }
+    Ok(())
+}
+
+/// The sources of a program, the files they `.include` and any profiles,
+/// each with its sha256.
+fn build_inputs(
+    sources: &[PathBuf],
+    include_paths: &[PathBuf],
+    profiles: &[String],
+) -> Result<Vec<ManifestInput>> {
+    let resolver = FsFileResolver::with_include_paths(include_paths.to_vec());
+    let mut pending: Vec<PathBuf> = sources.to_vec();
+    let mut seen = HashSet::new();
+    let mut inputs = Vec::new();
+    while !pending.is_empty() {
+        let file = pending.remove(0);
+        if !seen.insert(file.canonicalize()?) {
+            continue;
+        }
+        let bytes = fs::read(&file)
+            .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file.display(), e)))?;
+        let source = decode_source(&bytes)
+            .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file.display(), e)))?;
+        for include in include_directives(&source) {
+            if let Some(path) = resolver.locate(include, &file.to_string_lossy()) {
+                pending.push(path);
+            }
+        }
+        inputs.push(ManifestInput {
+            path: file.to_string_lossy().into_owned(),
+            sha256: sha256_hex(&bytes),
+        });
+    }
+    for profile in profiles {
+        let bytes = fs::read(profile)
+            .map_err(|e| Error::msg(format!("Failed to read profile '{}': {}", profile, e)))?;
+        inputs.push(ManifestInput {
+            path: profile.clone(),
+            sha256: sha256_hex(&bytes),
+        });
+    }
+    Ok(inputs)
+}
+
+/// Hash of everything besides the inputs that changes what a program is
+/// built into, so changing an option rebuilds it.
+fn options_fingerprint(args: &BuildArgs, build_info: &Option<BuildInfo>) -> String {
+    let options = serde_json::json!({
+        "sbpf_version": env!("CARGO_PKG_VERSION"),
+        "debug": args.debug,
+        "arch": value_name(args.arch()),
+        "opt_level": value_name(args.opt_level()),
+        "defines": args.defines,
+        "include_paths": args.include_paths,
+        "frontends": args.frontends,
+        "syscalls": args.syscalls,
+        "deny_warnings": args.deny_warnings,
+        "lints": format!("{:?}", args.lints),
+        "metadata": build_info.as_ref().map(|info| &info.git_commit),
+    });
+    sha256_hex(options.to_string().as_bytes())
+}
+
+/// The name clap accepts for an enum value, as recorded in a manifest.
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn write_stdout(elf: &[u8]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(elf)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Read execution profiles, keyed by the program each one belongs to.
+fn load_profiles(paths: &[String]) -> Result<HashMap<String, ExecutionProfile>> {
+    let mut profiles = HashMap::new();
+    for path in paths {
+        let name = Path::new(path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| Error::msg(format!("Invalid profile path '{}'", path)))?;
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::msg(format!("Failed to read profile '{}': {}", path, e)))?;
+        let profile = serde_json::from_str(&contents)
+            .map_err(|e| Error::msg(format!("Invalid profile '{}': {}", path, e)))?;
+        profiles.insert(name.to_string(), profile);
+    }
+    Ok(profiles)
+}
+
+/// Commit of the repository in the current directory, if there is one.
+fn current_git_commit() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_diagnostic() {
+        let source = ".globl e\n.text\ne:\n  ja nowhere\n  exit\n";
+        let errors = Assembler::new(AssemblerOption::default())
+            .assemble_full_with_preprocess(source, "main.s", None)
+            .unwrap_err();
+        let json = serde_json::to_value(json_diagnostic(&errors.errors[0], &errors.file_registry))
+            .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "undefined-label",
+                "severity": "error",
+                "message": "Undefined label 'nowhere'",
+                "file": "main.s",
+                "line": 4,
+                "column": 3,
+                "span": { "start": 20, "end": 30 },
+                "labels": [{
+                    "message": "Undefined label",
+                    "span": { "start": 20, "end": 30 },
+                    "primary": true,
+                }],
+                "notes": [],
+                "suggestions": [],
+            })
+        );
+    }
+}
@@ -1,4 +1,84 @@
-use serde::Deserialize;
+pub use sbpf_vm::{loader::LoadedProgram, solana_syscalls::SolanaSyscalls};
+
+use {
+    anyhow::{Error, Result},
+    clap::ValueEnum,
+    sbpf_assembler::{Assembler, AssemblerOption},
+    sbpf_common::program_error::{ErrorDefinition, ErrorRegistry},
+    sbpf_disassembler::program::Program,
+    sbpf_vm::loader::load_elf,
+    serde::{Deserialize, Serialize},
+    std::{fmt::Display, ops::Range},
+};
+
+/// What a command prints on stdout: text for people, or a single JSON
+/// document for scripts.
+#[derive(Clone, Copy, ValueEnum, Default, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+
+    /// Print a progress line. With JSON output it goes to stderr, so stdout
+    /// carries nothing but the document.
+    pub fn status(self, message: impl Display) {
+        match self {
+            OutputFormat::Text => println!("{}", message),
+            OutputFormat::Json => eprintln!("{}", message),
+        }
+    }
+}
+
+/// How errors and warnings are printed on stderr: rendered for people, or
+/// one JSON object per line for editors and CI.
+#[derive(Clone, Copy, ValueEnum, Default, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// A diagnostic as `--error-format json` prints it. `span` is a range of
+/// bytes in `file`; `line` and `column` count from 1.
+#[derive(Serialize, Default, Debug)]
+pub struct JsonDiagnostic {
+    pub code: String,
+    pub severity: &'static str,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<usize>,
+    pub span: Option<Range<usize>>,
+    pub labels: Vec<JsonLabel>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonLabel {
+    pub message: String,
+    pub span: Range<usize>,
+    pub primary: bool,
+}
+
+impl JsonDiagnostic {
+    /// Print the diagnostic on one line of stderr.
+    pub fn emit(&self) -> Result<()> {
+        eprintln!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
 
 #[derive(Deserialize, Debug)]
 pub struct SolanaConfig {
@@ -6,7 +86,65 @@ pub struct SolanaConfig {
     pub active_release_dir: String,
 }
 
+/// Assemble `source` with `options` and decode the ELF back into the
+/// instructions the VM runs, so syscalls and jump targets resolve exactly as
+/// in a built program.
+pub fn assemble_for_vm(source: &str, options: AssemblerOption) -> Result<LoadedProgram, String> {
+    let elf = Assembler::new(options).assemble(source).map_err(|errors| {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    })?;
+    load_for_vm(&elf)
+}
+
+/// Decode a built program into the form the local VM runs.
+pub fn load_for_vm(elf: &[u8]) -> Result<LoadedProgram, String> {
+    load_elf(elf).map_err(|e| e.to_string())
+}
+
+/// The custom errors listed in the `errors` of an Anchor-style IDL.
+pub fn load_error_registry(path: &str) -> Result<ErrorRegistry> {
+    #[derive(Deserialize)]
+    struct Idl {
+        #[serde(default)]
+        errors: Vec<ErrorDefinition>,
+    }
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", path, e)))?;
+    let idl: Idl = serde_json::from_str(&text)
+        .map_err(|e| Error::msg(format!("'{}' is not a valid IDL: {}", path, e)))?;
+    Ok(idl.errors.into_iter().collect())
+}
+
+/// The custom errors a built program declares with `.error`.
+pub fn embedded_error_registry(elf: &[u8]) -> ErrorRegistry {
+    Program::from_bytes(elf)
+        .map(|program| program.error_table())
+        .unwrap_or_default()
+}
+
+/// Bytes written as hex digits, with any whitespace between them.
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.split_whitespace().collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
 pub const PROGRAM: &str = r#".globl entrypoint
+
+.instruction hello, 0
+  .account payer, signer, writable
+.endinstruction
+
 entrypoint:
   lddw r1, message
   mov64 r2, 14
@@ -46,6 +184,23 @@ pub const README: &str = r#"# default_project_name
 
 Created with [sbpf](https://github.com/blueshift-gg/sbpf)"#;
 
+pub const SBPF_TOML: &str = r#"# Settings for `sbpf build`, `deploy`, `test` and `e2e`. Flags given on the
+# command line take precedence.
+
+[build]
+arch = "v3"
+opt-level = 0
+# include-paths = ["lib"]
+# syscalls = ["sol_log_"]
+
+[deploy]
+url = "localhost"
+
+# [lint]
+# max-stack = 4096
+# unused-label = "allow"
+"#;
+
 pub const GITIGNORE: &str = r#"build/**/*
 deploy/**/*
 node_modules
@@ -65,6 +220,7 @@ pub const PACKAGE_JSON: &str = r#"{
   "main": "index.js",
   "license": "MIT",
   "scripts": {
+    "pretest": "sbpf idlgen",
     "test": "KEYPAIR=$(solana config get | grep Keypair | cut -b 15-) && cross-env SIGNER=$(cat $KEYPAIR) mocha --import=tsx tests/**/*.ts"
   },
   "dependencies": {
@@ -82,11 +238,9 @@ pub const PACKAGE_JSON: &str = r#"{
 "#;
 
 pub const TS_TESTS: &str = r#"
-import { Connection, Keypair, Transaction, TransactionInstruction } from "@solana/web3.js"
-import programSeed from "../deploy/default_project_name-keypair.json"
+import { Connection, Keypair, Transaction } from "@solana/web3.js"
+import { hello } from "../client/default_project_name"
 
-const programKeypair = Keypair.fromSecretKey(new Uint8Array(programSeed))
-const program = programKeypair.publicKey
 const signerSeed = JSON.parse(process.env.SIGNER!)
 const signer = Keypair.fromSecretKey(new Uint8Array(signerSeed))
 
@@ -119,15 +273,7 @@ const signAndSend = async(tx: Transaction): Promise<string> => {
 describe('hello solana tests', () => {
     it('Logs out "Hello, Solana!"', async () => {
         const tx = new Transaction()
-        tx.instructions.push(
-            new TransactionInstruction({
-            keys: [{
-                pubkey: signer.publicKey,
-                isSigner: true,
-                isWritable: true
-            }],
-            programId: program
-        }))
+        tx.instructions.push(hello({ payer: signer.publicKey }))
         await signAndSend(tx).then(confirm).then(log);
     });
 });
@@ -157,7 +303,8 @@ edition = "2021"
 [dev-dependencies]
 mollusk-svm = "{{MOLLUSK_SVM_VERSION}}"
 solana-account = "{{SOLANA_ACCOUNT_VERSION}}"
-solana-address = "{{SOLANA_ADDRESS_VERSION}}"
+serde_json = "{{SERDE_JSON_VERSION}}"
+solana-address = { version = "{{SOLANA_ADDRESS_VERSION}}", features = ["decode"] }
 solana-instruction = "{{SOLANA_INSTRUCTION_VERSION}}"
 
 [features]
@@ -168,14 +315,19 @@ mod tests {
     use mollusk_svm::{result::Check, Mollusk};
     use solana_address::Address;
     use solana_instruction::Instruction;
+    use std::collections::HashMap;
+
+    /// The program id `sbpf` recorded for a program in deploy/program-ids.json.
+    fn program_id(name: &str) -> Address {
+        let ids: HashMap<String, String> =
+            serde_json::from_str(&std::fs::read_to_string("deploy/program-ids.json").unwrap())
+                .unwrap();
+        ids[name].parse().expect("invalid program id")
+    }
 
     #[test]
     fn test_hello_world() {
-        let program_id_keypair_bytes = std::fs::read("deploy/default_project_name-keypair.json").unwrap()
-            [..32]
-            .try_into()
-            .expect("slice with incorrect length");
-        let program_id = Address::new_from_array(program_id_keypair_bytes);
+        let program_id = program_id("default_project_name");
 
         let instruction = Instruction::new_with_bytes(
             program_id,
@@ -0,0 +1,183 @@
+use {
+    crate::commands::build::{ArchArg, BuildArgs, OptLevelArg},
+    anyhow::{Error, Result},
+    sbpf_analyze::SecurityRule,
+    sbpf_assembler::{LINTS, LintConfig},
+    serde::Deserialize,
+    std::{collections::BTreeMap, fs, io, path::PathBuf},
+};
+
+/// The project configuration file, read from the directory commands run in.
+pub const CONFIG_FILE: &str = "sbpf.toml";
+
+/// Settings from `sbpf.toml`. Flags given on the command line take
+/// precedence over them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub build: BuildConfig,
+    #[serde(default)]
+    pub deploy: DeployConfig,
+    /// Levels of the assembler's warnings and the security rules, and the
+    /// limits functions are checked against, for `sbpf build`, `sbpf lint`
+    /// and the language server.
+    #[serde(default)]
+    pub lint: LintConfig,
+    /// Programs whose entry file isn't `src/<name>/<name>.s`, by name.
+    #[serde(default)]
+    pub programs: BTreeMap<String, ProgramConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildConfig {
+    pub arch: Option<ArchArg>,
+    pub opt_level: Option<OptLevelArg>,
+    /// Searched after any `-I` directories.
+    pub include_paths: Vec<PathBuf>,
+    /// The only syscalls programs may `call`, unless `--syscall` is given.
+    pub syscalls: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeployConfig {
+    /// Cluster URL or moniker passed to `solana program deploy`.
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramConfig {
+    /// Assembled first; the other sources in its directory are linked with it.
+    pub entry: PathBuf,
+}
+
+impl ProjectConfig {
+    /// The configuration in the current directory, or the default one if
+    /// there is no `sbpf.toml`.
+    pub fn load() -> Result<Self> {
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::msg(format!(
+                "Failed to read '{}': {}",
+                CONFIG_FILE, e
+            ))),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)
+            .map_err(|e| Error::msg(format!("Invalid '{}': {}", CONFIG_FILE, e)))?;
+        if let Some(lint) = config.lint.levels.keys().find(|lint| !is_lint(lint)) {
+            return Err(Error::msg(format!(
+                "Invalid '{}': unknown lint '{}'",
+                CONFIG_FILE, lint
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Fill in whatever `args` leaves unset from the `[build]` and
+    /// `[programs]` tables.
+    pub fn apply_build(&self, args: &mut BuildArgs) {
+        args.arch = args.arch.or(self.build.arch);
+        args.opt_level = args.opt_level.or(self.build.opt_level);
+        for path in &self.build.include_paths {
+            if !args.include_paths.contains(path) {
+                args.include_paths.push(path.clone());
+            }
+        }
+        if args.syscalls.is_empty()
+            && let Some(syscalls) = &self.build.syscalls
+        {
+            args.syscalls = syscalls.clone();
+        }
+        args.entries = self
+            .programs
+            .iter()
+            .map(|(name, program)| (name.clone(), program.entry.clone()))
+            .collect();
+        args.lints = self.lint.clone();
+    }
+
+    /// The cluster to deploy to: `url` if given, then `[deploy]`'s, then the
+    /// local validator.
+    pub fn deploy_url(&self, url: Option<&str>) -> String {
+        url.or(self.deploy.url.as_deref())
+            .unwrap_or("localhost")
+            .to_string()
+    }
+}
+
+/// Whether `name` is an assembler warning or a security rule.
+fn is_lint(name: &str) -> bool {
+    LINTS.contains(&name) || SecurityRule::ALL.iter().any(|rule| rule.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_fills_unset_build_args() {
+        let config = ProjectConfig::parse(
+            r#"
+            [build]
+            arch = "v2"
+            opt-level = 2
+            include-paths = ["lib", "vendor"]
+            syscalls = ["sol_log_", "sol_log_64_"]
+
+            [deploy]
+            url = "devnet"
+
+            [lint]
+            max-stack = 512
+            unused-label = "allow"
+            missing-signer-check = "deny"
+
+            [programs.vault]
+            entry = "src/vault/main.s"
+            "#,
+        )
+        .unwrap();
+
+        let mut args = BuildArgs {
+            arch: Some(ArchArg::V3),
+            include_paths: vec![PathBuf::from("vendor")],
+            ..Default::default()
+        };
+        config.apply_build(&mut args);
+        assert!(matches!(args.arch, Some(ArchArg::V3)));
+        assert!(matches!(args.opt_level, Some(OptLevelArg::O2)));
+        assert_eq!(
+            args.include_paths,
+            [PathBuf::from("vendor"), PathBuf::from("lib")]
+        );
+        assert_eq!(args.syscalls, ["sol_log_", "sol_log_64_"]);
+        assert_eq!(
+            args.entries,
+            [("vault".to_string(), PathBuf::from("src/vault/main.s"))]
+        );
+        assert_eq!(args.lints.max_stack, Some(512));
+        assert!(args.lints.is_allowed("unused-label"));
+        assert!(args.lints.is_denied("missing-signer-check"));
+
+        assert_eq!(config.deploy_url(None), "devnet");
+        assert_eq!(config.deploy_url(Some("mainnet-beta")), "mainnet-beta");
+        assert_eq!(ProjectConfig::default().deploy_url(None), "localhost");
+
+        for invalid in [
+            "[build]\narch = \"v9\"",
+            "[build]\nopt-level = 3",
+            "[bulid]",
+            "[lint]\nunused-lable = \"allow\"",
+            "[lint]\nunused-label = \"off\"",
+        ] {
+            assert!(ProjectConfig::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
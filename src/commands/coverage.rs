@@ -0,0 +1,249 @@
+use {
+    super::{common::load_for_vm, run::display_path},
+    anyhow::{Error, Result},
+    sbpf_common::instruction::Instruction,
+    sbpf_disassembler::line_table::LineTable,
+    sbpf_vm::coverage::{Coverage, program_key, read_coverage},
+    serde::Serialize,
+    std::{
+        collections::BTreeMap,
+        fmt::Write,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Where `sbpf test --coverage` has the runtime record coverage.
+pub const COVERAGE_DIR: &str = "target/sbpf-coverage";
+
+/// Line coverage of the programs a test run executed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Source file, then each line instructions were assembled from and
+    /// whether any of them ran.
+    pub files: BTreeMap<String, BTreeMap<u32, bool>>,
+    /// Programs without debug info, which have no lines to report.
+    pub skipped: Vec<String>,
+}
+
+/// One file's totals, as `sbpf test --coverage --output json` reports them.
+#[derive(Debug, Serialize)]
+pub struct FileSummary<'a> {
+    pub file: &'a str,
+    pub lines: usize,
+    pub covered: usize,
+    pub missed: Vec<u32>,
+}
+
+impl CoverageReport {
+    /// Map the coverage recorded in `coverage_dir` onto the source lines of
+    /// each program in `deploy_dir`. Programs that never ran count as fully
+    /// missed.
+    pub fn collect(deploy_dir: &Path, coverage_dir: &Path) -> Result<Self> {
+        let read_error =
+            |path: &Path, e| Error::msg(format!("Failed to read '{}': {}", path.display(), e));
+        let mut report = Self::default();
+        for path in deployed_programs(deploy_dir)? {
+            let elf = fs::read(&path).map_err(|e| read_error(&path, e))?;
+            let Some(lines) = LineTable::from_elf_bytes(&elf) else {
+                report.skipped.push(path.display().to_string());
+                continue;
+            };
+            let program = load_for_vm(&elf).map_err(Error::msg)?;
+            let count = program.instructions.len();
+            let coverage = read_coverage(coverage_dir, &program_key(&elf), count)
+                .map_err(|e| read_error(coverage_dir, e))?
+                .unwrap_or_else(|| Coverage::new(count));
+            report.add_program(&program.instructions, &lines, &coverage);
+        }
+        Ok(report)
+    }
+
+    /// Mark the lines of a program covered where any instruction assembled
+    /// from them ran.
+    fn add_program(
+        &mut self,
+        instructions: &[Instruction],
+        lines: &LineTable,
+        coverage: &Coverage,
+    ) {
+        let mut offset = 0;
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if let Some(source) = lines.locate(offset)
+                && source.line > 0
+            {
+                *self
+                    .files
+                    .entry(display_path(&source.file))
+                    .or_default()
+                    .entry(source.line)
+                    .or_default() |= coverage.is_covered(pc);
+            }
+            offset += instruction.get_size();
+        }
+    }
+
+    pub fn summaries(&self) -> Vec<FileSummary<'_>> {
+        self.files
+            .iter()
+            .map(|(file, lines)| FileSummary {
+                file,
+                lines: lines.len(),
+                covered: lines.values().filter(|covered| **covered).count(),
+                missed: lines
+                    .iter()
+                    .filter(|(_, covered)| !**covered)
+                    .map(|(line, _)| *line)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The report as an lcov tracefile. Lines are counted as run once, as
+    /// coverage records whether instructions ran but not how often.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (file, lines) in &self.files {
+            writeln!(out, "TN:\nSF:{}", file).expect("writing to a String cannot fail");
+            for (line, covered) in lines {
+                writeln!(out, "DA:{},{}", line, u8::from(*covered))
+                    .expect("writing to a String cannot fail");
+            }
+            writeln!(
+                out,
+                "LF:{}\nLH:{}\nend_of_record",
+                lines.len(),
+                lines.values().filter(|covered| **covered).count()
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out
+    }
+
+    /// Lines covered per file, with the ranges of lines missed.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let (mut total, mut covered) = (0, 0);
+        for summary in self.summaries() {
+            writeln!(
+                out,
+                "  {}: {}/{} lines ({:.1}%){}",
+                summary.file,
+                summary.covered,
+                summary.lines,
+                percent(summary.covered, summary.lines),
+                if summary.missed.is_empty() {
+                    String::new()
+                } else {
+                    format!(", missed {}", line_ranges(&summary.missed))
+                }
+            )
+            .expect("writing to a String cannot fail");
+            total += summary.lines;
+            covered += summary.covered;
+        }
+        writeln!(
+            out,
+            "  total: {}/{} lines ({:.1}%)",
+            covered,
+            total,
+            percent(covered, total)
+        )
+        .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+/// The `.so` files in `dir`, sorted.
+pub(crate) fn deployed_programs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut programs: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", dir.display(), e)))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "so"))
+        .collect();
+    programs.sort();
+    Ok(programs)
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
+
+/// Sorted line numbers as ranges: `3-5, 9`.
+fn line_ranges(lines: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+        sbpf_vm::solana_syscalls::SolanaSyscalls,
+    };
+
+    #[test]
+    fn test_line_coverage() {
+        let source =
+            ".globl e\ne:\n  jeq r1, 0, skip\n  mov64 r0, 1\n  mov64 r0, 2\nskip:\n  exit\n";
+        let options = AssemblerOption::default().with_debug_mode(DebugMode {
+            filename: "prog.s".to_string(),
+            directory: String::new(),
+        });
+        let elf = Assembler::new(options).assemble(source).unwrap();
+        let program = load_for_vm(&elf).unwrap();
+        let mut vm = program
+            .clone()
+            .into_vm(Vec::new(), SolanaSyscalls::default());
+        vm.registers[1] = 0;
+        vm.enable_coverage();
+        vm.run().unwrap();
+
+        let mut report = CoverageReport::default();
+        report.add_program(
+            &program.instructions,
+            &LineTable::from_elf_bytes(&elf).unwrap(),
+            &vm.take_coverage().unwrap(),
+        );
+        assert_eq!(
+            report.files["prog.s"],
+            BTreeMap::from([(3, true), (4, false), (5, false), (7, true)])
+        );
+        assert_eq!(
+            report.to_lcov(),
+            "TN:\nSF:prog.s\nDA:3,1\nDA:4,0\nDA:5,0\nDA:7,1\nLF:4\nLH:2\nend_of_record\n"
+        );
+        assert_eq!(
+            report.render(),
+            "  prog.s: 2/4 lines (50.0%), missed 4-5\n  total: 2/4 lines (50.0%)\n"
+        );
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        assert_eq!(line_ranges(&[3, 4, 5, 9, 11, 12]), "3-5, 9, 11-12");
+        assert_eq!(line_ranges(&[]), "");
+    }
+}
@@ -24,12 +24,29 @@ pub struct DebugArgs {
     max_call_depth: usize,
     #[arg(long, default_value = "32768", help = "Heap size")]
     heap_size: usize,
+    #[arg(long, help = "Warp the clock to this slot before running")]
+    slot: Option<u64>,
+    #[arg(
+        long,
+        help = "Warp the clock to the first slot of this epoch before running"
+    )]
+    epoch: Option<u64>,
+    #[arg(long, help = "Set the clock's unix timestamp before running")]
+    unix_timestamp: Option<i64>,
     #[arg(long, help = "Run in adapter mode")]
     adapter: bool,
 }
 
 pub fn debug(args: DebugArgs) -> Result<()> {
-    let parsed = parse_input(&args.input)?;
+    let mut parsed = parse_input(&args.input)?;
+    // Command line clock overrides take precedence over the input file.
+    if args.slot.is_some() || args.epoch.is_some() {
+        parsed.clock.slot = args.slot;
+        parsed.clock.epoch = args.epoch;
+    }
+    if args.unix_timestamp.is_some() {
+        parsed.clock.unix_timestamp = args.unix_timestamp;
+    }
     let config = RuntimeConfig {
         compute_budget: args.compute_unit_limit,
         max_call_depth: args.max_call_depth,
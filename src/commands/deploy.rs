@@ -1,72 +1,537 @@
 use {
+    super::{
+        build::millis,
+        common::{OutputFormat, print_json},
+        config::ProjectConfig,
+        keys::{keypair_path, program_id, read_keypair},
+        replay::BPF_LOADER_UPGRADEABLE,
+        rpc::{Rpc, RpcAccount},
+    },
     anyhow::{Error, Result},
     clap::Args,
-    std::{io, path::Path, process::Command},
+    object::{
+        Endianness, FileFlags, Object, ObjectSection, ObjectSegment, SegmentFlags, elf::PF_W,
+        read::elf::ElfFile64,
+    },
+    serde::Serialize,
+    serde_json::Value,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+        time::Instant,
+    },
 };
 
+/// Size of the `UpgradeableLoaderState::ProgramData` header that precedes
+/// the ELF in a program data account.
+const PROGRAM_DATA_METADATA_LEN: usize = 45;
+
 #[derive(Args, Default)]
 pub struct DeployArgs {
     pub name: Option<String>,
     pub url: Option<String>,
+    #[arg(
+        short = 'k',
+        long,
+        value_name = "KEYPAIR",
+        help = "Keypair that pays for the deploy [default: the Solana CLI's keypair]"
+    )]
+    pub keypair: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "KEYPAIR",
+        help = "Upgrade authority of the program [default: the fee payer]"
+    )]
+    pub authority: Option<PathBuf>,
+    #[arg(
+        long = "final",
+        help = "Make the program immutable once deployed; it can never be upgraded again"
+    )]
+    pub finalize: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with each program's address and signature"
+    )]
+    pub output: OutputFormat,
+    #[arg(long, help = "Report how long each program took to deploy")]
+    pub timings: bool,
 }
 
-fn deploy_program(program_name: &str, url: &str) -> Result<(), Error> {
-    let program_id_file = format!("./deploy/{}-keypair.json", program_name);
-    let program_file = format!("./deploy/{}.so", program_name);
+/// One program as `sbpf deploy --output json` reports it.
+#[derive(Serialize)]
+pub struct DeployedProgram {
+    pub name: String,
+    pub program_id: Option<String>,
+    pub signature: Option<String>,
+    /// Whether an existing program was upgraded rather than deployed anew.
+    pub upgraded: bool,
+    /// Buffer account an upgrade was written to.
+    pub buffer: Option<String>,
+    /// Whether the program was made immutable with `--final`.
+    pub immutable: bool,
+    /// Time the deploy took, with `--timings`. The buffer writes and the
+    /// deploy or upgrade transaction are sent by `solana`, so they are not
+    /// timed apart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+}
 
-    if Path::new(&program_file).exists() {
-        println!("🔄 Deploying \"{}\"", program_name);
+/// What is at a program's address, which decides how it is deployed.
+#[derive(Debug, PartialEq, Eq)]
+enum ProgramState {
+    /// Nothing yet: deploy it.
+    Undeployed,
+    /// Deployed with the upgradeable loader and still upgradeable by
+    /// `authority`. `capacity` is how large an ELF its program data holds.
+    Upgradeable { authority: String, capacity: usize },
+    /// Deployed with the upgradeable loader and made final.
+    Immutable,
+    /// Owned by another loader or program, which can't be upgraded.
+    Foreign { owner: String },
+}
 
-        let status = Command::new("solana")
-            .arg("program")
-            .arg("deploy")
-            .arg(&program_file)
-            .arg("--program-id")
-            .arg(&program_id_file)
+/// The state of the program at `account`. `fetch` looks up its program data
+/// account.
+fn program_state(
+    account: Option<&RpcAccount>,
+    fetch: impl FnOnce(&str) -> Result<Option<RpcAccount>>,
+) -> Result<ProgramState> {
+    let Some(account) = account else {
+        return Ok(ProgramState::Undeployed);
+    };
+    if account.owner != BPF_LOADER_UPGRADEABLE {
+        return Ok(ProgramState::Foreign {
+            owner: account.owner.clone(),
+        });
+    }
+    let data = account.data()?;
+    // UpgradeableLoaderState::Program { programdata_address }
+    let address = data
+        .get(4..36)
+        .filter(|_| data.starts_with(&2u32.to_le_bytes()))
+        .ok_or_else(|| Error::msg("Not an upgradeable program account"))?;
+    let address = bs58::encode(address).into_string();
+    let program_data = fetch(&address)?
+        .ok_or_else(|| Error::msg(format!("Program data {} does not exist", address)))?
+        .data()?;
+    // UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }
+    if program_data.len() < PROGRAM_DATA_METADATA_LEN
+        || !program_data.starts_with(&3u32.to_le_bytes())
+    {
+        return Err(Error::msg(format!(
+            "{} is not a program data account",
+            address
+        )));
+    }
+    if program_data[12] == 0 {
+        return Ok(ProgramState::Immutable);
+    }
+    Ok(ProgramState::Upgradeable {
+        authority: bs58::encode(&program_data[13..45]).into_string(),
+        capacity: program_data.len() - PROGRAM_DATA_METADATA_LEN,
+    })
+}
+
+/// Runs `solana program` subcommands against one cluster with one fee
+/// payer.
+struct SolanaCli<'a> {
+    url: &'a str,
+    keypair: Option<&'a Path>,
+}
+
+impl SolanaCli<'_> {
+    fn program(&self, subcommand: &str) -> Command {
+        let mut command = Command::new("solana");
+        command
+            .args(["program", subcommand])
             .arg("-u")
-            .arg(url)
-            .status()?;
+            .arg(self.url);
+        if let Some(keypair) = self.keypair {
+            command.arg("-k").arg(keypair);
+        }
+        command
+    }
 
-        if !status.success() {
-            eprintln!("Failed to deploy program for {}", program_name);
-            return Err(Error::new(io::Error::other("❌ Deployment failed")));
+    /// Run `command` with `--output json` and return its document. Its
+    /// progress goes to stderr.
+    fn run(&self, mut command: Command, subcommand: &str) -> Result<Value> {
+        let result = command
+            .args(["--output", "json"])
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| {
+                Error::msg(format!(
+                    "Failed to run `solana program {}`: {}",
+                    subcommand, e
+                ))
+            })?;
+        if !result.status.success() {
+            return Err(Error::msg(format!(
+                "❌ `solana program {}` failed",
+                subcommand
+            )));
         }
+        Ok(serde_json::from_slice(&result.stdout).unwrap_or(Value::Null))
+    }
 
-        println!("✅ \"{}\" deployed successfully!", program_name);
-    } else {
+    /// The address of a signer, `keypair` or the Solana CLI's default one.
+    fn address(&self, keypair: Option<&Path>) -> Result<String> {
+        let mut command = Command::new("solana");
+        command.arg("address");
+        if let Some(keypair) = keypair.or(self.keypair) {
+            command.arg("-k").arg(keypair);
+        }
+        let result = command
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| Error::msg(format!("Failed to run `solana address`: {}", e)))?;
+        if !result.status.success() {
+            return Err(Error::msg("❌ `solana address` failed"));
+        }
+        Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
+    }
+}
+
+fn field(report: &Value, key: &str) -> Option<String> {
+    report.get(key).and_then(Value::as_str).map(String::from)
+}
+
+/// Where a program keeps the writable data that only `sbpf-vm` loads: a
+/// `.bss` or `.data` section, which the loader rejects unless it is
+/// `.data.rel*`, or a writable segment of a v3 program.
+fn writable_data(elf: &[u8]) -> Result<Option<String>, Error> {
+    let elf = ElfFile64::<Endianness>::parse(elf)
+        .map_err(|e| Error::msg(format!("Failed to parse ELF: {}", e)))?;
+    if let Some(name) = elf
+        .sections()
+        .filter_map(|section| section.name().ok())
+        .find(|name| {
+            name.starts_with(".bss")
+                || (name.starts_with(".data") && !name.starts_with(".data.rel"))
+        })
+    {
+        return Ok(Some(name.to_string()));
+    }
+    let is_v3 = matches!(elf.flags(), FileFlags::Elf { e_flags, .. } if e_flags >= 3);
+    let writable_segment = elf.segments().any(
+        |segment| matches!(segment.flags(), SegmentFlags::Elf { p_flags } if p_flags & PF_W != 0),
+    );
+    Ok((is_v3 && writable_segment).then(|| "a writable segment".to_string()))
+}
+
+fn deploy_program(
+    program_name: &str,
+    args: &DeployArgs,
+    rpc: &Rpc,
+    solana: &SolanaCli,
+) -> Result<DeployedProgram, Error> {
+    let program_id_file = keypair_path(Path::new("./deploy"), program_name);
+    let program_file = format!("./deploy/{}.so", program_name);
+
+    if !Path::new(&program_file).exists() {
         eprintln!("Program file {} not found", program_file);
         return Err(Error::new(io::Error::new(
             io::ErrorKind::NotFound,
             "❌ Program file not found",
         )));
     }
+    let elf = fs::read(&program_file)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", program_file, e)))?;
+    if let Some(section) = writable_data(&elf)? {
+        anyhow::bail!(
+            "{} has writable data in {}, which the Solana program loader rejects; it only runs in sbpf-vm",
+            program_file,
+            section
+        );
+    }
+    let address = program_id(&read_keypair(&program_id_file)?);
+    let (_, accounts) = rpc.accounts(std::slice::from_ref(&address))?;
+    let state = program_state(accounts[0].as_ref(), |key| {
+        Ok(rpc.accounts(&[key.to_string()])?.1.remove(0))
+    })?;
 
-    Ok(())
+    let mut deployed = DeployedProgram {
+        name: program_name.to_string(),
+        program_id: Some(address.clone()),
+        signature: None,
+        upgraded: false,
+        buffer: None,
+        immutable: false,
+        duration_ms: None,
+    };
+    let start = Instant::now();
+    match state {
+        ProgramState::Undeployed => {
+            args.output
+                .status(format!("🔄 Deploying \"{}\"", program_name));
+            let mut command = solana.program("deploy");
+            command
+                .arg(&program_file)
+                .arg("--program-id")
+                .arg(&program_id_file);
+            if let Some(authority) = &args.authority {
+                command.arg("--upgrade-authority").arg(authority);
+            }
+            if args.finalize {
+                command.arg("--final");
+            }
+            let report = solana.run(command, "deploy").inspect_err(|_| {
+                eprintln!("Failed to deploy program for {}", program_name);
+            })?;
+            deployed.signature = field(&report, "signature");
+            deployed.immutable = args.finalize;
+            args.output
+                .status(format!("✅ \"{}\" deployed successfully!", program_name));
+        }
+        ProgramState::Upgradeable {
+            authority,
+            capacity,
+        } => {
+            let signer = solana.address(args.authority.as_deref())?;
+            if signer != authority {
+                return Err(Error::msg(format!(
+                    "❌ \"{}\" at {} can only be upgraded by {}, not {}; pass its keypair with --authority",
+                    program_name, address, authority, signer
+                )));
+            }
+            args.output
+                .status(format!("🔄 Upgrading \"{}\" at {}", program_name, address));
+            upgrade_program(
+                &program_file,
+                &address,
+                capacity,
+                args,
+                solana,
+                &mut deployed,
+            )?;
+            args.output
+                .status(format!("✅ \"{}\" upgraded successfully!", program_name));
+        }
+        ProgramState::Immutable => {
+            return Err(Error::msg(format!(
+                "❌ \"{}\" at {} is final and can't be upgraded; deploy it to a new keypair with `sbpf keys new {} --force`",
+                program_name, address, program_name
+            )));
+        }
+        ProgramState::Foreign { owner } => {
+            return Err(Error::msg(format!(
+                "❌ {} is owned by {}, not the upgradeable loader, so \"{}\" can't be deployed there",
+                address, owner, program_name
+            )));
+        }
+    }
+
+    if args.timings {
+        let duration_ms = millis(start.elapsed());
+        args.output
+            .status(format!("   deploy     {:>9.3}ms", duration_ms));
+        deployed.duration_ms = Some(duration_ms);
+    }
+    Ok(deployed)
 }
 
-fn deploy_all_programs(url: &str) -> Result<(), Error> {
-    let deploy_path = Path::new("deploy");
-
-    for entry in deploy_path.read_dir()? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file()
-            && path.extension().and_then(|ext| ext.to_str()) == Some("so")
-            && let Some(filename) = path.file_stem().and_then(|name| name.to_str())
-        {
-            deploy_program(filename, url)?;
-        }
+/// Write the new ELF to a buffer account, growing the program data first if
+/// it doesn't fit, then swap it in with an upgrade.
+fn upgrade_program(
+    program_file: &str,
+    address: &str,
+    capacity: usize,
+    args: &DeployArgs,
+    solana: &SolanaCli,
+    deployed: &mut DeployedProgram,
+) -> Result<()> {
+    let len = fs::metadata(program_file)?.len() as usize;
+    if len > capacity {
+        args.output.status(format!(
+            "📏 Extending program data by {} bytes",
+            len - capacity
+        ));
+        let mut command = solana.program("extend");
+        command.arg(address).arg((len - capacity).to_string());
+        solana.run(command, "extend")?;
+    }
+
+    let mut command = solana.program("write-buffer");
+    command.arg(program_file);
+    if let Some(authority) = &args.authority {
+        command.arg("--buffer-authority").arg(authority);
     }
+    let buffer = field(&solana.run(command, "write-buffer")?, "buffer")
+        .ok_or_else(|| Error::msg("`solana program write-buffer` didn't report the buffer"))?;
+    args.output.status(format!("📦 Wrote buffer {}", buffer));
+    deployed.buffer = Some(buffer.clone());
 
+    let mut command = solana.program("upgrade");
+    command.arg(&buffer).arg(address);
+    if let Some(authority) = &args.authority {
+        command.arg("--upgrade-authority").arg(authority);
+    }
+    let report = solana.run(command, "upgrade").inspect_err(|_| {
+        eprintln!(
+            "The buffer still holds the program's rent; `solana program close {}` recovers it",
+            buffer
+        );
+    })?;
+    deployed.signature = field(&report, "signature");
+    deployed.upgraded = true;
+
+    if args.finalize {
+        let mut command = solana.program("set-upgrade-authority");
+        command.arg(address).arg("--final");
+        if let Some(authority) = &args.authority {
+            command.arg("--upgrade-authority").arg(authority);
+        }
+        solana.run(command, "set-upgrade-authority")?;
+        deployed.immutable = true;
+        args.output
+            .status(format!("🔒 {} is now immutable", address));
+    }
     Ok(())
 }
 
+/// Deploy the named program, or every program in `deploy`, returning what
+/// was deployed. Programs already on the cluster are upgraded in place.
+pub fn deploy_programs(args: &DeployArgs) -> Result<Vec<DeployedProgram>, Error> {
+    let url = &ProjectConfig::load()?.deploy_url(args.url.as_deref());
+    let rpc = Rpc::new(url);
+    let solana = SolanaCli {
+        url,
+        keypair: args.keypair.as_deref(),
+    };
+
+    let names = match args.name.as_deref() {
+        Some(name) => vec![name.to_string()],
+        None => {
+            let mut names = Vec::new();
+            for entry in Path::new("deploy").read_dir()? {
+                let path = entry?.path();
+                if path.is_file()
+                    && path.extension().and_then(|ext| ext.to_str()) == Some("so")
+                    && let Some(name) = path.file_stem().and_then(|name| name.to_str())
+                {
+                    names.push(name.to_string());
+                }
+            }
+            names.sort();
+            names
+        }
+    };
+    names
+        .iter()
+        .map(|name| deploy_program(name, args, &rpc, &solana))
+        .collect()
+}
+
 pub fn deploy(args: DeployArgs) -> Result<(), Error> {
-    let url = args.url.as_deref().unwrap_or("localhost");
+    let deployed = deploy_programs(&args)?;
+    if args.output.is_json() {
+        print_json(&serde_json::json!({ "programs": deployed }))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        base64::{Engine, engine::general_purpose::STANDARD},
+        sbpf_assembler::{Assembler, AssemblerOption, SbpfArch},
+    };
+
+    fn account(owner: &str, data: &[u8]) -> RpcAccount {
+        RpcAccount {
+            lamports: 1,
+            owner: owner.to_string(),
+            data: (STANDARD.encode(data), "base64".to_string()),
+            executable: true,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_writable_data() {
+        let assemble = |arch, source| {
+            Assembler::new(AssemblerOption::default().with_arch(arch))
+                .assemble(source)
+                .unwrap()
+        };
+        let data = ".globl entrypoint\nentrypoint:\n  exit\n.data\ncounter: .quad 0\n";
+        let bss = ".globl entrypoint\nentrypoint:\n  exit\n.bss\nscratch: .zero 64\n";
+        let rodata = ".globl entrypoint\nentrypoint:\n  exit\n.rodata\nfee: .quad 5\n";
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V0, data)).unwrap(),
+            Some(".data".to_string())
+        );
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V3, data)).unwrap(),
+            Some("a writable segment".to_string())
+        );
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V0, bss)).unwrap(),
+            Some(".bss".to_string())
+        );
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V3, bss)).unwrap(),
+            Some("a writable segment".to_string())
+        );
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V0, rodata)).unwrap(),
+            None
+        );
+        assert_eq!(
+            writable_data(&assemble(SbpfArch::V3, rodata)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_program_state() {
+        let program_data_address = [7u8; 32];
+        let authority = [9u8; 32];
+        let mut program = 2u32.to_le_bytes().to_vec();
+        program.extend(program_data_address);
+        let program_data = |authority: Option<[u8; 32]>| {
+            let mut data = 3u32.to_le_bytes().to_vec();
+            data.extend(300u64.to_le_bytes());
+            data.push(authority.is_some() as u8);
+            data.extend(authority.unwrap_or_default());
+            data.extend([0; 1000]);
+            account(BPF_LOADER_UPGRADEABLE, &data)
+        };
+        let upgradeable = account(BPF_LOADER_UPGRADEABLE, &program);
 
-    if let Some(program_name) = args.name.as_deref() {
-        deploy_program(program_name, url)
-    } else {
-        deploy_all_programs(url)
+        assert_eq!(
+            program_state(None, |_| unreachable!()).unwrap(),
+            ProgramState::Undeployed
+        );
+        assert_eq!(
+            program_state(Some(&upgradeable), |key| {
+                assert_eq!(key, bs58::encode(program_data_address).into_string());
+                Ok(Some(program_data(Some(authority))))
+            })
+            .unwrap(),
+            ProgramState::Upgradeable {
+                authority: bs58::encode(authority).into_string(),
+                capacity: 1000,
+            }
+        );
+        assert_eq!(
+            program_state(Some(&upgradeable), |_| Ok(Some(program_data(None)))).unwrap(),
+            ProgramState::Immutable
+        );
+        assert_eq!(
+            program_state(
+                Some(&account("BPFLoader2111111111111111111111111111111111", b"")),
+                |_| unreachable!()
+            )
+            .unwrap(),
+            ProgramState::Foreign {
+                owner: "BPFLoader2111111111111111111111111111111111".to_string()
+            }
+        );
+        assert!(program_state(Some(&upgradeable), |_| Ok(None)).is_err());
     }
 }
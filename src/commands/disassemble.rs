@@ -1,13 +1,36 @@
 use {
+    super::{
+        build::ArchArg,
+        common::{ErrorFormat, JsonDiagnostic, JsonLabel, OutputFormat, print_json},
+    },
     anyhow::{Error, Result},
     clap::Args,
     either::Either,
-    sbpf_common::{inst_param::Number, instruction::AsmFormat, opcode::Opcode},
+    sbpf_analyze::decompile,
+    sbpf_assembler::{Assembler, AssemblerOption},
+    sbpf_common::{
+        build_note::BuildNote,
+        inst_param::Number,
+        instruction::{AsmFormat, Instruction},
+        opcode::Opcode,
+        program_error::ErrorRegistry,
+        version::SbpfVersion,
+    },
     sbpf_disassembler::{
+        demangle::RustDemangler,
+        entry::{EntryAnalysis, EntrySource, FunctionOrigin},
         errors::DisassemblerError,
         program::{Disassembly, Program},
+        relocation::RelocationType,
+        rodata::RodataSection,
+        symbol::Symbol,
+    },
+    sbpf_ir::{InputNode, control_flow_graph},
+    std::{
+        collections::{BTreeMap, HashSet},
+        fs::File,
+        io::{BufWriter, Read, Write},
     },
-    std::{collections::HashSet, fs::File, io::Read},
 };
 
 #[derive(Args)]
@@ -29,6 +52,65 @@ pub struct DisassembleArgs {
         help = "Output raw instructions without labels or formatting"
     )]
     pub raw: bool,
+    #[arg(
+        long,
+        help = "Explain how the entry address was determined and list function starts"
+    )]
+    pub entry_analysis: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["debug", "raw", "entry_analysis"],
+        help = "Print C-like pseudo-code instead of assembly (experimental)"
+    )]
+    pub decompile: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["debug", "entry_analysis", "decompile"],
+        help = "Show each instruction's raw bytes in a column before it"
+    )]
+    pub bytes: bool,
+    #[arg(
+        short = 'C',
+        long,
+        conflicts_with = "debug",
+        help = "Demangle Rust symbol names"
+    )]
+    pub demangle: bool,
+    #[arg(
+        short = 'a',
+        long,
+        value_enum,
+        conflicts_with_all = ["debug", "entry_analysis"],
+        help = "Decode as this architecture (v0-v3) instead of the one the ELF header records"
+    )]
+    pub arch: Option<ArchArg>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        conflicts_with_all = ["debug", "entry_analysis", "decompile"],
+        help = "Output format: text, or json listing each instruction with its offset and labels"
+    )]
+    pub output: OutputFormat,
+    #[arg(
+        long,
+        conflicts_with_all = ["debug", "raw", "entry_analysis", "decompile", "bytes"],
+        help = "Print the .symtab and .dynsym symbols instead of the disassembly"
+    )]
+    pub symbols: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["debug", "raw", "entry_analysis", "decompile", "bytes", "demangle", "symbols", "output"],
+        help = "Print source that re-assembles to the same .text and .rodata, checked before it is printed"
+    )]
+    pub asm: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "human",
+        help = "How decode errors are printed on stderr: human, or json with one object per line"
+    )]
+    pub error_format: ErrorFormat,
 }
 
 pub fn disassemble(args: DisassembleArgs) -> Result<(), Error> {
@@ -36,10 +118,25 @@ pub fn disassemble(args: DisassembleArgs) -> Result<(), Error> {
     let mut b = vec![];
     file.read_to_end(&mut b)?;
 
-    let program = match Program::from_bytes(b.as_ref()) {
+    if args.entry_analysis {
+        let mut analysis = match EntryAnalysis::from_bytes(&b) {
+            Ok(analysis) => analysis,
+            Err(errors) => {
+                report(&errors, args.error_format, &args.filename, None)?;
+                anyhow::bail!("failed to analyze ELF file");
+            }
+        };
+        if args.demangle {
+            analysis.demangle(&RustDemangler);
+        }
+        print!("{}", render_entry_analysis(&analysis));
+        return Ok(());
+    }
+
+    let mut program = match Program::from_bytes(b.as_ref()) {
         Ok(program) => program,
         Err(errors) => {
-            report(&errors);
+            report(&errors, args.error_format, &args.filename, None)?;
             anyhow::bail!("failed to parse ELF file");
         }
     };
@@ -55,14 +152,45 @@ pub fn disassemble(args: DisassembleArgs) -> Result<(), Error> {
         return Ok(());
     }
 
+    if args.demangle {
+        program.demangle(&RustDemangler);
+    }
+    if args.symbols {
+        if args.output.is_json() {
+            return print_json(&serde_json::to_value(&program.symbols)?);
+        }
+        print!("{}", render_symbols(&program.symbols));
+        return Ok(());
+    }
+    if let Some(arch) = args.arch {
+        program = program.with_version(arch.into());
+    }
+    if args.asm {
+        if format != AsmFormat::Default {
+            anyhow::bail!("--asm prints the assembler's own syntax, not --format llvm");
+        }
+        // Decoded a second time without resolving targets, for the values
+        // of `lddw`s that `to_ixs` takes for function addresses.
+        let raw = Program::from_bytes(b.as_ref())
+            .map_err(|_| anyhow::anyhow!("failed to parse ELF file"))?
+            .with_version(program.version());
+        print!(
+            "{}",
+            round_trip(program, raw, args.error_format, &args.filename)?
+        );
+        return Ok(());
+    }
+
     let entrypoint_offset = program.get_entrypoint_offset();
     // Keep the raw .text bytes to show the words that fail to decode.
-    let text = program
+    let text_entry = program
         .section_header_entries
         .iter()
-        .find(|e| e.label.eq(".text\0"))
-        .map(|e| e.data.clone())
-        .unwrap_or_default();
+        .find(|e| e.label.eq(".text\0"));
+    let text_offset = text_entry.map(|e| e.offset);
+    let text = text_entry.map(|e| e.data.clone()).unwrap_or_default();
+    let build_note = program.build_note();
+    let errors = program.error_table();
     let disassembled = match if args.raw {
         program.to_ixs_raw()
     } else {
@@ -70,184 +198,846 @@ pub fn disassemble(args: DisassembleArgs) -> Result<(), Error> {
     } {
         Ok(disassembled) => disassembled,
         Err(errors) => {
-            report(&errors);
+            report(&errors, args.error_format, &args.filename, text_offset)?;
             anyhow::bail!("failed to disassemble");
         }
     };
 
-    report(&disassembled.errors);
+    report(
+        &disassembled.errors,
+        args.error_format,
+        &args.filename,
+        text_offset,
+    )?;
 
-    print!(
-        "{}",
-        render_asm(
+    if args.output.is_json() {
+        let mut json = render_json(
             disassembled.value,
             entrypoint_offset,
-            &text,
+            build_note.as_ref(),
             format,
-            args.raw
-        )?
-    );
+            args.raw,
+            args.bytes.then_some(text.as_slice()),
+        )?;
+        json["errors"] = serde_json::to_value(errors.iter().collect::<Vec<_>>())?;
+        return print_json(&json);
+    }
+
+    if args.decompile {
+        print!(
+            "{}",
+            render_pseudo_code(disassembled.value, entrypoint_offset)
+        );
+        return Ok(());
+    }
+
+    // Large programs run to hundreds of thousands of lines, so they are
+    // written out as they are rendered rather than collected first.
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    if !args.raw
+        && let Some(note) = build_note
+    {
+        out.write_all(render_build_note(&note, "// ").as_bytes())?;
+    }
+    if !args.raw {
+        out.write_all(render_error_table(&errors, "// ").as_bytes())?;
+    }
+    write_asm(
+        &mut out,
+        disassembled.value,
+        entrypoint_offset,
+        &text,
+        format,
+        args.raw,
+        args.bytes,
+    )?;
+    out.flush()?;
     Ok(())
 }
 
-fn render_asm(
+/// Build metadata as `key: value` lines, each starting with `prefix`.
+pub fn render_build_note(note: &BuildNote, prefix: &str) -> String {
+    let mut output = format!("{prefix}build-id: {}\n", note.build_id_hex());
+    output.push_str(&format!(
+        "{prefix}assembler: sbpf {}\n",
+        note.assembler_version
+    ));
+    if let Some(commit) = &note.git_commit {
+        output.push_str(&format!("{prefix}git commit: {}\n", commit));
+    }
+    output
+}
+
+/// The custom errors a program declares, one `error <code> <name>: <message>`
+/// line each, starting with `prefix`. The value a program exits with to
+/// return one is its code.
+pub fn render_error_table(errors: &ErrorRegistry, prefix: &str) -> String {
+    errors
+        .iter()
+        .map(|error| match &error.message {
+            Some(message) => format!("{prefix}error {} {}: {}\n", error.code, error.name, message),
+            None => format!("{prefix}error {} {}\n", error.code, error.name),
+        })
+        .collect()
+}
+
+/// Build metadata as the JSON object `sbpf metadata` prints.
+pub fn build_note_json(note: &BuildNote) -> serde_json::Value {
+    serde_json::json!({
+        "build_id": note.build_id_hex(),
+        "assembler_version": note.assembler_version,
+        "git_commit": note.git_commit,
+    })
+}
+
+fn write_asm(
+    output: &mut impl Write,
     disassembly: Disassembly,
     entrypoint_offset: Option<u64>,
     text: &[u8],
     format: AsmFormat,
     raw: bool,
-) -> Result<String, Error> {
-    let mut output = String::new();
-
-    let print_error = |output: &mut String, indent: &str, e: &DisassemblerError| {
+    show_bytes: bool,
+) -> Result<(), Error> {
+    let print_error = |output: &mut dyn Write, indent: &str, e: &DisassemblerError| {
         if let DisassemblerError::BytecodeError { error, span } = e
             && let Some(opcode) = text.get(span.start / 8 * 8)
         {
-            output.push_str(&format!(
-                "{indent}// 0x{opcode:02x} is skipped due to error: {error}\n"
-            ));
+            writeln!(
+                output,
+                "{indent}// 0x{opcode:02x} is skipped due to error: {error}"
+            )
         } else {
-            output.push_str(&format!("{indent}// skipped due to error: {e}\n"));
+            writeln!(output, "{indent}// skipped due to error: {e}")
         }
     };
 
+    let mut offset = 0;
+    // Write one instruction, after its first word of bytes when they are
+    // shown. The second word of an lddw goes on a line of its own.
+    let mut write_ix = |output: &mut dyn Write,
+                        indent: &str,
+                        ix: &Either<Instruction, DisassemblerError>|
+     -> Result<(), Error> {
+        let size = match ix {
+            Either::Left(ix) => ix.get_size() as usize,
+            Either::Right(_) => 8,
+        };
+        let prefix = if show_bytes {
+            format!("{indent}{:<23}  ", hex_word(text, offset))
+        } else {
+            indent.to_string()
+        };
+        match ix {
+            Either::Left(ix) => writeln!(output, "{}{}", prefix, ix.to_asm(format)?)?,
+            Either::Right(e) => print_error(output, &prefix, e)?,
+        }
+        if show_bytes && size == 16 {
+            writeln!(output, "{}{}", indent, hex_word(text, offset + 8))?;
+        }
+        offset += size;
+        Ok(())
+    };
+
     if raw {
         for ix in &disassembly.instructions {
-            match ix {
-                Either::Left(ix) => output.push_str(&format!("{}\n", ix.to_asm(format)?)),
-                Either::Right(e) => print_error(&mut output, "", e),
-            }
+            write_ix(output, "", ix)?;
         }
     } else {
         let mut ixs = disassembly.instructions;
         let rodata = disassembly.rodata;
-
-        // Build position map
-        let positions: Vec<u64> = ixs
-            .iter()
-            .scan(0u64, |pos, ix| {
-                let current = *pos;
-                *pos += match ix {
-                    Either::Left(ix) => ix.get_size(),
-                    Either::Right(_) => 8,
-                };
-                Some(current)
-            })
-            .collect();
-
-        // Collect all target positions
-        let mut jmp_targets: HashSet<u64> = HashSet::new();
-        let mut fn_targets: HashSet<u64> = HashSet::new();
-        for (idx, ix) in ixs.iter().enumerate() {
-            let Either::Left(ix) = ix else { continue };
-            if ix.is_jump()
-                && let Some(Either::Right(off)) = &ix.off
-            {
-                let target_idx = (idx as i64 + 1 + *off as i64) as usize;
-                if let Some(&target_pos) = positions.get(target_idx) {
-                    jmp_targets.insert(target_pos);
-                }
-            }
-
-            if ix.opcode == Opcode::Call
-                && let Some(Either::Right(Number::Int(imm))) = &ix.imm
-            {
-                let target_idx = (idx as i64 + 1 + *imm) as usize;
-                if let Some(&target_pos) = positions.get(target_idx) {
-                    fn_targets.insert(target_pos);
-                }
-            }
-        }
+        let labels = label_instructions(
+            &mut ixs,
+            entrypoint_offset,
+            rodata.as_ref(),
+            &disassembly.symbols,
+        );
 
         // Output .globl entrypoint directive at the top
-        output.push_str(".globl entrypoint\n");
+        writeln!(output, ".globl entrypoint")?;
+        writeln!(output, ".text")?;
 
         let mut in_labeled_block = false;
-        for (idx, ix) in ixs.iter_mut().enumerate() {
-            let pos = positions[idx];
-            let is_fn_target = fn_targets.contains(&pos);
-            let is_jmp_target = jmp_targets.contains(&pos);
-            let is_entrypoint = entrypoint_offset == Some(pos);
-
+        for (ix, labels) in ixs.iter().zip(&labels) {
             // Output labels if this position is a target or entrypoint
-            if is_fn_target || is_jmp_target || is_entrypoint {
-                output.push('\n');
-                if is_entrypoint {
-                    output.push_str("entrypoint:\n");
-                }
-                if is_fn_target && !is_entrypoint {
-                    output.push_str(&format!("fn_{:04x}:\n", pos));
-                }
-                if is_jmp_target {
-                    output.push_str(&format!("jmp_{:04x}:\n", pos));
+            if !labels.is_empty() {
+                writeln!(output)?;
+                for label in labels {
+                    writeln!(output, "{}:", label)?;
                 }
                 in_labeled_block = true;
             }
 
             // Indent instructions under labels
             let indent = if in_labeled_block { "  " } else { "" };
+            write_ix(output, indent, ix)?;
+        }
 
-            let ix = match ix {
-                Either::Left(ix) => ix,
-                Either::Right(e) => {
-                    print_error(&mut output, indent, e);
-                    continue;
-                }
-            };
+        // Output rodata section if present
+        if let Some(rodata) = rodata
+            && rodata.has_items()
+        {
+            writeln!(output)?;
+            output.write_all(rodata.to_asm().as_bytes())?;
+        }
+    }
 
-            // Replace numeric values with labels for display.
-            if ix.is_jump()
-                && let Some(Either::Right(off)) = &ix.off
-            {
-                let target_idx = (idx as i64 + 1 + *off as i64) as usize;
-                if let Some(&target_pos) = positions.get(target_idx) {
-                    ix.off = Some(Either::Left(format!("jmp_{:04x}", target_pos)));
-                }
+    Ok(())
+}
+
+/// Sections `--asm` output must reproduce byte for byte.
+const ROUND_TRIP_SECTIONS: &[&str] = &[".text", ".rodata"];
+
+/// Immediates used at least this often are given a `.equ` name in `--asm`
+/// output, unless they are small enough to read at a glance.
+const RECURRING_CONSTANT_USES: usize = 3;
+
+/// Source for `program` that assembles back to its `.text` and `.rodata`,
+/// checked by assembling it. `raw` is the same program, decoded again by
+/// `to_ixs_raw`. Decode errors are reported in `format` against `file`.
+fn round_trip(
+    program: Program,
+    raw: Program,
+    format: ErrorFormat,
+    file: &str,
+) -> Result<String, Error> {
+    let version = program.version();
+    let entrypoint_offset = program.get_entrypoint_offset();
+    let sections = program_sections(&program);
+    let relocated: HashSet<u64> = program
+        .section_header_entries
+        .iter()
+        .find(|entry| entry.label == ".text\0")
+        .map(|text| {
+            program
+                .relocations
+                .iter()
+                .filter(|relocation| relocation.rel_type == RelocationType::R_BPF_64_RELATIVE)
+                .map(|relocation| relocation.relative_offset(text.offset as u64))
+                .collect()
+        })
+        .unwrap_or_default();
+    let text_offset = program
+        .section_header_entries
+        .iter()
+        .find(|entry| entry.label == ".text\0")
+        .map(|text| text.offset);
+    let decode_error =
+        |errors: Vec<DisassemblerError>| match report(&errors, format, file, text_offset) {
+            Ok(()) => anyhow::anyhow!("failed to disassemble"),
+            Err(e) => e,
+        };
+    let disassembled = program.to_ixs().map_err(decode_error)?;
+    let raw = raw.to_ixs_raw().map_err(decode_error)?;
+    if !disassembled.errors.is_empty() {
+        report(&disassembled.errors, format, file, text_offset)?;
+        anyhow::bail!("words that fail to decode can't be re-assembled");
+    }
+
+    let source = render_round_trip(
+        disassembled.value,
+        &raw.value.instructions,
+        &relocated,
+        entrypoint_offset,
+    )?;
+    check_round_trip(&source, version, &sections)?;
+    Ok(source)
+}
+
+/// Disassembly that re-assembles to the same bytes: recurring constants are
+/// named with `.equ`, jump and call targets get labels, and `.rodata` keeps
+/// every byte. `raw` holds the instructions as `to_ixs_raw` decodes them,
+/// and `relocated` the .text offsets the loader rewrites.
+fn render_round_trip(
+    mut disassembly: Disassembly,
+    raw: &[Either<Instruction, DisassemblerError>],
+    relocated: &HashSet<u64>,
+    entrypoint_offset: Option<u64>,
+) -> Result<String, Error> {
+    // Symbols name functions only where the assembler accepts the name.
+    disassembly.symbols.retain(|_, name| is_identifier(name));
+    let mut ixs = disassembly.instructions;
+    let mut labels = label_instructions(
+        &mut ixs,
+        entrypoint_offset,
+        disassembly.rodata.as_ref(),
+        &disassembly.symbols,
+    );
+    // `to_ixs` turns an `lddw` of an address in .text into the index of the
+    // instruction there. A relocated one loads a function pointer, so it is
+    // written with the function's label; any other keeps the value it loads.
+    let positions: Vec<u64> = ixs
+        .iter()
+        .scan(0, |position, ix| {
+            let at = *position;
+            *position += ix.as_ref().left().map_or(8, Instruction::get_size);
+            Some(at)
+        })
+        .collect();
+    for (idx, raw) in raw.iter().enumerate() {
+        let (Some(Either::Left(ix)), Either::Left(raw)) = (ixs.get_mut(idx), raw) else {
+            continue;
+        };
+        let Some(Either::Right(Number::Int(target))) = ix.imm else {
+            continue;
+        };
+        if ix.opcode != Opcode::Lddw || ix.imm == raw.imm {
+            continue;
+        }
+        let target = target as usize;
+        if !relocated.contains(&positions[idx]) || target >= labels.len() {
+            ix.imm = raw.imm.clone();
+            continue;
+        }
+        let name = match labels[target]
+            .iter()
+            .find(|label| !label.starts_with("jmp_"))
+        {
+            Some(name) => name.clone(),
+            None => {
+                let name = format!("fn_{:04x}", positions[target]);
+                labels[target].insert(0, name.clone());
+                name
             }
+        };
+        ix.imm = Some(Either::Left(name));
+    }
 
+    // The entrypoint keeps the name it is exported under.
+    let entrypoint = entrypoint_offset
+        .and_then(|offset| positions.binary_search(&offset).ok())
+        .and_then(|idx| disassembly.symbols.get(&idx))
+        .filter(|name| *name != "entrypoint");
+    if let Some(name) = entrypoint {
+        for label in labels.iter_mut().flatten() {
+            if label == "entrypoint" {
+                label.clone_from(name);
+            }
+        }
+        for ix in ixs.iter_mut().filter_map(|ix| ix.as_mut().left()) {
             if ix.opcode == Opcode::Call
-                && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+                && matches!(&ix.imm, Some(Either::Left(target)) if target == "entrypoint")
             {
-                let target_idx = (idx as i64 + 1 + *imm) as usize;
-                if let Some(&target_pos) = positions.get(target_idx) {
-                    ix.imm = Some(Either::Left(format!("fn_{:04x}", target_pos)));
-                }
+                ix.imm = Some(Either::Left(name.clone()));
             }
+        }
+    }
+    let constants = name_constants(&mut ixs);
 
-            if ix.opcode == Opcode::Lddw
-                && let Some(Either::Right(Number::Int(imm))) = &ix.imm
-                && let Some(rodata) = &rodata
-                && let Some(label) = rodata.get_label(*imm as u64)
-            {
-                ix.imm = Some(Either::Left(label.to_string()));
+    let mut output = String::new();
+    for (value, name) in &constants {
+        output.push_str(&format!(".equ {}, {}\n", name, format_number(*value)));
+    }
+    if !constants.is_empty() {
+        output.push('\n');
+    }
+    output.push_str(&format!(
+        ".globl {}\n.text\n",
+        entrypoint.map_or("entrypoint", String::as_str)
+    ));
+    for (ix, labels) in ixs.iter().zip(&labels) {
+        if !labels.is_empty() {
+            output.push('\n');
+            for label in labels {
+                output.push_str(&format!("{}:\n", label));
             }
+        }
+        if let Either::Left(ix) = ix {
+            output.push_str(&format!("  {}\n", ix.to_asm(AsmFormat::Default)?));
+        }
+    }
+    if let Some(rodata) = &disassembly.rodata {
+        let rodata = rodata.to_exact_asm();
+        if !rodata.is_empty() {
+            output.push('\n');
+            output.push_str(&rodata);
+        }
+    }
+    Ok(output)
+}
 
-            output.push_str(&format!("{}{}\n", indent, ix.to_asm(format)?));
+/// Give each recurring immediate a name and use it in place of the number.
+/// Returns the names by value, for the `.equ` directives.
+fn name_constants(ixs: &mut [Either<Instruction, DisassemblerError>]) -> BTreeMap<i64, String> {
+    let mut uses: BTreeMap<i64, usize> = BTreeMap::new();
+    for ix in ixs.iter().filter_map(|ix| ix.as_ref().left()) {
+        if let Some(value) = constant_operand(ix) {
+            *uses.entry(value).or_default() += 1;
         }
+    }
+    let names: BTreeMap<i64, String> = uses
+        .into_iter()
+        .filter(|&(value, count)| count >= RECURRING_CONSTANT_USES && value.unsigned_abs() >= 0x100)
+        .map(|(value, _)| {
+            let name = if value < 0 {
+                format!("IMM_NEG_{:x}", value.unsigned_abs())
+            } else {
+                format!("IMM_{:x}", value)
+            };
+            (value, name)
+        })
+        .collect();
+    for ix in ixs.iter_mut().filter_map(|ix| ix.as_mut().left()) {
+        if let Some(value) = constant_operand(ix)
+            && let Some(name) = names.get(&value)
+        {
+            ix.imm = Some(Either::Left(name.clone()));
+        }
+    }
+    names
+}
 
-        // Output rodata section if present
-        if let Some(rodata) = rodata
-            && rodata.has_items()
+/// The immediate of `ix` if it is a plain number rather than a call target
+/// or the width of a byte swap.
+fn constant_operand(ix: &Instruction) -> Option<i64> {
+    if matches!(
+        ix.opcode,
+        Opcode::Call | Opcode::Callx | Opcode::Le | Opcode::Be
+    ) {
+        return None;
+    }
+    match &ix.imm {
+        Some(Either::Right(Number::Int(value))) => Some(*value),
+        _ => None,
+    }
+}
+
+fn format_number(value: i64) -> String {
+    if value < 0 {
+        format!("-0x{:x}", value.unsigned_abs())
+    } else {
+        format!("0x{:x}", value)
+    }
+}
+
+/// Whether the assembler reads `name` as a label rather than a register or
+/// something else.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !(name.len() <= 3
+            && name.starts_with('r')
+            && name[1..].parse::<u8>().is_ok_and(|n| n <= 10))
+}
+
+/// Contents of the sections `--asm` output has to reproduce.
+fn program_sections(program: &Program) -> Vec<(String, Vec<u8>)> {
+    program
+        .section_header_entries
+        .iter()
+        .filter_map(|entry| {
+            let name = entry.label.trim_end_matches('\0');
+            ROUND_TRIP_SECTIONS
+                .contains(&name)
+                .then(|| (name.to_string(), entry.data.clone()))
+        })
+        .collect()
+}
+
+/// Assemble `source` as `version` and check it reproduces `sections`.
+fn check_round_trip(
+    source: &str,
+    version: SbpfVersion,
+    sections: &[(String, Vec<u8>)],
+) -> Result<(), Error> {
+    let elf = Assembler::new(AssemblerOption::default().with_arch(version))
+        .assemble(source)
+        .map_err(|errors| {
+            anyhow::anyhow!(
+                "the disassembly does not re-assemble: {}",
+                errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )
+        })?;
+    let program = Program::from_bytes(&elf)
+        .map_err(|_| anyhow::anyhow!("the re-assembled program can't be read back"))?;
+    let rebuilt = program_sections(&program);
+    for (name, data) in sections {
+        let Some((_, rebuilt)) = rebuilt.iter().find(|(rebuilt, _)| rebuilt == name) else {
+            anyhow::bail!("the disassembly re-assembles without {}", name);
+        };
+        if let Some(offset) = data
+            .iter()
+            .zip(rebuilt)
+            .position(|(a, b)| a != b)
+            .or((data.len() != rebuilt.len()).then(|| data.len().min(rebuilt.len())))
         {
-            output.push('\n');
-            output.push_str(&rodata.to_asm());
+            anyhow::bail!(
+                "the disassembly re-assembles to a different {} from offset 0x{:x}",
+                name,
+                offset
+            );
         }
     }
+    Ok(())
+}
 
-    Ok(output)
+/// The symbol table, one symbol per line. Undefined symbols, such as the
+/// syscalls a program imports, show `UND` for their section.
+fn render_symbols(symbols: &[Symbol]) -> String {
+    if symbols.is_empty() {
+        return "No symbols\n".to_string();
+    }
+
+    let mut output = format!(
+        "{:<9}{:<20}{:<8}{:<10}{:<8}{:<12}{}\n",
+        "TABLE", "ADDRESS", "SIZE", "TYPE", "BIND", "SECTION", "NAME"
+    );
+    for symbol in symbols {
+        output.push_str(&format!(
+            "{:<9}{:<20}{:<8}{:<10}{:<8}{:<12}{}\n",
+            if symbol.dynamic { ".dynsym" } else { ".symtab" },
+            format!("0x{:016x}", symbol.address),
+            symbol.size,
+            symbol.symbol_type.as_str(),
+            if symbol.global { "global" } else { "local" },
+            symbol.section.as_deref().unwrap_or("UND"),
+            symbol.name
+        ));
+    }
+    output
+}
+
+/// The word of `.text` at `offset` as hex bytes, as much of it as there is.
+fn hex_word(text: &[u8], offset: usize) -> String {
+    text.iter()
+        .skip(offset)
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The disassembly as one JSON document. Every `.text` word that starts an
+/// instruction gets an entry, with `error` in place of `asm` when it could
+/// not be decoded. Given the `.text` bytes, each entry also carries its own.
+fn render_json(
+    disassembly: Disassembly,
+    entrypoint_offset: Option<u64>,
+    build_note: Option<&BuildNote>,
+    format: AsmFormat,
+    raw: bool,
+    text: Option<&[u8]>,
+) -> Result<serde_json::Value, Error> {
+    let mut ixs = disassembly.instructions;
+    let labels = if raw {
+        vec![Vec::new(); ixs.len()]
+    } else {
+        label_instructions(
+            &mut ixs,
+            entrypoint_offset,
+            disassembly.rodata.as_ref(),
+            &disassembly.symbols,
+        )
+    };
+
+    let mut offset = 0;
+    let mut instructions = Vec::with_capacity(ixs.len());
+    for (ix, labels) in ixs.iter().zip(labels) {
+        let mut entry = serde_json::json!({ "offset": offset, "labels": labels });
+        if let Some(text) = text {
+            let size = match ix {
+                Either::Left(ix) => ix.get_size(),
+                Either::Right(_) => 8,
+            };
+            let bytes: Vec<String> = (offset..offset + size)
+                .step_by(8)
+                .map(|word| hex_word(text, word as usize))
+                .collect();
+            entry["bytes"] = bytes.join(" ").into();
+        }
+        match ix {
+            Either::Left(ix) => {
+                entry["asm"] = ix.to_asm(format)?.into();
+                offset += ix.get_size();
+            }
+            Either::Right(e) => {
+                entry["error"] = e.to_string().into();
+                offset += 8;
+            }
+        }
+        instructions.push(entry);
+    }
+
+    let rodata: Vec<serde_json::Value> = disassembly
+        .rodata
+        .iter()
+        .flat_map(|rodata| &rodata.items)
+        .map(|item| {
+            serde_json::json!({
+                "label": item.label,
+                "offset": item.offset,
+                "size": item.size,
+                "asm": item.to_asm(),
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({
+        "entrypoint": entrypoint_offset,
+        "build_note": build_note.map(build_note_json),
+        "instructions": instructions,
+        "rodata": rodata,
+    }))
+}
+
+/// Name the entrypoint, call targets (`fn_<offset>`) and jump targets
+/// (`jmp_<offset>`), and rewrite jump, call and rodata operands to refer to
+/// them. Returns the labels that go before each instruction.
+pub fn label_instructions(
+    ixs: &mut [Either<Instruction, DisassemblerError>],
+    entrypoint_offset: Option<u64>,
+    rodata: Option<&RodataSection>,
+    symbols: &BTreeMap<usize, String>,
+) -> Vec<Vec<String>> {
+    // Build position map
+    let positions: Vec<u64> = ixs
+        .iter()
+        .scan(0u64, |pos, ix| {
+            let current = *pos;
+            *pos += match ix {
+                Either::Left(ix) => ix.get_size(),
+                Either::Right(_) => 8,
+            };
+            Some(current)
+        })
+        .collect();
+    let target = |idx: usize, delta: i64| positions.get((idx as i64 + 1 + delta) as usize);
+    // Functions are named after their symbol if they have one.
+    let function_name = |pos: u64| match positions.binary_search(&pos) {
+        Ok(idx) if symbols.contains_key(&idx) => symbols[&idx].clone(),
+        _ => format!("fn_{:04x}", pos),
+    };
+
+    // Collect all target positions
+    let mut jmp_targets: HashSet<u64> = HashSet::new();
+    let mut fn_targets: HashSet<u64> = HashSet::new();
+    for (idx, ix) in ixs.iter().enumerate() {
+        let Either::Left(ix) = ix else { continue };
+        if ix.is_jump()
+            && let Some(Either::Right(off)) = &ix.off
+            && let Some(&target_pos) = target(idx, *off as i64)
+        {
+            jmp_targets.insert(target_pos);
+        }
+
+        if ix.opcode == Opcode::Call
+            && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+            && let Some(&target_pos) = target(idx, *imm)
+        {
+            fn_targets.insert(target_pos);
+        }
+    }
+
+    let mut labels = Vec::with_capacity(ixs.len());
+    for (idx, ix) in ixs.iter_mut().enumerate() {
+        let pos = positions[idx];
+        let mut names = Vec::new();
+        if entrypoint_offset == Some(pos) {
+            names.push("entrypoint".to_string());
+        } else if fn_targets.contains(&pos) || symbols.contains_key(&idx) {
+            names.push(function_name(pos));
+        }
+        if jmp_targets.contains(&pos) {
+            names.push(format!("jmp_{:04x}", pos));
+        }
+        labels.push(names);
+
+        let Either::Left(ix) = ix else { continue };
+
+        // Replace numeric values with labels for display.
+        if ix.is_jump()
+            && let Some(Either::Right(off)) = &ix.off
+            && let Some(&target_pos) = target(idx, *off as i64)
+        {
+            ix.off = Some(Either::Left(format!("jmp_{:04x}", target_pos)));
+        }
+
+        if ix.opcode == Opcode::Call
+            && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+            && let Some(&target_pos) = target(idx, *imm)
+        {
+            ix.imm = Some(Either::Left(if entrypoint_offset == Some(target_pos) {
+                "entrypoint".to_string()
+            } else {
+                function_name(target_pos)
+            }));
+        }
+
+        if ix.opcode == Opcode::Lddw
+            && let Some(Either::Right(Number::Int(imm))) = &ix.imm
+            && let Some(rodata) = rodata
+            && let Some(label) = rodata.get_label(*imm as u64)
+        {
+            ix.imm = Some(Either::Left(label.to_string()));
+        }
+    }
+    labels
+}
+
+/// Collect the labels from `label_instructions` that start a function.
+/// Every block belongs to a function, so code ahead of the first call
+/// target or entrypoint gets one of its own.
+pub(crate) fn function_entries(labels: &mut [Vec<String>]) -> HashSet<String> {
+    if let Some(first) = labels.first_mut()
+        && first.iter().all(|label| label.starts_with("jmp_"))
+    {
+        first.insert(0, "fn_0000".to_string());
+    }
+    labels
+        .iter()
+        .flatten()
+        .filter(|label| !label.starts_with("jmp_"))
+        .cloned()
+        .collect()
+}
+
+/// Lift the disassembly into pseudo-code, one function per call target.
+/// Undecodable words are left out; they are reported separately.
+fn render_pseudo_code(disassembly: Disassembly, entrypoint_offset: Option<u64>) -> String {
+    let mut ixs = disassembly.instructions;
+    let mut labels = label_instructions(
+        &mut ixs,
+        entrypoint_offset,
+        disassembly.rodata.as_ref(),
+        &disassembly.symbols,
+    );
+    let function_entries = function_entries(&mut labels);
+
+    // Labels need an instruction after them, so stop at the last one.
+    let end = ixs
+        .iter()
+        .rposition(Either::is_left)
+        .map_or(0, |last| last + 1);
+    let mut nodes = Vec::new();
+    for (ix, labels) in ixs[..end].iter().zip(&labels) {
+        nodes.extend(labels.iter().map(|label| InputNode::Label(label)));
+        if let Either::Left(ix) = ix {
+            nodes.push(InputNode::Instruction(ix));
+        }
+    }
+    let cfg = control_flow_graph(nodes, &function_entries, Some("entrypoint"));
+    let mut output = decompile(&cfg);
+
+    if let Some(rodata) = disassembly.rodata
+        && rodata.has_items()
+    {
+        output.push('\n');
+        for line in rodata.to_asm().lines() {
+            output.push_str(&format!("// {}\n", line));
+        }
+    }
+    output
+}
+
+fn render_entry_analysis(analysis: &EntryAnalysis) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!("e_entry:       {:#x}\n", analysis.e_entry));
+    if let Some(text_vaddr) = analysis.text_vaddr {
+        output.push_str(&format!(".text vaddr:   {:#x}\n", text_vaddr));
+    }
+    let source = match &analysis.source {
+        EntrySource::ElfHeader => "e_entry in the ELF header".to_string(),
+        EntrySource::Symbol(name) => format!("`{}` symbol (e_entry is unusable)", name),
+        EntrySource::Heuristic => "heuristic, start of .text".to_string(),
+    };
+    match analysis.entry_offset {
+        Some(offset) => output.push_str(&format!("entry offset:  {:#06x}\n", offset)),
+        None => output.push_str("entry offset:  none\n"),
+    }
+    output.push_str(&format!("determined by: {}\n", source));
+
+    output.push_str(&format!(
+        "\nFunction starts ({}):\n",
+        analysis.functions.len()
+    ));
+    for function in &analysis.functions {
+        // Fall back to the labels `write_asm` would emit.
+        let name = match &function.name {
+            Some(name) => name.clone(),
+            None if function.origins.contains(&FunctionOrigin::Entry) => "entrypoint".to_string(),
+            None => format!("fn_{:04x}", function.offset),
+        };
+        let origins = function
+            .origins
+            .iter()
+            .map(|origin| match origin {
+                FunctionOrigin::Entry => "entry",
+                FunctionOrigin::Symbol => "symbol",
+                FunctionOrigin::CallTarget => "call target",
+                FunctionOrigin::TextStart => "start of .text",
+                FunctionOrigin::AfterExit => "after exit",
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        output.push_str(&format!(
+            "  {:#06x}  {:<20} {}\n",
+            function.offset, name, origins
+        ));
+    }
+
+    if !analysis.warnings.is_empty() {
+        output.push('\n');
+        for warning in &analysis.warnings {
+            output.push_str(&format!("warning: {}\n", warning));
+        }
+    }
+
+    output
 }
 
-fn report(errors: &[DisassemblerError]) {
+/// Print decode errors on stderr. `text_offset` is where `.text` starts in
+/// `file`, which the spans of bytecode errors count from.
+fn report(
+    errors: &[DisassemblerError],
+    format: ErrorFormat,
+    file: &str,
+    text_offset: Option<usize>,
+) -> Result<()> {
     for e in errors {
-        eprintln!("error: {e}")
+        if format == ErrorFormat::Human {
+            eprintln!("error: {e}");
+            continue;
+        }
+        let mut diagnostic = JsonDiagnostic {
+            code: e.code().to_string(),
+            severity: "error",
+            message: e.to_string(),
+            file: Some(file.to_string()),
+            ..Default::default()
+        };
+        if let (DisassemblerError::BytecodeError { error, span }, Some(offset)) = (e, text_offset) {
+            let span = span.start + offset..span.end + offset;
+            diagnostic.labels = vec![JsonLabel {
+                message: error.clone(),
+                span: span.clone(),
+                primary: true,
+            }];
+            diagnostic.span = Some(span);
+        }
+        diagnostic.emit()?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use {super::*, hex_literal::hex};
+    use {
+        super::*,
+        hex_literal::hex,
+        sbpf_assembler::{BuildInfo, SbpfArch},
+        sbpf_disassembler::symbol::SymbolType,
+        std::collections::BTreeSet,
+    };
 
-    /// Chain to_ixs + render_asm
+    /// Chain to_ixs + write_asm
     fn disassemble_program(program: Program, format: AsmFormat, raw: bool) -> String {
         let entrypoint_offset = program.get_entrypoint_offset();
         let text = program
@@ -262,7 +1052,18 @@ mod tests {
             program.to_ixs()
         }
         .unwrap();
-        render_asm(disassembled.value, entrypoint_offset, &text, format, raw).unwrap()
+        let mut output = Vec::new();
+        write_asm(
+            &mut output,
+            disassembled.value,
+            entrypoint_offset,
+            &text,
+            format,
+            raw,
+            false,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
     }
 
     #[test]
@@ -289,6 +1090,7 @@ mod tests {
         assert_eq!(
             disassemble_program(program, AsmFormat::Default, false),
             r#".globl entrypoint
+.text
 
 entrypoint:
   lddw r1, 0x1
@@ -392,6 +1194,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   call fn_0068
@@ -429,6 +1232,7 @@ fn_0088:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   call fn_0068
@@ -511,6 +1315,7 @@ fn_0088:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   call fn_0010
@@ -531,6 +1336,7 @@ jmp_0010:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   call fn_0010
@@ -613,6 +1419,7 @@ jmp_0010:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   lddw r1, data_0000
@@ -641,6 +1448,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   r1 = data_0000 ll
@@ -691,6 +1499,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   lddw r1, 0x1
@@ -706,6 +1515,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   r1 = 0x1 ll
@@ -751,6 +1561,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   lddw r1, str_0000
@@ -770,6 +1581,7 @@ entrypoint:
                 false,
             ),
             r#".globl entrypoint
+.text
 
 entrypoint:
   r1 = str_0000 ll
@@ -870,6 +1682,496 @@ exit
 r1 = 0x4 ll
 call sol_log_64_
 exit
+"#
+        );
+    }
+
+    fn analyze_source(source: &str, arch: SbpfArch) -> EntryAnalysis {
+        let options = AssemblerOption::default().with_arch(arch);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        EntryAnalysis::from_bytes(&bytecode).unwrap()
+    }
+
+    #[test]
+    fn test_entry_analysis_after_helper() {
+        let source = r#"
+.globl entrypoint
+helper:
+  mov64 r0, 1
+  exit
+entrypoint:
+  call helper
+  exit
+"#;
+        let expected = r#"e_entry:       0x100000010
+.text vaddr:   0x100000000
+entry offset:  0x0010
+determined by: e_entry in the ELF header
+
+Function starts (2):
+  0x0000  fn_0000              call target, start of .text
+  0x0010  entrypoint           entry, after exit
+"#;
+        assert_eq!(
+            render_entry_analysis(&analyze_source(source, SbpfArch::V3)),
+            expected
+        );
+        assert!(analyze_source(source, SbpfArch::V0).warnings.is_empty());
+    }
+
+    #[test]
+    fn test_entry_analysis_counts_lddw_slots() {
+        // Both lddw take two slots, so the jump lands on the call rather
+        // than past it, and the call reaches helper.
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  ja skip
+  lddw r0, 1
+  exit
+skip:
+  call helper
+  lddw r1, 2
+  exit
+helper:
+  mov64 r0, 0
+  exit
+"#;
+        let analysis = analyze_source(source, SbpfArch::V0);
+        let functions: Vec<_> = analysis
+            .functions
+            .iter()
+            .map(|function| (function.offset, function.origins.clone()))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                (
+                    0x0,
+                    BTreeSet::from([FunctionOrigin::Entry, FunctionOrigin::TextStart])
+                ),
+                (
+                    0x40,
+                    BTreeSet::from([FunctionOrigin::CallTarget, FunctionOrigin::AfterExit])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_analysis_mid_function_warns() {
+        let source = r#"
+.globl entrypoint
+helper:
+  mov64 r0, 1
+entrypoint:
+  call helper
+  exit
+"#;
+        let analysis = analyze_source(source, SbpfArch::V0);
+        assert_eq!(analysis.source, EntrySource::ElfHeader);
+        assert_eq!(analysis.entry_offset, Some(0x8));
+        assert_eq!(
+            analysis.warnings,
+            vec!["e_entry (offset 0x8) does not point at a recognized function".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_entry_analysis_symbol_fallback() {
+        let source = r#"
+.globl entrypoint
+helper:
+  mov64 r0, 1
+  exit
+entrypoint:
+  lddw r1, msg
+  mov64 r2, 5
+  call sol_log_
+  exit
+.rodata
+msg: .ascii "hello"
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let mut bytecode = Assembler::new(options).assemble(source).unwrap();
+        // Point e_entry before .text so the entrypoint symbol is used instead.
+        bytecode[24..32].copy_from_slice(&0u64.to_le_bytes());
+
+        let analysis = EntryAnalysis::from_bytes(&bytecode).unwrap();
+        assert_eq!(
+            analysis.source,
+            EntrySource::Symbol("entrypoint".to_string())
+        );
+        assert_eq!(analysis.entry_offset, Some(0x10));
+        assert_eq!(analysis.warnings, vec!["e_entry 0x0 lies before .text"]);
+        assert_eq!(analysis.functions[1].name.as_deref(), Some("entrypoint"));
+    }
+
+    #[test]
+    fn test_build_note_round_trip() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  lddw r1, msg
+  mov64 r2, 5
+  call sol_log_
+  exit
+.rodata
+msg: .ascii "hello"
+"#;
+        for arch in [SbpfArch::V0, SbpfArch::V3] {
+            let build = |git_commit: Option<&str>| {
+                let options =
+                    AssemblerOption::default()
+                        .with_arch(arch)
+                        .with_build_info(BuildInfo {
+                            git_commit: git_commit.map(str::to_string),
+                        });
+                Program::from_bytes(&Assembler::new(options).assemble(source).unwrap()).unwrap()
+            };
+
+            let program = build(Some("a26ee2f"));
+            let note = program.build_note().unwrap();
+            assert_eq!(note.build_id.len(), 20);
+            assert_eq!(note.assembler_version, env!("CARGO_PKG_VERSION"));
+            assert_eq!(
+                render_build_note(&note, "// "),
+                format!(
+                    "// build-id: {}\n// assembler: sbpf {}\n// git commit: a26ee2f\n",
+                    note.build_id_hex(),
+                    note.assembler_version
+                )
+            );
+            assert!(program.to_ixs().unwrap().errors.is_empty());
+
+            // The build-id only covers the program contents.
+            let without_commit = build(None).build_note().unwrap();
+            assert_eq!(without_commit.build_id, note.build_id);
+            assert_eq!(without_commit.git_commit, None);
+
+            let plain = AssemblerOption::default().with_arch(arch);
+            let bytecode = Assembler::new(plain).assemble(source).unwrap();
+            assert_eq!(Program::from_bytes(&bytecode).unwrap().build_note(), None);
+        }
+    }
+
+    #[test]
+    fn test_error_table() {
+        let source = r#"
+.globl entrypoint
+.equ ERROR_INVALID_PDA, 12
+.error ERROR_INVALID_PDA, "Vault address does not match its seeds"
+.error 13, "Amount is zero"
+entrypoint:
+  mov64 r0, ERROR_INVALID_PDA
+  exit
+"#;
+        for arch in [SbpfArch::V0, SbpfArch::V3] {
+            let options = AssemblerOption::default().with_arch(arch);
+            let bytecode = Assembler::new(options).assemble(source).unwrap();
+            let program = Program::from_bytes(&bytecode).unwrap();
+            assert_eq!(
+                render_error_table(&program.error_table(), "// "),
+                "// error 12 InvalidPda: Vault address does not match its seeds\n\
+                 // error 13 Error13: Amount is zero\n"
+            );
+            assert!(program.to_ixs().unwrap().errors.is_empty());
+        }
+
+        let plain = Assembler::new(AssemblerOption::default())
+            .assemble(".globl entrypoint\nentrypoint:\n  exit\n")
+            .unwrap();
+        assert!(
+            Program::from_bytes(&plain)
+                .unwrap()
+                .error_table()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_render_pseudo_code() {
+        let source = r#"
+.globl entrypoint
+helper:
+  ldxdw r0, [r1+8]
+  exit
+entrypoint:
+  call helper
+  jeq r0, 0, done
+  lddw r1, msg
+  mov64 r2, 5
+  call sol_log_
+done:
+  exit
+.rodata
+msg: .ascii "hello"
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let program = Program::from_bytes(&bytecode).unwrap();
+        let entrypoint_offset = program.get_entrypoint_offset();
+        let disassembled = program.to_ixs().unwrap();
+
+        assert_eq!(
+            render_pseudo_code(disassembled.value, entrypoint_offset),
+            r#"fn entrypoint() {
+    r0 = fn_0000(r1, r2, r3, r4, r5);
+    if (r0 == 0) goto jmp_0040;
+    r1 = msg;
+    r2 = 5;
+    r0 = sol_log_(r1, r2, r3, r4, r5);
+jmp_0040:
+    return r0;
+}
+
+fn fn_0000() {
+    r0 = *(u64 *)(r1 + 8);
+    return r0;
+}
+
+// .rodata
+//   msg: .ascii "hello"
+"#
+        );
+    }
+    #[test]
+    fn test_render_json() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  lddw r1, msg
+  jeq r1, 0, done
+  mov64 r0, 1
+done:
+  exit
+.rodata
+msg: .ascii "hi"
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let program = Program::from_bytes(&bytecode).unwrap();
+        let entrypoint_offset = program.get_entrypoint_offset();
+        let disassembled = program.to_ixs().unwrap();
+
+        assert_eq!(
+            render_json(
+                disassembled.value,
+                entrypoint_offset,
+                None,
+                AsmFormat::Default,
+                false,
+                None
+            )
+            .unwrap(),
+            serde_json::json!({
+                "entrypoint": 0,
+                "build_note": null,
+                "instructions": [
+                    { "offset": 0, "labels": ["entrypoint"], "asm": "lddw r1, msg" },
+                    { "offset": 16, "labels": [], "asm": "jeq r1, 0x0, jmp_0020" },
+                    { "offset": 24, "labels": [], "asm": "mov64 r0, 0x1" },
+                    { "offset": 32, "labels": ["jmp_0020"], "asm": "exit" },
+                ],
+                "rodata": [
+                    { "label": "msg", "offset": 0, "size": 2, "asm": "msg: .ascii \"hi\"" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_disassemble_bytes() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  lddw r1, 0x1122334455667788
+  exit
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let program = Program::from_bytes(&bytecode).unwrap();
+        let entrypoint_offset = program.get_entrypoint_offset();
+        let text = program
+            .section_header_entries
+            .iter()
+            .find(|e| e.label.eq(".text\0"))
+            .map(|e| e.data.clone())
+            .unwrap();
+        let disassembled = program.to_ixs().unwrap();
+
+        let mut output = Vec::new();
+        write_asm(
+            &mut output,
+            disassembled.value,
+            entrypoint_offset,
+            &text,
+            AsmFormat::Default,
+            false,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ".globl entrypoint
+.text
+
+entrypoint:
+  18 01 00 00 88 77 66 55  lddw r1, 0x1122334455667788
+  00 00 00 00 44 33 22 11
+  95 00 00 00 00 00 00 00  exit
+"
+        );
+
+        let disassembled = Program::from_bytes(&bytecode).unwrap().to_ixs().unwrap();
+        let json = render_json(
+            disassembled.value,
+            entrypoint_offset,
+            None,
+            AsmFormat::Default,
+            false,
+            Some(&text),
+        )
+        .unwrap();
+        assert_eq!(
+            json["instructions"][0]["bytes"],
+            "18 01 00 00 88 77 66 55 00 00 00 00 44 33 22 11"
+        );
+        assert_eq!(json["instructions"][1]["bytes"], "95 00 00 00 00 00 00 00");
+    }
+
+    #[test]
+    fn test_disassemble_demangled() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  call sol_log_
+  exit
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let mut program = Program::from_bytes(&bytecode).unwrap();
+        // Stand in for a call into a function rustc mangled.
+        for relocation in &mut program.relocations {
+            if relocation.symbol_name.as_deref() == Some("sol_log_") {
+                relocation.symbol_name = Some("_ZN6helper3add17h0123456789abcdefE".to_string());
+            }
+        }
+        program.demangle(&RustDemangler);
+        assert_eq!(
+            disassemble_program(program, AsmFormat::Default, false),
+            ".globl entrypoint
+.text
+
+entrypoint:
+  call helper::add
+  exit
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_with_symbols() {
+        let source = r#"
+.globl entrypoint
+entrypoint:
+  lddw r1, msg
+  call helper
+  call sol_log_
+  exit
+helper:
+  exit
+.rodata
+msg: .ascii "hi"
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let mut program = Program::from_bytes(&bytecode).unwrap();
+        assert_eq!(
+            render_symbols(&program.symbols),
+            "TABLE    ADDRESS             SIZE    TYPE      BIND    SECTION     NAME
+.symtab  0x0000000000000118  2       object    local   .rodata     msg
+.dynsym  0x00000000000000e8  0       unknown   global  .text       entrypoint
+.dynsym  0x0000000000000000  0       unknown   global  UND         sol_log_
+"
+        );
+
+        // Stand in for a function symbol of an unstripped program.
+        let text_vaddr = program.text_vaddr().unwrap();
+        program.symbols.push(Symbol {
+            name: "helper".to_string(),
+            address: text_vaddr + 0x28,
+            size: 0,
+            symbol_type: SymbolType::Function,
+            global: false,
+            dynamic: false,
+            section: Some(".text".to_string()),
+        });
+        assert_eq!(
+            disassemble_program(program, AsmFormat::Default, false),
+            r#".globl entrypoint
+.text
+
+entrypoint:
+  lddw r1, msg
+  call helper
+  call sol_log_
+  exit
+
+helper:
+  exit
+
+.rodata
+  msg: .ascii "hi"
+"#
+        );
+        assert_eq!(render_symbols(&[]), "No symbols\n");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = r#"
+.globl start
+start:
+  mov64 r1, 0x1000
+  jeq r1, 0x1000, done
+  lddw r2, helper
+  callx r2
+  add64 r1, 0x1000
+done:
+  exit
+helper:
+  exit
+.rodata
+message: .ascii "hi"
+  .byte 0x22, 0x0a, 0x00, 0x00
+"#;
+        let options = AssemblerOption::default().with_arch(SbpfArch::V0);
+        let bytecode = Assembler::new(options).assemble(source).unwrap();
+        let program = || Program::from_bytes(&bytecode).unwrap();
+        assert_eq!(
+            round_trip(program(), program(), ErrorFormat::Human, "test.so").unwrap(),
+            r#".equ IMM_1000, 0x1000
+
+.globl start
+.text
+
+start:
+  mov64 r1, IMM_1000
+  jeq r1, IMM_1000, jmp_0030
+  lddw r2, fn_0038
+  callx r2
+  add64 r1, IMM_1000
+
+jmp_0030:
+  exit
+
+fn_0038:
+  exit
+
+.rodata
+  message: .byte 0x68, 0x69, 0x22, 0x0a
+  .space 2
 "#
         );
     }
@@ -0,0 +1,52 @@
+use {
+    super::{
+        build::{BuildArgs, build},
+        deploy::{DeployArgs, deploy},
+        localnet::{Localnet, LocalnetOptions},
+        test::{TestArgs, test},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+};
+
+#[derive(Args)]
+pub struct E2eArgs {
+    #[command(flatten)]
+    pub deploy: DeployArgs,
+    #[arg(
+        long,
+        help = "Run against a solana-test-validator started for the run and stopped after it"
+    )]
+    pub localnet: bool,
+    #[command(flatten)]
+    pub localnet_options: LocalnetOptions,
+}
+
+pub fn e2e(mut args: E2eArgs) -> Result<()> {
+    let output = args.deploy.output;
+    let localnet = if args.localnet {
+        if args.deploy.url.is_some() {
+            return Err(Error::msg(
+                "--localnet deploys to the validator it starts; leave out the URL",
+            ));
+        }
+        let localnet = Localnet::start(&args.localnet_options, output)?;
+        localnet.airdrop(
+            args.localnet_options.airdrop,
+            args.deploy.keypair.as_deref(),
+            output,
+        )?;
+        args.deploy.url = Some(localnet.url.clone());
+        Some(localnet)
+    } else {
+        None
+    };
+
+    let result = build(BuildArgs::default())
+        .and_then(|()| deploy(args.deploy))
+        .and_then(|()| test(TestArgs::default()));
+    if let Some(localnet) = localnet {
+        localnet.stop(output);
+    }
+    result
+}
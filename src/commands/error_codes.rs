@@ -0,0 +1,182 @@
+use {
+    anyhow::{Error, Result},
+    sbpf_common::program_error::{ErrorDefinition, ErrorRegistry},
+    std::{
+        fmt::Write,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Write the `.error` codes of program `name` to `<name>-errors.rs` and
+/// `<name>-errors.ts` in `dir`, for tests to match the codes it exits with.
+/// Files left by an earlier build are removed once the program declares no
+/// errors.
+pub fn write_error_constants(dir: &Path, name: &str, errors: &ErrorRegistry) -> Result<()> {
+    if errors.is_empty() {
+        for extension in ["rs", "ts"] {
+            let _ = fs::remove_file(error_constants_path(dir, name, extension));
+        }
+        return Ok(());
+    }
+    let errors: Vec<ErrorDefinition> = errors.iter().cloned().collect();
+    for (extension, contents) in [
+        ("rs", render_rust_errors(name, &errors)),
+        ("ts", render_ts_errors(name, &errors)),
+    ] {
+        let path = error_constants_path(dir, name, extension);
+        fs::write(&path, contents)
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+fn error_constants_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{}-errors.{}", name, extension))
+}
+
+/// A constant per error code and a function from a code to its message.
+fn render_rust_errors(name: &str, errors: &[ErrorDefinition]) -> String {
+    let mut out = format!(
+        "// Generated by `sbpf build` from the `.error` directives of \"{}\". Do not edit.\n",
+        name
+    );
+    for error in errors {
+        out.push('\n');
+        if let Some(message) = &error.message {
+            writeln!(out, "/// {}", message).expect("writing to a String cannot fail");
+        }
+        writeln!(
+            out,
+            "pub const {}: u32 = {};",
+            screaming_snake_case(&error.name),
+            error.code
+        )
+        .expect("writing to a String cannot fail");
+    }
+    out.push_str(
+        "\n/// The message of a custom error code.\n\
+         pub fn error_message(code: u32) -> Option<&'static str> {\n    match code {\n",
+    );
+    for error in errors {
+        if let Some(message) = &error.message {
+            writeln!(
+                out,
+                "        {} => Some({:?}),",
+                screaming_snake_case(&error.name),
+                message
+            )
+            .expect("writing to a String cannot fail");
+        }
+    }
+    out.push_str("        _ => None,\n    }\n}\n");
+    out
+}
+
+/// An `ErrorCode` enum and a map from each code to its message.
+fn render_ts_errors(name: &str, errors: &[ErrorDefinition]) -> String {
+    let mut out = format!(
+        "// Generated by `sbpf build` from the `.error` directives of \"{}\". Do not edit.\n",
+        name
+    );
+    out.push_str(&ts_error_enum(errors));
+    out.push_str("\nexport const ERROR_MESSAGES: Record<number, string> = {\n");
+    for error in errors {
+        if let Some(message) = &error.message {
+            writeln!(
+                out,
+                "  [ErrorCode.{}]: {},",
+                error.name,
+                serde_json::Value::from(message.as_str())
+            )
+            .expect("writing to a String cannot fail");
+        }
+    }
+    out.push_str("};\n");
+    out
+}
+
+/// The errors as `export enum ErrorCode`, preceded by a blank line.
+pub(crate) fn ts_error_enum(errors: &[ErrorDefinition]) -> String {
+    let mut out = "\nexport enum ErrorCode {\n".to_string();
+    for error in errors {
+        writeln!(out, "  {} = {},", error.name, error.code)
+            .expect("writing to a String cannot fail");
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// `InvalidPda` as `INVALID_PDA`.
+fn screaming_snake_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous.is_some_and(|p| p.is_ascii_lowercase()) {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors() -> Vec<ErrorDefinition> {
+        vec![
+            ErrorDefinition {
+                code: 12,
+                name: "InvalidPda".to_string(),
+                message: Some("Vault \"address\" does not match".to_string()),
+            },
+            ErrorDefinition {
+                code: 13,
+                name: "Error13".to_string(),
+                message: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_rust_errors() {
+        assert_eq!(
+            render_rust_errors("vault", &errors()),
+            r#"// Generated by `sbpf build` from the `.error` directives of "vault". Do not edit.
+
+/// Vault "address" does not match
+pub const INVALID_PDA: u32 = 12;
+
+pub const ERROR13: u32 = 13;
+
+/// The message of a custom error code.
+pub fn error_message(code: u32) -> Option<&'static str> {
+    match code {
+        INVALID_PDA => Some("Vault \"address\" does not match"),
+        _ => None,
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_ts_errors() {
+        assert_eq!(
+            render_ts_errors("vault", &errors()),
+            r#"// Generated by `sbpf build` from the `.error` directives of "vault". Do not edit.
+
+export enum ErrorCode {
+  InvalidPda = 12,
+  Error13 = 13,
+}
+
+export const ERROR_MESSAGES: Record<number, string> = {
+  [ErrorCode.InvalidPda]: "Vault \"address\" does not match",
+};
+"#
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use {
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_assembler::{decode_source, format::format_source},
+    std::{
+        fs,
+        io::{self, Read, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+#[derive(Args)]
+pub struct FmtArgs {
+    #[arg(help = "Assembly files, or directories to format every .s file in [default: src]")]
+    pub paths: Vec<PathBuf>,
+    #[arg(
+        long,
+        help = "Report files that aren't formatted instead of rewriting them, failing if there are any"
+    )]
+    pub check: bool,
+    #[arg(
+        long,
+        conflicts_with = "paths",
+        help = "Format source read from stdin and write it to stdout"
+    )]
+    pub stdin: bool,
+}
+
+pub fn fmt(args: FmtArgs) -> Result<()> {
+    if args.stdin {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let source = decode_source(&bytes)
+            .map_err(|e| Error::msg(format!("Failed to read stdin: {}", e)))?;
+        let formatted = format(&source, Path::new("<stdin>"))?;
+        if args.check {
+            return match first_difference(&source, &formatted) {
+                Some(line) => Err(Error::msg(format!(
+                    "<stdin> is not formatted (first difference on line {})",
+                    line
+                ))),
+                None => Ok(()),
+            };
+        }
+        io::stdout().write_all(formatted.as_bytes())?;
+        return Ok(());
+    }
+
+    let paths = if args.paths.is_empty() {
+        vec![PathBuf::from("src")]
+    } else {
+        args.paths
+    };
+    let mut files = Vec::new();
+    for path in &paths {
+        if path.is_dir() {
+            collect_sources(path, &mut files)
+                .map_err(|e| Error::msg(format!("Failed to read '{}': {}", path.display(), e)))?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut unformatted = 0;
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file.display(), e)))?;
+        let formatted = format(&source, file)?;
+        let Some(line) = first_difference(&source, &formatted) else {
+            continue;
+        };
+        if args.check {
+            println!("{}:{}: not formatted", file.display(), line);
+            unformatted += 1;
+        } else {
+            fs::write(file, formatted)
+                .map_err(|e| Error::msg(format!("Failed to write '{}': {}", file.display(), e)))?;
+            println!("Formatted {}", file.display());
+        }
+    }
+
+    if unformatted > 0 {
+        return Err(Error::msg(format!(
+            "{} of {} files need formatting, run `sbpf fmt` to fix them",
+            unformatted,
+            files.len()
+        )));
+    }
+    Ok(())
+}
+
+fn format(source: &str, path: &Path) -> Result<String> {
+    format_source(source).map_err(|e| {
+        let before = source.get(..e.span().start).unwrap_or(source);
+        let line = before.matches('\n').count() + 1;
+        Error::msg(format!(
+            "Failed to format '{}': {} on line {}",
+            path.display(),
+            e,
+            line
+        ))
+    })
+}
+
+/// Every `.s` file under `dir`, sorted.
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "s") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The 1-based line where `formatted` first differs from `source`, or
+/// `None` if they're the same.
+fn first_difference(source: &str, formatted: &str) -> Option<usize> {
+    if source == formatted {
+        return None;
+    }
+    let same = source
+        .split('\n')
+        .zip(formatted.split('\n'))
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(same + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fmt_rewrites_and_checks() {
+        let dir = std::env::temp_dir().join(format!("sbpf-fmt-{}", std::process::id()));
+        let program = dir.join("prog");
+        fs::create_dir_all(&program).unwrap();
+        let file = program.join("prog.s");
+        fs::write(
+            &file,
+            ".globl entrypoint\nentrypoint:\n    mov64 r0,0\n  exit\n",
+        )
+        .unwrap();
+        let args = |check| FmtArgs {
+            paths: vec![dir.clone()],
+            check,
+            stdin: false,
+        };
+
+        let error = fmt(args(true)).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("1 of 1 files need formatting")
+        );
+        fmt(args(false)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            ".globl entrypoint\nentrypoint:\n  mov64 r0, 0\n  exit\n"
+        );
+        fmt(args(true)).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
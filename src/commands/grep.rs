@@ -0,0 +1,248 @@
+use {
+    super::{
+        build::{ArchArg, emit_assembler_errors},
+        common::{ErrorFormat, OutputFormat, print_json},
+        disassemble::label_instructions,
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    either::Either,
+    sbpf_analyze::Pattern,
+    sbpf_assembler::{Assembler, AssemblerOption, FsFileResolver, LineMapping, read_source},
+    sbpf_common::{
+        inst_param::Number,
+        instruction::{AsmFormat, Instruction},
+        opcode::Opcode,
+        syscalls::SYSCALLS,
+    },
+    sbpf_disassembler::program::Program,
+    std::{collections::BTreeMap, path::Path},
+};
+
+#[derive(Args)]
+pub struct GrepArgs {
+    #[arg(help = "Instruction pattern, e.g. 'stxdw [r10+$off], _ where $off > 4096'")]
+    pub pattern: String,
+    #[arg(
+        required = true,
+        help = "Programs to search: built ELF files (.so) or assembly sources (.s)"
+    )]
+    pub files: Vec<String>,
+    #[arg(
+        short = 'a',
+        long,
+        default_value = "v3",
+        help = "Target architecture for assembly sources (v0-v3)"
+    )]
+    pub arch: ArchArg,
+    #[arg(short, long, help = "Only print the number of matches in each file")]
+    pub count: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with one entry per match (or per file with --count)"
+    )]
+    pub output: OutputFormat,
+}
+
+/// One decoded instruction and where to report it.
+pub(crate) struct Located {
+    pub instruction: Instruction,
+    /// Labels naming this instruction, as `sbpf disassemble` prints them.
+    pub labels: Vec<String>,
+    pub location: String,
+    /// Source file and 1-based line, when assembled from source.
+    pub source: Option<(String, u32)>,
+}
+
+pub fn grep(args: GrepArgs) -> Result<(), Error> {
+    let pattern: Pattern = args
+        .pattern
+        .parse()
+        .map_err(|e| Error::msg(format!("Invalid pattern: {}", e)))?;
+
+    let mut json = Vec::new();
+    for file in &args.files {
+        let located = if file.ends_with(".s") {
+            assemble_source(file, args.arch, ErrorFormat::Human)?
+        } else {
+            let bytes = std::fs::read(file)
+                .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file, e)))?;
+            decode(&bytes, file, None)?
+        };
+        let instructions: Vec<Instruction> = located
+            .iter()
+            .map(|located| located.instruction.clone())
+            .collect();
+        let matches = pattern.find(&instructions);
+
+        if args.count {
+            if args.output.is_json() {
+                json.push(serde_json::json!({ "file": file, "count": matches.len() }));
+            } else {
+                println!("{}:{}", file, matches.len());
+            }
+            continue;
+        }
+        for found in matches {
+            let located = &located[found.index];
+            let asm = located
+                .instruction
+                .to_asm(AsmFormat::Default)
+                .unwrap_or_else(|_| located.instruction.opcode.to_string());
+            if args.output.is_json() {
+                let captures: BTreeMap<String, String> = found
+                    .captures
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect();
+                json.push(serde_json::json!({
+                    "file": file,
+                    "location": located.location,
+                    "asm": asm,
+                    "captures": captures,
+                }));
+                continue;
+            }
+            let mut captures: Vec<String> = found
+                .captures
+                .iter()
+                .map(|(name, value)| format!("${}={}", name, value))
+                .collect();
+            captures.sort();
+            if captures.is_empty() {
+                println!("{}: {}", located.location, asm);
+            } else {
+                println!("{}: {}  # {}", located.location, asm, captures.join(" "));
+            }
+        }
+    }
+    if args.output.is_json() {
+        print_json(&json)?;
+    }
+    Ok(())
+}
+
+/// Assemble `file` and decode the result, locating matches by source line.
+pub(crate) fn assemble_source(
+    file: &str,
+    arch: ArchArg,
+    error_format: ErrorFormat,
+) -> Result<Vec<Located>> {
+    let source = read_source(Path::new(file))
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file, e)))?;
+    let assembler = Assembler::new(AssemblerOption::default().with_arch(arch.into()));
+    let resolver = FsFileResolver::new();
+    let artifacts = match assembler.assemble_full_with_preprocess(&source, file, Some(&resolver)) {
+        Ok(artifacts) => artifacts,
+        Err(assemble_errors) => {
+            emit_assembler_errors(&assemble_errors, error_format)?;
+            return Err(Error::msg("Compilation failed"));
+        }
+    };
+    decode(&artifacts.elf, file, Some(&artifacts.sourcemap))
+}
+
+/// Decode the `.text` of an ELF, naming jump targets, functions and syscalls
+/// the way `sbpf disassemble` does. Instructions are located by `.text`
+/// offset, or by source line when a source map is given.
+pub(crate) fn decode(
+    bytes: &[u8],
+    file: &str,
+    sourcemap: Option<&[LineMapping]>,
+) -> Result<Vec<Located>> {
+    let program = Program::from_bytes(bytes)
+        .map_err(|_| Error::msg(format!("Failed to parse ELF file '{}'", file)))?;
+    let entrypoint_offset = program.get_entrypoint_offset();
+    let disassembly = program
+        .to_ixs()
+        .map_err(|_| Error::msg(format!("Failed to disassemble '{}'", file)))?
+        .value;
+    let mut ixs = disassembly.instructions;
+    let labels = label_instructions(
+        &mut ixs,
+        entrypoint_offset,
+        disassembly.rodata.as_ref(),
+        &disassembly.symbols,
+    );
+
+    let mut located = Vec::new();
+    let mut offset = 0;
+    // Labels on undecodable words carry over to the next instruction.
+    let mut pending = Vec::new();
+    for (ix, labels) in ixs.into_iter().zip(labels) {
+        pending.extend(labels);
+        let Either::Left(mut instruction) = ix else {
+            offset += 8;
+            continue;
+        };
+        let size = instruction.get_size();
+        // Static syscalls are called by hash.
+        if instruction.opcode == Opcode::Call
+            && let Some(Either::Right(Number::Int(hash))) = &instruction.imm
+            && let Some(name) = SYSCALLS.get(*hash as u32)
+        {
+            instruction.imm = Some(Either::Left(name.to_string()));
+        }
+        let mapping = sourcemap.and_then(|map| map.iter().find(|mapping| mapping.offset == offset));
+        let location = match mapping {
+            Some(mapping) => format!("{}:{}", mapping.file, mapping.line),
+            None => format!("{}:{:#06x}", file, offset),
+        };
+        located.push(Located {
+            instruction,
+            labels: std::mem::take(&mut pending),
+            location,
+            source: mapping.map(|mapping| (mapping.file.clone(), mapping.line)),
+        });
+        offset += size;
+    }
+    Ok(located)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep_source(pattern: &str, source: &str) -> Vec<String> {
+        let assembler = Assembler::new(AssemblerOption::default());
+        let artifacts = assembler
+            .assemble_full_with_preprocess(source, "test.s", None)
+            .unwrap();
+        let located = decode(&artifacts.elf, "test.s", Some(&artifacts.sourcemap)).unwrap();
+        let instructions: Vec<Instruction> = located
+            .iter()
+            .map(|located| located.instruction.clone())
+            .collect();
+        let pattern: Pattern = pattern.parse().unwrap();
+        pattern
+            .find(&instructions)
+            .iter()
+            .map(|found| located[found.index].location.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_grep_unguarded_division() {
+        let source = r#".globl entrypoint
+entrypoint:
+  ldxdw r2, [r1+0]
+  jeq r2, 0, fail
+  div64 r1, r2
+  ldxdw r3, [r1+8]
+  div64 r1, r3
+  exit
+fail:
+  call sol_log_
+  exit
+"#;
+        assert_eq!(
+            grep_source("div64 _, $d:reg not after jeq $d, 0", source),
+            vec!["test.s:7"]
+        );
+        assert_eq!(grep_source("call sol_log_", source), vec!["test.s:10"]);
+        assert_eq!(grep_source("jeq _, 0, fail", source), Vec::<String>::new());
+        assert_eq!(grep_source("jeq _, 0, jmp_*", source), vec!["test.s:4"]);
+    }
+}
@@ -0,0 +1,576 @@
+use {
+    super::{
+        build::{BuildArgs, emit_assembler_errors},
+        common::ErrorFormat,
+        config::{CONFIG_FILE, ProjectConfig},
+        error_codes::ts_error_enum,
+        keys::ProgramIds,
+        layout::{camel_case, data_view_method, ts_classes, ts_type},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_assembler::{
+        Assembler, AssemblerOption, FrontendRegistry, FsFileResolver, ProgramInterface,
+        preprocessor::layout::{FieldType, Instruction, Layout},
+        read_source,
+    },
+    sbpf_build::{program_entry, program_sources},
+    sbpf_common::program_error::ErrorDefinition,
+    serde::Serialize,
+    serde_json::{Value, json},
+    std::{
+        fmt::Write,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+#[derive(Args)]
+pub struct IdlgenArgs {
+    #[arg(help = "Program to generate for [default: every program in src]")]
+    pub program: Option<String>,
+    #[arg(
+        short = 'd',
+        long,
+        help = "Deploy directory the IDLs are written to and program ids are read from [default: deploy]"
+    )]
+    pub deploy_dir: Option<String>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "client",
+        help = "Directory the TypeScript clients are written to"
+    )]
+    pub client_dir: PathBuf,
+    #[arg(
+        short = 'I',
+        long = "include-path",
+        value_name = "DIR",
+        help = "Search DIR for .include files after the including file's directory"
+    )]
+    pub include_paths: Vec<PathBuf>,
+}
+
+/// An Anchor-style IDL. `sbpf run --idl` and `sbpf replay --idl` read the
+/// errors back to name the codes a program exits with.
+#[derive(Serialize)]
+struct Idl<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<&'a str>,
+    metadata: IdlMetadata<'a>,
+    instructions: Vec<IdlInstruction<'a>>,
+    types: Vec<IdlType<'a>>,
+    errors: &'a [ErrorDefinition],
+}
+
+#[derive(Serialize)]
+struct IdlMetadata<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entrypoint: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct IdlInstruction<'a> {
+    name: &'a str,
+    discriminator: [u8; 1],
+    accounts: Vec<IdlAccount<'a>>,
+    args: Vec<IdlField<'a>>,
+}
+
+#[derive(Serialize)]
+struct IdlAccount<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    writable: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    signer: bool,
+}
+
+#[derive(Serialize)]
+struct IdlField<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    ty: Value,
+    /// Byte offset in the account or instruction data. Fields given an
+    /// explicit `@ offset` may leave gaps Anchor's encoding can't express.
+    offset: usize,
+}
+
+#[derive(Serialize)]
+struct IdlType<'a> {
+    name: &'a str,
+    size: usize,
+    #[serde(rename = "type")]
+    ty: IdlStruct<'a>,
+}
+
+#[derive(Serialize)]
+struct IdlStruct<'a> {
+    kind: &'static str,
+    fields: Vec<IdlField<'a>>,
+}
+
+pub fn idlgen(args: IdlgenArgs) -> Result<()> {
+    let mut build_args = BuildArgs {
+        include_paths: args.include_paths,
+        ..Default::default()
+    };
+    ProjectConfig::load()?.apply_build(&mut build_args);
+    let include_paths = &build_args.include_paths;
+    let deploy = Path::new(args.deploy_dir.as_deref().unwrap_or("deploy"));
+
+    let mut programs = project_programs(&build_args.entries)?;
+    if let Some(program) = &args.program {
+        programs.retain(|(name, _)| name == program);
+        if programs.is_empty() {
+            anyhow::bail!("No program named \"{}\" in src or {}", program, CONFIG_FILE);
+        }
+    } else if programs.is_empty() {
+        anyhow::bail!("No programs found in src");
+    }
+
+    let ids = ProgramIds::load(deploy)?;
+    fs::create_dir_all(deploy)?;
+    fs::create_dir_all(&args.client_dir)?;
+    for (name, entry) in programs {
+        let interface = program_interface(&entry, include_paths)?;
+        let address = ids.get(&name);
+
+        let idl_path = deploy.join(format!("{}-idl.json", name));
+        let idl = serde_json::to_string_pretty(&idl(&name, &interface, address))? + "\n";
+        fs::write(&idl_path, idl)
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", idl_path.display(), e)))?;
+
+        let client_path = args.client_dir.join(format!("{}.ts", name));
+        fs::write(&client_path, render_client(&entry, &interface, address)).map_err(|e| {
+            Error::msg(format!(
+                "Failed to write '{}': {}",
+                client_path.display(),
+                e
+            ))
+        })?;
+
+        let count =
+            |n: usize, noun: &str| format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" });
+        println!(
+            "✅ Generated {} and {} for \"{}\" ({}, {})",
+            idl_path.display(),
+            client_path.display(),
+            name,
+            count(interface.instructions.len(), "instruction"),
+            count(interface.errors.len(), "error"),
+        );
+    }
+    Ok(())
+}
+
+/// Every program and its entry file: those `sbpf.toml` names, then each
+/// `src/<name>` with a `src/<name>/<name>.s`, as `sbpf build` finds them.
+fn project_programs(entries: &[(String, PathBuf)]) -> Result<Vec<(String, PathBuf)>> {
+    let mut programs = entries.to_vec();
+    let src = Path::new("src");
+    let dirs = src.read_dir().map_err(|e| {
+        Error::msg(format!(
+            "Failed to read 'src' directory: {}. Run this command from the root of an sbpf project.",
+            e
+        ))
+    })?;
+    let mut found = Vec::new();
+    for dir in dirs {
+        let path = dir?.path();
+        if path.is_dir()
+            && let Some(name) = path.file_name().and_then(|name| name.to_str())
+            && !entries.iter().any(|(program, _)| program == name)
+            && let Some(entry) = program_entry(&path, &FrontendRegistry::default())
+        {
+            found.push((name.to_string(), entry));
+        }
+    }
+    found.sort();
+    programs.extend(found);
+    Ok(programs)
+}
+
+/// The interface of a program, gathered from every source it is built
+/// from. The entry file declares the entrypoint; a block or error declared
+/// by more than one file is kept once.
+fn program_interface(entry: &Path, include_paths: &[PathBuf]) -> Result<ProgramInterface> {
+    let dir = entry.parent().unwrap_or(Path::new("."));
+    let files = program_sources(dir, entry, include_paths, &FrontendRegistry::default())?;
+    let assembler = Assembler::new(AssemblerOption {
+        include_paths: include_paths.to_vec(),
+        ..Default::default()
+    });
+    let resolver = FsFileResolver::with_include_paths(include_paths.to_vec());
+
+    let mut merged = ProgramInterface::default();
+    for (index, file) in files.iter().enumerate() {
+        let source = read_source(file)
+            .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file.display(), e)))?;
+        let interface = match assembler.interface_with_preprocess(
+            &source,
+            &file.to_string_lossy(),
+            Some(&resolver),
+        ) {
+            Ok(interface) => interface,
+            Err(errors) => {
+                emit_assembler_errors(&errors, ErrorFormat::Human)?;
+                anyhow::bail!("Failed to read the interface of '{}'", file.display());
+            }
+        };
+        if index == 0 {
+            merged.globals = interface.globals;
+        }
+        for layout in interface.layouts {
+            if !merged.layouts.iter().any(|other| other.name == layout.name) {
+                merged.layouts.push(layout);
+            }
+        }
+        for instruction in interface.instructions {
+            if let Some(other) = merged
+                .instructions
+                .iter()
+                .find(|other| other.discriminator == instruction.discriminator)
+            {
+                if other != &instruction {
+                    anyhow::bail!(
+                        "Instructions '{}' and '{}' share discriminator {}",
+                        other.data.name,
+                        instruction.data.name,
+                        instruction.discriminator
+                    );
+                }
+                continue;
+            }
+            merged.instructions.push(instruction);
+        }
+        for error in interface.errors {
+            if !merged.errors.iter().any(|other| other.code == error.code) {
+                merged.errors.push(error);
+            }
+        }
+    }
+    merged.errors.sort_by_key(|error| error.code);
+    Ok(merged)
+}
+
+fn idl<'a>(name: &'a str, interface: &'a ProgramInterface, address: Option<&'a str>) -> Idl<'a> {
+    Idl {
+        address,
+        metadata: IdlMetadata {
+            name,
+            entrypoint: interface.globals.first().map(String::as_str),
+        },
+        instructions: interface
+            .instructions
+            .iter()
+            .map(|instruction| IdlInstruction {
+                name: &instruction.data.name,
+                discriminator: [instruction.discriminator],
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|account| IdlAccount {
+                        name: &account.name,
+                        writable: account.writable,
+                        signer: account.signer,
+                    })
+                    .collect(),
+                args: idl_fields(&instruction.data),
+            })
+            .collect(),
+        types: interface
+            .layouts
+            .iter()
+            .map(|layout| IdlType {
+                name: &layout.name,
+                size: layout.size,
+                ty: IdlStruct {
+                    kind: "struct",
+                    fields: idl_fields(layout),
+                },
+            })
+            .collect(),
+        errors: &interface.errors,
+    }
+}
+
+fn idl_fields(layout: &Layout) -> Vec<IdlField<'_>> {
+    layout
+        .fields
+        .iter()
+        .map(|field| IdlField {
+            name: &field.name,
+            ty: match field.ty {
+                FieldType::U8 => json!("u8"),
+                FieldType::U16 => json!("u16"),
+                FieldType::U32 => json!("u32"),
+                FieldType::U64 => json!("u64"),
+                FieldType::I8 => json!("i8"),
+                FieldType::I16 => json!("i16"),
+                FieldType::I32 => json!("i32"),
+                FieldType::I64 => json!("i64"),
+                FieldType::Pubkey => json!("pubkey"),
+                FieldType::Bytes(len) => json!({ "array": ["u8", len] }),
+            },
+            offset: field.offset,
+        })
+        .collect()
+}
+
+/// A TypeScript module with the program id, a class per layout, a function
+/// building each instruction and an enum of the custom errors.
+fn render_client(entry: &Path, interface: &ProgramInterface, address: Option<&str>) -> String {
+    let mut out = format!(
+        "// Generated by `sbpf idlgen` from {}. Do not edit.\n\
+         import {{ PublicKey, TransactionInstruction }} from \"@solana/web3.js\";\n",
+        entry.display()
+    );
+    if let Some(address) = address {
+        write!(
+            out,
+            "\nexport const PROGRAM_ID = new PublicKey(\"{}\");\n",
+            address
+        )
+        .expect("writing to a String cannot fail");
+    }
+    out.push_str(&ts_classes(&interface.layouts));
+    for instruction in &interface.instructions {
+        render_instruction(&mut out, instruction, address.is_some());
+    }
+    if !interface.errors.is_empty() {
+        out.push_str(&ts_error_enum(&interface.errors));
+    }
+    out
+}
+
+/// The discriminator constant, the accounts and args interfaces and the
+/// function that builds the instruction. The function takes the program id
+/// last, defaulting to `PROGRAM_ID` when the program has one.
+fn render_instruction(out: &mut String, instruction: &Instruction, has_program_id: bool) {
+    let data = &instruction.data;
+    let type_name = camel_case(&data.name, true);
+    let discriminator = instruction.discriminator_constant();
+    write!(
+        out,
+        "\nexport const {} = {};\n",
+        discriminator, instruction.discriminator
+    )
+    .expect("writing to a String cannot fail");
+
+    let mut params = Vec::new();
+    if !instruction.accounts.is_empty() {
+        write!(out, "\nexport interface {}Accounts {{\n", type_name)
+            .expect("writing to a String cannot fail");
+        for account in &instruction.accounts {
+            writeln!(out, "  {}: PublicKey;", camel_case(&account.name, false))
+                .expect("writing to a String cannot fail");
+        }
+        out.push_str("}\n");
+        params.push(format!("accounts: {}Accounts", type_name));
+    }
+    if !data.fields.is_empty() {
+        write!(out, "\nexport interface {}Args {{\n", type_name)
+            .expect("writing to a String cannot fail");
+        for field in &data.fields {
+            writeln!(
+                out,
+                "  {}: {};",
+                camel_case(&field.name, false),
+                ts_type(field.ty)
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("}\n");
+        params.push(format!("args: {}Args", type_name));
+    }
+    params.push(if has_program_id {
+        "programId: PublicKey = PROGRAM_ID".to_string()
+    } else {
+        "programId: PublicKey".to_string()
+    });
+
+    write!(
+        out,
+        "\nexport function {}(\n",
+        camel_case(&data.name, false)
+    )
+    .expect("writing to a String cannot fail");
+    for param in &params {
+        writeln!(out, "  {},", param).expect("writing to a String cannot fail");
+    }
+    out.push_str("): TransactionInstruction {\n");
+    if data.fields.is_empty() {
+        writeln!(out, "  const data = new Uint8Array([{}]);", discriminator)
+            .expect("writing to a String cannot fail");
+    } else {
+        write!(
+            out,
+            "  const data = new Uint8Array({});\n  \
+             const view = new DataView(data.buffer);\n  \
+             view.setUint8(0, {});\n",
+            data.size, discriminator
+        )
+        .expect("writing to a String cannot fail");
+        for field in &data.fields {
+            let value = format!("args.{}", camel_case(&field.name, false));
+            match data_view_method(field.ty) {
+                Some(method) if field.ty.size() == 1 => {
+                    writeln!(out, "  view.set{}({}, {});", method, field.offset, value)
+                }
+                Some(method) => writeln!(
+                    out,
+                    "  view.set{}({}, {}, true);",
+                    method, field.offset, value
+                ),
+                None => writeln!(out, "  data.set({}, {});", value, field.offset),
+            }
+            .expect("writing to a String cannot fail");
+        }
+    }
+    out.push_str("  return new TransactionInstruction({\n    programId,\n    keys: [");
+    if !instruction.accounts.is_empty() {
+        out.push('\n');
+        for account in &instruction.accounts {
+            writeln!(
+                out,
+                "      {{ pubkey: accounts.{}, isSigner: {}, isWritable: {} }},",
+                camel_case(&account.name, false),
+                account.signer,
+                account.writable
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("    ");
+    }
+    out.push_str("],\n    data: Buffer.from(data),\n  });\n}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#".globl entrypoint
+.equ ERROR_INVALID_AMOUNT, 1
+.layout vault_state
+  bump: u8
+  owner: pubkey
+.endlayout
+.instruction deposit, 1
+  .account owner, signer, writable
+  .account vault_state, writable
+  amount: u64
+.endinstruction
+.instruction close, 2
+.endinstruction
+entrypoint:
+  exit
+"#;
+
+    fn interface() -> ProgramInterface {
+        Assembler::new(AssemblerOption::default())
+            .interface_with_preprocess(SOURCE, "vault.s", None)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_idl() {
+        let interface = interface();
+        let idl = serde_json::to_value(idl("vault", &interface, Some("Vau1t"))).unwrap();
+        assert_eq!(
+            idl,
+            json!({
+                "address": "Vau1t",
+                "metadata": { "name": "vault", "entrypoint": "entrypoint" },
+                "instructions": [
+                    {
+                        "name": "deposit",
+                        "discriminator": [1],
+                        "accounts": [
+                            { "name": "owner", "writable": true, "signer": true },
+                            { "name": "vault_state", "writable": true },
+                        ],
+                        "args": [{ "name": "amount", "type": "u64", "offset": 1 }],
+                    },
+                    { "name": "close", "discriminator": [2], "accounts": [], "args": [] },
+                ],
+                "types": [{
+                    "name": "vault_state",
+                    "size": 33,
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "bump", "type": "u8", "offset": 0 },
+                            { "name": "owner", "type": "pubkey", "offset": 1 },
+                        ],
+                    },
+                }],
+                "errors": [{ "code": 1, "name": "InvalidAmount" }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_client() {
+        let interface = interface();
+        let client = render_client(Path::new("src/vault/vault.s"), &interface, None);
+        let instructions = &client[client.find("\nexport const DEPOSIT").unwrap()..];
+        assert_eq!(
+            instructions,
+            r#"
+export const DEPOSIT_DISCRIMINATOR = 1;
+
+export interface DepositAccounts {
+  owner: PublicKey;
+  vaultState: PublicKey;
+}
+
+export interface DepositArgs {
+  amount: bigint;
+}
+
+export function deposit(
+  accounts: DepositAccounts,
+  args: DepositArgs,
+  programId: PublicKey,
+): TransactionInstruction {
+  const data = new Uint8Array(9);
+  const view = new DataView(data.buffer);
+  view.setUint8(0, DEPOSIT_DISCRIMINATOR);
+  view.setBigUint64(1, args.amount, true);
+  return new TransactionInstruction({
+    programId,
+    keys: [
+      { pubkey: accounts.owner, isSigner: true, isWritable: true },
+      { pubkey: accounts.vaultState, isSigner: false, isWritable: true },
+    ],
+    data: Buffer.from(data),
+  });
+}
+
+export const CLOSE_DISCRIMINATOR = 2;
+
+export function close(
+  programId: PublicKey,
+): TransactionInstruction {
+  const data = new Uint8Array([CLOSE_DISCRIMINATOR]);
+  return new TransactionInstruction({
+    programId,
+    keys: [],
+    data: Buffer.from(data),
+  });
+}
+
+export enum ErrorCode {
+  InvalidAmount = 1,
+}
+"#
+        );
+        assert!(client.contains("\nexport class VaultState {\n"));
+        assert!(
+            render_client(Path::new("vault.s"), &interface, Some("Vau1t"))
+                .contains("\nexport const PROGRAM_ID = new PublicKey(\"Vau1t\");\n")
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use {
+    crate::commands::{
+        build::BuildStats,
+        verify::{ManifestInput, sha256_hex},
+    },
+    anyhow::{Error, Result},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::Path,
+    },
+};
+
+/// Where the inputs of each program's last build are recorded, relative to
+/// the project root. `sbpf clean` removes it along with the rest of `.sbpf`.
+pub const BUILD_STATE_FILE: &str = ".sbpf/build-state.json";
+
+/// What each program was last built from, so a build can skip programs
+/// none of whose inputs changed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildState {
+    programs: BTreeMap<String, ProgramRecord>,
+}
+
+/// The last successful build of one program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramRecord {
+    /// Hash of the options the program was built with.
+    pub options: String,
+    /// Every file the build read, including transitively included ones and
+    /// profiles, with its sha256.
+    pub inputs: Vec<ManifestInput>,
+    pub output: String,
+    pub output_sha256: String,
+    pub executable_hash: String,
+    pub stats: BuildStats,
+}
+
+/// Whether a program has to be built again.
+#[derive(Debug)]
+pub enum Freshness<'a> {
+    UpToDate(&'a ProgramRecord),
+    /// Never built with this state file.
+    New,
+    /// Built before, but the reason given changed since.
+    Stale(String),
+}
+
+impl BuildState {
+    /// The state in `root`, or an empty one if there is none or it can't be
+    /// read, in which case every program is built.
+    pub fn load(root: &Path) -> Self {
+        fs::read_to_string(root.join(BUILD_STATE_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = root.join(BUILD_STATE_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))
+    }
+
+    /// Compare what `program` would be built from now with its last build.
+    pub fn freshness(
+        &self,
+        program: &str,
+        options: &str,
+        inputs: &[ManifestInput],
+        output: &Path,
+    ) -> Freshness<'_> {
+        let Some(record) = self.programs.get(program) else {
+            return Freshness::New;
+        };
+        if record.options != options {
+            return Freshness::Stale("build options changed".to_string());
+        }
+        if let Some(reason) = changed_input(&record.inputs, inputs) {
+            return Freshness::Stale(reason);
+        }
+        if record.output != output.to_string_lossy() {
+            return Freshness::Stale(format!("output moved to {}", output.display()));
+        }
+        match fs::read(output) {
+            Ok(elf) if sha256_hex(&elf) == record.output_sha256 => Freshness::UpToDate(record),
+            Ok(_) => Freshness::Stale(format!("{} was modified", output.display())),
+            Err(_) => Freshness::Stale(format!("{} is missing", output.display())),
+        }
+    }
+
+    pub fn record(&mut self, program: &str, record: ProgramRecord) {
+        self.programs.insert(program.to_string(), record);
+    }
+}
+
+/// The first difference between the inputs of two builds, described for the
+/// build log.
+fn changed_input(before: &[ManifestInput], now: &[ManifestInput]) -> Option<String> {
+    let previous: HashMap<&str, &str> = before
+        .iter()
+        .map(|input| (input.path.as_str(), input.sha256.as_str()))
+        .collect();
+    for input in now {
+        match previous.get(input.path.as_str()) {
+            None => return Some(format!("{} was added", input.path)),
+            Some(&sha256) if sha256 != input.sha256 => {
+                return Some(format!("{} changed", input.path));
+            }
+            Some(_) => {}
+        }
+    }
+    let current: Vec<&str> = now.iter().map(|input| input.path.as_str()).collect();
+    before
+        .iter()
+        .find(|input| !current.contains(&input.path.as_str()))
+        .map(|input| format!("{} was removed", input.path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(path: &str, sha256: &str) -> ManifestInput {
+        ManifestInput {
+            path: path.to_string(),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_freshness_follows_inputs_and_output() {
+        let root = std::env::temp_dir().join(format!("sbpf-incremental-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let output = root.join("vault.so");
+        fs::write(&output, b"elf").unwrap();
+        let inputs = vec![
+            input("src/vault/vault.s", "aa"),
+            input("src/lib/math.s", "bb"),
+        ];
+
+        let mut state = BuildState::default();
+        assert!(matches!(
+            state.freshness("vault", "O0", &inputs, &output),
+            Freshness::New
+        ));
+        state.record(
+            "vault",
+            ProgramRecord {
+                options: "O0".to_string(),
+                inputs: inputs.clone(),
+                output: output.to_string_lossy().into_owned(),
+                output_sha256: sha256_hex(b"elf"),
+                executable_hash: String::new(),
+                stats: BuildStats::default(),
+            },
+        );
+        state.save(&root).unwrap();
+        let state = BuildState::load(&root);
+
+        assert!(matches!(
+            state.freshness("vault", "O0", &inputs, &output),
+            Freshness::UpToDate(_)
+        ));
+        let stale = |options: &str, inputs: &[ManifestInput]| match state
+            .freshness("vault", options, inputs, &output)
+        {
+            Freshness::Stale(reason) => reason,
+            freshness => panic!("expected a stale build, found {:?}", freshness),
+        };
+        assert_eq!(stale("O2", &inputs), "build options changed");
+        assert_eq!(
+            stale("O0", &[inputs[0].clone(), input("src/lib/math.s", "cc")]),
+            "src/lib/math.s changed"
+        );
+        assert_eq!(stale("O0", &inputs[..1]), "src/lib/math.s was removed");
+
+        fs::write(&output, b"patched").unwrap();
+        assert!(stale("O0", &inputs).ends_with("vault.so was modified"));
+        fs::remove_file(&output).unwrap();
+        assert!(stale("O0", &inputs).ends_with("vault.so is missing"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
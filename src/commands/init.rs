@@ -1,10 +1,14 @@
 use {
-    super::common::{
-        CARGO_TOML, GITIGNORE, PACKAGE_JSON, PROGRAM, README, RUST_TESTS, TS_TESTS, TSCONFIG,
+    super::{
+        common::{
+            CARGO_TOML, GITIGNORE, PACKAGE_JSON, PROGRAM, README, RUST_TESTS, SBPF_TOML, TS_TESTS,
+            TSCONFIG,
+        },
+        config::CONFIG_FILE,
+        keys::ensure_keypair,
     },
     anyhow::{Error, Result},
     clap::Args,
-    ed25519_dalek::SigningKey,
     std::{
         fs,
         io::{self, Write},
@@ -13,6 +17,7 @@ use {
 };
 
 const MOLLUSK_SVM_VERSION: &str = env!("MOLLUSK_SVM_VERSION");
+const SERDE_JSON_VERSION: &str = env!("SERDE_JSON_VERSION");
 const SOLANA_ACCOUNT_VERSION: &str = env!("SOLANA_ACCOUNT_VERSION");
 const SOLANA_ADDRESS_VERSION: &str = env!("SOLANA_ADDRESS_VERSION");
 const SOLANA_INSTRUCTION_VERSION: &str = env!("SOLANA_INSTRUCTION_VERSION");
@@ -69,6 +74,7 @@ pub fn init(args: InitArgs) -> Result<(), Error> {
             README.replace("default_project_name", &project_name),
         )?;
         fs::write(project_path.join(".gitignore"), GITIGNORE)?;
+        fs::write(project_path.join(CONFIG_FILE), SBPF_TOML)?;
 
         fs::write(
             project_path
@@ -78,13 +84,7 @@ pub fn init(args: InitArgs) -> Result<(), Error> {
             PROGRAM,
         )?;
 
-        let mut rng = rand::rng();
-        fs::write(
-            project_path
-                .join("deploy")
-                .join(format!("{}-keypair.json", project_name)),
-            serde_json::json!(SigningKey::generate(&mut rng).to_keypair_bytes()[..]).to_string(),
-        )?;
+        let program_id = ensure_keypair(&project_path.join("deploy"), &project_name)?;
 
         if args.ts_tests {
             fs::write(
@@ -114,6 +114,7 @@ pub fn init(args: InitArgs) -> Result<(), Error> {
                 CARGO_TOML
                     .replace("default_project_name", &project_name)
                     .replace("{{MOLLUSK_SVM_VERSION}}", MOLLUSK_SVM_VERSION)
+                    .replace("{{SERDE_JSON_VERSION}}", SERDE_JSON_VERSION)
                     .replace("{{SOLANA_ACCOUNT_VERSION}}", SOLANA_ACCOUNT_VERSION)
                     .replace("{{SOLANA_ADDRESS_VERSION}}", SOLANA_ADDRESS_VERSION)
                     .replace("{{SOLANA_INSTRUCTION_VERSION}}", SOLANA_INSTRUCTION_VERSION),
@@ -125,6 +126,7 @@ pub fn init(args: InitArgs) -> Result<(), Error> {
             project_name,
             if args.ts_tests { "TypeScript" } else { "Rust" }
         );
+        println!("🔑 Program id: {}", program_id);
         Ok(())
     } else {
         println!("⚠️ Project '{}' already exists!", project_name);
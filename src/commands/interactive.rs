@@ -0,0 +1,319 @@
+use {
+    super::common::{SolanaSyscalls, assemble_for_vm},
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_assembler::AssemblerOption,
+    sbpf_common::instruction::Instruction,
+    sbpf_vm::{memory::MemoryRegion, vm::SbpfVm},
+    std::io::{self, Write},
+};
+
+/// Source placed before every snippet so it assembles as a complete program.
+const SNIPPET_PRELUDE: &str = ".globl entrypoint\nentrypoint:\n";
+
+const HELP: &str = "\
+Enter an instruction to assemble and run it, or `{` to start a block that
+runs when closed with `}`. Registers and memory persist between inputs.
+
+  :regs               show all registers
+  :mem <addr> [len]   dump memory, e.g. `:mem 0x200000ff8 8`
+  :reset              clear registers and memory
+  :help               show this message
+  :quit               leave the REPL";
+
+#[derive(Args)]
+pub struct InteractiveArgs {
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Size of the zeroed input region in bytes"
+    )]
+    pub input_size: usize,
+}
+
+pub fn interactive(args: InteractiveArgs) -> Result<(), Error> {
+    let mut session = Session::new(args.input_size);
+    println!("sBPF interactive. Type ':help' for commands.");
+
+    let stdin = io::stdin();
+    let mut block: Option<Vec<String>> = None;
+    loop {
+        print!("{}", if block.is_some() { "... " } else { "sbpf> " });
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if stdin.read_line(&mut input)? == 0 {
+            break;
+        }
+        let line = input.trim();
+
+        if let Some(lines) = &mut block {
+            if line == "}" {
+                let source = lines.join("\n");
+                block = None;
+                session.run_and_display(&source);
+            } else {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        match line {
+            "" => {}
+            "{" => block = Some(Vec::new()),
+            ":quit" | ":q" => break,
+            ":help" | ":h" => println!("{}", HELP),
+            ":regs" | ":r" => print!("{}", session.render_registers(&[])),
+            ":reset" => {
+                session.reset();
+                println!("State cleared");
+            }
+            cmd if cmd.split_whitespace().next() == Some(":mem") => {
+                match session.dump_memory(&cmd[4..]) {
+                    Ok(dump) => print!("{}", dump),
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+            cmd if cmd.starts_with(':') => {
+                println!("Error: unknown command '{}'. Type ':help'.", cmd)
+            }
+            source => session.run_and_display(source),
+        }
+    }
+    Ok(())
+}
+
+/// Result of running one snippet.
+#[derive(Debug)]
+struct Evaluation {
+    /// Registers whose value changed.
+    changed: Vec<usize>,
+    compute_units: u64,
+    /// Set when the snippet ran `exit` at the top level.
+    exit_code: Option<u64>,
+    logs: Vec<String>,
+}
+
+/// Persistent VM state that snippets run against.
+struct Session {
+    vm: SbpfVm<SolanaSyscalls>,
+}
+
+impl Session {
+    fn new(input_size: usize) -> Self {
+        Self {
+            vm: SbpfVm::new(
+                Vec::new(),
+                vec![0; input_size],
+                Vec::new(),
+                SolanaSyscalls::default(),
+            ),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.vm.reset();
+        for region in [MemoryRegion::Stack, MemoryRegion::Heap, MemoryRegion::Input] {
+            self.vm.memory.region_mut(region).fill(0);
+        }
+    }
+
+    fn run_and_display(&mut self, source: &str) {
+        match self.eval(source) {
+            Ok(evaluation) => {
+                for log in &evaluation.logs {
+                    println!("{}", log);
+                }
+                print!("{}", self.render_registers(&evaluation.changed));
+                match evaluation.exit_code {
+                    Some(code) => println!(
+                        "exit {} ({} CU); registers were kept",
+                        code, evaluation.compute_units
+                    ),
+                    None => println!("({} CU)", evaluation.compute_units),
+                }
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    /// Assemble `source` and run it to completion against the current state.
+    fn eval(&mut self, source: &str) -> Result<Evaluation, String> {
+        let program = assemble_snippet(source)?;
+        let before = self.vm.registers;
+
+        self.vm.program = program;
+        self.vm.pc = 0;
+        self.vm.halted = false;
+        self.vm.exit_code = None;
+        self.vm.call_stack.clear();
+        self.vm.compute_meter.reset();
+
+        let mut result = Ok(());
+        while !self.vm.halted && self.vm.pc < self.vm.program.len() {
+            result = self.vm.step();
+            if result.is_err() {
+                break;
+            }
+        }
+        // A fault or a snippet ending inside a call leaves frames behind.
+        self.vm.call_stack.clear();
+        let logs = std::mem::take(&mut self.vm.syscall_handler.logs);
+        if let Err(e) = result {
+            let mut message = String::new();
+            for log in logs {
+                message.push_str(&format!("{}\n", log));
+            }
+            message.push_str(&format!("{} at instruction {}", e, self.vm.pc));
+            return Err(message);
+        }
+
+        Ok(Evaluation {
+            changed: (0..before.len())
+                .filter(|&reg| before[reg] != self.vm.registers[reg])
+                .collect(),
+            compute_units: self.vm.compute_meter.get_consumed(),
+            exit_code: self.vm.exit_code,
+            logs,
+        })
+    }
+
+    /// All registers, with changed ones marked by `*`.
+    fn render_registers(&self, changed: &[usize]) -> String {
+        let mut output = String::new();
+        for (reg, value) in self.vm.registers.iter().enumerate() {
+            let marker = if changed.contains(&reg) { '*' } else { ' ' };
+            output.push_str(&format!(
+                "{:>3} {:#018x}{}",
+                format!("r{}", reg),
+                value,
+                marker
+            ));
+            output.push_str(if reg % 4 == 3 || reg == 10 {
+                "\n"
+            } else {
+                "  "
+            });
+        }
+        output
+    }
+
+    /// Hex dump of memory for the `:mem <addr> [len]` command.
+    fn dump_memory(&self, args: &str) -> Result<String, String> {
+        let mut args = args.split_whitespace();
+        let address = args
+            .next()
+            .ok_or("usage: :mem <addr> [len]")
+            .and_then(|arg| parse_u64(arg).ok_or("invalid address"))?;
+        let len = match args.next() {
+            Some(arg) => parse_u64(arg).ok_or("invalid length")? as usize,
+            None => 8,
+        };
+        let bytes = self
+            .vm
+            .memory
+            .read_slice(address, len)
+            .map_err(|e| e.to_string())?;
+
+        let mut output = String::new();
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            output.push_str(&format!(
+                "{:#011x}: {}\n",
+                address + row as u64 * 16,
+                hex.join(" ")
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// Assemble a snippet as the body of an entrypoint.
+fn assemble_snippet(source: &str) -> Result<Vec<Instruction>, String> {
+    // Snippets run off their end by design, which the loader would reject.
+    assemble_for_vm(
+        &format!("{}{}\n", SNIPPET_PRELUDE, source),
+        AssemblerOption::default().without_verification(),
+    )
+    .map(|program| program.instructions)
+}
+
+fn parse_u64(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_persists_between_snippets() {
+        let mut session = Session::new(16);
+        let evaluation = session.eval("mov64 r1, 5").unwrap();
+        assert_eq!(evaluation.changed, vec![1]);
+        assert_eq!(evaluation.compute_units, 1);
+
+        let evaluation = session
+            .eval("stxdw [r10-8], r1\nldxdw r2, [r10-8]\nadd64 r2, r1")
+            .unwrap();
+        assert_eq!(evaluation.changed, vec![2]);
+        assert_eq!(session.vm.registers[2], 10);
+        assert_eq!(evaluation.exit_code, None);
+
+        let evaluation = session.eval("mov64 r0, r2\nexit").unwrap();
+        assert_eq!(evaluation.exit_code, Some(10));
+        // Nothing is reset by exit.
+        assert_eq!(session.vm.registers[1], 5);
+    }
+
+    #[test]
+    fn test_jumps_and_syscalls() {
+        let mut session = Session::new(16);
+        let evaluation = session
+            .eval("mov64 r1, 3\nloop:\nsub64 r1, 1\njne r1, 0, loop\nmov64 r2, 7\ncall sol_log_64_")
+            .unwrap();
+        assert_eq!(session.vm.registers[1], 0);
+        assert_eq!(
+            evaluation.logs,
+            vec!["Program log: 0x0, 0x7, 0x0, 0x0, 0x0"]
+        );
+        // Nine instructions plus the syscall's own cost.
+        assert_eq!(evaluation.compute_units, 109);
+    }
+
+    #[test]
+    fn test_errors_keep_state() {
+        let mut session = Session::new(16);
+        session.eval("mov64 r3, 1").unwrap();
+        assert!(session.eval("mov64 r99, 1").is_err());
+        assert!(
+            session
+                .eval("div64 r3, r4")
+                .unwrap_err()
+                .contains("Division by zero")
+        );
+        assert_eq!(session.vm.registers[3], 1);
+        assert_eq!(
+            session.dump_memory(" 0x400000000 2").unwrap(),
+            "0x400000000: 00 00\n"
+        );
+    }
+
+    #[test]
+    fn test_reset_clears_memory() {
+        let mut session = Session::new(16);
+        session
+            .eval("mov64 r1, 7\nstxdw [r10-8], r1\nlddw r2, 0x300000000\nstxdw [r2+0], r1\nlddw r2, 0x400000000\nstxb [r2+0], r1")
+            .unwrap();
+        session.reset();
+        assert_eq!(session.vm.registers[2], 0);
+        for address in ["0x200000ff8", "0x300000000", "0x400000000"] {
+            assert_eq!(
+                session.dump_memory(&format!(" {address} 1")).unwrap(),
+                format!("{address}: 00\n")
+            );
+        }
+    }
+}
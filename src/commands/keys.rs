@@ -0,0 +1,413 @@
+use {
+    crate::commands::common::{OutputFormat, print_json},
+    anyhow::{Error, Result},
+    clap::{Args, Subcommand},
+    ed25519_dalek::SigningKey,
+    serde::Serialize,
+    std::{
+        collections::BTreeMap,
+        fs,
+        path::{Path, PathBuf},
+        sync::{
+            Mutex,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+        },
+        thread,
+        time::Instant,
+    },
+};
+
+/// File in the deploy directory mapping each program to its program id.
+pub const PROGRAM_IDS_FILE: &str = "program-ids.json";
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Args)]
+pub struct KeysArgs {
+    #[command(subcommand)]
+    pub command: KeysCommand,
+    #[arg(
+        short = 'd',
+        long,
+        global = true,
+        help = "Deploy directory holding the keypairs [default: deploy]"
+    )]
+    pub deploy_dir: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum KeysCommand {
+    #[command(about = "Generate a new program keypair")]
+    New {
+        #[arg(help = "Program to generate a keypair for [default: the only program in src]")]
+        program: Option<String>,
+        #[arg(
+            long,
+            help = "Keep generating until the program id starts with PREFIX (case-sensitive base58)"
+        )]
+        prefix: Option<String>,
+        #[arg(long, help = "Replace an existing keypair, changing the program id")]
+        force: bool,
+    },
+    #[command(about = "Show the program id of each program keypair")]
+    Show {
+        #[arg(help = "Only show this program")]
+        program: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Output format: text, or json with each program's id and keypair file"
+        )]
+        output: OutputFormat,
+    },
+    #[command(about = "Use an existing Solana keypair file as a program's keypair")]
+    Import {
+        #[arg(help = "Keypair file, as written by `solana-keygen`")]
+        keypair: PathBuf,
+        #[arg(help = "Program to import the keypair for [default: the only program in src]")]
+        program: Option<String>,
+        #[arg(long, help = "Replace an existing keypair, changing the program id")]
+        force: bool,
+    },
+}
+
+/// One program as `sbpf keys show --output json` reports it.
+#[derive(Serialize)]
+struct ProgramKey {
+    name: String,
+    program_id: String,
+    keypair: String,
+}
+
+pub fn keys(args: KeysArgs) -> Result<()> {
+    let deploy = PathBuf::from(args.deploy_dir.as_deref().unwrap_or("deploy"));
+    match args.command {
+        KeysCommand::New {
+            program,
+            prefix,
+            force,
+        } => {
+            let program = program_name(program)?;
+            refuse_overwrite(&deploy, &program, force)?;
+            let key = match &prefix {
+                Some(prefix) => {
+                    validate_prefix(prefix)?;
+                    println!("🔑 Grinding for a program id starting with \"{}\"", prefix);
+                    let start = Instant::now();
+                    let (key, attempts) = grind(prefix);
+                    println!(
+                        "   found after {} attempts in {:.1}s",
+                        attempts,
+                        start.elapsed().as_secs_f64()
+                    );
+                    key
+                }
+                None => SigningKey::generate(&mut rand::rng()),
+            };
+            let program_id = write_keypair(&deploy, &program, &key)?;
+            println!("✅ \"{}\" has program id {}", program, program_id);
+            Ok(())
+        }
+        KeysCommand::Show { program, output } => show(&deploy, program.as_deref(), output),
+        KeysCommand::Import {
+            keypair,
+            program,
+            force,
+        } => {
+            let program = program_name(program)?;
+            refuse_overwrite(&deploy, &program, force)?;
+            let key = read_keypair(&keypair)?;
+            let program_id = write_keypair(&deploy, &program, &key)?;
+            println!(
+                "✅ Imported {} for \"{}\", program id {}",
+                keypair.display(),
+                program,
+                program_id
+            );
+            Ok(())
+        }
+    }
+}
+
+fn show(deploy: &Path, program: Option<&str>, output: OutputFormat) -> Result<()> {
+    let names = match program {
+        Some(program) => vec![program.to_string()],
+        None => keypair_programs(deploy)?,
+    };
+    let mut keys = Vec::new();
+    for name in names {
+        let path = keypair_path(deploy, &name);
+        let key = read_keypair(&path)?;
+        keys.push(ProgramKey {
+            name,
+            program_id: program_id(&key),
+            keypair: path.to_string_lossy().into_owned(),
+        });
+    }
+    if output.is_json() {
+        return print_json(&serde_json::json!({ "programs": keys }));
+    }
+    if keys.is_empty() {
+        println!(
+            "No program keypairs in {}, run `sbpf keys new` to create one",
+            deploy.display()
+        );
+    }
+    for key in keys {
+        println!("{}: {}", key.name, key.program_id);
+    }
+    Ok(())
+}
+
+/// Where a program's keypair lives. `deploy` reads it from here to get the
+/// address to deploy to.
+pub fn keypair_path(deploy: &Path, program: &str) -> PathBuf {
+    deploy.join(format!("{}-keypair.json", program))
+}
+
+/// The base58 program id a keypair deploys to.
+pub fn program_id(key: &SigningKey) -> String {
+    bs58::encode(key.verifying_key().to_bytes()).into_string()
+}
+
+/// Generate a keypair for `program` if it doesn't have one, and make sure
+/// its id is in the program ids file. Returns the program id.
+pub fn ensure_keypair(deploy: &Path, program: &str) -> Result<String> {
+    let path = keypair_path(deploy, program);
+    if !path.exists() {
+        return write_keypair(deploy, program, &SigningKey::generate(&mut rand::rng()));
+    }
+    let program_id = program_id(&read_keypair(&path)?);
+    let mut ids = ProgramIds::load(deploy)?;
+    if ids.get(program) != Some(program_id.as_str()) {
+        ids.insert(program, &program_id);
+        ids.save(deploy)?;
+    }
+    Ok(program_id)
+}
+
+/// Write `key` as the keypair of `program` in the format `solana-keygen`
+/// uses, and record its program id. Returns the program id.
+pub fn write_keypair(deploy: &Path, program: &str, key: &SigningKey) -> Result<String> {
+    fs::create_dir_all(deploy)?;
+    let path = keypair_path(deploy, program);
+    fs::write(
+        &path,
+        serde_json::json!(key.to_keypair_bytes()[..]).to_string(),
+    )
+    .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))?;
+    let program_id = program_id(key);
+    let mut ids = ProgramIds::load(deploy)?;
+    ids.insert(program, &program_id);
+    ids.save(deploy)?;
+    Ok(program_id)
+}
+
+/// Read a keypair file written by `solana-keygen` or `sbpf keys`, checking
+/// that its public half matches its secret half.
+pub fn read_keypair(path: &Path) -> Result<SigningKey> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", path.display(), e)))?;
+    let bytes: Vec<u8> = serde_json::from_str(&contents)
+        .map_err(|e| Error::msg(format!("'{}' is not a keypair file: {}", path.display(), e)))?;
+    let bytes: [u8; 64] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+        Error::msg(format!(
+            "'{}' is not a keypair file: expected 64 bytes, found {}",
+            path.display(),
+            bytes.len()
+        ))
+    })?;
+    SigningKey::from_keypair_bytes(&bytes).map_err(|_| {
+        Error::msg(format!(
+            "'{}' is not a keypair file: its public key doesn't match its secret key",
+            path.display()
+        ))
+    })
+}
+
+/// Program ids by program name, kept in the deploy directory next to the
+/// keypairs so tests and scripts can look an id up without decoding a
+/// keypair.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProgramIds(BTreeMap<String, String>);
+
+impl ProgramIds {
+    pub fn load(deploy: &Path) -> Result<Self> {
+        let path = deploy.join(PROGRAM_IDS_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Self)
+                .map_err(|e| Error::msg(format!("Invalid '{}': {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::msg(format!(
+                "Failed to read '{}': {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    pub fn save(&self, deploy: &Path) -> Result<()> {
+        let path = deploy.join(PROGRAM_IDS_FILE);
+        fs::write(&path, serde_json::to_string_pretty(&self.0)? + "\n")
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))
+    }
+
+    pub fn get(&self, program: &str) -> Option<&str> {
+        self.0.get(program).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, program: &str, program_id: &str) {
+        self.0.insert(program.to_string(), program_id.to_string());
+    }
+}
+
+/// The program named on the command line, or the project's only program.
+fn program_name(program: Option<String>) -> Result<String> {
+    if let Some(program) = program {
+        return Ok(program);
+    }
+    let mut programs = Vec::new();
+    if let Ok(entries) = fs::read_dir("src") {
+        for entry in entries.flatten() {
+            if entry.path().is_dir()
+                && let Some(name) = entry.file_name().to_str()
+            {
+                programs.push(name.to_string());
+            }
+        }
+    }
+    programs.sort();
+    match programs.as_slice() {
+        [program] => Ok(program.clone()),
+        [] => Err(Error::msg(
+            "No programs in src; name the program the keypair is for",
+        )),
+        _ => Err(Error::msg(format!(
+            "Name the program the keypair is for, one of: {}",
+            programs.join(", ")
+        ))),
+    }
+}
+
+/// Programs with a keypair in `deploy`, sorted.
+fn keypair_programs(deploy: &Path) -> Result<Vec<String>> {
+    let mut programs = Vec::new();
+    if !deploy.is_dir() {
+        return Ok(programs);
+    }
+    for entry in fs::read_dir(deploy)? {
+        let name = entry?.file_name();
+        if let Some(program) = name
+            .to_str()
+            .and_then(|name| name.strip_suffix("-keypair.json"))
+        {
+            programs.push(program.to_string());
+        }
+    }
+    programs.sort();
+    Ok(programs)
+}
+
+fn refuse_overwrite(deploy: &Path, program: &str, force: bool) -> Result<()> {
+    let path = keypair_path(deploy, program);
+    if path.exists() && !force {
+        return Err(Error::msg(format!(
+            "\"{}\" already has a keypair in {}; pass --force to replace it and change its program id",
+            program,
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+fn validate_prefix(prefix: &str) -> Result<()> {
+    if let Some(c) = prefix.chars().find(|c| !BASE58_ALPHABET.contains(*c)) {
+        return Err(Error::msg(format!(
+            "'{}' can't appear in a program id; base58 leaves out 0, O, I and l",
+            c
+        )));
+    }
+    // A 32-byte key is at most 44 base58 characters, and each one more than
+    // a few multiplies the search by 58.
+    if prefix.len() > 8 {
+        return Err(Error::msg(
+            "Prefixes longer than 8 characters would take too long to find",
+        ));
+    }
+    Ok(())
+}
+
+/// Generate keypairs on every core until one's program id starts with
+/// `prefix`. Returns it with the number of keypairs tried.
+fn grind(prefix: &str) -> (SigningKey, u64) {
+    let found = Mutex::new(None);
+    let done = AtomicBool::new(false);
+    let attempts = AtomicU64::new(0);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut rng = rand::rng();
+                while !done.load(Ordering::Relaxed) {
+                    let key = SigningKey::generate(&mut rng);
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    if program_id(&key).starts_with(prefix) {
+                        done.store(true, Ordering::Relaxed);
+                        found.lock().unwrap().get_or_insert(key);
+                    }
+                }
+            });
+        }
+    });
+    let key = found
+        .into_inner()
+        .unwrap()
+        .expect("threads only stop once a key is found");
+    (key, attempts.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keypairs_and_program_ids() {
+        let deploy = std::env::temp_dir().join(format!("sbpf-keys-{}", std::process::id()));
+
+        let first = ensure_keypair(&deploy, "vault").unwrap();
+        assert_eq!(ensure_keypair(&deploy, "vault").unwrap(), first);
+        let key = read_keypair(&keypair_path(&deploy, "vault")).unwrap();
+        assert_eq!(program_id(&key), first);
+
+        // A keypair without an entry, as in projects made before the ids
+        // file, gets one.
+        fs::remove_file(deploy.join(PROGRAM_IDS_FILE)).unwrap();
+        ensure_keypair(&deploy, "vault").unwrap();
+        assert_eq!(
+            ProgramIds::load(&deploy).unwrap().get("vault"),
+            Some(first.as_str())
+        );
+
+        let (ground, _) = grind("A");
+        let second = write_keypair(&deploy, "escrow", &ground).unwrap();
+        assert!(second.starts_with('A'));
+        assert_eq!(keypair_programs(&deploy).unwrap(), ["escrow", "vault"]);
+        let ids = ProgramIds::load(&deploy).unwrap();
+        assert_eq!(ids.get("escrow"), Some(second.as_str()));
+        assert_eq!(ids.get("vault"), Some(first.as_str()));
+
+        let path = deploy.join("short.json");
+        fs::write(&path, "[1, 2, 3]").unwrap();
+        assert!(
+            read_keypair(&path)
+                .unwrap_err()
+                .to_string()
+                .ends_with("expected 64 bytes, found 3")
+        );
+        assert!(validate_prefix("S0L").is_err());
+
+        fs::remove_dir_all(&deploy).unwrap();
+    }
+}
@@ -0,0 +1,396 @@
+use {
+    anyhow::{Error, Result},
+    clap::{Args, ValueEnum},
+    sbpf_assembler::{
+        preprocessor::layout::{FieldType, Layout, parse_layouts},
+        read_source,
+    },
+    std::fmt::Write,
+};
+
+#[derive(Clone, Copy, ValueEnum, Default)]
+pub enum LayoutLang {
+    #[default]
+    Rust,
+    Ts,
+}
+
+#[derive(Args)]
+pub struct LayoutArgs {
+    #[arg(help = "Assembly source or sidecar file declaring .layout blocks")]
+    pub file: String,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "rust",
+        help = "Language of the generated structs"
+    )]
+    pub lang: LayoutLang,
+    #[arg(short, long, help = "Write to a file instead of stdout")]
+    pub output: Option<String>,
+}
+
+pub fn layout(args: LayoutArgs) -> Result<(), Error> {
+    let source = read_source(std::path::Path::new(&args.file))
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", args.file, e)))?;
+    let layouts = parse_layouts(&source).map_err(|errors| {
+        let messages: Vec<String> = errors
+            .iter()
+            .map(|(error, line)| format!("{}:{}: {} ({})", args.file, line, error, error.label()))
+            .collect();
+        Error::msg(messages.join("\n"))
+    })?;
+    if layouts.is_empty() {
+        anyhow::bail!("'{}' declares no .layout blocks", args.file);
+    }
+
+    let file_name = std::path::Path::new(&args.file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let generated = match args.lang {
+        LayoutLang::Rust => render_rust(&layouts, &file_name),
+        LayoutLang::Ts => render_ts(&layouts, &file_name),
+    };
+    match args.output {
+        Some(path) => std::fs::write(&path, generated)
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path, e)))?,
+        None => print!("{}", generated),
+    }
+    Ok(())
+}
+
+/// Rust structs with offset constants and little-endian `from_bytes` /
+/// `to_bytes` conversions.
+fn render_rust(layouts: &[Layout], file_name: &str) -> String {
+    let mut out = format!(
+        "// Generated by `sbpf layout` from {}. Do not edit.\n",
+        file_name
+    );
+    for layout in layouts {
+        let name = camel_case(&layout.name, true);
+        write!(
+            out,
+            "\n#[derive(Debug, Clone, Copy, PartialEq, Eq)]\npub struct {} {{\n",
+            name
+        )
+        .expect("writing to a String cannot fail");
+        for field in &layout.fields {
+            writeln!(
+                out,
+                "    pub {}: {},",
+                field.upper_name().to_ascii_lowercase(),
+                rust_type(field.ty)
+            )
+            .expect("writing to a String cannot fail");
+        }
+        write!(
+            out,
+            "}}\n\nimpl {} {{\n    pub const LEN: usize = {};\n",
+            name, layout.size
+        )
+        .expect("writing to a String cannot fail");
+        for field in &layout.fields {
+            writeln!(
+                out,
+                "    pub const {}_OFFSET: usize = {};",
+                field.upper_name(),
+                field.offset
+            )
+            .expect("writing to a String cannot fail");
+        }
+
+        out.push_str(
+            "\n    pub fn from_bytes(data: &[u8]) -> Option<Self> {\n        \
+             if data.len() < Self::LEN {\n            return None;\n        }\n        \
+             Some(Self {\n",
+        );
+        for field in &layout.fields {
+            let range = format!("{}..{}", field.offset, field.offset + field.ty.size());
+            let value = if field.ty.is_integer() {
+                format!(
+                    "{}::from_le_bytes(data[{}].try_into().unwrap())",
+                    rust_type(field.ty),
+                    range
+                )
+            } else {
+                format!("data[{}].try_into().unwrap()", range)
+            };
+            writeln!(
+                out,
+                "            {}: {},",
+                field.upper_name().to_ascii_lowercase(),
+                value
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("        })\n    }\n");
+
+        out.push_str(
+            "\n    pub fn to_bytes(&self) -> Vec<u8> {\n        \
+             let mut data = vec![0; Self::LEN];\n",
+        );
+        for field in &layout.fields {
+            let name = field.upper_name().to_ascii_lowercase();
+            let source = if field.ty.is_integer() {
+                format!("&self.{}.to_le_bytes()", name)
+            } else {
+                format!("&self.{}", name)
+            };
+            writeln!(
+                out,
+                "        data[{}..{}].copy_from_slice({});",
+                field.offset,
+                field.offset + field.ty.size(),
+                source
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("        data\n    }\n}\n");
+    }
+    out
+}
+
+fn rust_type(ty: FieldType) -> String {
+    match ty {
+        FieldType::U8 => "u8".to_string(),
+        FieldType::U16 => "u16".to_string(),
+        FieldType::U32 => "u32".to_string(),
+        FieldType::U64 => "u64".to_string(),
+        FieldType::I8 => "i8".to_string(),
+        FieldType::I16 => "i16".to_string(),
+        FieldType::I32 => "i32".to_string(),
+        FieldType::I64 => "i64".to_string(),
+        FieldType::Pubkey => "[u8; 32]".to_string(),
+        FieldType::Bytes(len) => format!("[u8; {}]", len),
+    }
+}
+
+/// TypeScript classes reading and writing through a little-endian `DataView`.
+fn render_ts(layouts: &[Layout], file_name: &str) -> String {
+    format!(
+        "// Generated by `sbpf layout` from {}. Do not edit.\n{}",
+        file_name,
+        ts_classes(layouts)
+    )
+}
+
+/// One TypeScript class per layout, each preceded by a blank line.
+pub(crate) fn ts_classes(layouts: &[Layout]) -> String {
+    let mut out = String::new();
+    for layout in layouts {
+        let name = camel_case(&layout.name, true);
+        write!(
+            out,
+            "\nexport class {} {{\n  static readonly LEN = {};\n",
+            name, layout.size
+        )
+        .expect("writing to a String cannot fail");
+        for field in &layout.fields {
+            writeln!(
+                out,
+                "  static readonly {}_OFFSET = {};",
+                field.upper_name(),
+                field.offset
+            )
+            .expect("writing to a String cannot fail");
+        }
+
+        out.push_str("\n  constructor(\n");
+        for field in &layout.fields {
+            writeln!(
+                out,
+                "    public {}: {},",
+                camel_case(&field.name, false),
+                ts_type(field.ty)
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("  ) {}\n");
+
+        write!(
+            out,
+            "\n  static fromBytes(data: Uint8Array): {} {{\n    \
+             const view = new DataView(data.buffer, data.byteOffset, data.byteLength);\n    \
+             return new {}(\n",
+            name, name
+        )
+        .expect("writing to a String cannot fail");
+        for field in &layout.fields {
+            let value = match data_view_method(field.ty) {
+                Some(method) if field.ty.size() == 1 => {
+                    format!("view.get{}({})", method, field.offset)
+                }
+                Some(method) => format!("view.get{}({}, true)", method, field.offset),
+                None => format!(
+                    "data.slice({}, {})",
+                    field.offset,
+                    field.offset + field.ty.size()
+                ),
+            };
+            writeln!(out, "      {},", value).expect("writing to a String cannot fail");
+        }
+        out.push_str("    );\n  }\n");
+
+        write!(
+            out,
+            "\n  toBytes(): Uint8Array {{\n    \
+             const data = new Uint8Array({}.LEN);\n    \
+             const view = new DataView(data.buffer);\n",
+            name
+        )
+        .expect("writing to a String cannot fail");
+        for field in &layout.fields {
+            let value = format!("this.{}", camel_case(&field.name, false));
+            match data_view_method(field.ty) {
+                Some(method) if field.ty.size() == 1 => {
+                    writeln!(out, "    view.set{}({}, {});", method, field.offset, value)
+                }
+                Some(method) => writeln!(
+                    out,
+                    "    view.set{}({}, {}, true);",
+                    method, field.offset, value
+                ),
+                None => writeln!(out, "    data.set({}, {});", value, field.offset),
+            }
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("    return data;\n  }\n}\n");
+    }
+    out
+}
+
+pub(crate) fn ts_type(ty: FieldType) -> &'static str {
+    match ty {
+        FieldType::U64 | FieldType::I64 => "bigint",
+        FieldType::Pubkey | FieldType::Bytes(_) => "Uint8Array",
+        _ => "number",
+    }
+}
+
+pub(crate) fn data_view_method(ty: FieldType) -> Option<&'static str> {
+    Some(match ty {
+        FieldType::U8 => "Uint8",
+        FieldType::U16 => "Uint16",
+        FieldType::U32 => "Uint32",
+        FieldType::U64 => "BigUint64",
+        FieldType::I8 => "Int8",
+        FieldType::I16 => "Int16",
+        FieldType::I32 => "Int32",
+        FieldType::I64 => "BigInt64",
+        FieldType::Pubkey | FieldType::Bytes(_) => return None,
+    })
+}
+
+/// `counter_state` becomes `CounterState`, or `counterState` when `upper` is
+/// false. Names already in camel case are kept.
+pub(crate) fn camel_case(name: &str, upper: bool) -> String {
+    let mut result = String::new();
+    let mut capitalize = upper;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize = !result.is_empty();
+        } else if capitalize {
+            result.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else if result.is_empty() && !upper {
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        ".layout counter_state\n  bump: u8\n  count: u64\n  owner: pubkey\n.endlayout\n";
+
+    #[test]
+    fn test_render_rust() {
+        let layouts = parse_layouts(SOURCE).unwrap();
+        assert_eq!(
+            render_rust(&layouts, "counter.layout"),
+            r#"// Generated by `sbpf layout` from counter.layout. Do not edit.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterState {
+    pub bump: u8,
+    pub count: u64,
+    pub owner: [u8; 32],
+}
+
+impl CounterState {
+    pub const LEN: usize = 41;
+    pub const BUMP_OFFSET: usize = 0;
+    pub const COUNT_OFFSET: usize = 1;
+    pub const OWNER_OFFSET: usize = 9;
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::LEN {
+            return None;
+        }
+        Some(Self {
+            bump: u8::from_le_bytes(data[0..1].try_into().unwrap()),
+            count: u64::from_le_bytes(data[1..9].try_into().unwrap()),
+            owner: data[9..41].try_into().unwrap(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; Self::LEN];
+        data[0..1].copy_from_slice(&self.bump.to_le_bytes());
+        data[1..9].copy_from_slice(&self.count.to_le_bytes());
+        data[9..41].copy_from_slice(&self.owner);
+        data
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_ts() {
+        let layouts = parse_layouts(SOURCE).unwrap();
+        assert_eq!(
+            render_ts(&layouts, "counter.layout"),
+            r#"// Generated by `sbpf layout` from counter.layout. Do not edit.
+
+export class CounterState {
+  static readonly LEN = 41;
+  static readonly BUMP_OFFSET = 0;
+  static readonly COUNT_OFFSET = 1;
+  static readonly OWNER_OFFSET = 9;
+
+  constructor(
+    public bump: number,
+    public count: bigint,
+    public owner: Uint8Array,
+  ) {}
+
+  static fromBytes(data: Uint8Array): CounterState {
+    const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+    return new CounterState(
+      view.getUint8(0),
+      view.getBigUint64(1, true),
+      data.slice(9, 41),
+    );
+  }
+
+  toBytes(): Uint8Array {
+    const data = new Uint8Array(CounterState.LEN);
+    const view = new DataView(data.buffer);
+    view.setUint8(0, this.bump);
+    view.setBigUint64(1, this.count, true);
+    data.set(this.owner, 9);
+    return data;
+  }
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use {
+    super::common::{LoadedProgram, SolanaSyscalls, assemble_for_vm, parse_hex},
+    anyhow::{Error, Result},
+    clap::{Args, Subcommand},
+    sbpf_assembler::{AssemblerOption, read_source},
+    serde::Deserialize,
+    std::{fs, path::Path},
+};
+
+/// Exercises shipped with sbpf, in the order they are meant to be done.
+const BUILTIN_EXERCISES: &[(&str, &str)] = &[
+    ("hello", include_str!("../exercises/hello.toml")),
+    ("add", include_str!("../exercises/add.toml")),
+    ("max", include_str!("../exercises/max.toml")),
+];
+
+#[derive(Args)]
+pub struct LearnArgs {
+    #[command(subcommand)]
+    pub command: LearnCommand,
+}
+
+#[derive(Subcommand)]
+pub enum LearnCommand {
+    #[command(about = "List the built-in exercises")]
+    List,
+    #[command(about = "Show an exercise and write its starter code")]
+    Start {
+        #[arg(help = "Built-in exercise name or path to an exercise .toml file")]
+        exercise: String,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the starter code [default: <exercise>.s]"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Check a solution against the exercise's tests")]
+    Check {
+        #[arg(help = "Built-in exercise name or path to an exercise .toml file")]
+        exercise: String,
+        #[arg(help = "Solution to check [default: <exercise>.s]")]
+        solution: Option<String>,
+    },
+}
+
+/// An exercise bundle: what to do, code to start from, and the tests a
+/// solution has to pass.
+#[derive(Deserialize, Debug)]
+pub struct Exercise {
+    pub title: String,
+    pub description: String,
+    pub starter: String,
+    #[serde(default)]
+    pub tests: Vec<ExerciseTest>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExerciseTest {
+    pub name: String,
+    /// Input region contents as hex; whitespace is ignored.
+    #[serde(default)]
+    pub input: String,
+    pub exit_code: Option<u64>,
+    /// Exact log lines, e.g. `Program log: hello`.
+    pub logs: Option<Vec<String>>,
+    pub max_compute_units: Option<u64>,
+}
+
+pub fn learn(args: LearnArgs) -> Result<(), Error> {
+    match args.command {
+        LearnCommand::List => {
+            for (name, source) in BUILTIN_EXERCISES {
+                let exercise = parse_exercise(source, name)?;
+                println!("{:<8} {}", name, exercise.title);
+            }
+            println!("\nStart one with `sbpf learn start <exercise>`.");
+            Ok(())
+        }
+        LearnCommand::Start { exercise, output } => {
+            let (name, exercise) = load_exercise(&exercise)?;
+            println!(
+                "# {}\n\n{}",
+                exercise.title,
+                exercise.description.trim_end()
+            );
+
+            let output = output.unwrap_or_else(|| format!("{}.s", name));
+            if Path::new(&output).exists() {
+                println!("\n{} already exists, leaving it as is.", output);
+            } else {
+                fs::write(&output, exercise.starter.trim_start())?;
+                println!("\n✅ Starter code written to {}", output);
+            }
+            println!(
+                "Check your solution with `sbpf learn check {} {}`.",
+                name, output
+            );
+            Ok(())
+        }
+        LearnCommand::Check { exercise, solution } => {
+            let (name, exercise) = load_exercise(&exercise)?;
+            let solution = solution.unwrap_or_else(|| format!("{}.s", name));
+            let source = read_source(Path::new(&solution))
+                .map_err(|e| Error::msg(format!("Failed to read '{}': {}", solution, e)))?;
+
+            let results = check_solution(&exercise, &source)?;
+            let passed = results
+                .iter()
+                .filter(|(_, failure)| failure.is_none())
+                .count();
+            for (test, failure) in &results {
+                match failure {
+                    None => println!("✅ {}", test),
+                    Some(failure) => println!("❌ {}: {}", test, failure),
+                }
+            }
+            println!("{}/{} tests passed", passed, results.len());
+            if passed == results.len() {
+                println!("🎉 \"{}\" solved!", exercise.title);
+                Ok(())
+            } else {
+                Err(Error::msg("Some tests failed"))
+            }
+        }
+    }
+}
+
+/// Resolve a built-in exercise name or a path to an exercise file.
+fn load_exercise(exercise: &str) -> Result<(String, Exercise)> {
+    if let Some((name, source)) = BUILTIN_EXERCISES.iter().find(|(name, _)| *name == exercise) {
+        return Ok((name.to_string(), parse_exercise(source, name)?));
+    }
+    let path = Path::new(exercise);
+    if !path.is_file() {
+        let names: Vec<&str> = BUILTIN_EXERCISES.iter().map(|(name, _)| *name).collect();
+        return Err(Error::msg(format!(
+            "Unknown exercise '{}'. Built-in exercises: {}",
+            exercise,
+            names.join(", ")
+        )));
+    }
+    let source = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", exercise, e)))?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("exercise")
+        .to_string();
+    let parsed = parse_exercise(&source, exercise)?;
+    Ok((name, parsed))
+}
+
+fn parse_exercise(source: &str, name: &str) -> Result<Exercise> {
+    toml::from_str(source).map_err(|e| Error::msg(format!("Invalid exercise '{}': {}", name, e)))
+}
+
+/// Run every test of `exercise` against `source`, returning each test name
+/// with the reason it failed, if it did.
+fn check_solution(exercise: &Exercise, source: &str) -> Result<Vec<(String, Option<String>)>> {
+    let program = assemble_for_vm(source, AssemblerOption::default())
+        .map_err(|e| Error::msg(format!("Solution does not assemble: {}", e)))?;
+    exercise
+        .tests
+        .iter()
+        .map(|test| Ok((test.name.clone(), run_test(&program, test)?)))
+        .collect()
+}
+
+fn run_test(program: &LoadedProgram, test: &ExerciseTest) -> Result<Option<String>> {
+    let input = parse_hex(&test.input)
+        .ok_or_else(|| Error::msg(format!("Invalid input in test '{}'", test.name)))?;
+    let mut vm = program.clone().into_vm(input, SolanaSyscalls::default());
+    if let Err(e) = vm.run() {
+        return Ok(Some(format!("program failed: {}", e)));
+    }
+
+    let exit_code = vm.exit_code.unwrap_or_default();
+    let compute_units = vm.compute_meter.get_consumed();
+    let failure = if let Some(expected) = test.exit_code
+        && expected != exit_code
+    {
+        Some(format!(
+            "expected exit code {}, got {}",
+            expected, exit_code
+        ))
+    } else if let Some(expected) = &test.logs
+        && *expected != vm.syscall_handler.logs
+    {
+        Some(format!(
+            "expected logs {:?}, got {:?}",
+            expected, vm.syscall_handler.logs
+        ))
+    } else if let Some(limit) = test.max_compute_units
+        && compute_units > limit
+    {
+        Some(format!(
+            "used {} compute units, the limit is {}",
+            compute_units, limit
+        ))
+    } else {
+        None
+    };
+    Ok(failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, solution: &str) -> Vec<(String, Option<String>)> {
+        let (_, exercise) = load_exercise(name).unwrap();
+        check_solution(&exercise, solution).unwrap()
+    }
+
+    fn all_pass(results: &[(String, Option<String>)]) -> bool {
+        results.iter().all(|(_, failure)| failure.is_none())
+    }
+
+    #[test]
+    fn test_builtin_exercises_are_solvable() {
+        let solutions = [
+            (
+                "hello",
+                ".globl entrypoint\nentrypoint:\n  lddw r1, message\n  mov64 r2, 14\n  call sol_log_\n  mov64 r0, 0\n  exit\n.rodata\n  message: .ascii \"Hello, Solana!\"\n",
+            ),
+            (
+                "add",
+                ".globl entrypoint\nentrypoint:\n  ldxdw r0, [r1+0]\n  ldxdw r2, [r1+8]\n  add64 r0, r2\n  exit\n",
+            ),
+            (
+                "max",
+                ".globl entrypoint\nentrypoint:\n  ldxdw r0, [r1+0]\n  ldxdw r2, [r1+8]\n  jge r0, r2, done\n  mov64 r0, r2\ndone:\n  exit\n",
+            ),
+        ];
+        assert_eq!(solutions.len(), BUILTIN_EXERCISES.len());
+        for (name, solution) in solutions {
+            let results = check(name, solution);
+            assert!(all_pass(&results), "{}: {:?}", name, results);
+        }
+    }
+
+    #[test]
+    fn test_solution_with_writable_data() {
+        // The sum goes through a .bss slot, plus a zero bias from .data.
+        let solution = ".globl entrypoint\nentrypoint:\n  lddw r3, sum\n  ldxdw r0, [r1+0]\n  stxdw [r3+0], r0\n  ldxdw r2, [r1+8]\n  ldxdw r0, [r3+0]\n  add64 r0, r2\n  lddw r4, bias\n  ldxdw r4, [r4+0]\n  add64 r0, r4\n  exit\n.data\n  bias: .quad 0\n.bss\n  sum: .zero 8\n";
+        let results = check("add", solution);
+        assert!(all_pass(&results), "{:?}", results);
+    }
+
+    #[test]
+    fn test_starter_code_fails() {
+        for (name, source) in BUILTIN_EXERCISES {
+            let exercise = parse_exercise(source, name).unwrap();
+            let results = check_solution(&exercise, &exercise.starter).unwrap();
+            assert!(!all_pass(&results), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_failure_reasons() {
+        let results = check(
+            "max",
+            ".globl entrypoint\nentrypoint:\n  ldxdw r0, [r1+0]\n  exit\n",
+        );
+        assert_eq!(results[0], ("first is larger".to_string(), None));
+        assert_eq!(results[1].1.as_deref(), Some("expected exit code 9, got 4"));
+
+        let results = check("add", ".globl entrypoint\nentrypoint:\n  ja entrypoint\n");
+        assert!(
+            results[0]
+                .1
+                .as_deref()
+                .unwrap()
+                .starts_with("program failed: Compute budget exceeded")
+        );
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("01 ff\n0a"), Some(vec![0x01, 0xff, 0x0a]));
+        assert_eq!(parse_hex(""), Some(vec![]));
+        assert_eq!(parse_hex("abc"), None);
+    }
+}
@@ -0,0 +1,337 @@
+use {
+    super::{
+        build::{ArchArg, emit_assembler_errors},
+        common::{ErrorFormat, OutputFormat, print_json},
+        config::ProjectConfig,
+        disassemble::function_entries,
+        grep::{Located, assemble_source, decode},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_analyze::{Finding, security_lints},
+    sbpf_assembler::{
+        Assembler, AssemblerOption, CompileError, FsFileResolver, LintConfig, is_allowed,
+        read_source,
+    },
+    sbpf_ir::{InputNode, control_flow_graph},
+    std::{collections::HashMap, path::Path},
+};
+
+/// Name of the rule reporting code nothing reaches.
+const UNREACHABLE_CODE: &str = "unreachable-code";
+/// Names of the rules reporting functions over the limits in `sbpf.toml`.
+const STACK_LIMIT_EXCEEDED: &str = "stack-limit-exceeded";
+const COMPUTE_LIMIT_EXCEEDED: &str = "compute-limit-exceeded";
+
+#[derive(Args)]
+pub struct LintArgs {
+    #[arg(
+        required = true,
+        help = "Programs to check: built ELF files (.so) or assembly sources (.s)"
+    )]
+    pub files: Vec<String>,
+    #[arg(
+        short = 'a',
+        long,
+        default_value = "v3",
+        help = "Target architecture for assembly sources (v0-v3)"
+    )]
+    pub arch: ArchArg,
+    #[arg(
+        long,
+        help = "Run the security rules: signer checks, lamport arithmetic and owner checks"
+    )]
+    pub security: bool,
+    #[arg(
+        long,
+        help = "Report instructions no jump, call or fall-through reaches (assembly sources only)"
+    )]
+    pub dead_code: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with one entry per finding"
+    )]
+    pub output: OutputFormat,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "human",
+        help = "How assembler errors are printed on stderr: human, or json with one object per line"
+    )]
+    pub error_format: ErrorFormat,
+}
+
+/// A finding of any rule, located for printing and suppression.
+struct Report {
+    location: String,
+    source: Option<(String, u32)>,
+    rule: &'static str,
+    message: String,
+}
+
+pub fn lint(args: LintArgs) -> Result<(), Error> {
+    let lints = ProjectConfig::load()?.lint;
+    // Without a selection every rule runs, with the function limits
+    // `sbpf.toml` sets.
+    let all = !args.security && !args.dead_code;
+    let mut sources = HashMap::new();
+    let mut reported = 0;
+    let mut json = Vec::new();
+    for file in &args.files {
+        let mut reports = Vec::new();
+        if args.security || all {
+            let located = if file.ends_with(".s") {
+                assemble_source(file, args.arch, args.error_format)?
+            } else {
+                let bytes = std::fs::read(file)
+                    .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file, e)))?;
+                decode(&bytes, file, None)?
+            };
+            reports.extend(check_security(&located));
+        }
+        // Built programs keep no labels for code reached only through a
+        // function pointer, so dead code is only looked for in sources.
+        if (args.dead_code || all) && file.ends_with(".s") {
+            reports.extend(
+                check_assembly(file, args.arch, &lints, args.error_format)?
+                    .into_iter()
+                    .filter(|report| all || report.rule == UNREACHABLE_CODE),
+            );
+        }
+        for report in reports {
+            if lints.is_allowed(report.rule) || suppressed(&report, &mut sources) {
+                continue;
+            }
+            if args.output.is_json() {
+                json.push(serde_json::json!({
+                    "file": file,
+                    "location": report.location,
+                    "rule": report.rule,
+                    "message": report.message,
+                }));
+            } else {
+                println!("{}: {}: {}", report.location, report.rule, report.message);
+            }
+            reported += 1;
+        }
+    }
+    if args.output.is_json() {
+        print_json(&json)?;
+    }
+    if reported > 0 {
+        return Err(Error::msg(format!(
+            "{} finding{}",
+            reported,
+            if reported == 1 { "" } else { "s" }
+        )));
+    }
+    Ok(())
+}
+
+/// Run the security rules over a decoded program.
+fn check_security(located: &[Located]) -> Vec<Report> {
+    check(located)
+        .into_iter()
+        .map(|(index, finding)| Report {
+            location: located[index].location.clone(),
+            source: located[index].source.clone(),
+            rule: finding.rule.name(),
+            message: finding.message,
+        })
+        .collect()
+}
+
+/// Report the unreachable code and the functions over `lints`' limits the
+/// assembler warns about in `file`.
+fn check_assembly(
+    file: &str,
+    arch: ArchArg,
+    lints: &LintConfig,
+    error_format: ErrorFormat,
+) -> Result<Vec<Report>> {
+    let source = read_source(Path::new(file))
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", file, e)))?;
+    let assembler = Assembler::new(
+        AssemblerOption::default()
+            .with_arch(arch.into())
+            .with_lints(lints.clone()),
+    );
+    let warnings = match assembler.lint_with_preprocess(&source, file, Some(&FsFileResolver::new()))
+    {
+        Ok(warnings) => warnings,
+        Err(assemble_errors) => {
+            emit_assembler_errors(&assemble_errors, error_format)?;
+            return Err(Error::msg("Compilation failed"));
+        }
+    };
+    Ok(warnings
+        .errors
+        .iter()
+        .filter_map(|warning| {
+            let (rule, message) = match &warning.error {
+                CompileError::UnreachableCode { .. } => {
+                    (UNREACHABLE_CODE, warning.error.label().to_string())
+                }
+                CompileError::StackLimitExceeded { .. } => {
+                    (STACK_LIMIT_EXCEEDED, warning.error.to_string())
+                }
+                CompileError::ComputeLimitExceeded { .. } => {
+                    (COMPUTE_LIMIT_EXCEEDED, warning.error.to_string())
+                }
+                _ => return None,
+            };
+            let origin = warning.origin.as_ref()?;
+            let path = warnings.file_registry.path(origin.file_id).to_string();
+            Some(Report {
+                location: format!("{}:{}", path, origin.line),
+                source: Some((path, origin.line)),
+                rule,
+                message,
+            })
+        })
+        .collect())
+}
+
+/// Run the rules over a decoded program, returning each finding with the
+/// index of the instruction it points at.
+fn check(located: &[Located]) -> Vec<(usize, Finding)> {
+    let mut labels: Vec<Vec<String>> = located.iter().map(|l| l.labels.clone()).collect();
+    let function_entries = function_entries(&mut labels);
+
+    // Node ids count labels too, so remember which instruction each names.
+    let mut nodes = Vec::new();
+    let mut instruction_of = Vec::new();
+    for (index, (located, labels)) in located.iter().zip(&labels).enumerate() {
+        for label in labels {
+            nodes.push(InputNode::Label(label));
+            instruction_of.push(index);
+        }
+        nodes.push(InputNode::Instruction(&located.instruction));
+        instruction_of.push(index);
+    }
+    let cfg = control_flow_graph(nodes, &function_entries, Some("entrypoint"));
+    security_lints(&cfg)
+        .into_iter()
+        .map(|finding| (instruction_of[finding.node_id], finding))
+        .collect()
+}
+
+/// Whether an `sbpf-allow(rule)` comment on the finding's source line, or
+/// attached to it from the lines above, silences it.
+fn suppressed(report: &Report, sources: &mut HashMap<String, String>) -> bool {
+    let Some((file, line)) = &report.source else {
+        return false;
+    };
+    let source = sources
+        .entry(file.clone())
+        .or_insert_with(|| std::fs::read_to_string(file).unwrap_or_default());
+    is_allowed(source, *line, report.rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption},
+    };
+
+    fn lint_source(source: &str) -> Vec<String> {
+        let assembler = Assembler::new(AssemblerOption::default());
+        let artifacts = assembler
+            .assemble_full_with_preprocess(source, "test.s", None)
+            .unwrap();
+        let located = decode(&artifacts.elf, "test.s", Some(&artifacts.sourcemap)).unwrap();
+        let mut sources = HashMap::from([("test.s".to_string(), source.to_string())]);
+        check_security(&located)
+            .into_iter()
+            .filter(|report| !suppressed(report, &mut sources))
+            .map(|report| format!("{}: {}", report.location, report.rule))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_security() {
+        let source = r#".globl entrypoint
+entrypoint:
+  ldxdw r2, [r1+80]
+  sub64 r2, 100
+  stxdw [r1+80], r2
+  ldxdw r3, [r1+10424]
+  jlt r3, 100, fail
+  add64 r3, 100
+  stxdw [r1+10424], r3
+  exit
+fail:
+  exit
+"#;
+        assert_eq!(
+            lint_source(source),
+            vec![
+                "test.s:4: unchecked-lamports-arithmetic",
+                "test.s:5: missing-signer-check",
+                "test.s:8: unchecked-lamports-arithmetic",
+            ]
+        );
+
+        let suppressed = source
+            .replace(
+                "  sub64 r2, 100",
+                "  ; sbpf-allow(missing-signer-check, unchecked-lamports-arithmetic)\n  sub64 r2, 100",
+            )
+            .replace(
+                "  stxdw [r1+80], r2",
+                "  stxdw [r1+80], r2 ; sbpf-allow(missing-signer-check)",
+            );
+        assert_eq!(
+            lint_source(&suppressed),
+            vec!["test.s:9: unchecked-lamports-arithmetic"]
+        );
+    }
+
+    #[test]
+    fn test_lint_unreachable_code() {
+        let dir = std::env::temp_dir().join("sbpf_test_lint_unreachable_code");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dead.s");
+        let file = path.to_str().unwrap();
+        let source = ".globl entrypoint\nentrypoint:\n  exit\n  mov64 r0, 1\n  exit\n";
+
+        std::fs::write(&path, source).unwrap();
+        let reports = check_assembly(
+            file,
+            ArchArg::V3,
+            &LintConfig::default(),
+            ErrorFormat::Human,
+        )
+        .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].location, format!("{}:4", file));
+        assert_eq!(reports[0].rule, "unreachable-code");
+        assert!(!suppressed(&reports[0], &mut HashMap::new()));
+
+        let allowed = source.replace("  mov64", "  ; sbpf-allow(unreachable-code)\n  mov64");
+        std::fs::write(&path, allowed).unwrap();
+        let reports = check_assembly(
+            file,
+            ArchArg::V3,
+            &LintConfig::default(),
+            ErrorFormat::Human,
+        )
+        .unwrap();
+        assert!(reports.is_empty());
+
+        std::fs::write(&path, source).unwrap();
+        let lints = LintConfig {
+            max_compute_units: Some(0),
+            ..Default::default()
+        };
+        let reports = check_assembly(file, ArchArg::V3, &lints, ErrorFormat::Human).unwrap();
+        let rules: Vec<&str> = reports.iter().map(|report| report.rule).collect();
+        assert_eq!(rules, ["compute-limit-exceeded", "unreachable-code"]);
+        assert_eq!(reports[0].location, format!("{}:2", file));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+use {
+    super::{
+        build::{BuildArgs, build},
+        common::OutputFormat,
+        deploy::{DeployArgs, deploy},
+        rpc::Rpc,
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    std::{
+        fs::{self, File},
+        path::{Path, PathBuf},
+        process::{Child, Command, Stdio},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// How often the validator is polled while it starts.
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Lines of the validator log shown when it fails to start.
+const LOG_TAIL_LINES: usize = 20;
+
+/// How to run `solana-test-validator`, shared by `localnet` and `e2e`.
+#[derive(Args, Clone)]
+pub struct LocalnetOptions {
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "test-ledger",
+        help = "Ledger directory of the validator"
+    )]
+    pub ledger: PathBuf,
+    #[arg(long, default_value_t = 8899, help = "RPC port of the validator")]
+    pub rpc_port: u16,
+    #[arg(long, help = "Start from an empty ledger")]
+    pub reset: bool,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 60,
+        help = "How long to wait for the validator to report healthy"
+    )]
+    pub startup_timeout: u64,
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = ".sbpf/localnet.log",
+        help = "File the validator's output is written to"
+    )]
+    pub log: PathBuf,
+    #[arg(
+        long,
+        value_name = "SOL",
+        default_value_t = 10,
+        help = "SOL to airdrop to the fee payer once the validator is up; 0 skips the airdrop"
+    )]
+    pub airdrop: u64,
+}
+
+#[derive(Args)]
+pub struct LocalnetArgs {
+    #[command(flatten)]
+    pub options: LocalnetOptions,
+    #[arg(
+        short = 'k',
+        long,
+        value_name = "KEYPAIR",
+        help = "Fee payer to fund [default: the Solana CLI's keypair]"
+    )]
+    pub keypair: Option<PathBuf>,
+    #[arg(long, help = "Build and deploy every program once the validator is up")]
+    pub deploy: bool,
+}
+
+/// A running `solana-test-validator`, stopped when dropped. One that was
+/// already running when it was started is left alone.
+pub struct Localnet {
+    validator: Option<Child>,
+    pub url: String,
+    log: PathBuf,
+}
+
+impl Localnet {
+    /// Start a validator and wait until its RPC reports healthy, or reuse
+    /// one that already serves `rpc_port`.
+    pub fn start(options: &LocalnetOptions, output: OutputFormat) -> Result<Self> {
+        let url = format!("http://127.0.0.1:{}", options.rpc_port);
+        let rpc = Rpc::new(&url);
+        if rpc.is_healthy() {
+            output.status(format!(
+                "♻️  Using the validator already running at {}",
+                url
+            ));
+            return Ok(Self {
+                validator: None,
+                url,
+                log: options.log.clone(),
+            });
+        }
+
+        if let Some(dir) = options.log.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let log = File::create(&options.log).map_err(|e| {
+            Error::msg(format!(
+                "Failed to create '{}': {}",
+                options.log.display(),
+                e
+            ))
+        })?;
+        let mut command = Command::new("solana-test-validator");
+        command
+            .arg("--ledger")
+            .arg(&options.ledger)
+            .arg("--rpc-port")
+            .arg(options.rpc_port.to_string())
+            .arg("--log")
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+        if options.reset {
+            command.arg("--reset");
+        }
+        let validator = command
+            .spawn()
+            .map_err(|e| Error::msg(format!("Failed to run solana-test-validator: {}", e)))?;
+        output.status(format!(
+            "🚀 Starting solana-test-validator at {} (log: {})",
+            url,
+            options.log.display()
+        ));
+        let mut localnet = Self {
+            validator: Some(validator),
+            url,
+            log: options.log.clone(),
+        };
+        localnet.wait_until_healthy(&rpc, Duration::from_secs(options.startup_timeout))?;
+        output.status("✅ Validator is healthy");
+        Ok(localnet)
+    }
+
+    /// Poll `getHealth` until it answers "ok", failing early if the
+    /// validator exits. Validators often take a few seconds to open their
+    /// RPC port, so refused connections are retried.
+    fn wait_until_healthy(&mut self, rpc: &Rpc, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            if rpc.is_healthy() {
+                return Ok(());
+            }
+            if let Some(validator) = &mut self.validator
+                && let Some(status) = validator.try_wait()?
+            {
+                return Err(Error::msg(format!(
+                    "❌ solana-test-validator exited with {} before it was healthy{}",
+                    status,
+                    log_tail(&self.log)
+                )));
+            }
+            if start.elapsed() > timeout {
+                return Err(Error::msg(format!(
+                    "❌ solana-test-validator wasn't healthy after {}s{}",
+                    timeout.as_secs(),
+                    log_tail(&self.log)
+                )));
+            }
+            thread::sleep(HEALTH_POLL_INTERVAL);
+        }
+    }
+
+    /// Fund `keypair`, or the Solana CLI's keypair, from the validator's
+    /// faucet.
+    pub fn airdrop(&self, sol: u64, keypair: Option<&Path>, output: OutputFormat) -> Result<()> {
+        if sol == 0 {
+            return Ok(());
+        }
+        let mut command = Command::new("solana");
+        command
+            .arg("airdrop")
+            .arg(sol.to_string())
+            .arg("-u")
+            .arg(&self.url);
+        if let Some(keypair) = keypair {
+            command.arg("-k").arg(keypair);
+        }
+        let status = command
+            .stdout(match output {
+                OutputFormat::Text => Stdio::inherit(),
+                OutputFormat::Json => std::io::stderr().into(),
+            })
+            .status()
+            .map_err(|e| Error::msg(format!("Failed to run `solana airdrop`: {}", e)))?;
+        if !status.success() {
+            return Err(Error::msg(format!(
+                "❌ Failed to airdrop {} SOL on {}",
+                sol, self.url
+            )));
+        }
+        Ok(())
+    }
+
+    /// Block until the validator exits, which Ctrl-C does along with this
+    /// process.
+    pub fn wait(mut self) -> Result<()> {
+        let Some(validator) = &mut self.validator else {
+            return Ok(());
+        };
+        let status = validator.wait()?;
+        self.validator = None;
+        if !status.success() {
+            return Err(Error::msg(format!(
+                "❌ solana-test-validator exited with {}{}",
+                status,
+                log_tail(&self.log)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Stop the validator if this started it.
+    pub fn stop(mut self, output: OutputFormat) {
+        if self.validator.is_some() {
+            self.kill();
+            output.status("🛑 Stopped solana-test-validator");
+        }
+    }
+
+    fn kill(&mut self) {
+        if let Some(mut validator) = self.validator.take() {
+            let _ = validator.kill();
+            let _ = validator.wait();
+        }
+    }
+}
+
+impl Drop for Localnet {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// The last lines of the validator log, to explain why it didn't start.
+fn log_tail(log: &Path) -> String {
+    let contents = fs::read_to_string(log).unwrap_or_default();
+    let lines: Vec<&str> = contents.lines().collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let tail = &lines[lines.len().saturating_sub(LOG_TAIL_LINES)..];
+    format!("; last lines of {}:\n{}", log.display(), tail.join("\n"))
+}
+
+pub fn localnet(args: LocalnetArgs) -> Result<()> {
+    let output = OutputFormat::Text;
+    let localnet = Localnet::start(&args.options, output)?;
+    localnet.airdrop(args.options.airdrop, args.keypair.as_deref(), output)?;
+    if args.deploy {
+        build(BuildArgs::default())?;
+        deploy(DeployArgs {
+            url: Some(localnet.url.clone()),
+            keypair: args.keypair.clone(),
+            ..Default::default()
+        })?;
+    }
+    if localnet.validator.is_none() {
+        return Ok(());
+    }
+    output.status(format!(
+        "🌐 Validator running at {}; press Ctrl-C to stop it",
+        localnet.url
+    ));
+    localnet.wait()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_tail() {
+        let log = std::env::temp_dir().join(format!("sbpf-localnet-{}.log", std::process::id()));
+        assert_eq!(log_tail(&log), "");
+        let lines: Vec<String> = (0..30).map(|i| format!("line {}", i)).collect();
+        fs::write(&log, lines.join("\n")).unwrap();
+        let tail = log_tail(&log);
+        assert!(tail.starts_with(&format!("; last lines of {}:\nline 10\n", log.display())));
+        assert!(tail.ends_with("line 29"));
+        fs::remove_file(&log).unwrap();
+    }
+}
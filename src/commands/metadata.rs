@@ -0,0 +1,45 @@
+use {
+    super::{
+        common::{OutputFormat, print_json},
+        disassemble::{build_note_json, render_build_note},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_disassembler::program::Program,
+};
+
+#[derive(Args)]
+pub struct MetadataArgs {
+    #[arg(help = "Path to the ELF file (.so) to inspect")]
+    pub filename: String,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text or json"
+    )]
+    pub output: OutputFormat,
+    #[arg(short, long, help = "Shorthand for --output json")]
+    pub json: bool,
+}
+
+pub fn metadata(args: MetadataArgs) -> Result<(), Error> {
+    let bytes = std::fs::read(&args.filename)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", args.filename, e)))?;
+    let program = Program::from_bytes(&bytes)
+        .map_err(|_| Error::msg(format!("Failed to parse ELF file '{}'", args.filename)))?;
+
+    let Some(note) = program.build_note() else {
+        anyhow::bail!(
+            "'{}' has no build metadata, rebuild it with `sbpf build --metadata`",
+            args.filename
+        );
+    };
+
+    if args.json || args.output.is_json() {
+        print_json(&build_note_json(&note))?;
+    } else {
+        print!("{}", render_build_note(&note, ""));
+    }
+    Ok(())
+}
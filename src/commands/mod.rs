@@ -4,12 +4,36 @@ pub use init::*;
 pub mod build;
 pub use build::*;
 
+pub mod config;
+
+pub mod incremental;
+
+pub mod error_codes;
+
+pub mod coverage;
+
+pub mod profile;
+
+pub mod watch;
+
 pub mod deploy;
 pub use deploy::*;
 
+pub mod keys;
+pub use keys::*;
+
+pub mod verify;
+pub use verify::*;
+
 pub mod test;
 pub use test::*;
 
+pub mod e2e;
+pub use e2e::*;
+
+pub mod localnet;
+pub use localnet::*;
+
 pub mod clean;
 pub use clean::*;
 
@@ -19,4 +43,44 @@ pub use disassemble::*;
 pub mod debug;
 pub use debug::*;
 
+pub mod run;
+pub use run::*;
+
+pub mod transaction;
+
+pub mod replay;
+pub use replay::*;
+
+pub mod relocs;
+pub use relocs::*;
+
+pub mod patch;
+pub use patch::*;
+
+pub mod metadata;
+pub use metadata::*;
+
+pub mod grep;
+pub use grep::*;
+
+pub mod lint;
+pub use lint::*;
+
+pub mod fmt;
+pub use fmt::*;
+
+pub mod layout;
+pub use layout::*;
+
+pub mod idlgen;
+pub use idlgen::*;
+
+pub mod interactive;
+pub use interactive::*;
+
+pub mod learn;
+pub use learn::*;
+
+pub mod rpc;
+
 pub mod common;
@@ -0,0 +1,211 @@
+use {
+    anyhow::{Error, Result},
+    clap::Args,
+    object::{Endianness, Object, ObjectSection, ObjectSymbol, SectionKind, read::elf::ElfFile64},
+    std::ops::Range,
+};
+
+#[derive(Args)]
+pub struct PatchArgs {
+    #[arg(help = "Path to the ELF file (.so) to patch")]
+    pub filename: String,
+    #[arg(short, long, help = "Name of the symbol whose bytes are rewritten")]
+    pub symbol: String,
+    #[arg(short, long, help = "Replacement bytes as hex, e.g. 0x0102ff")]
+    pub data: String,
+    #[arg(
+        short,
+        long,
+        help = "Write the patched ELF here instead of patching in place"
+    )]
+    pub output: Option<String>,
+}
+
+pub fn patch(args: PatchArgs) -> Result<(), Error> {
+    let mut bytes = std::fs::read(&args.filename)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", args.filename, e)))?;
+    let data = parse_hex(&args.data)?;
+
+    let range = patch_symbol(&mut bytes, &args.symbol, &data)?;
+
+    let output = args.output.as_deref().unwrap_or(&args.filename);
+    std::fs::write(output, &bytes)
+        .map_err(|e| Error::msg(format!("Failed to write '{}': {}", output, e)))?;
+    println!(
+        "✅ Patched {} bytes of `{}` at file offset 0x{:x} in {}",
+        data.len(),
+        args.symbol,
+        range.start,
+        output
+    );
+    Ok(())
+}
+
+/// Overwrite the bytes of `symbol` with `data`, returning the patched file range.
+/// The symbol must live in a non-executable section and `data` must match its size.
+fn patch_symbol(bytes: &mut [u8], symbol: &str, data: &[u8]) -> Result<Range<usize>, Error> {
+    let range = locate_symbol(bytes, symbol)?;
+    if range.len() != data.len() {
+        anyhow::bail!(
+            "`{}` is {} bytes but {} bytes of data were given",
+            symbol,
+            range.len(),
+            data.len()
+        );
+    }
+    bytes[range.clone()].copy_from_slice(data);
+    Ok(range)
+}
+
+fn locate_symbol(bytes: &[u8], name: &str) -> Result<Range<usize>, Error> {
+    let elf = ElfFile64::<Endianness>::parse(bytes)
+        .map_err(|e| Error::msg(format!("Failed to parse ELF: {}", e)))?;
+
+    let symbol = elf
+        .symbols()
+        .chain(elf.dynamic_symbols())
+        .find(|s| s.name() == Ok(name) && !s.is_undefined())
+        .ok_or_else(|| Error::msg(format!("Symbol `{}` not found in the symbol table", name)))?;
+
+    if symbol.size() == 0 {
+        anyhow::bail!("Symbol `{}` has no size, refusing to patch it", name);
+    }
+
+    let section_index = symbol
+        .section_index()
+        .ok_or_else(|| Error::msg(format!("Symbol `{}` is not defined in a section", name)))?;
+    let section = elf.section_by_index(section_index)?;
+    let section_name = section.name().unwrap_or("<unknown>");
+    if section.kind() == SectionKind::Text {
+        anyhow::bail!(
+            "Symbol `{}` is in executable section {}, only data can be patched",
+            name,
+            section_name
+        );
+    }
+    let (file_offset, file_size) = section.file_range().ok_or_else(|| {
+        Error::msg(format!(
+            "Section {} of `{}` has no bytes in the file",
+            section_name, name
+        ))
+    })?;
+
+    let start = symbol
+        .address()
+        .checked_sub(section.address())
+        .filter(|offset| offset + symbol.size() <= file_size)
+        .ok_or_else(|| {
+            Error::msg(format!(
+                "Symbol `{}` extends past the end of section {}",
+                name, section_name
+            ))
+        })?
+        + file_offset;
+    Ok(start as usize..(start + symbol.size()) as usize)
+}
+
+fn parse_hex(input: &str) -> Result<Vec<u8>, Error> {
+    let digits: String = input
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_')
+        .collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        anyhow::bail!("Expected an even number of hex digits, got '{}'", input);
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| Error::msg(format!("Invalid hex data '{}'", input)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, SbpfArch},
+    };
+
+    const SOURCE: &str = r#"
+.globl entrypoint
+entrypoint:
+    lddw r1, fee_bps
+    ldxdw r0, [r1+0]
+    exit
+.rodata
+admin_key: .ascii "AAAA"
+    .ascii "AAAA"
+fee_bps: .quad 5
+"#;
+
+    fn assemble(arch: SbpfArch) -> Vec<u8> {
+        Assembler::new(AssemblerOption::default().with_arch(arch))
+            .assemble(SOURCE)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_patch_symbol() {
+        for arch in [SbpfArch::V0, SbpfArch::V3] {
+            let mut elf = assemble(arch);
+            let original = elf.clone();
+            let range = patch_symbol(
+                &mut elf,
+                "fee_bps",
+                &parse_hex("0x0102030405060708").unwrap(),
+            )
+            .unwrap();
+            assert_eq!(&original[range.clone()], &5u64.to_le_bytes());
+            assert_eq!(&elf[range.start - 8..range.start], b"AAAAAAAA");
+            assert_eq!(&elf[range.clone()], &[1, 2, 3, 4, 5, 6, 7, 8]);
+            assert_eq!(elf[..range.start], original[..range.start]);
+            assert_eq!(elf[range.end..], original[range.end..]);
+        }
+    }
+
+    #[test]
+    fn test_patch_symbol_size_mismatch() {
+        let mut elf = assemble(SbpfArch::V0);
+        let original = elf.clone();
+        // `admin_key` covers its unlabeled continuation too.
+        let err = patch_symbol(&mut elf, "admin_key", &[0xff; 4]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`admin_key` is 8 bytes but 4 bytes of data were given"
+        );
+        assert_eq!(elf, original);
+    }
+
+    #[test]
+    fn test_patch_symbol_rejects_unknown_and_sizeless() {
+        let mut elf = assemble(SbpfArch::V0);
+        assert!(
+            patch_symbol(&mut elf, "missing", &[0])
+                .unwrap_err()
+                .to_string()
+                .contains("not found")
+        );
+        // Entrypoints are dynamic symbols without a size.
+        assert!(
+            patch_symbol(&mut elf, "entrypoint", &[0])
+                .unwrap_err()
+                .to_string()
+                .contains("has no size")
+        );
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(
+            parse_hex("0xdead_BEEF").unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert_eq!(parse_hex("01 02").unwrap(), vec![1, 2]);
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+        assert!(parse_hex("").is_err());
+    }
+}
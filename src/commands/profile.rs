@@ -0,0 +1,397 @@
+use {
+    super::{
+        common::{LoadedProgram, load_for_vm},
+        coverage::deployed_programs,
+        run::{display_path, function_names},
+    },
+    anyhow::{Error, Result},
+    sbpf_common::{
+        instruction::AsmFormat,
+        profile::{ComputeProfile, SyscallStats},
+    },
+    sbpf_disassembler::line_table::LineTable,
+    sbpf_vm::{
+        compute_profile::read_compute_profile, coverage::program_key, loader::instruction_offsets,
+    },
+    serde::Serialize,
+    std::{
+        cmp::Reverse,
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt::Write,
+        fs,
+        path::Path,
+        time::Duration,
+    },
+};
+
+/// Where `sbpf test --cu-profile` has the runtime record compute units.
+pub const PROFILE_DIR: &str = "target/sbpf-profile";
+
+/// Rows shown per table of a rendered report.
+const HOT_SPOTS: usize = 10;
+
+/// The compute units a program used, by source line and by function.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProfileReport {
+    pub total: u64,
+    /// Units per source line, or per instruction without debug info, most
+    /// first.
+    pub lines: Vec<LineUnits>,
+    /// Units per function, most spent in its own instructions first.
+    pub functions: Vec<FunctionUnits>,
+    /// Units per call stack, as the names of the functions on it joined by
+    /// `;`, outermost first.
+    #[serde(skip)]
+    pub stacks: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct LineUnits {
+    pub location: String,
+    pub units: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct FunctionUnits {
+    pub name: String,
+    /// Units spent in the function's own instructions.
+    pub units: u64,
+    /// Units spent in the function and everything it called.
+    pub total: u64,
+}
+
+impl ProfileReport {
+    /// Attribute `profile` to the functions of `elf` and, when it was built
+    /// with debug info, to the source lines its instructions came from.
+    pub fn new(elf: &[u8], program: &LoadedProgram, profile: &ComputeProfile) -> Result<Self> {
+        let offsets = instruction_offsets(&program.instructions);
+        let offset = |pc: usize| offsets.get(pc).copied().unwrap_or_default();
+        let source_lines = LineTable::from_elf_bytes(elf);
+        let location = |pc: usize| match source_lines
+            .as_ref()
+            .and_then(|lines| lines.locate(offset(pc)))
+        {
+            Some(source) if source.line > 0 => {
+                format!("{}:{}", display_path(&source.file), source.line)
+            }
+            _ => {
+                let asm = program
+                    .instructions
+                    .get(pc)
+                    .and_then(|instruction| instruction.to_asm(AsmFormat::Default).ok())
+                    .unwrap_or_default();
+                format!("{:#06x}  {}", offset(pc), asm)
+            }
+        };
+        let names: HashMap<u64, String> = function_names(elf)?
+            .into_iter()
+            .map(|(name, offset)| (offset, name))
+            .collect();
+        let name = |pc: usize| {
+            names
+                .get(&offset(pc))
+                .cloned()
+                .unwrap_or_else(|| format!("fn_{:04x}", offset(pc)))
+        };
+
+        // Lines with their units and first instruction, to order ties by.
+        let mut lines: HashMap<String, (u64, usize)> = HashMap::new();
+        for (pc, units) in profile.instruction_units() {
+            let line = lines.entry(location(pc)).or_insert((0, pc));
+            line.0 += units;
+            line.1 = line.1.min(pc);
+        }
+
+        let mut own: HashMap<usize, u64> = HashMap::new();
+        let mut total: HashMap<usize, u64> = HashMap::new();
+        let mut stacks = BTreeMap::new();
+        for (stack, units) in &profile.stacks {
+            let Some((_, frames)) = stack.split_last() else {
+                continue;
+            };
+            if let Some(function) = frames.last() {
+                *own.entry(*function).or_default() += units;
+            }
+            // Recursive functions are on the stack more than once.
+            let mut seen = HashSet::new();
+            for function in frames {
+                if seen.insert(function) {
+                    *total.entry(*function).or_default() += units;
+                }
+            }
+            let names: Vec<String> = frames.iter().map(|pc| name(*pc)).collect();
+            *stacks.entry(names.join(";")).or_default() += units;
+        }
+
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort_by_key(|(_, (units, pc))| (Reverse(*units), *pc));
+        let lines = lines
+            .into_iter()
+            .map(|(location, (units, _))| LineUnits { location, units })
+            .collect();
+        let mut functions: Vec<FunctionUnits> = total
+            .into_iter()
+            .map(|(pc, total)| FunctionUnits {
+                name: name(pc),
+                units: own.get(&pc).copied().unwrap_or(0),
+                total,
+            })
+            .collect();
+        functions.sort_by(|a, b| {
+            (b.units, b.total)
+                .cmp(&(a.units, a.total))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(Self {
+            total: profile.total(),
+            lines,
+            functions,
+            stacks,
+        })
+    }
+
+    /// Report the compute units recorded in `profile_dir` for each program in
+    /// `deploy_dir` that ran, named by its path.
+    pub fn collect(deploy_dir: &Path, profile_dir: &Path) -> Result<Vec<(String, Self)>> {
+        let read_error =
+            |path: &Path, e| Error::msg(format!("Failed to read '{}': {}", path.display(), e));
+        let mut reports = Vec::new();
+        for path in deployed_programs(deploy_dir)? {
+            let elf = fs::read(&path).map_err(|e| read_error(&path, e))?;
+            let Some(profile) = read_compute_profile(profile_dir, &program_key(&elf))
+                .map_err(|e| read_error(profile_dir, e))?
+            else {
+                continue;
+            };
+            let program = load_for_vm(&elf).map_err(Error::msg)?;
+            reports.push((
+                path.display().to_string(),
+                Self::new(&elf, &program, &profile)?,
+            ));
+        }
+        Ok(reports)
+    }
+
+    /// The call stacks in the folded format flamegraph tools read, one
+    /// `outer;inner units` line each, under `root` when given.
+    pub fn folded(&self, root: Option<&str>) -> String {
+        let mut out = String::new();
+        for (stack, units) in &self.stacks {
+            match root {
+                Some(root) => writeln!(out, "{};{} {}", root, stack, units),
+                None => writeln!(out, "{} {}", stack, units),
+            }
+            .expect("writing to a String cannot fail");
+        }
+        out
+    }
+
+    /// The total and the source lines and functions that used the most
+    /// units.
+    pub fn render(&self) -> String {
+        let mut out = format!("  total: {} compute units\n  hot lines:\n", self.total);
+        for line in self.lines.iter().take(HOT_SPOTS) {
+            writeln!(
+                out,
+                "  {:>8} {:>5.1}%  {}",
+                line.units,
+                share(line.units, self.total),
+                line.location
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out.push_str("  hot functions (own, with calls):\n");
+        for function in self.functions.iter().take(HOT_SPOTS) {
+            writeln!(
+                out,
+                "  {:>8} {:>5.1}% {:>8} {:>5.1}%  {}",
+                function.units,
+                share(function.units, self.total),
+                function.total,
+                share(function.total, self.total),
+                function.name
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out
+    }
+}
+
+/// Calls, compute units and host time per syscall as a table, most units
+/// first, with each syscall's share of the `total` units the run used.
+pub fn render_syscall_stats(stats: &SyscallStats, total: u64) -> String {
+    let width = stats
+        .syscalls
+        .keys()
+        .map(String::len)
+        .chain(["syscall".len(), "total".len()])
+        .max()
+        .unwrap_or_default();
+    let mut out = format!(
+        "  {:<width$}  {:>6}  {:>13}  {:>6}  {:>10}\n",
+        "syscall", "calls", "compute units", "share", "time"
+    );
+    let mut row = |name: &str, calls: u64, units: u64, time: Duration| {
+        writeln!(
+            out,
+            "  {:<width$}  {:>6}  {:>13}  {:>5.1}%  {:>10}",
+            name,
+            calls,
+            units,
+            share(units, total),
+            format!("{:.1?}", time)
+        )
+        .expect("writing to a String cannot fail");
+    };
+    for (name, usage) in stats.by_compute_units() {
+        row(name, usage.calls, usage.compute_units, usage.time);
+    }
+    row(
+        "total",
+        stats.syscalls.values().map(|usage| usage.calls).sum(),
+        stats.compute_units(),
+        stats.syscalls.values().map(|usage| usage.time).sum(),
+    );
+    out
+}
+
+/// The syscall stats as `sbpf run --output json` reports them, time in
+/// nanoseconds.
+pub fn syscall_stats_json(stats: &SyscallStats) -> serde_json::Value {
+    stats
+        .by_compute_units()
+        .into_iter()
+        .map(|(name, usage)| {
+            serde_json::json!({
+                "name": name,
+                "calls": usage.calls,
+                "compute_units": usage.compute_units,
+                "time_ns": usage.time.as_nanos() as u64,
+            })
+        })
+        .collect()
+}
+
+fn share(units: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        units as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+        sbpf_vm::solana_syscalls::SolanaSyscalls,
+    };
+
+    const SOURCE: &str = r#".globl entrypoint
+entrypoint:
+  mov64 r6, 2
+loop:
+  call helper
+  sub64 r6, 1
+  jne r6, 0, loop
+  exit
+helper:
+  mov64 r1, 7
+  call sol_log_64_
+  exit
+"#;
+
+    fn profile(debug: bool) -> ProfileReport {
+        let mut options = AssemblerOption::default();
+        if debug {
+            options = options.with_debug_mode(DebugMode {
+                filename: "prog.s".to_string(),
+                directory: String::new(),
+            });
+        }
+        let elf = Assembler::new(options).assemble(SOURCE).unwrap();
+        let program = load_for_vm(&elf).unwrap();
+        let mut vm = program
+            .clone()
+            .into_vm(Vec::new(), SolanaSyscalls::default());
+        vm.enable_compute_profiling();
+        vm.run().unwrap();
+        let report = ProfileReport::new(&elf, &program, &vm.take_compute_profile().unwrap());
+        assert_eq!(
+            report.as_ref().unwrap().total,
+            vm.compute_meter.get_consumed()
+        );
+        report.unwrap()
+    }
+
+    #[test]
+    fn test_units_by_line_and_function() {
+        let report = profile(true);
+        assert_eq!(report.total, 214);
+        assert_eq!(
+            report.lines[..2],
+            [
+                LineUnits {
+                    location: "prog.s:11".to_string(),
+                    units: 202,
+                },
+                LineUnits {
+                    location: "prog.s:5".to_string(),
+                    units: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            report.functions,
+            [
+                FunctionUnits {
+                    name: "fn_0028".to_string(),
+                    units: 206,
+                    total: 206,
+                },
+                FunctionUnits {
+                    name: "entrypoint".to_string(),
+                    units: 8,
+                    total: 214,
+                },
+            ]
+        );
+        assert_eq!(
+            report.folded(Some("prog")),
+            "prog;entrypoint 8\nprog;entrypoint;fn_0028 206\n"
+        );
+        assert!(
+            report
+                .render()
+                .contains("     206  96.3%      206  96.3%  fn_0028")
+        );
+    }
+
+    #[test]
+    fn test_units_by_instruction_without_debug_info() {
+        let report = profile(false);
+        assert_eq!(report.lines[0].location, "0x0030  call sol_log_64_");
+        assert_eq!(
+            report.folded(None),
+            "entrypoint 8\nentrypoint;fn_0028 206\n"
+        );
+    }
+
+    #[test]
+    fn test_render_syscall_stats() {
+        let mut stats = SyscallStats::new();
+        stats.record("sol_log_", 100, Duration::from_micros(4));
+        stats.record("sol_sha256", 300, Duration::from_micros(12));
+        stats.record("sol_log_", 100, Duration::from_micros(2));
+        assert_eq!(
+            render_syscall_stats(&stats, 800),
+            "  syscall      calls  compute units   share        time
+  sol_sha256       1            300   37.5%      12.0µs
+  sol_log_         2            200   25.0%       6.0µs
+  total            3            500   62.5%      18.0µs
+"
+        );
+        assert_eq!(syscall_stats_json(&stats)[1]["time_ns"], 6000);
+    }
+}
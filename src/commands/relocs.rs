@@ -0,0 +1,121 @@
+use {
+    crate::commands::{
+        build::{ArchArg, emit_assembler_errors},
+        common::{ErrorFormat, OutputFormat, print_json},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_assembler::{Assembler, AssemblerOption, FsFileResolver, RelDynMap, read_source},
+    std::path::Path,
+};
+
+#[derive(Args)]
+pub struct RelocsArgs {
+    #[arg(help = "Path to the assembly source file (.s)")]
+    pub filename: String,
+    #[arg(
+        short = 'a',
+        long,
+        default_value = "v3",
+        help = "Target architecture (v0-v3)"
+    )]
+    pub arch: ArchArg,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text or json"
+    )]
+    pub output: OutputFormat,
+    #[arg(short, long, help = "Shorthand for --output json")]
+    pub json: bool,
+}
+
+pub fn relocs(args: RelocsArgs) -> Result<(), Error> {
+    let source = read_source(Path::new(&args.filename))
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", args.filename, e)))?;
+
+    let assembler = Assembler::new(AssemblerOption::default().with_arch(args.arch.into()));
+    let resolver = FsFileResolver::new();
+    let relocations =
+        match assembler.relocations_with_preprocess(&source, &args.filename, Some(&resolver)) {
+            Ok(relocations) => relocations,
+            Err(assemble_errors) => {
+                emit_assembler_errors(&assemble_errors, ErrorFormat::Human)?;
+                return Err(Error::msg("Compilation failed"));
+            }
+        };
+
+    if args.json || args.output.is_json() {
+        print_json(&render_json(&relocations))?;
+    } else {
+        print!("{}", render_table(&relocations));
+    }
+    Ok(())
+}
+
+fn render_json(relocations: &RelDynMap) -> serde_json::Value {
+    relocations
+        .get_rel_dyns()
+        .into_iter()
+        .map(|(offset, rel_type, symbol)| {
+            serde_json::json!({
+                "offset": offset,
+                "type": rel_type.as_str(),
+                "symbol": symbol,
+            })
+        })
+        .collect()
+}
+
+fn render_table(relocations: &RelDynMap) -> String {
+    let rel_dyns = relocations.get_rel_dyns();
+    if rel_dyns.is_empty() {
+        return "No dynamic relocations\n".to_string();
+    }
+
+    let mut output = format!("{:<12}{:<20}{}\n", "OFFSET", "TYPE", "SYMBOL");
+    for (offset, rel_type, symbol) in rel_dyns {
+        output.push_str(&format!(
+            "{:<12}{:<20}{}\n",
+            format!("0x{:08x}", offset),
+            rel_type.as_str(),
+            symbol
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, sbpf_assembler::RelocationType};
+
+    fn sample() -> RelDynMap {
+        let mut map = RelDynMap::new();
+        map.add_rel_dyn(0x18, RelocationType::RSbfSyscall, "sol_log_".to_string());
+        map.add_rel_dyn(0x0, RelocationType::RSbf64Relative, "msg".to_string());
+        map
+    }
+
+    #[test]
+    fn test_render_table() {
+        assert_eq!(
+            render_table(&sample()),
+            "OFFSET      TYPE                SYMBOL\n\
+             0x00000000  R_SBF_64_RELATIVE   msg\n\
+             0x00000018  R_SBF_SYSCALL       sol_log_\n"
+        );
+        assert_eq!(render_table(&RelDynMap::new()), "No dynamic relocations\n");
+    }
+
+    #[test]
+    fn test_render_json() {
+        assert_eq!(
+            render_json(&sample()),
+            serde_json::json!([
+                { "offset": 0, "type": "R_SBF_64_RELATIVE", "symbol": "msg" },
+                { "offset": 24, "type": "R_SBF_SYSCALL", "symbol": "sol_log_" },
+            ])
+        );
+    }
+}
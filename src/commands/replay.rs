@@ -0,0 +1,740 @@
+use {
+    super::{
+        common::{
+            OutputFormat, SolanaSyscalls, embedded_error_registry, load_error_registry,
+            load_for_vm, print_json,
+        },
+        rpc::{Rpc, RpcAccount},
+        run::{Fault, TRACE_LEN, render_registers},
+    },
+    anyhow::{Error, Result},
+    clap::Args,
+    sbpf_common::instruction::AsmFormat,
+    sbpf_disassembler::line_table::LineTable,
+    sbpf_vm::input::{InputAccount, InputBuilder, InputFormat},
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        fs,
+    },
+};
+
+const BPF_LOADER_DEPRECATED: &str = "BPFLoader1111111111111111111111111111111111";
+const BPF_LOADER: &str = "BPFLoader2111111111111111111111111111111111";
+pub(crate) const BPF_LOADER_UPGRADEABLE: &str = "BPFLoaderUpgradeab1e11111111111111111111111";
+const LOADER_V4: &str = "LoaderV411111111111111111111111111111111111";
+
+/// Programs built into the validator rather than run in the VM, skipped
+/// when picking the instruction to replay.
+const NATIVE_PROGRAMS: [&str; 9] = [
+    "11111111111111111111111111111111",
+    "ComputeBudget111111111111111111111111111111",
+    "Vote111111111111111111111111111111111111111",
+    "Stake11111111111111111111111111111111111111",
+    "Config1111111111111111111111111111111111111",
+    "AddressLookupTab1e1111111111111111111111111",
+    "Ed25519SigVerify111111111111111111111111111",
+    "KeccakSecp256k11111111111111111111111111111",
+    "Secp256r1SigVerify1111111111111111111111111",
+];
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    #[arg(help = "Signature of a confirmed transaction")]
+    pub signature: String,
+    #[arg(
+        short,
+        long,
+        default_value = "mainnet-beta",
+        help = "RPC URL, or one of mainnet-beta, devnet, testnet and localhost"
+    )]
+    pub url: String,
+    #[arg(
+        short,
+        long,
+        value_name = "INDEX",
+        help = "Top-level instruction to replay [default: the first one not calling a native program]"
+    )]
+    pub instruction: Option<usize>,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Run this ELF instead of the deployed program, e.g. a local debug build"
+    )]
+    pub program: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "IDL whose `errors` name the custom error the program exits with [default: the program's `.error` table]"
+    )]
+    pub idl: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the reconstructed input region as hex, for `sbpf run --input`"
+    )]
+    pub save_input: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the instruction and its accounts as an `sbpf debug --input` file"
+    )]
+    pub save_debug_input: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with the trace, exit code, logs and on-chain result"
+    )]
+    pub output: OutputFormat,
+}
+
+/// One executed instruction and the registers it changed.
+#[derive(Serialize)]
+struct TraceStep {
+    offset: u64,
+    asm: String,
+    registers: BTreeMap<String, u64>,
+}
+
+pub fn replay(args: ReplayArgs) -> Result<()> {
+    let rpc = Rpc::new(&args.url);
+    args.output.status(format!(
+        "🔎 Fetching transaction {} from {}",
+        args.signature, rpc.url
+    ));
+    let transaction = Transaction::from_rpc(&rpc.call(
+        "getTransaction",
+        serde_json::json!([
+            args.signature,
+            {
+                "encoding": "json",
+                "commitment": "confirmed",
+                "maxSupportedTransactionVersion": 0,
+            }
+        ]),
+    )?)?;
+    let index = match args.instruction {
+        Some(index) => index,
+        None => transaction.default_instruction()?,
+    };
+    let instruction = transaction.instructions.get(index).ok_or_else(|| {
+        Error::msg(format!(
+            "The transaction has {} instructions, there is no instruction {}",
+            transaction.instructions.len(),
+            index
+        ))
+    })?;
+    let program_id = &transaction.keys[instruction.program];
+
+    let (slot, accounts) = rpc.accounts(&transaction.keys)?;
+    let program_account = accounts[instruction.program]
+        .as_ref()
+        .ok_or_else(|| Error::msg(format!("Program {} no longer exists", program_id)))?;
+    let (deployed, format) = program_elf(program_account, |address| {
+        Ok(rpc.accounts(&[address.to_string()])?.1.remove(0))
+    })?;
+    let elf = match &args.program {
+        Some(path) => {
+            fs::read(path).map_err(|e| Error::msg(format!("Failed to read '{}': {}", path, e)))?
+        }
+        None => {
+            if deployed.deployed_slot > transaction.slot {
+                eprintln!(
+                    "⚠️ {} was redeployed at slot {}, after the transaction; replaying the current code",
+                    program_id, deployed.deployed_slot
+                );
+            }
+            deployed.elf
+        }
+    };
+    if slot > transaction.slot {
+        eprintln!(
+            "⚠️ Account data is read at slot {} and may have changed since the transaction ran at slot {}; lamports are as they were before it",
+            slot, transaction.slot
+        );
+    }
+    if index > 0 {
+        eprintln!(
+            "⚠️ Instructions 0 to {} are not replayed first, so their effects on the accounts are missing",
+            index - 1
+        );
+    }
+
+    let input = transaction.input(index, &accounts, format)?;
+    if let Some(path) = &args.save_input {
+        fs::write(path, hex_lines(&input))
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path, e)))?;
+    }
+    if let Some(path) = &args.save_debug_input {
+        let input = transaction.debugger_input(index, &accounts)?;
+        fs::write(path, serde_json::to_string_pretty(&input)? + "\n")
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path, e)))?;
+    }
+    let errors = match &args.idl {
+        Some(path) => load_error_registry(path)?,
+        None => embedded_error_registry(&elf),
+    };
+    let program = load_for_vm(&elf)
+        .map_err(|e| Error::msg(format!("Failed to load program {}: {}", program_id, e)))?;
+    args.output.status(format!(
+        "🔁 Replaying instruction {} of slot {}, program {}",
+        index, transaction.slot, program_id
+    ));
+
+    let mut vm = program.into_vm(input, SolanaSyscalls::default());
+    let offsets: Vec<u64> = vm
+        .program
+        .iter()
+        .scan(0, |offset, instruction| {
+            let start = *offset;
+            *offset += instruction.get_size();
+            Some(start)
+        })
+        .collect();
+    let mut trace = Vec::new();
+    let mut recent = VecDeque::with_capacity(TRACE_LEN);
+    let mut result = Ok(());
+    while !vm.halted {
+        let pc = vm.pc;
+        if recent.len() == TRACE_LEN {
+            recent.pop_front();
+        }
+        recent.push_back(pc);
+        let before = vm.registers;
+        result = vm.step();
+        let step = TraceStep {
+            offset: offsets.get(pc).copied().unwrap_or_default(),
+            asm: vm.program.get(pc).map_or_else(String::new, |instruction| {
+                instruction
+                    .to_asm(AsmFormat::Default)
+                    .unwrap_or_else(|_| format!("{:?}", instruction.opcode))
+            }),
+            registers: (0..before.len())
+                .filter(|&reg| vm.registers[reg] != before[reg])
+                .map(|reg| (format!("r{}", reg), vm.registers[reg]))
+                .collect(),
+        };
+        if args.output.is_json() {
+            trace.push(step);
+        } else {
+            println!("{}", render_step(&step));
+        }
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let compute_units = vm.compute_meter.get_consumed();
+    let exit_error = vm.exit_code.and_then(|code| errors.describe(code));
+    if args.output.is_json() {
+        print_json(&serde_json::json!({
+            "signature": args.signature,
+            "slot": transaction.slot,
+            "instruction": index,
+            "program_id": program_id,
+            "trace": trace,
+            "exit_code": vm.exit_code,
+            "exit_error": exit_error,
+            "error": result.as_ref().err().map(ToString::to_string),
+            "compute_units": compute_units,
+            "logs": vm.syscall_handler.logs,
+            "onchain_error": transaction.error,
+        }))?;
+    } else {
+        for log in &vm.syscall_handler.logs {
+            println!("{}", log);
+        }
+        match (&result, vm.exit_code) {
+            (Err(e), _) => {
+                let recent: Vec<usize> = recent.into();
+                Fault {
+                    error: e,
+                    program: &vm.program,
+                    registers: &vm.registers,
+                    trace: &recent,
+                }
+                .emit(LineTable::from_elf_bytes(&elf).as_ref())?;
+            }
+            (Ok(()), Some(code)) => match exit_error {
+                Some(error) => println!("exit code: {} ({})", code, error),
+                None => println!("exit code: {}", code),
+            },
+            (Ok(()), None) => println!("exit code: none"),
+        }
+        println!("compute units: {}", compute_units);
+        if result.is_ok() {
+            print!("{}", render_registers(&vm.registers));
+        }
+        match &transaction.error {
+            Some(error) => println!("on chain: failed with {}", error),
+            None => println!("on chain: succeeded"),
+        }
+    }
+
+    result.map_err(|e| Error::msg(format!("Program failed: {}", e)))
+}
+
+fn render_step(step: &TraceStep) -> String {
+    let mut line = format!("{:#06x}  {:<32}", step.offset, step.asm);
+    for (reg, value) in &step.registers {
+        line.push_str(&format!(" {}={:#x}", reg, value));
+    }
+    line.trim_end().to_string()
+}
+
+/// Bytes as hex, 32 to a line, in the format `sbpf run --input` reads.
+fn hex_lines(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(32) {
+        let line: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        text.push_str(&line.join(" "));
+        text.push('\n');
+    }
+    text
+}
+
+/// The code of a deployed program and the slot it was deployed in, where the
+/// loader records one.
+struct DeployedProgram {
+    elf: Vec<u8>,
+    deployed_slot: u64,
+}
+
+/// The ELF a program account runs and the input layout its loader uses.
+/// `fetch` looks up the program data account of an upgradeable program.
+fn program_elf(
+    account: &RpcAccount,
+    fetch: impl FnOnce(&str) -> Result<Option<RpcAccount>>,
+) -> Result<(DeployedProgram, InputFormat)> {
+    let data = account.data()?;
+    let slot = |data: &[u8], at: usize| {
+        data.get(at..at + 8)
+            .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let deployed = |elf: &[u8], deployed_slot| DeployedProgram {
+        elf: elf.to_vec(),
+        deployed_slot,
+    };
+    match account.owner.as_str() {
+        BPF_LOADER_DEPRECATED => Ok((deployed(&data, 0), InputFormat::Unaligned)),
+        BPF_LOADER => Ok((deployed(&data, 0), InputFormat::Aligned)),
+        BPF_LOADER_UPGRADEABLE => {
+            // UpgradeableLoaderState::Program { programdata_address }
+            let address = data
+                .get(4..36)
+                .filter(|_| data.starts_with(&2u32.to_le_bytes()))
+                .ok_or_else(|| Error::msg("Not an upgradeable program account"))?;
+            let address = bs58::encode(address).into_string();
+            let program_data = fetch(&address)?
+                .ok_or_else(|| Error::msg(format!("Program data {} does not exist", address)))?
+                .data()?;
+            // UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }
+            let elf = program_data
+                .get(45..)
+                .filter(|_| program_data.starts_with(&3u32.to_le_bytes()))
+                .ok_or_else(|| Error::msg(format!("{} is not a program data account", address)))?;
+            Ok((deployed(elf, slot(&program_data, 4)), InputFormat::Aligned))
+        }
+        // LoaderV4State { slot, authority_address_or_next_version, status }
+        LOADER_V4 => {
+            let elf = data
+                .get(48..)
+                .ok_or_else(|| Error::msg("Truncated loader v4 program account"))?;
+            Ok((deployed(elf, slot(&data, 0)), InputFormat::Aligned))
+        }
+        owner => Err(Error::msg(format!(
+            "The program is owned by {}, not a BPF loader, so it can't be replayed",
+            owner
+        ))),
+    }
+}
+
+/// A top-level instruction of a transaction, by index into its keys.
+#[derive(Debug, PartialEq, Eq)]
+struct CompiledInstruction {
+    program: usize,
+    accounts: Vec<usize>,
+    data: Vec<u8>,
+}
+
+/// What replaying needs from a confirmed transaction.
+#[derive(Debug)]
+struct Transaction {
+    slot: u64,
+    block_time: Option<i64>,
+    /// Static keys followed by those loaded from lookup tables, writable
+    /// ones first.
+    keys: Vec<String>,
+    signers: Vec<bool>,
+    writable: Vec<bool>,
+    /// Lamports of each account before the transaction.
+    pre_balances: Vec<u64>,
+    instructions: Vec<CompiledInstruction>,
+    /// The error the transaction failed with on chain, if it did.
+    error: Option<Value>,
+}
+
+impl Transaction {
+    /// Read a `getTransaction` result in the `json` encoding.
+    fn from_rpc(result: &Value) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Header {
+            num_required_signatures: usize,
+            num_readonly_signed_accounts: usize,
+            num_readonly_unsigned_accounts: usize,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Instruction {
+            program_id_index: usize,
+            accounts: Vec<usize>,
+            data: String,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Message {
+            account_keys: Vec<String>,
+            header: Header,
+            instructions: Vec<Instruction>,
+        }
+        #[derive(Deserialize, Default)]
+        struct LoadedAddresses {
+            writable: Vec<String>,
+            readonly: Vec<String>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Meta {
+            err: Option<Value>,
+            pre_balances: Vec<u64>,
+            #[serde(default)]
+            loaded_addresses: Option<LoadedAddresses>,
+        }
+        #[derive(Deserialize)]
+        struct Inner {
+            message: Message,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Response {
+            slot: u64,
+            block_time: Option<i64>,
+            meta: Meta,
+            transaction: Inner,
+        }
+
+        let response: Response = serde_json::from_value(result.clone())
+            .map_err(|e| Error::msg(format!("Unexpected transaction format: {}", e)))?;
+        let message = response.transaction.message;
+        let header = message.header;
+        let loaded = response.meta.loaded_addresses.unwrap_or_default();
+        let static_keys = message.account_keys.len();
+
+        let mut signers = Vec::new();
+        let mut writable = Vec::new();
+        for index in 0..static_keys {
+            let signer = index < header.num_required_signatures;
+            signers.push(signer);
+            writable.push(if signer {
+                index < header.num_required_signatures - header.num_readonly_signed_accounts
+            } else {
+                index < static_keys - header.num_readonly_unsigned_accounts
+            });
+        }
+        signers.extend(std::iter::repeat_n(
+            false,
+            loaded.writable.len() + loaded.readonly.len(),
+        ));
+        writable.extend(std::iter::repeat_n(true, loaded.writable.len()));
+        writable.extend(std::iter::repeat_n(false, loaded.readonly.len()));
+        let mut keys = message.account_keys;
+        keys.extend(loaded.writable);
+        keys.extend(loaded.readonly);
+
+        let instructions = message
+            .instructions
+            .into_iter()
+            .map(|instruction| {
+                let data = bs58::decode(&instruction.data)
+                    .into_vec()
+                    .map_err(|e| Error::msg(format!("Invalid instruction data: {}", e)))?;
+                if let Some(&index) = instruction
+                    .accounts
+                    .iter()
+                    .chain([&instruction.program_id_index])
+                    .find(|&&index| index >= keys.len())
+                {
+                    anyhow::bail!("Instruction refers to account {} of {}", index, keys.len());
+                }
+                Ok(CompiledInstruction {
+                    program: instruction.program_id_index,
+                    accounts: instruction.accounts,
+                    data,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            slot: response.slot,
+            block_time: response.block_time,
+            keys,
+            signers,
+            writable,
+            pre_balances: response.meta.pre_balances,
+            instructions,
+            error: response.meta.err,
+        })
+    }
+
+    /// The first instruction that doesn't call a native program.
+    fn default_instruction(&self) -> Result<usize> {
+        self.instructions
+            .iter()
+            .position(|instruction| {
+                !NATIVE_PROGRAMS.contains(&self.keys[instruction.program].as_str())
+            })
+            .ok_or_else(|| {
+                Error::msg("Every instruction calls a native program; pick one with --instruction")
+            })
+    }
+
+    /// The input region instruction `index` was called with, from `accounts`
+    /// fetched for each of the transaction's keys. Lamports come from the
+    /// transaction's pre-balances and the rest from the fetched accounts;
+    /// accounts that don't exist are empty and owned by the system program.
+    fn input(
+        &self,
+        index: usize,
+        accounts: &[Option<RpcAccount>],
+        format: InputFormat,
+    ) -> Result<Vec<u8>> {
+        let instruction = &self.instructions[index];
+        let mut builder = InputBuilder::new(decode_key(&self.keys[instruction.program])?)
+            .with_instruction_data(&instruction.data)
+            .with_format(format);
+        for &account in &instruction.accounts {
+            let fetched = accounts.get(account).cloned().flatten();
+            builder = builder.with_account(InputAccount {
+                key: decode_key(&self.keys[account])?,
+                owner: match &fetched {
+                    Some(fetched) => decode_key(&fetched.owner)?,
+                    None => [0; 32],
+                },
+                lamports: match self.pre_balances.get(account) {
+                    Some(&lamports) => lamports,
+                    None => fetched.as_ref().map_or(0, |fetched| fetched.lamports),
+                },
+                data: match &fetched {
+                    Some(fetched) => fetched.data()?,
+                    None => Vec::new(),
+                },
+                is_signer: self.signers[account],
+                is_writable: self.writable[account],
+                executable: fetched.as_ref().is_some_and(|fetched| fetched.executable),
+                rent_epoch: fetched
+                    .as_ref()
+                    .map_or(u64::MAX, |fetched| fetched.rent_epoch),
+            });
+        }
+        Ok(builder.build())
+    }
+
+    /// Instruction `index` and its accounts in the format `sbpf debug
+    /// --input` reads, with the clock at the transaction's slot and block
+    /// time.
+    fn debugger_input(&self, index: usize, accounts: &[Option<RpcAccount>]) -> Result<Value> {
+        let instruction = &self.instructions[index];
+        let metas: Vec<Value> = instruction
+            .accounts
+            .iter()
+            .map(|&account| {
+                serde_json::json!({
+                    "pubkey": self.keys[account],
+                    "is_signer": self.signers[account],
+                    "is_writable": self.writable[account],
+                })
+            })
+            .collect();
+        let mut states = Vec::new();
+        for &account in &instruction.accounts {
+            let fetched = accounts.get(account).cloned().flatten();
+            let data = match &fetched {
+                Some(fetched) => fetched.data()?,
+                None => Vec::new(),
+            };
+            states.push(serde_json::json!({
+                "pubkey": self.keys[account],
+                "owner": fetched.as_ref().map_or("11111111111111111111111111111111", |fetched| fetched.owner.as_str()),
+                "lamports": self.pre_balances.get(account).copied().unwrap_or_default(),
+                "data": bs58::encode(data).into_string(),
+                "executable": fetched.as_ref().is_some_and(|fetched| fetched.executable),
+            }));
+        }
+        Ok(serde_json::json!({
+            "instruction": {
+                "program_id": self.keys[instruction.program],
+                "accounts": metas,
+                "data": bs58::encode(&instruction.data).into_string(),
+            },
+            "accounts": states,
+            "clock": { "slot": self.slot, "unix_timestamp": self.block_time },
+        }))
+    }
+}
+
+fn decode_key(key: &str) -> Result<[u8; 32]> {
+    let bytes = bs58::decode(key)
+        .into_vec()
+        .map_err(|e| Error::msg(format!("Invalid address '{}': {}", key, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| Error::msg(format!("Invalid address '{}': not 32 bytes", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        base64::{Engine, engine::general_purpose::STANDARD},
+    };
+
+    const PAYER: &str = "AWxggjuZRmWULwxwPeM6ZZxRtdDdekVq22mFRx2QbW7U";
+    const VAULT: &str = "GYVb4hWw8D22pkScWSZZB1QjT7jmuFkPCR1a9DCe1GjY";
+    const PROGRAM: &str = "2HRbXDoT3fpNhiFo8VxM7yeay29jBuxmLbzuq47Xbo43";
+    const TABLE_ACCOUNT: &str = "SysvarC1ock11111111111111111111111111111111";
+
+    fn transaction() -> Value {
+        serde_json::json!({
+            "slot": 250,
+            "blockTime": 1700000000,
+            "meta": {
+                "err": { "InstructionError": [1, { "Custom": 12 }] },
+                "preBalances": [5000000, 1000, 1, 1, 1169280],
+                "loadedAddresses": { "writable": [], "readonly": [TABLE_ACCOUNT] },
+            },
+            "transaction": {
+                "message": {
+                    "accountKeys": [PAYER, VAULT, "ComputeBudget111111111111111111111111111111", PROGRAM],
+                    "header": {
+                        "numRequiredSignatures": 1,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 2,
+                    },
+                    "instructions": [
+                        { "programIdIndex": 2, "accounts": [], "data": "3Bxs412MvVNQj175" },
+                        { "programIdIndex": 3, "accounts": [0, 1, 4], "data": "5T" },
+                    ],
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn test_reconstruct_instruction_input() {
+        let transaction = Transaction::from_rpc(&transaction()).unwrap();
+        assert_eq!(transaction.keys[4], TABLE_ACCOUNT);
+        assert_eq!(transaction.signers, [true, false, false, false, false]);
+        assert_eq!(transaction.writable, [true, true, false, false, false]);
+        assert_eq!(transaction.default_instruction().unwrap(), 1);
+        assert_eq!(transaction.instructions[1].data, [1, 2]);
+        assert!(transaction.error.is_some());
+
+        let owner = |owner: &str| RpcAccount {
+            lamports: 7,
+            owner: owner.to_string(),
+            data: (STANDARD.encode([9, 9, 9]), "base64".to_string()),
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        let accounts = [
+            Some(owner("11111111111111111111111111111111")),
+            Some(owner(PROGRAM)),
+            None,
+            None,
+            None,
+        ];
+        let input = transaction
+            .input(1, &accounts, InputFormat::Aligned)
+            .unwrap();
+        let expected = InputBuilder::new(decode_key(PROGRAM).unwrap())
+            .with_account(InputAccount {
+                key: decode_key(PAYER).unwrap(),
+                owner: [0; 32],
+                lamports: 5000000,
+                data: vec![9, 9, 9],
+                is_signer: true,
+                is_writable: true,
+                executable: false,
+                rent_epoch: u64::MAX,
+            })
+            .with_account(InputAccount {
+                key: decode_key(VAULT).unwrap(),
+                owner: decode_key(PROGRAM).unwrap(),
+                lamports: 1000,
+                data: vec![9, 9, 9],
+                is_signer: false,
+                is_writable: true,
+                executable: false,
+                rent_epoch: u64::MAX,
+            })
+            .with_account(InputAccount {
+                key: decode_key(TABLE_ACCOUNT).unwrap(),
+                lamports: 1169280,
+                rent_epoch: u64::MAX,
+                ..Default::default()
+            })
+            .with_instruction_data(&[1, 2])
+            .build();
+        assert_eq!(input, expected);
+
+        let debug = transaction.debugger_input(1, &accounts).unwrap();
+        assert_eq!(debug["instruction"]["program_id"], PROGRAM);
+        assert_eq!(debug["instruction"]["data"], "5T");
+        assert_eq!(debug["instruction"]["accounts"][1]["is_writable"], true);
+        assert_eq!(debug["accounts"][1]["owner"], PROGRAM);
+        assert_eq!(debug["accounts"][2]["lamports"], 1169280);
+        assert_eq!(debug["clock"]["slot"], 250);
+        assert_eq!(debug["clock"]["unix_timestamp"], 1700000000);
+    }
+
+    #[test]
+    fn test_upgradeable_program_elf() {
+        let program_data_address = [7u8; 32];
+        let mut program = 2u32.to_le_bytes().to_vec();
+        program.extend(program_data_address);
+        let mut program_data = 3u32.to_le_bytes().to_vec();
+        program_data.extend(300u64.to_le_bytes());
+        program_data.push(0);
+        program_data.extend([0; 32]);
+        program_data.extend(b"\x7fELF");
+        let account = |owner: &str, data: &[u8]| RpcAccount {
+            lamports: 1,
+            owner: owner.to_string(),
+            data: (STANDARD.encode(data), "base64".to_string()),
+            executable: true,
+            rent_epoch: 0,
+        };
+
+        let (deployed, format) = program_elf(&account(BPF_LOADER_UPGRADEABLE, &program), |key| {
+            assert_eq!(key, bs58::encode(program_data_address).into_string());
+            Ok(Some(account(BPF_LOADER_UPGRADEABLE, &program_data)))
+        })
+        .unwrap();
+        assert_eq!(deployed.elf, b"\x7fELF");
+        assert_eq!(deployed.deployed_slot, 300);
+        assert_eq!(format, InputFormat::Aligned);
+
+        let (deployed, format) = program_elf(
+            &account(BPF_LOADER_DEPRECATED, b"\x7fELF"),
+            |_| unreachable!(),
+        )
+        .unwrap();
+        assert_eq!(deployed.elf, b"\x7fELF");
+        assert_eq!(format, InputFormat::Unaligned);
+        assert!(program_elf(&account(PAYER, b""), |_| unreachable!()).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use {
+    anyhow::{Error, Result},
+    base64::{Engine, engine::general_purpose::STANDARD},
+    serde::Deserialize,
+    serde_json::Value,
+    std::{
+        io::Write,
+        process::{Command, Output, Stdio},
+    },
+};
+
+/// `getMultipleAccounts` takes at most this many keys.
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// A JSON-RPC endpoint, called through `curl` like the other tools the CLI
+/// runs.
+pub(crate) struct Rpc {
+    pub(crate) url: String,
+}
+
+impl Rpc {
+    pub(crate) fn new(url: &str) -> Self {
+        let url = match url {
+            "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+            "devnet" | "d" => "https://api.devnet.solana.com",
+            "testnet" | "t" => "https://api.testnet.solana.com",
+            "localhost" | "l" => "http://localhost:8899",
+            url => url,
+        };
+        Self {
+            url: url.to_string(),
+        }
+    }
+
+    pub(crate) fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let output = self.post(method, params, Stdio::inherit())?;
+        if !output.status.success() {
+            anyhow::bail!("{} request to {} failed", method, self.url);
+        }
+        let mut response: Value = serde_json::from_slice(&output.stdout).map_err(|e| {
+            Error::msg(format!(
+                "Invalid {} response from {}: {}",
+                method, self.url, e
+            ))
+        })?;
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(Value::as_str);
+            anyhow::bail!("{} failed: {}", method, message.unwrap_or("unknown error"));
+        }
+        match response["result"].take() {
+            Value::Null => {
+                anyhow::bail!("{} returned nothing; is the signature confirmed?", method)
+            }
+            result => Ok(result),
+        }
+    }
+
+    /// Whether the node answers `getHealth` with "ok". Failures aren't
+    /// reported, since a node that is starting up refuses connections.
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.post("getHealth", serde_json::json!([]), Stdio::null())
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| serde_json::from_slice::<Value>(&output.stdout).ok())
+            .is_some_and(|response| response["result"] == "ok")
+    }
+
+    fn post(&self, method: &str, params: Value, stderr: Stdio) -> Result<Output> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let mut curl = Command::new("curl")
+            .args(["-sS", "-X", "POST", "-H", "Content-Type: application/json"])
+            .args(["--data-binary", "@-", &self.url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()
+            .map_err(|e| Error::msg(format!("Failed to run curl: {}", e)))?;
+        if let Some(mut stdin) = curl.stdin.take() {
+            stdin.write_all(request.to_string().as_bytes())?;
+        }
+        Ok(curl.wait_with_output()?)
+    }
+
+    /// The accounts at `keys` as of the returned slot, `None` where there is
+    /// no account.
+    pub(crate) fn accounts(&self, keys: &[String]) -> Result<(u64, Vec<Option<RpcAccount>>)> {
+        #[derive(Deserialize)]
+        struct Context {
+            slot: u64,
+        }
+        #[derive(Deserialize)]
+        struct Response {
+            context: Context,
+            value: Vec<Option<RpcAccount>>,
+        }
+
+        let mut slot = 0;
+        let mut accounts = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            let response: Response = serde_json::from_value(self.call(
+                "getMultipleAccounts",
+                serde_json::json!([chunk, { "encoding": "base64", "commitment": "confirmed" }]),
+            )?)?;
+            slot = slot.max(response.context.slot);
+            accounts.extend(response.value);
+        }
+        Ok((slot, accounts))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RpcAccount {
+    pub(crate) lamports: u64,
+    pub(crate) owner: String,
+    /// Base64 data and its encoding.
+    pub(crate) data: (String, String),
+    pub(crate) executable: bool,
+    pub(crate) rent_epoch: u64,
+}
+
+impl RpcAccount {
+    pub(crate) fn data(&self) -> Result<Vec<u8>> {
+        STANDARD
+            .decode(&self.data.0)
+            .map_err(|e| Error::msg(format!("Invalid account data: {}", e)))
+    }
+}
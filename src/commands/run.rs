@@ -0,0 +1,609 @@
+use {
+    super::{
+        common::{
+            LoadedProgram, OutputFormat, SolanaSyscalls, embedded_error_registry,
+            load_error_registry, load_for_vm, parse_hex, print_json,
+        },
+        profile::{ProfileReport, render_syscall_stats, syscall_stats_json},
+        transaction::run_transaction,
+    },
+    anyhow::{Error, Result},
+    clap::{Args, ValueEnum},
+    codespan_reporting::{
+        diagnostic::{Diagnostic, Label},
+        files::SimpleFiles,
+        term,
+    },
+    sbpf_common::instruction::{AsmFormat, Instruction},
+    sbpf_disassembler::{
+        entry::{EntryAnalysis, FunctionOrigin},
+        line_table::LineTable,
+    },
+    sbpf_vm::{
+        errors::{SbpfVmError, SbpfVmResult},
+        snapshot::{Snapshot, SnapshotDiff},
+        trace::{JsonTraceWriter, TextTraceWriter},
+        vm::SbpfVm,
+    },
+    std::{
+        collections::VecDeque,
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::{Path, PathBuf},
+    },
+    termcolor::{ColorChoice, StandardStream},
+};
+
+/// Instructions shown leading up to a fault.
+pub(crate) const TRACE_LEN: usize = 8;
+
+/// How `sbpf run --trace` writes each instruction executed.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// A line of text
+    Text,
+    /// A line of JSON
+    Json,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    #[arg(help = "Path to the ELF file (.so) to run")]
+    pub filename: String,
+    #[arg(
+        short,
+        long,
+        value_name = "HEXFILE",
+        help = "File of hex bytes to place in the input region [default: empty]"
+    )]
+    pub input: Option<String>,
+    #[arg(
+        short,
+        long,
+        value_name = "LABEL",
+        help = "Start at this function instead of the entrypoint, named as `sbpf disassemble` names it"
+    )]
+    pub entry: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "IDL whose `errors` name the custom error the program exits with [default: the program's `.error` table]"
+    )]
+    pub idl: Option<String>,
+    #[arg(
+        long,
+        help = "Print the source lines and functions that used the most compute units"
+    )]
+    pub cu_profile: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the compute units used per call stack to FILE as folded stacks for flamegraph tools"
+    )]
+    pub folded: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print the calls, compute units and host time of each syscall the program made"
+    )]
+    pub syscall_stats: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        help = "Write each instruction executed, with the registers and memory it changed, to stderr"
+    )]
+    pub trace: Option<TraceFormat>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "trace",
+        help = "Write the trace to FILE instead of stderr"
+    )]
+    pub trace_file: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Save the registers and memory the run ends with to FILE, to compare a later run against"
+    )]
+    pub save_snapshot: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Print the registers and memory that differ from a snapshot saved with --save-snapshot"
+    )]
+    pub diff_against: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["input", "entry", "idl", "cu_profile", "folded", "syscall_stats", "trace", "save_snapshot", "diff_against"],
+        help = "Run the instructions of a transaction in order against the accounts they share, from a JSON file or string"
+    )]
+    pub transaction: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with the exit code, registers, compute units and logs"
+    )]
+    pub output: OutputFormat,
+}
+
+pub fn run(args: RunArgs) -> Result<(), Error> {
+    if let Some(transaction) = &args.transaction {
+        return run_transaction(&args.filename, transaction, args.output);
+    }
+    let elf = std::fs::read(&args.filename)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", args.filename, e)))?;
+    let program = load_for_vm(&elf)
+        .map_err(|e| Error::msg(format!("Failed to load '{}': {}", args.filename, e)))?;
+    let input = match &args.input {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| Error::msg(format!("Failed to read '{}': {}", path, e)))?;
+            parse_hex(&text)
+                .ok_or_else(|| Error::msg(format!("'{}' is not a list of hex bytes", path)))?
+        }
+        None => Vec::new(),
+    };
+    let entrypoint = match &args.entry {
+        Some(label) => function_index(&elf, &program, label)?,
+        None => program.entrypoint,
+    };
+    let errors = match &args.idl {
+        Some(path) => load_error_registry(path)?,
+        None => embedded_error_registry(&elf),
+    };
+
+    let mut vm = program.clone().into_vm(input, SolanaSyscalls::default());
+    vm.set_entrypoint(entrypoint);
+    if args.cu_profile || args.folded.is_some() {
+        vm.enable_compute_profiling();
+    }
+    if args.syscall_stats {
+        vm.enable_syscall_stats();
+    }
+    if let Some(format) = args.trace {
+        let writer: Box<dyn Write + Send> =
+            match &args.trace_file {
+                Some(path) => Box::new(File::create(path).map_err(|e| {
+                    Error::msg(format!("Failed to write '{}': {}", path.display(), e))
+                })?),
+                None => Box::new(io::stderr()),
+            };
+        let writer = BufWriter::new(writer);
+        match format {
+            TraceFormat::Text => vm.enable_tracing(TextTraceWriter::new(writer)),
+            TraceFormat::Json => vm.enable_tracing(JsonTraceWriter::new(writer)),
+        }
+    }
+    let (result, trace) = run_traced(&mut vm);
+    if let Some(mut sink) = vm.take_trace_sink() {
+        sink.flush()
+            .map_err(|e| Error::msg(format!("Failed to write trace: {}", e)))?;
+    }
+    let syscall_stats = vm.take_syscall_stats();
+
+    let profile = match vm.take_compute_profile() {
+        Some(profile) => Some(ProfileReport::new(&elf, &program, &profile)?),
+        None => None,
+    };
+    if let (Some(path), Some(profile)) = (&args.folded, &profile) {
+        std::fs::write(path, profile.folded(None))
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))?;
+    }
+
+    let snapshot = vm.snapshot();
+    if let Some(path) = &args.save_snapshot {
+        save_snapshot(path, &snapshot)?;
+    }
+    let diff = match &args.diff_against {
+        Some(path) => Some(diff_against(path, &snapshot)?),
+        None => None,
+    };
+
+    let compute_units = vm.compute_meter.get_consumed();
+    if args.output.is_json() {
+        print_json(&serde_json::json!({
+            "exit_code": vm.exit_code,
+            "exit_error": vm.exit_code.and_then(|code| errors.describe(code)),
+            "error": result.as_ref().err().map(ToString::to_string),
+            "registers": vm.registers,
+            "compute_units": compute_units,
+            "logs": vm.syscall_handler.logs,
+            "profile": profile.filter(|_| args.cu_profile),
+            "syscalls": syscall_stats.as_ref().map(syscall_stats_json),
+            "diff": diff.as_ref().map(ToString::to_string),
+        }))?;
+    } else {
+        for log in &vm.syscall_handler.logs {
+            println!("{}", log);
+        }
+        match (&result, vm.exit_code) {
+            (Err(e), _) => {
+                let fault = Fault {
+                    error: e,
+                    program: &vm.program,
+                    registers: &vm.registers,
+                    trace: &trace,
+                };
+                fault.emit(LineTable::from_elf_bytes(&elf).as_ref())?;
+            }
+            (Ok(()), Some(code)) => match errors.describe(code) {
+                Some(error) => println!("exit code: {} ({})", code, error),
+                None => println!("exit code: {}", code),
+            },
+            (Ok(()), None) => println!("exit code: none"),
+        }
+        println!("compute units: {}", compute_units);
+        // A fault shows the registers along with it.
+        if result.is_ok() {
+            print!("{}", render_registers(&vm.registers));
+        }
+        if let Some(profile) = profile.filter(|_| args.cu_profile) {
+            print!("📊 Compute units:\n{}", profile.render());
+        }
+        if let Some(stats) = &syscall_stats {
+            print!(
+                "📊 Syscalls:\n{}",
+                render_syscall_stats(stats, compute_units)
+            );
+        }
+        if let Some(diff) = &diff {
+            print!("🔍 Differences from the snapshot:\n{}", diff);
+        }
+    }
+
+    result.map_err(|e| Error::msg(format!("Program failed: {}", e)))
+}
+
+/// Write `snapshot` to `path` as JSON.
+fn save_snapshot(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    std::fs::write(path, serde_json::to_string(snapshot)?)
+        .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))
+}
+
+/// Compare `snapshot` against the one saved at `path`.
+fn diff_against(path: &Path, snapshot: &Snapshot) -> Result<SnapshotDiff> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", path.display(), e)))?;
+    let baseline: Snapshot = serde_json::from_str(&text)
+        .map_err(|e| Error::msg(format!("'{}' is not a snapshot: {}", path.display(), e)))?;
+    Ok(snapshot.diff(&baseline))
+}
+
+/// Index of the instruction starting the function `label`, named either by
+/// an ELF symbol or the way `sbpf disassemble` labels it.
+fn function_index(elf: &[u8], program: &LoadedProgram, label: &str) -> Result<usize> {
+    let names = function_names(elf)?;
+    let Some((_, offset)) = names.iter().find(|(name, _)| name == label) else {
+        let known: Vec<&str> = names.iter().map(|(name, _)| name.as_str()).collect();
+        anyhow::bail!(
+            "No function named '{}'; expected one of {}",
+            label,
+            known.join(", ")
+        );
+    };
+
+    let mut position = 0;
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        if position == *offset {
+            return Ok(index);
+        }
+        position += instruction.get_size();
+    }
+    anyhow::bail!("'{}' does not start an instruction", label)
+}
+
+/// Each function with the `.text` byte offset it starts at, named by its
+/// ELF symbol or the way `sbpf disassemble` labels it.
+pub(crate) fn function_names(elf: &[u8]) -> Result<Vec<(String, u64)>> {
+    let analysis = EntryAnalysis::from_bytes(elf)
+        .map_err(|errors| Error::msg(format!("Failed to analyze ELF file: {:?}", errors)))?;
+    Ok(analysis
+        .functions
+        .iter()
+        .map(|function| {
+            let name = match &function.name {
+                Some(name) => name.clone(),
+                None if function.origins.contains(&FunctionOrigin::Entry) => {
+                    "entrypoint".to_string()
+                }
+                None => format!("fn_{:04x}", function.offset),
+            };
+            (name, function.offset)
+        })
+        .collect())
+}
+
+/// Run `vm` to completion like [`SbpfVm::run`], keeping the program
+/// counters of the last instructions executed so that a fault can show how
+/// it was reached.
+fn run_traced(vm: &mut SbpfVm<SolanaSyscalls>) -> (SbpfVmResult<()>, Vec<usize>) {
+    let mut trace = VecDeque::with_capacity(TRACE_LEN);
+    while !vm.halted {
+        if trace.len() == TRACE_LEN {
+            trace.pop_front();
+        }
+        trace.push_back(vm.pc);
+        if let Err(e) = vm.step() {
+            return (Err(e), trace.into());
+        }
+    }
+    (Ok(()), trace.into())
+}
+
+/// A VM error and the state it left the program in.
+pub(crate) struct Fault<'a> {
+    pub error: &'a SbpfVmError,
+    pub program: &'a [Instruction],
+    pub registers: &'a [u64; 11],
+    /// The instructions executed last, ending with the faulting one.
+    pub trace: &'a [usize],
+}
+
+impl Fault<'_> {
+    /// Print the fault to stderr, in color where the terminal supports it.
+    pub fn emit(&self, lines: Option<&LineTable>) -> Result<()> {
+        let (files, diagnostic) = self.diagnostic(lines);
+        let writer = StandardStream::stderr(ColorChoice::Auto);
+        term::emit_to_write_style(
+            &mut writer.lock(),
+            &term::Config::default(),
+            &files,
+            &diagnostic,
+        )?;
+        Ok(())
+    }
+
+    /// The fault as a diagnostic pointing at the source line of the
+    /// faulting instruction, when the program was built with debug info and
+    /// the source can be read, with the registers and the instructions
+    /// leading up to it as notes.
+    fn diagnostic(
+        &self,
+        lines: Option<&LineTable>,
+    ) -> (SimpleFiles<String, String>, Diagnostic<usize>) {
+        let offsets: Vec<u64> = self
+            .program
+            .iter()
+            .scan(0, |offset, instruction| {
+                let start = *offset;
+                *offset += instruction.get_size();
+                Some(start)
+            })
+            .collect();
+        let describe = |pc: usize| -> String {
+            let Some(instruction) = self.program.get(pc) else {
+                return format!("instruction {} (outside the program)", pc);
+            };
+            let asm = instruction
+                .to_asm(AsmFormat::Default)
+                .unwrap_or_else(|_| format!("{:?}", instruction.opcode));
+            let mut line = format!("{:#06x}  {}", offsets[pc], asm);
+            if let Some(source) = lines.and_then(|lines| lines.locate(offsets[pc])) {
+                line.push_str(&format!(
+                    "  ({}:{})",
+                    display_path(&source.file),
+                    source.line
+                ));
+            }
+            line
+        };
+
+        let mut files = SimpleFiles::new();
+        let mut diagnostic = Diagnostic::error().with_message(self.error.to_string());
+        let pc = self.trace.last().copied();
+        let source = pc
+            .and_then(|pc| offsets.get(pc))
+            .and_then(|offset| lines?.locate(*offset));
+        let label = source.and_then(|source| {
+            let content = std::fs::read_to_string(&source.file).ok()?;
+            let start = line_start(&content, source.line)?;
+            let end = content[start..]
+                .find('\n')
+                .map_or(content.len(), |len| start + len);
+            // Columns are 1-based, and 0 when only the line is known, in
+            // which case the instruction is underlined without its indent.
+            let column = match source.column {
+                0 => content[start..end].len() - content[start..end].trim_start().len(),
+                column => column as usize - 1,
+            };
+            let start = (start + column).min(end);
+            let file = files.add(display_path(&source.file), content);
+            Some(Label::primary(file, start..end).with_message("faulting instruction"))
+        });
+        match (label, pc) {
+            (Some(label), _) => diagnostic = diagnostic.with_labels(vec![label]),
+            (None, Some(pc)) => {
+                diagnostic = diagnostic.with_notes(vec![format!("at {}", describe(pc))]);
+            }
+            (None, None) => {}
+        }
+
+        let mut trace = String::from("last instructions:");
+        for pc in self.trace {
+            trace.push_str(&format!("\n{}", describe(*pc)));
+        }
+        diagnostic.notes.push(format!(
+            "registers:\n{}",
+            render_registers(self.registers).trim_end()
+        ));
+        diagnostic.notes.push(trace);
+        (files, diagnostic)
+    }
+}
+
+/// Byte offset of 1-based line `line` in `content`.
+fn line_start(content: &str, line: u32) -> Option<usize> {
+    if line <= 1 {
+        return (line == 1).then_some(0);
+    }
+    content
+        .match_indices('\n')
+        .nth(line as usize - 2)
+        .map(|(i, _)| i + 1)
+}
+
+/// `path` relative to the working directory when it is inside it, as the
+/// build reports paths.
+pub(crate) fn display_path(path: &str) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            Path::new(path)
+                .strip_prefix(dir)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .map_or_else(|| path.to_string(), |path| path.display().to_string())
+}
+
+/// Registers four to a line, as the interactive mode shows them.
+pub(crate) fn render_registers(registers: &[u64; 11]) -> String {
+    let mut output = String::new();
+    for (reg, value) in registers.iter().enumerate() {
+        output.push_str(&format!("{:>3} {:#018x}", format!("r{}", reg), value));
+        output.push_str(if reg % 4 == 3 || reg == 10 {
+            "\n"
+        } else {
+            "  "
+        });
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption, DebugMode},
+        termcolor::NoColor,
+    };
+
+    #[test]
+    fn test_function_index() {
+        let source = r#".globl entrypoint
+helper:
+  mov64 r0, 7
+  exit
+entrypoint:
+  lddw r1, 1
+  call helper
+  exit
+"#;
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        let program = load_for_vm(&elf).unwrap();
+        assert_eq!(function_index(&elf, &program, "entrypoint").unwrap(), 2);
+        let helper = function_index(&elf, &program, "fn_0000").unwrap();
+        assert_eq!(helper, 0);
+        assert!(
+            function_index(&elf, &program, "missing")
+                .unwrap_err()
+                .to_string()
+                .starts_with("No function named 'missing'")
+        );
+
+        let mut vm = program.into_vm(Vec::new(), SolanaSyscalls::default());
+        vm.set_entrypoint(helper);
+        vm.run().unwrap();
+        assert_eq!(vm.exit_code, Some(7));
+    }
+
+    #[test]
+    fn test_fault_points_at_source() {
+        let dir = std::env::temp_dir().join("sbpf_test_run_fault");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = ".globl entrypoint\nentrypoint:\n  mov64 r1, 4\n  mov64 r2, 0\n  div64 r1, r2\n  exit\n";
+        std::fs::write(dir.join("fault.s"), source).unwrap();
+        let options = AssemblerOption {
+            debug_mode: Some(DebugMode {
+                filename: "fault.s".to_string(),
+                directory: dir.to_string_lossy().into_owned(),
+            }),
+            ..Default::default()
+        };
+        let elf = Assembler::new(options).assemble(source).unwrap();
+        let mut vm = load_for_vm(&elf)
+            .unwrap()
+            .into_vm(Vec::new(), SolanaSyscalls::default());
+        let (result, trace) = run_traced(&mut vm);
+        let error = result.unwrap_err();
+        assert_eq!(trace, [0, 1, 2]);
+
+        let fault = Fault {
+            error: &error,
+            program: &vm.program,
+            registers: &vm.registers,
+            trace: &trace,
+        };
+        let lines = LineTable::from_elf_bytes(&elf);
+        let (files, diagnostic) = fault.diagnostic(lines.as_ref());
+        let mut output = NoColor::new(Vec::new());
+        term::emit_to_write_style(&mut output, &term::Config::default(), &files, &diagnostic)
+            .unwrap();
+        let output = String::from_utf8(output.into_inner()).unwrap();
+        assert!(output.contains("error: Division by zero"), "{}", output);
+        assert!(output.contains("fault.s:5:3"), "{}", output);
+        assert!(
+            output.contains("^^^^^^^^^^^^ faulting instruction"),
+            "{}",
+            output
+        );
+        assert!(output.contains("r1 0x0000000000000004"), "{}", output);
+        assert!(output.contains("0x0010  div64 r1, r2"), "{}", output);
+
+        // Without debug info the fault is located by offset.
+        let (_, diagnostic) = fault.diagnostic(None);
+        assert!(diagnostic.labels.is_empty());
+        assert_eq!(diagnostic.notes[0], "at 0x0010  div64 r1, r2");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff_against_saved_snapshot() {
+        let dir = std::env::temp_dir().join("sbpf_test_run_snapshot");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("baseline.json");
+        // Copies the first input word to the second and returns it.
+        let source =
+            ".globl entrypoint\nentrypoint:\n  ldxdw r0, [r1+0]\n  stxdw [r1+8], r0\n  exit\n";
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        let run = |input: Vec<u8>| {
+            let mut vm = load_for_vm(&elf)
+                .unwrap()
+                .into_vm(input, SolanaSyscalls::default());
+            vm.run().unwrap();
+            vm.snapshot()
+        };
+
+        let baseline = run([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].to_vec());
+        save_snapshot(&path, &baseline).unwrap();
+        assert!(diff_against(&path, &baseline).unwrap().is_empty());
+
+        let changed = run([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].to_vec());
+        let diff = diff_against(&path, &changed).unwrap();
+        assert!(diff.registers.contains(&(0, 1, 2)), "{}", diff);
+        assert_eq!(diff.ranges.len(), 1, "{}", diff);
+        assert_eq!(diff.ranges[0].before, [1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(diff.ranges[0].after, [2, 0, 0, 0, 0, 0, 0, 0, 2]);
+        let report = diff.to_string();
+        assert!(report.contains("r0: 0x1 -> 0x2"), "{}", report);
+        assert!(
+            report.contains("input 0x400000000..0x400000009"),
+            "{}",
+            report
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(diff_against(&path, &changed).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
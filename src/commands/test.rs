@@ -1,10 +1,198 @@
 use {
+    super::{
+        build::{BuildArgs, build_programs},
+        common::{OutputFormat, embedded_error_registry, print_json},
+        config::ProjectConfig,
+        coverage::{COVERAGE_DIR, CoverageReport},
+        deploy::{DeployArgs, deploy_programs},
+        profile::{PROFILE_DIR, ProfileReport},
+    },
     anyhow::{Error, Result},
-    std::{fs, io, path::Path, process::Command},
+    clap::Args,
+    sbpf_common::program_error::ErrorRegistry,
+    sbpf_vm::{compute_profile::PROFILE_DIR_ENV, coverage::COVERAGE_DIR_ENV},
+    serde::Serialize,
+    std::{
+        env, fs,
+        io::{self, BufRead, BufReader, Read, Write},
+        path::{Path, PathBuf},
+        process::{Command, ExitStatus, Stdio},
+        thread,
+    },
 };
 
-pub fn test() -> Result<(), Error> {
-    println!("🧪 Running tests");
+/// How runners print the code of a `ProgramError::Custom`, as the prefix
+/// before the code and the code's radix.
+const CUSTOM_ERROR_FORMATS: [(&str, u32); 2] = [("custom program error: 0x", 16), ("Custom(", 10)];
+
+#[derive(Args, Default)]
+pub struct TestArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        default_missing_value = "examples",
+        help = "Build and test every example program, checking the internal VM against Mollusk"
+    )]
+    pub examples: Option<String>,
+    #[arg(
+        long,
+        value_name = "LCOV_FILE",
+        num_args = 0..=1,
+        default_missing_value = "lcov.info",
+        conflicts_with = "examples",
+        help = "Report the source lines tests ran in the sbpf runtime, written as lcov [default: lcov.info]"
+    )]
+    pub coverage: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with = "examples",
+        help = "Print the source lines and functions that used the most compute units in tests run in the sbpf runtime"
+    )]
+    pub cu_profile: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "examples",
+        help = "Write the compute units tests used per call stack to FILE as folded stacks for flamegraph tools"
+    )]
+    pub folded: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text, or json with the result of each run"
+    )]
+    pub output: OutputFormat,
+}
+
+/// One example as `sbpf test --examples --output json` reports it.
+#[derive(Serialize)]
+struct ExampleResult {
+    name: String,
+    passed: bool,
+    error: Option<String>,
+}
+
+/// Where a test runner's own output goes: straight through for text, to
+/// stderr for JSON so stdout carries only the report.
+fn runner_stdout(output: OutputFormat) -> Stdio {
+    match output {
+        OutputFormat::Text => Stdio::inherit(),
+        OutputFormat::Json => io::stderr().into(),
+    }
+}
+
+/// Run a test runner, naming each custom error code it prints after the
+/// `.error` it stands for in `errors`.
+fn run_runner(
+    command: &mut Command,
+    output: OutputFormat,
+    errors: &ErrorRegistry,
+) -> io::Result<ExitStatus> {
+    if errors.is_empty() {
+        return command.stdout(runner_stdout(output)).status();
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    thread::scope(|scope| {
+        scope.spawn(|| copy_annotated(stderr, io::stderr(), errors));
+        match output {
+            OutputFormat::Text => copy_annotated(stdout, io::stdout(), errors),
+            OutputFormat::Json => copy_annotated(stdout, io::stderr(), errors),
+        }
+    });
+    child.wait()
+}
+
+/// Copy `from` to `to` line by line through [`annotate_custom_errors`].
+fn copy_annotated(from: impl Read, mut to: impl Write, errors: &ErrorRegistry) {
+    for line in BufReader::new(from).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let _ = writeln!(to, "{}", annotate_custom_errors(&line, errors));
+    }
+}
+
+/// `line` with the name and message of each custom error it mentions after
+/// its code: `custom program error: 0xc (InvalidPda: Vault address does not
+/// match its seeds)`. Codes missing from `errors` are left alone.
+fn annotate_custom_errors(line: &str, errors: &ErrorRegistry) -> String {
+    let mut annotated = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some((start, prefix, radix)) = CUSTOM_ERROR_FORMATS
+        .iter()
+        .filter_map(|&(prefix, radix)| rest.find(prefix).map(|start| (start, prefix, radix)))
+        .min_by_key(|(start, ..)| *start)
+    {
+        let digits_start = start + prefix.len();
+        let digits_end = rest[digits_start..]
+            .find(|c: char| !c.is_digit(radix))
+            .map_or(rest.len(), |len| digits_start + len);
+        let mut end = digits_end;
+        if prefix.ends_with('(') && rest[end..].starts_with(')') {
+            end += 1;
+        }
+        annotated.push_str(&rest[..end]);
+        if let Ok(code) = u32::from_str_radix(&rest[digits_start..digits_end], radix)
+            && errors.get(code).is_some()
+            && let Some(description) = errors.describe(u64::from(code))
+        {
+            annotated.push_str(&format!(" ({})", description));
+        }
+        rest = &rest[end..];
+    }
+    annotated.push_str(rest);
+    annotated
+}
+
+/// The `.error` tables of the programs built into `dir`.
+fn deployed_errors(dir: &Path) -> ErrorRegistry {
+    let mut programs: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "so"))
+        .collect();
+    programs.sort();
+    programs
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .flat_map(|elf| {
+            embedded_error_registry(&elf)
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Build every program in the current directory as `sbpf build` would,
+/// with the settings from its `sbpf.toml`.
+fn build_project(output: OutputFormat, debug: bool) -> Result<(), Error> {
+    let mut args = BuildArgs {
+        output,
+        debug,
+        ..Default::default()
+    };
+    ProjectConfig::load()?.apply_build(&mut args);
+    build_programs(&args)?;
+    Ok(())
+}
+
+pub fn test(args: TestArgs) -> Result<(), Error> {
+    if let Some(dir) = &args.examples {
+        return test_examples(Path::new(dir), args.output);
+    }
+    let output = args.output;
+
+    output.status("🧪 Running tests");
 
     let deploy_dir = Path::new("deploy");
 
@@ -26,36 +214,81 @@ pub fn test() -> Result<(), Error> {
         }
     }
 
-    if !has_so_files(deploy_dir) {
-        println!("🔄 No .so files found in 'deploy' directory. Running build...");
-        crate::commands::build::build(crate::commands::build::BuildArgs::default())?;
+    let coverage_dir = env::current_dir()?.join(COVERAGE_DIR);
+    let profile_dir = env::current_dir()?.join(PROFILE_DIR);
+    let profiling = args.cu_profile || args.folded.is_some();
+    if args.coverage.is_some() || profiling {
+        // Lines are read from the DWARF line table.
+        output.status("🔄 Building with debug info...");
+        build_project(output, true)?;
+        if args.coverage.is_some() {
+            recreate_dir(&coverage_dir)?;
+        }
+        if profiling {
+            recreate_dir(&profile_dir)?;
+        }
+    } else if !has_so_files(deploy_dir) {
+        output.status("🔄 No .so files found in 'deploy' directory. Running build...");
+        build_project(output, false)?;
     }
 
+    let errors = deployed_errors(deploy_dir);
     let has_cargo = Path::new("Cargo.toml").exists();
     let has_package_json = Path::new("package.json").exists();
 
-    match (has_cargo, has_package_json) {
+    let report = |runner: &str, passed: bool| {
+        if output.is_json() {
+            print_json(&serde_json::json!({ "runner": runner, "passed": passed }))?;
+        }
+        Ok::<(), Error>(())
+    };
+
+    let result = match (has_cargo, has_package_json) {
         (true, _) => {
-            let output = Command::new("cargo")
+            let mut command = Command::new("cargo");
+            command
                 .arg("test-sbf")
                 .arg("--")
                 .arg("--nocapture")
-                .env("RUST_BACKTRACE", "1")
-                .status()?;
+                .env("RUST_BACKTRACE", "1");
+            if args.coverage.is_some() {
+                command.env(COVERAGE_DIR_ENV, &coverage_dir);
+            }
+            if profiling {
+                command.env(PROFILE_DIR_ENV, &profile_dir);
+            }
+            let status = run_runner(&mut command, output, &errors)?;
 
-            if !output.success() {
+            report("cargo", status.success())?;
+            if status.success() {
+                Ok(())
+            } else {
                 eprintln!("Failed to run Rust tests");
-                return Err(Error::new(io::Error::other("❌ Rust tests failed")));
+                Err(Error::new(io::Error::other("❌ Rust tests failed")))
             }
         }
         (false, true) => {
-            crate::commands::deploy::deploy(crate::commands::deploy::DeployArgs::default())?;
+            deploy_programs(&DeployArgs {
+                output,
+                ..Default::default()
+            })?;
 
-            let status = Command::new("yarn").arg("test").status()?;
+            let mut command = Command::new("yarn");
+            command.arg("test");
+            if args.coverage.is_some() {
+                command.env(COVERAGE_DIR_ENV, &coverage_dir);
+            }
+            if profiling {
+                command.env(PROFILE_DIR_ENV, &profile_dir);
+            }
+            let status = run_runner(&mut command, output, &errors)?;
 
-            if !status.success() {
+            report("yarn", status.success())?;
+            if status.success() {
+                Ok(())
+            } else {
                 eprintln!("Failed to run tests");
-                return Err(Error::new(io::Error::other("❌ Test failed")));
+                Err(Error::new(io::Error::other("❌ Test failed")))
             }
         }
         (false, false) => {
@@ -64,8 +297,216 @@ pub fn test() -> Result<(), Error> {
                 "❌ No test configuration found. Expected either Cargo.toml or package.json",
             )));
         }
+    };
+
+    // Failing tests still leave coverage and profiles worth reporting.
+    if let Some(lcov) = &args.coverage {
+        report_coverage(deploy_dir, &coverage_dir, lcov, output)?;
+    }
+    if profiling {
+        report_profiles(deploy_dir, &profile_dir, &args, output)?;
+    }
+    result?;
+
+    output.status("✅ Tests completed successfully!");
+    Ok(())
+}
+
+/// Write the coverage recorded in `coverage_dir` for the programs in
+/// `deploy_dir` to `lcov`, and summarize it per file.
+fn report_coverage(
+    deploy_dir: &Path,
+    coverage_dir: &Path,
+    lcov: &Path,
+    output: OutputFormat,
+) -> Result<(), Error> {
+    let recorded = fs::read_dir(coverage_dir).is_ok_and(|mut entries| entries.next().is_some());
+    if !recorded {
+        eprintln!(
+            "⚠️  No coverage was recorded. Only programs run in the sbpf runtime \
+             (sbpf_runtime::Runtime) are covered, not those run in Mollusk or a validator."
+        );
+    }
+    let report = CoverageReport::collect(deploy_dir, coverage_dir)?;
+    for program in &report.skipped {
+        eprintln!("⚠️  {} has no debug info and was left out", program);
+    }
+    fs::write(lcov, report.to_lcov())
+        .map_err(|e| Error::msg(format!("Failed to write '{}': {}", lcov.display(), e)))?;
+
+    if output.is_json() {
+        print_json(&serde_json::json!({
+            "coverage": { "lcov": lcov, "files": report.summaries() }
+        }))?;
+    } else {
+        print!("📊 Line coverage:\n{}", report.render());
+    }
+    output.status(format!("✅ Wrote {}", lcov.display()));
+    Ok(())
+}
+
+/// Report the compute units recorded in `profile_dir` for the programs in
+/// `deploy_dir`, and write their call stacks to `args.folded`.
+fn report_profiles(
+    deploy_dir: &Path,
+    profile_dir: &Path,
+    args: &TestArgs,
+    output: OutputFormat,
+) -> Result<(), Error> {
+    let reports = ProfileReport::collect(deploy_dir, profile_dir)?;
+    if reports.is_empty() {
+        eprintln!(
+            "⚠️  No compute units were recorded. Only programs run in the sbpf runtime \
+             (sbpf_runtime::Runtime) are profiled, not those run in Mollusk or a validator."
+        );
+    }
+    if let Some(path) = &args.folded {
+        // Each program's stacks sit under its name, so one flamegraph shows
+        // them side by side.
+        let folded: String = reports
+            .iter()
+            .map(|(program, report)| {
+                let name = Path::new(program).file_stem().map_or_else(
+                    || program.clone(),
+                    |stem| stem.to_string_lossy().into_owned(),
+                );
+                report.folded(Some(&name))
+            })
+            .collect();
+        fs::write(path, folded)
+            .map_err(|e| Error::msg(format!("Failed to write '{}': {}", path.display(), e)))?;
+        output.status(format!("✅ Wrote {}", path.display()));
+    }
+    if !args.cu_profile {
+        return Ok(());
+    }
+    if output.is_json() {
+        let programs: Vec<_> = reports
+            .iter()
+            .map(|(program, report)| serde_json::json!({ "program": program, "profile": report }))
+            .collect();
+        print_json(&serde_json::json!({ "profiles": programs }))?;
+    } else {
+        for (program, report) in &reports {
+            print!("📊 Compute units of {}:\n{}", program, report.render());
+        }
+    }
+    Ok(())
+}
+
+/// Empty `dir` of anything an earlier run recorded.
+fn recreate_dir(dir: &Path) -> Result<(), Error> {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)
+        .map_err(|e| Error::msg(format!("Failed to create '{}': {}", dir.display(), e)))
+}
+
+/// Build each example program under `dir` and run its tests with the
+/// `example-tests` feature. Every scenario runs in Mollusk and is then replayed
+/// in the internal VM, so a test fails if either the program or the VM
+/// misbehaves.
+fn test_examples(dir: &Path, output: OutputFormat) -> Result<(), Error> {
+    let mut examples: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", dir.display(), e)))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("Cargo.toml").is_file())
+        .collect();
+    examples.sort();
+    if examples.is_empty() {
+        return Err(Error::msg(format!(
+            "❌ No example programs found in '{}'",
+            dir.display()
+        )));
+    }
+
+    let root = env::current_dir()?;
+    let mut failed = Vec::new();
+    let mut results = Vec::new();
+    for example in &examples {
+        let name = example
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        output.status(format!("🧪 Testing {}", name));
+
+        env::set_current_dir(example)?;
+        let result = build_and_test_example(output);
+        env::set_current_dir(&root)?;
+
+        let error = match result {
+            Ok(()) => {
+                output.status(format!("✅ {} passed in Mollusk and the internal VM", name));
+                None
+            }
+            Err(e) => {
+                eprintln!("❌ {}: {}", name, e);
+                failed.push(name.clone());
+                Some(e.to_string())
+            }
+        };
+        results.push(ExampleResult {
+            name,
+            passed: error.is_none(),
+            error,
+        });
     }
 
-    println!("✅ Tests completed successfully!");
+    output.status(format!(
+        "{}/{} examples passed",
+        examples.len() - failed.len(),
+        examples.len()
+    ));
+    if output.is_json() {
+        print_json(&serde_json::json!({ "examples": results }))?;
+    }
+    if !failed.is_empty() {
+        return Err(Error::msg(format!(
+            "❌ Failing examples: {}",
+            failed.join(", ")
+        )));
+    }
+    output.status("🎉 All examples conform!");
     Ok(())
 }
+
+fn build_and_test_example(output: OutputFormat) -> Result<(), Error> {
+    build_project(output, false)?;
+    let status = Command::new("cargo")
+        .args(["test", "--features", "example-tests"])
+        .env("RUST_BACKTRACE", "1")
+        .stdout(runner_stdout(output))
+        .status()?;
+    if !status.success() {
+        return Err(Error::msg("tests failed"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, sbpf_common::program_error::ErrorDefinition};
+
+    #[test]
+    fn test_annotate_custom_errors() {
+        let errors: ErrorRegistry = [ErrorDefinition {
+            code: 12,
+            name: "InvalidPda".to_string(),
+            message: Some("Vault address does not match its seeds".to_string()),
+        }]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            annotate_custom_errors(
+                "Program failed: custom program error: 0xc; got Err(Custom(12)), Custom(13)",
+                &errors
+            ),
+            "Program failed: custom program error: 0xc (InvalidPda: Vault address does not \
+             match its seeds); got Err(Custom(12) (InvalidPda: Vault address does not match its \
+             seeds)), Custom(13)"
+        );
+        assert_eq!(annotate_custom_errors("Custom(", &errors), "Custom(");
+        assert_eq!(annotate_custom_errors("ok", &errors), "ok");
+    }
+}
@@ -0,0 +1,230 @@
+use {
+    super::common::{OutputFormat, embedded_error_registry, print_json},
+    anyhow::{Error, Result},
+    sbpf_debugger::input::{ParsedTransaction, parse_transaction},
+    sbpf_runtime::{Runtime, TransactionResult, config::RuntimeConfig, cpi::builtins::is_builtin},
+};
+
+/// Run the instructions of the transaction in `input`, a JSON file or
+/// string, in order against the accounts they share, with the ELF at
+/// `filename` as the program they call.
+pub fn run_transaction(filename: &str, input: &str, output: OutputFormat) -> Result<()> {
+    let elf = std::fs::read(filename)
+        .map_err(|e| Error::msg(format!("Failed to read '{}': {}", filename, e)))?;
+    let transaction = parse_transaction(input)
+        .map_err(|e| Error::msg(format!("Failed to read the transaction: {}", e)))?;
+    let errors = embedded_error_registry(&elf);
+    let program_id = transaction.instructions[program_index(&transaction)?].program_id;
+    let (runtime, result) = execute(elf, &transaction)
+        .map_err(|e| Error::msg(format!("Failed to run '{}': {}", filename, e)))?;
+    // A failed transaction's account changes were rolled back.
+    let flagged = if result.is_success() {
+        runtime.rent_violations(&transaction.instructions, &transaction.accounts)
+    } else {
+        Vec::new()
+    };
+    // Custom errors are only known for the program given on the command line.
+    let describe = |index: usize, code: u64| {
+        if transaction.instructions[index].program_id == program_id {
+            errors.describe(code)
+        } else {
+            None
+        }
+    };
+
+    if output.is_json() {
+        let instructions: Vec<_> = result
+            .results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| {
+                serde_json::json!({
+                    "program_id": transaction.instructions[index].program_id.to_string(),
+                    "exit_code": result.exit_code,
+                    "exit_error": result.exit_code.and_then(|code| describe(index, code)),
+                    "compute_units": result.compute_units_consumed,
+                    "logs": result.logs,
+                })
+            })
+            .collect();
+        let flagged_json: Vec<_> = flagged
+            .iter()
+            .map(|violation| {
+                serde_json::json!({
+                    "address": violation.address.to_string(),
+                    "lamports": violation.lamports,
+                    "minimum_balance": violation.minimum_balance,
+                })
+            })
+            .collect();
+        print_json(&serde_json::json!({
+            "success": result.is_success() && flagged.is_empty(),
+            "compute_units": result.compute_units_consumed(),
+            "instructions": instructions,
+            "below_rent_exemption": flagged_json,
+        }))?;
+    } else {
+        for (index, result) in result.results.iter().enumerate() {
+            println!(
+                "instruction {} ({}):",
+                index + 1,
+                transaction.instructions[index].program_id
+            );
+            for log in &result.logs {
+                println!("{}", log);
+            }
+            match result.exit_code {
+                Some(code) => match describe(index, code) {
+                    Some(error) => println!("exit code: {} ({})", code, error),
+                    None => println!("exit code: {}", code),
+                },
+                None => println!("exit code: none"),
+            }
+            println!("compute units: {}", result.compute_units_consumed);
+        }
+        if !result.is_success() {
+            println!(
+                "❌ Instruction {} failed, so the transaction's account changes were rolled back",
+                result.results.len()
+            );
+        } else if flagged.is_empty() {
+            println!(
+                "✅ Ran {} instructions in {} compute units",
+                result.results.len(),
+                result.compute_units_consumed()
+            );
+        } else {
+            println!("⚠️  Accounts left below rent exemption:");
+            for violation in &flagged {
+                println!(
+                    "  {}: {} lamports, {} needed",
+                    violation.address, violation.lamports, violation.minimum_balance
+                );
+            }
+        }
+    }
+    if !flagged.is_empty() {
+        anyhow::bail!(
+            "The transaction left {} account(s) below rent exemption",
+            flagged.len()
+        );
+    }
+    Ok(())
+}
+
+/// Index of the first instruction that calls neither a builtin nor one of
+/// the transaction's `programs`, which calls the program being run.
+fn program_index(transaction: &ParsedTransaction) -> Result<usize> {
+    transaction
+        .instructions
+        .iter()
+        .position(|instruction| {
+            !is_builtin(&instruction.program_id)
+                && !transaction
+                    .programs
+                    .iter()
+                    .any(|(program_id, _)| *program_id == instruction.program_id)
+        })
+        .ok_or_else(|| Error::msg("No instruction of the transaction calls the program"))
+}
+
+/// Run `transaction` in a runtime with `elf` loaded as the program its
+/// instructions call, along with the transaction's other `programs`.
+fn execute(elf: Vec<u8>, transaction: &ParsedTransaction) -> Result<(Runtime, TransactionResult)> {
+    let program_id = transaction.instructions[program_index(transaction)?].program_id;
+    let config = RuntimeConfig {
+        compute_budget: 1_400_000,
+        errors: embedded_error_registry(&elf),
+        ..RuntimeConfig::default()
+    };
+    let mut runtime = Runtime::new(program_id, elf, config)?;
+    for (program_id, elf) in &transaction.programs {
+        runtime.add_program(program_id, elf.clone());
+    }
+    runtime.sysvars_mut().apply_warp(&transaction.clock);
+    let result = runtime.run_transaction(&transaction.instructions, &transaction.accounts)?;
+    Ok((runtime, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sbpf_assembler::{Assembler, AssemblerOption},
+    };
+
+    #[test]
+    fn test_instructions_share_accounts() {
+        // Adds one to the u64 at the start of the first account's data.
+        let source = ".globl entrypoint\nentrypoint:\n  ldxdw r2, [r1+0x60]\n  add64 r2, 1\n  stxdw [r1+0x60], r2\n  mov64 r0, 0\n  exit\n";
+        let elf = Assembler::new(AssemblerOption::default())
+            .assemble(source)
+            .unwrap();
+        let (program, counter) = (
+            "78ycAjmvvq2Xjz6mBgGTsuHHNVADZ75NWgXKPY8wvF2s",
+            "3JF3sEqM796hk5WFqA6EtmEwJQ9quALszsfJyvXNQKy3",
+        );
+        let increment = format!(
+            r#"{{ "program_id": "{}", "accounts": [{{ "pubkey": "{}", "is_signer": false, "is_writable": true }}] }}"#,
+            program, counter
+        );
+        let transaction = parse_transaction(&format!(
+            r#"{{
+                "instructions": [{0}, {0}],
+                "accounts": [{{ "pubkey": "{1}", "owner": "{2}", "lamports": 1000000000, "data": "11111111" }}]
+            }}"#,
+            increment, counter, program
+        ))
+        .unwrap();
+        assert_eq!(program_index(&transaction).unwrap(), 0);
+
+        let (runtime, result) = execute(elf.clone(), &transaction).unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.results.len(), 2);
+        let (_, account) = runtime
+            .get_accounts()
+            .iter()
+            .find(|(address, _)| address.to_string() == counter)
+            .unwrap();
+        assert_eq!(account.data, 2u64.to_le_bytes());
+        assert!(
+            runtime
+                .rent_violations(&transaction.instructions, &transaction.accounts)
+                .is_empty()
+        );
+
+        // Transferring 600 lamports to an empty account leaves it rent-paying.
+        let (payer, recipient) = (
+            "LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY",
+            "QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF",
+        );
+        let system = "11111111111111111111111111111111";
+        // A system transfer of 600 lamports, base58-encoded.
+        let transfer = "3Bxs4FaSqfLcHjgs";
+        let underfunded = parse_transaction(&format!(
+            r#"{{
+                "instructions": [
+                    {0},
+                    {{ "program_id": "{5}", "accounts": [
+                        {{ "pubkey": "{3}", "is_signer": true, "is_writable": true }},
+                        {{ "pubkey": "{4}", "is_signer": false, "is_writable": true }}
+                    ], "data": "{6}" }}
+                ],
+                "accounts": [
+                    {{ "pubkey": "{1}", "owner": "{2}", "lamports": 1000000000, "data": "11111111" }},
+                    {{ "pubkey": "{3}", "owner": "{5}", "lamports": 1000000000 }},
+                    {{ "pubkey": "{4}", "owner": "{5}", "lamports": 0 }}
+                ]
+            }}"#,
+            increment, counter, program, payer, recipient, system, transfer
+        ))
+        .unwrap();
+        let (runtime, result) = execute(elf, &underfunded).unwrap();
+        assert!(result.is_success());
+        let flagged = runtime.rent_violations(&underfunded.instructions, &underfunded.accounts);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].address.to_string(), recipient);
+        assert_eq!(flagged[0].lamports, 600);
+        assert_eq!(flagged[0].minimum_balance, runtime.minimum_balance(0));
+    }
+}
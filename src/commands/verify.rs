@@ -0,0 +1,227 @@
+use {
+    super::common::{OutputFormat, print_json},
+    anyhow::{Error, Result},
+    clap::Args,
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+    },
+};
+
+/// What `sbpf build --verifiable` records next to each program, enough to
+/// rebuild it and to check a deployment against it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiableManifest {
+    pub program: String,
+    pub sbpf_version: String,
+    pub arch: String,
+    pub opt_level: String,
+    pub defines: Vec<(String, i64)>,
+    /// Container image the build ran in, pinned by digest, or `None` for a
+    /// build with the pinned toolchain on the host.
+    pub image: Option<String>,
+    /// Every file read by the build with its sha256, sources first.
+    pub inputs: Vec<ManifestInput>,
+    /// Hash of the executable in the format `solana-verify` reports.
+    pub executable_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestInput {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// Path of the manifest for `program` in a deploy directory.
+pub fn manifest_path(deploy_dir: &Path, program: &str) -> PathBuf {
+    deploy_dir.join(format!("{}.verifiable.json", program))
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// sha256 of a program with trailing zero bytes trimmed. Program data
+/// accounts are padded past the end of the ELF, so this is what matches
+/// between a local build and a dump of the deployed program.
+pub fn executable_hash(elf: &[u8]) -> String {
+    let end = elf.iter().rposition(|&byte| byte != 0).map_or(0, |i| i + 1);
+    sha256_hex(&elf[..end])
+}
+
+#[derive(Args)]
+pub struct VerifyOnchainArgs {
+    #[arg(help = "Address of the deployed program")]
+    pub program_id: String,
+    #[arg(
+        short,
+        long,
+        help = "Manifest written by `sbpf build --verifiable` [default: the only one in deploy/]"
+    )]
+    pub manifest: Option<String>,
+    #[arg(
+        short,
+        long,
+        default_value = "localhost",
+        help = "Cluster to read from"
+    )]
+    pub url: String,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Output format: text or json"
+    )]
+    pub output: OutputFormat,
+}
+
+pub fn verify_onchain(args: VerifyOnchainArgs) -> Result<(), Error> {
+    let manifest_file = match args.manifest {
+        Some(path) => PathBuf::from(path),
+        None => find_manifest(Path::new("deploy"))?,
+    };
+    let contents = fs::read_to_string(&manifest_file).map_err(|e| {
+        Error::msg(format!(
+            "Failed to read '{}': {}",
+            manifest_file.display(),
+            e
+        ))
+    })?;
+    let manifest: VerifiableManifest = serde_json::from_str(&contents).map_err(|e| {
+        Error::msg(format!(
+            "Invalid manifest '{}': {}",
+            manifest_file.display(),
+            e
+        ))
+    })?;
+
+    let output = args.output;
+    output.status(format!(
+        "🔍 Verifying {} against \"{}\" (sbpf {}, {})",
+        args.program_id,
+        manifest.program,
+        manifest.sbpf_version,
+        manifest.image.as_deref().unwrap_or("pinned toolchain")
+    ));
+
+    let dump = std::env::temp_dir().join(format!("sbpf-verify-{}.so", args.program_id));
+    let status = Command::new("solana")
+        .arg("program")
+        .arg("dump")
+        .arg(&args.program_id)
+        .arg(&dump)
+        .arg("-u")
+        .arg(&args.url)
+        .stdout(match output {
+            OutputFormat::Text => Stdio::inherit(),
+            OutputFormat::Json => std::io::stderr().into(),
+        })
+        .status()
+        .map_err(|e| Error::msg(format!("Failed to run `solana program dump`: {}", e)))?;
+    if !status.success() {
+        return Err(Error::msg(format!(
+            "❌ Failed to dump program {}",
+            args.program_id
+        )));
+    }
+    let onchain = fs::read(&dump);
+    let _ = fs::remove_file(&dump);
+    let onchain_hash = executable_hash(&onchain?);
+
+    let matches = onchain_hash == manifest.executable_hash;
+    if output.is_json() {
+        print_json(&serde_json::json!({
+            "program_id": args.program_id,
+            "program": manifest.program,
+            "expected_hash": manifest.executable_hash,
+            "onchain_hash": onchain_hash,
+            "matches": matches,
+        }))?;
+    } else {
+        println!("  expected: {}", manifest.executable_hash);
+        println!("  on-chain: {}", onchain_hash);
+    }
+    if !matches {
+        return Err(Error::msg(
+            "❌ The deployed program does not match the verifiable build",
+        ));
+    }
+    output.status(format!(
+        "✅ {} matches \"{}\"",
+        args.program_id, manifest.program
+    ));
+    Ok(())
+}
+
+fn find_manifest(deploy_dir: &Path) -> Result<PathBuf> {
+    let mut manifests = Vec::new();
+    if let Ok(entries) = deploy_dir.read_dir() {
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(".verifiable.json"))
+            {
+                manifests.push(path);
+            }
+        }
+    }
+    match manifests.len() {
+        1 => Ok(manifests.remove(0)),
+        0 => Err(Error::msg(format!(
+            "No manifest in '{}'; run `sbpf build --verifiable` first",
+            deploy_dir.display()
+        ))),
+        _ => Err(Error::msg(format!(
+            "Several manifests in '{}'; choose one with --manifest",
+            deploy_dir.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_executable_hash_ignores_padding() {
+        let elf = b"\x7fELF\x02\x01\x00\x05";
+        let mut padded = elf.to_vec();
+        padded.extend([0; 64]);
+        assert_eq!(executable_hash(&padded), executable_hash(elf));
+        assert_eq!(executable_hash(elf), sha256_hex(b"\x7fELF\x02\x01\x00\x05"));
+        assert_eq!(
+            executable_hash(&[0; 16]),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = VerifiableManifest {
+            program: "counter".to_string(),
+            sbpf_version: "0.2.4".to_string(),
+            arch: "v3".to_string(),
+            opt_level: "1".to_string(),
+            defines: vec![("DEBUG".to_string(), 1)],
+            image: None,
+            inputs: vec![ManifestInput {
+                path: "src/counter/counter.s".to_string(),
+                sha256: sha256_hex(b"exit\n"),
+            }],
+            executable_hash: executable_hash(b"\x7fELF"),
+        };
+        let json = serde_json::to_string_pretty(&manifest).unwrap();
+        assert_eq!(
+            serde_json::from_str::<VerifiableManifest>(&json).unwrap(),
+            manifest
+        );
+    }
+}
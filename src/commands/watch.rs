@@ -0,0 +1,178 @@
+use {
+    crate::commands::{
+        build::{BuildArgs, build_programs_cached},
+        common::print_json,
+        test::{TestArgs, test},
+    },
+    anyhow::Result,
+    sbpf_assembler::ParseCache,
+    std::{
+        collections::BTreeMap,
+        fs, io,
+        path::{Path, PathBuf},
+        thread,
+        time::{Duration, SystemTime},
+    },
+};
+
+/// How often sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Build every program, then rebuild each time a source changes until
+/// interrupted. Failed builds are reported and the watch goes on; the cache
+/// is shared across rebuilds so only files that changed are parsed again.
+pub fn watch(args: &BuildArgs) -> Result<()> {
+    let status = |message: String| {
+        if args.output.is_json() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    };
+    let roots = watched_paths(args);
+    let cache = ParseCache::new();
+
+    let mut snapshot = Snapshot::take(&roots)?;
+    rebuild(args, &cache)?;
+    status("👀 Watching src for changes (Ctrl-C to stop)".to_string());
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut current = Snapshot::take(&roots)?;
+        if current == snapshot {
+            continue;
+        }
+        // Editors often save in more than one write; wait for the files to
+        // settle so a half-written source isn't built.
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let settled = Snapshot::take(&roots)?;
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        let changed = snapshot.changed(&current);
+        snapshot = current;
+        status(match changed.as_slice() {
+            [path] => format!("\n🔄 {} changed, rebuilding", path.display()),
+            _ => format!("\n🔄 {} files changed, rebuilding", changed.len()),
+        });
+        rebuild(args, &cache)?;
+    }
+}
+
+/// Build once, and run the tests if asked to and the build succeeded. Only
+/// errors that stop the watch itself are returned.
+fn rebuild(args: &BuildArgs, cache: &ParseCache) -> Result<()> {
+    match build_programs_cached(args, Some(cache)) {
+        Ok(programs) => {
+            if args.output.is_json() {
+                print_json(&serde_json::json!({ "programs": programs }))?;
+            }
+            if args.test
+                && let Err(e) = test(TestArgs {
+                    output: args.output,
+                    ..Default::default()
+                })
+            {
+                eprintln!("❌ {}", e);
+            }
+        }
+        Err(e) => eprintln!("❌ {}", e),
+    }
+    Ok(())
+}
+
+/// Everything a build reads: the project's sources, the include paths and
+/// any execution profiles.
+fn watched_paths(args: &BuildArgs) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("src")];
+    paths.extend(args.include_paths.iter().cloned());
+    paths.extend(args.profile.iter().map(PathBuf::from));
+    paths
+}
+
+/// Size and modification time of every file under a set of paths. Paths
+/// that don't exist are skipped, so one appearing counts as a change.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Snapshot {
+    files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+}
+
+impl Snapshot {
+    fn take(roots: &[PathBuf]) -> io::Result<Self> {
+        let mut snapshot = Self::default();
+        for root in roots {
+            snapshot.add(root)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn add(&mut self, path: &Path) -> io::Result<()> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.add(&entry?.path())?;
+            }
+        } else {
+            self.files.insert(
+                path.to_path_buf(),
+                (metadata.len(), metadata.modified().ok()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Files added, removed or modified in `other`, sorted.
+    fn changed(&self, other: &Snapshot) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = other
+            .files
+            .iter()
+            .filter(|(path, stamp)| self.files.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            self.files
+                .keys()
+                .filter(|path| !other.files.contains_key(*path))
+                .cloned(),
+        );
+        changed.sort();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_reports_changed_files() {
+        let dir = std::env::temp_dir().join(format!("sbpf-watch-{}", std::process::id()));
+        let program = dir.join("prog");
+        fs::create_dir_all(&program).unwrap();
+        let main = program.join("prog.s");
+        let helper = program.join("helper.s");
+        fs::write(&main, "  exit\n").unwrap();
+        fs::write(&helper, "  exit\n").unwrap();
+        let roots = [dir.clone(), dir.join("missing")];
+
+        let before = Snapshot::take(&roots).unwrap();
+        assert_eq!(Snapshot::take(&roots).unwrap(), before);
+
+        // Sizes change too, so this holds on filesystems with coarse mtimes.
+        fs::write(&main, "  mov64 r0, 0\n  exit\n").unwrap();
+        fs::remove_file(&helper).unwrap();
+        let added = program.join("added.s");
+        fs::write(&added, "  exit\n").unwrap();
+        let after = Snapshot::take(&roots).unwrap();
+        assert_eq!(before.changed(&after), [added, helper, main]);
+        assert!(after.changed(&after).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -8,8 +8,24 @@ use {
         debug::{DebugArgs, debug},
         deploy::{DeployArgs, deploy},
         disassemble::{DisassembleArgs, disassemble},
+        e2e::{E2eArgs, e2e},
+        fmt::{FmtArgs, fmt},
+        grep::{GrepArgs, grep},
+        idlgen::{IdlgenArgs, idlgen},
         init::{InitArgs, init},
-        test::test,
+        interactive::{InteractiveArgs, interactive},
+        keys::{KeysArgs, keys},
+        layout::{LayoutArgs, layout},
+        learn::{LearnArgs, learn},
+        lint::{LintArgs, lint},
+        localnet::{LocalnetArgs, localnet},
+        metadata::{MetadataArgs, metadata},
+        patch::{PatchArgs, patch},
+        relocs::{RelocsArgs, relocs},
+        replay::{ReplayArgs, replay},
+        run::{RunArgs, run},
+        test::{TestArgs, test},
+        verify::{VerifyOnchainArgs, verify_onchain},
     },
 };
 
@@ -29,16 +45,46 @@ enum Commands {
     Build(BuildArgs),
     #[command(about = "Build and deploy the program")]
     Deploy(DeployArgs),
+    #[command(about = "Check a deployed program against a verifiable build")]
+    VerifyOnchain(VerifyOnchainArgs),
+    #[command(about = "Create, import and show program keypairs")]
+    Keys(KeysArgs),
     #[command(about = "Test deployed program")]
-    Test,
+    Test(TestArgs),
     #[command(about = "Build, deploy and test a program")]
-    E2E(DeployArgs),
+    E2E(E2eArgs),
+    #[command(about = "Run a local solana-test-validator")]
+    Localnet(LocalnetArgs),
     #[command(about = "Clean up build and deploy artifacts")]
     Clean,
     #[command(about = "Disassemble a Solana program executable")]
     Disassemble(DisassembleArgs),
     #[command(about = "Debug a program")]
     Debug(DebugArgs),
+    #[command(about = "Run a built program in the local VM")]
+    Run(RunArgs),
+    #[command(about = "Re-execute an instruction of a confirmed transaction in the local VM")]
+    Replay(ReplayArgs),
+    #[command(about = "List the dynamic relocations of an assembly program")]
+    Relocs(RelocsArgs),
+    #[command(about = "Rewrite the bytes of a data symbol in a built program")]
+    Patch(PatchArgs),
+    #[command(about = "Show build metadata embedded in a program")]
+    Metadata(MetadataArgs),
+    #[command(about = "Search programs for instructions matching a pattern")]
+    Grep(GrepArgs),
+    #[command(about = "Check programs for common security mistakes")]
+    Lint(LintArgs),
+    #[command(about = "Format assembly sources")]
+    Fmt(FmtArgs),
+    #[command(about = "Generate Rust or TypeScript structs from .layout blocks")]
+    Layout(LayoutArgs),
+    #[command(about = "Generate a JSON IDL and TypeScript client from .instruction blocks")]
+    Idlgen(IdlgenArgs),
+    #[command(about = "Assemble and run instructions interactively")]
+    Interactive(InteractiveArgs),
+    #[command(about = "Work through sBPF exercises checked in the local VM")]
+    Learn(LearnArgs),
 }
 
 fn main() -> Result<(), Error> {
@@ -48,14 +94,25 @@ fn main() -> Result<(), Error> {
         Commands::Init(args) => init(args),
         Commands::Build(args) => build(args),
         Commands::Deploy(args) => deploy(args),
-        Commands::Test => test(),
-        Commands::E2E(args) => {
-            build(BuildArgs::default())?;
-            deploy(args)?;
-            test()
-        }
+        Commands::VerifyOnchain(args) => verify_onchain(args),
+        Commands::Keys(args) => keys(args),
+        Commands::Test(args) => test(args),
+        Commands::E2E(args) => e2e(args),
+        Commands::Localnet(args) => localnet(args),
         Commands::Clean => clean(),
         Commands::Debug(args) => debug(args),
+        Commands::Run(args) => run(args),
+        Commands::Replay(args) => replay(args),
         Commands::Disassemble(args) => disassemble(args),
+        Commands::Relocs(args) => relocs(args),
+        Commands::Patch(args) => patch(args),
+        Commands::Metadata(args) => metadata(args),
+        Commands::Grep(args) => grep(args),
+        Commands::Lint(args) => lint(args),
+        Commands::Fmt(args) => fmt(args),
+        Commands::Layout(args) => layout(args),
+        Commands::Idlgen(args) => idlgen(args),
+        Commands::Interactive(args) => interactive(args),
+        Commands::Learn(args) => learn(args),
     }
 }
@@ -0,0 +1,89 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+const PROGRAM: &str = r#".equ OFFSET, 8
+
+.globl entrypoint
+entrypoint:
+  ldxdw r2, [r1+OFFSET]
+  jeq r2, 0, done
+  lddw r1, message
+  mov64 r2, 12
+  call sol_log_
+  mov64 r3, 0x1000
+  mov64 r4, 0x1000
+  jgt r4, 0x1000, done
+  lddw r5, helper
+  callx r5
+  lddw r1, table
+  ldxdw r3, [r1+8]
+done:
+  exit
+helper:
+  mov64 r0, 42
+  exit
+.rodata
+message: .ascii "Hello world!"
+table: .quad 1, 2, 0
+"#;
+
+fn run(args: &[&str], stdin: Option<&[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sbpf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run sbpf");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.unwrap_or_default())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "sbpf {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn build(source: &str, arch: &str) -> Vec<u8> {
+    run(
+        &["build", "--stdin", "--stdout", "--arch", arch],
+        Some(source.as_bytes()),
+    )
+    .stdout
+}
+
+fn disassemble(elf: &[u8], path: &Path) -> String {
+    fs::write(path, elf).unwrap();
+    let output = run(&["disassemble", "--asm", path.to_str().unwrap()], None);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_disassemble_round_trip() {
+    let dir = std::env::temp_dir().join("sbpf_test_round_trip");
+    fs::create_dir_all(&dir).unwrap();
+
+    for arch in ["v0", "v2", "v3"] {
+        let elf = build(PROGRAM, arch);
+        let source = disassemble(&elf, &dir.join(format!("{}.so", arch)));
+        assert!(source.contains(".equ IMM_1000, 0x1000"), "{}", source);
+        assert_eq!(build(&source, arch), elf, "{} round trip:\n{}", arch, source);
+
+        // The output is stable once it has been through the assembler.
+        let again = disassemble(&build(&source, arch), &dir.join("again.so"));
+        assert_eq!(again, source);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}